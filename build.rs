@@ -0,0 +1,56 @@
+//! Embeds the git commit and build time, reported by `GET /api/info` and on
+//! `{prefix}/bridge/info`
+
+use std::path::Path;
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+    println!("cargo:rerun-if-env-changed=RUSTBRIDGE_GIT_HASH");
+
+    // Rebuild on a new commit; outside a checkout (e.g. a crates.io package)
+    // there is nothing to watch and no hash to report
+    let head = Path::new(".git/HEAD");
+    if head.exists() {
+        println!("cargo:rerun-if-changed=.git/HEAD");
+        if let Ok(contents) = std::fs::read_to_string(head) {
+            if let Some(reference) = contents.trim().strip_prefix("ref: ") {
+                let path = Path::new(".git").join(reference);
+                if path.exists() {
+                    println!("cargo:rerun-if-changed={}", path.display());
+                }
+            }
+        }
+        if Path::new(".git/packed-refs").exists() {
+            println!("cargo:rerun-if-changed=.git/packed-refs");
+        }
+    }
+    // Given by builds without a checkout, e.g. the Docker image
+    let given = std::env::var("RUSTBRIDGE_GIT_HASH")
+        .ok()
+        .filter(|hash| !hash.is_empty());
+    let git_hash = given.or_else(|| {
+        Command::new("git")
+            .args(["rev-parse", "--short=12", "HEAD"])
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .and_then(|output| String::from_utf8(output.stdout).ok())
+            .map(|hash| hash.trim().to_string())
+    });
+    let git_hash = git_hash.unwrap_or_default();
+    println!("cargo:rustc-env=RUSTBRIDGE_GIT_HASH={}", git_hash);
+
+    // Reproducible builds pin the timestamp
+    let build_time = std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|epoch| epoch.parse::<u64>().ok())
+        .unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_secs())
+        });
+    println!("cargo:rustc-env=RUSTBRIDGE_BUILD_TIME={}", build_time);
+}
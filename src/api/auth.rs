@@ -2,6 +2,9 @@
 //!
 //! Provides tower-compatible middleware for API key validation.
 //! Keys are passed via the `X-API-Key` header.
+//!
+//! Every request gets a [`Scope`] extension: keys from `auth.tenant_keys`
+//! only reach the devices of their tenant, everything else reaches all.
 
 use axum::{
     body::Body,
@@ -16,6 +19,33 @@ use std::sync::Arc;
 
 use crate::config::AuthConfig;
 
+/// Devices a request may access
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Scope {
+    /// Every device (global API key, or authentication disabled)
+    All,
+    /// Only the devices of one tenant
+    Tenant(String),
+}
+
+impl Scope {
+    /// Tenant the request is limited to, if any
+    pub fn tenant(&self) -> Option<&str> {
+        match self {
+            Scope::All => None,
+            Scope::Tenant(tenant) => Some(tenant),
+        }
+    }
+
+    /// Whether a device of `tenant` (`None` for none) is in scope
+    pub fn allows(&self, tenant: Option<&str>) -> bool {
+        match self {
+            Scope::All => true,
+            Scope::Tenant(scope) => tenant == Some(scope.as_str()),
+        }
+    }
+}
+
 /// Authentication state shared across requests
 #[derive(Clone)]
 pub struct AuthState {
@@ -29,7 +59,19 @@ impl AuthState {
 
     /// Check if the given API key is valid
     pub fn is_valid_key(&self, key: &str) -> bool {
-        self.config.api_keys.iter().any(|k| k == key)
+        self.scope_of(key).is_some()
+    }
+
+    /// Scope of the given API key, `None` if it is not valid
+    pub fn scope_of(&self, key: &str) -> Option<Scope> {
+        if self.config.api_keys.iter().any(|k| k == key) {
+            return Some(Scope::All);
+        }
+        self.config
+            .tenant_keys
+            .iter()
+            .find(|(_, keys)| keys.iter().any(|k| k == key))
+            .map(|(tenant, _)| Scope::Tenant(tenant.clone()))
     }
 
     /// Check if the path is excluded from authentication
@@ -56,21 +98,16 @@ struct AuthError {
 /// API Key authentication middleware
 ///
 /// Validates the `X-API-Key` header against configured API keys.
-/// Paths in `exclude_paths` are allowed without authentication.
+/// Paths in `exclude_paths` are allowed without authentication, but a
+/// tenant key still limits them to its tenant.
 pub async fn api_key_auth(
     State(auth_state): State<Arc<AuthState>>,
-    request: Request<Body>,
+    mut request: Request<Body>,
     next: Next,
 ) -> Response {
     // Skip auth if disabled
     if !auth_state.config.enabled {
-        return next.run(request).await;
-    }
-
-    let path = request.uri().path();
-
-    // Skip auth for excluded paths
-    if auth_state.is_excluded_path(path) {
+        request.extensions_mut().insert(Scope::All);
         return next.run(request).await;
     }
 
@@ -79,13 +116,21 @@ pub async fn api_key_auth(
         .headers()
         .get("X-API-Key")
         .and_then(|v| v.to_str().ok());
+    let scope = api_key.and_then(|key| auth_state.scope_of(key));
+
+    // Skip auth for excluded paths
+    if auth_state.is_excluded_path(request.uri().path()) {
+        request.extensions_mut().insert(scope.unwrap_or(Scope::All));
+        return next.run(request).await;
+    }
 
-    match api_key {
-        Some(key) if auth_state.is_valid_key(key) => {
+    match (api_key, scope) {
+        (_, Some(scope)) => {
             // Valid key, proceed
+            request.extensions_mut().insert(scope);
             next.run(request).await
         }
-        Some(_) => {
+        (Some(_), None) => {
             // Invalid key
             (
                 StatusCode::UNAUTHORIZED,
@@ -96,7 +141,7 @@ pub async fn api_key_auth(
             )
                 .into_response()
         }
-        None => {
+        (None, None) => {
             // Missing key
             (
                 StatusCode::UNAUTHORIZED,
@@ -119,6 +164,7 @@ mod tests {
         let config = AuthConfig {
             enabled: true,
             api_keys: vec!["secret-key-123".to_string(), "another-key".to_string()],
+            tenant_keys: Default::default(),
             exclude_paths: vec!["/health".to_string()],
         };
         let state = AuthState::new(config);
@@ -134,6 +180,7 @@ mod tests {
         let config = AuthConfig {
             enabled: true,
             api_keys: vec![],
+            tenant_keys: Default::default(),
             exclude_paths: vec!["/health".to_string(), "/metrics".to_string()],
         };
         let state = AuthState::new(config);
@@ -149,6 +196,7 @@ mod tests {
         let config = AuthConfig {
             enabled: true,
             api_keys: vec![],
+            tenant_keys: Default::default(),
             exclude_paths: vec!["/public/*".to_string(), "/docs/*".to_string()],
         };
         let state = AuthState::new(config);
@@ -159,11 +207,32 @@ mod tests {
         assert!(!state.is_excluded_path("/api/devices"));
     }
 
+    #[test]
+    fn test_tenant_key_scope() {
+        let config = AuthConfig {
+            enabled: true,
+            api_keys: vec!["admin".to_string()],
+            tenant_keys: [("acme".to_string(), vec!["acme-key".to_string()])].into(),
+            exclude_paths: vec![],
+        };
+        let state = AuthState::new(config);
+
+        assert_eq!(state.scope_of("admin"), Some(Scope::All));
+        let scope = state.scope_of("acme-key").unwrap();
+        assert_eq!(scope.tenant(), Some("acme"));
+        assert!(scope.allows(Some("acme")));
+        assert!(!scope.allows(Some("globex")));
+        assert!(!scope.allows(None));
+        assert!(Scope::All.allows(None));
+        assert_eq!(state.scope_of("unknown"), None);
+    }
+
     #[test]
     fn test_empty_keys() {
         let config = AuthConfig {
             enabled: true,
             api_keys: vec![],
+            tenant_keys: Default::default(),
             exclude_paths: vec![],
         };
         let state = AuthState::new(config);
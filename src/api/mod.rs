@@ -1,34 +1,37 @@
 //! REST API module with WebSocket support
 //!
 //! Provides REST endpoints for reading/writing Modbus registers
-//! and WebSocket for real-time register updates.
+//! and WebSocket for real-time register updates. The HTTP server itself is
+//! behind the `http-api` feature; the shared state types are always built.
 
+#[cfg(feature = "http-api")]
 pub mod auth;
+#[cfg(feature = "http-api")]
+mod routes;
+
+#[cfg(feature = "http-api")]
+pub use routes::create_router;
 
-use axum::{
-    extract::{
-        ws::{Message, WebSocket, WebSocketUpgrade},
-        Path, State,
-    },
-    http::StatusCode,
-    middleware,
-    response::{IntoResponse, Json, Response},
-    routing::{get, post},
-    Router,
-};
-use futures_util::{SinkExt, StreamExt};
 use metrics_exporter_prometheus::PrometheusHandle;
 use serde::{Deserialize, Serialize};
+use std::sync::atomic::AtomicBool;
 use std::sync::Arc;
-use tokio::sync::broadcast;
-use tracing::{debug, error, info, warn};
-
-use crate::config::AuthConfig;
+use tokio::sync::{broadcast, mpsc};
+
+use crate::audit::{AuditLog, WriteSource};
+use crate::config::Config;
+use crate::control::DeviceControls;
+use crate::events::{self, ConnectionEvent, EventSender};
+use crate::metrics::diagnostics::DiagnosticsStore;
+use crate::metrics::error_log::ErrorLog;
+use crate::modbus::codec::Quality;
 use crate::modbus::reader::RegisterStore;
-
-use self::auth::{api_key_auth, AuthState};
+use crate::reload::ReloadRequest;
+use crate::remote_config::PullRequest;
+use crate::update::UpdateStatus;
 
 /// Broadcast channel capacity for WebSocket updates
+/// (the bridge uses `server.update_channel_capacity`)
 const BROADCAST_CAPACITY: usize = 1024;
 
 /// API state shared across handlers
@@ -36,8 +39,26 @@ const BROADCAST_CAPACITY: usize = 1024;
 pub struct ApiState {
     pub register_store: RegisterStore,
     pub update_tx: broadcast::Sender<RegisterUpdate>,
+    pub event_tx: EventSender,
     pub write_tx: tokio::sync::mpsc::Sender<WriteRequest>,
     pub metrics_handle: Option<PrometheusHandle>,
+    pub diagnostics: DiagnosticsStore,
+    pub error_log: ErrorLog,
+    pub audit_log: AuditLog,
+    /// MQTT broker connection flag, `None` when MQTT is disabled
+    pub mqtt_connected: Option<Arc<AtomicBool>>,
+    /// Pause/resume/poll-now controls of the polling tasks
+    pub controls: DeviceControls,
+    /// Configuration reload requests, `None` when reloading is not available
+    pub reload_tx: Option<tokio::sync::mpsc::Sender<ReloadRequest>>,
+    /// Running configuration, `None` when the bridge does not expose it
+    pub config_rx: Option<tokio::sync::watch::Receiver<Config>>,
+    /// Global write switch, checked before every write
+    pub writes_enabled: Arc<AtomicBool>,
+    /// Requests to check the fleet server now, `None` without `remote_config`
+    pub config_pull_tx: Option<mpsc::Sender<PullRequest>>,
+    /// Result of the latest release check, `None` without `update`
+    pub update_status: Option<tokio::sync::watch::Receiver<UpdateStatus>>,
 }
 
 impl ApiState {
@@ -50,8 +71,19 @@ impl ApiState {
         Self {
             register_store,
             update_tx,
+            event_tx: events::channel(),
             write_tx,
             metrics_handle: None,
+            diagnostics: DiagnosticsStore::default(),
+            error_log: ErrorLog::default(),
+            audit_log: AuditLog::default(),
+            mqtt_connected: None,
+            controls: DeviceControls::default(),
+            reload_tx: None,
+            config_rx: None,
+            writes_enabled: Arc::new(AtomicBool::new(true)),
+            config_pull_tx: None,
+            update_status: None,
         }
     }
 
@@ -65,8 +97,19 @@ impl ApiState {
         Self {
             register_store,
             update_tx,
+            event_tx: events::channel(),
             write_tx,
             metrics_handle: Some(metrics_handle),
+            diagnostics: DiagnosticsStore::default(),
+            error_log: ErrorLog::default(),
+            audit_log: AuditLog::default(),
+            mqtt_connected: None,
+            controls: DeviceControls::default(),
+            reload_tx: None,
+            config_rx: None,
+            writes_enabled: Arc::new(AtomicBool::new(true)),
+            config_pull_tx: None,
+            update_status: None,
         }
     }
 
@@ -74,581 +117,233 @@ impl ApiState {
     pub fn subscribe(&self) -> broadcast::Receiver<RegisterUpdate> {
         self.update_tx.subscribe()
     }
+
+    /// Get a receiver for connection lifecycle events
+    pub fn subscribe_events(&self) -> broadcast::Receiver<ConnectionEvent> {
+        self.event_tx.subscribe()
+    }
+
+    /// Report the memory used by the stores and buffers as metrics
+    pub async fn record_memory_usage(&self) {
+        let store = crate::modbus::reader::store_memory_bytes(&self.register_store).await;
+        crate::metrics::record_memory_usage("register_store", store);
+        crate::metrics::record_memory_usage("error_log", self.error_log.memory_bytes());
+        crate::metrics::record_memory_usage("audit_log", self.audit_log.memory_bytes());
+        crate::metrics::record_update_channel_len(self.update_tx.len());
+    }
 }
 
 /// Register update message for WebSocket broadcast
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct RegisterUpdate {
-    pub device_id: String,
-    pub register_name: String,
+    /// Shared with every other update of the device
+    pub device_id: Arc<str>,
+    /// Shared with every other update of the register
+    pub register_name: Arc<str>,
+    /// `null` in JSON when the reading was not a number
+    #[serde(with = "crate::modbus::codec::finite_or_null")]
     pub value: f64,
     pub raw: Vec<u16>,
     pub unit: Option<String>,
     pub timestamp: String,
+    /// Value from the previous successful read (`None` on the first read)
+    #[serde(default)]
+    pub previous_value: Option<f64>,
+    /// Whether `value` differs from `previous_value` (always true on the first read)
+    #[serde(default)]
+    pub changed: bool,
+    /// Device and register meta fields, merged into the top level of the JSON
+    #[serde(flatten)]
+    pub meta: crate::config::Meta,
+    /// Boolean coil; MQTT payloads carry `true`/`false` instead of 1.0/0.0
+    #[serde(skip)]
+    pub boolean: bool,
+    /// Bad or substituted readings, see `non_finite`; omitted when good
+    #[serde(default, skip_serializing_if = "Quality::is_good")]
+    pub quality: Quality,
 }
 
-/// Write request sent to Modbus client
-#[derive(Debug)]
-pub struct WriteRequest {
-    pub device_id: String,
-    pub address: u16,
-    pub value: u16,
-    pub response_tx: tokio::sync::oneshot::Sender<Result<(), String>>,
+/// Register updates as received by one sink
+///
+/// A `Lossy` receiver skips updates once it falls behind the pollers; a
+/// `Blocking` one is fed through a [`BlockingSink`] that makes the pollers
+/// wait instead, so nothing is lost.
+pub enum UpdateReceiver {
+    Lossy(broadcast::Receiver<RegisterUpdate>),
+    Blocking(mpsc::Receiver<RegisterUpdate>),
 }
 
-/// Create the API router
-pub fn create_router(state: ApiState, auth_config: AuthConfig) -> Router {
-    let auth_state = Arc::new(AuthState::new(auth_config));
-
-    Router::new()
-        // Health & Info
-        .route("/health", get(health))
-        .route("/api/info", get(api_info))
-        // Metrics (Prometheus)
-        .route("/metrics", get(metrics_handler))
-        // Devices
-        .route("/api/devices", get(list_devices))
-        .route("/api/devices/:device_id", get(get_device))
-        // Registers (read)
-        .route("/api/devices/:device_id/registers", get(get_registers))
-        .route(
-            "/api/devices/:device_id/registers/:register_name",
-            get(get_register),
-        )
-        // Registers (write)
-        .route(
-            "/api/devices/:device_id/registers/:register_name",
-            post(write_register),
-        )
-        // WebSocket
-        .route("/ws", get(ws_handler))
-        // Apply API key authentication middleware
-        .layer(middleware::from_fn_with_state(auth_state, api_key_auth))
-        .with_state(Arc::new(state))
-}
-
-// ============================================================================
-// Error Handling
-// ============================================================================
-
-/// API error response
-#[derive(Serialize)]
-struct ApiError {
-    error: String,
-    code: u16,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    details: Option<String>,
-}
-
-impl ApiError {
-    fn new(code: StatusCode, error: impl Into<String>) -> (StatusCode, Json<Self>) {
-        (
-            code,
-            Json(Self {
-                error: error.into(),
-                code: code.as_u16(),
-                details: None,
-            }),
-        )
-    }
-
-    fn with_details(
-        code: StatusCode,
-        error: impl Into<String>,
-        details: impl Into<String>,
-    ) -> (StatusCode, Json<Self>) {
-        (
-            code,
-            Json(Self {
-                error: error.into(),
-                code: code.as_u16(),
-                details: Some(details.into()),
-            }),
-        )
+impl UpdateReceiver {
+    /// Next update, or `None` once the bridge stops
+    ///
+    /// Skipped updates are logged and counted under `sink` in
+    /// `rustbridge_updates_dropped_total`.
+    pub async fn recv(&mut self, sink: &str) -> Option<RegisterUpdate> {
+        match self {
+            UpdateReceiver::Lossy(rx) => loop {
+                match rx.recv().await {
+                    Ok(update) => return Some(update),
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        record_lag(sink, skipped);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                }
+            },
+            UpdateReceiver::Blocking(rx) => rx.recv().await,
+        }
     }
 }
 
-// ============================================================================
-// Health & Info Endpoints
-// ============================================================================
-
-/// Health check response
-#[derive(Serialize)]
-struct HealthResponse {
-    status: &'static str,
-    version: &'static str,
-}
-
-async fn health() -> Json<HealthResponse> {
-    Json(HealthResponse {
-        status: "ok",
-        version: env!("CARGO_PKG_VERSION"),
-    })
+/// Log and count updates a lagging subscriber skipped
+pub(crate) fn record_lag(sink: &str, skipped: u64) {
+    crate::metrics::record_dropped_updates(sink, skipped);
+    tracing::warn!("{} subscriber lagged, dropped {} updates", sink, skipped);
 }
 
-/// API info response
-#[derive(Serialize)]
-struct ApiInfoResponse {
+/// Sending side of a sink that must not lose updates
+#[derive(Clone)]
+pub struct BlockingSink {
     name: &'static str,
-    version: &'static str,
-    description: &'static str,
-    endpoints: Vec<EndpointInfo>,
-}
-
-#[derive(Serialize)]
-struct EndpointInfo {
-    method: &'static str,
-    path: &'static str,
-    description: &'static str,
+    tx: mpsc::Sender<RegisterUpdate>,
 }
 
-async fn api_info() -> Json<ApiInfoResponse> {
-    Json(ApiInfoResponse {
-        name: "RustBridge API",
-        version: env!("CARGO_PKG_VERSION"),
-        description: "Industrial Protocol Bridge - Modbus TCP/RTU to JSON/MQTT Gateway",
-        endpoints: vec![
-            EndpointInfo {
-                method: "GET",
-                path: "/health",
-                description: "Health check",
-            },
-            EndpointInfo {
-                method: "GET",
-                path: "/api/info",
-                description: "API information",
-            },
-            EndpointInfo {
-                method: "GET",
-                path: "/api/devices",
-                description: "List all devices",
-            },
-            EndpointInfo {
-                method: "GET",
-                path: "/api/devices/:device_id",
-                description: "Get device details",
-            },
-            EndpointInfo {
-                method: "GET",
-                path: "/api/devices/:device_id/registers",
-                description: "List device registers",
-            },
-            EndpointInfo {
-                method: "GET",
-                path: "/api/devices/:device_id/registers/:name",
-                description: "Get register value",
-            },
-            EndpointInfo {
-                method: "POST",
-                path: "/api/devices/:device_id/registers/:name",
-                description: "Write register value",
-            },
-            EndpointInfo {
-                method: "GET",
-                path: "/ws",
-                description: "WebSocket for real-time updates",
-            },
-            EndpointInfo {
-                method: "GET",
-                path: "/metrics",
-                description: "Prometheus metrics endpoint",
-            },
-        ],
-    })
-}
+impl BlockingSink {
+    /// Sink buffering up to `capacity` updates, with the receiver feeding it
+    pub fn channel(name: &'static str, capacity: usize) -> (Self, UpdateReceiver) {
+        let (tx, rx) = mpsc::channel(capacity);
+        (Self { name, tx }, UpdateReceiver::Blocking(rx))
+    }
 
-/// Prometheus metrics endpoint
-async fn metrics_handler(State(state): State<Arc<ApiState>>) -> impl IntoResponse {
-    match &state.metrics_handle {
-        Some(handle) => {
-            let metrics = handle.render();
-            (
-                StatusCode::OK,
-                [("content-type", "text/plain; version=0.0.4; charset=utf-8")],
-                metrics,
-            )
+    /// Queue an update, waiting while the buffer is full
+    pub async fn send(&self, update: RegisterUpdate) {
+        match self.tx.try_send(update) {
+            Ok(()) => {}
+            Err(mpsc::error::TrySendError::Full(update)) => {
+                crate::metrics::record_backpressure_wait(self.name);
+                tracing::debug!("{} sink is full, holding the poller", self.name);
+                let _ = self.tx.send(update).await;
+            }
+            // The sink has stopped; nobody is waiting for the update
+            Err(mpsc::error::TrySendError::Closed(_)) => {}
         }
-        None => (
-            StatusCode::SERVICE_UNAVAILABLE,
-            [("content-type", "text/plain; charset=utf-8")],
-            "Metrics not enabled".to_string(),
-        ),
     }
 }
 
-// ============================================================================
-// Device Endpoints
-// ============================================================================
-
-/// Device list response
-#[derive(Serialize)]
-struct DeviceListResponse {
-    devices: Vec<DeviceSummary>,
-    count: usize,
-}
-
-#[derive(Serialize)]
-struct DeviceSummary {
-    id: String,
-    register_count: usize,
-    last_update: Option<String>,
+/// Value of a write request: a number, a boolean, or one of the strings
+/// `true`/`false`/`on`/`off`/`1`/`0` (numeric strings are accepted too)
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum WriteValue {
+    Number(f64),
+    Bool(bool),
+    Text(String),
 }
 
-async fn list_devices(State(state): State<Arc<ApiState>>) -> Json<DeviceListResponse> {
-    let store = state.register_store.read().await;
-
-    let devices: Vec<DeviceSummary> = store
-        .iter()
-        .map(|(id, registers)| {
-            let last_update = registers
-                .values()
-                .map(|r| r.timestamp)
-                .max()
-                .map(|t| t.to_rfc3339());
-
-            DeviceSummary {
-                id: id.clone(),
-                register_count: registers.len(),
-                last_update,
+impl WriteValue {
+    /// Numeric value to encode, with booleans as 1.0/0.0
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            WriteValue::Number(v) => Some(*v),
+            WriteValue::Bool(v) => Some(if *v { 1.0 } else { 0.0 }),
+            WriteValue::Text(text) => {
+                let text = text.trim();
+                match text.to_ascii_lowercase().as_str() {
+                    "true" | "on" => Some(1.0),
+                    "false" | "off" => Some(0.0),
+                    _ => text.parse().ok(),
+                }
             }
-        })
-        .collect();
-
-    let count = devices.len();
-    Json(DeviceListResponse { devices, count })
-}
-
-/// Device detail response
-#[derive(Serialize)]
-struct DeviceResponse {
-    id: String,
-    registers: Vec<RegisterResponse>,
-    register_count: usize,
-}
-
-#[derive(Serialize, Clone)]
-struct RegisterResponse {
-    name: String,
-    value: f64,
-    raw: Vec<u16>,
-    unit: Option<String>,
-    timestamp: String,
-}
-
-async fn get_device(
-    State(state): State<Arc<ApiState>>,
-    Path(device_id): Path<String>,
-) -> Result<Json<DeviceResponse>, (StatusCode, Json<ApiError>)> {
-    let store = state.register_store.read().await;
-
-    let registers = store
-        .get(&device_id)
-        .ok_or_else(|| ApiError::new(StatusCode::NOT_FOUND, "Device not found"))?;
-
-    let registers: Vec<RegisterResponse> = registers
-        .values()
-        .map(|r| RegisterResponse {
-            name: r.name.clone(),
-            value: r.value,
-            raw: r.raw.clone(),
-            unit: r.unit.clone(),
-            timestamp: r.timestamp.to_rfc3339(),
-        })
-        .collect();
-
-    let register_count = registers.len();
-    Ok(Json(DeviceResponse {
-        id: device_id,
-        registers,
-        register_count,
-    }))
+        }
+    }
 }
 
-// ============================================================================
-// Register Endpoints
-// ============================================================================
-
-async fn get_registers(
-    State(state): State<Arc<ApiState>>,
-    Path(device_id): Path<String>,
-) -> Result<Json<Vec<RegisterResponse>>, (StatusCode, Json<ApiError>)> {
-    let store = state.register_store.read().await;
-
-    let registers = store
-        .get(&device_id)
-        .ok_or_else(|| ApiError::new(StatusCode::NOT_FOUND, "Device not found"))?;
-
-    let registers: Vec<RegisterResponse> = registers
-        .values()
-        .map(|r| RegisterResponse {
-            name: r.name.clone(),
-            value: r.value,
-            raw: r.raw.clone(),
-            unit: r.unit.clone(),
-            timestamp: r.timestamp.to_rfc3339(),
-        })
-        .collect();
-
-    Ok(Json(registers))
+/// Write request sent to Modbus client
+///
+/// `value` is in engineering units; the bridge looks up the register and
+/// encodes it with the inverse of its scale and offset.
+#[derive(Debug)]
+pub struct WriteRequest {
+    pub device_id: String,
+    pub register_name: String,
+    pub source: WriteSource,
+    pub value: f64,
+    pub response_tx: tokio::sync::oneshot::Sender<Result<WriteOutcome, WriteError>>,
 }
 
-async fn get_register(
-    State(state): State<Arc<ApiState>>,
-    Path((device_id, register_name)): Path<(String, String)>,
-) -> Result<Json<RegisterResponse>, (StatusCode, Json<ApiError>)> {
-    let store = state.register_store.read().await;
-
-    let registers = store
-        .get(&device_id)
-        .ok_or_else(|| ApiError::new(StatusCode::NOT_FOUND, "Device not found"))?;
-
-    let register = registers
-        .get(&register_name)
-        .ok_or_else(|| ApiError::new(StatusCode::NOT_FOUND, "Register not found"))?;
-
-    Ok(Json(RegisterResponse {
-        name: register.name.clone(),
-        value: register.value,
-        raw: register.raw.clone(),
-        unit: register.unit.clone(),
-        timestamp: register.timestamp.to_rfc3339(),
-    }))
+/// Raw registers a write was encoded to
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct WriteOutcome {
+    pub address: u16,
+    pub raw: Vec<u16>,
+    /// Value the device reported when read back after the write
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub read_back: Option<f64>,
 }
 
-/// Write register request body
-#[derive(Deserialize)]
-struct WriteRegisterRequest {
-    /// Raw u16 value to write
-    value: u16,
+/// Why a write request failed
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum WriteError {
+    /// Unknown or read-only register, or a value it cannot hold or that is
+    /// outside its `write_limits`
+    #[error("{0}")]
+    Rejected(String),
+    /// Writes are switched off (`writes.enabled` or `POST /api/writes`)
+    #[error("{0}")]
+    Disabled(String),
+    /// Too soon after the previous write from the same source
+    #[error("{0}")]
+    RateLimited(String),
+    /// The device did not accept the write
+    #[error("{0}")]
+    Failed(String),
+    /// The device answered the write with a Modbus exception
+    #[error("{0}")]
+    Exception(ModbusException),
 }
 
-/// Write register response
-#[derive(Serialize)]
-struct WriteRegisterResponse {
-    success: bool,
-    device_id: String,
-    register_name: String,
-    value_written: u16,
-    message: String,
+/// Modbus exception a device answered a request with
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ModbusException {
+    /// Exception code from the response, e.g. 2
+    pub code: u8,
+    /// Name of the exception, e.g. `illegal_data_address`
+    pub name: &'static str,
+    pub device_id: String,
+    /// Address of the register the request was for
+    pub address: u16,
 }
 
-async fn write_register(
-    State(state): State<Arc<ApiState>>,
-    Path((device_id, register_name)): Path<(String, String)>,
-    Json(payload): Json<WriteRegisterRequest>,
-) -> Result<Json<WriteRegisterResponse>, (StatusCode, Json<ApiError>)> {
-    // Validate device and register exist
-    let address = {
-        let store = state.register_store.read().await;
-        let registers = store
-            .get(&device_id)
-            .ok_or_else(|| ApiError::new(StatusCode::NOT_FOUND, "Device not found"))?;
-
-        let _register = registers
-            .get(&register_name)
-            .ok_or_else(|| ApiError::new(StatusCode::NOT_FOUND, "Register not found"))?;
-
-        // For now, we'll use a placeholder address
-        // In production, this would come from the config
-        0u16
-    };
-
-    // Create response channel
-    let (response_tx, response_rx) = tokio::sync::oneshot::channel();
-
-    // Send write request
-    let write_request = WriteRequest {
-        device_id: device_id.clone(),
-        address,
-        value: payload.value,
-        response_tx,
-    };
-
-    state.write_tx.send(write_request).await.map_err(|_| {
-        ApiError::with_details(
-            StatusCode::SERVICE_UNAVAILABLE,
-            "Write service unavailable",
-            "The Modbus write handler is not running",
-        )
-    })?;
-
-    // Wait for response with timeout
-    let result = tokio::time::timeout(std::time::Duration::from_secs(5), response_rx)
-        .await
-        .map_err(|_| {
-            ApiError::with_details(
-                StatusCode::GATEWAY_TIMEOUT,
-                "Write timeout",
-                "The Modbus device did not respond in time",
-            )
-        })?
-        .map_err(|_| {
-            ApiError::with_details(
-                StatusCode::INTERNAL_SERVER_ERROR,
-                "Write failed",
-                "Response channel closed unexpectedly",
-            )
-        })?;
-
-    match result {
-        Ok(()) => {
-            info!(
-                "Write successful: {}:{} = {}",
-                device_id, register_name, payload.value
-            );
-            Ok(Json(WriteRegisterResponse {
-                success: true,
-                device_id,
-                register_name,
-                value_written: payload.value,
-                message: "Register written successfully".to_string(),
-            }))
+impl ModbusException {
+    pub fn new(exception: tokio_modbus::Exception, device_id: &str, address: u16) -> Self {
+        use tokio_modbus::Exception;
+
+        let (code, name) = match exception {
+            Exception::IllegalFunction => (0x01, "illegal_function"),
+            Exception::IllegalDataAddress => (0x02, "illegal_data_address"),
+            Exception::IllegalDataValue => (0x03, "illegal_data_value"),
+            Exception::ServerDeviceFailure => (0x04, "server_device_failure"),
+            Exception::Acknowledge => (0x05, "acknowledge"),
+            Exception::ServerDeviceBusy => (0x06, "server_device_busy"),
+            Exception::MemoryParityError => (0x08, "memory_parity_error"),
+            Exception::GatewayPathUnavailable => (0x0A, "gateway_path_unavailable"),
+            Exception::GatewayTargetDevice => (0x0B, "gateway_target_device_failed_to_respond"),
+            Exception::Custom(code) => (code, "unknown"),
+        };
+        Self {
+            code,
+            name,
+            device_id: device_id.to_string(),
+            address,
         }
-        Err(e) => Err(ApiError::with_details(
-            StatusCode::BAD_GATEWAY,
-            "Modbus write failed",
-            e,
-        )),
     }
 }
 
-// ============================================================================
-// WebSocket Endpoint
-// ============================================================================
-
-/// WebSocket message types
-#[derive(Serialize, Deserialize)]
-#[serde(tag = "type")]
-enum WsMessage {
-    /// Subscribe to specific devices/registers
-    #[serde(rename = "subscribe")]
-    Subscribe { devices: Option<Vec<String>> },
-    /// Unsubscribe from updates
-    #[serde(rename = "unsubscribe")]
-    Unsubscribe,
-    /// Register update (server -> client)
-    #[serde(rename = "update")]
-    Update(RegisterUpdate),
-    /// Error message
-    #[serde(rename = "error")]
-    Error { message: String },
-    /// Connection confirmed
-    #[serde(rename = "connected")]
-    Connected { message: String },
-    /// Ping/Pong for keepalive
-    #[serde(rename = "ping")]
-    Ping,
-    #[serde(rename = "pong")]
-    Pong,
-}
-
-async fn ws_handler(ws: WebSocketUpgrade, State(state): State<Arc<ApiState>>) -> Response {
-    ws.on_upgrade(|socket| handle_socket(socket, state))
-}
-
-async fn handle_socket(socket: WebSocket, state: Arc<ApiState>) {
-    let (mut sender, mut receiver) = socket.split();
-
-    // Send connection confirmation
-    let connected_msg = WsMessage::Connected {
-        message: format!("RustBridge WebSocket v{}", env!("CARGO_PKG_VERSION")),
-    };
-    if let Ok(msg) = serde_json::to_string(&connected_msg) {
-        if sender.send(Message::Text(msg)).await.is_err() {
-            return;
-        }
-    }
-
-    info!("WebSocket client connected");
-
-    // Subscribe to register updates
-    let mut update_rx = state.subscribe();
-
-    // Track subscribed devices (None = all devices)
-    let mut subscribed_devices: Option<Vec<String>> = None;
-
-    loop {
-        tokio::select! {
-            // Handle incoming messages from client
-            msg = receiver.next() => {
-                match msg {
-                    Some(Ok(Message::Text(text))) => {
-                        match serde_json::from_str::<WsMessage>(&text) {
-                            Ok(WsMessage::Subscribe { devices }) => {
-                                subscribed_devices = devices.clone();
-                                debug!("Client subscribed to: {:?}", subscribed_devices);
-                            }
-                            Ok(WsMessage::Unsubscribe) => {
-                                subscribed_devices = Some(vec![]);
-                                debug!("Client unsubscribed from all updates");
-                            }
-                            Ok(WsMessage::Ping) => {
-                                let pong = serde_json::to_string(&WsMessage::Pong).unwrap();
-                                if sender.send(Message::Text(pong)).await.is_err() {
-                                    break;
-                                }
-                            }
-                            Ok(_) => {
-                                // Ignore other message types from client
-                            }
-                            Err(e) => {
-                                warn!("Invalid WebSocket message: {}", e);
-                                let error = WsMessage::Error {
-                                    message: format!("Invalid message format: {}", e),
-                                };
-                                if let Ok(msg) = serde_json::to_string(&error) {
-                                    let _ = sender.send(Message::Text(msg)).await;
-                                }
-                            }
-                        }
-                    }
-                    Some(Ok(Message::Ping(data))) => {
-                        if sender.send(Message::Pong(data)).await.is_err() {
-                            break;
-                        }
-                    }
-                    Some(Ok(Message::Close(_))) => {
-                        info!("WebSocket client disconnected");
-                        break;
-                    }
-                    Some(Err(e)) => {
-                        error!("WebSocket error: {}", e);
-                        break;
-                    }
-                    None => break,
-                    _ => {}
-                }
-            }
-            // Handle register updates from broadcast channel
-            update = update_rx.recv() => {
-                match update {
-                    Ok(register_update) => {
-                        // Check if client is subscribed to this device
-                        let should_send = match &subscribed_devices {
-                            None => true, // Subscribed to all
-                            Some(devices) if devices.is_empty() => false, // Unsubscribed
-                            Some(devices) => devices.contains(&register_update.device_id),
-                        };
-
-                        if should_send {
-                            let msg = WsMessage::Update(register_update);
-                            if let Ok(json) = serde_json::to_string(&msg) {
-                                if sender.send(Message::Text(json)).await.is_err() {
-                                    break;
-                                }
-                            }
-                        }
-                    }
-                    Err(broadcast::error::RecvError::Lagged(n)) => {
-                        warn!("WebSocket client lagged, missed {} updates", n);
-                    }
-                    Err(broadcast::error::RecvError::Closed) => {
-                        break;
-                    }
-                }
-            }
-        }
+impl std::fmt::Display for ModbusException {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Device '{}' answered with Modbus exception {} ({}) at address {}",
+            self.device_id, self.code, self.name, self.address
+        )
     }
-
-    info!("WebSocket connection closed");
 }
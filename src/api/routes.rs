@@ -0,0 +1,1472 @@
+//! HTTP routes and WebSocket handler
+
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Extension, Path, Query, State,
+    },
+    http::{HeaderMap, StatusCode},
+    middleware,
+    response::{IntoResponse, Json, Response},
+    routing::{get, post},
+    Router,
+};
+use futures_util::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use tokio::sync::broadcast;
+use tracing::{debug, error, info, warn};
+
+use super::auth::{api_key_auth, AuthState, Scope};
+use super::{ApiState, ModbusException, RegisterUpdate, WriteError, WriteRequest, WriteValue};
+use crate::audit::{AuditEntry, WriteSource};
+use crate::build_info::BuildInfo;
+use crate::config::{AuthConfig, Config, Meta, RecipeConfig};
+use crate::control::{Command, GroupState, Target};
+use crate::error::RustbridgeError;
+use crate::events::ConnectionEvent;
+use crate::metrics::diagnostics::{CycleTiming, DeviceDiagnostics};
+use crate::metrics::error_log::ErrorEvent;
+use crate::modbus::reader::{RegisterValue, Snapshot};
+use crate::recipes::{self, RecipeOutcome};
+use crate::reload::{ReloadAction, ReloadReport, ReloadRequest};
+use crate::remote_config::PullOutcome;
+use crate::update::UpdateStatus;
+
+/// Create the API router
+pub fn create_router(state: ApiState, auth_config: AuthConfig) -> Router {
+    let auth_state = Arc::new(AuthState::new(auth_config));
+
+    Router::new()
+        // Health & Info
+        .route("/health", get(health))
+        .route("/api/info", get(api_info))
+        .route("/api/update", get(get_update))
+        // Metrics (Prometheus)
+        .route("/metrics", get(metrics_handler))
+        // Diagnostics
+        .route("/api/diagnostics", get(get_diagnostics))
+        .route("/api/errors", get(get_errors))
+        .route("/api/audit", get(get_audit))
+        .route("/api/snapshot", get(get_snapshot))
+        // Configuration
+        .route("/api/config", get(get_config).put(put_config))
+        .route("/api/config/validate", post(validate_config))
+        .route("/api/config/rollback", post(rollback_config))
+        .route("/api/config/reload", post(reload_config))
+        .route("/api/config/pull", post(pull_config))
+        // Write switch
+        .route("/api/writes", get(get_writes).post(set_writes))
+        // Devices
+        .route("/api/devices", get(list_devices))
+        .route("/api/devices/:device_id", get(get_device))
+        .route("/api/devices/:device_id/timing", get(get_device_timing))
+        // Registers (read)
+        .route("/api/devices/:device_id/registers", get(get_registers))
+        .route(
+            "/api/devices/:device_id/registers/:register_name",
+            get(get_register),
+        )
+        // Registers (write)
+        .route(
+            "/api/devices/:device_id/registers/:register_name",
+            post(write_register),
+        )
+        // Device control
+        .route("/api/devices/:device_id/command", post(device_command))
+        // Groups
+        .route("/api/groups", get(list_groups))
+        .route("/api/groups/:group", get(get_group))
+        .route("/api/groups/:group/command", post(group_command))
+        // Recipes
+        .route("/api/recipes", get(list_recipes))
+        .route("/api/recipes/:name", post(run_recipe))
+        // WebSocket
+        .route("/ws", get(ws_handler))
+        // Apply API key authentication middleware
+        .layer(middleware::from_fn_with_state(auth_state, api_key_auth))
+        .with_state(Arc::new(state))
+}
+
+// ============================================================================
+// Error Handling
+// ============================================================================
+
+/// API error response
+#[derive(Serialize)]
+struct ApiError {
+    error: String,
+    code: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    details: Option<String>,
+    /// Modbus exception the device answered with
+    #[serde(skip_serializing_if = "Option::is_none")]
+    exception: Option<ModbusException>,
+}
+
+impl ApiError {
+    fn new(code: StatusCode, error: impl Into<String>) -> (StatusCode, Json<Self>) {
+        (
+            code,
+            Json(Self {
+                error: error.into(),
+                code: code.as_u16(),
+                details: None,
+                exception: None,
+            }),
+        )
+    }
+
+    fn with_details(
+        code: StatusCode,
+        error: impl Into<String>,
+        details: impl Into<String>,
+    ) -> (StatusCode, Json<Self>) {
+        (
+            code,
+            Json(Self {
+                error: error.into(),
+                code: code.as_u16(),
+                details: Some(details.into()),
+                exception: None,
+            }),
+        )
+    }
+
+    /// Error for a Modbus exception, with the status that says what went wrong
+    fn exception(exception: ModbusException) -> (StatusCode, Json<Self>) {
+        let (code, error) = match exception.code {
+            0x01 => (
+                StatusCode::NOT_IMPLEMENTED,
+                "Function not supported by device",
+            ),
+            0x02 => (StatusCode::NOT_FOUND, "Address not available on device"),
+            0x03 => (StatusCode::UNPROCESSABLE_ENTITY, "Value refused by device"),
+            0x05 => (StatusCode::ACCEPTED, "Device is still processing"),
+            0x06 => (StatusCode::SERVICE_UNAVAILABLE, "Device busy"),
+            0x0B => (
+                StatusCode::GATEWAY_TIMEOUT,
+                "Gateway target did not respond",
+            ),
+            _ => (StatusCode::BAD_GATEWAY, "Device failure"),
+        };
+        (
+            code,
+            Json(Self {
+                error: error.to_string(),
+                code: code.as_u16(),
+                details: Some(exception.to_string()),
+                exception: Some(exception),
+            }),
+        )
+    }
+}
+
+// ============================================================================
+// Health & Info Endpoints
+// ============================================================================
+
+/// Health check response
+#[derive(Serialize)]
+struct HealthResponse {
+    status: &'static str,
+    version: &'static str,
+}
+
+async fn health() -> Json<HealthResponse> {
+    Json(HealthResponse {
+        status: "ok",
+        version: env!("CARGO_PKG_VERSION"),
+    })
+}
+
+/// API info response
+#[derive(Serialize)]
+struct ApiInfoResponse {
+    name: &'static str,
+    description: &'static str,
+    /// Version, commit, build time, features and configuration hash
+    #[serde(flatten)]
+    build: BuildInfo,
+    endpoints: Vec<EndpointInfo>,
+}
+
+#[derive(Serialize)]
+struct EndpointInfo {
+    method: &'static str,
+    path: &'static str,
+    description: &'static str,
+}
+
+async fn api_info(State(state): State<Arc<ApiState>>) -> Json<ApiInfoResponse> {
+    let build = match &state.config_rx {
+        Some(config_rx) => BuildInfo::new(Some(&*config_rx.borrow())),
+        None => BuildInfo::new(None),
+    };
+    Json(ApiInfoResponse {
+        name: "RustBridge API",
+        description: "Industrial Protocol Bridge - Modbus TCP/RTU to JSON/MQTT Gateway",
+        build,
+        endpoints: vec![
+            EndpointInfo {
+                method: "GET",
+                path: "/health",
+                description: "Health check",
+            },
+            EndpointInfo {
+                method: "GET",
+                path: "/api/info",
+                description: "Version, build and configuration information",
+            },
+            EndpointInfo {
+                method: "GET",
+                path: "/api/update",
+                description: "Result of the latest release check",
+            },
+            EndpointInfo {
+                method: "GET",
+                path: "/api/diagnostics",
+                description: "Per-device poll statistics",
+            },
+            EndpointInfo {
+                method: "GET",
+                path: "/api/errors",
+                description: "Recent error events",
+            },
+            EndpointInfo {
+                method: "GET",
+                path: "/api/audit",
+                description: "Write audit log",
+            },
+            EndpointInfo {
+                method: "GET",
+                path: "/api/snapshot",
+                description: "All current register values",
+            },
+            EndpointInfo {
+                method: "GET",
+                path: "/api/config",
+                description: "Running configuration",
+            },
+            EndpointInfo {
+                method: "POST",
+                path: "/api/config/validate",
+                description: "Validate a configuration and preview its changes",
+            },
+            EndpointInfo {
+                method: "PUT",
+                path: "/api/config",
+                description: "Save and apply a configuration",
+            },
+            EndpointInfo {
+                method: "POST",
+                path: "/api/config/rollback",
+                description: "Restore and apply the previous configuration file",
+            },
+            EndpointInfo {
+                method: "POST",
+                path: "/api/config/reload",
+                description: "Reload the configuration file",
+            },
+            EndpointInfo {
+                method: "POST",
+                path: "/api/config/pull",
+                description: "Check the fleet server for a new configuration",
+            },
+            EndpointInfo {
+                method: "GET",
+                path: "/api/writes",
+                description: "Whether register writes are enabled",
+            },
+            EndpointInfo {
+                method: "POST",
+                path: "/api/writes",
+                description: "Enable or disable all register writes",
+            },
+            EndpointInfo {
+                method: "GET",
+                path: "/api/devices",
+                description: "List all devices",
+            },
+            EndpointInfo {
+                method: "GET",
+                path: "/api/devices/:device_id",
+                description: "Get device details",
+            },
+            EndpointInfo {
+                method: "GET",
+                path: "/api/devices/:device_id/timing",
+                description: "Timing of the last poll cycles",
+            },
+            EndpointInfo {
+                method: "GET",
+                path: "/api/devices/:device_id/registers",
+                description: "List device registers",
+            },
+            EndpointInfo {
+                method: "GET",
+                path: "/api/devices/:device_id/registers/:name",
+                description: "Get register value",
+            },
+            EndpointInfo {
+                method: "POST",
+                path: "/api/devices/:device_id/registers/:name",
+                description: "Write register value",
+            },
+            EndpointInfo {
+                method: "POST",
+                path: "/api/devices/:device_id/command",
+                description: "Pause, resume or poll a device now",
+            },
+            EndpointInfo {
+                method: "GET",
+                path: "/api/groups",
+                description: "List device groups with aggregate status",
+            },
+            EndpointInfo {
+                method: "GET",
+                path: "/api/groups/:group",
+                description: "Get group status",
+            },
+            EndpointInfo {
+                method: "POST",
+                path: "/api/groups/:group/command",
+                description: "Pause, resume or poll all devices of a group now",
+            },
+            EndpointInfo {
+                method: "GET",
+                path: "/api/recipes",
+                description: "List recipes",
+            },
+            EndpointInfo {
+                method: "POST",
+                path: "/api/recipes/:name",
+                description: "Run a recipe's writes in order",
+            },
+            EndpointInfo {
+                method: "GET",
+                path: "/ws",
+                description: "WebSocket for real-time updates",
+            },
+            EndpointInfo {
+                method: "GET",
+                path: "/metrics",
+                description: "Prometheus metrics endpoint",
+            },
+        ],
+    })
+}
+
+/// Prometheus metrics endpoint
+async fn metrics_handler(
+    State(state): State<Arc<ApiState>>,
+    Extension(scope): Extension<Scope>,
+) -> impl IntoResponse {
+    match &state.metrics_handle {
+        Some(handle) => {
+            state.record_memory_usage().await;
+            let mut metrics = handle.render();
+            if let Some(tenant) = scope.tenant() {
+                metrics = crate::metrics::tenant_metrics(&metrics, tenant);
+            }
+            (
+                StatusCode::OK,
+                [("content-type", "text/plain; version=0.0.4; charset=utf-8")],
+                metrics,
+            )
+        }
+        None => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            [("content-type", "text/plain; charset=utf-8")],
+            "Metrics not enabled".to_string(),
+        ),
+    }
+}
+
+// ============================================================================
+// Diagnostics Endpoint
+// ============================================================================
+
+/// Diagnostics response
+#[derive(Serialize)]
+struct DiagnosticsResponse {
+    devices: std::collections::HashMap<String, DeviceDiagnostics>,
+    count: usize,
+    mqtt: MqttStatus,
+    /// Frame statistics of each RTU bus, keyed by serial port
+    buses: std::collections::BTreeMap<String, crate::metrics::bus_stats::BusStats>,
+}
+
+/// MQTT broker connection state
+#[derive(Serialize)]
+struct MqttStatus {
+    enabled: bool,
+    connected: bool,
+}
+
+async fn get_diagnostics(
+    State(state): State<Arc<ApiState>>,
+    Extension(scope): Extension<Scope>,
+) -> Json<DiagnosticsResponse> {
+    let mut devices = state.diagnostics.read().await.clone();
+    devices.retain(|device_id, _| in_scope(&state, &scope, device_id));
+    let count = devices.len();
+    let mqtt = MqttStatus {
+        enabled: state.mqtt_connected.is_some(),
+        connected: state
+            .mqtt_connected
+            .as_ref()
+            .is_some_and(|c| c.load(Ordering::SeqCst)),
+    };
+    Json(DiagnosticsResponse {
+        devices,
+        count,
+        mqtt,
+        // A bus can be shared by devices of several tenants
+        buses: match scope {
+            Scope::All => crate::metrics::bus_stats::snapshot(),
+            Scope::Tenant(_) => Default::default(),
+        },
+    })
+}
+
+async fn get_snapshot(
+    State(state): State<Arc<ApiState>>,
+    Extension(scope): Extension<Scope>,
+) -> Json<Snapshot> {
+    let mut snapshot = Snapshot::capture(&state.register_store).await;
+    snapshot
+        .devices
+        .retain(|device_id, _| in_scope(&state, &scope, device_id));
+    Json(snapshot)
+}
+
+/// Reject tenant API keys from configuration endpoints
+fn require_global_scope(scope: &Scope) -> Result<(), (StatusCode, Json<ApiError>)> {
+    if *scope == Scope::All {
+        Ok(())
+    } else {
+        Err(ApiError::with_details(
+            StatusCode::FORBIDDEN,
+            "Forbidden",
+            "Tenant API keys cannot read or change the configuration",
+        ))
+    }
+}
+
+/// Have the bridge apply a configuration change and report what changed
+async fn send_reload(
+    state: &ApiState,
+    action: ReloadAction,
+) -> Result<Json<ReloadReport>, (StatusCode, Json<ApiError>)> {
+    let unavailable = || {
+        ApiError::with_details(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Reload unavailable",
+            "The bridge is not accepting reload requests",
+        )
+    };
+    let reload_tx = state.reload_tx.as_ref().ok_or_else(unavailable)?;
+
+    let (response_tx, response_rx) = tokio::sync::oneshot::channel();
+    reload_tx
+        .send(ReloadRequest {
+            action,
+            response_tx,
+        })
+        .await
+        .map_err(|_| unavailable())?;
+
+    match response_rx.await.map_err(|_| unavailable())? {
+        Ok(report) => Ok(Json(report)),
+        Err(e) => Err(ApiError::with_details(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "Configuration rejected",
+            e.to_string(),
+        )),
+    }
+}
+
+async fn reload_config(
+    State(state): State<Arc<ApiState>>,
+    Extension(scope): Extension<Scope>,
+) -> Result<Json<ReloadReport>, (StatusCode, Json<ApiError>)> {
+    require_global_scope(&scope)?;
+    send_reload(&state, ReloadAction::Reload).await
+}
+
+/// Check the fleet server for a new configuration now, e.g. from its webhook
+async fn pull_config(
+    State(state): State<Arc<ApiState>>,
+    Extension(scope): Extension<Scope>,
+) -> Result<Json<PullOutcome>, (StatusCode, Json<ApiError>)> {
+    require_global_scope(&scope)?;
+    let unavailable = || {
+        ApiError::with_details(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Pull unavailable",
+            "The bridge has no remote_config",
+        )
+    };
+    let pull_tx = state.config_pull_tx.as_ref().ok_or_else(unavailable)?;
+
+    let (response_tx, response_rx) = tokio::sync::oneshot::channel();
+    pull_tx.send(response_tx).await.map_err(|_| unavailable())?;
+
+    match response_rx.await.map_err(|_| unavailable())? {
+        Ok(outcome) => Ok(Json(outcome)),
+        Err(e @ RustbridgeError::Config(_)) => Err(ApiError::with_details(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "Configuration rejected",
+            e.to_string(),
+        )),
+        Err(e) => Err(ApiError::with_details(
+            StatusCode::BAD_GATEWAY,
+            "Configuration server unreachable",
+            e.to_string(),
+        )),
+    }
+}
+
+/// Result of the latest release check
+async fn get_update(
+    State(state): State<Arc<ApiState>>,
+) -> Result<Json<UpdateStatus>, (StatusCode, Json<ApiError>)> {
+    let status_rx = state.update_status.as_ref().ok_or_else(|| {
+        ApiError::with_details(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Update checks disabled",
+            "The bridge has no update section",
+        )
+    })?;
+    let status = status_rx.borrow().clone();
+    Ok(Json(status))
+}
+
+async fn get_config(
+    State(state): State<Arc<ApiState>>,
+    Extension(scope): Extension<Scope>,
+) -> Result<Json<Config>, (StatusCode, Json<ApiError>)> {
+    require_global_scope(&scope)?;
+    let config_rx = state.config_rx.as_ref().ok_or_else(|| {
+        ApiError::new(StatusCode::SERVICE_UNAVAILABLE, "Configuration unavailable")
+    })?;
+    let config = config_rx.borrow().clone();
+    Ok(Json(config))
+}
+
+/// Outcome of `POST /api/config/validate`
+#[derive(Serialize)]
+struct ConfigValidation {
+    valid: bool,
+    /// Every problem found, parse errors included
+    errors: Vec<String>,
+    /// What applying the configuration would change, when it is valid
+    #[serde(skip_serializing_if = "Option::is_none")]
+    changes: Option<ReloadReport>,
+}
+
+/// Parse and validate an edited configuration, collecting every error
+///
+/// Profiles from git may be cloned, so this runs on the blocking thread pool.
+async fn check_config(body: serde_json::Value) -> std::result::Result<Config, Vec<String>> {
+    let mut config: Config = serde_json::from_value(body).map_err(|e| vec![e.to_string()])?;
+    let mut config =
+        tokio::task::spawn_blocking(move || crate::profiles::resolve(&mut config).map(|()| config))
+            .await
+            .map_err(|e| vec![e.to_string()])?
+            .map_err(|e| vec![e.to_string()])?;
+    config.derive_counts();
+    let errors = config.validation_errors();
+    if errors.is_empty() {
+        Ok(config)
+    } else {
+        Err(errors)
+    }
+}
+
+async fn validate_config(
+    State(state): State<Arc<ApiState>>,
+    Extension(scope): Extension<Scope>,
+    Json(body): Json<serde_json::Value>,
+) -> Result<Json<ConfigValidation>, (StatusCode, Json<ApiError>)> {
+    require_global_scope(&scope)?;
+    Ok(Json(match check_config(body).await {
+        Ok(config) => ConfigValidation {
+            valid: true,
+            errors: vec![],
+            changes: state
+                .config_rx
+                .as_ref()
+                .map(|running| ReloadReport::diff(&running.borrow(), &config)),
+        },
+        Err(errors) => ConfigValidation {
+            valid: false,
+            errors,
+            changes: None,
+        },
+    }))
+}
+
+async fn put_config(
+    State(state): State<Arc<ApiState>>,
+    Extension(scope): Extension<Scope>,
+    Json(body): Json<serde_json::Value>,
+) -> Result<Json<ReloadReport>, (StatusCode, Json<ApiError>)> {
+    require_global_scope(&scope)?;
+    let config = check_config(body).await.map_err(|errors| {
+        ApiError::with_details(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "Configuration rejected",
+            errors.join("; "),
+        )
+    })?;
+    send_reload(&state, ReloadAction::Apply(Box::new(config))).await
+}
+
+async fn rollback_config(
+    State(state): State<Arc<ApiState>>,
+    Extension(scope): Extension<Scope>,
+) -> Result<Json<ReloadReport>, (StatusCode, Json<ApiError>)> {
+    require_global_scope(&scope)?;
+    send_reload(&state, ReloadAction::Rollback).await
+}
+
+/// Global write switch state, also the body of `POST /api/writes`
+#[derive(Serialize, Deserialize)]
+struct WritesState {
+    enabled: bool,
+}
+
+async fn get_writes(State(state): State<Arc<ApiState>>) -> Json<WritesState> {
+    Json(WritesState {
+        enabled: state.writes_enabled.load(Ordering::Relaxed),
+    })
+}
+
+async fn set_writes(
+    State(state): State<Arc<ApiState>>,
+    Extension(scope): Extension<Scope>,
+    Json(payload): Json<WritesState>,
+) -> Result<Json<WritesState>, (StatusCode, Json<ApiError>)> {
+    if scope != Scope::All {
+        return Err(ApiError::with_details(
+            StatusCode::FORBIDDEN,
+            "Forbidden",
+            "Tenant API keys cannot switch writes on or off",
+        ));
+    }
+    state
+        .writes_enabled
+        .store(payload.enabled, Ordering::Relaxed);
+    if payload.enabled {
+        info!("Register writes enabled");
+    } else {
+        warn!("Register writes disabled");
+    }
+    Ok(Json(payload))
+}
+
+/// Error/audit log query parameters
+#[derive(Deserialize)]
+struct ErrorsQuery {
+    /// Maximum number of events to return (default: all)
+    limit: Option<usize>,
+    /// Only return errors for this device
+    device_id: Option<String>,
+}
+
+/// Error log response
+#[derive(Serialize)]
+struct ErrorsResponse {
+    errors: Vec<ErrorEvent>,
+    count: usize,
+    capacity: usize,
+}
+
+async fn get_errors(
+    State(state): State<Arc<ApiState>>,
+    Extension(scope): Extension<Scope>,
+    Query(query): Query<ErrorsQuery>,
+) -> Json<ErrorsResponse> {
+    let limit = query.limit.unwrap_or(usize::MAX);
+    let errors: Vec<ErrorEvent> = match scope {
+        Scope::All => state.error_log.recent(limit, query.device_id.as_deref()),
+        // Errors without a device (e.g. MQTT) are bridge-wide
+        Scope::Tenant(_) => state
+            .error_log
+            .recent(usize::MAX, query.device_id.as_deref())
+            .into_iter()
+            .filter(|e| {
+                e.device_id
+                    .as_deref()
+                    .is_some_and(|id| in_scope(&state, &scope, id))
+            })
+            .take(limit)
+            .collect(),
+    };
+    let count = errors.len();
+    Json(ErrorsResponse {
+        errors,
+        count,
+        capacity: state.error_log.capacity(),
+    })
+}
+
+/// Audit log response
+#[derive(Serialize)]
+struct AuditResponse {
+    entries: Vec<AuditEntry>,
+    count: usize,
+}
+
+async fn get_audit(
+    State(state): State<Arc<ApiState>>,
+    Extension(scope): Extension<Scope>,
+    Query(query): Query<ErrorsQuery>,
+) -> Json<AuditResponse> {
+    let limit = query.limit.unwrap_or(usize::MAX);
+    let entries: Vec<AuditEntry> = match scope {
+        Scope::All => state.audit_log.recent(limit, query.device_id.as_deref()),
+        Scope::Tenant(_) => state
+            .audit_log
+            .recent(usize::MAX, query.device_id.as_deref())
+            .into_iter()
+            .filter(|e| in_scope(&state, &scope, &e.device_id))
+            .take(limit)
+            .collect(),
+    };
+    let count = entries.len();
+    Json(AuditResponse { entries, count })
+}
+
+// ============================================================================
+// Device Endpoints
+// ============================================================================
+
+/// Device list response
+#[derive(Serialize)]
+struct DeviceListResponse {
+    devices: Vec<DeviceSummary>,
+    count: usize,
+}
+
+#[derive(Serialize)]
+struct DeviceSummary {
+    id: String,
+    register_count: usize,
+    last_update: Option<String>,
+}
+
+async fn list_devices(
+    State(state): State<Arc<ApiState>>,
+    Extension(scope): Extension<Scope>,
+) -> Json<DeviceListResponse> {
+    let store = state.register_store.read().await;
+
+    let devices: Vec<DeviceSummary> = store
+        .iter()
+        .filter(|(id, _)| in_scope(&state, &scope, id))
+        .map(|(id, registers)| {
+            let last_update = registers
+                .values()
+                .map(|r| r.timestamp)
+                .max()
+                .map(|t| t.to_rfc3339());
+
+            DeviceSummary {
+                id: id.clone(),
+                register_count: registers.len(),
+                last_update,
+            }
+        })
+        .collect();
+
+    let count = devices.len();
+    Json(DeviceListResponse { devices, count })
+}
+
+/// Device detail response
+#[derive(Serialize)]
+struct DeviceResponse {
+    id: String,
+    registers: Vec<RegisterResponse>,
+    register_count: usize,
+}
+
+#[derive(Serialize, Clone)]
+struct RegisterResponse {
+    name: Arc<str>,
+    value: f64,
+    raw: Vec<u16>,
+    unit: Option<String>,
+    timestamp: String,
+    #[serde(flatten)]
+    meta: Meta,
+}
+
+impl From<&RegisterValue> for RegisterResponse {
+    fn from(r: &RegisterValue) -> Self {
+        Self {
+            name: r.name.clone(),
+            value: r.value,
+            raw: r.raw.clone(),
+            unit: r.unit.clone(),
+            timestamp: r.timestamp.to_rfc3339(),
+            meta: r.meta.clone(),
+        }
+    }
+}
+
+async fn get_device(
+    State(state): State<Arc<ApiState>>,
+    Extension(scope): Extension<Scope>,
+    Path(device_id): Path<String>,
+) -> Result<Json<DeviceResponse>, (StatusCode, Json<ApiError>)> {
+    check_scope(&state, &scope, &device_id)?;
+    let store = state.register_store.read().await;
+
+    let registers = store
+        .get(&device_id)
+        .ok_or_else(|| ApiError::new(StatusCode::NOT_FOUND, "Device not found"))?;
+
+    let registers: Vec<RegisterResponse> = registers.values().map(RegisterResponse::from).collect();
+
+    let register_count = registers.len();
+    Ok(Json(DeviceResponse {
+        id: device_id,
+        registers,
+        register_count,
+    }))
+}
+
+/// Timing report query parameters
+#[derive(Deserialize)]
+struct TimingQuery {
+    /// Maximum number of cycles to return (default: all kept)
+    limit: Option<usize>,
+}
+
+/// Poll timing report of a device
+#[derive(Serialize)]
+struct TimingResponse {
+    device_id: String,
+    poll_interval_ms: u64,
+    /// Most recent cycles, newest first
+    cycles: Vec<CycleTiming>,
+    count: usize,
+    summary: TimingSummary,
+}
+
+/// Aggregates over the returned cycles
+#[derive(Serialize, Default)]
+struct TimingSummary {
+    avg_duration_ms: f64,
+    max_duration_ms: f64,
+    avg_queue_wait_ms: f64,
+    max_queue_wait_ms: f64,
+    overruns: usize,
+}
+
+impl TimingSummary {
+    fn of(cycles: &[CycleTiming]) -> Self {
+        if cycles.is_empty() {
+            return Self::default();
+        }
+        let count = cycles.len() as f64;
+        Self {
+            avg_duration_ms: cycles.iter().map(|c| c.duration_ms).sum::<f64>() / count,
+            max_duration_ms: cycles.iter().map(|c| c.duration_ms).fold(0.0, f64::max),
+            avg_queue_wait_ms: cycles.iter().map(|c| c.queue_wait_ms).sum::<f64>() / count,
+            max_queue_wait_ms: cycles.iter().map(|c| c.queue_wait_ms).fold(0.0, f64::max),
+            overruns: cycles.iter().filter(|c| c.overran).count(),
+        }
+    }
+}
+
+async fn get_device_timing(
+    State(state): State<Arc<ApiState>>,
+    Extension(scope): Extension<Scope>,
+    Path(device_id): Path<String>,
+    Query(query): Query<TimingQuery>,
+) -> Result<Json<TimingResponse>, (StatusCode, Json<ApiError>)> {
+    check_scope(&state, &scope, &device_id)?;
+    let diagnostics = state.diagnostics.read().await;
+    let device = diagnostics
+        .get(&device_id)
+        .ok_or_else(|| ApiError::new(StatusCode::NOT_FOUND, "Device not found"))?;
+
+    let cycles: Vec<CycleTiming> = device
+        .recent_cycles
+        .iter()
+        .rev()
+        .take(query.limit.unwrap_or(usize::MAX))
+        .cloned()
+        .collect();
+    Ok(Json(TimingResponse {
+        device_id,
+        poll_interval_ms: device.poll_interval_ms,
+        count: cycles.len(),
+        summary: TimingSummary::of(&cycles),
+        cycles,
+    }))
+}
+
+// ============================================================================
+// Register Endpoints
+// ============================================================================
+
+async fn get_registers(
+    State(state): State<Arc<ApiState>>,
+    Extension(scope): Extension<Scope>,
+    Path(device_id): Path<String>,
+) -> Result<Json<Vec<RegisterResponse>>, (StatusCode, Json<ApiError>)> {
+    check_scope(&state, &scope, &device_id)?;
+    let store = state.register_store.read().await;
+
+    let registers = store
+        .get(&device_id)
+        .ok_or_else(|| ApiError::new(StatusCode::NOT_FOUND, "Device not found"))?;
+
+    let registers: Vec<RegisterResponse> = registers.values().map(RegisterResponse::from).collect();
+
+    Ok(Json(registers))
+}
+
+async fn get_register(
+    State(state): State<Arc<ApiState>>,
+    Extension(scope): Extension<Scope>,
+    Path((device_id, register_name)): Path<(String, String)>,
+) -> Result<Json<RegisterResponse>, (StatusCode, Json<ApiError>)> {
+    check_scope(&state, &scope, &device_id)?;
+    let store = state.register_store.read().await;
+
+    let registers = store
+        .get(&device_id)
+        .ok_or_else(|| ApiError::new(StatusCode::NOT_FOUND, "Device not found"))?;
+
+    let register = registers
+        .get(&register_name)
+        .ok_or_else(|| ApiError::new(StatusCode::NOT_FOUND, "Register not found"))?;
+
+    Ok(Json(RegisterResponse::from(register)))
+}
+
+/// Write register request body
+#[derive(Deserialize)]
+struct WriteRegisterRequest {
+    /// Value in engineering units; scale and offset are inverted on write
+    value: WriteValue,
+}
+
+/// Write register response
+#[derive(Serialize)]
+struct WriteRegisterResponse {
+    success: bool,
+    device_id: String,
+    register_name: String,
+    value_written: f64,
+    /// Registers the value was encoded to
+    raw_written: Vec<u16>,
+    /// Value read back from the device after the write
+    #[serde(skip_serializing_if = "Option::is_none")]
+    read_back: Option<f64>,
+    message: String,
+}
+
+async fn write_register(
+    State(state): State<Arc<ApiState>>,
+    Extension(scope): Extension<Scope>,
+    Path((device_id, register_name)): Path<(String, String)>,
+    headers: HeaderMap,
+    Json(payload): Json<WriteRegisterRequest>,
+) -> Result<Json<WriteRegisterResponse>, (StatusCode, Json<ApiError>)> {
+    // Validate device and register exist
+    check_scope(&state, &scope, &device_id)?;
+    {
+        let store = state.register_store.read().await;
+        let registers = store
+            .get(&device_id)
+            .ok_or_else(|| ApiError::new(StatusCode::NOT_FOUND, "Device not found"))?;
+
+        registers
+            .get(&register_name)
+            .ok_or_else(|| ApiError::new(StatusCode::NOT_FOUND, "Register not found"))?;
+    }
+
+    let value = payload.value.as_f64().ok_or_else(|| {
+        ApiError::with_details(
+            StatusCode::BAD_REQUEST,
+            "Invalid value",
+            "Expected a number, true/false, on/off or 1/0",
+        )
+    })?;
+
+    // Create response channel
+    let (response_tx, response_rx) = tokio::sync::oneshot::channel();
+
+    // Send write request
+    let api_key = headers.get("X-API-Key").and_then(|v| v.to_str().ok());
+
+    let write_request = WriteRequest {
+        device_id: device_id.clone(),
+        register_name: register_name.clone(),
+        source: WriteSource::api(api_key),
+        value,
+        response_tx,
+    };
+
+    state.write_tx.send(write_request).await.map_err(|_| {
+        ApiError::with_details(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Write service unavailable",
+            "The Modbus write handler is not running",
+        )
+    })?;
+
+    // Wait for response with timeout
+    let result = tokio::time::timeout(std::time::Duration::from_secs(5), response_rx)
+        .await
+        .map_err(|_| {
+            ApiError::with_details(
+                StatusCode::GATEWAY_TIMEOUT,
+                "Write timeout",
+                "The Modbus device did not respond in time",
+            )
+        })?
+        .map_err(|_| {
+            ApiError::with_details(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Write failed",
+                "Response channel closed unexpectedly",
+            )
+        })?;
+
+    match result {
+        Ok(outcome) => {
+            info!(
+                "Write successful: {}:{} = {} (raw {:?})",
+                device_id, register_name, value, outcome.raw
+            );
+            Ok(Json(WriteRegisterResponse {
+                success: true,
+                device_id,
+                register_name,
+                value_written: value,
+                raw_written: outcome.raw,
+                read_back: outcome.read_back,
+                message: "Register written successfully".to_string(),
+            }))
+        }
+        Err(WriteError::Rejected(e)) => Err(ApiError::with_details(
+            StatusCode::BAD_REQUEST,
+            "Write rejected",
+            e,
+        )),
+        Err(WriteError::Disabled(e)) => Err(ApiError::with_details(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Writes disabled",
+            e,
+        )),
+        Err(WriteError::RateLimited(e)) => Err(ApiError::with_details(
+            StatusCode::TOO_MANY_REQUESTS,
+            "Write rate limited",
+            e,
+        )),
+        Err(WriteError::Failed(e)) => Err(ApiError::with_details(
+            StatusCode::BAD_GATEWAY,
+            "Modbus write failed",
+            e,
+        )),
+        Err(WriteError::Exception(exception)) => Err(ApiError::exception(exception)),
+    }
+}
+
+// ============================================================================
+// Device Control & Group Endpoints
+// ============================================================================
+
+/// Command request body
+#[derive(Deserialize)]
+struct CommandRequest {
+    command: Command,
+}
+
+/// Command response
+#[derive(Serialize)]
+struct CommandResponse {
+    command: Command,
+    devices: Vec<String>,
+}
+
+fn run_command(
+    state: &ApiState,
+    target: Target<'_>,
+    command: Command,
+) -> Result<Json<CommandResponse>, (StatusCode, Json<ApiError>)> {
+    let devices = state.controls.apply(target, command).map_err(|e| {
+        ApiError::with_details(StatusCode::NOT_FOUND, "Unknown target", e.to_string())
+    })?;
+    info!("Command {:?} applied to {:?}", command, devices);
+    Ok(Json(CommandResponse { command, devices }))
+}
+
+async fn device_command(
+    State(state): State<Arc<ApiState>>,
+    Extension(scope): Extension<Scope>,
+    Path(device_id): Path<String>,
+    Json(payload): Json<CommandRequest>,
+) -> Result<Json<CommandResponse>, (StatusCode, Json<ApiError>)> {
+    check_scope(&state, &scope, &device_id)?;
+    run_command(&state, Target::Device(&device_id), payload.command)
+}
+
+async fn group_command(
+    State(state): State<Arc<ApiState>>,
+    Extension(scope): Extension<Scope>,
+    Path(group): Path<String>,
+    Json(payload): Json<CommandRequest>,
+) -> Result<Json<CommandResponse>, (StatusCode, Json<ApiError>)> {
+    if !group_in_scope(&state, &scope, &group) {
+        return Err(ApiError::with_details(
+            StatusCode::NOT_FOUND,
+            "Unknown target",
+            format!("unknown group '{}'", group),
+        ));
+    }
+    run_command(&state, Target::Group(&group), payload.command)
+}
+
+/// Group list response
+#[derive(Serialize)]
+struct GroupListResponse {
+    groups: Vec<GroupState>,
+    count: usize,
+}
+
+async fn list_groups(
+    State(state): State<Arc<ApiState>>,
+    Extension(scope): Extension<Scope>,
+) -> Json<GroupListResponse> {
+    let groups: Vec<GroupState> = state
+        .controls
+        .group_names()
+        .iter()
+        .filter(|group| group_in_scope(&state, &scope, group))
+        .filter_map(|group| state.controls.group_state(group))
+        .collect();
+    let count = groups.len();
+    Json(GroupListResponse { groups, count })
+}
+
+async fn get_group(
+    State(state): State<Arc<ApiState>>,
+    Extension(scope): Extension<Scope>,
+    Path(group): Path<String>,
+) -> Result<Json<GroupState>, (StatusCode, Json<ApiError>)> {
+    state
+        .controls
+        .group_state(&group)
+        .filter(|_| group_in_scope(&state, &scope, &group))
+        .map(Json)
+        .ok_or_else(|| ApiError::new(StatusCode::NOT_FOUND, "Group not found"))
+}
+
+// ============================================================================
+// Recipe Endpoints
+// ============================================================================
+
+/// Recipes response
+#[derive(Serialize)]
+struct RecipeListResponse {
+    recipes: Vec<RecipeConfig>,
+    count: usize,
+}
+
+/// Recipes whose devices are all visible to the request's scope
+fn recipes_in_scope(state: &ApiState, scope: &Scope) -> Vec<RecipeConfig> {
+    let Some(config_rx) = &state.config_rx else {
+        return Vec::new();
+    };
+    let config = config_rx.borrow();
+    config
+        .recipes
+        .iter()
+        .filter(|recipe| {
+            recipe
+                .steps
+                .iter()
+                .all(|step| in_scope(state, scope, &step.device_id))
+        })
+        .cloned()
+        .collect()
+}
+
+async fn list_recipes(
+    State(state): State<Arc<ApiState>>,
+    Extension(scope): Extension<Scope>,
+) -> Json<RecipeListResponse> {
+    let recipes = recipes_in_scope(&state, &scope);
+    let count = recipes.len();
+    Json(RecipeListResponse { recipes, count })
+}
+
+async fn run_recipe(
+    State(state): State<Arc<ApiState>>,
+    Extension(scope): Extension<Scope>,
+    Path(name): Path<String>,
+    headers: HeaderMap,
+) -> Result<(StatusCode, Json<RecipeOutcome>), (StatusCode, Json<ApiError>)> {
+    let recipe = recipes_in_scope(&state, &scope)
+        .into_iter()
+        .find(|recipe| recipe.name == name)
+        .ok_or_else(|| ApiError::new(StatusCode::NOT_FOUND, "Recipe not found"))?;
+    let api_key = headers.get("X-API-Key").and_then(|v| v.to_str().ok());
+    let outcome = recipes::run(
+        &state.write_tx,
+        &state.register_store,
+        &recipe,
+        WriteSource::api(api_key),
+    )
+    .await;
+    let status = if outcome.success {
+        StatusCode::OK
+    } else {
+        StatusCode::BAD_GATEWAY
+    };
+    Ok((status, Json(outcome)))
+}
+
+// ============================================================================
+// WebSocket Endpoint
+// ============================================================================
+
+/// WebSocket message types
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+enum WsMessage {
+    /// Subscribe to specific devices/registers
+    #[serde(rename = "subscribe")]
+    Subscribe { devices: Option<Vec<String>> },
+    /// Unsubscribe from updates
+    #[serde(rename = "unsubscribe")]
+    Unsubscribe,
+    /// Register update (server -> client)
+    #[serde(rename = "update")]
+    Update(RegisterUpdate),
+    /// Connection lifecycle event (server -> client)
+    #[serde(rename = "event")]
+    Event(ConnectionEvent),
+    /// Error message
+    #[serde(rename = "error")]
+    Error { message: String },
+    /// Connection confirmed
+    #[serde(rename = "connected")]
+    Connected { message: String },
+    /// Ping/Pong for keepalive
+    #[serde(rename = "ping")]
+    Ping,
+    #[serde(rename = "pong")]
+    Pong,
+}
+
+/// WebSocket query parameters, narrowing the stream for the whole connection
+#[derive(Deserialize)]
+struct WsQuery {
+    /// Only stream this device
+    device_id: Option<String>,
+    /// Only stream updates of this register
+    register_name: Option<String>,
+}
+
+impl WsQuery {
+    /// Whether the connection receives messages for a device and, for
+    /// updates, a register
+    fn allows(&self, device_id: &str, register: Option<&str>) -> bool {
+        self.device_id.as_deref().is_none_or(|id| id == device_id)
+            && register
+                .zip(self.register_name.as_deref())
+                .is_none_or(|(register, name)| register == name)
+    }
+}
+
+async fn ws_handler(
+    Query(query): Query<WsQuery>,
+    State(state): State<Arc<ApiState>>,
+    Extension(scope): Extension<Scope>,
+    ws: WebSocketUpgrade,
+) -> Response {
+    ws.on_upgrade(|socket| handle_socket(socket, state, scope, query))
+}
+
+async fn handle_socket(socket: WebSocket, state: Arc<ApiState>, scope: Scope, query: WsQuery) {
+    let (mut sender, mut receiver) = socket.split();
+
+    // Send connection confirmation
+    let connected_msg = WsMessage::Connected {
+        message: format!("RustBridge WebSocket v{}", env!("CARGO_PKG_VERSION")),
+    };
+    if let Ok(msg) = serde_json::to_string(&connected_msg) {
+        if sender.send(Message::Text(msg)).await.is_err() {
+            return;
+        }
+    }
+
+    info!("WebSocket client connected");
+
+    // Subscribe to register updates and connection events
+    let mut update_rx = state.subscribe();
+    let mut event_rx = state.subscribe_events();
+
+    // Track subscribed devices (None = all devices)
+    let mut subscribed_devices: Option<Vec<String>> = None;
+
+    loop {
+        tokio::select! {
+            // Handle incoming messages from client
+            msg = receiver.next() => {
+                match msg {
+                    Some(Ok(Message::Text(text))) => {
+                        match serde_json::from_str::<WsMessage>(&text) {
+                            Ok(WsMessage::Subscribe { devices }) => {
+                                subscribed_devices = devices.clone();
+                                debug!("Client subscribed to: {:?}", subscribed_devices);
+                            }
+                            Ok(WsMessage::Unsubscribe) => {
+                                subscribed_devices = Some(vec![]);
+                                debug!("Client unsubscribed from all updates");
+                            }
+                            Ok(WsMessage::Ping) => {
+                                let pong = serde_json::to_string(&WsMessage::Pong).unwrap();
+                                if sender.send(Message::Text(pong)).await.is_err() {
+                                    break;
+                                }
+                            }
+                            Ok(_) => {
+                                // Ignore other message types from client
+                            }
+                            Err(e) => {
+                                warn!("Invalid WebSocket message: {}", e);
+                                let error = WsMessage::Error {
+                                    message: format!("Invalid message format: {}", e),
+                                };
+                                if let Ok(msg) = serde_json::to_string(&error) {
+                                    let _ = sender.send(Message::Text(msg)).await;
+                                }
+                            }
+                        }
+                    }
+                    Some(Ok(Message::Ping(data))) => {
+                        let pong = sender.send(Message::Pong(data)).await;
+                        if pong.is_err() {
+                            break;
+                        }
+                    }
+                    Some(Ok(Message::Close(_))) => {
+                        info!("WebSocket client disconnected");
+                        break;
+                    }
+                    Some(Err(e)) => {
+                        error!("WebSocket error: {}", e);
+                        break;
+                    }
+                    None => break,
+                    _ => {}
+                }
+            }
+            // Handle register updates from broadcast channel
+            update = update_rx.recv() => {
+                match update {
+                    Ok(register_update) => {
+                        // Check if client is subscribed to this device
+                        if is_subscribed(&subscribed_devices, &register_update.device_id)
+                            && query.allows(
+                                &register_update.device_id,
+                                Some(&register_update.register_name),
+                            )
+                            && in_scope(&state, &scope, &register_update.device_id)
+                        {
+                            let msg = WsMessage::Update(register_update);
+                            if let Ok(json) = serde_json::to_string(&msg) {
+                                if sender.send(Message::Text(json)).await.is_err() {
+                                    break;
+                                }
+                            }
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        super::record_lag("websocket", n);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => {
+                        break;
+                    }
+                }
+            }
+            // Handle connection lifecycle events
+            event = event_rx.recv() => {
+                match event {
+                    Ok(connection_event) => {
+                        if is_subscribed(&subscribed_devices, &connection_event.device_id)
+                            && query.allows(&connection_event.device_id, None)
+                            && in_scope(&state, &scope, &connection_event.device_id)
+                        {
+                            let msg = WsMessage::Event(connection_event);
+                            if let Ok(json) = serde_json::to_string(&msg) {
+                                if sender.send(Message::Text(json)).await.is_err() {
+                                    break;
+                                }
+                            }
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        warn!("WebSocket client lagged, missed {} events", n);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    info!("WebSocket connection closed");
+}
+
+/// Whether a device is visible to a request's scope
+fn in_scope(state: &ApiState, scope: &Scope, device_id: &str) -> bool {
+    match scope {
+        Scope::All => true,
+        Scope::Tenant(_) => scope.allows(state.controls.tenant_of(device_id).as_deref()),
+    }
+}
+
+/// Reject devices outside the request's scope as if they did not exist
+fn check_scope(
+    state: &ApiState,
+    scope: &Scope,
+    device_id: &str,
+) -> Result<(), (StatusCode, Json<ApiError>)> {
+    if in_scope(state, scope, device_id) {
+        Ok(())
+    } else {
+        Err(ApiError::new(StatusCode::NOT_FOUND, "Device not found"))
+    }
+}
+
+/// Whether a group is visible to a request's scope (all its devices must be)
+fn group_in_scope(state: &ApiState, scope: &Scope, group: &str) -> bool {
+    match scope.tenant() {
+        None => true,
+        Some(tenant) => state.controls.group_in_tenant(group, tenant),
+    }
+}
+
+/// Whether a client with the given subscription receives messages for a device
+fn is_subscribed(subscribed_devices: &Option<Vec<String>>, device_id: &str) -> bool {
+    match subscribed_devices {
+        None => true,                                 // Subscribed to all
+        Some(devices) if devices.is_empty() => false, // Unsubscribed
+        Some(devices) => devices.iter().any(|d| d == device_id),
+    }
+}
@@ -0,0 +1,247 @@
+//! Audit log for write operations
+//!
+//! Every register/coil write is recorded with its source (API key, MQTT
+//! topic, rule), the previous and requested values, and the result.
+//! Entries are appended as JSON lines to the configured audit file and
+//! kept in memory for `GET /api/audit`.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use tracing::error;
+
+use crate::config::AuditConfig;
+use crate::metrics::memory::{MemorySize, RingBuffer};
+
+/// Origin of a write request
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum WriteSource {
+    /// REST API, identified by a masked API key when authentication is enabled
+    Api { api_key: Option<String> },
+    /// MQTT command topic
+    Mqtt { topic: String },
+    /// Automation rule
+    Rule { name: String },
+    /// Safe state written after contact with the MQTT broker was lost
+    #[serde(rename = "dead_man")]
+    DeadMan,
+}
+
+impl WriteSource {
+    /// API source with the key masked to its first four characters
+    pub fn api(api_key: Option<&str>) -> Self {
+        WriteSource::Api {
+            api_key: api_key.map(mask_key),
+        }
+    }
+}
+
+/// Keep enough of a key to identify it without exposing it
+fn mask_key(key: &str) -> String {
+    let prefix: String = key.chars().take(4).collect();
+    format!("{}****", prefix)
+}
+
+/// Outcome of a write
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WriteResult {
+    Success,
+    Failure,
+}
+
+/// A single audited write
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditEntry {
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    pub source: WriteSource,
+    pub device_id: String,
+    pub register: String,
+    pub address: u16,
+    /// Last known value before the write, if any
+    pub old_value: Option<f64>,
+    /// Requested value
+    pub new_value: f64,
+    pub result: WriteResult,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl MemorySize for AuditEntry {
+    fn memory_size(&self) -> usize {
+        let source = match &self.source {
+            WriteSource::Api { api_key } => api_key.as_ref().map_or(0, String::capacity),
+            WriteSource::Mqtt { topic } => topic.capacity(),
+            WriteSource::Rule { name } => name.capacity(),
+            WriteSource::DeadMan => 0,
+        };
+        std::mem::size_of::<Self>()
+            + source
+            + self.device_id.capacity()
+            + self.register.capacity()
+            + self.error.as_ref().map_or(0, String::capacity)
+    }
+}
+
+/// Append-only audit log, cheap to clone and share
+#[derive(Debug, Clone)]
+pub struct AuditLog {
+    file: Option<Arc<Mutex<File>>>,
+    recent: Arc<Mutex<RingBuffer<AuditEntry>>>,
+}
+
+impl Default for AuditLog {
+    fn default() -> Self {
+        Self::in_memory(AuditConfig::default().memory_size)
+    }
+}
+
+impl AuditLog {
+    /// Open the audit log described by the configuration
+    pub fn open(config: &AuditConfig) -> Result<Self> {
+        let recent = RingBuffer::new("audit_log", config.memory_size)
+            .with_max_bytes(config.memory_max_bytes);
+        let mut log = Self {
+            file: None,
+            recent: Arc::new(Mutex::new(recent)),
+        };
+
+        if let Some(path) = &config.file {
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .with_context(|| format!("Failed to open audit log: {}", path))?;
+            log.file = Some(Arc::new(Mutex::new(file)));
+        }
+
+        Ok(log)
+    }
+
+    /// Audit log kept only in memory
+    pub fn in_memory(capacity: usize) -> Self {
+        Self {
+            file: None,
+            recent: Arc::new(Mutex::new(RingBuffer::new("audit_log", capacity))),
+        }
+    }
+
+    /// Record a write, appending it to the audit file if configured
+    pub fn record(&self, entry: AuditEntry) {
+        if let Some(file) = &self.file {
+            if let Err(e) = Self::append(file, &entry) {
+                error!("Failed to write audit log entry: {}", e);
+            }
+        }
+
+        self.recent_entries().push(entry);
+    }
+
+    fn recent_entries(&self) -> std::sync::MutexGuard<'_, RingBuffer<AuditEntry>> {
+        self.recent.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Estimated memory taken by the entries kept in memory
+    pub fn memory_bytes(&self) -> usize {
+        self.recent_entries().memory_bytes()
+    }
+
+    fn append(file: &Mutex<File>, entry: &AuditEntry) -> Result<()> {
+        let mut line = serde_json::to_string(entry)?;
+        line.push('\n');
+
+        let mut file = file.lock().unwrap_or_else(|e| e.into_inner());
+        file.write_all(line.as_bytes())?;
+        file.flush()?;
+        Ok(())
+    }
+
+    /// Most recent entries first, optionally filtered by device
+    pub fn recent(&self, limit: usize, device_id: Option<&str>) -> Vec<AuditEntry> {
+        self.recent_entries()
+            .iter()
+            .rev()
+            .filter(|e| device_id.is_none_or(|id| e.device_id == id))
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(device: &str, new_value: f64, result: WriteResult) -> AuditEntry {
+        AuditEntry {
+            timestamp: chrono::Utc::now(),
+            source: WriteSource::api(Some("secret-key-123")),
+            device_id: device.to_string(),
+            register: "setpoint".to_string(),
+            address: 40,
+            old_value: Some(20.0),
+            new_value,
+            result,
+            error: None,
+        }
+    }
+
+    #[test]
+    fn test_api_key_masked() {
+        assert_eq!(
+            WriteSource::api(Some("secret-key-123")),
+            WriteSource::Api {
+                api_key: Some("secr****".to_string())
+            }
+        );
+        assert_eq!(WriteSource::api(None), WriteSource::Api { api_key: None });
+    }
+
+    #[test]
+    fn test_recent_newest_first() {
+        let log = AuditLog::in_memory(2);
+        log.record(entry("plc-001", 1.0, WriteResult::Success));
+        log.record(entry("plc-002", 2.0, WriteResult::Failure));
+        log.record(entry("plc-001", 3.0, WriteResult::Success));
+
+        let recent = log.recent(10, None);
+        assert_eq!(recent.len(), 2);
+        assert_eq!(recent[0].new_value, 3.0);
+
+        let filtered = log.recent(10, Some("plc-002"));
+        assert_eq!(filtered.len(), 1);
+        assert_eq!(filtered[0].result, WriteResult::Failure);
+    }
+
+    #[test]
+    fn test_file_is_append_only_json_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.log");
+        std::fs::write(&path, "{\"existing\":true}\n").unwrap();
+
+        let log = AuditLog::open(&AuditConfig {
+            file: Some(path.to_string_lossy().to_string()),
+            memory_size: 10,
+            ..Default::default()
+        })
+        .unwrap();
+        log.record(entry("plc-001", 25.0, WriteResult::Success));
+        log.record(entry("plc-001", 26.0, WriteResult::Failure));
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], "{\"existing\":true}");
+
+        let json: serde_json::Value = serde_json::from_str(lines[1]).unwrap();
+        assert_eq!(json["device_id"], "plc-001");
+        assert_eq!(json["source"]["type"], "api");
+        assert_eq!(json["source"]["api_key"], "secr****");
+        assert_eq!(json["old_value"], 20.0);
+        assert_eq!(json["new_value"], 25.0);
+        assert_eq!(json["result"], "success");
+    }
+}
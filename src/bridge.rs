@@ -1,22 +1,57 @@
 //! Main bridge orchestration
+//!
+//! [`Bridge::start`] spawns device polling, MQTT publishing and the HTTP API
+//! and returns a [`BridgeHandle`]; [`Bridge::run`] does the same and waits for
+//! Ctrl+C or SIGTERM before shutting down.
 
-use anyhow::Result;
-use std::collections::HashMap;
+use futures_util::{Stream, StreamExt};
+use std::collections::{HashMap, HashSet};
 use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
-use std::time::Instant;
-use tokio::sync::RwLock;
-use tracing::info;
+use std::time::{Duration, Instant, SystemTime};
+use tokio::sync::{broadcast, watch, RwLock};
+use tokio::task::{AbortHandle, JoinHandle, JoinSet};
+use tracing::{info, Instrument};
 
-use crate::api::{self, ApiState, RegisterUpdate, WriteRequest};
-use crate::config::Config;
+#[cfg(feature = "http-api")]
+use crate::api;
+use crate::api::{
+    ApiState, BlockingSink, ModbusException, RegisterUpdate, WriteError, WriteOutcome, WriteRequest,
+};
+use crate::audit::{AuditEntry, AuditLog, WriteResult, WriteSource};
+use crate::config::{
+    CircuitBreakerConfig, Config, ConnectionConfig, DeviceConfig, Priority, ReadBlocksConfig,
+    ReconnectConfig, RegisterConfig, RegisterType,
+};
+use crate::control::DeviceControls;
+use crate::daemon;
+use crate::error::{Result, RustbridgeError};
+use crate::events::{ConnectionEvent, ConnectionEventKind, EventSender};
+use crate::metrics::diagnostics::{
+    CycleHealth, CycleStats, CycleStatsWindow, CycleTiming, CycleTraffic, DiagnosticsStore,
+    RegisterTiming,
+};
+use crate::metrics::error_log::{ErrorEvent, ErrorKind, ErrorLog, ErrorSource};
 use crate::metrics::{self, ReadMetrics};
-use crate::modbus::reader::{self, RegisterStore, RegisterValue};
-use crate::mqtt::MqttPublisher;
+use crate::modbus::codec;
+use crate::modbus::reader::{self, ReadBlock, RegisterStore, RegisterValue, Snapshot};
+use crate::reload::{self, ReloadAction, ReloadReport, ReloadRequest};
+use crate::remote_config::ConfigPuller;
+use crate::systemd::{self, Heartbeats};
+use crate::update::{self, UpdateChecker, UpdateStatus};
+#[cfg(feature = "mqtt")]
+use crate::{
+    api::UpdateReceiver,
+    config::Backpressure,
+    mqtt::{rpc_request_topic, MqttPublisher, RpcContext},
+};
 
 /// Main bridge that orchestrates all components
 pub struct Bridge {
     config: Config,
+    config_path: Option<String>,
     register_store: RegisterStore,
 }
 
@@ -27,194 +62,3678 @@ impl Bridge {
 
         Ok(Self {
             config,
+            config_path: None,
             register_store,
         })
     }
 
-    /// Run the bridge
+    /// Configuration file re-read by `POST /api/config/reload`
+    ///
+    /// Without it, reload requests are rejected.
+    pub fn with_config_path(mut self, path: impl Into<String>) -> Self {
+        self.config_path = Some(path.into());
+        self
+    }
+
+    /// Run the bridge until Ctrl+C or SIGTERM, then shut down gracefully
     pub async fn run(self) -> Result<()> {
+        let mut handle = self.start().await?;
+
+        let restart = tokio::select! {
+            result = &mut handle.server => {
+                handle.tasks.shutdown().await;
+                return result.map_err(server_failed)?;
+            }
+            Some(exe) = handle.restart_rx.recv() => Some(exe),
+            _ = shutdown_signal() => None,
+        };
+
+        match restart {
+            Some(exe) => {
+                info!("Shutting down to restart into {}", exe.display());
+                handle.shutdown().await?;
+                Err(update::restart(&exe))
+            }
+            None => {
+                info!("Shutting down");
+                handle.shutdown().await
+            }
+        }
+    }
+
+    /// Start all subsystems in the background and return a handle to control them
+    pub async fn start(self) -> Result<BridgeHandle> {
+        let started_at = Instant::now();
+        let mut tasks = JoinSet::new();
+
         // Create write request channel
-        let (write_tx, mut write_rx) = tokio::sync::mpsc::channel::<WriteRequest>(100);
+        let (write_tx, write_rx) = tokio::sync::mpsc::channel::<WriteRequest>(100);
 
         // Initialize Prometheus metrics if enabled
-        let api_state = if self.config.server.metrics_enabled {
+        let mut api_state = if self.config.server.metrics_enabled {
             let metrics_handle = metrics::init_metrics();
             info!("Prometheus metrics enabled at /metrics");
             ApiState::with_metrics(self.register_store.clone(), write_tx, metrics_handle)
         } else {
             ApiState::new(self.register_store.clone(), write_tx)
         };
+        api_state.error_log = ErrorLog::with_max_bytes(
+            self.config.server.error_log_size,
+            self.config.server.error_log_max_bytes,
+        );
+        api_state.audit_log = AuditLog::open(&self.config.audit)
+            .map_err(|e| RustbridgeError::Config(format!("{:#}", e)))?;
+        api_state.controls = DeviceControls::new(&self.config.devices);
+        metrics::set_device_tenants(&self.config.devices);
+        metrics::gauges::configure(&self.config.server.register_gauges, &self.config.devices);
+        api_state.update_tx = broadcast::channel(self.config.server.update_channel_capacity).0;
+
+        // The persisted store is newer than a preload file, so it goes first
+        if let Some(path) = &self.config.snapshot.persist {
+            self.preload_snapshot(path).await?;
+        }
+        if let Some(path) = &self.config.snapshot.preload {
+            self.preload_snapshot(path).await?;
+        }
+        if let Some(path) = self.config.snapshot.persist.clone() {
+            let store = self.register_store.clone();
+            let every = Duration::from_secs(self.config.snapshot.persist_interval_secs);
+            tasks.spawn(async move {
+                let mut ticker = tokio::time::interval(every);
+                ticker.tick().await;
+                loop {
+                    ticker.tick().await;
+                    persist_snapshot(&store, &path).await;
+                }
+            });
+        }
 
         // Clone for the polling tasks to broadcast updates
         let update_broadcaster = api_state.update_tx.clone();
 
-        // Start MQTT publisher if enabled
-        if self.config.mqtt.enabled {
-            let mqtt_publisher = Arc::new(MqttPublisher::new(&self.config.mqtt).await?);
-            let mqtt_rx = api_state.subscribe();
+        // Writes resolve registers against the configuration in effect
+        let (config_tx, config_rx) = watch::channel(self.config.clone());
 
-            // Spawn MQTT publishing loop
-            tokio::spawn(async move {
-                mqtt_publisher.start_publishing(mqtt_rx).await;
+        // Check for new releases; installing one or rolling it back restarts
+        // the bridge from `run`
+        let (restart_tx, restart_rx) = tokio::sync::mpsc::channel(1);
+        if let Some(update) = &self.config.update {
+            let (status_tx, status_rx) = watch::channel(UpdateStatus::default());
+            let checker = UpdateChecker::new(update.clone(), status_tx)?;
+            api_state.update_status = Some(status_rx);
+            tasks.spawn(checker.run(api_state.diagnostics.clone(), restart_tx));
+        }
+
+        // Poll statistics only go to MQTT
+        let stats_tx = self
+            .config
+            .mqtt_enabled()
+            .filter(|mqtt| mqtt.stats_every_cycles > 0)
+            .map(|mqtt| {
+                (
+                    mqtt.stats_every_cycles,
+                    broadcast::channel(STATS_CAPACITY).0,
+                )
             });
+        let stats_rx = stats_tx.as_ref().map(|(_, tx)| tx.subscribe());
 
-            info!(
-                "MQTT publishing enabled: {}:{}/{}",
-                self.config.mqtt.host, self.config.mqtt.port, self.config.mqtt.topic_prefix
-            );
-        } else {
-            info!("MQTT publishing disabled");
+        // Start MQTT publisher if enabled
+        let blocking_sinks: Vec<BlockingSink> = self
+            .start_mqtt(
+                &mut api_state,
+                &mut tasks,
+                started_at,
+                config_rx.clone(),
+                stats_rx,
+            )
+            .await?
+            .into_iter()
+            .collect();
+
+        // Report readiness and watchdog keepalives to systemd (no-op outside Type=notify)
+        let heartbeats = Heartbeats::default();
+        {
+            let config = self.config.clone();
+            let events = api_state.subscribe_events();
+            let heartbeats = heartbeats.clone();
+            tasks.spawn(async move {
+                systemd::run_notifier(&config, events, heartbeats).await;
+            });
         }
 
+        // Watch for serial ports being opened before dropping privileges
+        let serial_events = self
+            .config
+            .daemon
+            .user
+            .is_some()
+            .then(|| api_state.subscribe_events());
+
         // Start polling for each device with WebSocket broadcast
-        for device in &self.config.devices {
-            let store = self.register_store.clone();
-            let device_config = device.clone();
-            let broadcaster = update_broadcaster.clone();
+        let polling_context = PollingContext {
+            store: self.register_store.clone(),
+            broadcaster: update_broadcaster.clone(),
+            blocking_sinks,
+            diagnostics: api_state.diagnostics.clone(),
+            error_log: api_state.error_log.clone(),
+            events: api_state.event_tx.clone(),
+            controls: api_state.controls.clone(),
+            heartbeats,
+            writers: DeviceWriters::default(),
+            names: NameCache::default(),
+            debounce: Debounce::default(),
+            stats: stats_tx.map(|(every, tx)| StatsSink { every, tx }),
+        };
+        let writers = polling_context.writers.clone();
 
-            tokio::spawn(async move {
-                if let Err(e) =
-                    start_polling_with_broadcast(device_config, store, broadcaster).await
-                {
-                    tracing::error!("Polling error: {}", e);
-                }
-            });
+        let mut pollers = DevicePollers::new(polling_context);
+        for device in &self.config.devices {
+            pollers.spawn(device.clone());
+        }
+        let (reload_tx, reload_rx) = tokio::sync::mpsc::channel::<ReloadRequest>(4);
+        if let Some(remote) = &self.config.remote_config {
+            let puller = ConfigPuller::new(remote.clone())?;
+            let (pull_tx, pull_rx) = tokio::sync::mpsc::channel(4);
+            api_state.config_pull_tx = Some(pull_tx);
+            let reload_tx = reload_tx.clone();
+            let config_rx = config_rx.clone();
+            tasks.spawn(puller.run(reload_tx, config_rx, pull_rx));
         }
+        api_state.reload_tx = Some(reload_tx);
+        api_state.config_rx = Some(config_rx.clone());
+        let config_path = self.config_path.clone();
+        tasks.spawn(pollers.supervise(config_tx, config_path, reload_rx));
 
         // Spawn write request handler
-        tokio::spawn(async move {
-            while let Some(request) = write_rx.recv().await {
-                // For now, acknowledge the write request
-                // In production, this would forward to the actual Modbus client
-                let _ = request.response_tx.send(Ok(()));
-                info!(
-                    "Write request received: {}@{} = {}",
-                    request.device_id, request.address, request.value
-                );
+        let write_handler = WriteHandler {
+            gate: WriteGate::new(self.register_store.clone()),
+            writers,
+            audit_log: api_state.audit_log.clone(),
+            events: api_state.event_tx.clone(),
+        };
+        api_state
+            .writes_enabled
+            .store(self.config.writes.enabled, Ordering::Relaxed);
+        let writes_enabled = api_state.writes_enabled.clone();
+        tasks.spawn(write_handler.run(write_rx, config_rx, writes_enabled));
+
+        // Bind the API server
+        let update_tx = api_state.update_tx.clone();
+        let event_tx = api_state.event_tx.clone();
+        let controls = api_state.controls.clone();
+        let api_server = ApiServer::bind(&self.config, api_state).await?;
+        let local_addr = api_server.local_addr();
+
+        // Drop root once the API port is bound and serial ports have been opened
+        if let (Some(user), Some(events)) = (&self.config.daemon.user, serial_events) {
+            wait_for_serial_ports(&self.config, events).await;
+            daemon::drop_privileges(user, self.config.daemon.group.as_deref())
+                .map_err(|e| RustbridgeError::Config(format!("{:#}", e)))?;
+            info!("Dropped privileges to user {}", user);
+        }
+
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+        let server = api_server.serve(shutdown_rx);
+
+        Ok(BridgeHandle {
+            local_addr,
+            persist: self.config.snapshot.persist.clone(),
+            register_store: self.register_store,
+            update_tx,
+            event_tx,
+            controls,
+            shutdown_tx,
+            server,
+            tasks,
+            restart_rx,
+        })
+    }
+
+    /// Seed the register store from a snapshot file
+    ///
+    /// A missing file is not an error, so the bridge can start before the first
+    /// snapshot has been taken.
+    async fn preload_snapshot(&self, path: &str) -> Result<()> {
+        if !std::path::Path::new(path).exists() {
+            tracing::warn!("Snapshot {} not found, starting with an empty store", path);
+            return Ok(());
+        }
+        let snapshot = Snapshot::load(path)?;
+        let restored = snapshot
+            .preload(&self.register_store, &self.config.devices)
+            .await;
+        info!(
+            "Preloaded {} register values from snapshot {}",
+            restored, path
+        );
+        Ok(())
+    }
+
+    /// Spawn the MQTT publishing tasks, if MQTT is configured
+    ///
+    /// Returns the sink the pollers have to feed when MQTT is set to
+    /// `backpressure: block`.
+    #[cfg(feature = "mqtt")]
+    async fn start_mqtt(
+        &self,
+        api_state: &mut ApiState,
+        tasks: &mut JoinSet<()>,
+        started_at: Instant,
+        config_rx: watch::Receiver<Config>,
+        stats_rx: Option<broadcast::Receiver<CycleStats>>,
+    ) -> Result<Option<BlockingSink>> {
+        let Some(mqtt) = self.config.mqtt_enabled() else {
+            info!("MQTT publishing disabled");
+            return Ok(None);
+        };
+
+        let mqtt_publisher = Arc::new(
+            MqttPublisher::new(mqtt, api_state.error_log.clone())
+                .await?
+                .with_controls(api_state.controls.clone()),
+        );
+        api_state.mqtt_connected = Some(mqtt_publisher.connection_flag());
+        mqtt_publisher.prepare_topics(&self.config.devices);
+        let (mqtt_rx, blocking_sink) = match mqtt.backpressure {
+            Backpressure::Drop => (UpdateReceiver::Lossy(api_state.subscribe()), None),
+            Backpressure::Block => {
+                let (sink, rx) =
+                    BlockingSink::channel("mqtt", self.config.server.update_channel_capacity);
+                (rx, Some(sink))
             }
+        };
+        let mqtt_event_rx = api_state.subscribe_events();
+
+        // Spawn MQTT heartbeat loop
+        if mqtt.heartbeat_interval_secs > 0 {
+            let heartbeat_publisher = mqtt_publisher.clone();
+            let interval = Duration::from_secs(mqtt.heartbeat_interval_secs);
+            let diagnostics = api_state.diagnostics.clone();
+            let devices_configured = self.config.devices.len();
+            tasks.spawn(async move {
+                heartbeat_publisher
+                    .start_heartbeat(interval, diagnostics, devices_configured, started_at)
+                    .await;
+            });
+        }
+
+        // Spawn the retained bridge info, republished on every reload
+        let info_publisher = mqtt_publisher.clone();
+        let info_config_rx = config_rx.clone();
+        tasks.spawn(async move {
+            info_publisher.start_info_publishing(info_config_rx).await;
+        });
+
+        // Spawn the retained result of release checks
+        if let Some(status_rx) = api_state.update_status.clone() {
+            let update_publisher = mqtt_publisher.clone();
+            tasks.spawn(async move {
+                update_publisher.start_update_publishing(status_rx).await;
+            });
+        }
+
+        // Spawn the per-device poll statistics
+        if let Some(stats_rx) = stats_rx {
+            let stats_publisher = mqtt_publisher.clone();
+            tasks.spawn(async move {
+                stats_publisher.start_stats_publishing(stats_rx).await;
+            });
+        }
+
+        // Spawn MQTT connection event loop
+        let event_publisher = mqtt_publisher.clone();
+        tasks.spawn(async move {
+            event_publisher.start_event_publishing(mqtt_event_rx).await;
+        });
+
+        // Spawn group status publishing and group command handling (groups may
+        // appear on a config reload, so these run even without groups)
+        let group_publisher = mqtt_publisher.clone();
+        let controls = api_state.controls.clone();
+        let group_event_rx = api_state.subscribe_events();
+        tasks.spawn(async move {
+            group_publisher
+                .start_group_status_publishing(controls, group_event_rx)
+                .await;
+        });
+
+        let command_publisher = mqtt_publisher.clone();
+        let controls = api_state.controls.clone();
+        tasks.spawn(async move {
+            command_publisher.start_command_handling(controls).await;
+        });
+
+        if mqtt.discovery.enabled {
+            let discovery_publisher = mqtt_publisher.clone();
+            let discovery = mqtt.discovery.clone();
+            let accept_writes = mqtt.accept_writes;
+            let config_rx = config_rx.clone();
+            tasks.spawn(async move {
+                discovery_publisher
+                    .start_discovery_publishing(discovery, accept_writes, config_rx)
+                    .await;
+            });
+        }
+        if mqtt.accept_writes {
+            let set_publisher = mqtt_publisher.clone();
+            let config_rx = config_rx.clone();
+            let write_tx = api_state.write_tx.clone();
+            tasks.spawn(async move {
+                set_publisher.start_set_handling(config_rx, write_tx).await;
+            });
+        }
+        if mqtt.rpc_enabled {
+            let rpc_publisher = mqtt_publisher.clone();
+            let context = RpcContext {
+                config_rx: config_rx.clone(),
+                register_store: self.register_store.clone(),
+                controls: api_state.controls.clone(),
+                write_tx: api_state.write_tx.clone(),
+                source: WriteSource::Mqtt {
+                    topic: rpc_request_topic(&mqtt.topic_prefix),
+                },
+            };
+            tasks.spawn(async move {
+                rpc_publisher.start_rpc_handling(context).await;
+            });
+        }
+
+        if let Some(dead_man) = mqtt.dead_man.clone() {
+            let dead_man_publisher = mqtt_publisher.clone();
+            let write_tx = api_state.write_tx.clone();
+            tasks.spawn(async move {
+                dead_man_publisher.start_dead_man(dead_man, write_tx).await;
+            });
+        }
+
+        // Spawn MQTT publishing loop
+        tasks.spawn(async move {
+            mqtt_publisher.start_publishing(mqtt_rx, config_rx).await;
         });
 
-        // Start API server
-        let app = api::create_router(api_state, self.config.auth.clone());
+        info!(
+            "MQTT publishing enabled: {}:{}/{}",
+            mqtt.host, mqtt.port, mqtt.topic_prefix
+        );
+        Ok(blocking_sink)
+    }
+
+    #[cfg(not(feature = "mqtt"))]
+    async fn start_mqtt(
+        &self,
+        _api_state: &mut ApiState,
+        _tasks: &mut JoinSet<()>,
+        _started_at: Instant,
+        _config_rx: watch::Receiver<Config>,
+        _stats_rx: Option<broadcast::Receiver<CycleStats>>,
+    ) -> Result<Option<BlockingSink>> {
+        if self.config.mqtt_enabled().is_some() {
+            tracing::warn!("MQTT is enabled in the config but support is not compiled in (enable the `mqtt` feature)");
+        }
+        Ok(None)
+    }
+}
+
+/// API server with its port bound but not yet serving
+#[cfg(feature = "http-api")]
+struct ApiServer {
+    listener: tokio::net::TcpListener,
+    app: axum::Router,
+}
+
+#[cfg(feature = "http-api")]
+impl ApiServer {
+    async fn bind(config: &Config, api_state: ApiState) -> Result<Self> {
+        let app = api::create_router(api_state, config.auth.clone());
 
         // Log authentication status
-        if self.config.auth.enabled {
+        if config.auth.enabled {
             info!(
                 "API authentication enabled with {} API key(s)",
-                self.config.auth.api_keys.len()
+                config.auth.api_keys.len()
             );
         } else {
             info!("API authentication disabled (open access)");
         }
 
-        let addr: SocketAddr =
-            format!("{}:{}", self.config.server.host, self.config.server.port).parse()?;
+        let addr: SocketAddr = format!("{}:{}", config.server.host, config.server.port)
+            .parse()
+            .map_err(|e| RustbridgeError::Config(format!("Invalid server address: {}", e)))?;
 
-        info!("Starting API server on http://{}", addr);
-        info!("  - Health check: http://{}/health", addr);
-        info!("  - API info:     http://{}/api/info", addr);
-        info!("  - Devices:      http://{}/api/devices", addr);
-        info!("  - WebSocket:    ws://{}/ws", addr);
-        if self.config.server.metrics_enabled {
-            info!("  - Metrics:      http://{}/metrics", addr);
+        let listener = tokio::net::TcpListener::bind(addr).await.map_err(|e| {
+            RustbridgeError::Connection(format!("Failed to bind API server to {}: {}", addr, e))
+        })?;
+        let local_addr = listener
+            .local_addr()
+            .map_err(|e| RustbridgeError::Connection(e.to_string()))?;
+
+        info!("Starting API server on http://{}", local_addr);
+        info!("  - Health check: http://{}/health", local_addr);
+        info!("  - API info:     http://{}/api/info", local_addr);
+        info!("  - Devices:      http://{}/api/devices", local_addr);
+        info!("  - WebSocket:    ws://{}/ws", local_addr);
+        if config.server.metrics_enabled {
+            info!("  - Metrics:      http://{}/metrics", local_addr);
         }
 
-        let listener = tokio::net::TcpListener::bind(addr).await?;
-        axum::serve(listener, app).await?;
+        Ok(Self { listener, app })
+    }
+
+    fn local_addr(&self) -> Option<SocketAddr> {
+        self.listener.local_addr().ok()
+    }
 
-        Ok(())
+    fn serve(self, mut shutdown_rx: watch::Receiver<bool>) -> JoinHandle<Result<()>> {
+        tokio::spawn(async move {
+            axum::serve(self.listener, self.app)
+                .with_graceful_shutdown(async move {
+                    let _ = shutdown_rx.wait_for(|stop| *stop).await;
+                })
+                .await
+                .map_err(|e| RustbridgeError::Connection(format!("API server failed: {}", e)))
+        })
     }
 }
 
-/// Start polling with WebSocket broadcast support and metrics
-async fn start_polling_with_broadcast(
-    config: crate::config::DeviceConfig,
-    store: RegisterStore,
-    broadcaster: tokio::sync::broadcast::Sender<RegisterUpdate>,
-) -> Result<()> {
-    use crate::modbus::ModbusClient;
-    use tokio::time::{interval, Duration};
+/// Placeholder when the HTTP API is not compiled in
+#[cfg(not(feature = "http-api"))]
+struct ApiServer;
 
-    let mut client = ModbusClient::new(&config).await?;
-    let device_id = config.id.clone();
-    let poll_interval = Duration::from_millis(config.poll_interval_ms);
+#[cfg(not(feature = "http-api"))]
+impl ApiServer {
+    async fn bind(_config: &Config, _api_state: ApiState) -> Result<Self> {
+        info!("HTTP API not compiled in (enable the `http-api` feature)");
+        Ok(Self)
+    }
 
-    info!(
-        "Starting polling for device {} every {}ms",
-        device_id, config.poll_interval_ms
-    );
+    fn local_addr(&self) -> Option<SocketAddr> {
+        None
+    }
 
-    // Record device as connected
-    metrics::record_device_status(&device_id, true);
+    /// Stand-in server task that finishes on shutdown, so `Bridge::run` behaves the same
+    fn serve(self, mut shutdown_rx: watch::Receiver<bool>) -> JoinHandle<Result<()>> {
+        tokio::spawn(async move {
+            let _ = shutdown_rx.wait_for(|stop| *stop).await;
+            Ok(())
+        })
+    }
+}
 
-    let mut ticker = interval(poll_interval);
+/// Handle to a started [`Bridge`]
+///
+/// Dropping the handle without calling [`BridgeHandle::shutdown`] stops the
+/// polling and publishing tasks but leaves the API server running.
+pub struct BridgeHandle {
+    local_addr: Option<SocketAddr>,
+    /// Snapshot file the store is saved to on shutdown
+    persist: Option<String>,
+    register_store: RegisterStore,
+    update_tx: broadcast::Sender<RegisterUpdate>,
+    event_tx: EventSender,
+    controls: DeviceControls,
+    shutdown_tx: watch::Sender<bool>,
+    server: JoinHandle<Result<()>>,
+    tasks: JoinSet<()>,
+    /// Binary to execute after an update was installed or rolled back
+    restart_rx: tokio::sync::mpsc::Receiver<PathBuf>,
+}
 
-    loop {
-        ticker.tick().await;
-        let cycle_start = Instant::now();
+impl BridgeHandle {
+    /// Address the API server is listening on (useful with `server.port: 0`),
+    /// or `None` when built without the `http-api` feature
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.local_addr
+    }
 
-        for register in &config.registers {
-            // Start metrics timing
-            let read_metrics = ReadMetrics::start(&device_id, &register.name);
+    /// Latest register values, keyed by device ID and register name
+    pub fn register_store(&self) -> RegisterStore {
+        self.register_store.clone()
+    }
 
-            match client.read_registers(register).await {
-                Ok(raw_values) => {
-                    let value = reader::convert_value(&raw_values, register);
+    /// Stream of every register update as it is polled
+    ///
+    /// Updates missed by a slow consumer are skipped; the stream ends when
+    /// the bridge shuts down.
+    pub fn updates(&self) -> impl Stream<Item = RegisterUpdate> + Send + Unpin + 'static {
+        update_stream(self.update_tx.subscribe())
+    }
 
-                    // Record successful read metrics
-                    read_metrics.success(value);
+    /// Stream of updates for a single register
+    pub fn subscribe(
+        &self,
+        device_id: &str,
+        register_name: &str,
+    ) -> impl Stream<Item = RegisterUpdate> + Send + Unpin + 'static {
+        let device_id = device_id.to_string();
+        let register_name = register_name.to_string();
+        self.updates().filter(move |update| {
+            std::future::ready(
+                *update.device_id == *device_id && *update.register_name == *register_name,
+            )
+        })
+    }
 
-                    let reg_value = RegisterValue {
-                        name: register.name.clone(),
-                        raw: raw_values.clone(),
-                        value,
-                        unit: register.unit.clone(),
-                        timestamp: chrono::Utc::now(),
-                    };
+    /// Stream of updates for all registers of a device
+    pub fn subscribe_device(
+        &self,
+        device_id: &str,
+    ) -> impl Stream<Item = RegisterUpdate> + Send + Unpin + 'static {
+        let device_id = device_id.to_string();
+        self.updates()
+            .filter(move |update| std::future::ready(*update.device_id == *device_id))
+    }
 
-                    // Store the value
-                    {
-                        let mut store = store.write().await;
-                        let device_map =
-                            store.entry(device_id.clone()).or_insert_with(HashMap::new);
-                        device_map.insert(register.name.clone(), reg_value.clone());
-                    }
-
-                    // Broadcast to WebSocket clients (and MQTT if enabled)
-                    let update = RegisterUpdate {
-                        device_id: device_id.clone(),
-                        register_name: register.name.clone(),
-                        value: reg_value.value,
-                        raw: reg_value.raw,
-                        unit: reg_value.unit,
-                        timestamp: reg_value.timestamp.to_rfc3339(),
-                    };
-                    let _ = broadcaster.send(update);
+    /// Receive device connection lifecycle events
+    pub fn subscribe_events(&self) -> broadcast::Receiver<ConnectionEvent> {
+        self.event_tx.subscribe()
+    }
 
-                    tracing::debug!(
-                        "Device {} register {} = {} {:?}",
-                        device_id,
-                        register.name,
-                        value,
-                        register.unit
-                    );
+    /// Pause, resume or trigger polls of devices and groups
+    pub fn controls(&self) -> DeviceControls {
+        self.controls.clone()
+    }
+
+    /// Stop polling and publishing, let in-flight API requests finish, then return
+    pub async fn shutdown(mut self) -> Result<()> {
+        self.tasks.shutdown().await;
+        if let Some(path) = &self.persist {
+            persist_snapshot(&self.register_store, path).await;
+        }
+        let _ = self.shutdown_tx.send(true);
+        match tokio::time::timeout(SHUTDOWN_TIMEOUT, &mut self.server).await {
+            Ok(result) => result.map_err(server_failed)?,
+            Err(_) => {
+                self.server.abort();
+                Ok(())
+            }
+        }
+    }
+}
+
+/// The API server task panicked or was cancelled
+fn server_failed(error: tokio::task::JoinError) -> RustbridgeError {
+    RustbridgeError::Connection(format!("API server task failed: {}", error))
+}
+
+/// Save the register store to the `snapshot.persist` file, logging failures
+async fn persist_snapshot(store: &RegisterStore, path: &str) {
+    match Snapshot::capture(store).await.save(path) {
+        Ok(()) => tracing::debug!("Saved register values to {}", path),
+        Err(e) => tracing::warn!("Failed to persist register values: {}", e),
+    }
+}
+
+/// Turn a broadcast receiver into a stream, skipping updates lost to lag
+fn update_stream(
+    rx: broadcast::Receiver<RegisterUpdate>,
+) -> impl Stream<Item = RegisterUpdate> + Send + Unpin + 'static {
+    futures_util::stream::unfold(rx, |mut rx| async move {
+        loop {
+            match rx.recv().await {
+                Ok(update) => return Some((update, rx)),
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    crate::api::record_lag("stream", skipped);
                 }
-                Err(e) => {
-                    // Record failed read metrics
-                    read_metrics.failure("modbus_error");
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    })
+    .boxed()
+}
 
-                    tracing::error!(
-                        "Failed to read register {} from {}: {}",
-                        register.name,
-                        device_id,
-                        e
+/// Statistics messages buffered for the MQTT publisher
+const STATS_CAPACITY: usize = 256;
+
+/// Longest wait for the API server to finish in-flight requests on shutdown
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Resolve on Ctrl+C or, on Unix, SIGTERM
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        if let Ok(mut sigterm) = signal(SignalKind::terminate()) {
+            tokio::select! {
+                _ = tokio::signal::ctrl_c() => {}
+                _ = sigterm.recv() => {}
+            }
+            return;
+        }
+    }
+    let _ = tokio::signal::ctrl_c().await;
+}
+
+/// Longest wait for serial ports to open before dropping privileges anyway
+const SERIAL_OPEN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Wait until every RTU device has made its first connection attempt
+async fn wait_for_serial_ports(
+    config: &Config,
+    mut events: tokio::sync::broadcast::Receiver<ConnectionEvent>,
+) {
+    let mut pending: Vec<&str> = config
+        .devices
+        .iter()
+        .filter(|d| matches!(d.connection, ConnectionConfig::Rtu(_)))
+        .map(|d| d.id.as_str())
+        .collect();
+
+    let deadline = tokio::time::Instant::now() + SERIAL_OPEN_TIMEOUT;
+    while !pending.is_empty() {
+        match tokio::time::timeout_at(deadline, events.recv()).await {
+            Ok(Ok(event)) => {
+                if matches!(
+                    event.event,
+                    ConnectionEventKind::Connected | ConnectionEventKind::SerialPortError
+                ) {
+                    pending.retain(|id| *id != event.device_id);
+                }
+            }
+            Ok(Err(tokio::sync::broadcast::error::RecvError::Lagged(_))) => continue,
+            Ok(Err(_)) | Err(_) => break,
+        }
+    }
+}
+
+/// Polling tasks of the configured devices, restarted one by one on reload
+struct DevicePollers {
+    ctx: PollingContext,
+    tasks: JoinSet<()>,
+    running: HashMap<String, AbortHandle>,
+}
+
+impl DevicePollers {
+    fn new(ctx: PollingContext) -> Self {
+        Self {
+            ctx,
+            tasks: JoinSet::new(),
+            running: HashMap::new(),
+        }
+    }
+
+    /// Start polling a device
+    fn spawn(&mut self, device_config: crate::config::DeviceConfig) {
+        let device_id = device_config.id.clone();
+        let ctx = self.ctx.clone();
+        let span = tracing::info_span!("device", device_id = %device_id);
+        let (write_tx, write_rx) = tokio::sync::mpsc::channel(16);
+        ctx.writers.insert(&device_id, write_tx);
+
+        let handle = self.tasks.spawn(
+            async move {
+                let device_id = device_config.id.clone();
+                let error_log = ctx.error_log.clone();
+                if let Err(e) = start_polling_with_broadcast(device_config, ctx, write_rx).await {
+                    error_log.record(
+                        ErrorEvent::new(
+                            ErrorSource::Modbus,
+                            ErrorKind::classify_modbus(&format!("{:#}", e)),
+                            format!("{:#}", e),
+                        )
+                        .device(device_id),
                     );
+                    tracing::error!(error = %e, "Polling error: {}", e);
                 }
             }
+            .instrument(span),
+        );
+        self.running.insert(device_id, handle);
+    }
+
+    /// Stop polling a device (closing its connection)
+    fn stop(&mut self, device_id: &str) {
+        if let Some(handle) = self.running.remove(device_id) {
+            handle.abort();
         }
+        self.ctx.writers.remove(device_id);
+    }
 
-        // Record poll cycle duration
-        let cycle_duration = cycle_start.elapsed().as_millis() as u64;
-        metrics::record_poll_cycle(&device_id, cycle_duration);
+    /// Keep the pollers running and apply reload requests until the bridge stops
+    async fn supervise(
+        mut self,
+        config_tx: watch::Sender<Config>,
+        config_path: Option<String>,
+        mut reload_rx: tokio::sync::mpsc::Receiver<ReloadRequest>,
+    ) {
+        let mut config = config_tx.borrow().clone();
+        loop {
+            tokio::select! {
+                request = reload_rx.recv() => {
+                    let Some(request) = request else { break };
+                    let result = match config_path.as_deref() {
+                        Some(path) => self.apply(&mut config, path, request.action).await,
+                        None => Err(RustbridgeError::Config(
+                            "the bridge was started without a configuration file".to_string(),
+                        )),
+                    };
+                    if result.is_ok() {
+                        config_tx.send_replace(config.clone());
+                    }
+                    let _ = request.response_tx.send(result);
+                }
+                Some(_) = self.tasks.join_next(), if !self.tasks.is_empty() => {}
+            }
+        }
+        while self.tasks.join_next().await.is_some() {}
+    }
+
+    /// Update the configuration file as `action` asks, then reload it
+    ///
+    /// A written configuration that fails to reload is rolled back on disk.
+    async fn apply(
+        &mut self,
+        config: &mut Config,
+        path: &str,
+        action: ReloadAction,
+    ) -> Result<ReloadReport> {
+        match action {
+            ReloadAction::Reload => self.reload(config, path).await,
+            ReloadAction::Apply(new_config) => {
+                new_config.validate()?;
+                reload::write_config(path, &new_config)?;
+                let result = self.reload(config, path).await;
+                if result.is_err() {
+                    reload::restore_backup(path)?;
+                }
+                result
+            }
+            ReloadAction::Rollback => {
+                reload::restore_backup(path)?;
+                self.reload(config, path).await
+            }
+        }
+    }
+
+    /// Re-read and validate the configuration file, then apply its device changes
+    ///
+    /// Nothing is touched unless the new configuration is valid.
+    async fn reload(&mut self, config: &mut Config, path: &str) -> Result<ReloadReport> {
+        if !std::path::Path::new(path).exists() {
+            return Err(RustbridgeError::Config(format!(
+                "Config file {} not found",
+                path
+            )));
+        }
+        // Resolving may clone device profiles from git
+        let owned_path = path.to_string();
+        let new_config =
+            tokio::task::spawn_blocking(move || crate::config::load_config(&owned_path))
+                .await
+                .map_err(|e| RustbridgeError::Config(e.to_string()))??;
+        new_config.validate()?;
+        let report = ReloadReport::diff(config, &new_config);
+
+        let restarted: Vec<&str> = report
+            .devices_removed
+            .iter()
+            .chain(report.devices_changed.iter().map(|c| &c.device_id))
+            .map(String::as_str)
+            .collect();
+        for device_id in &restarted {
+            self.stop(device_id);
+        }
+
+        // Drop values and statistics that no longer match a configured register
+        {
+            let mut store = self.ctx.store.write().await;
+            let mut diagnostics = self.ctx.diagnostics.write().await;
+            for device_id in &report.devices_removed {
+                store.remove(device_id);
+                diagnostics.remove(device_id);
+                self.ctx.heartbeats.forget(device_id);
+                self.ctx.names.forget(device_id);
+                self.ctx.debounce.forget(device_id);
+            }
+            for change in &report.devices_changed {
+                if let Some(values) = store.get_mut(&change.device_id) {
+                    for name in change
+                        .registers_removed
+                        .iter()
+                        .chain(&change.registers_changed)
+                    {
+                        values.remove(name);
+                    }
+                }
+            }
+        }
+
+        self.ctx.controls.reconfigure(&new_config.devices);
+        metrics::set_device_tenants(&new_config.devices);
+        metrics::gauges::configure(&new_config.server.register_gauges, &new_config.devices);
+        for device in &new_config.devices {
+            if report.devices_added.contains(&device.id) || restarted.contains(&device.id.as_str())
+            {
+                self.spawn(device.clone());
+            }
+        }
+
+        info!(
+            "Configuration reloaded: {} added, {} removed, {} changed{}",
+            report.devices_added.len(),
+            report.devices_removed.len(),
+            report.devices_changed.len(),
+            if report.restart_required.is_empty() {
+                String::new()
+            } else {
+                format!(" ({} need a restart)", report.restart_required.join(", "))
+            }
+        );
+        *config = new_config;
+        Ok(report)
+    }
+}
+
+/// Find the register a write targets and encode the value for it
+fn resolve_write(
+    config: &Config,
+    device_id: &str,
+    register_name: &str,
+    value: f64,
+) -> std::result::Result<(RegisterConfig, WriteOutcome), WriteError> {
+    let device = config
+        .devices
+        .iter()
+        .find(|d| d.id == device_id)
+        .ok_or_else(|| WriteError::Rejected(format!("Device '{}' not found", device_id)))?;
+    if matches!(&device.connection, ConnectionConfig::Rtu(rtu) if rtu.listen_only) {
+        return Err(WriteError::Rejected(format!(
+            "Device '{}' is listen-only",
+            device_id
+        )));
+    }
+    let register = device
+        .registers
+        .iter()
+        .find(|r| r.name == register_name)
+        .ok_or_else(|| WriteError::Rejected(format!("Register '{}' not found", register_name)))?;
+
+    match register.register_type {
+        RegisterType::Holding | RegisterType::Coil => {}
+        RegisterType::Input => {
+            return Err(WriteError::Rejected(format!(
+                "Register '{}' is read-only (input register)",
+                register_name
+            )))
+        }
+        RegisterType::Discrete => {
+            return Err(WriteError::Rejected(format!(
+                "Register '{}' is read-only (discrete input)",
+                register_name
+            )))
+        }
+        RegisterType::ExceptionStatus => {
+            return Err(WriteError::Rejected(format!(
+                "Register '{}' is read-only (exception status)",
+                register_name
+            )))
+        }
+        RegisterType::Fifo => {
+            return Err(WriteError::Rejected(format!(
+                "Register '{}' is read-only (FIFO queue)",
+                register_name
+            )))
+        }
+    }
+
+    if let Some(limits) = register
+        .write_limits
+        .as_ref()
+        .filter(|limits| !limits.allows(value))
+    {
+        metrics::record_write_blocked(device_id, register_name, "range");
+        if !limits.values.is_empty() && !limits.values.contains(&value) {
+            let values: Vec<String> = limits.values.iter().map(f64::to_string).collect();
+            return Err(WriteError::Rejected(format!(
+                "Value {} for '{}' is not one of the allowed values {}",
+                value,
+                register_name,
+                values.join(", ")
+            )));
+        }
+        return Err(WriteError::Rejected(format!(
+            "Value {} for '{}' is outside the allowed range {}..{}",
+            value,
+            register_name,
+            limits.min.map_or(String::new(), |min| min.to_string()),
+            limits.max.map_or(String::new(), |max| max.to_string()),
+        )));
+    }
+
+    let raw = codec::encode_value(value, register).map_err(WriteError::Rejected)?;
+    Ok((
+        register.clone(),
+        WriteOutcome {
+            address: register.address,
+            raw,
+            read_back: None,
+        },
+    ))
+}
+
+/// Hand an encoded write to the polling task of its device and wait for the result
+async fn send_device_write(
+    writer: Option<tokio::sync::mpsc::Sender<DeviceWrite>>,
+    device_id: &str,
+    register: RegisterConfig,
+    outcome: WriteOutcome,
+) -> std::result::Result<WriteOutcome, WriteError> {
+    let not_polling = || WriteError::Failed(format!("Device '{}' is not polling", device_id));
+    let writer = writer.ok_or_else(not_polling)?;
+    let (response_tx, response_rx) = tokio::sync::oneshot::channel();
+    writer
+        .send(DeviceWrite {
+            register,
+            raw: outcome.raw.clone(),
+            response_tx,
+        })
+        .await
+        .map_err(|_| not_polling())?;
+
+    match response_rx.await {
+        Ok(Ok(read_back)) => Ok(WriteOutcome {
+            read_back,
+            ..outcome
+        }),
+        Ok(Err(RustbridgeError::Exception(exception))) => Err(WriteError::Exception(
+            ModbusException::new(exception, device_id, outcome.address),
+        )),
+        Ok(Err(e)) => Err(WriteError::Failed(e.to_string())),
+        Err(_) => Err(not_polling()),
+    }
+}
+
+/// An encoded write executed by the polling task that owns the device connection
+#[derive(Debug)]
+struct DeviceWrite {
+    register: RegisterConfig,
+    raw: Vec<u16>,
+    /// Value read back after the write, `None` if it could not be
+    response_tx: tokio::sync::oneshot::Sender<Result<Option<f64>>>,
+}
+
+impl DeviceWrite {
+    /// Write, then read the register back and publish what the device now
+    /// holds, so subscribers see the write confirmed
+    ///
+    /// A failed read-back does not fail the write; the next poll catches up.
+    async fn execute(
+        &self,
+        client: &mut crate::modbus::ModbusClient,
+        device: &DeviceConfig,
+        ctx: &PollingContext,
+    ) -> Result<Option<f64>> {
+        write_and_read_back(client, device, ctx, &self.register, &self.raw).await
+    }
+}
+
+/// Write `raw` to a register, then read it back and publish it
+async fn write_and_read_back(
+    client: &mut crate::modbus::ModbusClient,
+    device: &DeviceConfig,
+    ctx: &PollingContext,
+    register: &RegisterConfig,
+    raw: &[u16],
+) -> Result<Option<f64>> {
+    write_raw(client, &register.register_type, register.address, raw).await?;
+    // Nobody answers a broadcast, and a block publishes its points
+    if device.is_broadcast() || !register.points.is_empty() {
+        return Ok(None);
+    }
+    match client.read_registers(register).await {
+        Ok(raw) => Ok(ctx.publish_value(device, register, raw).await),
+        Err(e) => {
+            tracing::warn!(
+                device_id = %device.id,
+                register = %register.name,
+                "Failed to read back {} on {} after writing it: {}",
+                register.name,
+                device.id,
+                e
+            );
+            Ok(None)
+        }
+    }
+}
+
+/// Last commanded values of a device's `persistent` registers
+///
+/// The device counts as offline after losing the connection or a cycle in
+/// which every read failed. The values are written again after the first
+/// cycle with a successful read that follows, as a device that was powered
+/// off may have come back with its defaults. They live as long as the poller,
+/// so a configuration change of the device forgets them.
+#[derive(Debug, Default)]
+struct Setpoints {
+    values: HashMap<String, Vec<u16>>,
+    offline: bool,
+}
+
+impl Setpoints {
+    /// Remember a successful write
+    fn written(&mut self, register: &RegisterConfig, raw: &[u16]) {
+        if register.persistent {
+            self.values.insert(register.name.clone(), raw.to_vec());
+        }
+    }
+
+    /// Note that the connection is lost
+    fn lost(&mut self) {
+        self.offline = true;
+    }
+
+    /// Note the reads of a cycle, telling whether the setpoints are due
+    fn answered(&mut self, reads_ok: u32, reads_failed: u32) -> bool {
+        if reads_ok == 0 {
+            self.offline |= reads_failed > 0;
+            return false;
+        }
+        std::mem::take(&mut self.offline) && !self.values.is_empty()
+    }
+
+    /// Write the remembered values again, in configuration order
+    async fn restore(
+        &self,
+        client: &mut crate::modbus::ModbusClient,
+        device: &DeviceConfig,
+        ctx: &PollingContext,
+    ) -> Result<()> {
+        let mut restored = 0;
+        for register in &device.registers {
+            let Some(raw) = self.values.get(&register.name) else {
+                continue;
+            };
+            match write_and_read_back(client, device, ctx, register, raw).await {
+                Ok(_) => restored += 1,
+                Err(e) => {
+                    let message = format!("Failed to restore {}: {}", register.name, e);
+                    let kind = ErrorKind::classify(&e);
+                    ctx.error_log.record(
+                        ErrorEvent::new(ErrorSource::Modbus, kind, message.as_str())
+                            .device(device.id.as_str())
+                            .register(register.name.as_str(), register.address),
+                    );
+                    tracing::warn!(
+                        device_id = %device.id,
+                        register = %register.name,
+                        "Failed to restore setpoint {} on {}: {}",
+                        register.name,
+                        device.id,
+                        e
+                    );
+                    if matches!(kind, ErrorKind::Connection | ErrorKind::Transport) {
+                        return Err(e);
+                    }
+                }
+            }
+        }
+        info!(
+            device_id = %device.id,
+            "Restored {} setpoints on {} after it was offline",
+            restored,
+            device.id
+        );
+        Ok(())
+    }
+}
+
+/// Write a coil (FC05), a single register (FC06) or several registers (FC16)
+async fn write_raw(
+    client: &mut crate::modbus::ModbusClient,
+    register_type: &RegisterType,
+    address: u16,
+    raw: &[u16],
+) -> Result<()> {
+    match (register_type, raw) {
+        (RegisterType::Coil, [value]) => client.write_coil(address, *value != 0).await,
+        (RegisterType::Holding, [value]) => client.write_register(address, *value).await,
+        (RegisterType::Holding, values) => client.write_registers(address, values).await,
+        (register_type, values) => Err(RustbridgeError::Config(format!(
+            "Cannot write {} values to a {:?} register",
+            values.len(),
+            register_type
+        ))),
+    }
+}
+
+/// Write the value of a `trigger` register
+async fn write_trigger(
+    client: &mut crate::modbus::ModbusClient,
+    register: &RegisterConfig,
+    value: f64,
+) -> Result<()> {
+    let raw = codec::encode_value(value, register).map_err(RustbridgeError::Config)?;
+    write_raw(client, &register.register_type, register.address, &raw).await
+}
+
+/// Device ID, register name and source of a write
+type WriteKey = (String, String, WriteSource);
+
+/// Time of the last accepted write to each register, per source
+#[derive(Clone, Default)]
+struct WriteLimiter(Arc<std::sync::Mutex<HashMap<WriteKey, Instant>>>);
+
+impl WriteLimiter {
+    /// Reject a write that comes less than the register's `min_interval_ms`
+    /// after the last one recorded from the same source
+    fn check(
+        &self,
+        register: &RegisterConfig,
+        request: &WriteRequest,
+        now: Instant,
+    ) -> std::result::Result<(), WriteError> {
+        let Some(min_interval) = Self::min_interval(register) else {
+            return Ok(());
+        };
+        let last = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(previous) = last.get(&Self::key(request)) {
+            let elapsed = now.saturating_duration_since(*previous);
+            if elapsed < min_interval {
+                metrics::record_write_blocked(
+                    &request.device_id,
+                    &request.register_name,
+                    "rate_limit",
+                );
+                return Err(WriteError::RateLimited(format!(
+                    "'{}' accepts one write every {} ms from the same source, retry in {} ms",
+                    request.register_name,
+                    min_interval.as_millis(),
+                    (min_interval - elapsed).as_millis()
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Count a write against the rate limit of its register
+    fn record(&self, request: &WriteRequest, now: Instant) {
+        let mut last = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        last.insert(Self::key(request), now);
+    }
+
+    fn min_interval(register: &RegisterConfig) -> Option<Duration> {
+        let limits = register.write_limits.as_ref()?;
+        limits.min_interval_ms.map(Duration::from_millis)
+    }
+
+    fn key(request: &WriteRequest) -> WriteKey {
+        (
+            request.device_id.clone(),
+            request.register_name.clone(),
+            request.source.clone(),
+        )
+    }
+}
+
+/// Writes queued for a device before they reach its poller
+const DEVICE_WRITE_QUEUE: usize = 32;
+
+/// A write request with the register and raw values it resolved to
+type QueuedWrite = (
+    WriteRequest,
+    std::result::Result<(RegisterConfig, WriteOutcome), WriteError>,
+);
+
+/// Hands write requests to their devices and records the outcome
+#[derive(Clone)]
+struct WriteHandler {
+    gate: WriteGate,
+    writers: DeviceWriters,
+    audit_log: AuditLog,
+    events: EventSender,
+}
+
+impl WriteHandler {
+    /// Resolve each request and queue it for its device
+    ///
+    /// Every device has a queue worked off in arrival order, so a later
+    /// setpoint is never overtaken by an earlier one, while writes to
+    /// different devices don't wait for each other. A full queue holds up
+    /// `write_rx`, and with it the senders. The workers end with this task.
+    async fn run(
+        self,
+        mut write_rx: tokio::sync::mpsc::Receiver<WriteRequest>,
+        config_rx: watch::Receiver<Config>,
+        writes_enabled: Arc<std::sync::atomic::AtomicBool>,
+    ) {
+        let mut queues: HashMap<String, tokio::sync::mpsc::Sender<QueuedWrite>> = HashMap::new();
+        let mut workers = JoinSet::new();
+        while let Some(request) = write_rx.recv().await {
+            let resolved = if writes_enabled.load(Ordering::Relaxed) {
+                resolve_write(
+                    &config_rx.borrow(),
+                    &request.device_id,
+                    &request.register_name,
+                    request.value,
+                )
+            } else {
+                metrics::record_write_blocked(
+                    &request.device_id,
+                    &request.register_name,
+                    "disabled",
+                );
+                Err(WriteError::Disabled(
+                    "Writes are disabled on this bridge".to_string(),
+                ))
+            };
+            // Only writes to configured devices get a queue
+            if resolved.is_err() {
+                self.apply(request, resolved).await;
+                continue;
+            }
+            let queue = queues.entry(request.device_id.clone()).or_insert_with(|| {
+                let (queue_tx, queue_rx) = tokio::sync::mpsc::channel(DEVICE_WRITE_QUEUE);
+                workers.spawn(self.clone().work_off(queue_rx));
+                queue_tx
+            });
+            let _ = queue.send((request, resolved)).await;
+        }
+    }
+
+    /// Apply the writes of one device in order
+    async fn work_off(self, mut queue: tokio::sync::mpsc::Receiver<QueuedWrite>) {
+        while let Some((request, resolved)) = queue.recv().await {
+            self.apply(request, resolved).await;
+        }
+    }
+
+    async fn apply(
+        &self,
+        request: WriteRequest,
+        resolved: std::result::Result<(RegisterConfig, WriteOutcome), WriteError>,
+    ) {
+        let writer = self.writers.get(&request.device_id);
+        let (old_value, result) = self.gate.write(&request, resolved, writer).await;
+
+        self.audit_log.record(AuditEntry {
+            timestamp: chrono::Utc::now(),
+            source: request.source.clone(),
+            device_id: request.device_id.clone(),
+            register: request.register_name.clone(),
+            address: result.as_ref().map_or(0, |outcome| outcome.address),
+            old_value,
+            new_value: request.value,
+            result: if result.is_ok() {
+                WriteResult::Success
+            } else {
+                WriteResult::Failure
+            },
+            error: result.as_ref().err().map(|e| e.to_string()),
+        });
+
+        if let Ok(outcome) = &result {
+            info!(
+                "Wrote {}@{} = {} (raw {:?})",
+                request.device_id, outcome.address, request.value, outcome.raw
+            );
+            let _ = self.events.send(
+                ConnectionEvent::new(request.device_id.as_str(), ConnectionEventKind::Write)
+                    .register_value(request.register_name.as_str(), request.value)
+                    .source(request.source.clone()),
+            );
+        }
+        let _ = request.response_tx.send(result);
+    }
+}
+
+/// Checks the rate limits and interlocks of writes and sends them to their
+/// device
+///
+/// [`WriteHandler`] passes the writes of a device one at a time, from the
+/// checks until the device answered, so a write cannot break the interlock
+/// of another one that was already checked, and only writes the device took
+/// count against the rate limit.
+#[derive(Clone)]
+struct WriteGate {
+    store: RegisterStore,
+    limiter: WriteLimiter,
+}
+
+impl WriteGate {
+    fn new(store: RegisterStore) -> Self {
+        Self {
+            store,
+            limiter: WriteLimiter::default(),
+        }
+    }
+
+    /// Write a resolved request unless it comes too soon or an interlock of
+    /// its register is not met, returning the value the register had before
+    /// as well
+    async fn write(
+        &self,
+        request: &WriteRequest,
+        resolved: std::result::Result<(RegisterConfig, WriteOutcome), WriteError>,
+        writer: Option<tokio::sync::mpsc::Sender<DeviceWrite>>,
+    ) -> (Option<f64>, std::result::Result<WriteOutcome, WriteError>) {
+        // A safe state does not wait for interlocks or rate limits
+        let dead_man = request.source == WriteSource::DeadMan;
+        let now = Instant::now();
+        let (old_value, checked) = {
+            let store = self.store.read().await;
+            let old_value = store
+                .get(&request.device_id)
+                .and_then(|registers| registers.get(&request.register_name))
+                .map(|r| r.value);
+            let checked = resolved.and_then(|(register, outcome)| {
+                if !dead_man {
+                    self.limiter.check(&register, request, now)?;
+                }
+                let unmet = register
+                    .write_limits
+                    .as_ref()
+                    .filter(|_| !dead_man)
+                    .and_then(|limits| {
+                        reader::unmet_interlock(&limits.require, &request.device_id, &store)
+                    });
+                if let Some(unmet) = unmet {
+                    metrics::record_write_blocked(
+                        &request.device_id,
+                        &request.register_name,
+                        "interlock",
+                    );
+                    return Err(WriteError::Rejected(unmet));
+                }
+                Ok((register, outcome))
+            });
+            (old_value, checked)
+        };
+
+        let result = match checked {
+            Ok((register, outcome)) => {
+                let limited = !dead_man && WriteLimiter::min_interval(&register).is_some();
+                let result = send_device_write(writer, &request.device_id, register, outcome).await;
+                // Only a write the device took counts against the rate limit
+                if limited && result.is_ok() {
+                    self.limiter.record(request, now);
+                }
+                result
+            }
+            Err(e) => Err(e),
+        };
+        (old_value, result)
+    }
+}
+
+/// Write channels of the running device pollers, by device ID
+#[derive(Clone, Default)]
+struct DeviceWriters(
+    Arc<std::sync::Mutex<HashMap<String, tokio::sync::mpsc::Sender<DeviceWrite>>>>,
+);
+
+impl DeviceWriters {
+    fn get(&self, device_id: &str) -> Option<tokio::sync::mpsc::Sender<DeviceWrite>> {
+        self.0.lock().unwrap().get(device_id).cloned()
+    }
+
+    fn insert(&self, device_id: &str, writer: tokio::sync::mpsc::Sender<DeviceWrite>) {
+        self.0.lock().unwrap().insert(device_id.to_string(), writer);
+    }
+
+    fn remove(&self, device_id: &str) {
+        self.0.lock().unwrap().remove(device_id);
+    }
+}
+
+/// Interned device and register names
+///
+/// Every update of a register shares the same name strings, so publishing a
+/// value does not allocate them again.
+#[derive(Clone, Default)]
+struct NameCache(Arc<std::sync::RwLock<HashMap<String, DeviceNames>>>);
+
+struct DeviceNames {
+    id: Arc<str>,
+    registers: HashMap<String, Arc<str>>,
+}
+
+impl NameCache {
+    /// Shared names of a device and one of its registers
+    fn get(&self, device_id: &str, register_name: &str) -> (Arc<str>, Arc<str>) {
+        if let Some(names) = self.0.read().unwrap().get(device_id) {
+            if let Some(register) = names.registers.get(register_name) {
+                return (names.id.clone(), register.clone());
+            }
+        }
+        let mut devices = self.0.write().unwrap();
+        let names = devices
+            .entry(device_id.to_string())
+            .or_insert_with(|| DeviceNames {
+                id: device_id.into(),
+                registers: HashMap::new(),
+            });
+        let register = names
+            .registers
+            .entry(register_name.to_string())
+            .or_insert_with(|| register_name.into())
+            .clone();
+        (names.id.clone(), register)
+    }
+
+    /// Drop the names of a device that is no longer configured
+    fn forget(&self, device_id: &str) {
+        self.0.write().unwrap().remove(device_id);
+    }
+}
+
+/// Changes of `min_stable_ms` registers that have yet to hold, by device
+#[derive(Clone, Default)]
+struct Debounce(Arc<std::sync::Mutex<HashMap<String, PendingChanges>>>);
+
+/// Changed value of each register and when it was first read
+type PendingChanges = HashMap<String, (f64, Instant)>;
+
+impl Debounce {
+    /// Whether a value differing from the stored one has now held for `min_stable`
+    fn settled(
+        &self,
+        device_id: &str,
+        register: &str,
+        value: f64,
+        min_stable: Duration,
+        now: Instant,
+    ) -> bool {
+        let mut pending = self.0.lock().unwrap();
+        let registers = pending.entry(device_id.to_string()).or_default();
+        match registers.get(register) {
+            Some(&(candidate, since)) if candidate == value => {
+                let settled = now.duration_since(since) >= min_stable;
+                if settled {
+                    registers.remove(register);
+                }
+                settled
+            }
+            _ => {
+                registers.insert(register.to_string(), (value, now));
+                false
+            }
+        }
+    }
+
+    /// Drop a pending change after the stored value was read again
+    fn reset(&self, device_id: &str, register: &str) {
+        if let Some(registers) = self.0.lock().unwrap().get_mut(device_id) {
+            registers.remove(register);
+        }
+    }
+
+    /// Drop the pending changes of a device that is no longer configured
+    fn forget(&self, device_id: &str) {
+        self.0.lock().unwrap().remove(device_id);
+    }
+}
+
+/// Shared state handed to each device polling task
+#[derive(Clone)]
+struct PollingContext {
+    store: RegisterStore,
+    broadcaster: tokio::sync::broadcast::Sender<RegisterUpdate>,
+    /// Sinks that must not lose updates; polling waits for them when full
+    blocking_sinks: Vec<BlockingSink>,
+    diagnostics: DiagnosticsStore,
+    error_log: ErrorLog,
+    events: EventSender,
+    controls: DeviceControls,
+    heartbeats: Heartbeats,
+    writers: DeviceWriters,
+    names: NameCache,
+    /// Receives each device's `$stats`, `None` when they are not published
+    stats: Option<StatsSink>,
+    debounce: Debounce,
+}
+
+/// Where pollers send their statistics every `every` cycles
+#[derive(Clone)]
+struct StatsSink {
+    every: u32,
+    tx: broadcast::Sender<CycleStats>,
+}
+
+impl PollingContext {
+    /// Emit a connection lifecycle event (dropped when nobody is listening)
+    fn emit(&self, event: ConnectionEvent) {
+        let _ = self.events.send(event);
+    }
+
+    /// Rescore a device's health, announcing state changes
+    async fn record_health(&self, device_id: &str, cycle: CycleHealth) {
+        let (health, previous) = {
+            let mut diagnostics = self.diagnostics.write().await;
+            let device_diag = diagnostics.entry(device_id.to_string()).or_default();
+            let previous = device_diag.record_health(cycle);
+            (device_diag.health.clone(), previous)
+        };
+        metrics::record_device_health(device_id, health.score);
+        if let Some(previous) = previous {
+            info!(
+                device_id = %device_id,
+                score = health.score,
+                "Device {} is {} (was {})",
+                device_id,
+                health.state,
+                previous
+            );
+            self.emit(
+                ConnectionEvent::new(device_id, ConnectionEventKind::HealthChanged)
+                    .message(format!("{} -> {}", previous, health.state))
+                    .value(health.score),
+            );
+        }
+    }
+
+    /// Emit each entry drained from a FIFO register as an event
+    fn publish_fifo_entries(
+        &self,
+        device: &DeviceConfig,
+        register: &RegisterConfig,
+        entries: Vec<u16>,
+    ) {
+        for entry in entries {
+            let value = codec::convert_value(&[entry], register);
+            self.emit(
+                ConnectionEvent::new(device.id.as_str(), ConnectionEventKind::FifoEntry)
+                    .register_value(register.name.as_str(), value),
+            );
+        }
+    }
+
+    /// Publish the named points of a coil or discrete input block, returning
+    /// how many were published
+    async fn publish_points(
+        &self,
+        device: &DeviceConfig,
+        register: &RegisterConfig,
+        bits: &[u16],
+    ) -> usize {
+        let mut published = 0;
+        for point in &register.points {
+            let Some(&bit) = bits.get(point.offset as usize) else {
+                continue;
+            };
+            if self
+                .publish_value(device, &register.point(point), vec![bit])
+                .await
+                .is_some()
+            {
+                published += 1;
+            }
+        }
+        published
+    }
+
+    /// Store a register value read from a device and broadcast it to
+    /// WebSocket clients (and MQTT if enabled), returning the scaled value
+    ///
+    /// NaN and infinite readings are handled by the register's `non_finite`
+    /// policy; `None` when the reading was dropped.
+    async fn publish_value(
+        &self,
+        device: &DeviceConfig,
+        register: &RegisterConfig,
+        raw: Vec<u16>,
+    ) -> Option<f64> {
+        let (device_id, register_name) = self.names.get(&device.id, &register.name);
+
+        // Store the value, keeping the previous one for change detection
+        let (reg_value, previous_value, last_good) = {
+            let mut store = self.store.write().await;
+            // Only the first value of a device allocates its key
+            if !store.contains_key(&device.id) {
+                store.insert(device.id.clone(), HashMap::new());
+            }
+            let device_map = store.get_mut(&device.id)?;
+            let last_good = device_map
+                .get(&register.name)
+                .map(|previous| previous.value)
+                .filter(|v| v.is_finite());
+            let Some((value, quality)) = codec::convert_reading(&raw, register, last_good) else {
+                tracing::warn!(
+                    device_id = %device.id,
+                    register = %register.name,
+                    "Dropped non-finite reading of {} on {} (raw {:?})",
+                    register.name,
+                    device.id,
+                    raw
+                );
+                return None;
+            };
+            if let Some(min_stable_ms) = register.min_stable_ms {
+                match device_map.get(&register.name).map(|stored| stored.value) {
+                    Some(stored) if stored != value => {
+                        let min_stable = Duration::from_millis(min_stable_ms);
+                        let settled = self.debounce.settled(
+                            &device.id,
+                            &register.name,
+                            value,
+                            min_stable,
+                            Instant::now(),
+                        );
+                        if !settled {
+                            // The stored value stands until the change holds
+                            return Some(stored);
+                        }
+                    }
+                    _ => self.debounce.reset(&device.id, &register.name),
+                }
+            }
+            let reg_value = RegisterValue {
+                name: register_name.clone(),
+                raw,
+                value,
+                quality,
+                restored: false,
+                unit: register.unit.clone(),
+                timestamp: chrono::Utc::now(),
+                meta: device.register_meta(register),
+            };
+            let previous = match device_map.get_mut(&register.name) {
+                Some(slot) => Some(std::mem::replace(slot, reg_value.clone())),
+                None => device_map.insert(register.name.clone(), reg_value.clone()),
+            };
+            (
+                reg_value,
+                previous.map(|previous| previous.value),
+                last_good,
+            )
+        };
+        let value = reg_value.value;
+
+        // A `null` reading leaves the alarm as it was
+        if let Some(alarm) = register.alarm.as_ref().filter(|_| value.is_finite()) {
+            let was_active = last_good.is_some_and(|v| alarm.is_met(v));
+            let kind = match (was_active, alarm.is_met(value)) {
+                (false, true) => Some(ConnectionEventKind::AlarmRaised),
+                (true, false) => Some(ConnectionEventKind::AlarmCleared),
+                _ => None,
+            };
+            if let Some(kind) = kind {
+                self.emit(
+                    ConnectionEvent::new(device.id.as_str(), kind)
+                        .register_value(register.name.as_str(), value),
+                );
+            }
+        }
+
+        let update = RegisterUpdate {
+            device_id,
+            register_name,
+            value: reg_value.value,
+            raw: reg_value.raw,
+            unit: reg_value.unit,
+            timestamp: reg_value.timestamp.to_rfc3339(),
+            previous_value,
+            // A run of `null` readings is not a change
+            changed: !previous_value.is_some_and(|p| p == value || (p.is_nan() && value.is_nan())),
+            meta: reg_value.meta,
+            boolean: register.is_boolean(),
+            quality: reg_value.quality,
+        };
+        for sink in &self.blocking_sinks {
+            sink.send(update.clone()).await;
+        }
+        let _ = self.broadcaster.send(update);
+
+        tracing::debug!(
+            device_id = %device.id,
+            register = %register.name,
+            value,
+            "Device {} register {} = {} {:?}",
+            device.id,
+            register.name,
+            value,
+            register.unit
+        );
+        Some(value)
+    }
+}
+
+/// Publish the register values a listen-only device is seen to exchange with
+/// the bus master, never transmitting anything
+#[cfg(feature = "rtu")]
+async fn listen_to_device(
+    config: DeviceConfig,
+    rtu: crate::config::RtuConnection,
+    ctx: PollingContext,
+) -> Result<()> {
+    use crate::modbus::sniffer;
+    use tokio::sync::broadcast::error::RecvError;
+
+    let device_id = config.id.clone();
+    let control = ctx.controls.device(&device_id).unwrap_or_default();
+    let retry_interval = Duration::from_millis(config.poll_interval_ms);
+    info!(
+        "Listening for traffic of device {} (unit {})",
+        device_id, rtu.unit_id
+    );
+
+    loop {
+        ctx.heartbeats.beat(&device_id);
+        let mut observations = match sniffer::subscribe(&rtu) {
+            Ok(observations) => observations,
+            Err(e) => {
+                let message = format!("{:#}", e);
+                metrics::record_device_status(&device_id, false);
+                control.set_online(false);
+                ctx.emit(
+                    ConnectionEvent::new(device_id.as_str(), ConnectionEventKind::SerialPortError)
+                        .message(&message),
+                );
+                ctx.error_log.record(
+                    ErrorEvent::new(ErrorSource::Modbus, ErrorKind::Connection, &message)
+                        .device(device_id.as_str()),
+                );
+                tracing::error!(
+                    device_id = %device_id,
+                    error = %message,
+                    "Failed to listen on the bus of device {}: {}",
+                    device_id,
+                    message
+                );
+                tokio::time::sleep(retry_interval).await;
+                continue;
+            }
+        };
+        metrics::record_device_status(&device_id, true);
+        control.set_online(true);
+        ctx.emit(ConnectionEvent::new(
+            device_id.as_str(),
+            ConnectionEventKind::Connected,
+        ));
+
+        // The bus may be quiet; keep the watchdog fed meanwhile
+        let mut ticker = tokio::time::interval(retry_interval);
+        loop {
+            let observation = tokio::select! {
+                _ = ticker.tick() => {
+                    ctx.heartbeats.beat(&device_id);
+                    continue;
+                }
+                observation = observations.recv() => observation,
+            };
+            let observation = match observation {
+                Ok(observation) => observation,
+                Err(RecvError::Lagged(skipped)) => {
+                    tracing::warn!(
+                        device_id = %device_id,
+                        "Device {} fell behind the bus, {} observations skipped",
+                        device_id,
+                        skipped
+                    );
+                    continue;
+                }
+                Err(RecvError::Closed) => break,
+            };
+            if control.is_paused() {
+                continue;
+            }
+            for register in &config.registers {
+                if let Some(raw) = observation.registers_of(rtu.unit_id, register) {
+                    if register.points.is_empty() {
+                        ctx.publish_value(&config, register, raw.to_vec()).await;
+                    } else {
+                        ctx.publish_points(&config, register, raw).await;
+                    }
+                }
+            }
+        }
+
+        metrics::record_device_status(&device_id, false);
+        control.set_online(false);
+        ctx.emit(
+            ConnectionEvent::new(device_id.as_str(), ConnectionEventKind::Disconnected)
+                .message("serial port closed"),
+        );
+        tokio::time::sleep(retry_interval).await;
+    }
+}
+
+/// Start polling with WebSocket broadcast support and metrics
+async fn start_polling_with_broadcast(
+    config: crate::config::DeviceConfig,
+    ctx: PollingContext,
+    mut write_rx: tokio::sync::mpsc::Receiver<DeviceWrite>,
+) -> Result<()> {
+    use crate::modbus::ModbusClient;
+    use tokio::time::{interval, MissedTickBehavior};
+
+    let device_id = config.id.clone();
+    let poll_interval = Duration::from_millis(config.poll_interval_ms);
+    let is_serial = matches!(config.connection, ConnectionConfig::Rtu(_));
+    let control = ctx.controls.device(&device_id).unwrap_or_default();
+
+    if let ConnectionConfig::Rtu(rtu) = &config.connection {
+        if rtu.listen_only {
+            #[cfg(feature = "rtu")]
+            return listen_to_device(config.clone(), rtu.clone(), ctx).await;
+            #[cfg(not(feature = "rtu"))]
+            return Err(RustbridgeError::Config(
+                "listen_only needs RTU support (enable the `rtu` feature)".to_string(),
+            ));
+        }
+    }
+
+    info!(
+        "Starting polling for device {} every {}ms{}",
+        device_id,
+        config.poll_interval_ms,
+        if config.align_to_clock {
+            ", aligned to the clock"
+        } else {
+            ""
+        }
+    );
+
+    // Connected lazily on the first tick and re-established after transport errors
+    let mut client: Option<ModbusClient> = None;
+    let mut reconnecting = false;
+    let mut reconnect_attempt: u32 = 0;
+    let mut backoff = ReconnectBackoff::new(config.reconnect.clone());
+
+    let mut ticker = interval(poll_interval);
+    // Don't burst to catch up after an overrun; keep cycles evenly spaced instead
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    let poll_order = reader::poll_order(&config.registers);
+    let mut blocks = BlockReads::new(&device_id, &poll_order, config.read_blocks.as_ref());
+    let mut setpoints = Setpoints::default();
+    let mut previous_cycle_start: Option<Instant> = None;
+    // Device clocks are set after every connect, then every `interval_secs`
+    let mut next_time_sync = Instant::now();
+    let mut overrun_warning = RateLimitedWarning::new(OVERRUN_WARNING_INTERVAL);
+    let mut shedding = LoadShedding::default();
+    let mut breakers = CircuitBreakers::new(config.circuit_breaker.clone());
+    let mut budget = CycleBudget::new(config.cycle_budget_ms);
+    // Time spent on writes since the last poll cycle
+    let mut writes_time = Duration::ZERO;
+    let mut stats_window = ctx
+        .stats
+        .as_ref()
+        .map(|stats| CycleStatsWindow::new(&device_id, stats.every));
+
+    loop {
+        if config.align_to_clock {
+            // Taken from the wall clock every time, so overruns skip to the
+            // next boundary and the monotonic clock cannot drift off the grid
+            ticker.reset_after(until_clock_boundary(SystemTime::now(), poll_interval));
+        }
+        // Scheduled start of the cycle, `None` when triggered by poll-now
+        let scheduled = tokio::select! {
+            deadline = ticker.tick() => Some(deadline.into_std()),
+            _ = control.poll_requested() => None,
+            Some(write) = write_rx.recv() => {
+                // Writes share the connection with polling, between cycles
+                let write_start = Instant::now();
+                let result = match client.as_mut() {
+                    Some(connection) => write.execute(connection, &config, &ctx).await,
+                    None => Err(RustbridgeError::Connection(format!(
+                        "Device {} is not connected",
+                        device_id
+                    ))),
+                };
+                if result.is_ok() {
+                    setpoints.written(&write.register, &write.raw);
+                }
+                if let Err(e) = &result {
+                    if client.is_some()
+                        && matches!(
+                            ErrorKind::classify(e),
+                            ErrorKind::Connection | ErrorKind::Transport
+                        )
+                    {
+                        // Reconnect on the next tick
+                        client = None;
+                        reconnecting = true;
+                        setpoints.lost();
+                        metrics::record_device_status(&device_id, false);
+                        control.set_online(false);
+                    }
+                }
+                let _ = write.response_tx.send(result);
+                writes_time += write_start.elapsed();
+                continue;
+            }
+        };
+        ctx.heartbeats.beat(&device_id);
+        if control.is_paused() {
+            // The achieved interval restarts after resuming
+            previous_cycle_start = None;
+            writes_time = Duration::ZERO;
+            continue;
+        }
+        if client.is_none() && scheduled.is_some() && !backoff.due(Instant::now()) {
+            // Backing off between connection attempts; poll-now tries right away
+            continue;
+        }
+        let cycle_start = Instant::now();
+        let started_at = chrono::Utc::now();
+        let queue_wait = scheduled.map_or(Duration::ZERO, |deadline| {
+            cycle_start.saturating_duration_since(deadline)
+        });
+        let mut register_timings = Vec::new();
+        let mut traffic = CycleTraffic::default();
+
+        if let Some(previous) = previous_cycle_start.replace(cycle_start) {
+            let achieved_ms = cycle_start.duration_since(previous).as_millis() as u64;
+            metrics::record_achieved_interval(&device_id, achieved_ms);
+            ctx.diagnostics
+                .write()
+                .await
+                .entry(device_id.clone())
+                .or_default()
+                .record_achieved_interval(achieved_ms);
+        }
+
+        // Whether this cycle re-establishes a lost connection
+        let reconnect = client.is_none() && reconnecting;
+        if client.is_none() {
+            if reconnecting {
+                reconnect_attempt += 1;
+                ctx.emit(
+                    ConnectionEvent::new(device_id.as_str(), ConnectionEventKind::ReconnectAttempt)
+                        .attempt(reconnect_attempt),
+                );
+                info!(
+                    device_id = %device_id,
+                    attempt = reconnect_attempt,
+                    "Reconnecting to device {} (attempt {})",
+                    device_id,
+                    reconnect_attempt
+                );
+            }
+
+            match ModbusClient::new(&config).await {
+                Ok(connected) => {
+                    client = Some(connected);
+                    next_time_sync = Instant::now();
+                    reconnecting = false;
+                    reconnect_attempt = 0;
+                    backoff.reset();
+                    metrics::record_device_status(&device_id, true);
+                    control.set_online(true);
+                    ctx.emit(ConnectionEvent::new(
+                        device_id.as_str(),
+                        ConnectionEventKind::Connected,
+                    ));
+                }
+                Err(e) => {
+                    reconnecting = true;
+                    setpoints.lost();
+                    metrics::record_device_status(&device_id, false);
+                    control.set_online(false);
+
+                    let message = format!("{:#}", e);
+                    let kind = if is_serial {
+                        ConnectionEventKind::SerialPortError
+                    } else {
+                        ConnectionEventKind::Disconnected
+                    };
+                    ctx.emit(ConnectionEvent::new(device_id.as_str(), kind).message(&message));
+                    ctx.error_log.record(
+                        ErrorEvent::new(ErrorSource::Modbus, ErrorKind::Connection, &message)
+                            .device(device_id.as_str()),
+                    );
+
+                    tracing::error!(
+                        device_id = %device_id,
+                        error = %message,
+                        "Failed to connect to device {}: {}",
+                        device_id,
+                        message
+                    );
+                    if backoff.record_failure(Instant::now()).is_none() {
+                        tracing::error!(
+                            device_id = %device_id,
+                            "Giving up on device {} after {} failed connection attempts",
+                            device_id,
+                            config.reconnect.max_retries
+                        );
+                    }
+                    let attempt = CycleHealth {
+                        reads_failed: 1,
+                        reconnect,
+                        ..Default::default()
+                    };
+                    ctx.record_health(&device_id, attempt).await;
+                    continue;
+                }
+            }
+        }
+        let Some(connection) = client.as_mut() else {
+            continue;
+        };
+
+        // Set when a transport error means the connection has to be re-established
+        let mut connection_lost: Option<String> = serial_port_gone(connection);
+
+        // Nobody answers a broadcast; the device exists for writes and time_sync
+        let polled: &[&RegisterConfig] = if config.is_broadcast() {
+            &[]
+        } else {
+            &poll_order
+        };
+        // FC07 returns every status bit at once; read it once per cycle
+        let mut exception_status = None;
+        // Registers read or triggered in this cycle, for `read_after`
+        let mut completed: HashSet<&str> = HashSet::new();
+        blocks.start_cycle();
+        for &register in polled {
+            if connection_lost.is_some() {
+                break;
+            }
+            if !shedding.reads(register.priority) {
+                continue;
+            }
+            if !breakers.allows(&register.name, Instant::now()) {
+                continue;
+            }
+            if budget.defers(register, cycle_start.elapsed()) {
+                continue;
+            }
+            let gated = {
+                let store = ctx.store.read().await;
+                !reader::condition_met(register, store.get(&device_id))
+            };
+            if gated {
+                tracing::debug!(
+                    device_id = %device_id,
+                    register = %register.name,
+                    "Skipping {} on {}: read_when condition not met",
+                    register.name,
+                    device_id
+                );
+                continue;
+            }
+            if let Some(after) = &register.read_after {
+                if !completed.contains(after.as_str()) {
+                    tracing::debug!(
+                        device_id = %device_id,
+                        register = %register.name,
+                        "Skipping {} on {}: {} was not read in this cycle",
+                        register.name,
+                        device_id,
+                        after
+                    );
+                    continue;
+                }
+            }
+            // Start metrics timing
+            let wait = cycle_start.elapsed();
+            if let Some(value) = register.trigger {
+                let write_start = Instant::now();
+                let result = write_trigger(connection, register, value).await;
+                traffic.record(reader::frame_bytes(register, is_serial), result.is_ok());
+                register_timings.push(RegisterTiming {
+                    name: register.name.clone(),
+                    wait_ms: wait.as_secs_f64() * 1000.0,
+                    latency_ms: write_start.elapsed().as_secs_f64() * 1000.0,
+                    ok: result.is_ok(),
+                });
+                match result {
+                    Ok(()) => {
+                        completed.insert(register.name.as_str());
+                    }
+                    Err(e) => {
+                        let message = e.to_string();
+                        let kind = ErrorKind::classify(&e);
+                        ctx.error_log.record(
+                            ErrorEvent::new(ErrorSource::Modbus, kind, message.as_str())
+                                .device(device_id.as_str())
+                                .register(register.name.as_str(), register.address),
+                        );
+                        tracing::error!(
+                            device_id = %device_id,
+                            register = %register.name,
+                            address = register.address,
+                            error = %e,
+                            "Failed to write trigger {} on {}: {}",
+                            register.name,
+                            device_id,
+                            e
+                        );
+                        if matches!(kind, ErrorKind::Connection | ErrorKind::Transport) {
+                            connection_lost = Some(message);
+                            break;
+                        }
+                    }
+                }
+                continue;
+            }
+            let read_metrics = ReadMetrics::start(&device_id, &register.name);
+
+            // Members of a block take their values from its response
+            let from_block = blocks
+                .read(connection, register, &mut traffic, is_serial)
+                .await;
+            let in_block = from_block.is_some();
+            let read = match from_block {
+                Some(read) => read,
+                None => match register.register_type {
+                    RegisterType::ExceptionStatus => {
+                        read_status_bit(connection, register, &mut exception_status).await
+                    }
+                    RegisterType::Fifo => drain_fifo(connection, register).await,
+                    _ => match &register.oversample {
+                        Some(oversample) => {
+                            reader::read_oversampled(connection, register, oversample).await
+                        }
+                        None => connection.read_registers(register).await,
+                    },
+                },
+            };
+            let duration_ms = read_metrics.elapsed().as_secs_f64() * 1000.0;
+            if !in_block {
+                traffic.record(reader::frame_bytes(register, is_serial), read.is_ok());
+            }
+            register_timings.push(RegisterTiming {
+                name: register.name.clone(),
+                wait_ms: wait.as_secs_f64() * 1000.0,
+                latency_ms: duration_ms,
+                ok: read.is_ok(),
+            });
+            match read {
+                Ok(raw_values) => {
+                    completed.insert(register.name.as_str());
+                    let closed = breakers.record_success(&register.name);
+                    {
+                        let mut diagnostics = ctx.diagnostics.write().await;
+                        let device_diag = diagnostics.entry(device_id.clone()).or_default();
+                        device_diag.record_success(&register.name, duration_ms);
+                        if closed {
+                            device_diag.set_circuit(&register.name, None);
+                        }
+                    }
+                    if closed {
+                        info!(
+                            device_id = %device_id,
+                            register = %register.name,
+                            "Register {} on {} answered again, polling it every cycle",
+                            register.name,
+                            device_id
+                        );
+                    }
+                    metrics::record_failure_streak(&device_id, &register.name, 0, 0);
+
+                    if register.register_type == RegisterType::Fifo {
+                        let drained = raw_values.len();
+                        ctx.publish_fifo_entries(&config, register, raw_values);
+                        read_metrics.success(drained as f64);
+                    } else if !register.points.is_empty() {
+                        let published = ctx.publish_points(&config, register, &raw_values).await;
+                        read_metrics.success(published as f64);
+                    } else {
+                        match ctx.publish_value(&config, register, raw_values).await {
+                            // Record successful read metrics
+                            Some(value) if value.is_finite() => read_metrics.success(value),
+                            _ => read_metrics.failure("non_finite"),
+                        }
+                    }
+                }
+                Err(e) => {
+                    let message = e.to_string();
+                    let kind = ErrorKind::classify(&e);
+                    let backoff = match kind {
+                        ErrorKind::Exception => {
+                            breakers.record_exception(&register.name, &message, Instant::now())
+                        }
+                        _ => None,
+                    };
+                    let (register_streak, device_streak) = {
+                        let mut diagnostics = ctx.diagnostics.write().await;
+                        let device_diag = diagnostics.entry(device_id.clone()).or_default();
+                        let register_streak =
+                            device_diag.record_failure(&register.name, duration_ms, &message);
+                        if let Some(backoff) = backoff {
+                            let retry_at = chrono::Utc::now()
+                                + chrono::Duration::from_std(backoff).unwrap_or_default();
+                            device_diag.set_circuit(&register.name, Some(retry_at));
+                        }
+                        (register_streak, device_diag.consecutive_failures)
+                    };
+                    if let Some(backoff) = backoff {
+                        tracing::warn!(
+                            device_id = %device_id,
+                            register = %register.name,
+                            "Register {} on {} keeps failing ({}), next attempt in {}s",
+                            register.name,
+                            device_id,
+                            message,
+                            backoff.as_secs()
+                        );
+                    }
+                    metrics::record_failure_streak(
+                        &device_id,
+                        &register.name,
+                        register_streak,
+                        device_streak,
+                    );
+
+                    // Record failed read metrics
+                    read_metrics.failure("modbus_error");
+
+                    ctx.error_log.record(
+                        ErrorEvent::new(ErrorSource::Modbus, kind, message.as_str())
+                            .device(device_id.as_str())
+                            .register(register.name.as_str(), register.address),
+                    );
+
+                    tracing::error!(
+                        device_id = %device_id,
+                        register = %register.name,
+                        address = register.address,
+                        error = %e,
+                        "Failed to read register {} from {}: {}",
+                        register.name,
+                        device_id,
+                        e
+                    );
+
+                    if matches!(kind, ErrorKind::Connection | ErrorKind::Transport) {
+                        connection_lost = Some(message);
+                        break;
+                    }
+                }
+            }
+        }
+
+        if let Some(time_sync) = config.time_sync.as_ref() {
+            if connection_lost.is_none() && Instant::now() >= next_time_sync {
+                next_time_sync = Instant::now() + Duration::from_secs(time_sync.interval_secs);
+                let registers = crate::modbus::time_sync::registers_now(time_sync);
+                match connection
+                    .write_registers(time_sync.address, &registers)
+                    .await
+                {
+                    Ok(()) => tracing::debug!(
+                        device_id = %device_id,
+                        "Synchronized clock of device {}",
+                        device_id
+                    ),
+                    Err(e) => {
+                        let message = format!("Clock synchronization failed: {}", e);
+                        let kind = ErrorKind::classify(&e);
+                        ctx.error_log.record(
+                            ErrorEvent::new(ErrorSource::Modbus, kind, message.as_str())
+                                .device(device_id.as_str()),
+                        );
+                        tracing::warn!(
+                            device_id = %device_id,
+                            error = %e,
+                            "Failed to synchronize clock of device {}: {}",
+                            device_id,
+                            e
+                        );
+                        if matches!(kind, ErrorKind::Connection | ErrorKind::Transport) {
+                            connection_lost = Some(message);
+                        }
+                    }
+                }
+            }
+        }
+
+        if let Some(message) = connection_lost {
+            client = None;
+            reconnecting = true;
+            setpoints.lost();
+            metrics::record_device_status(&device_id, false);
+            control.set_online(false);
+
+            if is_serial {
+                ctx.emit(
+                    ConnectionEvent::new(device_id.as_str(), ConnectionEventKind::SerialPortError)
+                        .message(&message),
+                );
+            }
+            ctx.emit(
+                ConnectionEvent::new(device_id.as_str(), ConnectionEventKind::Disconnected)
+                    .message(&message),
+            );
+            tracing::warn!(
+                device_id = %device_id,
+                "Lost connection to device {}, reconnecting on next poll",
+                device_id
+            );
+        }
+
+        // Record poll cycle duration
+        let elapsed = cycle_start.elapsed();
+        let cycle_duration = elapsed.as_millis() as u64;
+        metrics::record_poll_cycle(&device_id, cycle_duration);
+        let mut health = CycleHealth {
+            reconnect,
+            ..Default::default()
+        };
+        for timing in &register_timings {
+            if timing.ok {
+                health.reads_ok += 1;
+            } else {
+                health.reads_failed += 1;
+            }
+            health.latency_ms += timing.latency_ms;
+        }
+        if let Some(connection) = client.as_mut() {
+            if setpoints.answered(health.reads_ok, health.reads_failed)
+                && setpoints.restore(connection, &config, &ctx).await.is_err()
+            {
+                // Reconnect and restore on the next tick
+                client = None;
+                reconnecting = true;
+                setpoints.lost();
+                metrics::record_device_status(&device_id, false);
+                control.set_online(false);
+            }
+        }
+        let overran = {
+            let mut diagnostics = ctx.diagnostics.write().await;
+            let device_diag = diagnostics.entry(device_id.clone()).or_default();
+            let overran = device_diag.record_cycle(cycle_duration, config.poll_interval_ms);
+            device_diag.record_timing(CycleTiming {
+                started_at,
+                duration_ms: elapsed.as_secs_f64() * 1000.0,
+                queue_wait_ms: queue_wait.as_secs_f64() * 1000.0,
+                writes_ms: std::mem::take(&mut writes_time).as_secs_f64() * 1000.0,
+                overran,
+                registers: register_timings,
+            });
+            overran
+        };
+        health.overran = overran;
+        ctx.record_health(&device_id, health).await;
+        if let (Some(window), Some(stats)) = (stats_window.as_mut(), ctx.stats.as_ref()) {
+            if let Some(cycle_stats) = window.record(elapsed.as_secs_f64() * 1000.0, traffic) {
+                let _ = stats.tx.send(cycle_stats);
+            }
+        }
+
+        if overran {
+            metrics::record_poll_overrun(&device_id);
+            if let Some(suppressed) = overrun_warning.check() {
+                tracing::warn!(
+                    device_id = %device_id,
+                    cycle_ms = cycle_duration,
+                    poll_interval_ms = config.poll_interval_ms,
+                    "Poll cycle for {} took {}ms, exceeding the {}ms poll interval ({} more overruns since last warning)",
+                    device_id,
+                    cycle_duration,
+                    config.poll_interval_ms,
+                    suppressed
+                );
+            }
+        }
+        let deferred = budget.finish_cycle();
+        if deferred > 0 {
+            metrics::record_deferred_reads(&device_id, deferred);
+            tracing::debug!(
+                device_id = %device_id,
+                deferred,
+                "Poll cycle for {} exceeded its budget, deferred {} registers",
+                device_id,
+                deferred
+            );
+        }
+        if shedding.record_cycle(cycle_duration, config.poll_interval_ms) {
+            metrics::record_load_shedding(&device_id, shedding.level);
+            info!(
+                device_id = %device_id,
+                level = shedding.level,
+                "Device {} now skips {} priority registers",
+                device_id,
+                shedding.describe()
+            );
+        }
+    }
+}
+
+/// Read one exception status bit, reusing the status byte read earlier in the cycle
+async fn read_status_bit(
+    client: &mut crate::modbus::ModbusClient,
+    register: &RegisterConfig,
+    status: &mut Option<u8>,
+) -> Result<Vec<u16>> {
+    let byte = match *status {
+        Some(byte) => byte,
+        None => *status.insert(client.read_exception_status().await?),
+    };
+    Ok(vec![codec::status_bit(byte, register.address)])
+}
+
+/// Blocks of a device's registers read with one request, see `read_blocks`
+struct BlockReads {
+    device_id: String,
+    blocks: Vec<ReadBlock>,
+    /// Block of each register read in one
+    member_of: HashMap<String, usize>,
+    /// Response of each block read in this cycle, `None` when it failed
+    responses: HashMap<usize, Option<Vec<u16>>>,
+}
+
+impl BlockReads {
+    fn new(
+        device_id: &str,
+        registers: &[&RegisterConfig],
+        config: Option<&ReadBlocksConfig>,
+    ) -> Self {
+        let blocks = config.map_or_else(Vec::new, |config| reader::plan_blocks(registers, config));
+        let member_of = blocks
+            .iter()
+            .enumerate()
+            .flat_map(|(index, block)| block.members.iter().map(move |m| (m.clone(), index)))
+            .collect();
+        if !blocks.is_empty() {
+            tracing::debug!(
+                device_id = %device_id,
+                "Reading registers of {} in {} blocks",
+                device_id,
+                blocks.len()
+            );
+        }
+        Self {
+            device_id: device_id.to_string(),
+            blocks,
+            member_of,
+            responses: HashMap::new(),
+        }
+    }
+
+    /// Forget the responses of the previous cycle
+    fn start_cycle(&mut self) {
+        self.responses.clear();
+    }
+
+    /// Read `register` from the response of its block, requesting the block
+    /// the first time one of its registers is read in a cycle
+    ///
+    /// `None` when the register is read on its own: it is not in a block, or
+    /// the block read failed. A block the device answers with an exception
+    /// is split up for good, as it probably spans addresses the device does
+    /// not have. Connection errors are returned, as they fail every read.
+    async fn read(
+        &mut self,
+        client: &mut crate::modbus::ModbusClient,
+        register: &RegisterConfig,
+        traffic: &mut CycleTraffic,
+        rtu: bool,
+    ) -> Option<Result<Vec<u16>>> {
+        let &index = self.member_of.get(&register.name)?;
+        let block = &self.blocks[index];
+        if !self.responses.contains_key(&index) {
+            let read = client.read_registers(&block.request).await;
+            traffic.record(reader::frame_bytes(&block.request, rtu), read.is_ok());
+            let response = match read {
+                Ok(raw) => Some(raw),
+                Err(e) => match ErrorKind::classify(&e) {
+                    ErrorKind::Connection | ErrorKind::Transport => return Some(Err(e)),
+                    ErrorKind::Exception => {
+                        tracing::warn!(
+                            device_id = %self.device_id,
+                            "Device {} refused block read {} ({}), reading its registers one by one",
+                            self.device_id,
+                            block.request.name,
+                            e
+                        );
+                        for member in &block.members {
+                            self.member_of.remove(member);
+                        }
+                        None
+                    }
+                    _ => None,
+                },
+            };
+            self.responses.insert(index, response);
+        }
+        let raw = self.responses[&index].as_ref()?;
+        block.slice(raw, register).map(Ok)
+    }
+}
+
+/// Most FC24 reads per cycle, for a FIFO that refills as fast as it is read
+const MAX_FIFO_READS: usize = 8;
+
+/// Read a FIFO queue until it is empty and return the entries, oldest first
+async fn drain_fifo(
+    client: &mut crate::modbus::ModbusClient,
+    register: &RegisterConfig,
+) -> Result<Vec<u16>> {
+    let mut entries = Vec::new();
+    for _ in 0..MAX_FIFO_READS {
+        match client.read_fifo_queue(register.address).await {
+            Ok(batch) => {
+                let full = batch.len() == 31;
+                entries.extend(batch);
+                if !full {
+                    break;
+                }
+            }
+            // Publish what was drained; the error shows up again next cycle
+            Err(_) if !entries.is_empty() => break,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(entries)
+}
+
+/// Skips low-priority registers while a device's poll cycles overrun
+///
+/// Every overrunning cycle sheds one more priority (`low`, then `normal`);
+/// every cycle finishing in under half the interval takes one back. `high`
+/// registers are always read.
+#[derive(Debug, Default)]
+struct LoadShedding {
+    level: u8,
+}
+
+impl LoadShedding {
+    /// Whether registers of this priority are read in the next cycle
+    fn reads(&self, priority: Priority) -> bool {
+        match self.level {
+            0 => true,
+            1 => priority >= Priority::Normal,
+            _ => priority == Priority::High,
+        }
+    }
+
+    /// Adjust to a finished cycle; true if the level changed
+    fn record_cycle(&mut self, cycle_ms: u64, poll_interval_ms: u64) -> bool {
+        let before = self.level;
+        if cycle_ms > poll_interval_ms {
+            self.level = (self.level + 1).min(2);
+        } else if cycle_ms * 2 < poll_interval_ms {
+            self.level = self.level.saturating_sub(1);
+        }
+        self.level != before
+    }
+
+    fn describe(&self) -> &'static str {
+        match self.level {
+            0 => "no",
+            1 => "low",
+            _ => "low and normal",
+        }
+    }
+}
+
+/// Time from `now` until the next multiple of `period` since the Unix epoch,
+/// where the polls of an `align_to_clock` device start
+fn until_clock_boundary(now: SystemTime, period: Duration) -> Duration {
+    let since_epoch = now
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default();
+    let period_ns = period.as_nanos().max(1);
+    let remaining = period_ns - since_epoch.as_nanos() % period_ns;
+    Duration::from_nanos(remaining as u64)
+}
+
+/// Deferring `low` and `normal` registers once a cycle exceeds `cycle_budget_ms`
+///
+/// A register deferred in one cycle is read in the next regardless of the
+/// budget, so a slow device delays its low-priority data instead of starving it.
+struct CycleBudget {
+    budget: Option<Duration>,
+    /// Deferred by the previous cycle
+    due: HashSet<String>,
+    /// Deferred by the current cycle
+    deferred: HashSet<String>,
+}
+
+impl CycleBudget {
+    fn new(budget_ms: Option<u64>) -> Self {
+        Self {
+            budget: budget_ms.map(Duration::from_millis),
+            due: HashSet::new(),
+            deferred: HashSet::new(),
+        }
+    }
+
+    /// Whether to skip the register this far into the cycle
+    fn defers(&mut self, register: &RegisterConfig, elapsed: Duration) -> bool {
+        let over = self.budget.is_some_and(|budget| elapsed >= budget);
+        if !over || register.priority == Priority::High || self.due.contains(&register.name) {
+            return false;
+        }
+        self.deferred.insert(register.name.clone());
+        true
+    }
+
+    /// Start over for the next cycle, returning how many registers were deferred
+    fn finish_cycle(&mut self) -> usize {
+        self.due = std::mem::take(&mut self.deferred);
+        self.due.len()
+    }
+}
+
+/// Per-register circuit breakers of a device, see [`CircuitBreakerConfig`]
+struct CircuitBreakers {
+    config: CircuitBreakerConfig,
+    registers: HashMap<String, Breaker>,
+}
+
+/// Identical exceptions of one register in a row
+struct Breaker {
+    error: String,
+    failures: u32,
+    backoff: Duration,
+    /// Set while the breaker is open
+    open_until: Option<Instant>,
+}
+
+impl CircuitBreakers {
+    fn new(config: CircuitBreakerConfig) -> Self {
+        Self {
+            config,
+            registers: HashMap::new(),
+        }
+    }
+
+    /// Whether the register is read now: its breaker is closed or due for a probe
+    fn allows(&self, register: &str, now: Instant) -> bool {
+        self.registers
+            .get(register)
+            .and_then(|breaker| breaker.open_until)
+            .is_none_or(|until| now >= until)
+    }
+
+    /// Reset after a successful read; true if the breaker was open
+    fn record_success(&mut self, register: &str) -> bool {
+        self.registers
+            .remove(register)
+            .is_some_and(|breaker| breaker.open_until.is_some())
+    }
+
+    /// Count a Modbus exception, returning the wait until the next probe when
+    /// the breaker opens or a probe failed
+    fn record_exception(&mut self, register: &str, error: &str, now: Instant) -> Option<Duration> {
+        if self.config.failures == 0 {
+            return None;
+        }
+        let initial = Duration::from_millis(self.config.backoff_ms);
+        let fresh = || Breaker {
+            error: error.to_string(),
+            failures: 0,
+            backoff: initial,
+            open_until: None,
+        };
+        let breaker = self
+            .registers
+            .entry(register.to_string())
+            .or_insert_with(fresh);
+        if breaker.error != error {
+            *breaker = fresh();
+        }
+        breaker.failures += 1;
+        if breaker.failures < self.config.failures {
+            return None;
+        }
+        if breaker.open_until.is_some() {
+            let max = Duration::from_millis(self.config.max_backoff_ms);
+            breaker.backoff = (breaker.backoff * 2).min(max);
+        }
+        breaker.open_until = Some(now + breaker.backoff);
+        Some(breaker.backoff)
+    }
+}
+
+/// Waits between connection attempts of a device, see [`ReconnectConfig`]
+struct ReconnectBackoff {
+    config: ReconnectConfig,
+    /// Failed attempts in a row
+    failures: u32,
+    backoff: Duration,
+    next_attempt: Option<Instant>,
+}
+
+impl ReconnectBackoff {
+    fn new(config: ReconnectConfig) -> Self {
+        Self {
+            config,
+            failures: 0,
+            backoff: Duration::ZERO,
+            next_attempt: None,
+        }
+    }
+
+    fn gave_up(&self) -> bool {
+        self.config.max_retries > 0 && self.failures >= self.config.max_retries
+    }
+
+    /// Whether a connection attempt is due
+    fn due(&self, now: Instant) -> bool {
+        !self.gave_up() && self.next_attempt.is_none_or(|at| now >= at)
+    }
+
+    /// Count a failed attempt, returning the wait before the next one or
+    /// `None` once out of retries
+    fn record_failure(&mut self, now: Instant) -> Option<Duration> {
+        self.failures += 1;
+        if self.gave_up() {
+            self.next_attempt = None;
+            return None;
+        }
+        let max = Duration::from_millis(self.config.max_backoff_ms);
+        self.backoff = if self.failures == 1 {
+            Duration::from_millis(self.config.backoff_ms)
+        } else {
+            (self.backoff * 2).min(max)
+        };
+        self.next_attempt = Some(now + self.backoff);
+        Some(self.backoff)
+    }
+
+    /// Start over after connecting
+    fn reset(&mut self) {
+        self.failures = 0;
+        self.next_attempt = None;
+    }
+}
+
+/// Message for a serial device node that no longer exists, e.g. an unplugged USB adapter
+///
+/// The port is reopened (and a USB match re-resolved) on the next reconnect attempt.
+fn serial_port_gone(client: &crate::modbus::ModbusClient) -> Option<String> {
+    let port = client.serial_port()?;
+    (cfg!(unix) && !std::path::Path::new(port).exists())
+        .then(|| format!("Serial port {} disappeared", port))
+}
+
+/// Minimum time between repeated overrun warnings for a device
+const OVERRUN_WARNING_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Limits a recurring warning to once per interval
+struct RateLimitedWarning {
+    interval: Duration,
+    last_emitted: Option<Instant>,
+    suppressed: u64,
+}
+
+impl RateLimitedWarning {
+    fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            last_emitted: None,
+            suppressed: 0,
+        }
+    }
+
+    /// Returns the number of suppressed occurrences if the warning should be emitted now
+    fn check(&mut self) -> Option<u64> {
+        let due = self
+            .last_emitted
+            .is_none_or(|last| last.elapsed() >= self.interval);
+
+        if due {
+            self.last_emitted = Some(Instant::now());
+            Some(std::mem::take(&mut self.suppressed))
+        } else {
+            self.suppressed += 1;
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "http-api")]
+    #[tokio::test]
+    async fn test_start_and_shutdown() {
+        let mut config = Config::default();
+        config.server.host = "127.0.0.1".to_string();
+        config.server.port = 0;
+        config.server.metrics_enabled = false;
+
+        let handle = Bridge::new(config).await.unwrap().start().await.unwrap();
+        let addr = handle.local_addr().unwrap();
+        assert_ne!(addr.port(), 0);
+        assert!(tokio::net::TcpStream::connect(addr).await.is_ok());
+        assert!(handle.register_store().read().await.is_empty());
+
+        handle.shutdown().await.unwrap();
+        assert!(tokio::net::TcpStream::connect(addr).await.is_err());
+    }
+
+    #[cfg(feature = "http-api")]
+    #[tokio::test]
+    async fn test_restart_with_metrics() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let mut config = Config::default();
+        config.server.host = "127.0.0.1".to_string();
+        config.server.port = 0;
+        config.server.metrics_enabled = true;
+
+        // The second start reuses the recorder installed by the first
+        for _ in 0..2 {
+            let handle = Bridge::new(config.clone())
+                .await
+                .unwrap()
+                .start()
+                .await
+                .unwrap();
+            let mut stream = tokio::net::TcpStream::connect(handle.local_addr().unwrap())
+                .await
+                .unwrap();
+            stream
+                .write_all(b"GET /metrics HTTP/1.0\r\n\r\n")
+                .await
+                .unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).await.unwrap();
+            assert!(response.starts_with("HTTP/1.0 200"), "{}", response);
+            handle.shutdown().await.unwrap();
+        }
+    }
+
+    #[cfg(feature = "http-api")]
+    #[tokio::test]
+    async fn test_persisted_values_survive_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("values.json");
+        let yaml = format!(
+            "{}snapshot: {{ persist: {} }}\n",
+            config_yaml(&[device_yaml("plc-001", &["setpoint"])]),
+            path.display()
+        );
+        let config = crate::config::load_config_from_str(&yaml).unwrap();
+
+        let handle = Bridge::new(config.clone())
+            .await
+            .unwrap()
+            .start()
+            .await
+            .unwrap();
+        handle
+            .register_store()
+            .write()
+            .await
+            .entry("plc-001".to_string())
+            .or_default()
+            .insert(
+                "setpoint".to_string(),
+                RegisterValue {
+                    name: "setpoint".into(),
+                    raw: vec![42],
+                    value: 42.0,
+                    quality: Default::default(),
+                    restored: false,
+                    unit: None,
+                    timestamp: chrono::Utc::now(),
+                    meta: Default::default(),
+                },
+            );
+        handle.shutdown().await.unwrap();
+        assert!(path.exists());
+
+        let handle = Bridge::new(config).await.unwrap().start().await.unwrap();
+        let store = handle.register_store();
+        let value = store.read().await["plc-001"]["setpoint"].clone();
+        assert_eq!(value.value, 42.0);
+        assert!(value.restored);
+        handle.shutdown().await.unwrap();
+    }
+
+    #[test]
+    fn test_load_shedding() {
+        let mut shedding = LoadShedding::default();
+        assert!(shedding.reads(Priority::Low));
+
+        // Overruns shed one priority at a time, never `high`
+        assert!(shedding.record_cycle(1200, 1000));
+        assert!(!shedding.reads(Priority::Low));
+        assert!(shedding.reads(Priority::Normal));
+        assert!(shedding.record_cycle(1100, 1000));
+        assert!(!shedding.record_cycle(1100, 1000));
+        assert!(!shedding.reads(Priority::Normal));
+        assert!(shedding.reads(Priority::High));
+
+        // Cycles within the interval but above half of it hold the level
+        assert!(!shedding.record_cycle(800, 1000));
+        assert!(shedding.record_cycle(300, 1000));
+        assert!(shedding.reads(Priority::Normal));
+        assert!(shedding.record_cycle(300, 1000));
+        assert!(shedding.reads(Priority::Low));
+        assert_eq!(shedding.describe(), "no");
+    }
+
+    #[test]
+    fn test_until_clock_boundary() {
+        let period = Duration::from_secs(15);
+        let at = |secs: u64, millis: u64| {
+            SystemTime::UNIX_EPOCH + Duration::from_secs(secs) + Duration::from_millis(millis)
+        };
+        // 12:00:07.250 waits for 12:00:15
+        assert_eq!(
+            until_clock_boundary(at(43_207, 250), period),
+            Duration::from_millis(7_750)
+        );
+        // Right on a boundary, the next one is a full period away
+        assert_eq!(until_clock_boundary(at(43_200, 0), period), period);
+        assert_eq!(
+            until_clock_boundary(at(43_214, 999), period),
+            Duration::from_millis(1)
+        );
+    }
+
+    #[test]
+    fn test_cycle_budget() {
+        let register = |name: &str, priority| {
+            let mut register: RegisterConfig = serde_yaml::from_str(&format!(
+                "{{ name: {}, address: 0, register_type: holding, count: 1, data_type: u16 }}",
+                name
+            ))
+            .unwrap();
+            register.priority = priority;
+            register
+        };
+        let alarm = register("alarm", Priority::High);
+        let level = register("level", Priority::Normal);
+        let version = register("version", Priority::Low);
+        let early = Duration::from_millis(100);
+        let late = Duration::from_millis(600);
+
+        let mut budget = CycleBudget::new(Some(500));
+        assert!(!budget.defers(&level, early));
+        assert!(!budget.defers(&alarm, late));
+        assert!(budget.defers(&version, late));
+        assert_eq!(budget.finish_cycle(), 1);
+
+        // Deferred registers are read in the next cycle, others still wait
+        assert!(!budget.defers(&version, late));
+        assert!(budget.defers(&level, late));
+        assert_eq!(budget.finish_cycle(), 1);
+        assert!(budget.defers(&version, late));
+        assert_eq!(budget.finish_cycle(), 1);
+
+        let mut unlimited = CycleBudget::new(None);
+        assert!(!unlimited.defers(&version, Duration::from_secs(60)));
+        assert_eq!(unlimited.finish_cycle(), 0);
+    }
+
+    #[test]
+    fn test_reconnect_backoff() {
+        let mut backoff = ReconnectBackoff::new(ReconnectConfig {
+            backoff_ms: 1000,
+            max_backoff_ms: 3000,
+            max_retries: 4,
+        });
+        let start = Instant::now();
+        let ms = Duration::from_millis;
+        assert!(backoff.due(start));
+
+        assert_eq!(backoff.record_failure(start), Some(ms(1000)));
+        assert!(!backoff.due(start + ms(999)));
+        assert!(backoff.due(start + ms(1000)));
+        assert_eq!(backoff.record_failure(start), Some(ms(2000)));
+        assert_eq!(backoff.record_failure(start), Some(ms(3000)));
+        // Out of retries, nothing is due anymore
+        assert_eq!(backoff.record_failure(start), None);
+        assert!(!backoff.due(start + ms(60_000)));
+
+        backoff.reset();
+        assert!(backoff.due(start));
+        assert_eq!(backoff.record_failure(start), Some(ms(1000)));
+    }
+
+    #[test]
+    fn test_setpoints() {
+        let register = |yaml: &str| -> RegisterConfig { serde_yaml::from_str(yaml).unwrap() };
+        let speed = register(
+            "{ name: speed, address: 0, register_type: holding, count: 1, data_type: u16, persistent: true }",
+        );
+        let reset =
+            register("{ name: reset, address: 1, register_type: coil, count: 1, data_type: bool }");
+        let mut setpoints = Setpoints::default();
+        setpoints.written(&reset, &[1]);
+        setpoints.lost();
+        // Nothing persistent was written
+        assert!(!setpoints.answered(3, 0));
+
+        setpoints.written(&speed, &[1200]);
+        setpoints.written(&speed, &[1500]);
+        assert_eq!(setpoints.values.len(), 1);
+        assert_eq!(setpoints.values["speed"], [1500]);
+        assert!(!setpoints.answered(3, 0));
+
+        // A silent device is offline until a read succeeds again, then due once
+        assert!(!setpoints.answered(0, 3));
+        assert!(!setpoints.answered(0, 0));
+        assert!(setpoints.answered(1, 2));
+        assert!(!setpoints.answered(3, 0));
+
+        setpoints.lost();
+        assert!(setpoints.answered(3, 0));
+    }
+
+    #[test]
+    fn test_circuit_breaker() {
+        let mut breakers = CircuitBreakers::new(CircuitBreakerConfig {
+            failures: 3,
+            backoff_ms: 1000,
+            max_backoff_ms: 3000,
+        });
+        let start = Instant::now();
+        let illegal = "Modbus exception: IllegalDataAddress";
+
+        assert_eq!(breakers.record_exception("a", illegal, start), None);
+        // A different error starts the count over
+        assert_eq!(
+            breakers.record_exception("a", "Modbus exception: ServerDeviceBusy", start),
+            None
+        );
+        assert_eq!(breakers.record_exception("a", illegal, start), None);
+        assert_eq!(breakers.record_exception("a", illegal, start), None);
+        assert!(breakers.allows("a", start));
+        assert_eq!(
+            breakers.record_exception("a", illegal, start),
+            Some(Duration::from_secs(1))
+        );
+        assert!(!breakers.allows("a", start));
+        assert!(breakers.allows("b", start));
+
+        // Failed probes double the wait up to the maximum
+        let probe = start + Duration::from_secs(1);
+        assert!(breakers.allows("a", probe));
+        assert_eq!(
+            breakers.record_exception("a", illegal, probe),
+            Some(Duration::from_secs(2))
+        );
+        assert_eq!(
+            breakers.record_exception("a", illegal, probe),
+            Some(Duration::from_secs(3))
+        );
+
+        assert!(breakers.record_success("a"));
+        assert!(breakers.allows("a", probe));
+        assert!(!breakers.record_success("a"));
+    }
+
+    fn device_yaml(id: &str, registers: &[&str]) -> String {
+        let registers: Vec<String> = registers
+            .iter()
+            .enumerate()
+            .map(|(address, name)| {
+                format!(
+                    "      - {{ name: {}, address: {}, register_type: holding, count: 1, data_type: u16 }}",
+                    name, address
+                )
+            })
+            .collect();
+        format!(
+            "  - id: {}\n    name: {}\n    device_type: tcp\n    connection: {{ host: 127.0.0.1, port: 1, unit_id: 1 }}\n    poll_interval_ms: 60000\n    registers:\n{}\n",
+            id,
+            id,
+            registers.join("\n")
+        )
+    }
+
+    async fn set_value(store: &RegisterStore, device_id: &str, register: &str, value: f64) {
+        store
+            .write()
+            .await
+            .entry(device_id.to_string())
+            .or_default()
+            .insert(
+                register.to_string(),
+                RegisterValue {
+                    name: register.into(),
+                    raw: vec![value as u16],
+                    value,
+                    quality: Default::default(),
+                    restored: false,
+                    unit: None,
+                    timestamp: chrono::Utc::now(),
+                    meta: Default::default(),
+                },
+            );
+    }
+
+    fn config_yaml(devices: &[String]) -> String {
+        format!(
+            "server: {{ host: 127.0.0.1, port: 0, metrics_enabled: false }}\ndevices:\n{}",
+            devices.concat()
+        )
+    }
+
+    #[test]
+    fn test_resolve_write() {
+        let yaml = config_yaml(&[device_yaml("plc-001", &["temperature", "setpoint"])])
+            .replace(
+                "address: 1, register_type: holding",
+                "address: 1, register_type: holding, scale: 0.1",
+            )
+            .replace(
+                "address: 0, register_type: holding",
+                "address: 0, register_type: input",
+            );
+        let config = crate::config::load_config_from_str(&yaml).unwrap();
+
+        assert_eq!(
+            resolve_write(&config, "plc-001", "setpoint", 23.5).map(|(_, outcome)| outcome),
+            Ok(WriteOutcome {
+                address: 1,
+                raw: vec![235],
+                read_back: None,
+            })
+        );
+        assert!(matches!(
+            resolve_write(&config, "plc-001", "temperature", 1.0),
+            Err(WriteError::Rejected(e)) if e.contains("read-only")
+        ));
+        assert!(resolve_write(&config, "plc-001", "setpoint", -1.0).is_err());
+        assert!(resolve_write(&config, "meter", "setpoint", 1.0).is_err());
+    }
+
+    #[test]
+    fn test_write_limits() {
+        let yaml = config_yaml(&[device_yaml("plc-001", &["setpoint"])]).replace(
+            "data_type: u16 }",
+            "data_type: u16, write_limits: { min: 10, max: 30, min_interval_ms: 1000 } }",
+        );
+        let config = crate::config::load_config_from_str(&yaml).unwrap();
+
+        assert!(resolve_write(&config, "plc-001", "setpoint", 30.0).is_ok());
+        assert!(matches!(
+            resolve_write(&config, "plc-001", "setpoint", 31.0),
+            Err(WriteError::Rejected(e)) if e.contains("allowed range 10..30")
+        ));
+
+        let request = |source: WriteSource| WriteRequest {
+            device_id: "plc-001".to_string(),
+            register_name: "setpoint".to_string(),
+            source,
+            value: 20.0,
+            response_tx: tokio::sync::oneshot::channel().0,
+        };
+        let api = request(WriteSource::api(Some("key-1")));
+        let rule = request(WriteSource::Rule {
+            name: "night".to_string(),
+        });
+        let setpoint = &config.devices[0].registers[0];
+        let limiter = WriteLimiter::default();
+        let start = Instant::now();
+
+        // Checking alone does not use up the interval
+        assert!(limiter.check(setpoint, &api, start).is_ok());
+        assert!(limiter.check(setpoint, &api, start).is_ok());
+        limiter.record(&api, start);
+        assert!(matches!(
+            limiter.check(setpoint, &api, start + Duration::from_millis(400)),
+            Err(WriteError::RateLimited(e)) if e.contains("retry in 600 ms")
+        ));
+        // Other sources have their own budget
+        assert!(limiter.check(setpoint, &rule, start).is_ok());
+        assert!(limiter
+            .check(setpoint, &api, start + Duration::from_millis(1000))
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_write_handler_keeps_order() {
+        let yaml = config_yaml(&[device_yaml("plc-001", &["running", "setpoint"])]).replace(
+            "address: 1, register_type: holding, count: 1, data_type: u16",
+            "address: 1, register_type: holding, count: 1, data_type: u16, write_limits: { require: [{ register: running, equals: 0 }] }",
+        );
+        let config = crate::config::load_config_from_str(&yaml).unwrap();
+        let store = RegisterStore::default();
+        set_value(&store, "plc-001", "running", 0.0).await;
+        let (writer, mut device) = tokio::sync::mpsc::channel::<DeviceWrite>(4);
+        let writers = DeviceWriters::default();
+        writers.insert("plc-001", writer);
+        let handler = WriteHandler {
+            gate: WriteGate::new(store.clone()),
+            writers,
+            audit_log: AuditLog::in_memory(16),
+            events: broadcast::channel(16).0,
+        };
+        let (write_tx, write_rx) = tokio::sync::mpsc::channel(16);
+        let enabled = Arc::new(std::sync::atomic::AtomicBool::new(true));
+        let handle = tokio::spawn(handler.run(write_rx, watch::channel(config).1, enabled));
+        let write = |register: &str, value: f64| {
+            let (response_tx, response_rx) = tokio::sync::oneshot::channel();
+            let request = WriteRequest {
+                device_id: "plc-001".to_string(),
+                register_name: register.to_string(),
+                source: WriteSource::api(None),
+                value,
+                response_tx,
+            };
+            (request, response_rx)
+        };
+
+        let (setpoint, setpoint_rx) = write("setpoint", 20.0);
+        let (running, running_rx) = write("running", 1.0);
+        let (late, late_rx) = write("setpoint", 25.0);
+        for request in [setpoint, running, late] {
+            write_tx.send(request).await.unwrap();
+        }
+
+        // Each write waits until the one before it was answered
+        let first = device.recv().await.unwrap();
+        assert_eq!(first.register.name, "setpoint");
+        let waiting = tokio::time::timeout(Duration::from_millis(50), device.recv()).await;
+        assert!(waiting.is_err());
+        first.response_tx.send(Ok(Some(20.0))).unwrap();
+        assert_eq!(setpoint_rx.await.unwrap().unwrap().read_back, Some(20.0));
+
+        let second = device.recv().await.unwrap();
+        assert_eq!(second.register.name, "running");
+        set_value(&store, "plc-001", "running", 1.0).await;
+        second.response_tx.send(Ok(Some(1.0))).unwrap();
+        assert!(running_rx.await.unwrap().is_ok());
+
+        // The setpoint queued behind the start sees the interlock broken
+        assert_eq!(
+            late_rx.await.unwrap(),
+            Err(WriteError::Rejected(
+                "Interlock not met: plc-001:running is 1".to_string()
+            ))
+        );
+        assert!(device.try_recv().is_err());
+
+        // Stopping the handler stops the device workers
+        handle.abort();
+        let _ = handle.await;
+        assert!(device.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_write_gate_rate_limit() {
+        let yaml = config_yaml(&[device_yaml("plc-001", &["running", "setpoint"])]).replace(
+            "address: 1, register_type: holding, count: 1, data_type: u16",
+            "address: 1, register_type: holding, count: 1, data_type: u16, write_limits: { min_interval_ms: 60000, require: [{ register: running, equals: 0 }] }",
+        );
+        let config = crate::config::load_config_from_str(&yaml).unwrap();
+        let store = RegisterStore::default();
+        set_value(&store, "plc-001", "running", 1.0).await;
+        let gate = WriteGate::new(store.clone());
+        let (writer, mut device) = tokio::sync::mpsc::channel::<DeviceWrite>(4);
+        let write = |value: f64| {
+            let request = WriteRequest {
+                device_id: "plc-001".to_string(),
+                register_name: "setpoint".to_string(),
+                source: WriteSource::api(None),
+                value,
+                response_tx: tokio::sync::oneshot::channel().0,
+            };
+            let resolved = resolve_write(&config, "plc-001", "setpoint", value);
+            let (gate, writer) = (gate.clone(), writer.clone());
+            tokio::spawn(async move { gate.write(&request, resolved, Some(writer)).await.1 })
+        };
+
+        // Neither a write blocked by an interlock nor one the device
+        // refused uses up the interval
+        assert!(matches!(
+            write(20.0).await.unwrap(),
+            Err(WriteError::Rejected(e)) if e.starts_with("Interlock not met")
+        ));
+        set_value(&store, "plc-001", "running", 0.0).await;
+        let failed = write(20.0);
+        let refused = device.recv().await.unwrap();
+        let _ = refused.response_tx.send(Err(RustbridgeError::Timeout));
+        assert!(matches!(failed.await.unwrap(), Err(WriteError::Failed(_))));
+
+        let written = write(20.0);
+        let _ = device.recv().await.unwrap().response_tx.send(Ok(None));
+        assert!(written.await.unwrap().is_ok());
+        assert!(matches!(
+            write(21.0).await.unwrap(),
+            Err(WriteError::RateLimited(_))
+        ));
+        assert!(device.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_device_write_splits_typed_values() {
+        let yaml = config_yaml(&[device_yaml("plc-001", &["mode", "setpoint"])]).replace(
+            "address: 1, register_type: holding, count: 1, data_type: u16",
+            "address: 1, register_type: holding, count: 2, data_type: f32, byte_order: cdab",
+        );
+        let config = crate::config::load_config_from_str(&yaml).unwrap();
+        let mock = crate::modbus::transport::MockTransport::new();
+        let mut client = crate::modbus::ModbusClient::with_transport("plc-001", mock.clone());
+
+        let device = &config.devices[0];
+        let ctx = context(&config);
+        let mut updates = ctx.broadcaster.subscribe();
+
+        for (register, value) in [("mode", 3.0), ("setpoint", 21.5)] {
+            let (register, outcome) = resolve_write(&config, "plc-001", register, value).unwrap();
+            let write = DeviceWrite {
+                register,
+                raw: outcome.raw,
+                response_tx: tokio::sync::oneshot::channel().0,
+            };
+            // Confirmed by reading the register back
+            let read_back = write.execute(&mut client, device, &ctx).await.unwrap();
+            assert_eq!(read_back, Some(value));
+            assert_eq!(updates.try_recv().unwrap().value, value);
+        }
+
+        assert_eq!(mock.holding(0), Some(3));
+        // 21.5f32 = 0x41AC0000, low word first
+        assert_eq!(mock.holding(1), Some(0x0000));
+        assert_eq!(mock.holding(2), Some(0x41AC));
+    }
+
+    fn context(config: &Config) -> PollingContext {
+        PollingContext {
+            store: Default::default(),
+            broadcaster: broadcast::channel(16).0,
+            blocking_sinks: Vec::new(),
+            diagnostics: Default::default(),
+            error_log: ErrorLog::default(),
+            events: crate::events::channel(),
+            controls: DeviceControls::new(&config.devices),
+            heartbeats: Heartbeats::default(),
+            writers: DeviceWriters::default(),
+            names: NameCache::default(),
+            debounce: Debounce::default(),
+            stats: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_publish_value_min_stable() {
+        let yaml = config_yaml(&[device_yaml("plc-001", &["door"])]).replace(
+            "register_type: holding, count: 1, data_type: u16",
+            "register_type: discrete, count: 1, data_type: bool, min_stable_ms: 50",
+        );
+        let config = crate::config::load_config_from_str(&yaml).unwrap();
+        let device = &config.devices[0];
+        let door = &device.registers[0];
+        let ctx = context(&config);
+        let mut updates = ctx.broadcaster.subscribe();
+
+        // The first reading is taken as it is
+        assert_eq!(ctx.publish_value(device, door, vec![0]).await, Some(0.0));
+        updates.try_recv().unwrap();
+
+        // A bounce is neither stored nor published
+        assert_eq!(ctx.publish_value(device, door, vec![1]).await, Some(0.0));
+        assert_eq!(ctx.publish_value(device, door, vec![0]).await, Some(0.0));
+        assert!(!updates.try_recv().unwrap().changed);
+        assert_eq!(ctx.publish_value(device, door, vec![1]).await, Some(0.0));
+        assert!(updates.try_recv().is_err());
+
+        // A change that held is
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert_eq!(ctx.publish_value(device, door, vec![1]).await, Some(1.0));
+        let update = updates.try_recv().unwrap();
+        assert!(update.changed);
+        assert_eq!(update.previous_value, Some(0.0));
+    }
+
+    #[tokio::test]
+    async fn test_publish_points() {
+        let yaml = config_yaml(&[device_yaml("plc-001", &["outputs"])]).replace(
+            "register_type: holding, count: 1, data_type: u16",
+            "register_type: coil, count: 64, data_type: bool, \
+             points: [{ name: pump, offset: 0 }, { name: alarm_horn, offset: 63 }]",
+        );
+        let config = crate::config::load_config_from_str(&yaml).unwrap();
+        let device = &config.devices[0];
+        let outputs = &device.registers[0];
+        let ctx = context(&config);
+        let mut updates = ctx.broadcaster.subscribe();
+
+        let mut bits = vec![0u16; 64];
+        bits[63] = 1;
+        assert_eq!(ctx.publish_points(device, outputs, &bits).await, 2);
+        let pump = updates.try_recv().unwrap();
+        assert_eq!(&*pump.register_name, "pump");
+        assert_eq!(pump.value, 0.0);
+        assert!(pump.boolean);
+        let horn = updates.try_recv().unwrap();
+        assert_eq!(&*horn.register_name, "alarm_horn");
+        assert_eq!(horn.value, 1.0);
+        assert!(updates.try_recv().is_err());
+
+        let store = ctx.store.read().await;
+        assert!(store["plc-001"].contains_key("alarm_horn"));
+        assert!(!store["plc-001"].contains_key("outputs"));
+    }
+
+    #[tokio::test]
+    async fn test_publish_value_non_finite() {
+        let yaml = config_yaml(&[device_yaml("plc-001", &["dropped", "nulled", "held"])])
+            .replace("count: 1, data_type: u16", "count: 2, data_type: f32")
+            .replace(
+                "address: 1, register_type: holding",
+                "address: 1, register_type: holding, non_finite: null",
+            )
+            .replace(
+                "address: 2, register_type: holding",
+                "address: 2, register_type: holding, non_finite: last_good",
+            );
+        let config = crate::config::load_config_from_str(&yaml).unwrap();
+        let device = &config.devices[0];
+        let ctx = context(&config);
+        let mut updates = ctx.broadcaster.subscribe();
+        const NAN: [u16; 2] = [0x7FC0, 0x0000];
+        const ONE: [u16; 2] = [0x3F80, 0x0000];
+
+        let [dropped, nulled, held] = &device.registers[..] else {
+            panic!("three registers expected");
+        };
+        assert_eq!(ctx.publish_value(device, dropped, NAN.to_vec()).await, None);
+        // Nothing to substitute before the first good reading
+        assert_eq!(ctx.publish_value(device, held, NAN.to_vec()).await, None);
+        assert!(updates.try_recv().is_err());
+
+        assert!(ctx
+            .publish_value(device, nulled, NAN.to_vec())
+            .await
+            .unwrap()
+            .is_nan());
+        let update = updates.try_recv().unwrap();
+        assert_eq!(update.quality, codec::Quality::Bad);
+        assert!(update.changed);
+        ctx.publish_value(device, nulled, NAN.to_vec()).await;
+        assert!(!updates.try_recv().unwrap().changed);
+
+        ctx.publish_value(device, held, ONE.to_vec()).await;
+        assert_eq!(
+            ctx.publish_value(device, held, NAN.to_vec()).await,
+            Some(1.0)
+        );
+        updates.try_recv().unwrap();
+        let update = updates.try_recv().unwrap();
+        assert_eq!(
+            (update.value, update.quality),
+            (1.0, codec::Quality::Substituted)
+        );
+        assert_eq!(update.raw, NAN.to_vec());
+    }
+
+    #[tokio::test]
+    async fn test_alarm_events() {
+        let yaml = config_yaml(&[device_yaml("boiler", &["temperature"])])
+            .replace("data_type: u16", "data_type: u16, alarm: { above: 80 }");
+        let config = crate::config::load_config_from_str(&yaml).unwrap();
+        let device = &config.devices[0];
+        let register = &device.registers[0];
+        let ctx = context(&config);
+        let mut events = ctx.events.subscribe();
+
+        for raw in [70, 85, 90, 75, 60] {
+            ctx.publish_value(device, register, vec![raw]).await;
+        }
+        let raised = events.try_recv().unwrap();
+        assert_eq!(raised.event, ConnectionEventKind::AlarmRaised);
+        assert_eq!(raised.register.as_deref(), Some("temperature"));
+        assert_eq!(raised.value, Some(85.0));
+        let cleared = events.try_recv().unwrap();
+        assert_eq!(cleared.event, ConnectionEventKind::AlarmCleared);
+        assert_eq!(cleared.value, Some(75.0));
+        assert!(cleared.id > raised.id);
+        assert!(events.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_drain_fifo_publishes_entries() {
+        let yaml = config_yaml(&[device_yaml("recorder", &["log"])])
+            .replace("register_type: holding", "register_type: fifo, scale: 0.5");
+        let config = crate::config::load_config_from_str(&yaml).unwrap();
+        let device = &config.devices[0];
+        let register = &device.registers[0];
+        let mock = crate::modbus::transport::MockTransport::new();
+        let mut client = crate::modbus::ModbusClient::with_transport("recorder", mock.clone());
+
+        // 40 entries take two reads, the second one not full
+        let queued: Vec<u16> = (1..=40).collect();
+        mock.push_fifo(0, &queued);
+        assert_eq!(drain_fifo(&mut client, register).await.unwrap(), queued);
+        assert_eq!(mock.requests(), 2);
+        assert!(drain_fifo(&mut client, register).await.unwrap().is_empty());
+
+        let ctx = context(&config);
+        let mut events = ctx.events.subscribe();
+        ctx.publish_fifo_entries(device, register, vec![4, 6]);
+        for expected in [2.0, 3.0] {
+            let event = events.try_recv().unwrap();
+            assert_eq!(event.event, ConnectionEventKind::FifoEntry);
+            assert_eq!(event.register.as_deref(), Some("log"));
+            assert_eq!(event.value, Some(expected));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reload_applies_device_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.yaml");
+        let path = path.to_str().unwrap();
+        std::fs::write(
+            path,
+            config_yaml(&[
+                device_yaml("plc-001", &["temperature", "pressure"]),
+                device_yaml("meter", &["energy"]),
+            ]),
+        )
+        .unwrap();
+        let mut config = crate::config::load_config(path).unwrap();
+
+        let ctx = context(&config);
+        {
+            let mut store = ctx.store.write().await;
+            for (device_id, register) in [
+                ("plc-001", "temperature"),
+                ("plc-001", "pressure"),
+                ("meter", "energy"),
+            ] {
+                let value = RegisterValue {
+                    name: register.into(),
+                    raw: vec![1],
+                    value: 1.0,
+                    quality: Default::default(),
+                    restored: false,
+                    unit: None,
+                    timestamp: chrono::Utc::now(),
+                    meta: Default::default(),
+                };
+                store
+                    .entry(device_id.to_string())
+                    .or_default()
+                    .insert(register.to_string(), value);
+            }
+        }
+        let mut pollers = DevicePollers::new(ctx.clone());
+        for device in &config.devices {
+            pollers.spawn(device.clone());
+        }
+
+        // An invalid file leaves everything as it was
+        std::fs::write(path, config_yaml(&[device_yaml("plc-001", &["a", "a"])])).unwrap();
+        assert!(pollers.reload(&mut config, path).await.is_err());
+        assert_eq!(config.devices.len(), 2);
+        assert_eq!(pollers.running.len(), 2);
+
+        std::fs::write(
+            path,
+            config_yaml(&[
+                device_yaml("plc-001", &["temperature"]),
+                device_yaml("tank", &["level"]),
+            ]),
+        )
+        .unwrap();
+        let report = pollers.reload(&mut config, path).await.unwrap();
+        assert_eq!(report.devices_added, vec!["tank"]);
+        assert_eq!(report.devices_removed, vec!["meter"]);
+        assert_eq!(
+            report.devices_changed[0].registers_removed,
+            vec!["pressure"]
+        );
+
+        let mut running: Vec<&str> = pollers.running.keys().map(String::as_str).collect();
+        running.sort();
+        assert_eq!(running, vec!["plc-001", "tank"]);
+        assert!(ctx.controls.device("meter").is_none());
+        assert!(ctx.controls.device("tank").is_some());
+
+        let store = ctx.store.read().await;
+        assert!(!store.contains_key("meter"));
+        assert!(store["plc-001"].contains_key("temperature"));
+        assert!(!store["plc-001"].contains_key("pressure"));
+    }
+
+    fn update(device_id: &str, register_name: &str, value: f64) -> RegisterUpdate {
+        RegisterUpdate {
+            device_id: device_id.into(),
+            register_name: register_name.into(),
+            value,
+            raw: vec![],
+            unit: None,
+            timestamp: String::new(),
+            previous_value: None,
+            changed: true,
+            meta: Default::default(),
+            boolean: false,
+            quality: Default::default(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_update_stream_filters() {
+        let (tx, _) = broadcast::channel(16);
+        let mut all = update_stream(tx.subscribe());
+        let mut temperature = update_stream(tx.subscribe())
+            .filter(|u| std::future::ready(&*u.register_name == "temperature"));
+
+        tx.send(update("plc-001", "pressure", 1.0)).unwrap();
+        tx.send(update("plc-001", "temperature", 21.5)).unwrap();
+        drop(tx);
+
+        assert_eq!(&*all.next().await.unwrap().register_name, "pressure");
+        assert_eq!(&*all.next().await.unwrap().register_name, "temperature");
+        assert!(all.next().await.is_none());
+        assert_eq!(temperature.next().await.unwrap().value, 21.5);
+        assert!(temperature.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_update_stream_skips_lag() {
+        let (tx, rx) = broadcast::channel(2);
+        for i in 0..4 {
+            tx.send(update("plc-001", "counter", i as f64)).unwrap();
+        }
+        drop(tx);
+
+        let values: Vec<f64> = update_stream(rx).map(|u| u.value).collect().await;
+        assert_eq!(values, vec![2.0, 3.0]);
+    }
+
+    #[tokio::test]
+    async fn test_blocking_sink_keeps_every_update() {
+        let (sink, mut rx) = BlockingSink::channel("test", 2);
+        // The producer outruns the buffer and has to wait for the receiver
+        let producer = tokio::spawn(async move {
+            for i in 0..5 {
+                sink.send(update("plc-001", "counter", i as f64)).await;
+            }
+        });
+
+        let mut values = Vec::new();
+        while let Some(update) = rx.recv("test").await {
+            values.push(update.value);
+        }
+        producer.await.unwrap();
+        assert_eq!(values, vec![0.0, 1.0, 2.0, 3.0, 4.0]);
     }
 }
@@ -0,0 +1,146 @@
+//! Version and build metadata of the running bridge
+//!
+//! Served by `GET /api/info` and published (retained) on
+//! `{prefix}/bridge/info`, so fleet management can check what is deployed
+//! where: which release, built from which commit, with which features, and
+//! running which configuration.
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::config::Config;
+
+/// Cargo features the binary can be built with
+const FEATURES: &[(&str, bool)] = &[
+    ("mqtt", cfg!(feature = "mqtt")),
+    ("http-api", cfg!(feature = "http-api")),
+    ("rtu", cfg!(feature = "rtu")),
+    ("monitor", cfg!(feature = "monitor")),
+    ("api-client", cfg!(feature = "api-client")),
+    ("tls", cfg!(feature = "tls")),
+    ("embedded", cfg!(feature = "embedded")),
+    ("testing", cfg!(feature = "testing")),
+];
+
+/// What is deployed: release, build and configuration
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BuildInfo {
+    pub version: &'static str,
+    /// Commit the binary was built from, `None` outside a git checkout
+    pub git_hash: Option<&'static str>,
+    /// RFC 3339 time of the build (`SOURCE_DATE_EPOCH` when set)
+    pub build_timestamp: Option<String>,
+    pub features: Vec<&'static str>,
+    /// Hash of the running configuration, see [`config_hash`]
+    pub config_hash: Option<String>,
+}
+
+impl BuildInfo {
+    /// Build information with the hash of `config`, if given
+    pub fn new(config: Option<&Config>) -> Self {
+        let build_time = env!("RUSTBRIDGE_BUILD_TIME").parse::<i64>().ok();
+        Self {
+            version: env!("CARGO_PKG_VERSION"),
+            git_hash: Some(env!("RUSTBRIDGE_GIT_HASH")).filter(|hash| !hash.is_empty()),
+            build_timestamp: build_time
+                .and_then(|secs| chrono::DateTime::from_timestamp(secs, 0))
+                .map(|time| time.to_rfc3339()),
+            features: enabled_features(),
+            config_hash: config.map(config_hash),
+        }
+    }
+}
+
+/// Names of the Cargo features compiled in
+pub fn enabled_features() -> Vec<&'static str> {
+    FEATURES
+        .iter()
+        .filter(|(_, enabled)| *enabled)
+        .map(|(name, _)| *name)
+        .collect()
+}
+
+/// Hash of a configuration, as 16 hex digits
+///
+/// Computed over the parsed settings rather than the file, so formatting,
+/// comments and key order do not change it, and stable across builds and
+/// platforms (64-bit FNV-1a).
+pub fn config_hash(config: &Config) -> String {
+    let mut hasher = Fnv1a::default();
+    match serde_json::to_value(config) {
+        Ok(value) => hash_value(&value, &mut hasher),
+        Err(_) => hasher.write(b"unserializable"),
+    }
+    format!("{:016x}", hasher.0)
+}
+
+struct Fnv1a(u64);
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Fnv1a {
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+}
+
+/// Feed a JSON value to the hasher with object keys sorted
+fn hash_value(value: &Value, hasher: &mut Fnv1a) {
+    match value {
+        Value::Object(map) => {
+            let mut keys: Vec<&String> = map.keys().collect();
+            keys.sort();
+            hasher.write(b"{");
+            for key in keys {
+                hasher.write(key.as_bytes());
+                hasher.write(b":");
+                hash_value(&map[key], hasher);
+                hasher.write(b",");
+            }
+            hasher.write(b"}");
+        }
+        Value::Array(items) => {
+            hasher.write(b"[");
+            for item in items {
+                hash_value(item, hasher);
+                hasher.write(b",");
+            }
+            hasher.write(b"]");
+        }
+        scalar => hasher.write(scalar.to_string().as_bytes()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_info() {
+        let config = Config::default();
+        let info = BuildInfo::new(Some(&config));
+        assert_eq!(info.version, env!("CARGO_PKG_VERSION"));
+        assert!(info.build_timestamp.is_some());
+        assert_eq!(info.features.contains(&"mqtt"), cfg!(feature = "mqtt"));
+        assert_eq!(info.config_hash, Some(config_hash(&config)));
+        assert!(BuildInfo::new(None).config_hash.is_none());
+    }
+
+    #[test]
+    fn test_config_hash() {
+        let mut config = Config::default();
+        let hash = config_hash(&config);
+        assert_eq!(hash.len(), 16);
+        assert_eq!(config_hash(&config.clone()), hash);
+
+        config.server.port += 1;
+        assert_ne!(config_hash(&config), hash);
+    }
+}
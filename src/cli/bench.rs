@@ -0,0 +1,419 @@
+//! `rustbridge bench` - bus throughput benchmark
+//!
+//! Reads the configured register set of one device back-to-back for a fixed
+//! duration and reports requests/sec, latency percentiles and error rates,
+//! to help choose a safe `poll_interval_ms`.
+
+use anyhow::{anyhow, bail, Context, Result};
+use clap::Args;
+use serde::Serialize;
+use std::time::{Duration, Instant};
+use tokio::time::timeout;
+
+use rustbridge::config::{self, DeviceConfig};
+use rustbridge::metrics::error_log::ErrorKind;
+use rustbridge::modbus::ModbusClient;
+
+/// Headroom applied to the p99 cycle time when suggesting a poll interval
+const POLL_INTERVAL_HEADROOM: f64 = 1.5;
+/// Suggested poll intervals are rounded up to a multiple of this
+const POLL_INTERVAL_STEP_MS: u64 = 50;
+
+#[derive(Debug, Args)]
+pub struct BenchArgs {
+    /// Device ID from the configuration file
+    #[arg(long)]
+    pub device: String,
+
+    /// How long to run, e.g. 60s, 500ms, 2m
+    #[arg(long, default_value = "10s", value_parser = parse_duration)]
+    pub duration: Duration,
+
+    /// Per-request timeout in milliseconds
+    #[arg(long, default_value_t = 1000)]
+    pub timeout_ms: u64,
+
+    /// Print the report as JSON
+    #[arg(long)]
+    pub json: bool,
+}
+
+/// Error counts by category
+#[derive(Debug, Default, Serialize)]
+struct ErrorCounts {
+    exception: u64,
+    timeout: u64,
+    connection: u64,
+    transport: u64,
+}
+
+impl ErrorCounts {
+    fn record(&mut self, kind: ErrorKind) {
+        match kind {
+            ErrorKind::Exception => self.exception += 1,
+            ErrorKind::Timeout => self.timeout += 1,
+            ErrorKind::Connection => self.connection += 1,
+            ErrorKind::Transport | ErrorKind::Publish => self.transport += 1,
+        }
+    }
+
+    fn total(&self) -> u64 {
+        self.exception + self.timeout + self.connection + self.transport
+    }
+}
+
+/// Latency percentiles in milliseconds
+#[derive(Debug, Default, PartialEq, Serialize)]
+struct Percentiles {
+    min: f64,
+    p50: f64,
+    p90: f64,
+    p99: f64,
+    max: f64,
+}
+
+impl Percentiles {
+    /// Nearest-rank percentiles of the given samples
+    fn from_samples(samples: &mut [f64]) -> Self {
+        if samples.is_empty() {
+            return Self::default();
+        }
+        samples.sort_by(|a, b| a.total_cmp(b));
+        let rank = |p: f64| {
+            let index = ((p / 100.0) * samples.len() as f64).ceil() as usize;
+            samples[index.clamp(1, samples.len()) - 1]
+        };
+        Self {
+            min: samples[0],
+            p50: rank(50.0),
+            p90: rank(90.0),
+            p99: rank(99.0),
+            max: samples[samples.len() - 1],
+        }
+    }
+}
+
+/// Raw measurements collected during the run
+#[derive(Debug, Default)]
+struct Samples {
+    request_ms: Vec<f64>,
+    cycle_ms: Vec<f64>,
+    errors: ErrorCounts,
+    reconnects: u64,
+}
+
+/// Benchmark result
+#[derive(Debug, Serialize)]
+struct Report {
+    device_id: String,
+    registers: usize,
+    duration_secs: f64,
+    requests: u64,
+    requests_per_sec: f64,
+    error_rate: f64,
+    errors: ErrorCounts,
+    reconnects: u64,
+    latency_ms: Percentiles,
+    cycles: usize,
+    cycle_ms: Percentiles,
+    suggested_poll_interval_ms: Option<u64>,
+}
+
+impl Report {
+    fn new(device: &DeviceConfig, elapsed: Duration, mut samples: Samples) -> Self {
+        let successes = samples.request_ms.len() as u64;
+        let requests = successes + samples.errors.total();
+        let duration_secs = elapsed.as_secs_f64();
+        let cycle_ms = Percentiles::from_samples(&mut samples.cycle_ms);
+
+        Self {
+            device_id: device.id.clone(),
+            registers: device.registers.len(),
+            duration_secs,
+            requests,
+            requests_per_sec: if duration_secs > 0.0 {
+                requests as f64 / duration_secs
+            } else {
+                0.0
+            },
+            error_rate: if requests > 0 {
+                samples.errors.total() as f64 / requests as f64
+            } else {
+                0.0
+            },
+            errors: samples.errors,
+            reconnects: samples.reconnects,
+            latency_ms: Percentiles::from_samples(&mut samples.request_ms),
+            cycles: samples.cycle_ms.len(),
+            suggested_poll_interval_ms: suggest_poll_interval(&cycle_ms, samples.cycle_ms.len()),
+            cycle_ms,
+        }
+    }
+
+    fn render(&self) -> String {
+        let mut out = format!(
+            "Device {} ({} registers), {:.1}s\n\n\
+             Requests:     {} ({:.1} req/s)\n\
+             Errors:       {} ({:.2}%) - exception {}, timeout {}, connection {}, transport {}\n\
+             Reconnects:   {}\n\
+             Latency (ms): min {:.2}  p50 {:.2}  p90 {:.2}  p99 {:.2}  max {:.2}\n\
+             Cycle (ms):   min {:.2}  p50 {:.2}  p90 {:.2}  p99 {:.2}  max {:.2} ({} cycles)\n",
+            self.device_id,
+            self.registers,
+            self.duration_secs,
+            self.requests,
+            self.requests_per_sec,
+            self.errors.total(),
+            self.error_rate * 100.0,
+            self.errors.exception,
+            self.errors.timeout,
+            self.errors.connection,
+            self.errors.transport,
+            self.reconnects,
+            self.latency_ms.min,
+            self.latency_ms.p50,
+            self.latency_ms.p90,
+            self.latency_ms.p99,
+            self.latency_ms.max,
+            self.cycle_ms.min,
+            self.cycle_ms.p50,
+            self.cycle_ms.p90,
+            self.cycle_ms.p99,
+            self.cycle_ms.max,
+            self.cycles,
+        );
+        match self.suggested_poll_interval_ms {
+            Some(ms) => out.push_str(&format!("\nSuggested poll_interval_ms: >= {}\n", ms)),
+            None => {
+                out.push_str("\nNo complete error-free cycles; cannot suggest a poll interval\n")
+            }
+        }
+        out
+    }
+}
+
+/// Run the bench command
+pub async fn run(config_path: &str, args: BenchArgs) -> Result<()> {
+    super::init_quiet_logging()?;
+
+    let config = config::load_config(config_path)?;
+    let device = config
+        .devices
+        .iter()
+        .find(|d| d.id == args.device)
+        .ok_or_else(|| anyhow!("Device '{}' not found in configuration", args.device))?;
+    if device.registers.is_empty() {
+        bail!("Device '{}' has no registers configured", device.id);
+    }
+
+    eprintln!(
+        "Benchmarking {} ({} registers) for {:.1}s...",
+        device.id,
+        device.registers.len(),
+        args.duration.as_secs_f64()
+    );
+
+    let started = Instant::now();
+    let samples = bench(
+        device,
+        args.duration,
+        Duration::from_millis(args.timeout_ms),
+    )
+    .await?;
+    let report = Report::new(device, started.elapsed(), samples);
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        print!("{}", report.render());
+    }
+
+    Ok(())
+}
+
+/// Read the register set back-to-back until the duration elapses
+async fn bench(
+    device: &DeviceConfig,
+    duration: Duration,
+    request_timeout: Duration,
+) -> Result<Samples> {
+    let deadline = Instant::now() + duration;
+    let mut client = Some(
+        timeout(request_timeout, ModbusClient::new(device))
+            .await
+            .map_err(|_| anyhow!("Connection to {} timed out", device.id))?
+            .with_context(|| format!("Failed to connect to {}", device.id))?,
+    );
+    let mut samples = Samples::default();
+
+    while Instant::now() < deadline {
+        let cycle_start = Instant::now();
+        let mut cycle_ok = true;
+
+        for register in &device.registers {
+            let Some(ctx) = client.as_mut() else {
+                break;
+            };
+            let start = Instant::now();
+            let error = match timeout(request_timeout, ctx.read_registers(register)).await {
+                Ok(Ok(_)) => None,
+                Ok(Err(e)) => Some(ErrorKind::classify(&e)),
+                Err(_) => Some(ErrorKind::Timeout),
+            };
+            match error {
+                None => samples
+                    .request_ms
+                    .push(start.elapsed().as_secs_f64() * 1000.0),
+                Some(kind) => {
+                    cycle_ok = false;
+                    samples.errors.record(kind);
+                    if matches!(kind, ErrorKind::Connection | ErrorKind::Transport) {
+                        client = None;
+                    }
+                }
+            }
+        }
+
+        if cycle_ok && client.is_some() {
+            samples
+                .cycle_ms
+                .push(cycle_start.elapsed().as_secs_f64() * 1000.0);
+        }
+
+        if client.is_none() && Instant::now() < deadline {
+            samples.reconnects += 1;
+            match timeout(request_timeout, ModbusClient::new(device)).await {
+                Ok(Ok(reconnected)) => client = Some(reconnected),
+                Ok(Err(_)) => samples.errors.record(ErrorKind::Connection),
+                Err(_) => samples.errors.record(ErrorKind::Timeout),
+            }
+        }
+    }
+
+    Ok(samples)
+}
+
+/// Suggest a poll interval from the p99 of error-free cycle times
+fn suggest_poll_interval(cycle_ms: &Percentiles, cycles: usize) -> Option<u64> {
+    if cycles == 0 {
+        return None;
+    }
+    let ms = (cycle_ms.p99 * POLL_INTERVAL_HEADROOM).ceil() as u64;
+    Some(ms.div_ceil(POLL_INTERVAL_STEP_MS).max(1) * POLL_INTERVAL_STEP_MS)
+}
+
+/// Parse a duration such as "60s", "500ms", "2m" or a plain number of seconds
+fn parse_duration(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit() && c != '.');
+    let (number, unit) = s.split_at(split.unwrap_or(s.len()));
+    let value: f64 = number
+        .parse()
+        .map_err(|_| format!("invalid duration '{}'", s))?;
+    let secs = match unit {
+        "ms" => value / 1000.0,
+        "" | "s" => value,
+        "m" => value * 60.0,
+        "h" => value * 3600.0,
+        _ => {
+            return Err(format!(
+                "invalid duration unit '{}' (use ms, s, m or h)",
+                unit
+            ))
+        }
+    };
+    if secs <= 0.0 {
+        return Err("duration must be positive".to_string());
+    }
+    Ok(Duration::from_secs_f64(secs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustbridge::config::{ConnectionConfig, DeviceType, TcpConnection};
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("60s").unwrap(), Duration::from_secs(60));
+        assert_eq!(parse_duration("500ms").unwrap(), Duration::from_millis(500));
+        assert_eq!(parse_duration("2m").unwrap(), Duration::from_secs(120));
+        assert_eq!(parse_duration("1.5").unwrap(), Duration::from_millis(1500));
+        assert!(parse_duration("10d").is_err());
+        assert!(parse_duration("0s").is_err());
+        assert!(parse_duration("fast").is_err());
+    }
+
+    #[test]
+    fn test_percentiles() {
+        let mut samples: Vec<f64> = (1..=100).rev().map(f64::from).collect();
+        let p = Percentiles::from_samples(&mut samples);
+        assert_eq!(
+            p,
+            Percentiles {
+                min: 1.0,
+                p50: 50.0,
+                p90: 90.0,
+                p99: 99.0,
+                max: 100.0,
+            }
+        );
+        assert_eq!(Percentiles::from_samples(&mut []), Percentiles::default());
+    }
+
+    #[test]
+    fn test_suggest_poll_interval() {
+        let cycle = Percentiles {
+            p99: 120.0,
+            ..Default::default()
+        };
+        assert_eq!(suggest_poll_interval(&cycle, 10), Some(200));
+        assert_eq!(suggest_poll_interval(&Percentiles::default(), 10), Some(50));
+        assert_eq!(suggest_poll_interval(&cycle, 0), None);
+    }
+
+    #[test]
+    fn test_report() {
+        let device = DeviceConfig {
+            id: "plc-001".to_string(),
+            name: "PLC".to_string(),
+            device_type: DeviceType::Tcp,
+            connection: ConnectionConfig::Tcp(TcpConnection {
+                host: "127.0.0.1".to_string(),
+                port: 502,
+                unit_id: 1,
+                ..Default::default()
+            }),
+            poll_interval_ms: 1000,
+            profile: None,
+            registers: vec![],
+            meta: Default::default(),
+            group: None,
+            tenant: None,
+            time_sync: None,
+            circuit_breaker: Default::default(),
+            cycle_budget_ms: None,
+            align_to_clock: false,
+            reconnect: Default::default(),
+            read_blocks: None,
+        };
+        let mut samples = Samples {
+            request_ms: vec![2.0; 18],
+            cycle_ms: vec![4.0; 9],
+            ..Default::default()
+        };
+        samples.errors.record(ErrorKind::Timeout);
+        samples.errors.record(ErrorKind::Exception);
+
+        let report = Report::new(&device, Duration::from_secs(2), samples);
+        assert_eq!(report.requests, 20);
+        assert_eq!(report.requests_per_sec, 10.0);
+        assert_eq!(report.error_rate, 0.1);
+        assert_eq!(report.suggested_poll_interval_ms, Some(50));
+
+        let text = report.render();
+        assert!(text.contains("20 (10.0 req/s)"));
+        assert!(text.contains("timeout 1"));
+        assert!(text.contains("Suggested poll_interval_ms: >= 50"));
+    }
+}
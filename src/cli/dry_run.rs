@@ -0,0 +1,341 @@
+//! `rustbridge --dry-run` - pre-deployment check
+//!
+//! Validates the configuration, connects to every device and the MQTT
+//! broker, performs one poll cycle and prints what would be published
+//! without publishing anything. The exit code tells deployment pipelines
+//! whether the bridge is ready to run.
+
+use anyhow::Result;
+use std::time::Duration;
+use tokio::time::timeout;
+
+use rustbridge::api::RegisterUpdate;
+use rustbridge::config::{self, Config, ConnectionConfig, DeviceConfig};
+use rustbridge::modbus::{codec, ModbusClient};
+#[cfg(feature = "mqtt")]
+use rustbridge::mqtt;
+
+/// Everything checked out
+pub const EXIT_OK: i32 = 0;
+/// Configuration could not be loaded or is invalid
+pub const EXIT_INVALID_CONFIG: i32 = 1;
+/// A device, register or the broker failed
+pub const EXIT_CHECK_FAILED: i32 = 2;
+
+/// Time allowed for each connection attempt and register read
+const CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Outcome of the dry run
+#[derive(Debug, Default)]
+struct Summary {
+    devices_ok: usize,
+    devices_total: usize,
+    registers_ok: usize,
+    registers_total: usize,
+    broker_ok: Option<bool>,
+}
+
+impl Summary {
+    fn exit_code(&self) -> i32 {
+        let all_ok = self.devices_ok == self.devices_total
+            && self.registers_ok == self.registers_total
+            && self.broker_ok != Some(false);
+        if all_ok {
+            EXIT_OK
+        } else {
+            EXIT_CHECK_FAILED
+        }
+    }
+}
+
+/// Run the dry run and return the process exit code
+pub async fn run(config_path: &str) -> Result<i32> {
+    super::init_quiet_logging()?;
+
+    let config = match load_validated(config_path) {
+        Ok(config) => config,
+        Err(e) => {
+            println!("Configuration: FAILED ({})\n{:#}", config_path, e);
+            return Ok(EXIT_INVALID_CONFIG);
+        }
+    };
+    println!(
+        "Configuration: OK ({}, {} devices, {} registers)",
+        config_path,
+        config.devices.len(),
+        config
+            .devices
+            .iter()
+            .map(|d| d.registers.len())
+            .sum::<usize>()
+    );
+
+    let mut summary = Summary {
+        devices_total: config.devices.len(),
+        ..Default::default()
+    };
+
+    summary.broker_ok = check_broker(&config).await;
+
+    for device in &config.devices {
+        check_device(&config, device, &mut summary).await;
+    }
+
+    println!(
+        "\nSummary: {}/{} devices connected, {}/{} registers read, broker {}",
+        summary.devices_ok,
+        summary.devices_total,
+        summary.registers_ok,
+        summary.registers_total,
+        match summary.broker_ok {
+            Some(true) => "OK",
+            Some(false) => "FAILED",
+            None => "disabled",
+        }
+    );
+
+    Ok(summary.exit_code())
+}
+
+fn load_validated(config_path: &str) -> Result<Config> {
+    let config = config::load_config(config_path)?;
+    config.validate()?;
+    Ok(config)
+}
+
+/// Connect to a device, read each register once and print the would-be publications
+async fn check_device(config: &Config, device: &DeviceConfig, summary: &mut Summary) {
+    if matches!(&device.connection, ConnectionConfig::Rtu(rtu) if rtu.listen_only) {
+        println!(
+            "\nDevice {}: skipped (listen_only devices are never polled)",
+            device.id
+        );
+        summary.devices_ok += 1;
+        return;
+    }
+    if device.is_broadcast() {
+        println!(
+            "\nDevice {}: skipped (unit 0 is the broadcast address and cannot be read)",
+            device.id
+        );
+        summary.devices_ok += 1;
+        return;
+    }
+    summary.registers_total += device.registers.len();
+
+    let target = match &device.connection {
+        ConnectionConfig::Tcp(tcp) => format!("TCP {}:{} unit {}", tcp.host, tcp.port, tcp.unit_id),
+        ConnectionConfig::Rtu(rtu) => {
+            format!(
+                "RTU {} @ {} baud unit {}",
+                rtu.port, rtu.baud_rate, rtu.unit_id
+            )
+        }
+    };
+
+    let mut client = match timeout(CHECK_TIMEOUT, ModbusClient::new(device)).await {
+        Ok(Ok(client)) => client,
+        Ok(Err(e)) => {
+            println!("\nDevice {} ({}): FAILED ({:#})", device.id, target, e);
+            return;
+        }
+        Err(_) => {
+            println!(
+                "\nDevice {} ({}): FAILED (connection timed out)",
+                device.id, target
+            );
+            return;
+        }
+    };
+    println!("\nDevice {} ({}): connected", device.id, target);
+    summary.devices_ok += 1;
+
+    for register in &device.registers {
+        let raw = match timeout(CHECK_TIMEOUT, client.read_registers(register)).await {
+            Ok(Ok(raw)) => raw,
+            Ok(Err(e)) => {
+                println!("  FAILED {}: {:#}", register.name, e);
+                continue;
+            }
+            Err(_) => {
+                println!("  FAILED {}: read timed out", register.name);
+                continue;
+            }
+        };
+        summary.registers_ok += 1;
+
+        let update = RegisterUpdate {
+            device_id: device.id.as_str().into(),
+            register_name: register.name.as_str().into(),
+            value: codec::convert_value(&raw, register),
+            raw,
+            unit: register.unit.clone(),
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            previous_value: None,
+            changed: true,
+            meta: device.register_meta(register),
+            boolean: register.is_boolean(),
+            quality: Default::default(),
+        };
+        println!("  {}", publication(config, &update));
+    }
+}
+
+/// Check the broker connection, `None` when MQTT is disabled
+#[cfg(feature = "mqtt")]
+async fn check_broker(config: &Config) -> Option<bool> {
+    let Some(mqtt) = config.mqtt_enabled() else {
+        println!("MQTT: disabled");
+        return None;
+    };
+    match mqtt::check_connection(mqtt, CHECK_TIMEOUT).await {
+        Ok(()) => {
+            println!("MQTT broker {}:{}: OK", mqtt.host, mqtt.port);
+            Some(true)
+        }
+        Err(e) => {
+            println!("MQTT broker: FAILED ({:#})", e);
+            Some(false)
+        }
+    }
+}
+
+#[cfg(not(feature = "mqtt"))]
+async fn check_broker(_config: &Config) -> Option<bool> {
+    println!("MQTT: not compiled in");
+    None
+}
+
+/// Describe what would be published for an update
+#[cfg(feature = "mqtt")]
+fn publication(config: &Config, update: &RegisterUpdate) -> String {
+    let payload = mqtt::register_payload(update);
+    match config.mqtt_enabled() {
+        Some(mqtt) => format!(
+            "{} {}",
+            mqtt::register_topic(
+                &mqtt.topic_prefix,
+                config
+                    .devices
+                    .iter()
+                    .find(|d| *d.id == *update.device_id)
+                    .and_then(|d| d.tenant.as_deref()),
+                update
+            ),
+            payload
+        ),
+        None => format!("{} {}", update.register_name, payload),
+    }
+}
+
+#[cfg(not(feature = "mqtt"))]
+fn publication(_config: &Config, update: &RegisterUpdate) -> String {
+    format!("{} {}", update.register_name, update.value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "mqtt")]
+    fn update() -> RegisterUpdate {
+        RegisterUpdate {
+            device_id: "plc-001".into(),
+            register_name: "temperature".into(),
+            value: 23.5,
+            raw: vec![235],
+            unit: None,
+            timestamp: "2025-01-15T10:30:00+00:00".to_string(),
+            previous_value: None,
+            changed: true,
+            meta: Default::default(),
+            boolean: false,
+            quality: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_exit_code() {
+        let mut summary = Summary {
+            devices_ok: 2,
+            devices_total: 2,
+            registers_ok: 5,
+            registers_total: 5,
+            broker_ok: None,
+        };
+        assert_eq!(summary.exit_code(), EXIT_OK);
+
+        summary.broker_ok = Some(false);
+        assert_eq!(summary.exit_code(), EXIT_CHECK_FAILED);
+
+        summary.broker_ok = Some(true);
+        summary.registers_ok = 4;
+        assert_eq!(summary.exit_code(), EXIT_CHECK_FAILED);
+    }
+
+    #[cfg(feature = "mqtt")]
+    #[test]
+    fn test_publication() {
+        let mut config = Config::default();
+        assert!(publication(&config, &update()).starts_with("temperature {"));
+
+        config.mqtt = Some(config::MqttConfig {
+            enabled: true,
+            ..Default::default()
+        });
+        let line = publication(&config, &update());
+        assert!(line.starts_with("rustbridge/plc-001/temperature {"));
+        assert!(line.contains("\"value\":23.5"));
+    }
+
+    #[test]
+    fn test_load_validated_rejects_invalid_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.yaml");
+        std::fs::write(&path, "server: [not, a, map]").unwrap();
+
+        assert!(load_validated(path.to_str().unwrap()).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_device() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        drop(listener);
+
+        let config = Config {
+            devices: vec![DeviceConfig {
+                id: "plc-001".to_string(),
+                name: "PLC".to_string(),
+                device_type: config::DeviceType::Tcp,
+                connection: ConnectionConfig::Tcp(config::TcpConnection {
+                    host: "127.0.0.1".to_string(),
+                    port,
+                    unit_id: 1,
+                    ..Default::default()
+                }),
+                poll_interval_ms: 1000,
+                profile: None,
+                registers: vec![],
+                meta: Default::default(),
+                group: None,
+                tenant: None,
+                time_sync: None,
+                circuit_breaker: Default::default(),
+                cycle_budget_ms: None,
+                align_to_clock: false,
+                reconnect: Default::default(),
+                read_blocks: None,
+            }],
+            ..Default::default()
+        };
+
+        let mut summary = Summary {
+            devices_total: 1,
+            ..Default::default()
+        };
+        check_device(&config, &config.devices[0], &mut summary).await;
+        assert_eq!(summary.devices_ok, 0);
+        assert_eq!(summary.exit_code(), EXIT_CHECK_FAILED);
+    }
+}
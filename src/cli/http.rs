@@ -0,0 +1,94 @@
+//! Minimal HTTP client for commands that talk to a running bridge
+
+use anyhow::{bail, Context, Result};
+use http_body_util::{BodyExt, Empty};
+use hyper::body::Bytes;
+use hyper_util::rt::TokioIo;
+use tokio::net::TcpStream;
+
+/// Host and port of the bridge API
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Endpoint {
+    pub host: String,
+    pub port: u16,
+}
+
+impl Endpoint {
+    /// Parse `http://host:port`, `host:port` or `host` (port 3000)
+    pub fn parse(url: &str) -> Result<Self> {
+        let authority = url
+            .strip_prefix("http://")
+            .unwrap_or(url)
+            .trim_end_matches('/');
+        if authority.contains("://") || authority.contains('/') {
+            bail!(
+                "Unsupported bridge URL '{}', expected http://host:port",
+                url
+            );
+        }
+
+        match authority.rsplit_once(':') {
+            Some((host, port)) => Ok(Self {
+                host: host.to_string(),
+                port: port
+                    .parse()
+                    .with_context(|| format!("Invalid port in '{}'", url))?,
+            }),
+            None => Ok(Self {
+                host: authority.to_string(),
+                port: 3000,
+            }),
+        }
+    }
+
+    pub fn authority(&self) -> String {
+        format!("{}:{}", self.host, self.port)
+    }
+}
+
+/// Minimal HTTP/1.1 GET returning a JSON body
+pub async fn get_json<T: serde::de::DeserializeOwned>(
+    endpoint: &Endpoint,
+    path: &str,
+    api_key: Option<&str>,
+) -> Result<T> {
+    let stream = TcpStream::connect(endpoint.authority()).await?;
+    let (mut sender, connection) =
+        hyper::client::conn::http1::handshake(TokioIo::new(stream)).await?;
+    tokio::spawn(connection);
+
+    let mut request = hyper::Request::get(path).header(hyper::header::HOST, endpoint.authority());
+    if let Some(key) = api_key {
+        request = request.header("X-API-Key", key);
+    }
+    let response = sender
+        .send_request(request.body(Empty::<Bytes>::new())?)
+        .await?;
+
+    let status = response.status();
+    if !status.is_success() {
+        bail!("{} returned {}", path, status);
+    }
+
+    let body = response.into_body().collect().await?.to_bytes();
+    Ok(serde_json::from_slice(&body)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_endpoint_parse() {
+        assert_eq!(
+            Endpoint::parse("http://10.0.0.2:8080/").unwrap(),
+            Endpoint {
+                host: "10.0.0.2".to_string(),
+                port: 8080
+            }
+        );
+        assert_eq!(Endpoint::parse("bridge.local").unwrap().port, 3000);
+        assert!(Endpoint::parse("https://bridge.local").is_err());
+        assert!(Endpoint::parse("bridge.local:http").is_err());
+    }
+}
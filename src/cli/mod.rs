@@ -0,0 +1,135 @@
+//! Command line interface
+//!
+//! Running without a subcommand starts the bridge. Subcommands provide
+//! one-shot tools for field troubleshooting.
+
+use anyhow::Result;
+use clap::{Parser, Subcommand};
+use serde::de::DeserializeOwned;
+
+use rustbridge::config::LoggingConfig;
+use rustbridge::logging;
+
+pub mod bench;
+pub mod dry_run;
+#[cfg(feature = "api-client")]
+pub mod http;
+#[cfg(feature = "monitor")]
+pub mod monitor;
+#[cfg(feature = "rtu")]
+pub mod ports;
+pub mod read;
+pub mod scan;
+#[cfg(feature = "api-client")]
+pub mod snapshot;
+
+/// RustBridge - Modbus TCP/RTU to JSON/MQTT gateway
+#[derive(Debug, Parser)]
+#[command(name = "rustbridge", version, about)]
+pub struct Cli {
+    /// Configuration file path
+    #[arg(
+        short,
+        long,
+        global = true,
+        env = "RUSTBRIDGE_CONFIG",
+        default_value = "config.yaml"
+    )]
+    pub config: String,
+
+    /// Validate config, connect to all devices and the broker, poll once and
+    /// print what would be published, then exit
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Fork into the background and write a PID file (`daemon.pid_file`)
+    #[arg(long, conflicts_with = "dry_run")]
+    pub daemon: bool,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Run the bridge (default)
+    Run,
+    /// Read a single register and print its value
+    Read(read::ReadArgs),
+    /// Discover Modbus TCP devices on a network
+    Scan(scan::ScanArgs),
+    /// Interactive terminal monitor
+    #[cfg(feature = "monitor")]
+    Monitor(monitor::MonitorArgs),
+    /// List serial ports with USB details
+    #[cfg(feature = "rtu")]
+    Ports(ports::PortsArgs),
+    /// Measure bus throughput and latency for a device
+    Bench(bench::BenchArgs),
+    /// Save the current register values of a running bridge
+    #[cfg(feature = "api-client")]
+    Snapshot(snapshot::SnapshotArgs),
+}
+
+/// Only log warnings and errors so command output stays readable (`RUST_LOG` still applies)
+fn init_quiet_logging() -> Result<()> {
+    logging::init(&LoggingConfig {
+        level: "warn".to_string(),
+        ..Default::default()
+    })
+}
+
+/// Parse a config enum from its YAML name (e.g. "holding", "f32")
+fn parse_config_enum<T: DeserializeOwned>(s: &str) -> Result<T, String> {
+    serde_json::from_value(serde_json::Value::String(s.to_lowercase())).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+    use rustbridge::config::{DataType, RegisterType};
+
+    #[test]
+    fn test_cli_definition() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn test_no_subcommand_runs_bridge() {
+        let cli = Cli::try_parse_from(["rustbridge", "--config", "/etc/rustbridge.yaml"]).unwrap();
+        assert_eq!(cli.config, "/etc/rustbridge.yaml");
+        assert!(cli.command.is_none());
+    }
+
+    #[test]
+    fn test_daemon_flag() {
+        let cli = Cli::try_parse_from(["rustbridge", "--daemon"]).unwrap();
+        assert!(cli.daemon);
+        assert!(Cli::try_parse_from(["rustbridge", "--daemon", "--dry-run"]).is_err());
+    }
+
+    #[cfg(feature = "api-client")]
+    #[test]
+    fn test_snapshot_args() {
+        let cli = Cli::try_parse_from(["rustbridge", "snapshot", "--out", "values.json"]).unwrap();
+        let Some(Command::Snapshot(args)) = cli.command else {
+            panic!("Expected snapshot command");
+        };
+        assert_eq!(args.out.unwrap().to_str(), Some("values.json"));
+        assert!(args.url.is_none());
+    }
+
+    #[test]
+    fn test_parse_config_enum() {
+        assert!(matches!(
+            parse_config_enum::<RegisterType>("Holding"),
+            Ok(RegisterType::Holding)
+        ));
+        assert!(matches!(
+            parse_config_enum::<DataType>("f32"),
+            Ok(DataType::F32)
+        ));
+        assert!(parse_config_enum::<DataType>("f128").is_err());
+    }
+}
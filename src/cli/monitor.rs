@@ -0,0 +1,560 @@
+//! `rustbridge monitor` - interactive terminal monitor
+//!
+//! Shows live register values, device health, error counters and MQTT
+//! state. Connects to a running bridge (`--url`) via its WebSocket and
+//! diagnostics API, or starts the bridge in-process from the configuration
+//! file and monitors it over the loopback interface.
+
+use anyhow::{anyhow, Result};
+use clap::Args;
+use futures_util::StreamExt;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Paragraph, Row, Table};
+use ratatui::Frame;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::http::HeaderValue;
+use tokio_tungstenite::tungstenite::Message;
+
+use rustbridge::api::RegisterUpdate;
+use rustbridge::bridge::Bridge;
+use rustbridge::config::{self, LogTarget, LoggingConfig};
+use rustbridge::events::{ConnectionEvent, ConnectionEventKind};
+use rustbridge::logging;
+
+use super::http::{get_json, Endpoint};
+
+/// Delay before reconnecting the WebSocket after it drops
+const RECONNECT_DELAY: Duration = Duration::from_secs(2);
+/// Screen redraw interval
+const REDRAW_INTERVAL: Duration = Duration::from_millis(250);
+
+#[derive(Debug, Args)]
+pub struct MonitorArgs {
+    /// URL of a running bridge (e.g. http://10.0.0.2:3000); starts the bridge in-process if omitted
+    #[arg(long)]
+    pub url: Option<String>,
+
+    /// API key when the bridge has authentication enabled
+    #[arg(long, env = "RUSTBRIDGE_API_KEY")]
+    pub api_key: Option<String>,
+
+    /// Diagnostics refresh interval in milliseconds
+    #[arg(long, default_value_t = 1000)]
+    pub refresh_ms: u64,
+}
+
+/// Subset of the `/api/diagnostics` device entry shown in the monitor
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+struct DeviceHealth {
+    reads_ok: u64,
+    reads_failed: u64,
+    consecutive_failures: u64,
+    last_cycle_ms: u64,
+    overruns: u64,
+}
+
+/// `/api/diagnostics` response
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct DiagnosticsSnapshot {
+    devices: HashMap<String, DeviceHealth>,
+    mqtt: Option<MqttState>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+struct MqttState {
+    enabled: bool,
+    connected: bool,
+}
+
+/// Latest value of a register
+#[derive(Debug, Clone)]
+struct RegisterView {
+    value: f64,
+    unit: Option<String>,
+    timestamp: String,
+}
+
+/// Everything the monitor knows about a device
+#[derive(Debug, Clone, Default)]
+struct DeviceView {
+    connected: Option<bool>,
+    last_event: Option<String>,
+    health: DeviceHealth,
+    registers: BTreeMap<String, RegisterView>,
+}
+
+/// Messages received over the bridge WebSocket
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type")]
+enum WsMessage {
+    #[serde(rename = "update")]
+    Update(RegisterUpdate),
+    #[serde(rename = "event")]
+    Event(ConnectionEvent),
+    #[serde(other)]
+    Other,
+}
+
+/// Shared monitor state, written by the network tasks and read by the UI
+#[derive(Debug, Default)]
+struct MonitorState {
+    endpoint: String,
+    websocket_connected: bool,
+    last_error: Option<String>,
+    mqtt: Option<MqttState>,
+    devices: BTreeMap<String, DeviceView>,
+}
+
+impl MonitorState {
+    fn apply_update(&mut self, update: RegisterUpdate) {
+        let device = self
+            .devices
+            .entry(update.device_id.to_string())
+            .or_default();
+        // Fresh data means the device is reachable
+        device.connected = Some(true);
+        device.registers.insert(
+            update.register_name.to_string(),
+            RegisterView {
+                value: update.value,
+                unit: update.unit,
+                timestamp: update.timestamp,
+            },
+        );
+    }
+
+    fn apply_event(&mut self, event: ConnectionEvent) {
+        let device = self.devices.entry(event.device_id).or_default();
+        match event.event {
+            ConnectionEventKind::Connected => device.connected = Some(true),
+            ConnectionEventKind::Disconnected | ConnectionEventKind::SerialPortError => {
+                device.connected = Some(false)
+            }
+            _ => {}
+        }
+
+        let mut description = format!("{:?}", event.event);
+        if let Some(attempt) = event.attempt {
+            description.push_str(&format!(" #{}", attempt));
+        }
+        if let (Some(register), Some(value)) = (event.register, event.value) {
+            description.push_str(&format!(" {} = {}", register, value));
+        }
+        if let Some(message) = event.message {
+            description.push_str(&format!(": {}", message));
+        }
+        device.last_event = Some(description);
+    }
+
+    fn apply_message(&mut self, text: &str) {
+        match serde_json::from_str::<WsMessage>(text) {
+            Ok(WsMessage::Update(update)) => self.apply_update(update),
+            Ok(WsMessage::Event(event)) => self.apply_event(event),
+            Ok(WsMessage::Other) => {}
+            Err(e) => self.last_error = Some(format!("Invalid WebSocket message: {}", e)),
+        }
+    }
+
+    fn apply_diagnostics(&mut self, snapshot: DiagnosticsSnapshot) {
+        for (device_id, health) in snapshot.devices {
+            let device = self.devices.entry(device_id).or_default();
+            if health.consecutive_failures > 0 && device.connected.is_none() {
+                device.connected = Some(false);
+            }
+            device.health = health;
+        }
+        self.mqtt = snapshot.mqtt;
+    }
+}
+
+type SharedState = Arc<Mutex<MonitorState>>;
+
+/// Run the monitor command
+pub async fn run(config_path: &str, args: MonitorArgs) -> Result<()> {
+    let endpoint = match &args.url {
+        Some(url) => {
+            // Any log output would corrupt the screen
+            logging::init(&LoggingConfig {
+                level: "off".to_string(),
+                ..Default::default()
+            })?;
+            Endpoint::parse(url)?
+        }
+        None => start_in_process(config_path).await?,
+    };
+
+    let state: SharedState = Arc::new(Mutex::new(MonitorState {
+        endpoint: endpoint.authority(),
+        ..Default::default()
+    }));
+
+    let websocket = tokio::spawn(follow_websocket(
+        endpoint.clone(),
+        args.api_key.clone(),
+        state.clone(),
+    ));
+    let diagnostics = tokio::spawn(poll_diagnostics(
+        endpoint,
+        args.api_key,
+        Duration::from_millis(args.refresh_ms),
+        state.clone(),
+    ));
+
+    let ui = tokio::task::spawn_blocking(move || run_ui(state)).await;
+
+    websocket.abort();
+    diagnostics.abort();
+    ui?
+}
+
+/// Start the bridge in the background and return its loopback API endpoint
+async fn start_in_process(config_path: &str) -> Result<Endpoint> {
+    let config = config::load_config(config_path)?;
+
+    // Stdout logging would corrupt the screen; keep file/syslog targets as configured
+    if matches!(config.logging.target, LogTarget::Stdout) {
+        let mut logging_config = config.logging.clone();
+        logging_config.level = "off".to_string();
+        logging_config.modules.clear();
+        logging::init(&logging_config)?;
+    } else {
+        logging::init(&config.logging)?;
+    }
+
+    let endpoint = Endpoint {
+        host: "127.0.0.1".to_string(),
+        port: config.server.port,
+    };
+
+    let bridge = Bridge::new(config).await?.with_config_path(config_path);
+    tokio::spawn(async move {
+        if let Err(e) = bridge.run().await {
+            tracing::error!("Bridge stopped: {:#}", e);
+        }
+    });
+
+    Ok(endpoint)
+}
+
+/// Keep a WebSocket connection to the bridge and apply incoming messages
+async fn follow_websocket(endpoint: Endpoint, api_key: Option<String>, state: SharedState) {
+    loop {
+        if let Err(e) = stream_websocket(&endpoint, api_key.as_deref(), &state).await {
+            let mut state = state.lock().unwrap_or_else(|e| e.into_inner());
+            state.websocket_connected = false;
+            state.last_error = Some(format!("WebSocket: {:#}", e));
+        }
+        tokio::time::sleep(RECONNECT_DELAY).await;
+    }
+}
+
+async fn stream_websocket(
+    endpoint: &Endpoint,
+    api_key: Option<&str>,
+    state: &SharedState,
+) -> Result<()> {
+    let mut request = format!("ws://{}/ws", endpoint.authority()).into_client_request()?;
+    if let Some(key) = api_key {
+        request
+            .headers_mut()
+            .insert("X-API-Key", HeaderValue::from_str(key)?);
+    }
+
+    let (mut ws, _) = tokio_tungstenite::connect_async(request).await?;
+    {
+        let mut state = state.lock().unwrap_or_else(|e| e.into_inner());
+        state.websocket_connected = true;
+        state.last_error = None;
+    }
+
+    while let Some(message) = ws.next().await {
+        if let Message::Text(text) = message? {
+            state
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .apply_message(&text);
+        }
+    }
+
+    Err(anyhow!("connection closed"))
+}
+
+/// Periodically fetch `/api/diagnostics`
+async fn poll_diagnostics(
+    endpoint: Endpoint,
+    api_key: Option<String>,
+    refresh: Duration,
+    state: SharedState,
+) {
+    let mut ticker = tokio::time::interval(refresh);
+    loop {
+        ticker.tick().await;
+        let result =
+            get_json::<DiagnosticsSnapshot>(&endpoint, "/api/diagnostics", api_key.as_deref())
+                .await;
+
+        let mut state = state.lock().unwrap_or_else(|e| e.into_inner());
+        match result {
+            Ok(snapshot) => state.apply_diagnostics(snapshot),
+            Err(e) => state.last_error = Some(format!("Diagnostics: {:#}", e)),
+        }
+    }
+}
+
+/// Draw the screen until the user quits
+fn run_ui(state: SharedState) -> Result<()> {
+    let mut terminal = ratatui::init();
+
+    let result = (|| -> Result<()> {
+        loop {
+            {
+                let state = state.lock().unwrap_or_else(|e| e.into_inner());
+                terminal.draw(|frame| draw(frame, &state))?;
+            }
+
+            if event::poll(REDRAW_INTERVAL)? {
+                if let Event::Key(key) = event::read()? {
+                    if key.kind == KeyEventKind::Press
+                        && matches!(key.code, KeyCode::Char('q') | KeyCode::Esc)
+                    {
+                        return Ok(());
+                    }
+                }
+            }
+        }
+    })();
+
+    ratatui::restore();
+    result
+}
+
+fn draw(frame: &mut Frame, state: &MonitorState) {
+    let [header, devices, registers, footer] = Layout::vertical([
+        Constraint::Length(3),
+        Constraint::Length(state.devices.len() as u16 + 3),
+        Constraint::Min(3),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+
+    frame.render_widget(
+        Paragraph::new(status_line(state)).block(Block::bordered().title(" RustBridge Monitor ")),
+        header,
+    );
+    frame.render_widget(devices_table(state), devices);
+    frame.render_widget(registers_table(state), registers);
+    frame.render_widget(
+        Paragraph::new(
+            state
+                .last_error
+                .clone()
+                .unwrap_or_else(|| "q: quit".to_string()),
+        ),
+        footer,
+    );
+}
+
+fn status_line(state: &MonitorState) -> Line<'static> {
+    let websocket = if state.websocket_connected {
+        "connected"
+    } else {
+        "disconnected"
+    };
+    let mqtt = match state.mqtt {
+        None => "unknown",
+        Some(MqttState { enabled: false, .. }) => "disabled",
+        Some(MqttState {
+            connected: true, ..
+        }) => "connected",
+        Some(_) => "disconnected",
+    };
+    Line::from(format!(
+        "Bridge: {}   WebSocket: {}   MQTT: {}   Devices: {}",
+        state.endpoint,
+        websocket,
+        mqtt,
+        state.devices.len()
+    ))
+}
+
+fn devices_table(state: &MonitorState) -> Table<'static> {
+    let rows = state.devices.iter().map(|(id, device)| {
+        let (status, color) = match device.connected {
+            Some(true) if device.health.consecutive_failures == 0 => ("online", Color::Green),
+            Some(true) => ("failing", Color::Yellow),
+            Some(false) => ("offline", Color::Red),
+            None => ("unknown", Color::Gray),
+        };
+        Row::new(vec![
+            id.clone(),
+            status.to_string(),
+            device.health.reads_ok.to_string(),
+            device.health.reads_failed.to_string(),
+            device.health.consecutive_failures.to_string(),
+            format!("{}ms", device.health.last_cycle_ms),
+            device.health.overruns.to_string(),
+            device.last_event.clone().unwrap_or_default(),
+        ])
+        .style(Style::default().fg(color))
+    });
+
+    Table::new(
+        rows,
+        [
+            Constraint::Length(16),
+            Constraint::Length(8),
+            Constraint::Length(10),
+            Constraint::Length(8),
+            Constraint::Length(8),
+            Constraint::Length(8),
+            Constraint::Length(9),
+            Constraint::Min(10),
+        ],
+    )
+    .header(
+        Row::new(vec![
+            "Device",
+            "Status",
+            "Reads OK",
+            "Failed",
+            "Streak",
+            "Cycle",
+            "Overruns",
+            "Last event",
+        ])
+        .style(Style::default().add_modifier(Modifier::BOLD)),
+    )
+    .block(Block::bordered().title(" Devices "))
+}
+
+fn registers_table(state: &MonitorState) -> Table<'static> {
+    let rows = state.devices.iter().flat_map(|(id, device)| {
+        device.registers.iter().map(move |(name, register)| {
+            Row::new(vec![
+                id.clone(),
+                name.clone(),
+                format!("{:.3}", register.value),
+                register.unit.clone().unwrap_or_default(),
+                register.timestamp.clone(),
+            ])
+        })
+    });
+
+    Table::new(
+        rows,
+        [
+            Constraint::Length(16),
+            Constraint::Length(20),
+            Constraint::Length(14),
+            Constraint::Length(8),
+            Constraint::Min(20),
+        ],
+    )
+    .header(
+        Row::new(vec!["Device", "Register", "Value", "Unit", "Updated"])
+            .style(Style::default().add_modifier(Modifier::BOLD)),
+    )
+    .block(Block::bordered().title(" Registers "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::backend::TestBackend;
+    use ratatui::Terminal;
+
+    fn update(device: &str, register: &str, value: f64) -> String {
+        serde_json::json!({
+            "type": "update",
+            "device_id": device,
+            "register_name": register,
+            "value": value,
+            "raw": [0],
+            "unit": "°C",
+            "timestamp": "2025-01-15T10:30:00+00:00",
+        })
+        .to_string()
+    }
+
+    #[test]
+    fn test_apply_websocket_messages() {
+        let mut state = MonitorState::default();
+        state.apply_message(&update("plc-001", "temperature", 23.5));
+        state.apply_message(r#"{"type":"connected","message":"RustBridge WebSocket"}"#);
+
+        let device = &state.devices["plc-001"];
+        assert_eq!(device.connected, Some(true));
+        assert_eq!(device.registers["temperature"].value, 23.5);
+
+        state.apply_message(
+            &serde_json::json!({
+                "type": "event",
+                "device_id": "plc-001",
+                "event": "disconnected",
+                "message": "Connection reset by peer",
+                "timestamp": "2025-01-15T10:30:01+00:00",
+            })
+            .to_string(),
+        );
+        let device = &state.devices["plc-001"];
+        assert_eq!(device.connected, Some(false));
+        assert_eq!(
+            device.last_event.as_deref(),
+            Some("Disconnected: Connection reset by peer")
+        );
+        assert!(state.last_error.is_none());
+    }
+
+    #[test]
+    fn test_apply_diagnostics() {
+        let mut state = MonitorState::default();
+        let snapshot: DiagnosticsSnapshot = serde_json::from_value(serde_json::json!({
+            "devices": {
+                "plc-001": {"reads_ok": 10, "reads_failed": 2, "consecutive_failures": 2, "last_cycle_ms": 40, "overruns": 0, "registers": {}}
+            },
+            "count": 1,
+            "mqtt": {"enabled": true, "connected": false}
+        }))
+        .unwrap();
+        state.apply_diagnostics(snapshot);
+
+        let device = &state.devices["plc-001"];
+        assert_eq!(device.health.reads_ok, 10);
+        assert_eq!(device.connected, Some(false));
+        assert!(state.mqtt.is_some_and(|m| m.enabled && !m.connected));
+    }
+
+    #[test]
+    fn test_draw() {
+        let mut state = MonitorState {
+            endpoint: "127.0.0.1:3000".to_string(),
+            websocket_connected: true,
+            ..Default::default()
+        };
+        state.apply_message(&update("plc-001", "temperature", 23.5));
+
+        let mut terminal = Terminal::new(TestBackend::new(120, 20)).unwrap();
+        terminal.draw(|frame| draw(frame, &state)).unwrap();
+
+        let screen: String = terminal
+            .backend()
+            .buffer()
+            .content()
+            .iter()
+            .map(|cell| cell.symbol())
+            .collect();
+        assert!(screen.contains("WebSocket: connected"));
+        assert!(screen.contains("plc-001"));
+        assert!(screen.contains("temperature"));
+        assert!(screen.contains("23.500"));
+    }
+}
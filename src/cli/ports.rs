@@ -0,0 +1,282 @@
+//! `rustbridge ports` - serial port listing
+//!
+//! Enumerates serial ports with USB VID/PID, manufacturer and product so
+//! the RS-485 adapter can be told apart from other `/dev/ttyUSB*` devices.
+
+use anyhow::{Context, Result};
+use clap::Args;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tokio_serial::{SerialPortInfo, SerialPortType};
+
+/// Directory with stable, serial-number based symlinks (Linux/udev)
+const BY_ID_DIR: &str = "/dev/serial/by-id";
+
+#[derive(Debug, Args)]
+pub struct PortsArgs {
+    /// Print the result as JSON
+    #[arg(long)]
+    pub json: bool,
+}
+
+/// A serial port as shown to the user
+#[derive(Debug, Clone, Default, Serialize)]
+struct PortEntry {
+    port: String,
+    #[serde(rename = "type")]
+    port_type: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    vid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    chip: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    manufacturer: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    product: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    serial_number: Option<String>,
+    /// Stable `/dev/serial/by-id/...` path, preferable in config files
+    #[serde(skip_serializing_if = "Option::is_none")]
+    by_id: Option<String>,
+}
+
+/// Run the ports command
+pub async fn run(args: PortsArgs) -> Result<()> {
+    super::init_quiet_logging()?;
+
+    let ports = tokio_serial::available_ports().context("Failed to enumerate serial ports")?;
+    let by_id = stable_paths(Path::new(BY_ID_DIR));
+    let mut entries: Vec<PortEntry> = ports.iter().map(|p| entry(p, &by_id)).collect();
+    entries.sort_by(|a, b| a.port.cmp(&b.port));
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&entries)?);
+    } else if entries.is_empty() {
+        eprintln!("No serial ports found");
+    } else {
+        print!("{}", render_table(&entries));
+    }
+
+    Ok(())
+}
+
+fn entry(info: &SerialPortInfo, by_id: &HashMap<PathBuf, String>) -> PortEntry {
+    let mut entry = PortEntry {
+        port: info.port_name.clone(),
+        by_id: Path::new(&info.port_name)
+            .canonicalize()
+            .ok()
+            .and_then(|p| by_id.get(&p).cloned()),
+        ..Default::default()
+    };
+
+    match &info.port_type {
+        SerialPortType::UsbPort(usb) => {
+            entry.port_type = "usb";
+            entry.vid = Some(format!("{:04x}", usb.vid));
+            entry.pid = Some(format!("{:04x}", usb.pid));
+            entry.chip = usb_chip(usb.vid, usb.pid);
+            entry.manufacturer = usb.manufacturer.clone();
+            entry.product = usb.product.clone();
+            entry.serial_number = usb.serial_number.clone();
+        }
+        SerialPortType::PciPort => entry.port_type = "pci",
+        SerialPortType::BluetoothPort => entry.port_type = "bluetooth",
+        SerialPortType::Unknown => entry.port_type = "unknown",
+    }
+
+    entry
+}
+
+/// USB-serial bridge chips commonly found in RS-485 adapters
+fn usb_chip(vid: u16, pid: u16) -> Option<&'static str> {
+    match (vid, pid) {
+        (0x0403, 0x6001) => Some("FTDI FT232R"),
+        (0x0403, 0x6010) => Some("FTDI FT2232"),
+        (0x0403, 0x6011) => Some("FTDI FT4232"),
+        (0x0403, 0x6014) => Some("FTDI FT232H"),
+        (0x0403, 0x6015) => Some("FTDI FT-X"),
+        (0x10c4, 0xea60) => Some("Silicon Labs CP210x"),
+        (0x1a86, 0x7523) => Some("WCH CH340"),
+        (0x1a86, 0x55d4) => Some("WCH CH9102"),
+        (0x067b, 0x2303) => Some("Prolific PL2303"),
+        _ => None,
+    }
+}
+
+/// Map canonical device paths to their `/dev/serial/by-id` symlinks
+fn stable_paths(dir: &Path) -> HashMap<PathBuf, String> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return HashMap::new();
+    };
+
+    entries
+        .flatten()
+        .filter(|e| e.file_type().is_ok_and(|t| t.is_symlink()))
+        .filter_map(|e| {
+            let link = e.path();
+            let target = link.canonicalize().ok()?;
+            Some((target, link.to_string_lossy().to_string()))
+        })
+        .collect()
+}
+
+/// Render entries as an aligned text table
+fn render_table(entries: &[PortEntry]) -> String {
+    let rows: Vec<[String; 5]> = entries
+        .iter()
+        .map(|e| {
+            let id = match (&e.vid, &e.pid) {
+                (Some(vid), Some(pid)) => format!("{}:{}", vid, pid),
+                _ => "-".to_string(),
+            };
+            let description = [
+                e.chip.map(str::to_string),
+                e.manufacturer.clone(),
+                e.product.clone(),
+            ]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+            .join(" / ");
+            [
+                e.port.clone(),
+                e.port_type.to_string(),
+                id,
+                e.serial_number.clone().unwrap_or_else(|| "-".to_string()),
+                if description.is_empty() {
+                    "-".to_string()
+                } else {
+                    description
+                },
+            ]
+        })
+        .collect();
+
+    let header = ["PORT", "TYPE", "VID:PID", "SERIAL", "DESCRIPTION"];
+    let mut widths = header.map(str::len);
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let mut out = String::new();
+    let mut push_row = |cells: [&str; 5]| {
+        let line = cells
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect::<Vec<_>>()
+            .join("  ");
+        out.push_str(line.trim_end());
+        out.push('\n');
+    };
+
+    push_row(header);
+    for row in &rows {
+        push_row(row.each_ref().map(String::as_str));
+    }
+    for entry in entries.iter().filter(|e| e.by_id.is_some()) {
+        out.push_str(&format!(
+            "\n{} -> {}",
+            entry.port,
+            entry.by_id.as_deref().unwrap_or_default()
+        ));
+    }
+    if entries.iter().any(|e| e.by_id.is_some()) {
+        out.push('\n');
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio_serial::UsbPortInfo;
+
+    fn usb_port(name: &str, vid: u16, pid: u16) -> SerialPortInfo {
+        SerialPortInfo {
+            port_name: name.to_string(),
+            port_type: SerialPortType::UsbPort(UsbPortInfo {
+                vid,
+                pid,
+                serial_number: Some("A10K5XYZ".to_string()),
+                manufacturer: Some("FTDI".to_string()),
+                product: Some("USB-RS485 Cable".to_string()),
+            }),
+        }
+    }
+
+    #[test]
+    fn test_usb_entry() {
+        let entry = entry(&usb_port("/dev/ttyUSB0", 0x0403, 0x6001), &HashMap::new());
+
+        assert_eq!(entry.port_type, "usb");
+        assert_eq!(entry.vid.as_deref(), Some("0403"));
+        assert_eq!(entry.pid.as_deref(), Some("6001"));
+        assert_eq!(entry.chip, Some("FTDI FT232R"));
+        assert_eq!(entry.product.as_deref(), Some("USB-RS485 Cable"));
+    }
+
+    #[test]
+    fn test_render_table() {
+        let entries = vec![
+            entry(&usb_port("/dev/ttyUSB0", 0x1a86, 0x7523), &HashMap::new()),
+            entry(
+                &SerialPortInfo {
+                    port_name: "/dev/ttyS0".to_string(),
+                    port_type: SerialPortType::Unknown,
+                },
+                &HashMap::new(),
+            ),
+        ];
+
+        let table = render_table(&entries);
+        let lines: Vec<&str> = table.lines().collect();
+        assert!(lines[0].starts_with("PORT"));
+        assert!(lines[1].contains("1a86:7523"));
+        assert!(lines[1].contains("WCH CH340 / FTDI / USB-RS485 Cable"));
+        assert!(lines[2].starts_with("/dev/ttyS0"));
+        assert!(lines[2].contains("unknown"));
+    }
+
+    #[test]
+    fn test_json_omits_missing_fields() {
+        let entry = entry(
+            &SerialPortInfo {
+                port_name: "/dev/ttyAMA0".to_string(),
+                port_type: SerialPortType::Unknown,
+            },
+            &HashMap::new(),
+        );
+        let json = serde_json::to_value(&entry).unwrap();
+
+        assert_eq!(json["port"], "/dev/ttyAMA0");
+        assert_eq!(json["type"], "unknown");
+        assert!(json.get("vid").is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_stable_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let device = dir.path().join("ttyUSB0");
+        std::fs::write(&device, "").unwrap();
+        let link = dir
+            .path()
+            .join("usb-FTDI_USB-RS485_Cable_A10K5XYZ-if00-port0");
+        std::os::unix::fs::symlink(&device, &link).unwrap();
+
+        let paths = stable_paths(dir.path());
+        assert_eq!(
+            paths.get(&device.canonicalize().unwrap()),
+            Some(&link.to_string_lossy().to_string())
+        );
+        assert!(stable_paths(Path::new("/nonexistent/by-id")).is_empty());
+    }
+}
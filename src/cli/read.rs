@@ -0,0 +1,393 @@
+//! `rustbridge read` - one-shot register read
+//!
+//! Reads a register defined in the configuration file
+//! (`--device plc1 --register temperature`) or an ad-hoc address
+//! (`--tcp 10.0.0.5:502 --unit 1 --address 100 --type holding --datatype f32`)
+//! and prints the value.
+
+use anyhow::{anyhow, bail, Context, Result};
+use clap::Args;
+
+use rustbridge::config::{
+    self, ConnectionConfig, DataType, DeviceConfig, DeviceType, RegisterConfig, RegisterType,
+    RtuConnection, TcpConnection,
+};
+use rustbridge::modbus::builder::parse_tcp_target;
+use rustbridge::modbus::codec;
+use rustbridge::modbus::reader::RegisterValue;
+use rustbridge::modbus::ModbusClient;
+
+#[derive(Debug, Args)]
+pub struct ReadArgs {
+    /// Device ID from the configuration file
+    #[arg(long, requires = "register", conflicts_with_all = ["tcp", "rtu"])]
+    pub device: Option<String>,
+
+    /// Register name from the device configuration
+    #[arg(long, requires = "device")]
+    pub register: Option<String>,
+
+    /// Ad-hoc Modbus TCP target (host:port)
+    #[arg(long, conflicts_with = "rtu", requires = "address")]
+    pub tcp: Option<String>,
+
+    /// Ad-hoc Modbus RTU serial port (e.g. /dev/ttyUSB0)
+    #[arg(long, requires = "address")]
+    pub rtu: Option<String>,
+
+    /// Serial baud rate
+    #[arg(long, default_value_t = 9600)]
+    pub baud: u32,
+
+    /// Serial parity: none, even, odd
+    #[arg(long, default_value = "none")]
+    pub parity: String,
+
+    /// Serial data bits
+    #[arg(long, default_value_t = 8)]
+    pub data_bits: u8,
+
+    /// Serial stop bits
+    #[arg(long, default_value_t = 1)]
+    pub stop_bits: u8,
+
+    /// Modbus unit ID
+    #[arg(long, default_value_t = 1)]
+    pub unit: u8,
+
+    /// Register address
+    #[arg(long)]
+    pub address: Option<u16>,
+
+    /// Register type: holding, input, coil, discrete
+    #[arg(long = "type", default_value = "holding", value_parser = super::parse_config_enum::<RegisterType>)]
+    pub register_type: RegisterType,
+
+    /// Data type: u16, i16, u32, i32, f32, bool
+    #[arg(long, default_value = "u16", value_parser = super::parse_config_enum::<DataType>)]
+    pub datatype: DataType,
+
+    /// Number of registers to read (defaults to the size of the data type)
+    #[arg(long)]
+    pub count: Option<u16>,
+
+    /// Scaling factor applied to the raw value
+    #[arg(long)]
+    pub scale: Option<f64>,
+
+    /// Offset added after scaling
+    #[arg(long)]
+    pub offset: Option<f64>,
+
+    /// Print the result as JSON
+    #[arg(long)]
+    pub json: bool,
+}
+
+/// Run the read command
+pub async fn run(config_path: &str, args: ReadArgs) -> Result<()> {
+    super::init_quiet_logging()?;
+
+    let (device, register) = if args.device.is_some() {
+        resolve_configured(&config::load_config(config_path)?, &args)?
+    } else {
+        resolve_adhoc(&args)?
+    };
+
+    let mut client = ModbusClient::new(&device).await?;
+    let raw = client.read_registers(&register).await.with_context(|| {
+        format!(
+            "Failed to read {} (address {}) from {}",
+            register.name, register.address, device.id
+        )
+    })?;
+
+    let value = RegisterValue {
+        name: register.name.as_str().into(),
+        value: codec::convert_value(&raw, &register),
+        quality: Default::default(),
+        restored: false,
+        raw,
+        unit: register.unit.clone(),
+        timestamp: chrono::Utc::now(),
+        meta: Default::default(),
+    };
+
+    println!("{}", format_value(&value, args.json)?);
+    Ok(())
+}
+
+/// Look up the device and register in the configuration
+fn resolve_configured(
+    config: &config::Config,
+    args: &ReadArgs,
+) -> Result<(DeviceConfig, RegisterConfig)> {
+    let device_id = args.device.as_deref().unwrap_or_default();
+    let register_name = args.register.as_deref().unwrap_or_default();
+
+    let device = config
+        .devices
+        .iter()
+        .find(|d| d.id == device_id)
+        .ok_or_else(|| anyhow!("Device '{}' not found in configuration", device_id))?;
+    let register = device
+        .registers
+        .iter()
+        .find(|r| r.name == register_name)
+        .ok_or_else(|| {
+            anyhow!(
+                "Register '{}' not found on device '{}'",
+                register_name,
+                device_id
+            )
+        })?;
+
+    Ok((device.clone(), register.clone()))
+}
+
+/// Build a device and register from the ad-hoc arguments
+fn resolve_adhoc(args: &ReadArgs) -> Result<(DeviceConfig, RegisterConfig)> {
+    let address = args
+        .address
+        .ok_or_else(|| anyhow!("--address is required for ad-hoc reads"))?;
+
+    let (device_type, connection) = match (&args.tcp, &args.rtu) {
+        (Some(target), None) => {
+            let (host, port) = parse_tcp_target(target)?;
+            (
+                DeviceType::Tcp,
+                ConnectionConfig::Tcp(TcpConnection {
+                    host,
+                    port,
+                    unit_id: args.unit,
+                    ..Default::default()
+                }),
+            )
+        }
+        (None, Some(port)) => (
+            DeviceType::Rtu,
+            ConnectionConfig::Rtu(RtuConnection {
+                port: port.clone(),
+                usb: None,
+                baud_rate: args.baud,
+                data_bits: args.data_bits,
+                stop_bits: args.stop_bits,
+                parity: args.parity.clone(),
+                unit_id: args.unit,
+                response_timeout_ms: None,
+                retries: 0,
+                listen_only: false,
+            }),
+        ),
+        _ => bail!("Specify either --device/--register or one of --tcp/--rtu"),
+    };
+
+    let device = DeviceConfig {
+        id: "cli".to_string(),
+        name: "Command line".to_string(),
+        device_type,
+        connection,
+        poll_interval_ms: 0,
+        profile: None,
+        registers: vec![],
+        meta: Default::default(),
+        group: None,
+        tenant: None,
+        time_sync: None,
+        circuit_breaker: Default::default(),
+        cycle_budget_ms: None,
+        align_to_clock: false,
+        reconnect: Default::default(),
+        read_blocks: None,
+    };
+
+    let register = RegisterConfig {
+        name: format!("{:?}@{}", args.register_type, address).to_lowercase(),
+        address,
+        register_type: args.register_type.clone(),
+        count: args.count.unwrap_or_else(|| args.datatype.register_count()),
+        data_type: args.datatype.clone(),
+        unit: None,
+        scale: args.scale,
+        offset: args.offset,
+        byte_order: Default::default(),
+        non_finite: Default::default(),
+        priority: Default::default(),
+        meta: Default::default(),
+        read_when: None,
+        alarm: None,
+        write_limits: None,
+        oversample: None,
+        read_after: None,
+        trigger: None,
+        min_stable_ms: None,
+        persistent: false,
+        publish_on_change: false,
+        deadband: None,
+        heartbeat_interval_s: None,
+        points: Vec::new(),
+        expression: None,
+        lookup: None,
+    };
+
+    Ok((device, register))
+}
+
+/// Render a value for the terminal
+fn format_value(value: &RegisterValue, json: bool) -> Result<String> {
+    if json {
+        return Ok(serde_json::to_string_pretty(value)?);
+    }
+
+    let unit = value
+        .unit
+        .as_deref()
+        .map(|u| format!(" {}", u))
+        .unwrap_or_default();
+    Ok(format!(
+        "{} = {}{} (raw: {:?})",
+        value.name, value.value, unit, value.raw
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::{Cli, Command};
+    use clap::Parser;
+
+    fn parse(args: &[&str]) -> ReadArgs {
+        let mut argv = vec!["rustbridge", "read"];
+        argv.extend_from_slice(args);
+        match Cli::try_parse_from(argv).unwrap().command {
+            Some(Command::Read(args)) => args,
+            other => panic!("unexpected command: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_adhoc_tcp_read() {
+        let args = parse(&[
+            "--tcp",
+            "10.0.0.5:5020",
+            "--unit",
+            "3",
+            "--address",
+            "100",
+            "--type",
+            "input",
+            "--datatype",
+            "f32",
+        ]);
+        let (device, register) = resolve_adhoc(&args).unwrap();
+
+        match device.connection {
+            ConnectionConfig::Tcp(tcp) => {
+                assert_eq!(tcp.host, "10.0.0.5");
+                assert_eq!(tcp.port, 5020);
+                assert_eq!(tcp.unit_id, 3);
+            }
+            _ => panic!("expected TCP connection"),
+        }
+        assert_eq!(register.address, 100);
+        assert_eq!(register.count, 2);
+        assert!(matches!(register.register_type, RegisterType::Input));
+        assert!(matches!(register.data_type, DataType::F32));
+    }
+
+    #[test]
+    fn test_adhoc_rtu_read() {
+        let args = parse(&["--rtu", "/dev/ttyUSB0", "--baud", "19200", "--address", "7"]);
+        let (device, register) = resolve_adhoc(&args).unwrap();
+
+        match device.connection {
+            ConnectionConfig::Rtu(rtu) => {
+                assert_eq!(rtu.port, "/dev/ttyUSB0");
+                assert_eq!(rtu.baud_rate, 19200);
+            }
+            _ => panic!("expected RTU connection"),
+        }
+        assert_eq!(register.count, 1);
+    }
+
+    #[test]
+    fn test_configured_read() {
+        let config = config::load_config_from_str(
+            r#"
+server:
+  host: "0.0.0.0"
+  port: 3000
+  metrics_enabled: true
+mqtt:
+  enabled: false
+  host: "localhost"
+  port: 1883
+  client_id: "test"
+  topic_prefix: "test"
+  qos: 1
+  retain: false
+devices:
+  - id: "plc1"
+    name: "PLC"
+    device_type: tcp
+    connection:
+      host: "127.0.0.1"
+      port: 502
+      unit_id: 1
+    poll_interval_ms: 1000
+    registers:
+      - name: "temperature"
+        address: 100
+        register_type: holding
+        count: 1
+        data_type: i16
+        scale: 0.1
+"#,
+        )
+        .unwrap();
+
+        let args = parse(&["--device", "plc1", "--register", "temperature"]);
+        let (device, register) = resolve_configured(&config, &args).unwrap();
+        assert_eq!(device.id, "plc1");
+        assert_eq!(register.scale, Some(0.1));
+
+        let args = parse(&["--device", "plc1", "--register", "missing"]);
+        assert!(resolve_configured(&config, &args).is_err());
+    }
+
+    #[test]
+    fn test_device_conflicts_with_adhoc_target() {
+        assert!(Cli::try_parse_from([
+            "rustbridge",
+            "read",
+            "--device",
+            "plc1",
+            "--register",
+            "temperature",
+            "--tcp",
+            "10.0.0.5:502",
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_format_value() {
+        let value = RegisterValue {
+            name: "temperature".into(),
+            raw: vec![235],
+            value: 23.5,
+            quality: Default::default(),
+            restored: false,
+            unit: Some("°C".to_string()),
+            timestamp: chrono::Utc::now(),
+            meta: Default::default(),
+        };
+        assert_eq!(
+            format_value(&value, false).unwrap(),
+            "temperature = 23.5 °C (raw: [235])"
+        );
+        assert!(format_value(&value, true)
+            .unwrap()
+            .contains("\"value\": 23.5"));
+    }
+}
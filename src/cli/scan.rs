@@ -0,0 +1,346 @@
+//! `rustbridge scan` - Modbus TCP network discovery
+//!
+//! Probes the Modbus TCP port across an IPv4 subnet, asks each responding
+//! host for its device identification (FC 0x2B / MEI 0x0E) and prints the
+//! discovered devices as a `devices:` block ready to paste into config.
+
+use anyhow::{bail, Context, Result};
+use clap::Args;
+use futures_util::{stream, StreamExt};
+use serde::Serialize;
+use std::borrow::Cow;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio::time::timeout;
+use tokio_modbus::prelude::*;
+
+use rustbridge::config::{ConnectionConfig, DeviceConfig, DeviceType, TcpConnection};
+
+/// Encapsulated Interface Transport function code
+const FC_ENCAPSULATED_INTERFACE: u8 = 0x2B;
+/// MEI type for Read Device Identification
+const MEI_READ_DEVICE_ID: u8 = 0x0E;
+/// Largest subnet scanned in one go (a /16)
+const MAX_HOSTS: usize = 65_534;
+
+#[derive(Debug, Args)]
+pub struct ScanArgs {
+    /// IPv4 network to scan in CIDR notation (e.g. 192.168.10.0/24)
+    #[arg(long)]
+    pub network: String,
+
+    /// Modbus TCP port
+    #[arg(long, default_value_t = 502)]
+    pub port: u16,
+
+    /// Unit ID used for identification requests
+    #[arg(long, default_value_t = 1)]
+    pub unit: u8,
+
+    /// Connect and request timeout per host in milliseconds
+    #[arg(long, default_value_t = 500)]
+    pub timeout_ms: u64,
+
+    /// Number of hosts probed in parallel
+    #[arg(long, default_value_t = 64)]
+    pub concurrency: usize,
+}
+
+/// Device identification objects (basic category)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DeviceIdentification {
+    pub vendor_name: Option<String>,
+    pub product_code: Option<String>,
+    pub revision: Option<String>,
+}
+
+impl DeviceIdentification {
+    /// Human-readable description, e.g. "Schneider Electric BMX P34 (v3.1)"
+    fn description(&self) -> Option<String> {
+        let name = [self.vendor_name.as_deref(), self.product_code.as_deref()]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+            .join(" ");
+        if name.is_empty() {
+            return None;
+        }
+        Some(match &self.revision {
+            Some(revision) => format!("{} ({})", name, revision),
+            None => name,
+        })
+    }
+}
+
+/// A host that answered on the Modbus TCP port
+#[derive(Debug)]
+struct Discovered {
+    address: Ipv4Addr,
+    identification: Option<DeviceIdentification>,
+}
+
+#[derive(Serialize)]
+struct DevicesSnippet {
+    devices: Vec<DeviceConfig>,
+}
+
+/// Run the scan command
+pub async fn run(args: ScanArgs) -> Result<()> {
+    super::init_quiet_logging()?;
+
+    let hosts = parse_network(&args.network)?;
+    let probe_timeout = Duration::from_millis(args.timeout_ms);
+
+    eprintln!(
+        "Scanning {} host(s) on {} port {}...",
+        hosts.len(),
+        args.network,
+        args.port
+    );
+
+    let mut discovered: Vec<Discovered> = stream::iter(hosts)
+        .map(|host| probe(host, args.port, args.unit, probe_timeout))
+        .buffer_unordered(args.concurrency.max(1))
+        .filter_map(|found| async move { found })
+        .collect()
+        .await;
+    discovered.sort_by_key(|d| d.address);
+
+    if discovered.is_empty() {
+        eprintln!("No Modbus TCP devices found");
+        return Ok(());
+    }
+
+    eprintln!("Found {} device(s)\n", discovered.len());
+    println!("{}", render_config(&discovered, args.port, args.unit)?);
+
+    Ok(())
+}
+
+/// Expand an IPv4 CIDR into its host addresses
+fn parse_network(network: &str) -> Result<Vec<Ipv4Addr>> {
+    let (addr, prefix) = match network.split_once('/') {
+        Some((addr, prefix)) => (
+            addr,
+            prefix
+                .parse::<u8>()
+                .with_context(|| format!("Invalid prefix length in '{}'", network))?,
+        ),
+        None => (network, 32),
+    };
+    let addr: Ipv4Addr = addr
+        .parse()
+        .with_context(|| format!("Invalid IPv4 network '{}'", network))?;
+    if prefix > 32 {
+        bail!("Invalid prefix length /{}", prefix);
+    }
+
+    let mask = u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0);
+    let base = u32::from(addr) & mask;
+    let size = 1u64 << (32 - prefix as u32);
+
+    let hosts: Vec<Ipv4Addr> = if size <= 2 {
+        // /31 and /32 have no network/broadcast addresses
+        (0..size).map(|i| Ipv4Addr::from(base + i as u32)).collect()
+    } else {
+        if (size - 2) as usize > MAX_HOSTS {
+            bail!(
+                "Network {} is too large to scan (maximum is a /16)",
+                network
+            );
+        }
+        (1..size - 1)
+            .map(|i| Ipv4Addr::from(base + i as u32))
+            .collect()
+    };
+
+    Ok(hosts)
+}
+
+/// Probe a single host; returns it if the Modbus port accepted a connection
+async fn probe(host: Ipv4Addr, port: u16, unit: u8, probe_timeout: Duration) -> Option<Discovered> {
+    let addr = SocketAddr::from((host, port));
+    let stream = timeout(probe_timeout, TcpStream::connect(addr))
+        .await
+        .ok()?
+        .ok()?;
+
+    let mut ctx = tcp::attach_slave(stream, Slave(unit));
+    let request = Request::Custom(
+        FC_ENCAPSULATED_INTERFACE,
+        Cow::Borrowed(&[MEI_READ_DEVICE_ID, 0x01, 0x00]),
+    );
+    let identification = match timeout(probe_timeout, ctx.call(request)).await {
+        Ok(Ok(Ok(Response::Custom(_, data)))) => parse_device_identification(&data),
+        _ => None,
+    };
+
+    Some(Discovered {
+        address: host,
+        identification,
+    })
+}
+
+/// Parse a Read Device Identification response (bytes after the function code)
+pub fn parse_device_identification(data: &[u8]) -> Option<DeviceIdentification> {
+    // MEI type, read code, conformity level, more follows, next object id, object count
+    if data.len() < 6 || data[0] != MEI_READ_DEVICE_ID {
+        return None;
+    }
+
+    let mut identification = DeviceIdentification::default();
+    let mut objects = &data[6..];
+    for _ in 0..data[5] {
+        let [id, len, rest @ ..] = objects else {
+            break;
+        };
+        let len = *len as usize;
+        if rest.len() < len {
+            break;
+        }
+        let value = String::from_utf8_lossy(&rest[..len]).trim().to_string();
+        match id {
+            0x00 => identification.vendor_name = Some(value),
+            0x01 => identification.product_code = Some(value),
+            0x02 => identification.revision = Some(value),
+            _ => {}
+        }
+        objects = &rest[len..];
+    }
+
+    Some(identification)
+}
+
+/// Render discovered devices as a config snippet
+fn render_config(discovered: &[Discovered], port: u16, unit: u8) -> Result<String> {
+    let devices = discovered
+        .iter()
+        .map(|d| DeviceConfig {
+            id: format!("device-{}", d.address.to_string().replace('.', "-")),
+            name: d
+                .identification
+                .as_ref()
+                .and_then(DeviceIdentification::description)
+                .unwrap_or_else(|| format!("Modbus device at {}", d.address)),
+            device_type: DeviceType::Tcp,
+            connection: ConnectionConfig::Tcp(TcpConnection {
+                host: d.address.to_string(),
+                port,
+                unit_id: unit,
+                ..Default::default()
+            }),
+            poll_interval_ms: 1000,
+            profile: None,
+            registers: vec![],
+            meta: Default::default(),
+            group: None,
+            tenant: None,
+            time_sync: None,
+            circuit_breaker: Default::default(),
+            cycle_budget_ms: None,
+            align_to_clock: false,
+            reconnect: Default::default(),
+            read_blocks: None,
+        })
+        .collect();
+
+    Ok(serde_yaml::to_string(&DevicesSnippet { devices })?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_network() {
+        let hosts = parse_network("192.168.10.77/24").unwrap();
+        assert_eq!(hosts.len(), 254);
+        assert_eq!(hosts[0], Ipv4Addr::new(192, 168, 10, 1));
+        assert_eq!(hosts[253], Ipv4Addr::new(192, 168, 10, 254));
+
+        assert_eq!(
+            parse_network("10.0.0.5").unwrap(),
+            vec![Ipv4Addr::new(10, 0, 0, 5)]
+        );
+        assert_eq!(parse_network("10.0.0.4/31").unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_parse_network_rejects_invalid() {
+        assert!(parse_network("192.168.10.0/33").is_err());
+        assert!(parse_network("plc.local/24").is_err());
+        assert!(parse_network("10.0.0.0/8").is_err());
+    }
+
+    #[test]
+    fn test_parse_device_identification() {
+        let mut data = vec![0x0E, 0x01, 0x01, 0x00, 0x00, 0x03];
+        for (id, value) in [(0u8, "Acme"), (1, "PLC-9000"), (2, "v2.4")] {
+            data.push(id);
+            data.push(value.len() as u8);
+            data.extend_from_slice(value.as_bytes());
+        }
+
+        let identification = parse_device_identification(&data).unwrap();
+        assert_eq!(identification.vendor_name.as_deref(), Some("Acme"));
+        assert_eq!(identification.product_code.as_deref(), Some("PLC-9000"));
+        assert_eq!(
+            identification.description().as_deref(),
+            Some("Acme PLC-9000 (v2.4)")
+        );
+    }
+
+    #[test]
+    fn test_parse_device_identification_truncated() {
+        assert!(parse_device_identification(&[0x0E, 0x01]).is_none());
+
+        // Object length runs past the end of the frame
+        let identification =
+            parse_device_identification(&[0x0E, 0x01, 0x01, 0x00, 0x00, 0x01, 0x00, 0x09, b'A'])
+                .unwrap();
+        assert_eq!(identification, DeviceIdentification::default());
+    }
+
+    #[test]
+    fn test_render_config() {
+        let discovered = vec![
+            Discovered {
+                address: Ipv4Addr::new(192, 168, 10, 5),
+                identification: Some(DeviceIdentification {
+                    vendor_name: Some("Acme".to_string()),
+                    product_code: Some("PLC-9000".to_string()),
+                    revision: None,
+                }),
+            },
+            Discovered {
+                address: Ipv4Addr::new(192, 168, 10, 9),
+                identification: None,
+            },
+        ];
+
+        let yaml = render_config(&discovered, 502, 1).unwrap();
+        assert!(yaml.contains("id: device-192-168-10-5"));
+        assert!(yaml.contains("name: Acme PLC-9000"));
+        assert!(yaml.contains("name: Modbus device at 192.168.10.9"));
+
+        // Snippet round-trips through the config parser
+        let parsed: DevicesSnippetOwned = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(parsed.devices.len(), 2);
+    }
+
+    #[derive(serde::Deserialize)]
+    struct DevicesSnippetOwned {
+        devices: Vec<DeviceConfig>,
+    }
+
+    #[tokio::test]
+    async fn test_probe_closed_port() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        drop(listener);
+
+        let found = probe(Ipv4Addr::LOCALHOST, port, 1, Duration::from_millis(200)).await;
+        assert!(found.is_none());
+    }
+}
@@ -0,0 +1,62 @@
+//! `rustbridge snapshot` - export the current register values
+//!
+//! Fetches `/api/snapshot` from a running bridge and writes it as JSON. The
+//! file can be preloaded on the next start with `snapshot.preload`.
+
+use anyhow::{Context, Result};
+use clap::Args;
+use std::path::PathBuf;
+
+use rustbridge::config;
+use rustbridge::modbus::reader::Snapshot;
+
+use super::http::{get_json, Endpoint};
+
+#[derive(Debug, Args)]
+pub struct SnapshotArgs {
+    /// URL of the running bridge (default: http://127.0.0.1 on the configured server port)
+    #[arg(long)]
+    pub url: Option<String>,
+
+    /// API key when the bridge has authentication enabled
+    #[arg(long, env = "RUSTBRIDGE_API_KEY")]
+    pub api_key: Option<String>,
+
+    /// Output file (default: stdout)
+    #[arg(long)]
+    pub out: Option<PathBuf>,
+}
+
+pub async fn run(config_path: &str, args: SnapshotArgs) -> Result<()> {
+    let endpoint = match &args.url {
+        Some(url) => Endpoint::parse(url)?,
+        None => Endpoint {
+            host: "127.0.0.1".to_string(),
+            port: config::load_config(config_path)?.server.port,
+        },
+    };
+
+    let snapshot: Snapshot = get_json(&endpoint, "/api/snapshot", args.api_key.as_deref())
+        .await
+        .with_context(|| format!("Failed to fetch snapshot from {}", endpoint.authority()))?;
+    let json = serde_json::to_string_pretty(&snapshot)?;
+
+    let Some(out) = &args.out else {
+        println!("{}", json);
+        return Ok(());
+    };
+    // Write next to the target and rename, so a preloading bridge never sees a partial file
+    let partial = out.with_extension("partial");
+    std::fs::write(&partial, json + "\n")
+        .with_context(|| format!("Failed to write {}", partial.display()))?;
+    std::fs::rename(&partial, out).with_context(|| format!("Failed to write {}", out.display()))?;
+
+    let values: usize = snapshot.devices.values().map(|values| values.len()).sum();
+    eprintln!(
+        "Saved {} values of {} devices to {}",
+        values,
+        snapshot.devices.len(),
+        out.display()
+    );
+    Ok(())
+}
@@ -1,21 +1,279 @@
 //! Configuration management for RustBridge
 
-use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 use std::path::Path;
 
+use crate::error::{Result, RustbridgeError};
+use crate::modbus::codec::{ByteOrder, NonFinitePolicy};
+use crate::modbus::transform::{Expression, LookupTable};
+
 /// Main configuration structure
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
     /// Server configuration
     pub server: ServerConfig,
-    /// MQTT broker configuration
-    pub mqtt: MqttConfig,
+    /// MQTT broker configuration (omit to run without MQTT)
+    #[serde(default)]
+    pub mqtt: Option<MqttConfig>,
     /// API authentication configuration
     #[serde(default)]
     pub auth: AuthConfig,
+    /// Logging configuration
+    #[serde(default)]
+    pub logging: LoggingConfig,
+    /// Write audit log configuration
+    #[serde(default)]
+    pub audit: AuditConfig,
+    /// systemd readiness and watchdog configuration
+    #[serde(default)]
+    pub systemd: SystemdConfig,
+    /// Daemon mode and privilege drop configuration
+    #[serde(default)]
+    pub daemon: DaemonConfig,
+    /// Register value snapshot configuration
+    #[serde(default)]
+    pub snapshot: SnapshotConfig,
+    /// Async runtime sizing
+    #[serde(default)]
+    pub runtime: RuntimeConfig,
+    /// Global write switch
+    #[serde(default)]
+    pub writes: WritesConfig,
+    /// Configuration pulled from a fleet management server
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remote_config: Option<RemoteConfig>,
+    /// Checks for (and optionally installs) new releases
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub update: Option<UpdateConfig>,
+    /// Where device `profile`s are loaded from
+    #[serde(default)]
+    pub profiles: ProfilesConfig,
     /// List of Modbus devices
     pub devices: Vec<DeviceConfig>,
+    /// Named sequences of writes, run as a unit
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub recipes: Vec<RecipeConfig>,
+}
+
+/// A named, ordered list of register writes, e.g. for a machine changeover
+///
+/// ```yaml
+/// recipes:
+///   - name: product-b
+///     description: Changeover to product B
+///     steps:
+///       - { device_id: press-1, register: pressure_setpoint, value: 180 }
+///       - { device_id: oven-1, register: temperature_setpoint, value: 215.5, tolerance: 0.5 }
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecipeConfig {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Conditions that must all hold before each step is written
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub require: Vec<Interlock>,
+    pub steps: Vec<RecipeStep>,
+}
+
+/// One write of a recipe
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecipeStep {
+    pub device_id: String,
+    pub register: String,
+    pub value: f64,
+    /// Largest difference accepted between `value` and the value read back
+    /// after the write; rounding differences only when omitted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tolerance: Option<f64>,
+}
+
+/// Configuration pulled from a URL and applied like a reload
+///
+/// ```yaml
+/// remote_config:
+///   url: https://fleet.example.com/gateways/line-3.yaml
+///   authorization: "Bearer 8f2c41d0"
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RemoteConfig {
+    /// `https://` URL of the configuration YAML
+    pub url: String,
+    /// Seconds between checks for a new version; 0 only pulls on
+    /// `POST /api/config/pull`
+    #[serde(default = "RemoteConfig::default_poll_interval_secs")]
+    pub poll_interval_secs: u64,
+    /// Value of the `Authorization` header sent with every request
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub authorization: Option<String>,
+    /// CA certificate (PEM) verifying the server instead of the system roots
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ca_cert: Option<String>,
+    /// Seconds to wait for the server before giving up on a check
+    #[serde(default = "RemoteConfig::default_timeout_secs")]
+    pub timeout_secs: u64,
+}
+
+impl RemoteConfig {
+    fn default_poll_interval_secs() -> u64 {
+        300
+    }
+
+    fn default_timeout_secs() -> u64 {
+        10
+    }
+}
+
+/// Release checks against a manifest URL
+///
+/// ```yaml
+/// update:
+///   url: https://releases.example.com/rustbridge/{os}-{arch}.json
+///   auto_install: true
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UpdateConfig {
+    /// `https://` or `http://` URL of the release manifest (`https://` only
+    /// with `auto_install`); `{os}` and `{arch}` are replaced with the
+    /// platform the bridge was built for
+    pub url: String,
+    /// Seconds between checks; 0 only checks at startup
+    #[serde(default = "UpdateConfig::default_check_interval_secs")]
+    pub check_interval_secs: u64,
+    /// Value of the `Authorization` header sent with manifest requests, and
+    /// with downloads from the same host
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub authorization: Option<String>,
+    /// CA certificate (PEM) verifying the server instead of the system roots
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ca_cert: Option<String>,
+    /// Seconds to wait for the server before giving up on a request
+    #[serde(default = "UpdateConfig::default_timeout_secs")]
+    pub timeout_secs: u64,
+    /// Download, verify and restart into a newer release (Unix only)
+    #[serde(default)]
+    pub auto_install: bool,
+    /// Seconds a newly installed release has to read from a device before it
+    /// is kept; otherwise the previous binary is restored
+    #[serde(default = "UpdateConfig::default_health_check_secs")]
+    pub health_check_secs: u64,
+}
+
+impl UpdateConfig {
+    fn default_check_interval_secs() -> u64 {
+        3600
+    }
+
+    fn default_timeout_secs() -> u64 {
+        30
+    }
+
+    fn default_health_check_secs() -> u64 {
+        120
+    }
+
+    /// The manifest URL with `{os}` and `{arch}` filled in
+    pub fn manifest_url(&self) -> String {
+        self.url
+            .replace("{os}", std::env::consts::OS)
+            .replace("{arch}", std::env::consts::ARCH)
+    }
+}
+
+/// Sources of device profiles, searched in order
+///
+/// ```yaml
+/// profiles:
+///   paths: [/etc/rustbridge/profiles]
+///   git:
+///     - url: https://github.com/example/rustbridge-profiles.git
+///       rev: main
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProfilesConfig {
+    /// Directories searched first
+    #[serde(default)]
+    pub paths: Vec<String>,
+    /// Git repositories searched after `paths`
+    #[serde(default)]
+    pub git: Vec<GitProfiles>,
+    /// Directory the git repositories are cloned into
+    #[serde(default = "ProfilesConfig::default_cache_dir")]
+    pub cache_dir: String,
+}
+
+impl ProfilesConfig {
+    fn default_cache_dir() -> String {
+        "profile-cache".to_string()
+    }
+}
+
+impl Default for ProfilesConfig {
+    fn default() -> Self {
+        Self {
+            paths: Vec::new(),
+            git: Vec::new(),
+            cache_dir: Self::default_cache_dir(),
+        }
+    }
+}
+
+/// Git repository of device profiles
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GitProfiles {
+    /// Clone URL
+    pub url: String,
+    /// Branch or tag to check out, the default branch if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rev: Option<String>,
+}
+
+impl GitProfiles {
+    /// Why git would take the URL or revision for an option, if it would
+    pub fn option_error(&self) -> Option<String> {
+        [("url", Some(&self.url)), ("rev", self.rev.as_ref())]
+            .into_iter()
+            .find_map(|(field, value)| {
+                let value = value.filter(|value| value.starts_with('-'))?;
+                Some(format!(
+                    "profiles.git {} '{}' must not start with '-'",
+                    field, value
+                ))
+            })
+    }
+}
+
+/// Register value snapshot configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotConfig {
+    /// Snapshot file (from `rustbridge snapshot`) seeding the store at startup,
+    /// so retained values are served before the first poll completes
+    #[serde(default)]
+    pub preload: Option<String>,
+    /// File the store is saved to periodically and on shutdown, and restored
+    /// from at startup
+    #[serde(default)]
+    pub persist: Option<String>,
+    /// Seconds between saves of the `persist` file
+    #[serde(default = "SnapshotConfig::default_persist_interval_secs")]
+    pub persist_interval_secs: u64,
+}
+
+impl SnapshotConfig {
+    fn default_persist_interval_secs() -> u64 {
+        60
+    }
+}
+
+impl Default for SnapshotConfig {
+    fn default() -> Self {
+        Self {
+            preload: None,
+            persist: None,
+            persist_interval_secs: Self::default_persist_interval_secs(),
+        }
+    }
 }
 
 /// API Authentication configuration
@@ -27,6 +285,9 @@ pub struct AuthConfig {
     /// List of valid API keys
     #[serde(default)]
     pub api_keys: Vec<String>,
+    /// API keys by tenant, limited to the devices of their tenant
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub tenant_keys: BTreeMap<String, Vec<String>>,
     /// Paths excluded from authentication (e.g., /health, /metrics)
     #[serde(default = "AuthConfig::default_exclude_paths")]
     pub exclude_paths: Vec<String>,
@@ -37,6 +298,7 @@ impl Default for AuthConfig {
         Self {
             enabled: false,
             api_keys: vec![],
+            tenant_keys: BTreeMap::new(),
             exclude_paths: Self::default_exclude_paths(),
         }
     }
@@ -48,6 +310,260 @@ impl AuthConfig {
     }
 }
 
+/// Write audit log configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditConfig {
+    /// Append-only JSON lines file (optional; entries are always kept in memory)
+    #[serde(default)]
+    pub file: Option<String>,
+    /// Number of recent entries kept in memory for `/api/audit`
+    #[serde(default = "AuditConfig::default_memory_size")]
+    pub memory_size: usize,
+    /// Estimated bytes the in-memory entries may take before the oldest are
+    /// evicted (`null` for no limit)
+    #[serde(default = "AuditConfig::default_memory_max_bytes")]
+    pub memory_max_bytes: Option<usize>,
+}
+
+impl Default for AuditConfig {
+    fn default() -> Self {
+        Self {
+            file: None,
+            memory_size: Self::default_memory_size(),
+            memory_max_bytes: Self::default_memory_max_bytes(),
+        }
+    }
+}
+
+impl AuditConfig {
+    fn default_memory_size() -> usize {
+        1000
+    }
+
+    fn default_memory_max_bytes() -> Option<usize> {
+        Some(4 * 1024 * 1024)
+    }
+}
+
+/// Async runtime sizing, for gateways with little memory
+///
+/// Unset values keep tokio's defaults.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RuntimeConfig {
+    /// Worker threads (default: one per CPU core); `1` runs everything on the
+    /// main thread
+    #[serde(default)]
+    pub worker_threads: Option<usize>,
+    /// Threads for blocking work such as file I/O (default: 512)
+    #[serde(default)]
+    pub max_blocking_threads: Option<usize>,
+    /// Stack size of runtime threads in bytes (default: 2 MiB)
+    #[serde(default)]
+    pub thread_stack_size: Option<usize>,
+}
+
+/// Global write switch, e.g. to keep upstream systems off the actuators
+/// while commissioning
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WritesConfig {
+    /// Accept register writes at startup; toggled at runtime with
+    /// `POST /api/writes`
+    #[serde(default = "WritesConfig::default_enabled")]
+    pub enabled: bool,
+}
+
+impl Default for WritesConfig {
+    fn default() -> Self {
+        Self {
+            enabled: Self::default_enabled(),
+        }
+    }
+}
+
+impl WritesConfig {
+    fn default_enabled() -> bool {
+        true
+    }
+}
+
+/// systemd integration (only active when started with `Type=notify`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SystemdConfig {
+    /// Report ready after this long even if not all devices have connected
+    #[serde(default = "SystemdConfig::default_startup_grace_secs")]
+    pub startup_grace_secs: u64,
+    /// Stop watchdog keepalives when a device's poll loop has not ticked for
+    /// this long beyond its poll interval
+    #[serde(default = "SystemdConfig::default_stall_timeout_secs")]
+    pub stall_timeout_secs: u64,
+}
+
+impl Default for SystemdConfig {
+    fn default() -> Self {
+        Self {
+            startup_grace_secs: Self::default_startup_grace_secs(),
+            stall_timeout_secs: Self::default_stall_timeout_secs(),
+        }
+    }
+}
+
+impl SystemdConfig {
+    fn default_startup_grace_secs() -> u64 {
+        30
+    }
+
+    fn default_stall_timeout_secs() -> u64 {
+        60
+    }
+}
+
+/// Daemon mode (`--daemon`) and privilege drop configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DaemonConfig {
+    /// PID file written in daemon mode
+    #[serde(default = "DaemonConfig::default_pid_file")]
+    pub pid_file: String,
+    /// Drop root privileges to this user once serial ports and the API port are open
+    #[serde(default)]
+    pub user: Option<String>,
+    /// Group to switch to (defaults to the user's primary group)
+    #[serde(default)]
+    pub group: Option<String>,
+}
+
+impl Default for DaemonConfig {
+    fn default() -> Self {
+        Self {
+            pid_file: Self::default_pid_file(),
+            user: None,
+            group: None,
+        }
+    }
+}
+
+impl DaemonConfig {
+    fn default_pid_file() -> String {
+        "/run/rustbridge.pid".to_string()
+    }
+}
+
+/// Logging configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoggingConfig {
+    /// Default log level: "trace", "debug", "info", "warn", "error"
+    #[serde(default = "LoggingConfig::default_level")]
+    pub level: String,
+    /// Output format: "text" or "json"
+    #[serde(default)]
+    pub format: LogFormat,
+    /// Per-module level overrides (e.g., `rustbridge::modbus: debug`)
+    #[serde(default)]
+    pub modules: HashMap<String, String>,
+    /// Output target: "stdout" or "file"
+    #[serde(default)]
+    pub target: LogTarget,
+    /// Log file path (required when target is "file")
+    #[serde(default)]
+    pub file: Option<String>,
+    /// Syslog collector settings (required when target is "syslog")
+    #[serde(default)]
+    pub syslog: Option<SyslogConfig>,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            level: Self::default_level(),
+            format: LogFormat::default(),
+            modules: HashMap::new(),
+            target: LogTarget::default(),
+            file: None,
+            syslog: None,
+        }
+    }
+}
+
+impl LoggingConfig {
+    fn default_level() -> String {
+        "info".to_string()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogTarget {
+    #[default]
+    Stdout,
+    File,
+    Syslog,
+}
+
+/// Syslog (RFC 5424) output configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyslogConfig {
+    /// Collector host
+    pub host: String,
+    /// Collector port (default: 514 for udp/tcp, 6514 for tls)
+    #[serde(default)]
+    pub port: Option<u16>,
+    /// Transport: "udp", "tcp" or "tls"
+    #[serde(default)]
+    pub transport: SyslogTransport,
+    /// Syslog facility (e.g., "daemon", "local0")
+    #[serde(default = "SyslogConfig::default_facility")]
+    pub facility: String,
+    /// APP-NAME header field
+    #[serde(default = "SyslogConfig::default_app_name")]
+    pub app_name: String,
+    /// HOSTNAME header field (default: system hostname)
+    #[serde(default)]
+    pub hostname: Option<String>,
+    /// CA certificate for TLS (default: system roots)
+    #[serde(default)]
+    pub ca_cert: Option<String>,
+}
+
+impl Default for SyslogConfig {
+    fn default() -> Self {
+        Self {
+            host: "localhost".to_string(),
+            port: None,
+            transport: SyslogTransport::default(),
+            facility: Self::default_facility(),
+            app_name: Self::default_app_name(),
+            hostname: None,
+            ca_cert: None,
+        }
+    }
+}
+
+impl SyslogConfig {
+    fn default_facility() -> String {
+        "daemon".to_string()
+    }
+
+    fn default_app_name() -> String {
+        "rustbridge".to_string()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SyslogTransport {
+    #[default]
+    Udp,
+    Tcp,
+    Tls,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ServerConfig {
     /// HTTP API host
@@ -56,6 +572,82 @@ pub struct ServerConfig {
     pub port: u16,
     /// Enable metrics endpoint
     pub metrics_enabled: bool,
+    /// Number of recent error events kept for `/api/errors`
+    #[serde(default = "ServerConfig::default_error_log_size")]
+    pub error_log_size: usize,
+    /// Estimated bytes the error events may take before the oldest are
+    /// evicted (`null` for no limit)
+    #[serde(default = "ServerConfig::default_error_log_max_bytes")]
+    pub error_log_max_bytes: Option<usize>,
+    /// Register updates buffered for each subscriber (WebSocket, MQTT,
+    /// library streams); a subscriber further behind starts dropping updates
+    #[serde(default = "ServerConfig::default_update_channel_capacity")]
+    pub update_channel_capacity: usize,
+    /// Name, labels and selection of the register value gauges on `/metrics`
+    #[serde(default, skip_serializing_if = "RegisterGauges::is_default")]
+    pub register_gauges: RegisterGauges,
+}
+
+impl ServerConfig {
+    fn default_error_log_size() -> usize {
+        100
+    }
+
+    fn default_error_log_max_bytes() -> Option<usize> {
+        Some(1024 * 1024)
+    }
+
+    fn default_update_channel_capacity() -> usize {
+        1024
+    }
+}
+
+/// Gauges exporting register values on `/metrics`
+///
+/// `name` and the values of `labels` are templates: `{device}`, `{register}`,
+/// `{group}`, `{unit}` and `{meta.KEY}` are replaced with the register's
+/// device ID, name, device group, unit and device or register `meta` field.
+///
+/// ```yaml
+/// register_gauges:
+///   name: "plant_{register}"
+///   labels: { site: "{meta.site}", asset: "{device}" }
+///   filter:
+///     exclude:
+///       - register: "debug_*"
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RegisterGauges {
+    /// Metric name template
+    #[serde(default = "RegisterGauges::default_name")]
+    pub name: String,
+    /// Label templates by label name; `device` and `register` when empty.
+    /// Devices of a tenant always get a `tenant` label.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub labels: BTreeMap<String, String>,
+    /// Registers exported, all by default
+    #[serde(default, skip_serializing_if = "PublishFilter::is_empty")]
+    pub filter: PublishFilter,
+}
+
+impl RegisterGauges {
+    fn default_name() -> String {
+        "rustbridge_register_value".to_string()
+    }
+
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl Default for RegisterGauges {
+    fn default() -> Self {
+        Self {
+            name: Self::default_name(),
+            labels: BTreeMap::new(),
+            filter: PublishFilter::default(),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -80,62 +672,765 @@ pub struct MqttConfig {
     pub username: Option<String>,
     /// Password (optional)
     pub password: Option<String>,
+    /// Accept register writes on `{prefix}/{device_id}/{register}/set`
+    #[serde(default)]
+    pub accept_writes: bool,
+    /// Answer JSON-RPC requests on `{prefix}/bridge/rpc/request`
+    #[serde(default)]
+    pub rpc_enabled: bool,
+    /// Bridge heartbeat interval in seconds (0 disables the heartbeat)
+    #[serde(default = "MqttConfig::default_heartbeat_interval_secs")]
+    pub heartbeat_interval_secs: u64,
+    /// What happens when publishing falls behind the pollers
+    #[serde(default)]
+    pub backpressure: Backpressure,
+    /// How bad and substituted readings are published
+    #[serde(default)]
+    pub quality_policy: QualityPolicy,
+    /// One message per register update, or one per device and poll cycle
+    #[serde(default)]
+    pub publish_mode: PublishMode,
+    /// Batching of `publish_mode: device`
+    #[serde(default)]
+    pub batch: BatchConfig,
+    /// Publish poll statistics of each device on `{prefix}/{device_id}/$stats`
+    /// every this many poll cycles (0 disables them)
+    #[serde(default)]
+    pub stats_every_cycles: u32,
+    /// Home Assistant MQTT discovery
+    #[serde(default)]
+    pub discovery: DiscoveryConfig,
+    /// Which register values are published to the broker
+    #[serde(default, skip_serializing_if = "PublishFilter::is_empty")]
+    pub filter: PublishFilter,
+    /// Start a new session on every connect; `false` keeps the broker
+    /// session, so QoS 1/2 messages survive reconnects
+    #[serde(default = "MqttConfig::default_clean_session")]
+    pub clean_session: bool,
+    /// Seconds between keep-alive pings while nothing else is sent (0
+    /// disables them)
+    #[serde(default = "MqttConfig::default_keep_alive_secs")]
+    pub keep_alive_secs: u16,
+    /// Message the broker publishes when the bridge disconnects uncleanly
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_will: Option<LastWillConfig>,
+    /// QoS 1/2 publishes awaiting acknowledgement before publishing waits
+    #[serde(default = "MqttConfig::default_inflight")]
+    pub inflight: u16,
+    /// Largest packet sent or accepted, in bytes
+    #[serde(default = "MqttConfig::default_max_packet_size")]
+    pub max_packet_size: usize,
+    /// Connect to the broker over TLS
+    #[serde(default)]
+    pub use_tls: bool,
+    /// CA certificate (PEM) verifying the broker instead of the system roots
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ca_cert: Option<String>,
+    /// Certificate chain (PEM) the bridge authenticates with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_cert: Option<String>,
+    /// Private key (PEM) of `client_cert`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_key: Option<String>,
+    /// Accept any broker certificate, for test brokers with self-signed
+    /// certificates only
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub insecure_skip_verify: bool,
+    /// How often the certificate files are checked for renewal, in seconds
+    /// (0 disables the checks)
+    #[serde(default = "MqttConfig::default_cert_check_secs")]
+    pub cert_check_secs: u64,
+    /// Safe state written when contact with the broker is lost
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dead_man: Option<DeadManConfig>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct DeviceConfig {
-    /// Unique device ID
-    pub id: String,
-    /// Human-readable name
-    pub name: String,
-    /// Device type: "tcp" or "rtu"
-    pub device_type: DeviceType,
-    /// Connection settings
-    pub connection: ConnectionConfig,
-    /// Polling interval in milliseconds
-    pub poll_interval_ms: u64,
-    /// Registers to read
-    pub registers: Vec<RegisterConfig>,
+/// Granularity of register value messages
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PublishMode {
+    /// Every update on `{prefix}/{device_id}/{register}`
+    #[default]
+    Register,
+    /// The updates of a poll cycle together on `{prefix}/{device_id}`
+    Device,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "lowercase")]
-pub enum DeviceType {
-    Tcp,
-    Rtu,
+/// How updates are collected into device messages
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BatchConfig {
+    /// Updates of a device arriving within this many milliseconds of the
+    /// first are published together
+    #[serde(default = "BatchConfig::default_window_ms")]
+    pub window_ms: u64,
+    /// Include only registers whose value changed since the last message
+    #[serde(default)]
+    pub delta: bool,
+    /// With `delta`, every Nth message of a device carries all registers
+    #[serde(default = "BatchConfig::default_full_snapshot_every")]
+    pub full_snapshot_every: u32,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(untagged)]
-pub enum ConnectionConfig {
-    Tcp(TcpConnection),
-    Rtu(RtuConnection),
+impl BatchConfig {
+    fn default_window_ms() -> u64 {
+        100
+    }
+
+    fn default_full_snapshot_every() -> u32 {
+        10
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct TcpConnection {
-    /// Host address
-    pub host: String,
-    /// Port (default: 502)
-    pub port: u16,
-    /// Modbus unit ID
-    pub unit_id: u8,
+impl Default for BatchConfig {
+    fn default() -> Self {
+        Self {
+            window_ms: Self::default_window_ms(),
+            delta: false,
+            full_snapshot_every: Self::default_full_snapshot_every(),
+        }
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct RtuConnection {
-    /// Serial port path (e.g., /dev/ttyUSB0)
-    pub port: String,
-    /// Baud rate
-    pub baud_rate: u32,
-    /// Data bits
-    pub data_bits: u8,
-    /// Stop bits
-    pub stop_bits: u8,
-    /// Parity: "none", "even", "odd"
-    pub parity: String,
-    /// Modbus unit ID
-    pub unit_id: u8,
+/// Register values published to the broker
+///
+/// An update is published when it matches a rule of `include` (or `include`
+/// is empty) and no rule of `exclude`. Everything else still reaches the API,
+/// WebSocket and other local consumers.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PublishFilter {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<FilterRule>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude: Vec<FilterRule>,
+}
+
+impl PublishFilter {
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+}
+
+/// Matches registers meeting every condition given; `*` in a pattern matches
+/// any characters
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FilterRule {
+    /// Device ID pattern
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device: Option<String>,
+    /// Register name pattern
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub register: Option<String>,
+    /// Device group pattern
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    /// Device or register `meta` fields with these values
+    #[serde(default, skip_serializing_if = "Meta::is_empty")]
+    pub meta: Meta,
+}
+
+impl FilterRule {
+    fn is_empty(&self) -> bool {
+        self.device.is_none()
+            && self.register.is_none()
+            && self.group.is_none()
+            && self.meta.is_empty()
+    }
+}
+
+/// Announcing registers to Home Assistant
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiscoveryConfig {
+    /// Publish retained discovery messages for every register
+    #[serde(default)]
+    pub enabled: bool,
+    /// Topic prefix Home Assistant watches for discovery messages
+    #[serde(default = "DiscoveryConfig::default_prefix")]
+    pub prefix: String,
+}
+
+impl DiscoveryConfig {
+    fn default_prefix() -> String {
+        "homeassistant".to_string()
+    }
+}
+
+impl Default for DiscoveryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            prefix: Self::default_prefix(),
+        }
+    }
+}
+
+/// Last Will and Testament of the bridge, published at the configured `qos`
+///
+/// ```yaml
+/// last_will:
+///   topic: rustbridge/bridge/status
+///   payload: offline
+///   retain: true
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LastWillConfig {
+    pub topic: String,
+    #[serde(default)]
+    pub payload: String,
+    #[serde(default)]
+    pub retain: bool,
+}
+
+/// Fail-safe writes after losing contact with the broker
+///
+/// ```yaml
+/// dead_man:
+///   timeout_secs: 30
+///   heartbeat_topic: scada/heartbeat
+///   safe_state:
+///     - { device_id: press-1, register: run_enable, value: 0 }
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeadManConfig {
+    /// Seconds without contact after which the safe state is written
+    pub timeout_secs: u64,
+    /// Topic on which every message counts as contact; without it, being
+    /// connected to the broker does
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub heartbeat_topic: Option<String>,
+    /// Registers written, in order, when the timeout expires
+    pub safe_state: Vec<SafeOutput>,
+}
+
+/// Value a register is put in by the dead-man switch
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SafeOutput {
+    pub device_id: String,
+    pub register: String,
+    pub value: f64,
+}
+
+/// How a slow sink is handled once its buffer of updates is full
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Backpressure {
+    /// Skip the oldest updates and keep polling on schedule
+    #[default]
+    Drop,
+    /// Hold the pollers until the sink catches up, so no update is lost
+    Block,
+}
+
+/// How register updates that are not of good quality reach MQTT
+///
+/// Applies to `null` readings (`bad`) and stale last good values
+/// (`substituted`), see the register `non_finite` option.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QualityPolicy {
+    /// Publish them like good readings, without a `quality` field
+    Publish,
+    /// Publish them with a `quality` field
+    #[default]
+    Tag,
+    /// Publish nothing on the register topic; only the retained
+    /// `{register topic}/quality` status changes
+    Suppress,
+}
+
+impl MqttConfig {
+    fn default_heartbeat_interval_secs() -> u64 {
+        30
+    }
+
+    fn default_clean_session() -> bool {
+        true
+    }
+
+    fn default_keep_alive_secs() -> u16 {
+        30
+    }
+
+    fn default_inflight() -> u16 {
+        100
+    }
+
+    fn default_max_packet_size() -> usize {
+        10 * 1024
+    }
+
+    fn default_cert_check_secs() -> u64 {
+        60
+    }
+}
+
+impl Default for MqttConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            host: "localhost".to_string(),
+            port: 1883,
+            client_id: "rustbridge".to_string(),
+            topic_prefix: "rustbridge".to_string(),
+            qos: 1,
+            retain: false,
+            username: None,
+            password: None,
+            accept_writes: false,
+            rpc_enabled: false,
+            heartbeat_interval_secs: Self::default_heartbeat_interval_secs(),
+            backpressure: Backpressure::default(),
+            quality_policy: QualityPolicy::default(),
+            publish_mode: PublishMode::default(),
+            batch: BatchConfig::default(),
+            stats_every_cycles: 0,
+            discovery: DiscoveryConfig::default(),
+            filter: PublishFilter::default(),
+            clean_session: Self::default_clean_session(),
+            keep_alive_secs: Self::default_keep_alive_secs(),
+            last_will: None,
+            inflight: Self::default_inflight(),
+            max_packet_size: Self::default_max_packet_size(),
+            use_tls: false,
+            ca_cert: None,
+            client_cert: None,
+            client_key: None,
+            insecure_skip_verify: false,
+            cert_check_secs: Self::default_cert_check_secs(),
+            dead_man: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceConfig {
+    /// Unique device ID
+    pub id: String,
+    /// Human-readable name
+    pub name: String,
+    /// Device type: "tcp" or "rtu"
+    pub device_type: DeviceType,
+    /// Connection settings
+    pub connection: ConnectionConfig,
+    /// Polling interval in milliseconds
+    pub poll_interval_ms: u64,
+    /// Profile (e.g. `eastron/sdm630`) whose registers the device reads in
+    /// addition to `registers`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+    /// Registers to read; one named like a profile register replaces it
+    #[serde(default)]
+    pub registers: Vec<RegisterConfig>,
+    /// Custom fields added to every payload of this device
+    #[serde(default, skip_serializing_if = "Meta::is_empty")]
+    pub meta: Meta,
+    /// Group (e.g. production line) the device can be controlled and monitored with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    /// Customer the device belongs to; prefixes its MQTT topics, limits it to
+    /// the tenant's API keys and labels its metrics
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tenant: Option<String>,
+    /// Periodically write the bridge clock to the device's RTC registers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time_sync: Option<TimeSyncConfig>,
+    /// Backing off from registers that keep failing with the same exception
+    #[serde(default, skip_serializing_if = "CircuitBreakerConfig::is_default")]
+    pub circuit_breaker: CircuitBreakerConfig,
+    /// Time after which the rest of a cycle's `low` and `normal` registers
+    /// are deferred to the next cycle
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cycle_budget_ms: Option<u64>,
+    /// Start polls at multiples of `poll_interval_ms` on the wall clock, e.g.
+    /// at :00, :15, :30 and :45 with 15 s
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub align_to_clock: bool,
+    /// Waiting between attempts to re-establish the connection
+    #[serde(default, skip_serializing_if = "ReconnectConfig::is_default")]
+    pub reconnect: ReconnectConfig,
+    /// Read neighbouring registers with one request
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub read_blocks: Option<ReadBlocksConfig>,
+}
+
+/// Skipping registers that keep failing with the same Modbus exception
+///
+/// After `failures` identical exceptions in a row a register is skipped for
+/// `backoff_ms`, then read once as a probe. Each failed probe doubles the wait
+/// up to `max_backoff_ms`; a successful read resumes normal polling.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CircuitBreakerConfig {
+    /// Identical exceptions in a row that open the breaker (0 disables it)
+    #[serde(default = "CircuitBreakerConfig::default_failures")]
+    pub failures: u32,
+    /// Wait before the first probe in milliseconds
+    #[serde(default = "CircuitBreakerConfig::default_backoff_ms")]
+    pub backoff_ms: u64,
+    /// Longest wait between probes in milliseconds
+    #[serde(default = "CircuitBreakerConfig::default_max_backoff_ms")]
+    pub max_backoff_ms: u64,
+}
+
+impl CircuitBreakerConfig {
+    fn default_failures() -> u32 {
+        5
+    }
+
+    fn default_backoff_ms() -> u64 {
+        10_000
+    }
+
+    fn default_max_backoff_ms() -> u64 {
+        600_000
+    }
+
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            failures: Self::default_failures(),
+            backoff_ms: Self::default_backoff_ms(),
+            max_backoff_ms: Self::default_max_backoff_ms(),
+        }
+    }
+}
+
+/// Re-establishing the connection to a device that cannot be reached
+///
+/// After a failed connection attempt the next one waits `backoff_ms`, doubling
+/// after each further failure up to `max_backoff_ms`. After `max_retries`
+/// failures in a row the device is given up until it is asked to poll now or
+/// its configuration changes.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReconnectConfig {
+    /// Wait after the first failed attempt in milliseconds
+    #[serde(default = "ReconnectConfig::default_backoff_ms")]
+    pub backoff_ms: u64,
+    /// Longest wait between attempts in milliseconds
+    #[serde(default = "ReconnectConfig::default_max_backoff_ms")]
+    pub max_backoff_ms: u64,
+    /// Failed attempts in a row before giving up (0 retries forever)
+    #[serde(default)]
+    pub max_retries: u32,
+}
+
+impl ReconnectConfig {
+    fn default_backoff_ms() -> u64 {
+        1_000
+    }
+
+    fn default_max_backoff_ms() -> u64 {
+        60_000
+    }
+
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl Default for ReconnectConfig {
+    fn default() -> Self {
+        Self {
+            backoff_ms: Self::default_backoff_ms(),
+            max_backoff_ms: Self::default_max_backoff_ms(),
+            max_retries: 0,
+        }
+    }
+}
+
+/// Reading registers that lie close together with one request
+///
+/// Registers of the same type at most `max_gap` addresses apart are read as
+/// one block of up to `max_registers` registers (or bits), which is then split
+/// up again. Registers with `read_when`, `read_after`, `trigger` or
+/// `oversample` are always read on their own.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReadBlocksConfig {
+    /// Unused addresses allowed between two registers of a block
+    #[serde(default)]
+    pub max_gap: u16,
+    /// Largest block, at most the 125 registers a Modbus read can return
+    #[serde(default = "ReadBlocksConfig::default_max_registers")]
+    pub max_registers: u16,
+}
+
+impl ReadBlocksConfig {
+    fn default_max_registers() -> u16 {
+        125
+    }
+}
+
+impl Default for ReadBlocksConfig {
+    fn default() -> Self {
+        Self {
+            max_gap: 0,
+            max_registers: Self::default_max_registers(),
+        }
+    }
+}
+
+/// Clock registers of a device and how often to set them
+///
+/// ```yaml
+/// time_sync:
+///   address: 200
+///   layout: [year, month, day, hour, minute, second]
+///   interval_secs: 3600
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimeSyncConfig {
+    /// First holding register of the clock
+    pub address: u16,
+    /// Fields written to consecutive registers, in order
+    pub layout: Vec<TimeField>,
+    /// Seconds between writes (the first write follows each connect)
+    #[serde(default = "TimeSyncConfig::default_interval_secs")]
+    pub interval_secs: u64,
+    /// Write UTC or the bridge's local time
+    #[serde(default)]
+    pub timezone: TimeZoneMode,
+    /// Encode each field as BCD (e.g. 2026 as 0x2026)
+    #[serde(default)]
+    pub bcd: bool,
+}
+
+impl TimeSyncConfig {
+    fn default_interval_secs() -> u64 {
+        3600
+    }
+
+    /// Number of registers the layout occupies
+    pub fn register_count(&self) -> usize {
+        self.layout.iter().map(TimeField::register_count).sum()
+    }
+}
+
+/// A clock field occupying one register (two for `unix`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimeField {
+    /// Four-digit year
+    Year,
+    /// Year within the century (0-99)
+    Year2,
+    /// Month (1-12)
+    Month,
+    /// Day of month (1-31)
+    Day,
+    /// Hour (0-23)
+    Hour,
+    /// Minute (0-59)
+    Minute,
+    /// Second (0-59)
+    Second,
+    /// Millisecond (0-999)
+    Millisecond,
+    /// Day of week, 0 = Sunday
+    Weekday,
+    /// Seconds since the Unix epoch, high word first
+    Unix,
+    /// Register left at zero
+    Reserved,
+}
+
+impl TimeField {
+    /// Registers taken by the field
+    pub fn register_count(&self) -> usize {
+        match self {
+            TimeField::Unix => 2,
+            _ => 1,
+        }
+    }
+}
+
+/// Time zone of the clock written to a device
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TimeZoneMode {
+    #[default]
+    Utc,
+    Local,
+}
+
+/// Custom key/value fields attached to devices and registers
+pub type Meta = BTreeMap<String, serde_json::Value>;
+
+/// Payload fields that `meta` keys may not shadow
+pub const RESERVED_META_KEYS: &[&str] = &[
+    "device_id",
+    "register_name",
+    "name",
+    "value",
+    "raw",
+    "unit",
+    "timestamp",
+    "previous_value",
+    "changed",
+    "quality",
+];
+
+impl DeviceConfig {
+    /// Device meta overlaid with the register's own meta
+    pub fn register_meta(&self, register: &RegisterConfig) -> Meta {
+        let mut meta = self.meta.clone();
+        meta.extend(register.meta.clone());
+        meta
+    }
+
+    /// An RTU device addressed as unit 0: writes reach every slave on the
+    /// bus, nobody answers and nothing can be read
+    pub fn is_broadcast(&self) -> bool {
+        matches!(&self.connection, ConnectionConfig::Rtu(rtu) if rtu.unit_id == 0 && !rtu.listen_only)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeviceType {
+    Tcp,
+    Rtu,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ConnectionConfig {
+    Tcp(TcpConnection),
+    Rtu(RtuConnection),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TcpConnection {
+    /// Host address
+    pub host: String,
+    /// Port (default: 502)
+    pub port: u16,
+    /// Modbus unit ID
+    pub unit_id: u8,
+    /// Give up connecting after this many milliseconds (OS default when unset)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub connect_timeout_ms: Option<u64>,
+    /// Send TCP keepalive probes after this many idle seconds (disabled when unset)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tcp_keepalive_secs: Option<u64>,
+    /// Disable Nagle's algorithm so requests are sent immediately
+    #[serde(default = "TcpConnection::default_nodelay")]
+    pub tcp_nodelay: bool,
+    /// Local IP address to connect from, e.g. to use a specific network interface
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub local_address: Option<std::net::IpAddr>,
+}
+
+impl Default for TcpConnection {
+    fn default() -> Self {
+        Self {
+            host: "127.0.0.1".to_string(),
+            port: 502,
+            unit_id: 1,
+            connect_timeout_ms: None,
+            tcp_keepalive_secs: None,
+            tcp_nodelay: Self::default_nodelay(),
+            local_address: None,
+        }
+    }
+}
+
+impl TcpConnection {
+    fn default_nodelay() -> bool {
+        true
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RtuConnection {
+    /// Serial port path (e.g., /dev/ttyUSB0); may be omitted when `usb` is set
+    #[serde(default)]
+    pub port: String,
+    /// Find the port by USB adapter identity instead of its device node
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usb: Option<UsbMatch>,
+    /// Baud rate
+    pub baud_rate: u32,
+    /// Data bits
+    pub data_bits: u8,
+    /// Stop bits
+    pub stop_bits: u8,
+    /// Parity: "none", "even", "odd"
+    pub parity: String,
+    /// Modbus unit ID
+    pub unit_id: u8,
+    /// Time to wait for a response before counting a timeout (waits indefinitely if unset)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response_timeout_ms: Option<u64>,
+    /// Times a read is sent again after a timeout or corrupt response
+    #[serde(default)]
+    pub retries: u8,
+    /// Never transmit: decode the traffic of the existing master on the bus
+    /// and publish the register values it reads and writes
+    #[serde(default)]
+    pub listen_only: bool,
+}
+
+/// Identifies a USB serial adapter regardless of the device node it gets
+///
+/// IDs are hexadecimal as printed by `rustbridge ports`. Every field that is
+/// set must match.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UsbMatch {
+    /// USB vendor ID, e.g. "0403"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vid: Option<String>,
+    /// USB product ID, e.g. "6001"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pid: Option<String>,
+    /// Adapter serial number
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub serial_number: Option<String>,
+}
+
+impl UsbMatch {
+    /// Whether an adapter with these IDs matches
+    pub fn matches(&self, vid: u16, pid: u16, serial_number: Option<&str>) -> bool {
+        fn id_matches(expected: &Option<String>, actual: u16) -> bool {
+            expected
+                .as_deref()
+                .is_none_or(|id| parse_usb_id(id) == Some(actual))
+        }
+
+        id_matches(&self.vid, vid)
+            && id_matches(&self.pid, pid)
+            && self
+                .serial_number
+                .as_deref()
+                .is_none_or(|expected| serial_number == Some(expected))
+    }
+}
+
+impl std::fmt::Display for UsbMatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let fields = [
+            ("vid", &self.vid),
+            ("pid", &self.pid),
+            ("serial_number", &self.serial_number),
+        ];
+        let parts: Vec<String> = fields
+            .iter()
+            .filter_map(|(name, value)| value.as_ref().map(|v| format!("{}={}", name, v)))
+            .collect();
+        write!(f, "{}", parts.join(" "))
+    }
+}
+
+/// Parse a hexadecimal USB vendor or product ID ("0403" or "0x0403")
+fn parse_usb_id(id: &str) -> Option<u16> {
+    let id = id.trim();
+    let digits = id
+        .strip_prefix("0x")
+        .or_else(|| id.strip_prefix("0X"))
+        .unwrap_or(id);
+    u16::from_str_radix(digits, 16).ok()
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -146,7 +1441,9 @@ pub struct RegisterConfig {
     pub address: u16,
     /// Register type: "holding", "input", "coil", "discrete"
     pub register_type: RegisterType,
-    /// Number of registers to read
+    /// Number of registers (bits for coils and discrete inputs) to read;
+    /// derived from `data_type` when omitted
+    #[serde(default)]
     pub count: u16,
     /// Data type for interpretation
     pub data_type: DataType,
@@ -156,15 +1453,296 @@ pub struct RegisterConfig {
     pub scale: Option<f64>,
     /// Offset (optional)
     pub offset: Option<f64>,
+    /// Expression of the decoded value `raw`, used instead of scale and offset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expression: Option<Expression>,
+    /// Calibration points `[raw, value]` to interpolate instead of scale and offset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lookup: Option<LookupTable>,
+    /// Byte and word order of multi-register values
+    #[serde(default)]
+    pub byte_order: ByteOrder,
+    /// Handling of readings that convert to NaN or infinity
+    #[serde(default)]
+    pub non_finite: NonFinitePolicy,
+    /// Which registers are skipped first when the poll cycle overruns
+    #[serde(default)]
+    pub priority: Priority,
+    /// Custom fields added to every payload of this register
+    #[serde(default, skip_serializing_if = "Meta::is_empty")]
+    pub meta: Meta,
+    /// Only read while another register's last value meets a condition
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub read_when: Option<ReadCondition>,
+    /// Condition on this register's value raising an alarm event while it holds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alarm: Option<ValueCondition>,
+    /// Interlocks checked before a write reaches the device
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub write_limits: Option<WriteLimits>,
+    /// Read the register several times per poll and publish one combined value
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub oversample: Option<Oversample>,
+    /// Only read in a cycle after this register of the same device was read
+    /// (or written, for a trigger) successfully, right after it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub read_after: Option<String>,
+    /// Written with this value every poll cycle instead of being read, e.g.
+    /// a command that latches a snapshot of other registers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trigger: Option<f64>,
+    /// A changed bit is stored and published only once it has read the same
+    /// for this long, so bouncing inputs do not flap
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_stable_ms: Option<u64>,
+    /// Bits of a coil or discrete input block published as named points
+    /// instead of the block itself
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub points: Vec<BitPoint>,
+    /// Written again with the last commanded value when the device answers
+    /// after being offline, for devices that lose setpoints on power loss
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub persistent: bool,
+    /// Published to MQTT only when the value moved by more than `deadband`
+    /// since it was last published, or its quality changed
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub publish_on_change: bool,
+    /// Change in engineering units a value must exceed to be published again
+    /// with `publish_on_change` (default: any change)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deadband: Option<f64>,
+    /// With `publish_on_change`, publish an unchanged value again after this
+    /// many seconds so consumers know the register is still read
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub heartbeat_interval_s: Option<u64>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Named bit of a coil or discrete input block
+///
+/// ```yaml
+/// points:
+///   - { name: pump_running, offset: 0 }
+///   - { name: valve_open, offset: 5 }
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BitPoint {
+    /// Name the bit is published under, like a register
+    pub name: String,
+    /// Position of the bit in the block, from 0
+    pub offset: u16,
+}
+
+/// Most reads of an oversampled register per poll
+pub const MAX_OVERSAMPLES: u16 = 100;
+
+/// Several reads of a noisy register combined into one value per poll
+///
+/// ```yaml
+/// oversample:
+///   samples: 5
+///   spacing_ms: 20
+///   method: median
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Oversample {
+    /// Number of reads per poll
+    pub samples: u16,
+    /// Pause between two reads
+    #[serde(default = "Oversample::default_spacing_ms")]
+    pub spacing_ms: u64,
+    /// How the samples are combined
+    #[serde(default)]
+    pub method: SampleMethod,
+}
+
+impl Oversample {
+    fn default_spacing_ms() -> u64 {
+        10
+    }
+}
+
+/// How the samples of an oversampled register are combined
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SampleMethod {
+    /// Arithmetic mean, rounded to a raw step for integer types
+    #[default]
+    Mean,
+    /// Middle sample, robust against single spikes
+    Median,
+}
+
+/// Safety interlocks of a writable register
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct WriteLimits {
+    /// Lowest value accepted, in engineering units
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min: Option<f64>,
+    /// Highest value accepted, in engineering units
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max: Option<f64>,
+    /// The only values accepted, e.g. the modes of a selector
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub values: Vec<f64>,
+    /// Minimum time between two writes from the same source (API key, MQTT
+    /// topic or rule)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_interval_ms: Option<u64>,
+    /// Conditions on current register values that must all hold
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub require: Vec<Interlock>,
+}
+
+/// Precondition on the last value of a register, checked before writing
+///
+/// ```yaml
+/// require:
+///   - { register: running, equals: 1 }
+///   - { device_id: guard-plc, register: door_closed, bit: 0 }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Interlock {
+    /// Device of the register: the written device when omitted, required in
+    /// recipes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device_id: Option<String>,
+    pub register: String,
+    #[serde(flatten)]
+    pub test: ValueCondition,
+}
+
+impl WriteLimits {
+    /// Whether `value` is within `min` and `max` and one of `values`, if set
+    pub fn allows(&self, value: f64) -> bool {
+        self.min.is_none_or(|min| value >= min)
+            && self.max.is_none_or(|max| value <= max)
+            && (self.values.is_empty() || self.values.contains(&value))
+    }
+}
+
+impl RegisterConfig {
+    /// Register holding a single on/off state
+    pub fn is_bit(&self) -> bool {
+        matches!(
+            self.register_type,
+            RegisterType::Coil | RegisterType::Discrete | RegisterType::ExceptionStatus
+        ) || matches!(self.data_type, DataType::Bool)
+    }
+
+    /// Coil or status bit read as `bool`: published as `true`/`false` rather
+    /// than a number
+    pub fn is_boolean(&self) -> bool {
+        matches!(
+            self.register_type,
+            RegisterType::Coil | RegisterType::ExceptionStatus
+        ) && matches!(self.data_type, DataType::Bool)
+    }
+
+    /// Single-bit register of a point of this block, keeping its meta,
+    /// priority and debouncing
+    pub fn point(&self, point: &BitPoint) -> RegisterConfig {
+        RegisterConfig {
+            name: point.name.clone(),
+            address: self.address.wrapping_add(point.offset),
+            count: 1,
+            data_type: DataType::Bool,
+            unit: None,
+            scale: None,
+            offset: None,
+            expression: None,
+            lookup: None,
+            alarm: None,
+            write_limits: None,
+            oversample: None,
+            trigger: None,
+            points: Vec::new(),
+            ..self.clone()
+        }
+    }
+}
+
+/// Condition on the last value of another register of the same device
+///
+/// ```yaml
+/// read_when:
+///   register: pump_status
+///   bit: 0
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ReadCondition {
+    /// Name of the register whose value is tested
+    pub register: String,
+    #[serde(flatten)]
+    pub test: ValueCondition,
+}
+
+impl ReadCondition {
+    /// Whether `value` satisfies the condition
+    pub fn is_met(&self, value: f64) -> bool {
+        self.test.is_met(value)
+    }
+}
+
+/// Tests on a register value, as used by `read_when` and `alarm`
+///
+/// Every test that is set must hold; with none set, the value must be non-zero.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ValueCondition {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub equals: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub not_equals: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub above: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub below: Option<f64>,
+    /// Bit (0 = least significant) of the integer value that must be set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bit: Option<u8>,
+}
+
+impl ValueCondition {
+    /// Whether `value` satisfies the condition
+    pub fn is_met(&self, value: f64) -> bool {
+        let tests = [
+            self.equals.map(|v| value == v),
+            self.not_equals.map(|v| value != v),
+            self.above.map(|v| value > v),
+            self.below.map(|v| value < v),
+            self.bit.map(|bit| (value as i64) >> bit & 1 == 1),
+        ];
+        if tests.iter().all(Option::is_none) {
+            return value != 0.0;
+        }
+        tests.iter().flatten().all(|&passed| passed)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum RegisterType {
     Holding,
     Input,
     Coil,
     Discrete,
+    /// Bit `address` (0-7) of the Read Exception Status (FC07) byte
+    #[serde(rename = "exception_status")]
+    ExceptionStatus,
+    /// FIFO queue (FC24) at the pointer `address`, drained every poll
+    Fifo,
+}
+
+/// Importance of a register when its device cannot be polled in time
+///
+/// While poll cycles overrun the interval, `low` registers are skipped
+/// first, then `normal` ones; `high` registers are always read.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    Low,
+    #[default]
+    Normal,
+    High,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -174,10 +1752,29 @@ pub enum DataType {
     I16,
     U32,
     I32,
+    U64,
+    I64,
     F32,
+    F64,
     Bool,
 }
 
+impl DataType {
+    /// Number of 16-bit registers needed to hold a value of this type
+    pub fn register_count(&self) -> u16 {
+        match self {
+            DataType::U64 | DataType::I64 | DataType::F64 => 4,
+            DataType::U32 | DataType::I32 | DataType::F32 => 2,
+            DataType::U16 | DataType::I16 | DataType::Bool => 1,
+        }
+    }
+
+    /// Whether values of this type are floating point
+    pub fn is_float(&self) -> bool {
+        matches!(self, DataType::F32 | DataType::F64)
+    }
+}
+
 impl Default for Config {
     fn default() -> Self {
         Self {
@@ -185,35 +1782,813 @@ impl Default for Config {
                 host: "0.0.0.0".to_string(),
                 port: 3000,
                 metrics_enabled: true,
+                error_log_size: ServerConfig::default_error_log_size(),
+                error_log_max_bytes: ServerConfig::default_error_log_max_bytes(),
+                update_channel_capacity: ServerConfig::default_update_channel_capacity(),
+                register_gauges: RegisterGauges::default(),
             },
-            mqtt: MqttConfig {
-                enabled: false,
-                host: "localhost".to_string(),
-                port: 1883,
-                client_id: "rustbridge".to_string(),
-                topic_prefix: "rustbridge".to_string(),
-                qos: 1,
-                retain: false,
-                username: None,
-                password: None,
-            },
+            mqtt: None,
             auth: AuthConfig::default(),
+            logging: LoggingConfig::default(),
+            audit: AuditConfig::default(),
+            systemd: SystemdConfig::default(),
+            daemon: DaemonConfig::default(),
+            snapshot: SnapshotConfig::default(),
+            runtime: RuntimeConfig::default(),
+            writes: WritesConfig::default(),
+            remote_config: None,
+            update: None,
+            profiles: ProfilesConfig::default(),
+            recipes: Vec::new(),
             devices: vec![],
         }
     }
 }
 
-/// Load configuration from file or use defaults
-pub fn load_config() -> Result<Config> {
-    let config_path =
-        std::env::var("RUSTBRIDGE_CONFIG").unwrap_or_else(|_| "config.yaml".to_string());
+impl Config {
+    /// MQTT settings when publishing is configured and enabled
+    pub fn mqtt_enabled(&self) -> Option<&MqttConfig> {
+        self.mqtt.as_ref().filter(|mqtt| mqtt.enabled)
+    }
 
-    if Path::new(&config_path).exists() {
-        let content = std::fs::read_to_string(&config_path)
-            .with_context(|| format!("Failed to read config file: {}", config_path))?;
+    /// Fill in the `count` of registers that leave it out from their data type
+    pub fn derive_counts(&mut self) {
+        let registers = self.devices.iter_mut().flat_map(|d| &mut d.registers);
+        for register in registers.filter(|r| r.count == 0) {
+            register.count = register.data_type.register_count();
+        }
+    }
 
-        let config: Config =
-            serde_yaml::from_str(&content).with_context(|| "Failed to parse config file")?;
+    /// Check the configuration for mistakes serde cannot catch, reporting all of them at once
+    pub fn validate(&self) -> Result<()> {
+        let errors = self.validation_errors();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(RustbridgeError::Config(format!(
+                "Invalid configuration:\n  - {}",
+                errors.join("\n  - ")
+            )))
+        }
+    }
+
+    /// Every mistake [`validate`](Self::validate) reports, one message each
+    pub fn validation_errors(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+
+        if let Some(mqtt) = self.mqtt.as_ref() {
+            if mqtt.qos > 2 {
+                errors.push(format!("mqtt.qos must be 0, 1 or 2 (got {})", mqtt.qos));
+            }
+            if mqtt.batch.window_ms == 0 {
+                errors.push("mqtt.batch.window_ms must be at least 1".to_string());
+            }
+            if mqtt.batch.full_snapshot_every == 0 {
+                errors.push("mqtt.batch.full_snapshot_every must be at least 1".to_string());
+            }
+            if !mqtt.clean_session {
+                if mqtt.client_id.is_empty() {
+                    errors.push("mqtt.clean_session: false needs a client_id".to_string());
+                }
+                if mqtt.qos == 0 {
+                    errors.push(
+                        "mqtt.clean_session: false needs qos 1 or 2, the broker does not keep QoS 0 messages"
+                            .to_string(),
+                    );
+                }
+            }
+            if mqtt.client_cert.is_some() != mqtt.client_key.is_some() {
+                errors.push("mqtt.client_cert and mqtt.client_key go together".to_string());
+            }
+            if !mqtt.use_tls
+                && (mqtt.ca_cert.is_some()
+                    || mqtt.client_cert.is_some()
+                    || mqtt.insecure_skip_verify)
+            {
+                errors.push(
+                    "mqtt.ca_cert, mqtt.client_cert and mqtt.insecure_skip_verify need use_tls: true"
+                        .to_string(),
+                );
+            }
+            if let Some(will) = &mqtt.last_will {
+                if will.topic.is_empty() || will.topic.contains(['+', '#']) {
+                    errors.push(format!(
+                        "mqtt.last_will.topic '{}' must be a topic without wildcards",
+                        will.topic
+                    ));
+                }
+            }
+            if mqtt.use_tls && !cfg!(feature = "tls") {
+                errors.push(
+                    "mqtt.use_tls: TLS support not compiled in (enable the `tls` feature)"
+                        .to_string(),
+                );
+            }
+            if mqtt.inflight == 0 {
+                errors.push("mqtt.inflight must be at least 1".to_string());
+            }
+            if mqtt.max_packet_size == 0 {
+                errors.push("mqtt.max_packet_size must be at least 1".to_string());
+            }
+            let filter = &mqtt.filter;
+            if filter
+                .include
+                .iter()
+                .chain(&filter.exclude)
+                .any(FilterRule::is_empty)
+            {
+                errors.push(
+                    "mqtt.filter rules need at least one of device, register, group or meta"
+                        .to_string(),
+                );
+            }
+            if let Some(dead_man) = &mqtt.dead_man {
+                check_dead_man(dead_man, &self.devices, &mut errors);
+            }
+            if mqtt.discovery.enabled {
+                if mqtt.publish_mode != PublishMode::Register {
+                    errors.push("mqtt.discovery requires publish_mode: register".to_string());
+                }
+                let prefix = &mqtt.discovery.prefix;
+                if prefix.is_empty() || prefix.contains(['+', '#']) {
+                    errors.push(format!(
+                        "mqtt.discovery.prefix '{}' must be a topic without wildcards",
+                        prefix
+                    ));
+                }
+            }
+        }
+        if self.server.update_channel_capacity == 0 {
+            errors.push("server.update_channel_capacity must be > 0".to_string());
+        }
+        crate::metrics::gauges::check(&self.server.register_gauges, &mut errors);
+        let mut keys: HashMap<&str, &str> = HashMap::new();
+        for key in &self.auth.api_keys {
+            keys.insert(key, "all devices");
+        }
+        for (tenant, tenant_keys) in &self.auth.tenant_keys {
+            check_tenant(tenant, "auth.tenant_keys", &mut errors);
+            for key in tenant_keys {
+                if let Some(other) = keys.insert(key, tenant) {
+                    errors.push(format!(
+                        "auth.tenant_keys: a key of tenant '{}' is also a key for {}",
+                        tenant, other
+                    ));
+                }
+            }
+        }
+        if self.runtime.worker_threads == Some(0) {
+            errors.push("runtime.worker_threads must be > 0".to_string());
+        }
+        if self.runtime.max_blocking_threads == Some(0) {
+            errors.push("runtime.max_blocking_threads must be > 0".to_string());
+        }
+        if self.snapshot.persist.is_some() && self.snapshot.persist_interval_secs == 0 {
+            errors.push("snapshot.persist_interval_secs must be > 0".to_string());
+        }
+        for git in &self.profiles.git {
+            errors.extend(git.option_error());
+        }
+        if let Some(remote) = &self.remote_config {
+            match crate::fetch::RemoteUrl::parse(&remote.url) {
+                Err(e) => errors.push(format!("remote_config.url: {}", e)),
+                // A pulled configuration controls every write and the update
+                Ok(url) if !url.tls => {
+                    errors.push("remote_config.url must be an https:// url".to_string());
+                }
+                Ok(_) => {}
+            }
+            if remote.timeout_secs == 0 {
+                errors.push("remote_config.timeout_secs must be > 0".to_string());
+            }
+        }
+        if let Some(update) = &self.update {
+            match crate::fetch::RemoteUrl::parse(&update.manifest_url()) {
+                Err(e) => errors.push(format!("update.url: {}", e)),
+                // The manifest vouches for the binary, so it must not be tampered with
+                Ok(url) if update.auto_install && !url.tls => {
+                    errors.push("update.auto_install needs an https:// url".to_string());
+                }
+                Ok(_) => {}
+            }
+            if update.timeout_secs == 0 {
+                errors.push("update.timeout_secs must be > 0".to_string());
+            }
+            if update.auto_install {
+                if !cfg!(unix) {
+                    errors.push("update.auto_install is only supported on Unix".to_string());
+                }
+                if update.health_check_secs == 0 {
+                    errors.push("update.health_check_secs must be > 0".to_string());
+                }
+            }
+        }
+
+        let mut device_ids = std::collections::HashSet::new();
+        for device in &self.devices {
+            if device.id.is_empty() {
+                errors.push("device with empty id".to_string());
+            } else if !device_ids.insert(device.id.as_str()) {
+                errors.push(format!("duplicate device id '{}'", device.id));
+            }
+            if device.poll_interval_ms == 0 {
+                errors.push(format!(
+                    "device '{}': poll_interval_ms must be > 0",
+                    device.id
+                ));
+            }
+            let breaker = &device.circuit_breaker;
+            if breaker.failures > 0 && breaker.backoff_ms == 0 {
+                errors.push(format!(
+                    "device '{}': circuit_breaker.backoff_ms must be > 0",
+                    device.id
+                ));
+            }
+            if breaker.max_backoff_ms < breaker.backoff_ms {
+                errors.push(format!(
+                    "device '{}': circuit_breaker.max_backoff_ms must be at least backoff_ms",
+                    device.id
+                ));
+            }
+            if device.reconnect.max_backoff_ms < device.reconnect.backoff_ms {
+                errors.push(format!(
+                    "device '{}': reconnect.max_backoff_ms must be at least backoff_ms",
+                    device.id
+                ));
+            }
+            if let Some(blocks) = &device.read_blocks {
+                if !(1..=125).contains(&blocks.max_registers) {
+                    errors.push(format!(
+                        "device '{}': read_blocks.max_registers must be between 1 and 125",
+                        device.id
+                    ));
+                }
+            }
+            if device.cycle_budget_ms == Some(0) {
+                errors.push(format!(
+                    "device '{}': cycle_budget_ms must be greater than 0",
+                    device.id
+                ));
+            }
+            check_meta(
+                &device.meta,
+                &format!("device '{}'", device.id),
+                &mut errors,
+            );
+            if let Some(tenant) = &device.tenant {
+                check_tenant(tenant, &format!("device '{}'", device.id), &mut errors);
+            }
+            if let ConnectionConfig::Rtu(rtu) = &device.connection {
+                check_serial_port(rtu, &format!("device '{}'", device.id), &mut errors);
+                if rtu.listen_only {
+                    if device.time_sync.is_some() {
+                        errors.push(format!(
+                            "device '{}': a listen_only device cannot use time_sync",
+                            device.id
+                        ));
+                    }
+                    // The bus master owns the line; polling it as well would collide
+                    let polled = self.devices.iter().find(|other| {
+                        matches!(&other.connection, ConnectionConfig::Rtu(other_rtu)
+                            if !other_rtu.listen_only && !rtu.port.is_empty() && other_rtu.port == rtu.port)
+                    });
+                    if let Some(other) = polled {
+                        errors.push(format!(
+                            "device '{}': port {} is listen_only but device '{}' polls it",
+                            device.id, rtu.port, other.id
+                        ));
+                    }
+                } else {
+                    // Polled devices on a port share one connection and its line settings
+                    let line = |rtu: &RtuConnection| {
+                        (
+                            rtu.baud_rate,
+                            rtu.data_bits,
+                            rtu.stop_bits,
+                            rtu.parity.to_lowercase(),
+                        )
+                    };
+                    let first = self
+                        .devices
+                        .iter()
+                        .find_map(|other| match &other.connection {
+                            ConnectionConfig::Rtu(other_rtu)
+                                if !other_rtu.listen_only
+                                    && !rtu.port.is_empty()
+                                    && other_rtu.port == rtu.port =>
+                            {
+                                Some((other, other_rtu))
+                            }
+                            _ => None,
+                        });
+                    if let Some((other, _)) =
+                        first.filter(|(_, other_rtu)| line(other_rtu) != line(rtu))
+                    {
+                        errors.push(format!(
+                            "device '{}': baud rate, data bits, stop bits or parity on port {} differ from device '{}'",
+                            device.id, rtu.port, other.id
+                        ));
+                    }
+                }
+            }
+            if let Some(time_sync) = &device.time_sync {
+                check_time_sync(time_sync, &format!("device '{}'", device.id), &mut errors);
+            }
+            if device.is_broadcast() {
+                for register in &device.registers {
+                    if !matches!(
+                        register.register_type,
+                        RegisterType::Holding | RegisterType::Coil
+                    ) {
+                        errors.push(format!(
+                            "device '{}' register '{}': unit 0 is the broadcast address, only holding registers and coils can be written",
+                            device.id, register.name
+                        ));
+                    }
+                    if register.read_when.is_some() {
+                        errors.push(format!(
+                            "device '{}' register '{}': read_when has no effect on a broadcast device",
+                            device.id, register.name
+                        ));
+                    }
+                }
+            }
+
+            let mut register_names = std::collections::HashSet::new();
+            for register in &device.registers {
+                let context = format!("device '{}' register '{}'", device.id, register.name);
+                if !register_names.insert(register.name.as_str()) {
+                    errors.push(format!(
+                        "device '{}': duplicate register name '{}'",
+                        device.id, register.name
+                    ));
+                }
+                if register.count == 0 {
+                    errors.push(format!("{}: count must be > 0", context));
+                } else if register.address as u32 + register.count as u32 > 65536 {
+                    errors.push(format!(
+                        "{}: address {} + count {} exceeds the Modbus address space",
+                        context, register.address, register.count
+                    ));
+                }
+                check_meta(&register.meta, &context, &mut errors);
+                if register.register_type == RegisterType::ExceptionStatus
+                    && (register.address > 7
+                        || register.count != 1
+                        || !matches!(register.data_type, DataType::Bool))
+                {
+                    errors.push(format!(
+                        "{}: exception_status registers name one bit: address 0-7, count 1, data_type bool",
+                        context
+                    ));
+                }
+                if register.register_type == RegisterType::Fifo
+                    && (register.count != 1 || register.data_type.register_count() != 1)
+                {
+                    errors.push(format!(
+                        "{}: fifo entries are single registers: count 1 and a 16-bit data_type",
+                        context
+                    ));
+                }
+                if let Some(condition) = &register.read_when {
+                    if condition.register == register.name {
+                        errors.push(format!("{}: read_when cannot refer to itself", context));
+                    } else if !device
+                        .registers
+                        .iter()
+                        .any(|r| r.name == condition.register)
+                    {
+                        errors.push(format!(
+                            "{}: read_when refers to unknown register '{}'",
+                            context, condition.register
+                        ));
+                    }
+                    if condition.test.bit.is_some_and(|bit| bit > 63) {
+                        errors.push(format!("{}: read_when bit must be 0-63", context));
+                    }
+                }
+                if register
+                    .alarm
+                    .as_ref()
+                    .is_some_and(|alarm| alarm.bit.is_some_and(|bit| bit > 63))
+                {
+                    errors.push(format!("{}: alarm bit must be 0-63", context));
+                }
+                if let Some(limits) = &register.write_limits {
+                    if let (Some(min), Some(max)) = (limits.min, limits.max) {
+                        if min > max {
+                            errors.push(format!(
+                                "{}: write_limits min ({}) is greater than max ({})",
+                                context, min, max
+                            ));
+                        }
+                    }
+                    if limits.min_interval_ms == Some(0) {
+                        errors.push(format!(
+                            "{}: write_limits.min_interval_ms must be > 0",
+                            context
+                        ));
+                    }
+                    check_interlocks(
+                        &limits.require,
+                        Some(&device.id),
+                        &self.devices,
+                        &context,
+                        &mut errors,
+                    );
+                }
+                let linear = register.scale.is_some() || register.offset.is_some();
+                if [
+                    linear,
+                    register.expression.is_some(),
+                    register.lookup.is_some(),
+                ]
+                .iter()
+                .filter(|set| **set)
+                .count()
+                    > 1
+                {
+                    errors.push(format!(
+                        "{}: use only one of scale/offset, expression and lookup",
+                        context
+                    ));
+                }
+                if let Some(after) = &register.read_after {
+                    if *after == register.name {
+                        errors.push(format!("{}: read_after cannot refer to itself", context));
+                    } else if !device.registers.iter().any(|r| r.name == *after) {
+                        errors.push(format!(
+                            "{}: read_after refers to unknown register '{}'",
+                            context, after
+                        ));
+                    } else if read_after_cycle(&device.registers, register) {
+                        errors.push(format!(
+                            "{}: read_after relationships form a cycle",
+                            context
+                        ));
+                    }
+                }
+                if let Some(trigger) = register.trigger {
+                    if !matches!(
+                        register.register_type,
+                        RegisterType::Holding | RegisterType::Coil
+                    ) {
+                        errors.push(format!(
+                            "{}: trigger needs a holding or coil register",
+                            context
+                        ));
+                    } else if let Err(e) = crate::modbus::codec::encode_value(trigger, register) {
+                        errors.push(format!("{}: invalid trigger value: {}", context, e));
+                    }
+                }
+                let writable = matches!(
+                    register.register_type,
+                    RegisterType::Holding | RegisterType::Coil
+                );
+                if register.persistent && (!writable || register.trigger.is_some()) {
+                    errors.push(format!(
+                        "{}: persistent needs a holding or coil register without trigger",
+                        context
+                    ));
+                }
+                if let Some(deadband) = register.deadband {
+                    if !deadband.is_finite() || deadband < 0.0 {
+                        errors.push(format!("{}: deadband must be >= 0", context));
+                    }
+                }
+                if register.heartbeat_interval_s == Some(0) {
+                    errors.push(format!("{}: heartbeat_interval_s must be > 0", context));
+                }
+                if !register.publish_on_change
+                    && (register.deadband.is_some() || register.heartbeat_interval_s.is_some())
+                {
+                    errors.push(format!(
+                        "{}: deadband and heartbeat_interval_s need publish_on_change",
+                        context
+                    ));
+                }
+                if register.min_stable_ms.is_some() && !register.is_bit() {
+                    errors.push(format!(
+                        "{}: min_stable_ms needs a coil, discrete input, exception status or bool register",
+                        context
+                    ));
+                }
+                if !register.points.is_empty() {
+                    if !matches!(
+                        register.register_type,
+                        RegisterType::Coil | RegisterType::Discrete
+                    ) {
+                        errors.push(format!(
+                            "{}: points need a coil or discrete register",
+                            context
+                        ));
+                    }
+                    if register.trigger.is_some() || register.oversample.is_some() {
+                        errors.push(format!(
+                            "{}: points cannot be combined with trigger or oversample",
+                            context
+                        ));
+                    }
+                    let mut seen = std::collections::HashSet::new();
+                    for point in &register.points {
+                        if point.offset >= register.count {
+                            errors.push(format!(
+                                "{}: point '{}' offset {} is outside the {} bits read",
+                                context, point.name, point.offset, register.count
+                            ));
+                        }
+                        if !seen.insert(point.name.as_str()) {
+                            errors.push(format!("{}: duplicate point '{}'", context, point.name));
+                        } else if device.registers.iter().any(|r| {
+                            r.name == point.name
+                                || (!std::ptr::eq(r, register)
+                                    && r.points.iter().any(|p| p.name == point.name))
+                        }) {
+                            errors.push(format!(
+                                "{}: point '{}' has the name of another register or point",
+                                context, point.name
+                            ));
+                        }
+                    }
+                }
+                if let Some(oversample) = &register.oversample {
+                    if !matches!(
+                        register.register_type,
+                        RegisterType::Holding | RegisterType::Input
+                    ) {
+                        errors.push(format!(
+                            "{}: oversample needs a holding or input register",
+                            context
+                        ));
+                    }
+                    if !(1..=MAX_OVERSAMPLES).contains(&oversample.samples) {
+                        errors.push(format!(
+                            "{}: oversample.samples must be between 1 and {}",
+                            context, MAX_OVERSAMPLES
+                        ));
+                    }
+                }
+                let needed = register.data_type.register_count();
+                let words = matches!(
+                    register.register_type,
+                    RegisterType::Holding | RegisterType::Input
+                );
+                if register.count != 0
+                    && (register.count < needed || (words && register.count != needed))
+                {
+                    errors.push(format!(
+                        "{}: data type {:?} takes {} registers but count is {}",
+                        context, register.data_type, needed, register.count
+                    ));
+                }
+            }
+        }
+
+        let mut recipe_names = std::collections::HashSet::new();
+        for recipe in &self.recipes {
+            if recipe.name.is_empty() || recipe.name.contains(['/', '+', '#']) {
+                errors.push(format!(
+                    "recipe '{}': name must be non-empty without '/', '+' or '#'",
+                    recipe.name
+                ));
+            } else if !recipe_names.insert(recipe.name.as_str()) {
+                errors.push(format!("duplicate recipe name '{}'", recipe.name));
+            }
+            if recipe.steps.is_empty() {
+                errors.push(format!("recipe '{}': needs at least one step", recipe.name));
+            }
+            check_interlocks(
+                &recipe.require,
+                None,
+                &self.devices,
+                &format!("recipe '{}'", recipe.name),
+                &mut errors,
+            );
+            for (index, step) in recipe.steps.iter().enumerate() {
+                let context = format!("recipe '{}' step {}", recipe.name, index + 1);
+                let Some(device) = self.devices.iter().find(|d| d.id == step.device_id) else {
+                    errors.push(format!(
+                        "{}: device '{}' not found",
+                        context, step.device_id
+                    ));
+                    continue;
+                };
+                match device.registers.iter().find(|r| r.name == step.register) {
+                    None => errors.push(format!(
+                        "{}: register '{}' not found on device '{}'",
+                        context, step.register, step.device_id
+                    )),
+                    Some(register)
+                        if !matches!(
+                            register.register_type,
+                            RegisterType::Holding | RegisterType::Coil
+                        ) =>
+                    {
+                        errors.push(format!(
+                            "{}: register '{}' is not writable",
+                            context, step.register
+                        ))
+                    }
+                    Some(_) => {}
+                }
+                if !step.value.is_finite() || step.tolerance.is_some_and(|t| t.is_nan() || t < 0.0)
+                {
+                    errors.push(format!(
+                        "{}: value must be a number and tolerance at least 0",
+                        context
+                    ));
+                }
+            }
+        }
+
+        errors
+    }
+}
+
+/// Whether following `read_after` from `register` leads back to it
+fn read_after_cycle(registers: &[RegisterConfig], register: &RegisterConfig) -> bool {
+    let mut current = register;
+    // A chain longer than the register list has to repeat itself
+    for _ in 0..registers.len() {
+        let Some(after) = &current.read_after else {
+            return false;
+        };
+        let Some(next) = registers.iter().find(|r| r.name == *after) else {
+            return false;
+        };
+        if next.name == register.name {
+            return true;
+        }
+        current = next;
+    }
+    true
+}
+
+/// Require a port path or a usable USB match
+fn check_serial_port(rtu: &RtuConnection, context: &str, errors: &mut Vec<String>) {
+    let Some(usb) = &rtu.usb else {
+        if rtu.port.is_empty() {
+            errors.push(format!(
+                "{}: serial connection needs a port or a usb match",
+                context
+            ));
+        }
+        return;
+    };
+
+    if *usb == UsbMatch::default() {
+        errors.push(format!(
+            "{}: usb match needs vid, pid or serial_number",
+            context
+        ));
+    }
+    for (name, id) in [("vid", &usb.vid), ("pid", &usb.pid)] {
+        if let Some(id) = id.as_deref().filter(|id| parse_usb_id(id).is_none()) {
+            errors.push(format!(
+                "{}: usb {} '{}' is not a hexadecimal ID",
+                context, name, id
+            ));
+        }
+    }
+}
+
+/// Require a writable clock layout
+fn check_time_sync(time_sync: &TimeSyncConfig, context: &str, errors: &mut Vec<String>) {
+    let count = time_sync.register_count();
+    if count == 0 {
+        errors.push(format!("{}: time_sync layout is empty", context));
+    } else if count > 123 {
+        errors.push(format!(
+            "{}: time_sync layout needs {} registers, more than one write allows (123)",
+            context, count
+        ));
+    } else if time_sync.address as usize + count > 65536 {
+        errors.push(format!(
+            "{}: time_sync address {} + {} registers exceeds the Modbus address space",
+            context, time_sync.address, count
+        ));
+    }
+    if time_sync.interval_secs == 0 {
+        errors.push(format!("{}: time_sync interval_secs must be > 0", context));
+    }
+    if time_sync.bcd && time_sync.layout.contains(&TimeField::Unix) {
+        errors.push(format!(
+            "{}: time_sync unix field cannot be BCD encoded",
+            context
+        ));
+    }
+}
+
+/// Interlocks must name known registers; `device` is the default device, if any
+fn check_interlocks(
+    interlocks: &[Interlock],
+    device: Option<&str>,
+    devices: &[DeviceConfig],
+    context: &str,
+    errors: &mut Vec<String>,
+) {
+    for interlock in interlocks {
+        let Some(device_id) = interlock.device_id.as_deref().or(device) else {
+            errors.push(format!(
+                "{}: interlock on '{}' needs a device_id",
+                context, interlock.register
+            ));
+            continue;
+        };
+        let known = devices
+            .iter()
+            .find(|d| d.id == device_id)
+            .is_some_and(|d| d.registers.iter().any(|r| r.name == interlock.register));
+        if !known {
+            errors.push(format!(
+                "{}: interlock refers to unknown register '{}' of device '{}'",
+                context, interlock.register, device_id
+            ));
+        }
+        if interlock.test.bit.is_some_and(|bit| bit > 63) {
+            errors.push(format!("{}: interlock bit must be 0-63", context));
+        }
+    }
+}
+
+/// The dead-man switch needs a timeout and writable registers to put in a safe state
+fn check_dead_man(dead_man: &DeadManConfig, devices: &[DeviceConfig], errors: &mut Vec<String>) {
+    if dead_man.timeout_secs == 0 {
+        errors.push("mqtt.dead_man.timeout_secs must be > 0".to_string());
+    }
+    if let Some(topic) = &dead_man.heartbeat_topic {
+        if topic.is_empty() || topic.contains(['+', '#']) {
+            errors.push(format!(
+                "mqtt.dead_man.heartbeat_topic '{}' must be a topic without wildcards",
+                topic
+            ));
+        }
+    }
+    if dead_man.safe_state.is_empty() {
+        errors.push("mqtt.dead_man.safe_state needs at least one register".to_string());
+    }
+    for output in &dead_man.safe_state {
+        let context = format!(
+            "mqtt.dead_man.safe_state '{}:{}'",
+            output.device_id, output.register
+        );
+        let register = devices
+            .iter()
+            .find(|d| d.id == output.device_id)
+            .and_then(|d| d.registers.iter().find(|r| r.name == output.register));
+        match register {
+            None => errors.push(format!("{}: register not found", context)),
+            Some(register)
+                if !matches!(
+                    register.register_type,
+                    RegisterType::Holding | RegisterType::Coil
+                ) =>
+            {
+                errors.push(format!("{}: register is not writable", context))
+            }
+            Some(register) => {
+                if let Err(e) = crate::modbus::codec::encode_value(output.value, register) {
+                    errors.push(format!("{}: invalid value: {}", context, e));
+                }
+            }
+        }
+    }
+}
+
+/// Tenants become an MQTT topic level, so they cannot hold separators or wildcards
+fn check_tenant(tenant: &str, context: &str, errors: &mut Vec<String>) {
+    if tenant.is_empty() || tenant.contains(['/', '+', '#']) {
+        errors.push(format!(
+            "{}: tenant '{}' must be non-empty without '/', '+' or '#'",
+            context, tenant
+        ));
+    }
+}
+
+/// Reject meta keys that would overwrite a payload field
+fn check_meta(meta: &Meta, context: &str, errors: &mut Vec<String>) {
+    for key in meta.keys() {
+        if RESERVED_META_KEYS.contains(&key.as_str()) {
+            errors.push(format!("{}: meta key '{}' is reserved", context, key));
+        }
+    }
+}
+
+/// Load configuration from file or use defaults
+pub fn load_config(config_path: &str) -> Result<Config> {
+    if Path::new(config_path).exists() {
+        let content = std::fs::read_to_string(config_path).map_err(|e| {
+            RustbridgeError::Config(format!("Failed to read config file {}: {}", config_path, e))
+        })?;
+
+        let mut config: Config = serde_yaml::from_str(&content).map_err(|e| {
+            RustbridgeError::Config(format!(
+                "Failed to parse config file {}: {}",
+                config_path, e
+            ))
+        })?;
+        crate::profiles::resolve(&mut config)?;
+        config.derive_counts();
 
         Ok(config)
     } else {
@@ -222,320 +2597,1322 @@ pub fn load_config() -> Result<Config> {
     }
 }
 
-/// Load configuration from a YAML string (used in tests)
-#[cfg(test)]
+/// Load configuration from a YAML string
 pub fn load_config_from_str(yaml: &str) -> Result<Config> {
-    serde_yaml::from_str(yaml).with_context(|| "Failed to parse config")
+    let mut config: Config = serde_yaml::from_str(yaml)
+        .map_err(|e| RustbridgeError::Config(format!("Failed to parse config: {}", e)))?;
+    crate::profiles::resolve(&mut config)?;
+    config.derive_counts();
+    Ok(config)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    #[test]
-    fn test_default_config() {
-        let config = Config::default();
+    #[test]
+    fn test_default_config() {
+        let config = Config::default();
+
+        assert_eq!(config.server.host, "0.0.0.0");
+        assert_eq!(config.server.port, 3000);
+        assert!(config.server.metrics_enabled);
+        assert!(config.mqtt.is_none()); // MQTT disabled by default
+        assert!(config.mqtt_enabled().is_none());
+
+        let mqtt = MqttConfig::default();
+        assert!(!mqtt.enabled);
+        assert_eq!(mqtt.host, "localhost");
+        assert_eq!(mqtt.port, 1883);
+        assert_eq!(mqtt.qos, 1);
+        assert!(!mqtt.retain);
+        assert!(config.devices.is_empty());
+    }
+
+    #[test]
+    fn test_parse_minimal_config() {
+        let yaml = r#"
+server:
+  host: "127.0.0.1"
+  port: 8080
+  metrics_enabled: false
+mqtt:
+  host: "mqtt.example.com"
+  port: 1883
+  client_id: "test-client"
+  topic_prefix: "test"
+  qos: 2
+devices: []
+"#;
+        let config = load_config_from_str(yaml).unwrap();
+
+        assert_eq!(config.server.host, "127.0.0.1");
+        assert_eq!(config.server.port, 8080);
+        assert!(!config.server.metrics_enabled);
+        assert_eq!(config.server.error_log_size, 100);
+        assert_eq!(config.server.error_log_max_bytes, Some(1024 * 1024));
+        assert_eq!(config.server.update_channel_capacity, 1024);
+        let mqtt = config.mqtt.unwrap();
+        assert_eq!(mqtt.host, "mqtt.example.com");
+        assert_eq!(mqtt.qos, 2);
+        assert_eq!(mqtt.heartbeat_interval_secs, 30);
+        assert_eq!(mqtt.backpressure, Backpressure::Drop);
+    }
+
+    #[test]
+    fn test_parse_tcp_device() {
+        let yaml = r#"
+server:
+  host: "0.0.0.0"
+  port: 3000
+  metrics_enabled: true
+mqtt:
+  host: "localhost"
+  port: 1883
+  client_id: "rustbridge"
+  topic_prefix: "rustbridge"
+  qos: 1
+devices:
+  - id: "plc-001"
+    name: "Test PLC"
+    device_type: tcp
+    connection:
+      host: "192.168.1.100"
+      port: 502
+      unit_id: 1
+    poll_interval_ms: 1000
+    registers:
+      - name: "temperature"
+        address: 0
+        register_type: holding
+        count: 1
+        data_type: i16
+        unit: "°C"
+        scale: 0.1
+"#;
+        let config = load_config_from_str(yaml).unwrap();
+
+        assert_eq!(config.devices.len(), 1);
+        let device = &config.devices[0];
+        assert_eq!(device.id, "plc-001");
+        assert_eq!(device.name, "Test PLC");
+        assert_eq!(device.poll_interval_ms, 1000);
+
+        match &device.connection {
+            ConnectionConfig::Tcp(tcp) => {
+                assert_eq!(tcp.host, "192.168.1.100");
+                assert_eq!(tcp.port, 502);
+                assert_eq!(tcp.unit_id, 1);
+            }
+            _ => panic!("Expected TCP connection"),
+        }
+
+        assert_eq!(device.registers.len(), 1);
+        let reg = &device.registers[0];
+        assert_eq!(reg.name, "temperature");
+        assert_eq!(reg.address, 0);
+        assert_eq!(reg.scale, Some(0.1));
+        assert_eq!(reg.unit, Some("°C".to_string()));
+    }
+
+    #[test]
+    fn test_parse_tcp_socket_options() {
+        let yaml = r#"
+server:
+  host: "0.0.0.0"
+  port: 3000
+  metrics_enabled: true
+devices:
+  - id: "plc-001"
+    name: "Test PLC"
+    device_type: tcp
+    connection:
+      host: "192.168.1.100"
+      port: 502
+      unit_id: 1
+      connect_timeout_ms: 2000
+      tcp_keepalive_secs: 15
+      tcp_nodelay: false
+      local_address: "10.10.0.2"
+    poll_interval_ms: 1000
+    registers: []
+"#;
+        let config = load_config_from_str(yaml).unwrap();
+
+        match &config.devices[0].connection {
+            ConnectionConfig::Tcp(tcp) => {
+                assert_eq!(tcp.connect_timeout_ms, Some(2000));
+                assert_eq!(tcp.tcp_keepalive_secs, Some(15));
+                assert!(!tcp.tcp_nodelay);
+                assert_eq!(tcp.local_address, Some("10.10.0.2".parse().unwrap()));
+            }
+            _ => panic!("Expected TCP connection"),
+        }
+    }
+
+    #[test]
+    fn test_parse_rtu_device() {
+        let yaml = r#"
+server:
+  host: "0.0.0.0"
+  port: 3000
+  metrics_enabled: true
+mqtt:
+  host: ""
+  port: 1883
+  client_id: "rustbridge"
+  topic_prefix: "rustbridge"
+  qos: 1
+devices:
+  - id: "sensor-001"
+    name: "RTU Sensor"
+    device_type: rtu
+    connection:
+      port: "/dev/ttyUSB0"
+      baud_rate: 9600
+      data_bits: 8
+      stop_bits: 1
+      parity: "none"
+      unit_id: 1
+    poll_interval_ms: 500
+    registers:
+      - name: "humidity"
+        address: 100
+        register_type: input
+        count: 1
+        data_type: u16
+        unit: "%"
+"#;
+        let config = load_config_from_str(yaml).unwrap();
+
+        assert_eq!(config.devices.len(), 1);
+        let device = &config.devices[0];
 
-        assert_eq!(config.server.host, "0.0.0.0");
-        assert_eq!(config.server.port, 3000);
-        assert!(config.server.metrics_enabled);
-        assert!(!config.mqtt.enabled); // MQTT disabled by default
-        assert_eq!(config.mqtt.host, "localhost");
-        assert_eq!(config.mqtt.port, 1883);
-        assert_eq!(config.mqtt.qos, 1);
-        assert!(!config.mqtt.retain);
-        assert!(config.devices.is_empty());
+        match &device.connection {
+            ConnectionConfig::Rtu(rtu) => {
+                assert_eq!(rtu.port, "/dev/ttyUSB0");
+                assert_eq!(rtu.baud_rate, 9600);
+                assert_eq!(rtu.data_bits, 8);
+                assert_eq!(rtu.parity, "none");
+            }
+            _ => panic!("Expected RTU connection"),
+        }
     }
 
     #[test]
-    fn test_parse_minimal_config() {
+    fn test_all_register_types() {
         let yaml = r#"
 server:
-  host: "127.0.0.1"
-  port: 8080
-  metrics_enabled: false
+  host: "0.0.0.0"
+  port: 3000
+  metrics_enabled: true
 mqtt:
-  host: "mqtt.example.com"
+  host: ""
   port: 1883
-  client_id: "test-client"
-  topic_prefix: "test"
-  qos: 2
-devices: []
+  client_id: "rustbridge"
+  topic_prefix: "rustbridge"
+  qos: 1
+devices:
+  - id: "test"
+    name: "Test"
+    device_type: tcp
+    connection:
+      host: "localhost"
+      port: 502
+      unit_id: 1
+    poll_interval_ms: 1000
+    registers:
+      - name: "holding_reg"
+        address: 0
+        register_type: holding
+        count: 1
+        data_type: u16
+      - name: "input_reg"
+        address: 10
+        register_type: input
+        count: 1
+        data_type: i16
+      - name: "coil_reg"
+        address: 20
+        register_type: coil
+        count: 1
+        data_type: bool
+      - name: "discrete_reg"
+        address: 30
+        register_type: discrete
+        count: 1
+        data_type: bool
 "#;
         let config = load_config_from_str(yaml).unwrap();
 
-        assert_eq!(config.server.host, "127.0.0.1");
-        assert_eq!(config.server.port, 8080);
-        assert!(!config.server.metrics_enabled);
-        assert_eq!(config.mqtt.host, "mqtt.example.com");
-        assert_eq!(config.mqtt.qos, 2);
+        let regs = &config.devices[0].registers;
+        assert_eq!(regs.len(), 4);
+
+        assert!(matches!(regs[0].register_type, RegisterType::Holding));
+        assert!(matches!(regs[1].register_type, RegisterType::Input));
+        assert!(matches!(regs[2].register_type, RegisterType::Coil));
+        assert!(matches!(regs[3].register_type, RegisterType::Discrete));
     }
 
     #[test]
-    fn test_parse_tcp_device() {
+    fn test_all_data_types() {
         let yaml = r#"
 server:
   host: "0.0.0.0"
   port: 3000
   metrics_enabled: true
 mqtt:
-  host: "localhost"
+  host: ""
   port: 1883
   client_id: "rustbridge"
   topic_prefix: "rustbridge"
   qos: 1
 devices:
-  - id: "plc-001"
-    name: "Test PLC"
+  - id: "test"
+    name: "Test"
     device_type: tcp
     connection:
-      host: "192.168.1.100"
+      host: "localhost"
       port: 502
       unit_id: 1
     poll_interval_ms: 1000
     registers:
-      - name: "temperature"
+      - name: "u16_val"
         address: 0
         register_type: holding
         count: 1
+        data_type: u16
+      - name: "i16_val"
+        address: 1
+        register_type: holding
+        count: 1
         data_type: i16
-        unit: "°C"
-        scale: 0.1
+      - name: "u32_val"
+        address: 2
+        register_type: holding
+        count: 2
+        data_type: u32
+      - name: "i32_val"
+        address: 4
+        register_type: holding
+        count: 2
+        data_type: i32
+      - name: "f32_val"
+        address: 6
+        register_type: holding
+        count: 2
+        data_type: f32
+      - name: "bool_val"
+        address: 8
+        register_type: holding
+        count: 1
+        data_type: bool
 "#;
         let config = load_config_from_str(yaml).unwrap();
 
-        assert_eq!(config.devices.len(), 1);
-        let device = &config.devices[0];
-        assert_eq!(device.id, "plc-001");
-        assert_eq!(device.name, "Test PLC");
-        assert_eq!(device.poll_interval_ms, 1000);
+        let regs = &config.devices[0].registers;
+        assert_eq!(regs.len(), 6);
 
-        match &device.connection {
-            ConnectionConfig::Tcp(tcp) => {
-                assert_eq!(tcp.host, "192.168.1.100");
-                assert_eq!(tcp.port, 502);
-                assert_eq!(tcp.unit_id, 1);
-            }
-            _ => panic!("Expected TCP connection"),
-        }
+        assert!(matches!(regs[0].data_type, DataType::U16));
+        assert!(matches!(regs[1].data_type, DataType::I16));
+        assert!(matches!(regs[2].data_type, DataType::U32));
+        assert!(matches!(regs[3].data_type, DataType::I32));
+        assert!(matches!(regs[4].data_type, DataType::F32));
+        assert!(matches!(regs[5].data_type, DataType::Bool));
+    }
 
-        assert_eq!(device.registers.len(), 1);
-        let reg = &device.registers[0];
-        assert_eq!(reg.name, "temperature");
-        assert_eq!(reg.address, 0);
-        assert_eq!(reg.scale, Some(0.1));
-        assert_eq!(reg.unit, Some("°C".to_string()));
+    #[test]
+    fn test_invalid_yaml() {
+        let yaml = "this is not valid yaml: [";
+        let result = load_config_from_str(yaml);
+        assert!(result.is_err());
     }
 
     #[test]
-    fn test_parse_rtu_device() {
+    fn test_mqtt_with_auth() {
         let yaml = r#"
 server:
   host: "0.0.0.0"
   port: 3000
   metrics_enabled: true
 mqtt:
-  host: ""
+  host: "mqtt.secure.com"
+  port: 8883
+  client_id: "secure-client"
+  topic_prefix: "secure"
+  qos: 2
+  username: "admin"
+  password: "secret123"
+  backpressure: block
+  quality_policy: suppress
+  publish_mode: device
+  batch:
+    delta: true
+devices: []
+"#;
+        let config = load_config_from_str(yaml).unwrap();
+
+        let mqtt = config.mqtt.unwrap();
+        assert_eq!(mqtt.username, Some("admin".to_string()));
+        assert_eq!(mqtt.password, Some("secret123".to_string()));
+        assert_eq!(mqtt.backpressure, Backpressure::Block);
+        assert_eq!(mqtt.quality_policy, QualityPolicy::Suppress);
+        assert_eq!(mqtt.publish_mode, PublishMode::Device);
+        assert!(mqtt.batch.delta);
+        assert_eq!(mqtt.batch.window_ms, 100);
+    }
+
+    #[test]
+    fn test_parse_logging_config() {
+        let yaml = r#"
+server:
+  host: "0.0.0.0"
+  port: 3000
+  metrics_enabled: true
+mqtt:
+  host: "localhost"
   port: 1883
   client_id: "rustbridge"
   topic_prefix: "rustbridge"
   qos: 1
-devices:
-  - id: "sensor-001"
-    name: "RTU Sensor"
-    device_type: rtu
-    connection:
-      port: "/dev/ttyUSB0"
-      baud_rate: 9600
-      data_bits: 8
-      stop_bits: 1
-      parity: "none"
-      unit_id: 1
-    poll_interval_ms: 500
-    registers:
-      - name: "humidity"
-        address: 100
-        register_type: input
-        count: 1
-        data_type: u16
-        unit: "%"
+logging:
+  level: warn
+  format: json
+  target: file
+  file: "/var/log/rustbridge.log"
+  modules:
+    rustbridge::modbus: debug
+devices: []
+"#;
+        let config = load_config_from_str(yaml).unwrap();
+
+        assert_eq!(config.logging.level, "warn");
+        assert_eq!(config.logging.format, LogFormat::Json);
+        assert_eq!(config.logging.target, LogTarget::File);
+        assert_eq!(
+            config.logging.file,
+            Some("/var/log/rustbridge.log".to_string())
+        );
+        assert_eq!(
+            config.logging.modules.get("rustbridge::modbus"),
+            Some(&"debug".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_syslog_config() {
+        let yaml = r#"
+server:
+  host: "0.0.0.0"
+  port: 3000
+  metrics_enabled: true
+mqtt:
+  host: "localhost"
+  port: 1883
+  client_id: "rustbridge"
+  topic_prefix: "rustbridge"
+  qos: 1
+logging:
+  target: syslog
+  syslog:
+    host: "syslog.plant.local"
+    transport: tls
+    facility: local3
+    ca_cert: "/etc/rustbridge/syslog-ca.pem"
+devices: []
+"#;
+        let config = load_config_from_str(yaml).unwrap();
+
+        assert_eq!(config.logging.target, LogTarget::Syslog);
+        let syslog = config.logging.syslog.unwrap();
+        assert_eq!(syslog.host, "syslog.plant.local");
+        assert_eq!(syslog.transport, SyslogTransport::Tls);
+        assert_eq!(syslog.port, None);
+        assert_eq!(syslog.facility, "local3");
+        assert_eq!(syslog.app_name, "rustbridge");
+    }
+
+    #[test]
+    fn test_logging_defaults() {
+        let config = Config::default();
+
+        assert_eq!(config.audit.file, None);
+        assert_eq!(config.systemd.startup_grace_secs, 30);
+        assert_eq!(config.daemon.pid_file, "/run/rustbridge.pid");
+        assert_eq!(config.daemon.user, None);
+        assert_eq!(config.audit.memory_size, 1000);
+        assert_eq!(config.logging.level, "info");
+        assert_eq!(config.logging.format, LogFormat::Text);
+        assert_eq!(config.logging.target, LogTarget::Stdout);
+        assert!(config.logging.modules.is_empty());
+    }
+
+    fn register(name: &str, address: u16, count: u16, data_type: DataType) -> RegisterConfig {
+        RegisterConfig {
+            name: name.to_string(),
+            address,
+            register_type: RegisterType::Holding,
+            count,
+            data_type,
+            unit: None,
+            scale: None,
+            offset: None,
+            byte_order: Default::default(),
+            non_finite: Default::default(),
+            priority: Default::default(),
+            meta: Default::default(),
+            read_when: None,
+            alarm: None,
+            write_limits: None,
+            oversample: None,
+            read_after: None,
+            trigger: None,
+            min_stable_ms: None,
+            persistent: false,
+            publish_on_change: false,
+            deadband: None,
+            heartbeat_interval_s: None,
+            points: Vec::new(),
+            expression: None,
+            lookup: None,
+        }
+    }
+
+    fn tcp_device(id: &str, registers: Vec<RegisterConfig>) -> DeviceConfig {
+        DeviceConfig {
+            id: id.to_string(),
+            name: id.to_string(),
+            device_type: DeviceType::Tcp,
+            connection: ConnectionConfig::Tcp(TcpConnection {
+                host: "127.0.0.1".to_string(),
+                port: 502,
+                unit_id: 1,
+                ..Default::default()
+            }),
+            poll_interval_ms: 1000,
+            profile: None,
+            registers,
+            meta: Default::default(),
+            group: None,
+            tenant: None,
+            time_sync: None,
+            circuit_breaker: Default::default(),
+            cycle_budget_ms: None,
+            align_to_clock: false,
+            reconnect: Default::default(),
+            read_blocks: None,
+        }
+    }
+
+    #[test]
+    fn test_validate_ok() {
+        let config = Config {
+            devices: vec![tcp_device(
+                "plc-001",
+                vec![
+                    register("temperature", 100, 1, DataType::I16),
+                    register("energy", 200, 2, DataType::F32),
+                ],
+            )],
+            ..Default::default()
+        };
+
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_validate_reports_all_errors() {
+        let mut config = Config {
+            mqtt: Some(MqttConfig {
+                qos: 3,
+                ..Default::default()
+            }),
+            devices: vec![
+                tcp_device(
+                    "plc-001",
+                    vec![
+                        register("temperature", 100, 1, DataType::I16),
+                        register("temperature", 101, 1, DataType::I16),
+                        register("energy", 200, 1, DataType::F32),
+                        register("tail", 65535, 2, DataType::U32),
+                    ],
+                ),
+                tcp_device("plc-001", vec![]),
+            ],
+            ..Default::default()
+        };
+        config.server.update_channel_capacity = 0;
+        config.runtime.worker_threads = Some(0);
+        config.devices[1].tenant = Some("acme/north".to_string());
+        config.auth.api_keys = vec!["shared".to_string()];
+        config
+            .auth
+            .tenant_keys
+            .insert("acme".to_string(), vec!["shared".to_string()]);
+
+        let message = config.validate().unwrap_err().to_string();
+        assert!(message.contains("mqtt.qos"));
+        assert!(message.contains("server.update_channel_capacity"));
+        assert!(message.contains("runtime.worker_threads"));
+        assert!(message.contains("tenant 'acme/north' must be non-empty"));
+        assert!(message.contains("a key of tenant 'acme' is also a key for all devices"));
+        assert!(message.contains("duplicate register name 'temperature'"));
+        assert!(message.contains("F32 takes 2 registers but count is 1"));
+        assert!(message.contains("exceeds the Modbus address space"));
+        assert!(message.contains("duplicate device id 'plc-001'"));
+    }
+
+    #[test]
+    fn test_config_serialization() {
+        let config = Config::default();
+        let yaml = serde_yaml::to_string(&config).unwrap();
+
+        // Should be able to deserialize back
+        let parsed: Config = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(parsed.server.port, config.server.port);
+        assert!(parsed.mqtt.is_none());
+    }
+
+    #[test]
+    fn test_parse_config_without_mqtt() {
+        let yaml = r#"
+server:
+  host: "0.0.0.0"
+  port: 3000
+  metrics_enabled: true
+devices: []
 "#;
         let config = load_config_from_str(yaml).unwrap();
 
-        assert_eq!(config.devices.len(), 1);
-        let device = &config.devices[0];
-
-        match &device.connection {
-            ConnectionConfig::Rtu(rtu) => {
-                assert_eq!(rtu.port, "/dev/ttyUSB0");
-                assert_eq!(rtu.baud_rate, 9600);
-                assert_eq!(rtu.data_bits, 8);
-                assert_eq!(rtu.parity, "none");
-            }
-            _ => panic!("Expected RTU connection"),
-        }
+        assert!(config.mqtt.is_none());
+        assert!(config.mqtt_enabled().is_none());
+        assert!(config.validate().is_ok());
     }
 
     #[test]
-    fn test_all_register_types() {
+    fn test_parse_device_and_register_meta() {
         let yaml = r#"
 server:
   host: "0.0.0.0"
   port: 3000
   metrics_enabled: true
-mqtt:
-  host: ""
-  port: 1883
-  client_id: "rustbridge"
-  topic_prefix: "rustbridge"
-  qos: 1
 devices:
-  - id: "test"
-    name: "Test"
+  - id: "plc-001"
+    name: "Test PLC"
     device_type: tcp
     connection:
-      host: "localhost"
+      host: "192.168.1.100"
       port: 502
       unit_id: 1
     poll_interval_ms: 1000
+    meta:
+      site: "plant-2"
+      line: "A"
     registers:
-      - name: "holding_reg"
+      - name: "temperature"
         address: 0
         register_type: holding
         count: 1
-        data_type: u16
-      - name: "input_reg"
-        address: 10
-        register_type: input
-        count: 1
         data_type: i16
-      - name: "coil_reg"
-        address: 20
-        register_type: coil
-        count: 1
-        data_type: bool
-      - name: "discrete_reg"
-        address: 30
-        register_type: discrete
-        count: 1
-        data_type: bool
+        meta:
+          line: "B"
+          sensor: 7
 "#;
         let config = load_config_from_str(yaml).unwrap();
+        let device = &config.devices[0];
+        let meta = device.register_meta(&device.registers[0]);
 
-        let regs = &config.devices[0].registers;
-        assert_eq!(regs.len(), 4);
+        assert_eq!(meta["site"], "plant-2");
+        assert_eq!(meta["line"], "B");
+        assert_eq!(meta["sensor"], 7);
+        assert!(config.validate().is_ok());
+    }
 
-        assert!(matches!(regs[0].register_type, RegisterType::Holding));
-        assert!(matches!(regs[1].register_type, RegisterType::Input));
-        assert!(matches!(regs[2].register_type, RegisterType::Coil));
-        assert!(matches!(regs[3].register_type, RegisterType::Discrete));
+    #[test]
+    fn test_validate_rejects_reserved_meta_key() {
+        let mut device = tcp_device("plc-001", vec![]);
+        device
+            .meta
+            .insert("value".to_string(), serde_json::json!("x"));
+        let config = Config {
+            devices: vec![device],
+            ..Default::default()
+        };
+
+        let message = config.validate().unwrap_err().to_string();
+        assert!(message.contains("meta key 'value' is reserved"));
     }
 
     #[test]
-    fn test_all_data_types() {
+    fn test_parse_rtu_usb_match() {
         let yaml = r#"
 server:
   host: "0.0.0.0"
   port: 3000
   metrics_enabled: true
-mqtt:
-  host: ""
-  port: 1883
-  client_id: "rustbridge"
-  topic_prefix: "rustbridge"
-  qos: 1
 devices:
-  - id: "test"
-    name: "Test"
-    device_type: tcp
+  - id: "rtu-001"
+    name: "Meter"
+    device_type: rtu
     connection:
-      host: "localhost"
-      port: 502
+      usb:
+        vid: "0403"
+        serial_number: "A10K5XYZ"
+      baud_rate: 9600
+      data_bits: 8
+      stop_bits: 1
+      parity: "none"
       unit_id: 1
     poll_interval_ms: 1000
+    registers: []
+"#;
+        let config = load_config_from_str(yaml).unwrap();
+        assert!(config.validate().is_ok());
+
+        let ConnectionConfig::Rtu(rtu) = &config.devices[0].connection else {
+            panic!("Expected RTU connection");
+        };
+        let usb = rtu.usb.as_ref().unwrap();
+        assert!(rtu.port.is_empty());
+        assert!(usb.matches(0x0403, 0x6001, Some("A10K5XYZ")));
+        assert!(!usb.matches(0x0403, 0x6001, Some("OTHER")));
+        assert!(!usb.matches(0x1a86, 0x7523, Some("A10K5XYZ")));
+        assert_eq!(usb.to_string(), "vid=0403 serial_number=A10K5XYZ");
+    }
+
+    #[test]
+    fn test_validate_serial_port_source() {
+        let mut device = tcp_device("rtu-001", vec![]);
+        device.connection = ConnectionConfig::Rtu(RtuConnection {
+            port: String::new(),
+            usb: Some(UsbMatch {
+                pid: Some("xyz".to_string()),
+                ..Default::default()
+            }),
+            baud_rate: 9600,
+            data_bits: 8,
+            stop_bits: 1,
+            parity: "none".to_string(),
+            unit_id: 1,
+            response_timeout_ms: None,
+            retries: 0,
+            listen_only: false,
+        });
+        let config = Config {
+            devices: vec![device],
+            ..Default::default()
+        };
+
+        let message = config.validate().unwrap_err().to_string();
+        assert!(message.contains("usb pid 'xyz' is not a hexadecimal ID"));
+    }
+
+    #[test]
+    fn test_validate_read_when() {
+        let mut totalizer = register("totalizer", 10, 2, DataType::U32);
+        totalizer.read_when = Some(ReadCondition {
+            register: "pump".to_string(),
+            ..Default::default()
+        });
+        let mut looped = register("flow", 20, 1, DataType::U16);
+        looped.read_when = Some(ReadCondition {
+            register: "flow".to_string(),
+            test: ValueCondition {
+                bit: Some(64),
+                ..Default::default()
+            },
+        });
+        let config = Config {
+            devices: vec![tcp_device("plc-001", vec![totalizer, looped])],
+            ..Default::default()
+        };
+
+        let message = config.validate().unwrap_err().to_string();
+        assert!(message.contains("read_when refers to unknown register 'pump'"));
+        assert!(message.contains("read_when cannot refer to itself"));
+        assert!(message.contains("read_when bit must be 0-63"));
+    }
+
+    #[test]
+    fn test_derive_count() {
+        let yaml = r#"
+server: { host: "127.0.0.1", port: 3000, metrics_enabled: false }
+devices:
+  - id: meter-1
+    name: Meter
+    device_type: tcp
+    poll_interval_ms: 1000
+    connection: { host: 10.0.0.1, port: 502, unit_id: 1 }
     registers:
-      - name: "u16_val"
-        address: 0
-        register_type: holding
-        count: 1
-        data_type: u16
-      - name: "i16_val"
-        address: 1
-        register_type: holding
-        count: 1
-        data_type: i16
-      - name: "u32_val"
-        address: 2
-        register_type: holding
-        count: 2
-        data_type: u32
-      - name: "i32_val"
-        address: 4
-        register_type: holding
-        count: 2
-        data_type: i32
-      - name: "f32_val"
-        address: 6
-        register_type: holding
-        count: 2
-        data_type: f32
-      - name: "bool_val"
-        address: 8
-        register_type: holding
-        count: 1
-        data_type: bool
+      - { name: power, address: 0, register_type: input, data_type: f32 }
+      - { name: energy, address: 2, register_type: input, data_type: i64 }
+      - { name: relay, address: 0, register_type: coil, data_type: bool }
+      - { name: inputs, address: 0, register_type: discrete, count: 16, data_type: bool }
 "#;
         let config = load_config_from_str(yaml).unwrap();
+        let counts: Vec<u16> = config.devices[0]
+            .registers
+            .iter()
+            .map(|r| r.count)
+            .collect();
+        assert_eq!(counts, [2, 4, 1, 16]);
+        assert!(config.validate().is_ok());
 
-        let regs = &config.devices[0].registers;
-        assert_eq!(regs.len(), 6);
+        // A count that does not fit the data type is an error, not garbage
+        let wrong = yaml.replace("data_type: f32", "count: 4, data_type: f32");
+        let message = load_config_from_str(&wrong)
+            .unwrap()
+            .validate()
+            .unwrap_err()
+            .to_string();
+        assert!(message.contains("data type F32 takes 2 registers but count is 4"));
+    }
 
-        assert!(matches!(regs[0].data_type, DataType::U16));
-        assert!(matches!(regs[1].data_type, DataType::I16));
-        assert!(matches!(regs[2].data_type, DataType::U32));
-        assert!(matches!(regs[3].data_type, DataType::I32));
-        assert!(matches!(regs[4].data_type, DataType::F32));
-        assert!(matches!(regs[5].data_type, DataType::Bool));
+    #[test]
+    fn test_validate_points() {
+        let point = |name: &str, offset| BitPoint {
+            name: name.to_string(),
+            offset,
+        };
+        let mut outputs = register("outputs", 0, 64, DataType::Bool);
+        outputs.register_type = RegisterType::Coil;
+        outputs.points = vec![point("pump", 0), point("fan", 64), point("pump", 1)];
+        let mut level = register("level", 100, 1, DataType::U16);
+        level.points = vec![point("outputs", 0)];
+        let config = Config {
+            devices: vec![tcp_device("plc-001", vec![outputs, level])],
+            ..Default::default()
+        };
+
+        let message = config.validate().unwrap_err().to_string();
+        assert!(message.contains("point 'fan' offset 64 is outside the 64 bits read"));
+        assert!(message.contains("duplicate point 'pump'"));
+        assert!(message.contains("points need a coil or discrete register"));
+        assert!(message.contains("point 'outputs' has the name of another register or point"));
     }
 
     #[test]
-    fn test_invalid_yaml() {
-        let yaml = "this is not valid yaml: [";
-        let result = load_config_from_str(yaml);
-        assert!(result.is_err());
+    fn test_validate_recipes() {
+        let mut level = register("level", 100, 1, DataType::U16);
+        level.register_type = RegisterType::Input;
+        let setpoint = register("setpoint", 10, 1, DataType::U16);
+        let step = |device_id: &str, register: &str| RecipeStep {
+            device_id: device_id.to_string(),
+            register: register.to_string(),
+            value: 1.0,
+            tolerance: None,
+        };
+        let recipe = |name: &str, steps| RecipeConfig {
+            name: name.to_string(),
+            description: None,
+            require: vec![],
+            steps,
+        };
+        let mut config = Config {
+            devices: vec![tcp_device("plc-001", vec![level, setpoint])],
+            recipes: vec![recipe("product-a", vec![step("plc-001", "setpoint")])],
+            ..Default::default()
+        };
+        assert!(config.validate().is_ok());
+
+        config.recipes.extend([
+            recipe(
+                "product-b",
+                vec![
+                    step("plc-001", "level"),
+                    step("plc-002", "setpoint"),
+                    step("plc-001", "mode"),
+                ],
+            ),
+            recipe("product-a", vec![]),
+        ]);
+        config.recipes[1].require = vec![Interlock {
+            register: "level".to_string(),
+            ..Default::default()
+        }];
+        let message = config.validate().unwrap_err().to_string();
+        assert!(message.contains("recipe 'product-b' step 1: register 'level' is not writable"));
+        assert!(message.contains("recipe 'product-b' step 2: device 'plc-002' not found"));
+        assert!(message.contains("step 3: register 'mode' not found on device 'plc-001'"));
+        assert!(message.contains("duplicate recipe name 'product-a'"));
+        assert!(message.contains("recipe 'product-a': needs at least one step"));
+        assert!(message.contains("recipe 'product-b': interlock on 'level' needs a device_id"));
     }
 
     #[test]
-    fn test_mqtt_with_auth() {
+    fn test_validate_write_limits() {
+        let mut setpoint = register("setpoint", 10, 1, DataType::U16);
+        setpoint.write_limits = Some(WriteLimits {
+            min: Some(50.0),
+            max: Some(10.0),
+            values: vec![],
+            min_interval_ms: Some(0),
+            require: vec![Interlock {
+                register: "running".to_string(),
+                ..Default::default()
+            }],
+        });
+        let config = Config {
+            devices: vec![tcp_device("plc-001", vec![setpoint])],
+            ..Default::default()
+        };
+        assert!(config.writes.enabled);
+
+        let message = config.validate().unwrap_err().to_string();
+        assert!(message.contains("write_limits min (50) is greater than max (10)"));
+        assert!(message.contains("write_limits.min_interval_ms must be > 0"));
+        assert!(
+            message.contains("interlock refers to unknown register 'running' of device 'plc-001'")
+        );
+    }
+
+    #[test]
+    fn test_parse_register_transforms() {
+        let yaml = r#"
+server: { host: "127.0.0.1", port: 3000, metrics_enabled: false }
+devices:
+  - id: plc-001
+    name: PLC
+    device_type: tcp
+    poll_interval_ms: 1000
+    connection: { host: 10.0.0.1, port: 502, unit_id: 1 }
+    registers:
+      - { name: flow, address: 0, register_type: input, count: 1, data_type: u16,
+          expression: "sqrt(raw) * 1.414" }
+      - { name: level, address: 1, register_type: input, count: 1, data_type: u16,
+          lookup: [[0, 0], [4000, 2.5], [20000, 10]] }
+      - { name: pressure, address: 2, register_type: input, count: 1, data_type: u16,
+          scale: 0.1, expression: "raw / 10" }
+"#;
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+        let registers = &config.devices[0].registers;
+        assert_eq!(registers[0].expression.as_ref().unwrap().eval(16.0), 5.656);
+        assert_eq!(registers[1].lookup.as_ref().unwrap().apply(2000.0), 1.25);
+        let message = config.validate().unwrap_err().to_string();
+        assert!(message.contains("use only one of scale/offset, expression and lookup"));
+
+        let error = serde_yaml::from_str::<Config>(&yaml.replace("sqrt(raw)", "sqrt(raw"))
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("expected ')'"), "{}", error);
+    }
+
+    #[test]
+    fn test_parse_oversample() {
+        let yaml = r#"
+server: { host: "127.0.0.1", port: 3000, metrics_enabled: false }
+devices:
+  - id: plc-001
+    name: PLC
+    device_type: tcp
+    poll_interval_ms: 1000
+    connection: { host: 10.0.0.1, port: 502, unit_id: 1 }
+    registers:
+      - { name: level, address: 0, register_type: input, count: 1, data_type: u16,
+          oversample: { samples: 5, method: median } }
+      - { name: pump, address: 0, register_type: coil, count: 1, data_type: bool,
+          oversample: { samples: 0 } }
+"#;
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+        let registers = &config.devices[0].registers;
+        assert_eq!(
+            registers[0].oversample,
+            Some(Oversample {
+                samples: 5,
+                spacing_ms: 10,
+                method: SampleMethod::Median,
+            })
+        );
+        let message = config.validate().unwrap_err().to_string();
+        assert!(message.contains("oversample needs a holding or input register"));
+        assert!(message.contains("oversample.samples must be between 1 and 100"));
+        assert!(!message.contains("'level'"), "{}", message);
+    }
+
+    #[test]
+    fn test_read_after_validation() {
+        let yaml = r#"
+server: { host: "127.0.0.1", port: 3000, metrics_enabled: false }
+devices:
+  - id: plc-001
+    name: PLC
+    device_type: tcp
+    poll_interval_ms: 1000
+    connection: { host: 10.0.0.1, port: 502, unit_id: 1 }
+    registers:
+      - { name: latch, address: 0, register_type: holding, count: 1, data_type: u16, trigger: 1 }
+      - { name: level, address: 1, register_type: input, count: 1, data_type: u16, read_after: latch }
+"#;
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(config.devices[0].registers[0].trigger, Some(1.0));
+        config.validate().unwrap();
+
+        let invalid = yaml
+            .replace("trigger: 1 }", "trigger: -1, read_after: level }")
+            .replace("register_type: holding", "register_type: input");
+        let config: Config = serde_yaml::from_str(&invalid).unwrap();
+        let message = config.validate().unwrap_err().to_string();
+        assert!(message.contains("read_after relationships form a cycle"));
+        assert!(message.contains("trigger needs a holding or coil register"));
+
+        let config: Config =
+            serde_yaml::from_str(&yaml.replace("read_after: latch", "read_after: pump")).unwrap();
+        let message = config.validate().unwrap_err().to_string();
+        assert!(message.contains("read_after refers to unknown register 'pump'"));
+    }
+
+    #[test]
+    fn test_parse_time_sync() {
         let yaml = r#"
 server:
   host: "0.0.0.0"
   port: 3000
   metrics_enabled: true
-mqtt:
-  host: "mqtt.secure.com"
-  port: 8883
-  client_id: "secure-client"
-  topic_prefix: "secure"
-  qos: 2
-  username: "admin"
-  password: "secret123"
-devices: []
+devices:
+  - id: "plc-001"
+    name: "PLC"
+    device_type: tcp
+    connection:
+      host: "127.0.0.1"
+      port: 502
+      unit_id: 1
+    poll_interval_ms: 1000
+    registers: []
+    time_sync:
+      address: 200
+      layout: [year, month, day, hour, minute, second]
+      timezone: local
 "#;
         let config = load_config_from_str(yaml).unwrap();
+        assert!(config.validate().is_ok());
 
-        assert_eq!(config.mqtt.username, Some("admin".to_string()));
-        assert_eq!(config.mqtt.password, Some("secret123".to_string()));
+        let time_sync = config.devices[0].time_sync.as_ref().unwrap();
+        assert_eq!(time_sync.register_count(), 6);
+        assert_eq!(time_sync.interval_secs, 3600);
+        assert_eq!(time_sync.timezone, TimeZoneMode::Local);
+        assert!(!time_sync.bcd);
     }
 
     #[test]
-    fn test_config_serialization() {
-        let config = Config::default();
-        let yaml = serde_yaml::to_string(&config).unwrap();
+    fn test_validate_persistent() {
+        let mut setpoint = register("setpoint", 0, 1, DataType::U16);
+        setpoint.persistent = true;
+        let mut level = register("level", 1, 1, DataType::U16);
+        level.register_type = RegisterType::Input;
+        level.persistent = true;
+        let mut config = Config {
+            devices: vec![tcp_device("plc-001", vec![setpoint])],
+            ..Default::default()
+        };
+        assert!(config.validate().is_ok());
 
-        // Should be able to deserialize back
-        let parsed: Config = serde_yaml::from_str(&yaml).unwrap();
-        assert_eq!(parsed.server.port, config.server.port);
-        assert_eq!(parsed.mqtt.host, config.mqtt.host);
+        config.devices[0].registers.push(level);
+        let message = config.validate().unwrap_err().to_string();
+        assert!(message.contains("'level': persistent needs a holding or coil register"));
+    }
+
+    #[test]
+    fn test_validate_publish_on_change() {
+        let mut level = register("level", 0, 1, DataType::U16);
+        level.publish_on_change = true;
+        level.deadband = Some(0.5);
+        level.heartbeat_interval_s = Some(60);
+        let mut config = Config {
+            devices: vec![tcp_device("plc-001", vec![level])],
+            ..Default::default()
+        };
+        assert!(config.validate().is_ok());
+
+        let mut flow = register("flow", 1, 1, DataType::U16);
+        flow.deadband = Some(-1.0);
+        flow.heartbeat_interval_s = Some(0);
+        config.devices[0].registers.push(flow);
+        let message = config.validate().unwrap_err().to_string();
+        assert!(message.contains("'flow': deadband must be >= 0"));
+        assert!(message.contains("'flow': heartbeat_interval_s must be > 0"));
+        assert!(
+            message.contains("'flow': deadband and heartbeat_interval_s need publish_on_change")
+        );
+    }
+
+    #[test]
+    fn test_validate_mqtt_will_and_tls() {
+        let mut config = Config {
+            mqtt: Some(MqttConfig {
+                use_tls: cfg!(feature = "tls"),
+                insecure_skip_verify: cfg!(feature = "tls"),
+                last_will: Some(LastWillConfig {
+                    topic: "rustbridge/bridge/status".to_string(),
+                    payload: "offline".to_string(),
+                    retain: true,
+                }),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        assert!(config.validate().is_ok());
+
+        let mqtt = config.mqtt.as_mut().unwrap();
+        mqtt.use_tls = false;
+        mqtt.insecure_skip_verify = true;
+        mqtt.last_will.as_mut().unwrap().topic = "rustbridge/#".to_string();
+        let message = config.validate().unwrap_err().to_string();
+        assert!(message.contains("mqtt.insecure_skip_verify need use_tls: true"));
+        assert!(message
+            .contains("mqtt.last_will.topic 'rustbridge/#' must be a topic without wildcards"));
+    }
+
+    #[test]
+    fn test_validate_update() {
+        let update: UpdateConfig =
+            serde_yaml::from_str("{ url: 'http://releases.example.com/{os}-{arch}.json' }")
+                .unwrap();
+        let mut config = Config {
+            update: Some(update),
+            ..Default::default()
+        };
+        assert!(config.validate().is_ok());
+
+        config.update.as_mut().unwrap().auto_install = true;
+        let message = config.validate().unwrap_err().to_string();
+        assert!(message.contains("update.auto_install needs an https:// url"));
+    }
+
+    #[test]
+    fn test_validate_remote_config() {
+        let remote: RemoteConfig =
+            serde_yaml::from_str("{ url: 'http://fleet.example.com/gw.yaml' }").unwrap();
+        let mut config = Config {
+            remote_config: Some(remote),
+            ..Default::default()
+        };
+        let message = config.validate().unwrap_err().to_string();
+        assert!(message.contains("remote_config.url must be an https:// url"));
+
+        if cfg!(feature = "tls") {
+            config.remote_config.as_mut().unwrap().url =
+                "https://fleet.example.com/gw.yaml".to_string();
+            assert!(config.validate().is_ok());
+        }
+    }
+
+    #[test]
+    fn test_validate_profiles_git() {
+        let mut config = Config::default();
+        config.profiles.git = vec![GitProfiles {
+            url: "https://github.com/example/rustbridge-profiles.git".to_string(),
+            rev: Some("main".to_string()),
+        }];
+        assert!(config.validate().is_ok());
+
+        config.profiles.git.push(GitProfiles {
+            url: "--upload-pack=touch /tmp/pwned".to_string(),
+            rev: Some("-b".to_string()),
+        });
+        let message = config.validate().unwrap_err().to_string();
+        assert!(message.contains("profiles.git url '--upload-pack=touch /tmp/pwned' must not"));
+    }
+
+    #[test]
+    fn test_validate_dead_man() {
+        let mut level = register("level", 1, 1, DataType::U16);
+        level.register_type = RegisterType::Input;
+        let mut config = Config {
+            mqtt: Some(MqttConfig {
+                dead_man: Some(DeadManConfig {
+                    timeout_secs: 30,
+                    heartbeat_topic: Some("scada/heartbeat".to_string()),
+                    safe_state: vec![SafeOutput {
+                        device_id: "plc-001".to_string(),
+                        register: "run_enable".to_string(),
+                        value: 0.0,
+                    }],
+                }),
+                ..Default::default()
+            }),
+            devices: vec![tcp_device(
+                "plc-001",
+                vec![register("run_enable", 0, 1, DataType::U16), level],
+            )],
+            ..Default::default()
+        };
+        assert!(config.validate().is_ok());
+
+        let dead_man = config.mqtt.as_mut().unwrap().dead_man.as_mut().unwrap();
+        dead_man.timeout_secs = 0;
+        dead_man.heartbeat_topic = Some("scada/+".to_string());
+        dead_man.safe_state[0].value = -1.0;
+        dead_man.safe_state.push(SafeOutput {
+            device_id: "plc-001".to_string(),
+            register: "level".to_string(),
+            value: 0.0,
+        });
+        dead_man.safe_state.push(SafeOutput {
+            device_id: "plc-002".to_string(),
+            register: "run_enable".to_string(),
+            value: 0.0,
+        });
+        let message = config.validate().unwrap_err().to_string();
+        assert!(message.contains("mqtt.dead_man.timeout_secs must be > 0"));
+        assert!(message.contains("heartbeat_topic 'scada/+' must be a topic without wildcards"));
+        assert!(message.contains("safe_state 'plc-001:run_enable': invalid value"));
+        assert!(message.contains("safe_state 'plc-001:level': register is not writable"));
+        assert!(message.contains("safe_state 'plc-002:run_enable': register not found"));
+    }
+
+    #[test]
+    fn test_validate_time_sync() {
+        let mut device = tcp_device("plc-001", vec![]);
+        device.time_sync = Some(TimeSyncConfig {
+            address: 65534,
+            layout: vec![TimeField::Unix, TimeField::Second],
+            interval_secs: 0,
+            timezone: TimeZoneMode::Utc,
+            bcd: true,
+        });
+        let config = Config {
+            devices: vec![device],
+            ..Default::default()
+        };
+
+        let message = config.validate().unwrap_err().to_string();
+        assert!(message.contains("exceeds the Modbus address space"));
+        assert!(message.contains("interval_secs must be > 0"));
+        assert!(message.contains("unix field cannot be BCD encoded"));
+    }
+
+    #[test]
+    fn test_validate_read_blocks() {
+        let mut device = tcp_device("plc-001", vec![]);
+        device.read_blocks = Some(ReadBlocksConfig {
+            max_gap: 4,
+            max_registers: 200,
+        });
+        let config = Config {
+            devices: vec![device],
+            ..Default::default()
+        };
+
+        let message = config.validate().unwrap_err().to_string();
+        assert!(message.contains("read_blocks.max_registers must be between 1 and 125"));
+    }
+
+    #[test]
+    fn test_validate_listen_only() {
+        let rtu = |id: &str, unit_id: u8, listen_only: bool| {
+            format!(
+                r#"
+  - id: {}
+    name: {}
+    device_type: rtu
+    connection: {{ port: /dev/ttyUSB0, baud_rate: 9600, data_bits: 8, stop_bits: 1, parity: none, unit_id: {}, listen_only: {} }}
+    poll_interval_ms: 1000
+    registers: []"#,
+                id, id, unit_id, listen_only
+            )
+        };
+        let yaml = |devices: &[String]| {
+            format!(
+                "server: {{ host: 0.0.0.0, port: 3000, metrics_enabled: true }}\ndevices:{}",
+                devices.concat()
+            )
+        };
+
+        let config =
+            load_config_from_str(&yaml(&[rtu("meter-1", 1, true), rtu("meter-2", 2, true)]))
+                .unwrap();
+        assert!(config.validate().is_ok());
+
+        let config =
+            load_config_from_str(&yaml(&[rtu("meter-1", 1, true), rtu("plc", 2, false)])).unwrap();
+        let message = config.validate().unwrap_err().to_string();
+        assert!(
+            message.contains("listen_only but device 'plc' polls it"),
+            "{}",
+            message
+        );
+
+        // Polled devices share the port's connection and its line settings
+        let config =
+            load_config_from_str(&yaml(&[rtu("plc-1", 1, false), rtu("plc-2", 2, false)])).unwrap();
+        assert!(config.validate().is_ok());
+        let config = load_config_from_str(&yaml(&[
+            rtu("plc-1", 1, false),
+            rtu("plc-2", 2, false).replace("baud_rate: 9600", "baud_rate: 19200"),
+        ]))
+        .unwrap();
+        let message = config.validate().unwrap_err().to_string();
+        assert!(
+            message.contains("on port /dev/ttyUSB0 differ from device 'plc-1'"),
+            "{}",
+            message
+        );
+    }
+
+    #[test]
+    fn test_validate_exception_status_bits() {
+        let yaml = |register: &str| {
+            format!(
+                r#"
+server: {{ host: 0.0.0.0, port: 3000, metrics_enabled: true }}
+devices:
+  - id: legacy-plc
+    name: Legacy PLC
+    device_type: rtu
+    connection: {{ port: /dev/ttyUSB0, baud_rate: 9600, data_bits: 8, stop_bits: 1, parity: none, unit_id: 3 }}
+    poll_interval_ms: 1000
+    registers:
+      - {}
+"#,
+                register
+            )
+        };
+
+        let config = load_config_from_str(&yaml(
+            "{ name: overtemp, address: 2, register_type: exception_status, count: 1, data_type: bool }",
+        ))
+        .unwrap();
+        assert!(config.devices[0].registers[0].is_boolean());
+        assert!(config.validate().is_ok());
+
+        for register in [
+            "{ name: overtemp, address: 8, register_type: exception_status, count: 1, data_type: bool }",
+            "{ name: overtemp, address: 2, register_type: exception_status, count: 1, data_type: u16 }",
+        ] {
+            let message = load_config_from_str(&yaml(register))
+                .unwrap()
+                .validate()
+                .unwrap_err()
+                .to_string();
+            assert!(message.contains("name one bit"), "{}", message);
+        }
+    }
+
+    #[test]
+    fn test_validate_broadcast_device() {
+        let yaml = |register_type: &str| {
+            format!(
+                r#"
+server: {{ host: 0.0.0.0, port: 3000, metrics_enabled: true }}
+devices:
+  - id: all-meters
+    name: All meters
+    device_type: rtu
+    connection: {{ port: /dev/ttyUSB0, baud_rate: 9600, data_bits: 8, stop_bits: 1, parity: none, unit_id: 0 }}
+    poll_interval_ms: 1000
+    registers:
+      - {{ name: reset, address: 10, register_type: {}, data_type: u16, count: 1 }}
+"#,
+                register_type
+            )
+        };
+
+        let config = load_config_from_str(&yaml("holding")).unwrap();
+        assert!(config.devices[0].is_broadcast());
+        assert!(config.validate().is_ok());
+
+        let config = load_config_from_str(&yaml("input")).unwrap();
+        let message = config.validate().unwrap_err().to_string();
+        assert!(message.contains("broadcast address"), "{}", message);
     }
 }
@@ -0,0 +1,431 @@
+//! Runtime control of device polling
+//!
+//! Each polling task watches a [`DeviceControl`] that can pause and resume it
+//! or trigger an immediate poll. [`DeviceControls`] holds them for all devices
+//! and resolves commands aimed at a single device or at every device of a
+//! `group`, e.g. to pause one production line during maintenance. Commands come
+//! from the HTTP API and the MQTT `{prefix}/groups/{group}/command` topic.
+//!
+//! The controls also know the `tenant` of each device, which scopes API
+//! access and prefixes MQTT topics on gateways shared by several customers.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use tokio::sync::Notify;
+
+use crate::config::DeviceConfig;
+use crate::error::{Result, RustbridgeError};
+
+/// Operation applied to one device or a whole group
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Command {
+    /// Stop polling until resumed (the connection is kept open)
+    Pause,
+    /// Continue polling on the regular interval
+    Resume,
+    /// Poll once right away, without waiting for the next interval
+    PollNow,
+}
+
+impl FromStr for Command {
+    type Err = RustbridgeError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim() {
+            "pause" => Ok(Self::Pause),
+            "resume" => Ok(Self::Resume),
+            "poll_now" | "poll-now" => Ok(Self::PollNow),
+            other => Err(RustbridgeError::Config(format!(
+                "unknown command '{}' (expected pause, resume or poll_now)",
+                other
+            ))),
+        }
+    }
+}
+
+/// What a command is applied to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target<'a> {
+    Device(&'a str),
+    Group(&'a str),
+}
+
+/// Control flags of a single device's polling task
+#[derive(Debug, Default)]
+pub struct DeviceControl {
+    paused: AtomicBool,
+    online: AtomicBool,
+    poll_now: Notify,
+}
+
+impl DeviceControl {
+    /// Whether polling is paused
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    /// Whether the device is currently connected
+    pub fn is_online(&self) -> bool {
+        self.online.load(Ordering::SeqCst)
+    }
+
+    /// Record the connection state reported by the polling task
+    pub fn set_online(&self, online: bool) {
+        self.online.store(online, Ordering::SeqCst);
+    }
+
+    /// Resolve when an immediate poll has been requested
+    ///
+    /// A request made while nobody is waiting is kept until the next call.
+    pub async fn poll_requested(&self) {
+        self.poll_now.notified().await;
+    }
+
+    fn apply(&self, command: Command) {
+        match command {
+            Command::Pause => self.paused.store(true, Ordering::SeqCst),
+            Command::Resume => self.paused.store(false, Ordering::SeqCst),
+            Command::PollNow => self.poll_now.notify_one(),
+        }
+    }
+}
+
+/// Controls of all configured devices, with their group membership
+#[derive(Debug, Clone, Default)]
+pub struct DeviceControls {
+    inner: Arc<RwLock<ControlsInner>>,
+}
+
+#[derive(Debug, Default)]
+struct ControlsInner {
+    devices: HashMap<String, Arc<DeviceControl>>,
+    groups: BTreeMap<String, Vec<String>>,
+    tenants: HashMap<String, Arc<str>>,
+}
+
+impl DeviceControls {
+    /// Create controls for the configured devices
+    pub fn new(devices: &[DeviceConfig]) -> Self {
+        let controls = Self::default();
+        controls.reconfigure(devices);
+        controls
+    }
+
+    /// Replace the device set after a configuration reload
+    ///
+    /// Devices that remain keep their control, so a paused device stays paused.
+    pub fn reconfigure(&self, devices: &[DeviceConfig]) {
+        let mut inner = self.write();
+        let mut groups: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for device in devices {
+            if let Some(group) = &device.group {
+                groups
+                    .entry(group.clone())
+                    .or_default()
+                    .push(device.id.clone());
+            }
+        }
+
+        let previous = std::mem::take(&mut inner.devices);
+        inner.devices = devices
+            .iter()
+            .map(|d| {
+                let control = previous.get(&d.id).cloned().unwrap_or_default();
+                (d.id.clone(), control)
+            })
+            .collect();
+        inner.groups = groups;
+        inner.tenants = devices
+            .iter()
+            .filter_map(|d| Some((d.id.clone(), d.tenant.as_deref()?.into())))
+            .collect();
+    }
+
+    fn read(&self) -> std::sync::RwLockReadGuard<'_, ControlsInner> {
+        self.inner.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn write(&self) -> std::sync::RwLockWriteGuard<'_, ControlsInner> {
+        self.inner.write().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Control of a single device
+    pub fn device(&self, device_id: &str) -> Option<Arc<DeviceControl>> {
+        self.read().devices.get(device_id).cloned()
+    }
+
+    /// Device IDs of a group, in configuration order
+    pub fn group(&self, group: &str) -> Option<Vec<String>> {
+        self.read().groups.get(group).cloned()
+    }
+
+    /// Names of all configured groups
+    pub fn group_names(&self) -> Vec<String> {
+        self.read().groups.keys().cloned().collect()
+    }
+
+    /// Group a device belongs to, if any
+    pub fn group_of(&self, device_id: &str) -> Option<String> {
+        self.read()
+            .groups
+            .iter()
+            .find(|(_, members)| members.iter().any(|id| id == device_id))
+            .map(|(group, _)| group.clone())
+    }
+
+    /// Tenant a device belongs to, if any
+    pub fn tenant_of(&self, device_id: &str) -> Option<Arc<str>> {
+        self.read().tenants.get(device_id).cloned()
+    }
+
+    /// Whether every device of a group belongs to `tenant`
+    pub fn group_in_tenant(&self, group: &str, tenant: &str) -> bool {
+        let inner = self.read();
+        inner.groups.get(group).is_some_and(|members| {
+            members
+                .iter()
+                .all(|id| inner.tenants.get(id).is_some_and(|t| &**t == tenant))
+        })
+    }
+
+    /// Apply a command and return the IDs of the devices it reached
+    pub fn apply(&self, target: Target<'_>, command: Command) -> Result<Vec<String>> {
+        let inner = self.read();
+        let device_ids: Vec<String> = match target {
+            Target::Device(id) if inner.devices.contains_key(id) => vec![id.to_string()],
+            Target::Device(id) => {
+                return Err(RustbridgeError::Config(format!("unknown device '{}'", id)))
+            }
+            Target::Group(group) => inner
+                .groups
+                .get(group)
+                .ok_or_else(|| RustbridgeError::Config(format!("unknown group '{}'", group)))?
+                .clone(),
+        };
+
+        for id in &device_ids {
+            if let Some(control) = inner.devices.get(id) {
+                control.apply(command);
+            }
+        }
+        Ok(device_ids)
+    }
+
+    /// Aggregate connection state of a group
+    pub fn group_state(&self, group: &str) -> Option<GroupState> {
+        let inner = self.read();
+        let members = inner.groups.get(group)?;
+        let devices: Vec<GroupMember> = members
+            .iter()
+            .map(|id| {
+                let control = inner.devices.get(id);
+                GroupMember {
+                    device_id: id.clone(),
+                    online: control.is_some_and(|c| c.is_online()),
+                    paused: control.is_some_and(|c| c.is_paused()),
+                }
+            })
+            .collect();
+        let online = devices.iter().filter(|d| d.online).count();
+
+        Some(GroupState {
+            group: group.to_string(),
+            status: GroupStatus::from_counts(online, devices.len()),
+            online,
+            total: devices.len(),
+            devices,
+        })
+    }
+}
+
+/// Aggregate status of a device group
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum GroupStatus {
+    /// Every device of the group is connected
+    AllOnline,
+    /// Some, but not all, devices are connected
+    Partial,
+    /// No device of the group is connected
+    Offline,
+}
+
+impl GroupStatus {
+    /// Status for `online` connected devices out of `total`
+    pub fn from_counts(online: usize, total: usize) -> Self {
+        if online == 0 {
+            Self::Offline
+        } else if online >= total {
+            Self::AllOnline
+        } else {
+            Self::Partial
+        }
+    }
+}
+
+/// Status of a group and its devices
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GroupState {
+    pub group: String,
+    pub status: GroupStatus,
+    pub online: usize,
+    pub total: usize,
+    pub devices: Vec<GroupMember>,
+}
+
+/// Connection and pause state of a device within a [`GroupState`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GroupMember {
+    pub device_id: String,
+    pub online: bool,
+    pub paused: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{ConnectionConfig, DeviceType, TcpConnection};
+
+    fn device(id: &str, group: Option<&str>) -> DeviceConfig {
+        DeviceConfig {
+            id: id.to_string(),
+            name: id.to_string(),
+            device_type: DeviceType::Tcp,
+            connection: ConnectionConfig::Tcp(TcpConnection {
+                host: "127.0.0.1".to_string(),
+                port: 502,
+                unit_id: 1,
+                ..Default::default()
+            }),
+            poll_interval_ms: 1000,
+            profile: None,
+            registers: vec![],
+            meta: Default::default(),
+            group: group.map(str::to_string),
+            tenant: None,
+            time_sync: None,
+            circuit_breaker: Default::default(),
+            cycle_budget_ms: None,
+            align_to_clock: false,
+            reconnect: Default::default(),
+            read_blocks: None,
+        }
+    }
+
+    fn controls() -> DeviceControls {
+        DeviceControls::new(&[
+            device("press-1", Some("line-a")),
+            device("press-2", Some("line-a")),
+            device("oven-1", Some("line-b")),
+            device("meter", None),
+        ])
+    }
+
+    #[test]
+    fn test_group_commands() {
+        let controls = controls();
+
+        let paused = controls
+            .apply(Target::Group("line-a"), Command::Pause)
+            .unwrap();
+        assert_eq!(paused, vec!["press-1", "press-2"]);
+        assert!(controls.device("press-2").unwrap().is_paused());
+        assert!(!controls.device("oven-1").unwrap().is_paused());
+
+        controls
+            .apply(Target::Device("press-1"), Command::Resume)
+            .unwrap();
+        assert!(!controls.device("press-1").unwrap().is_paused());
+
+        assert!(controls
+            .apply(Target::Group("line-c"), Command::Pause)
+            .is_err());
+        assert!(controls
+            .apply(Target::Device("missing"), Command::Pause)
+            .is_err());
+        assert_eq!(controls.group_of("oven-1").as_deref(), Some("line-b"));
+        assert_eq!(controls.group_of("meter"), None);
+    }
+
+    #[test]
+    fn test_group_status() {
+        let controls = controls();
+        assert_eq!(
+            controls.group_state("line-a").unwrap().status,
+            GroupStatus::Offline
+        );
+
+        controls.device("press-1").unwrap().set_online(true);
+        let state = controls.group_state("line-a").unwrap();
+        assert_eq!(state.status, GroupStatus::Partial);
+        assert_eq!((state.online, state.total), (1, 2));
+
+        controls.device("press-2").unwrap().set_online(true);
+        let json = serde_json::to_value(controls.group_state("line-a").unwrap()).unwrap();
+        assert_eq!(json["status"], "all-online");
+        assert!(controls.group_state("meter").is_none());
+    }
+
+    #[test]
+    fn test_reconfigure_keeps_device_state() {
+        let controls = controls();
+        controls
+            .apply(Target::Device("press-1"), Command::Pause)
+            .unwrap();
+
+        controls.reconfigure(&[
+            device("press-1", Some("line-c")),
+            device("mixer", Some("line-c")),
+        ]);
+        assert!(controls.device("press-1").unwrap().is_paused());
+        assert!(!controls.device("mixer").unwrap().is_paused());
+        assert!(controls.device("oven-1").is_none());
+        assert_eq!(controls.group_names(), vec!["line-c"]);
+        assert_eq!(controls.group("line-c").unwrap(), vec!["press-1", "mixer"]);
+    }
+
+    #[test]
+    fn test_tenants() {
+        let tenant = |id: &str, group: &str, tenant: &str| DeviceConfig {
+            tenant: Some(tenant.to_string()),
+            ..device(id, Some(group))
+        };
+        let controls = DeviceControls::new(&[
+            tenant("press-1", "line-a", "acme"),
+            tenant("press-2", "line-a", "acme"),
+            tenant("oven-1", "line-b", "acme"),
+            tenant("oven-2", "line-b", "globex"),
+            device("meter", None),
+        ]);
+
+        assert_eq!(controls.tenant_of("oven-2").as_deref(), Some("globex"));
+        assert_eq!(controls.tenant_of("meter"), None);
+        assert!(controls.group_in_tenant("line-a", "acme"));
+        assert!(!controls.group_in_tenant("line-b", "acme"));
+        assert!(!controls.group_in_tenant("line-c", "acme"));
+    }
+
+    #[tokio::test]
+    async fn test_poll_now_is_kept_until_awaited() {
+        let controls = controls();
+        controls
+            .apply(Target::Device("meter"), Command::PollNow)
+            .unwrap();
+
+        let control = controls.device("meter").unwrap();
+        tokio::time::timeout(std::time::Duration::from_secs(1), control.poll_requested())
+            .await
+            .unwrap();
+    }
+
+    #[test]
+    fn test_parse_command() {
+        assert_eq!("pause".parse::<Command>().unwrap(), Command::Pause);
+        assert_eq!(" poll-now\n".parse::<Command>().unwrap(), Command::PollNow);
+        assert!("reboot".parse::<Command>().is_err());
+    }
+}
@@ -0,0 +1,210 @@
+//! Daemon mode for hosts without systemd
+//!
+//! `--daemon` forks into the background, detaches from the terminal and
+//! writes a PID file. Independently of daemon mode, the bridge can drop root
+//! privileges to `daemon.user` once serial ports and the API port are open.
+
+use anyhow::{anyhow, bail, Context, Result};
+use std::path::{Path, PathBuf};
+
+/// PID file removed when dropped
+pub struct PidFile {
+    path: PathBuf,
+}
+
+impl PidFile {
+    /// Write the current process ID, refusing if another instance is running
+    pub fn create(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        check_not_running(&path)?;
+        std::fs::write(&path, format!("{}\n", std::process::id()))
+            .with_context(|| format!("Failed to write PID file: {}", path.display()))?;
+        Ok(Self { path })
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        // May fail after dropping privileges; a stale file is detected on the next start
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Fail if the PID file names a live process
+pub fn check_not_running(path: &Path) -> Result<()> {
+    let Ok(contents) = std::fs::read_to_string(path) else {
+        return Ok(());
+    };
+    match contents.trim().parse::<u32>() {
+        Ok(pid) if pid != std::process::id() && process_alive(pid) => bail!(
+            "RustBridge is already running (PID {} in {})",
+            pid,
+            path.display()
+        ),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(unix)]
+fn process_alive(pid: u32) -> bool {
+    use nix::errno::Errno;
+    use nix::sys::signal::kill;
+    use nix::unistd::Pid;
+
+    let Ok(pid) = i32::try_from(pid) else {
+        return false;
+    };
+    // EPERM means the process exists but belongs to another user
+    matches!(kill(Pid::from_raw(pid), None), Ok(()) | Err(Errno::EPERM))
+}
+
+#[cfg(not(unix))]
+fn process_alive(_pid: u32) -> bool {
+    false
+}
+
+/// Fork into the background and detach from the controlling terminal.
+///
+/// Must be called before the async runtime (or any other thread) is started.
+/// The working directory is kept so relative paths in the config still resolve.
+#[cfg(unix)]
+pub fn daemonize() -> Result<()> {
+    use nix::sys::stat::{umask, Mode};
+    use nix::unistd::{fork, setsid, ForkResult};
+
+    // SAFETY: single-threaded at this point; the child only continues normal execution
+    if let ForkResult::Parent { .. } = unsafe { fork() }.context("First fork failed")? {
+        std::process::exit(0);
+    }
+    setsid().context("setsid failed")?;
+    // Second fork so the daemon can never reacquire a controlling terminal
+    // SAFETY: as above
+    if let ForkResult::Parent { .. } = unsafe { fork() }.context("Second fork failed")? {
+        std::process::exit(0);
+    }
+
+    umask(Mode::from_bits_truncate(0o027));
+    redirect_stdio()
+}
+
+#[cfg(not(unix))]
+pub fn daemonize() -> Result<()> {
+    bail!("--daemon is only supported on Unix")
+}
+
+/// Point stdin, stdout and stderr at /dev/null
+#[cfg(unix)]
+fn redirect_stdio() -> Result<()> {
+    use nix::unistd::dup2;
+    use std::os::fd::AsRawFd;
+
+    let null = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/null")
+        .context("Failed to open /dev/null")?;
+    for fd in 0..=2 {
+        dup2(null.as_raw_fd(), fd).context("Failed to redirect standard streams")?;
+    }
+    Ok(())
+}
+
+/// Switch to an unprivileged user (and group, defaulting to the user's primary group)
+#[cfg(unix)]
+pub fn drop_privileges(user: &str, group: Option<&str>) -> Result<()> {
+    use nix::unistd::{setgid, setuid, Group, Uid, User};
+
+    let account = User::from_name(user)
+        .with_context(|| format!("Failed to look up user '{}'", user))?
+        .ok_or_else(|| anyhow!("User '{}' does not exist", user))?;
+    let gid = match group {
+        Some(group) => {
+            Group::from_name(group)
+                .with_context(|| format!("Failed to look up group '{}'", group))?
+                .ok_or_else(|| anyhow!("Group '{}' does not exist", group))?
+                .gid
+        }
+        None => account.gid,
+    };
+
+    if Uid::effective() == account.uid {
+        return Ok(());
+    }
+    if !Uid::effective().is_root() {
+        bail!(
+            "Cannot switch to user '{}': privilege drop requires starting as root",
+            user
+        );
+    }
+
+    // Keep supplementary groups such as dialout for serial reconnects
+    #[cfg(target_os = "linux")]
+    {
+        let name = std::ffi::CString::new(account.name.as_str())?;
+        nix::unistd::initgroups(&name, gid).context("initgroups failed")?;
+    }
+    setgid(gid).context("setgid failed")?;
+    setuid(account.uid).context("setuid failed")?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn drop_privileges(_user: &str, _group: Option<&str>) -> Result<()> {
+    bail!("Privilege drop is only supported on Unix")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pid_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("rustbridge.pid");
+
+        let pid_file = PidFile::create(&path).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap().trim(),
+            std::process::id().to_string()
+        );
+
+        drop(pid_file);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_stale_pid_file_is_ignored() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("rustbridge.pid");
+        std::fs::write(&path, "not-a-pid\n").unwrap();
+        assert!(check_not_running(&path).is_ok());
+
+        // PID far above pid_max, never alive
+        std::fs::write(&path, "2147483646\n").unwrap();
+        assert!(check_not_running(&path).is_ok());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_running_instance_detected() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("rustbridge.pid");
+        let mut child = std::process::Command::new("sleep")
+            .arg("5")
+            .spawn()
+            .unwrap();
+        std::fs::write(&path, format!("{}\n", child.id())).unwrap();
+
+        let result = check_not_running(&path);
+        child.kill().unwrap();
+        let _ = child.wait();
+
+        assert!(result.unwrap_err().to_string().contains("already running"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_drop_privileges_unknown_user() {
+        assert!(drop_privileges("rustbridge-no-such-user", None).is_err());
+    }
+}
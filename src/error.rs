@@ -0,0 +1,101 @@
+//! Error type returned by the public library API
+//!
+//! Internals and the CLI use `anyhow`; functions that library users call
+//! return [`RustbridgeError`] so they can react to the kind of failure,
+//! e.g. retry on a timeout but give up on a configuration error.
+
+use tokio_modbus::Exception;
+
+use crate::modbus::client::ModbusError;
+
+/// Result type of the public library API
+pub type Result<T, E = RustbridgeError> = std::result::Result<T, E>;
+
+/// Errors from configuration, device I/O and publishing
+#[derive(Debug, thiserror::Error)]
+pub enum RustbridgeError {
+    /// Invalid or unreadable configuration
+    #[error("Configuration error: {0}")]
+    Config(String),
+    /// Could not connect, or an established connection failed
+    #[error("Connection error: {0}")]
+    Connection(String),
+    /// The device or broker did not answer in time
+    #[error("Request timed out")]
+    Timeout,
+    /// The device's answer could not be decoded, e.g. after repeated corrupt
+    /// frames
+    #[error("Invalid response: {0}")]
+    InvalidResponse(String),
+    /// The device answered with a Modbus exception
+    #[error("Modbus exception: {0:?}")]
+    Exception(Exception),
+    /// A message could not be published
+    #[error("Publish error: {0}")]
+    Publish(String),
+}
+
+impl RustbridgeError {
+    /// Whether retrying the same operation later may succeed
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            Self::Connection(_) | Self::InvalidResponse(_) | Self::Timeout
+        )
+    }
+}
+
+impl From<ModbusError> for RustbridgeError {
+    fn from(error: ModbusError) -> Self {
+        match error {
+            ModbusError::Exception(exception) => Self::Exception(exception),
+            ModbusError::Io(e) if e.kind() == std::io::ErrorKind::TimedOut => Self::Timeout,
+            // tokio-modbus gives up with InvalidData after repeated corrupt frames
+            ModbusError::Io(e) | ModbusError::Transport(tokio_modbus::Error::Transport(e))
+                if e.kind() == std::io::ErrorKind::InvalidData =>
+            {
+                Self::InvalidResponse(e.to_string())
+            }
+            ModbusError::Io(e) => Self::Connection(e.to_string()),
+            ModbusError::Transport(e) => Self::Connection(format!("Transport error: {}", e)),
+            ModbusError::Serial(e) => Self::Connection(format!("Serial port error: {}", e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_modbus_error() {
+        let timeout = ModbusError::Io(std::io::Error::new(
+            std::io::ErrorKind::TimedOut,
+            "request timed out",
+        ));
+        assert!(matches!(
+            RustbridgeError::from(timeout),
+            RustbridgeError::Timeout
+        ));
+
+        let exception =
+            RustbridgeError::from(ModbusError::Exception(Exception::IllegalDataAddress));
+        assert!(matches!(
+            exception,
+            RustbridgeError::Exception(Exception::IllegalDataAddress)
+        ));
+        assert!(!exception.is_retryable());
+
+        let corrupt = ModbusError::Transport(tokio_modbus::Error::Transport(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "Too many retries",
+        )));
+        assert!(matches!(
+            RustbridgeError::from(corrupt),
+            RustbridgeError::InvalidResponse(_)
+        ));
+
+        let broken = ModbusError::Io(std::io::Error::from(std::io::ErrorKind::BrokenPipe));
+        assert!(RustbridgeError::from(broken).is_retryable());
+    }
+}
@@ -0,0 +1,191 @@
+//! Device events
+//!
+//! Structured events emitted when a device connects, disconnects, is being
+//! reconnected or its serial port fails, when a register alarm is raised or
+//! cleared, when a write is performed, when the device's health state
+//! changes, and for entries drained from FIFO queue registers. Events are
+//! sent on a dedicated broadcast channel, published (not retained) to
+//! `{prefix}/{device_id}/events` and forwarded to WebSocket clients. Each
+//! event has a unique, increasing `id`, so consumers can build an event log
+//! and drop duplicates.
+
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::sync::broadcast;
+
+use crate::audit::WriteSource;
+
+/// Broadcast channel capacity for connection events
+pub const EVENT_CAPACITY: usize = 256;
+
+/// Sender half of the connection event channel
+pub type EventSender = broadcast::Sender<ConnectionEvent>;
+
+/// Kind of device event
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConnectionEventKind {
+    /// Connection to the device established
+    Connected,
+    /// Connection to the device lost or could not be established
+    Disconnected,
+    /// About to try reconnecting to the device
+    ReconnectAttempt,
+    /// Serial port could not be opened or failed
+    SerialPortError,
+    /// Entry drained from a FIFO queue register (FC24)
+    FifoEntry,
+    /// A register's `alarm` condition started to hold
+    AlarmRaised,
+    /// A register's `alarm` condition stopped holding
+    AlarmCleared,
+    /// A register or coil was written
+    Write,
+    /// The device's health state changed, `value` holds its score
+    HealthChanged,
+}
+
+/// A single device event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConnectionEvent {
+    /// Unique and increasing: microseconds since the Unix epoch at creation,
+    /// bumped when two events share a microsecond
+    #[serde(default)]
+    pub id: u64,
+    pub device_id: String,
+    pub event: ConnectionEventKind,
+    /// Reconnect attempt number, starting at 1
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub attempt: Option<u32>,
+    /// Error or detail message
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    /// Register the event is about
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub register: Option<String>,
+    /// Scaled value carried by the event
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<f64>,
+    /// Origin of a write
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<WriteSource>,
+    pub timestamp: String,
+}
+
+impl ConnectionEvent {
+    /// Create an event timestamped now
+    pub fn new(device_id: impl Into<String>, event: ConnectionEventKind) -> Self {
+        Self {
+            id: next_id(),
+            device_id: device_id.into(),
+            event,
+            attempt: None,
+            message: None,
+            register: None,
+            value: None,
+            source: None,
+            timestamp: chrono::Utc::now().to_rfc3339(),
+        }
+    }
+
+    /// Attach the reconnect attempt number
+    pub fn attempt(mut self, attempt: u32) -> Self {
+        self.attempt = Some(attempt);
+        self
+    }
+
+    /// Attach the register and value the event carries
+    pub fn register_value(mut self, register: impl Into<String>, value: f64) -> Self {
+        self.register = Some(register.into());
+        self.value = Some(value);
+        self
+    }
+
+    /// Attach the value the event carries
+    pub fn value(mut self, value: f64) -> Self {
+        self.value = Some(value);
+        self
+    }
+
+    /// Attach the origin of a write
+    pub fn source(mut self, source: WriteSource) -> Self {
+        self.source = Some(source);
+        self
+    }
+
+    /// Attach an error or detail message
+    pub fn message(mut self, message: impl Into<String>) -> Self {
+        self.message = Some(message.into());
+        self
+    }
+}
+
+/// Next event ID, see [`ConnectionEvent::id`]
+fn next_id() -> u64 {
+    static LAST_ID: AtomicU64 = AtomicU64::new(0);
+    let now = chrono::Utc::now().timestamp_micros().max(0) as u64;
+    let previous = LAST_ID
+        .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |last| {
+            Some(now.max(last + 1))
+        })
+        .unwrap_or_default();
+    now.max(previous + 1)
+}
+
+/// Create the device event channel
+pub fn channel() -> EventSender {
+    let (tx, _) = broadcast::channel(EVENT_CAPACITY);
+    tx
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_serialization() {
+        let event = ConnectionEvent::new("plc-001", ConnectionEventKind::ReconnectAttempt)
+            .attempt(3)
+            .message("Connection refused");
+        let json = serde_json::to_value(&event).unwrap();
+
+        assert_eq!(json["device_id"], "plc-001");
+        assert_eq!(json["event"], "reconnect_attempt");
+        assert_eq!(json["attempt"], 3);
+        assert_eq!(json["message"], "Connection refused");
+    }
+
+    #[test]
+    fn test_ids_increase() {
+        let first = ConnectionEvent::new("plc-001", ConnectionEventKind::Connected);
+        let second = ConnectionEvent::new("plc-001", ConnectionEventKind::Write);
+        assert!(second.id > first.id);
+        assert!(first.id > 1_600_000_000_000_000);
+    }
+
+    #[test]
+    fn test_optional_fields_omitted() {
+        let event = ConnectionEvent::new("plc-001", ConnectionEventKind::Connected);
+        let json = serde_json::to_value(&event).unwrap();
+
+        assert_eq!(json["event"], "connected");
+        assert!(json.get("attempt").is_none());
+        assert!(json.get("message").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_channel_delivers_to_subscribers() {
+        let tx = channel();
+        let mut rx = tx.subscribe();
+
+        tx.send(ConnectionEvent::new(
+            "rtu-001",
+            ConnectionEventKind::SerialPortError,
+        ))
+        .unwrap();
+
+        let event = rx.recv().await.unwrap();
+        assert_eq!(event.device_id, "rtu-001");
+        assert_eq!(event.event, ConnectionEventKind::SerialPortError);
+    }
+}
@@ -0,0 +1,513 @@
+//! Minimal HTTP(S) GET for reaching fleet management servers
+//!
+//! Used by [`remote_config`](crate::remote_config) and
+//! [`update`](crate::update). Requests are HTTP/1.0, so servers answer without
+//! chunked encoding and close the connection after the body. A body shorter
+//! than its `Content-Length`, or one without a length whose TLS connection
+//! ends without a close_notify, is rejected as truncated. TLS uses the same
+//! rustls setup as the syslog output, and the blocking I/O runs on the
+//! blocking thread pool.
+
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+#[cfg(feature = "tls")]
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::error::{Result, RustbridgeError};
+
+/// Longest response header accepted
+const MAX_HEAD_BYTES: usize = 64 * 1024;
+
+/// Parts of an `http://` or `https://` URL
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemoteUrl {
+    pub tls: bool,
+    /// Host name or IP address, IPv6 addresses without brackets
+    pub host: String,
+    pub port: u16,
+    /// Path and query, starting with `/`
+    pub path: String,
+}
+
+impl RemoteUrl {
+    /// Parse `https://host[:port]/path` or `http://host[:port]/path`; an
+    /// IPv6 host is written in brackets, e.g. `https://[fd00::1]:8443/`
+    pub fn parse(url: &str) -> std::result::Result<Self, String> {
+        let (tls, rest) = if let Some(rest) = url.strip_prefix("https://") {
+            (true, rest)
+        } else if let Some(rest) = url.strip_prefix("http://") {
+            (false, rest)
+        } else {
+            return Err(format!("'{}' is not an http:// or https:// URL", url));
+        };
+        if tls && !cfg!(feature = "tls") {
+            return Err("https needs TLS support (enable the `tls` feature)".to_string());
+        }
+
+        let (authority, path) = match rest.find(['/', '?']) {
+            Some(index) if rest[index..].starts_with('?') => {
+                (&rest[..index], format!("/{}", &rest[index..]))
+            }
+            Some(index) => (&rest[..index], rest[index..].to_string()),
+            None => (rest, "/".to_string()),
+        };
+        let default_port = if tls { 443 } else { 80 };
+        let (host, port) = match authority.strip_prefix('[') {
+            Some(bracketed) => {
+                let (host, port) = bracketed
+                    .split_once(']')
+                    .ok_or_else(|| format!("unclosed '[' in '{}'", url))?;
+                if host.parse::<std::net::Ipv6Addr>().is_err() {
+                    return Err(format!("invalid IPv6 address in '{}'", url));
+                }
+                match port {
+                    "" => (host, None),
+                    port => match port.strip_prefix(':') {
+                        Some(port) => (host, Some(port)),
+                        None => return Err(format!("invalid port in '{}'", url)),
+                    },
+                }
+            }
+            None => match authority.split_once(':') {
+                Some((host, port)) => (host, Some(port)),
+                None => (authority, None),
+            },
+        };
+        let port = match port {
+            Some(port) => port
+                .parse()
+                .map_err(|_| format!("invalid port in '{}'", url))?,
+            None => default_port,
+        };
+        if host.is_empty() {
+            return Err(format!("no host in '{}'", url));
+        }
+        Ok(Self {
+            tls,
+            host: host.to_string(),
+            port,
+            path,
+        })
+    }
+
+    /// `host[:port]` as sent in the `Host` header, the port only when it is
+    /// not the default of the scheme
+    pub fn authority(&self) -> String {
+        let host = if self.host.contains(':') {
+            format!("[{}]", self.host)
+        } else {
+            self.host.clone()
+        };
+        match (self.tls, self.port) {
+            (true, 443) | (false, 80) => host,
+            (_, port) => format!("{}:{}", host, port),
+        }
+    }
+}
+
+/// Status and headers of a response
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Response {
+    pub status: u16,
+    headers: Vec<(String, String)>,
+}
+
+impl Response {
+    /// Value of a header, by case-insensitive name
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// GET requests to one URL
+#[derive(Clone)]
+pub struct Fetcher {
+    source: String,
+    url: RemoteUrl,
+    authorization: Option<String>,
+    timeout: Duration,
+    #[cfg(feature = "tls")]
+    tls_config: Option<Arc<rustls::ClientConfig>>,
+}
+
+impl Fetcher {
+    /// `authorization` is sent as the `Authorization` header; `ca_cert`
+    /// replaces the system roots for `https://` URLs
+    pub fn new(
+        url: &str,
+        authorization: Option<&str>,
+        ca_cert: Option<&str>,
+        timeout: Duration,
+    ) -> std::result::Result<Self, String> {
+        let parsed = RemoteUrl::parse(url)?;
+        #[cfg(feature = "tls")]
+        let tls_config = if parsed.tls {
+            let config = crate::logging::syslog::tls_client_config(ca_cert)
+                .map_err(|e| format!("{:#}", e))?;
+            Some(Arc::new(config))
+        } else {
+            None
+        };
+        #[cfg(not(feature = "tls"))]
+        let _ = ca_cert;
+        Ok(Self {
+            source: url.to_string(),
+            url: parsed,
+            authorization: authorization.map(str::to_string),
+            timeout,
+            #[cfg(feature = "tls")]
+            tls_config,
+        })
+    }
+
+    /// The URL requested
+    pub fn url(&self) -> &str {
+        &self.source
+    }
+
+    /// GET the URL with extra request headers, returning at most `max_bytes`
+    /// of body
+    pub async fn get(
+        &self,
+        headers: &[(&str, &str)],
+        max_bytes: u64,
+    ) -> Result<(Response, Vec<u8>)> {
+        self.get_into(headers, Vec::new(), max_bytes).await
+    }
+
+    /// GET the URL, writing the body to `sink` as it arrives
+    pub async fn get_into<W: Write + Send + 'static>(
+        &self,
+        headers: &[(&str, &str)],
+        sink: W,
+        max_bytes: u64,
+    ) -> Result<(Response, W)> {
+        let request = self.request(headers);
+        let fetcher = self.clone();
+        tokio::task::spawn_blocking(move || fetcher.exchange(&request, sink, max_bytes))
+            .await
+            .map_err(|e| RustbridgeError::Connection(e.to_string()))?
+            .map_err(|e| match e.kind() {
+                std::io::ErrorKind::TimedOut | std::io::ErrorKind::WouldBlock => {
+                    RustbridgeError::Timeout
+                }
+                _ => RustbridgeError::Connection(format!("{}: {}", self.source, e)),
+            })
+    }
+
+    fn request(&self, headers: &[(&str, &str)]) -> String {
+        let mut request = format!(
+            "GET {} HTTP/1.0\r\nHost: {}\r\nUser-Agent: rustbridge/{}\r\n",
+            self.url.path,
+            self.url.authority(),
+            env!("CARGO_PKG_VERSION")
+        );
+        if let Some(authorization) = &self.authorization {
+            request.push_str(&format!("Authorization: {}\r\n", authorization));
+        }
+        for (name, value) in headers {
+            request.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        request.push_str("\r\n");
+        request
+    }
+
+    fn exchange<W: Write>(
+        &self,
+        request: &str,
+        sink: W,
+        max_bytes: u64,
+    ) -> std::io::Result<(Response, W)> {
+        let address = (self.url.host.as_str(), self.url.port)
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| std::io::Error::other(format!("{} not found", self.url.host)))?;
+        let stream = TcpStream::connect_timeout(&address, self.timeout)?;
+        stream.set_read_timeout(Some(self.timeout))?;
+        stream.set_write_timeout(Some(self.timeout))?;
+        #[cfg(feature = "tls")]
+        if let Some(tls_config) = &self.tls_config {
+            let server_name = rustls::pki_types::ServerName::try_from(self.url.host.clone())
+                .map_err(std::io::Error::other)?;
+            let connection = rustls::ClientConnection::new(tls_config.clone(), server_name)
+                .map_err(std::io::Error::other)?;
+            let stream = rustls::StreamOwned::new(connection, stream);
+            return exchange(stream, request, sink, max_bytes);
+        }
+        exchange(stream, request, sink, max_bytes)
+    }
+}
+
+/// Send a request, parse the response head and copy the body to `sink`
+/// until its `Content-Length` or, without one, until the server closes the
+/// connection
+fn exchange<S: Read + Write, W: Write>(
+    mut stream: S,
+    request: &str,
+    mut sink: W,
+    max_bytes: u64,
+) -> std::io::Result<(Response, W)> {
+    stream.write_all(request.as_bytes())?;
+    stream.flush()?;
+
+    let mut received = Vec::new();
+    let mut buf = [0u8; 8192];
+    let head_end = loop {
+        if let Some(end) = received.windows(4).position(|window| window == b"\r\n\r\n") {
+            break end;
+        }
+        if received.len() > MAX_HEAD_BYTES {
+            return Err(std::io::Error::other("response header too long"));
+        }
+        match stream.read(&mut buf) {
+            Ok(0) => {
+                return Err(std::io::Error::other(
+                    "connection closed before the response",
+                ))
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                return Err(std::io::Error::other(
+                    "connection closed before the response",
+                ))
+            }
+            result => received.extend_from_slice(&buf[..result?]),
+        }
+    };
+    let response = parse_head(&received[..head_end])?;
+    if response.header("Transfer-Encoding").is_some() {
+        return Err(std::io::Error::other("unexpected Transfer-Encoding"));
+    }
+    // These never have a body, whatever length they announce
+    let expected = match response.status {
+        204 | 304 => Some(0),
+        _ => response
+            .header("Content-Length")
+            .map(|length| {
+                length
+                    .parse::<u64>()
+                    .map_err(|_| std::io::Error::other("invalid Content-Length"))
+            })
+            .transpose()?,
+    };
+
+    let mut written = 0u64;
+    let mut write = |bytes: &[u8], sink: &mut W| {
+        // Anything after the announced length is not part of the body
+        let bytes = match expected {
+            Some(expected) => &bytes[..bytes.len().min((expected - written) as usize)],
+            None => bytes,
+        };
+        written += bytes.len() as u64;
+        if written > max_bytes {
+            return Err(std::io::Error::other(format!(
+                "response larger than {} bytes",
+                max_bytes
+            )));
+        }
+        sink.write_all(bytes)?;
+        Ok(written)
+    };
+    let mut body_len = write(&received[head_end + 4..], &mut sink)?;
+    while expected != Some(body_len) {
+        match stream.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => body_len = write(&buf[..n], &mut sink)?,
+            // A TLS connection closed without close_notify may have been cut
+            // short, which only a length can rule out
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof && expected.is_some() => {
+                break
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                return Err(std::io::Error::other(
+                    "connection closed without TLS close_notify and no Content-Length; response may be truncated",
+                ))
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    if let Some(expected) = expected.filter(|expected| *expected != body_len) {
+        return Err(std::io::Error::other(format!(
+            "response truncated: {} of {} bytes",
+            body_len, expected
+        )));
+    }
+    sink.flush()?;
+    Ok((response, sink))
+}
+
+fn parse_head(head: &[u8]) -> std::io::Result<Response> {
+    let invalid = |what: &str| std::io::Error::other(format!("invalid HTTP response: {}", what));
+    let head = std::str::from_utf8(head).map_err(|_| invalid("header not UTF-8"))?;
+    let mut lines = head.split("\r\n");
+    let status = lines
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .and_then(|code| code.parse().ok())
+        .ok_or_else(|| invalid("no status code"))?;
+    let headers = lines
+        .filter_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            Some((key.trim().to_string(), value.trim().to_string()))
+        })
+        .collect();
+    Ok(Response { status, headers })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_url() {
+        assert_eq!(
+            RemoteUrl::parse("http://fleet.local:8080/gateways/a.yaml?v=2").unwrap(),
+            RemoteUrl {
+                tls: false,
+                host: "fleet.local".to_string(),
+                port: 8080,
+                path: "/gateways/a.yaml?v=2".to_string(),
+            }
+        );
+        let bare = RemoteUrl::parse("http://fleet.local").unwrap();
+        assert_eq!((bare.port, bare.path.as_str()), (80, "/"));
+        if cfg!(feature = "tls") {
+            assert_eq!(RemoteUrl::parse("https://fleet.local/a").unwrap().port, 443);
+        }
+        assert!(RemoteUrl::parse("ftp://fleet.local/a").is_err());
+        assert!(RemoteUrl::parse("http://:80/a").is_err());
+        assert!(RemoteUrl::parse("http://fleet.local:x/a").is_err());
+
+        let v6 = RemoteUrl::parse("http://[fd00::1]:8080/a").unwrap();
+        assert_eq!((v6.host.as_str(), v6.port), ("fd00::1", 8080));
+        assert_eq!(v6.authority(), "[fd00::1]:8080");
+        let v6 = RemoteUrl::parse("http://[fd00::1]/a").unwrap();
+        assert_eq!((v6.host.as_str(), v6.port), ("fd00::1", 80));
+        assert_eq!(v6.authority(), "[fd00::1]");
+        assert!(RemoteUrl::parse("http://fd00::1/a").is_err());
+        assert!(RemoteUrl::parse("http://[fd00::1/a").is_err());
+        assert!(RemoteUrl::parse("http://[fleet.local]/a").is_err());
+        assert_eq!(
+            RemoteUrl::parse("http://fleet.local:8080/")
+                .unwrap()
+                .authority(),
+            "fleet.local:8080"
+        );
+    }
+
+    /// In-memory connection answering with a canned response
+    struct Canned {
+        response: std::io::Cursor<Vec<u8>>,
+        request: Vec<u8>,
+        /// End like a TLS connection closed without close_notify
+        cut: bool,
+    }
+
+    impl Read for Canned {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            // Small reads, so the header arrives in pieces
+            let len = buf.len().min(7);
+            match self.response.read(&mut buf[..len])? {
+                0 if self.cut => Err(std::io::ErrorKind::UnexpectedEof.into()),
+                n => Ok(n),
+            }
+        }
+    }
+
+    impl Write for Canned {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.request.write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn canned(response: &[u8]) -> Canned {
+        Canned {
+            response: std::io::Cursor::new(response.to_vec()),
+            request: Vec::new(),
+            cut: false,
+        }
+    }
+
+    fn cut(response: &[u8]) -> Canned {
+        Canned {
+            cut: true,
+            ..canned(response)
+        }
+    }
+
+    #[test]
+    fn test_exchange() {
+        let (response, body) = exchange(
+            canned(b"HTTP/1.1 200 OK\r\netag: \"v7\"\r\n\r\ndevices: []\n"),
+            "GET / HTTP/1.0\r\n\r\n",
+            Vec::new(),
+            1024,
+        )
+        .unwrap();
+        assert_eq!(response.status, 200);
+        assert_eq!(response.header("ETag"), Some("\"v7\""));
+        assert_eq!(body, b"devices: []\n");
+
+        let (response, body) = exchange(
+            canned(b"HTTP/1.1 304 Not Modified\r\n\r\n"),
+            "GET / HTTP/1.0\r\n\r\n",
+            Vec::new(),
+            1024,
+        )
+        .unwrap();
+        assert_eq!(response.status, 304);
+        assert!(body.is_empty());
+
+        let too_large = exchange(
+            canned(b"HTTP/1.1 200 OK\r\n\r\n0123456789"),
+            "GET / HTTP/1.0\r\n\r\n",
+            Vec::new(),
+            4,
+        );
+        assert!(too_large.is_err());
+        assert!(exchange(canned(b"garbage"), "", Vec::new(), 1024).is_err());
+    }
+
+    #[test]
+    fn test_exchange_truncated() {
+        let get = |stream: Canned| exchange(stream, "GET / HTTP/1.0\r\n\r\n", Vec::new(), 1024);
+
+        // A verified length needs no close_notify
+        let (_, body) = get(cut(
+            b"HTTP/1.1 200 OK\r\nContent-Length: 12\r\n\r\ndevices: []\n",
+        ))
+        .unwrap();
+        assert_eq!(body, b"devices: []\n");
+        let (_, body) = get(canned(b"HTTP/1.1 200 OK\r\n\r\ndevices: []\n")).unwrap();
+        assert_eq!(body, b"devices: []\n");
+        let (response, body) = get(cut(
+            b"HTTP/1.1 304 Not Modified\r\nContent-Length: 12\r\n\r\n",
+        ))
+        .unwrap();
+        assert_eq!((response.status, body.len()), (304, 0));
+
+        let short = get(canned(
+            b"HTTP/1.1 200 OK\r\nContent-Length: 40\r\n\r\ndevices: []\n",
+        ));
+        assert!(short
+            .unwrap_err()
+            .to_string()
+            .contains("response truncated: 12 of 40 bytes"));
+        let long = get(canned(
+            b"HTTP/1.1 200 OK\r\nContent-Length: 4\r\n\r\ndevices: []\n",
+        ));
+        assert_eq!(long.unwrap().1, b"devi");
+        let unverified = get(cut(b"HTTP/1.1 200 OK\r\n\r\ndevices: []\n"));
+        assert!(unverified
+            .unwrap_err()
+            .to_string()
+            .contains("without TLS close_notify"));
+    }
+}
@@ -0,0 +1,131 @@
+//! Selection of registers by device, name, group and meta
+//!
+//! Used for the values published to the broker (`mqtt.filter`) and the
+//! register gauges exported on `/metrics` (`server.register_gauges.filter`):
+//!
+//! ```yaml
+//! filter:
+//!   include:
+//!     - group: site-a
+//!     - device: "meter-*"
+//!       register: "energy_*"
+//!   exclude:
+//!     - meta: { cloud: false }
+//! ```
+
+use crate::config::{FilterRule, Meta, PublishFilter};
+
+/// Register as seen by filter rules
+pub struct Subject<'a> {
+    pub device_id: &'a str,
+    pub register: &'a str,
+    pub group: Option<&'a str>,
+    /// Device meta overlaid with the register's
+    pub meta: &'a Meta,
+}
+
+/// Whether `subject` is selected by `filter`
+pub fn allows(filter: &PublishFilter, subject: &Subject) -> bool {
+    (filter.include.is_empty() || filter.include.iter().any(|rule| matches(rule, subject)))
+        && !filter.exclude.iter().any(|rule| matches(rule, subject))
+}
+
+fn matches(rule: &FilterRule, subject: &Subject) -> bool {
+    rule.device
+        .as_ref()
+        .is_none_or(|pattern| glob(pattern, subject.device_id))
+        && rule
+            .register
+            .as_ref()
+            .is_none_or(|pattern| glob(pattern, subject.register))
+        && rule
+            .group
+            .as_ref()
+            .is_none_or(|pattern| subject.group.is_some_and(|group| glob(pattern, group)))
+        && rule
+            .meta
+            .iter()
+            .all(|(key, value)| subject.meta.get(key) == Some(value))
+}
+
+/// Match `text` against a pattern where `*` stands for any characters
+fn glob(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_publish_filter() {
+        let filter: PublishFilter = serde_yaml::from_str(
+            r#"
+include:
+  - group: site-a
+  - device: "meter-*"
+    register: "energy_*"
+exclude:
+  - meta: { cloud: false }
+"#,
+        )
+        .unwrap();
+        let meta = Meta::new();
+        let private: Meta = [("cloud".to_string(), serde_json::json!(false))].into();
+        let subject = |device_id, register, group, meta| Subject {
+            device_id,
+            register,
+            group,
+            meta,
+        };
+
+        assert!(allows(
+            &filter,
+            &subject("plc-1", "level", Some("site-a"), &meta)
+        ));
+        assert!(!allows(
+            &filter,
+            &subject("plc-1", "level", Some("site-b"), &meta)
+        ));
+        assert!(!allows(&filter, &subject("plc-1", "level", None, &meta)));
+        assert!(allows(
+            &filter,
+            &subject("meter-7", "energy_total", None, &meta)
+        ));
+        assert!(!allows(
+            &filter,
+            &subject("meter-7", "voltage", None, &meta)
+        ));
+        assert!(!allows(
+            &filter,
+            &subject("plc-1", "level", Some("site-a"), &private)
+        ));
+        assert!(allows(
+            &PublishFilter::default(),
+            &subject("plc-1", "level", None, &private)
+        ));
+
+        assert!(glob("*", ""));
+        assert!(glob("a*b*c", "abbc"));
+        assert!(glob("a*c", "ac"));
+        assert!(!glob("a*c", "acb"));
+        assert!(!glob("ab*ba", "aba"));
+        assert!(glob("exact", "exact"));
+        assert!(!glob("exact", "exactly"));
+    }
+}
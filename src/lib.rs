@@ -2,10 +2,80 @@
 //!
 //! High-performance Modbus TCP/RTU to JSON/MQTT gateway
 //! Built with Rust for Industry 4.0 edge deployments
+//!
+//! The crate can be embedded in other Rust programs. Load a [`Config`],
+//! start a [`Bridge`] and consume register updates from the returned
+//! [`BridgeHandle`] as a `Stream`:
+//!
+//! ```no_run
+//! # async fn example() -> Result<(), rustbridge::RustbridgeError> {
+//! use futures_util::StreamExt;
+//!
+//! let config = rustbridge::config::load_config("config.yaml")?;
+//! let handle = rustbridge::Bridge::new(config).await?.start().await?;
+//!
+//! let mut updates = handle.subscribe_device("plc-001");
+//! while let Some(update) = updates.next().await {
+//!     println!("{}/{} = {}", update.device_id, update.register_name, update.value);
+//! }
+//!
+//! handle.shutdown().await
+//! # }
+//! ```
+//!
+//! For direct device access without the bridge, use [`ModbusClient`].
+//!
+//! # Cargo features
+//!
+//! All enabled by default; disable them for smaller gateway builds:
+//!
+//! - `mqtt`: MQTT publishing (rumqttc)
+//! - `http-api`: REST API, WebSocket stream and `/metrics` (axum)
+//! - `rtu`: Modbus RTU over serial ports
+//! - `monitor`: `rustbridge monitor` terminal UI (implies `http-api` and
+//!   `api-client`)
+//! - `api-client`: HTTP client for commands that query a running bridge,
+//!   e.g. `rustbridge snapshot`
+//! - `tls`: rustls for MQTT and syslog over TLS, self-update downloads and
+//!   `remote_config`
+//!
+//! `embedded` is the minimal gateway build, `mqtt` and `rtu` only; use it
+//! with `--no-default-features --profile embedded`.
+//!
+//! The `testing` feature (off by default) adds [`testing`], an in-process
+//! Modbus TCP server and MQTT broker for end-to-end tests of a setup.
 
 pub mod api;
+pub mod audit;
 pub mod bridge;
+pub mod build_info;
 pub mod config;
+pub mod control;
+pub mod daemon;
+pub mod error;
+pub mod events;
+pub mod fetch;
+pub mod filter;
+pub mod logging;
 pub mod metrics;
 pub mod modbus;
+#[cfg(feature = "mqtt")]
 pub mod mqtt;
+pub mod profiles;
+pub mod recipes;
+pub mod reload;
+pub mod remote_config;
+pub mod systemd;
+#[cfg(feature = "testing")]
+pub mod testing;
+pub mod update;
+
+pub use api::RegisterUpdate;
+pub use bridge::{Bridge, BridgeHandle};
+pub use config::Config;
+pub use error::RustbridgeError;
+pub use modbus::codec::{ByteOrder, FromRegisters, ToRegisters, TypedValue};
+pub use modbus::reader::RegisterStore;
+pub use modbus::{MockTransport, ModbusClient, ModbusClientBuilder, ModbusTransport};
+#[cfg(feature = "mqtt")]
+pub use mqtt::MqttPublisher;
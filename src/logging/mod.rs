@@ -0,0 +1,266 @@
+//! Logging initialization
+//!
+//! Configures the global tracing subscriber from the `logging` config section:
+//! - Human-readable text or JSON lines (for Loki/ELK pipelines)
+//! - Default level plus per-module overrides
+//! - stdout, append-only file, or RFC 5424 syslog output
+//!
+//! `RUST_LOG`, when set, takes precedence over the configured levels.
+//! The log file can be reopened (after rotation) with [`reopen`].
+
+pub mod syslog;
+
+use anyhow::{Context, Result};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Layer};
+
+use crate::config::{LogFormat, LogTarget, LoggingConfig};
+
+/// Log file of the global subscriber, kept for [`reopen`]
+static LOG_FILE: OnceLock<LogFile> = OnceLock::new();
+
+/// Initialize the global tracing subscriber
+pub fn init(config: &LoggingConfig) -> Result<()> {
+    let filter = match std::env::var("RUST_LOG") {
+        Ok(directives) if !directives.is_empty() => EnvFilter::try_new(&directives)
+            .with_context(|| format!("Invalid RUST_LOG directives: {}", directives))?,
+        _ => build_filter(config)?,
+    };
+
+    let output_layer = if config.target == LogTarget::Syslog {
+        let syslog_config = config
+            .syslog
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("logging.syslog is required when target is syslog"))?;
+        syslog::SyslogLayer::new(syslog_config)?.boxed()
+    } else {
+        fmt_layer(config)?
+    };
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(output_layer)
+        .try_init()
+        .with_context(|| "Failed to initialize logging")?;
+
+    Ok(())
+}
+
+/// Build the stdout/file formatting layer
+fn fmt_layer<S>(config: &LoggingConfig) -> Result<Box<dyn Layer<S> + Send + Sync>>
+where
+    S: tracing::Subscriber + for<'a> tracing_subscriber::registry::LookupSpan<'a>,
+{
+    let writer = make_writer(config)?;
+
+    Ok(match config.format {
+        LogFormat::Text => tracing_subscriber::fmt::layer()
+            .with_writer(writer)
+            .with_ansi(config.target == LogTarget::Stdout)
+            .with_target(false)
+            .with_thread_ids(true)
+            .with_file(true)
+            .with_line_number(true)
+            .boxed(),
+        LogFormat::Json => tracing_subscriber::fmt::layer()
+            .json()
+            .with_writer(writer)
+            .with_current_span(true)
+            .with_span_list(false)
+            .flatten_event(true)
+            .with_thread_ids(true)
+            .with_file(true)
+            .with_line_number(true)
+            .boxed(),
+    })
+}
+
+/// Build the level filter from the default level and per-module overrides
+pub fn build_filter(config: &LoggingConfig) -> Result<EnvFilter> {
+    for level in std::iter::once(&config.level).chain(config.modules.values()) {
+        level
+            .parse::<LevelFilter>()
+            .map_err(|_| anyhow::anyhow!("Invalid log level: {}", level))?;
+    }
+
+    EnvFilter::try_new(filter_directives(config))
+        .with_context(|| "Invalid logging level configuration")
+}
+
+/// Render the configured levels as `EnvFilter` directives
+fn filter_directives(config: &LoggingConfig) -> String {
+    let mut modules: Vec<_> = config.modules.iter().collect();
+    modules.sort();
+
+    std::iter::once(config.level.clone())
+        .chain(
+            modules
+                .into_iter()
+                .map(|(module, level)| format!("{}={}", module, level)),
+        )
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn make_writer(config: &LoggingConfig) -> Result<BoxMakeWriter> {
+    match config.target {
+        LogTarget::Stdout | LogTarget::Syslog => Ok(BoxMakeWriter::new(std::io::stdout)),
+        LogTarget::File => {
+            let path = config
+                .file
+                .as_deref()
+                .ok_or_else(|| anyhow::anyhow!("logging.file is required when target is file"))?;
+
+            let log_file = LogFile::open(PathBuf::from(path))?;
+            // Only the first (global) subscriber's file is reopened on request
+            let _ = LOG_FILE.set(log_file.clone());
+            Ok(BoxMakeWriter::new(move || log_file.clone()))
+        }
+    }
+}
+
+/// Reopen the log file, e.g. after logrotate moved it away.
+///
+/// Does nothing when logging to stdout or syslog.
+pub fn reopen() -> Result<()> {
+    match LOG_FILE.get() {
+        Some(log_file) => log_file.reopen(),
+        None => Ok(()),
+    }
+}
+
+/// Append-only log file that can be swapped for a fresh handle
+#[derive(Clone)]
+struct LogFile {
+    path: PathBuf,
+    file: Arc<Mutex<File>>,
+}
+
+impl LogFile {
+    fn open(path: PathBuf) -> Result<Self> {
+        let file = Self::open_file(&path)?;
+        Ok(Self {
+            path,
+            file: Arc::new(Mutex::new(file)),
+        })
+    }
+
+    fn open_file(path: &Path) -> Result<File> {
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open log file: {}", path.display()))
+    }
+
+    fn reopen(&self) -> Result<()> {
+        let file = Self::open_file(&self.path)?;
+        if let Ok(mut current) = self.file.lock() {
+            *current = file;
+        }
+        Ok(())
+    }
+}
+
+impl Write for LogFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self.file.lock() {
+            Ok(mut file) => file.write(buf),
+            Err(_) => Ok(buf.len()),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self.file.lock() {
+            Ok(mut file) => file.flush(),
+            Err(_) => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter_directives_default() {
+        let config = LoggingConfig::default();
+        assert_eq!(filter_directives(&config), "info");
+    }
+
+    #[test]
+    fn test_filter_directives_with_modules() {
+        let mut config = LoggingConfig {
+            level: "warn".to_string(),
+            ..Default::default()
+        };
+        config
+            .modules
+            .insert("rustbridge::mqtt".to_string(), "info".to_string());
+        config
+            .modules
+            .insert("rustbridge::modbus".to_string(), "debug".to_string());
+
+        assert_eq!(
+            filter_directives(&config),
+            "warn,rustbridge::modbus=debug,rustbridge::mqtt=info"
+        );
+        assert!(build_filter(&config).is_ok());
+    }
+
+    #[test]
+    fn test_invalid_level_rejected() {
+        let config = LoggingConfig {
+            level: "loud".to_string(),
+            ..Default::default()
+        };
+        assert!(build_filter(&config).is_err());
+    }
+
+    #[test]
+    fn test_file_target_requires_path() {
+        let config = LoggingConfig {
+            target: LogTarget::File,
+            ..Default::default()
+        };
+        assert!(make_writer(&config).is_err());
+    }
+
+    #[test]
+    fn test_file_target_creates_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("rustbridge.log");
+        let config = LoggingConfig {
+            target: LogTarget::File,
+            file: Some(path.to_string_lossy().to_string()),
+            ..Default::default()
+        };
+
+        assert!(make_writer(&config).is_ok());
+        assert!(path.exists());
+    }
+
+    #[test]
+    fn test_log_file_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("rustbridge.log");
+        let rotated = dir.path().join("rustbridge.log.1");
+
+        let mut log_file = LogFile::open(path.clone()).unwrap();
+        log_file.write_all(b"before\n").unwrap();
+        std::fs::rename(&path, &rotated).unwrap();
+
+        log_file.reopen().unwrap();
+        log_file.write_all(b"after\n").unwrap();
+
+        assert_eq!(std::fs::read_to_string(&rotated).unwrap(), "before\n");
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "after\n");
+    }
+}
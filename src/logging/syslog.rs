@@ -0,0 +1,542 @@
+//! RFC 5424 syslog output
+//!
+//! A tracing layer that formats events as RFC 5424 messages and ships them
+//! to a syslog collector over UDP, TCP (RFC 6587 octet counting) or TLS
+//! (RFC 5425). Event and span fields (e.g., `device_id`, `register`) are
+//! emitted as structured data.
+//!
+//! Sending happens on a dedicated thread so logging never blocks the
+//! runtime; if the collector is unreachable messages are dropped.
+
+use anyhow::{Context, Result};
+use std::fmt::Write as _;
+use std::io::Write;
+use std::net::{TcpStream, UdpSocket};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+#[cfg(feature = "tls")]
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::layer::Context as LayerContext;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+use crate::config::{SyslogConfig, SyslogTransport};
+
+/// Structured data ID (private enterprise number 32473 is reserved for documentation)
+const SD_ID: &str = "rustbridge@32473";
+
+/// Maximum number of messages waiting to be sent
+const QUEUE_CAPACITY: usize = 1024;
+
+/// Delay before reconnecting to the collector after a failure
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// Tracing layer emitting RFC 5424 syslog messages
+pub struct SyslogLayer {
+    facility: u8,
+    hostname: String,
+    app_name: String,
+    proc_id: String,
+    octet_counting: bool,
+    tx: SyncSender<Vec<u8>>,
+}
+
+impl SyslogLayer {
+    /// Create the layer and start the sender thread
+    pub fn new(config: &SyslogConfig) -> Result<Self> {
+        let facility = facility_code(&config.facility)
+            .ok_or_else(|| anyhow::anyhow!("Invalid syslog facility: {}", config.facility))?;
+
+        let transport = Transport::new(config)?;
+        let (tx, rx) = mpsc::sync_channel(QUEUE_CAPACITY);
+
+        std::thread::Builder::new()
+            .name("syslog-sender".to_string())
+            .spawn(move || run_sender(transport, rx))
+            .with_context(|| "Failed to spawn syslog sender thread")?;
+
+        Ok(Self {
+            facility,
+            hostname: config.hostname.clone().unwrap_or_else(local_hostname),
+            app_name: config.app_name.clone(),
+            proc_id: std::process::id().to_string(),
+            octet_counting: config.transport != SyslogTransport::Udp,
+            tx,
+        })
+    }
+}
+
+impl<S> Layer<S> for SyslogLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(
+        &self,
+        attrs: &tracing::span::Attributes<'_>,
+        id: &tracing::span::Id,
+        ctx: LayerContext<'_, S>,
+    ) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+
+        let mut visitor = FieldVisitor::default();
+        attrs.record(&mut visitor);
+        span.extensions_mut().insert(SpanFields(visitor.fields));
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: LayerContext<'_, S>) {
+        let mut visitor = FieldVisitor::default();
+        event.record(&mut visitor);
+
+        let mut fields = Vec::new();
+        if let Some(scope) = ctx.event_scope(event) {
+            for span in scope.from_root() {
+                if let Some(span_fields) = span.extensions().get::<SpanFields>() {
+                    fields.extend(span_fields.0.iter().cloned());
+                }
+            }
+        }
+        fields.extend(visitor.fields);
+
+        let message = format_message(
+            &Header {
+                facility: self.facility,
+                severity: severity(event.metadata().level()),
+                timestamp: chrono::Utc::now(),
+                hostname: &self.hostname,
+                app_name: &self.app_name,
+                proc_id: &self.proc_id,
+                msg_id: event.metadata().target(),
+            },
+            &fields,
+            &visitor.message,
+        );
+
+        let frame = if self.octet_counting {
+            frame_octet_counted(&message)
+        } else {
+            message.into_bytes()
+        };
+
+        // Drop the message rather than block if the collector is slow
+        let _ = self.tx.try_send(frame);
+    }
+}
+
+/// Span fields stored in span extensions for later events
+struct SpanFields(Vec<(String, String)>);
+
+/// Collects the message and key/value fields of an event or span
+#[derive(Default)]
+struct FieldVisitor {
+    message: String,
+    fields: Vec<(String, String)>,
+}
+
+impl Visit for FieldVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message = value.to_string();
+        } else {
+            self.fields
+                .push((field.name().to_string(), value.to_string()));
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            self.message = format!("{:?}", value);
+        } else {
+            self.fields
+                .push((field.name().to_string(), format!("{:?}", value)));
+        }
+    }
+}
+
+/// RFC 5424 header fields
+struct Header<'a> {
+    facility: u8,
+    severity: u8,
+    timestamp: chrono::DateTime<chrono::Utc>,
+    hostname: &'a str,
+    app_name: &'a str,
+    proc_id: &'a str,
+    msg_id: &'a str,
+}
+
+/// Format an RFC 5424 message
+fn format_message(header: &Header<'_>, fields: &[(String, String)], message: &str) -> String {
+    let pri = header.facility as u16 * 8 + header.severity as u16;
+
+    let mut out = format!(
+        "<{}>1 {} {} {} {} {} ",
+        pri,
+        header
+            .timestamp
+            .to_rfc3339_opts(chrono::SecondsFormat::Micros, true),
+        header_field(header.hostname, 255),
+        header_field(header.app_name, 48),
+        header_field(header.proc_id, 128),
+        header_field(header.msg_id, 32),
+    );
+
+    if fields.is_empty() {
+        out.push('-');
+    } else {
+        let _ = write!(out, "[{}", SD_ID);
+        for (name, value) in fields {
+            let _ = write!(out, " {}=\"{}\"", sd_name(name), escape_param(value));
+        }
+        out.push(']');
+    }
+
+    if !message.is_empty() {
+        out.push(' ');
+        out.push_str(message);
+    }
+
+    out
+}
+
+/// Header fields are printable ASCII without spaces, or "-" when empty
+fn header_field(value: &str, max_len: usize) -> String {
+    let cleaned: String = value
+        .chars()
+        .filter(|c| c.is_ascii_graphic())
+        .take(max_len)
+        .collect();
+
+    if cleaned.is_empty() {
+        "-".to_string()
+    } else {
+        cleaned
+    }
+}
+
+/// SD-NAMEs are at most 32 printable characters excluding '=', ' ', ']' and '"'
+fn sd_name(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_ascii_graphic() && !matches!(c, '=' | ']' | '"'))
+        .take(32)
+        .collect()
+}
+
+/// Escape '"', '\' and ']' in SD parameter values
+fn escape_param(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '"' | '\\' | ']') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// Prefix a message with its length (RFC 6587 octet counting)
+fn frame_octet_counted(message: &str) -> Vec<u8> {
+    format!("{} {}", message.len(), message).into_bytes()
+}
+
+/// Map tracing levels to syslog severities
+fn severity(level: &Level) -> u8 {
+    match *level {
+        Level::ERROR => 3,
+        Level::WARN => 4,
+        Level::INFO => 6,
+        Level::DEBUG | Level::TRACE => 7,
+    }
+}
+
+/// Map facility names to codes
+fn facility_code(name: &str) -> Option<u8> {
+    let code = match name.to_lowercase().as_str() {
+        "kern" => 0,
+        "user" => 1,
+        "mail" => 2,
+        "daemon" => 3,
+        "auth" => 4,
+        "syslog" => 5,
+        "lpr" => 6,
+        "news" => 7,
+        "uucp" => 8,
+        "cron" => 9,
+        "authpriv" => 10,
+        "ftp" => 11,
+        "local0" => 16,
+        "local1" => 17,
+        "local2" => 18,
+        "local3" => 19,
+        "local4" => 20,
+        "local5" => 21,
+        "local6" => 22,
+        "local7" => 23,
+        _ => return None,
+    };
+    Some(code)
+}
+
+fn local_hostname() -> String {
+    std::env::var("HOSTNAME")
+        .ok()
+        .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+        .map(|h| h.trim().to_string())
+        .filter(|h| !h.is_empty())
+        .unwrap_or_else(|| "-".to_string())
+}
+
+/// Connection settings for the sender thread
+enum Transport {
+    Udp {
+        addr: String,
+    },
+    Tcp {
+        addr: String,
+    },
+    #[cfg(feature = "tls")]
+    Tls {
+        addr: String,
+        server_name: rustls::pki_types::ServerName<'static>,
+        tls_config: Arc<rustls::ClientConfig>,
+    },
+}
+
+impl Transport {
+    fn new(config: &SyslogConfig) -> Result<Self> {
+        let port = config.port.unwrap_or(match config.transport {
+            SyslogTransport::Udp | SyslogTransport::Tcp => 514,
+            SyslogTransport::Tls => 6514,
+        });
+        let addr = format!("{}:{}", config.host, port);
+
+        Ok(match config.transport {
+            SyslogTransport::Udp => Transport::Udp { addr },
+            SyslogTransport::Tcp => Transport::Tcp { addr },
+            #[cfg(not(feature = "tls"))]
+            SyslogTransport::Tls => {
+                anyhow::bail!("Syslog TLS support not compiled in (enable the `tls` feature)")
+            }
+            #[cfg(feature = "tls")]
+            SyslogTransport::Tls => {
+                let server_name = rustls::pki_types::ServerName::try_from(config.host.clone())
+                    .with_context(|| format!("Invalid syslog TLS host: {}", config.host))?;
+                Transport::Tls {
+                    addr,
+                    server_name,
+                    tls_config: Arc::new(tls_client_config(config.ca_cert.as_deref())?),
+                }
+            }
+        })
+    }
+
+    fn connect(&self) -> std::io::Result<Connection> {
+        match self {
+            Transport::Udp { addr } => {
+                let socket = UdpSocket::bind("0.0.0.0:0")?;
+                socket.connect(addr)?;
+                Ok(Connection::Udp(socket))
+            }
+            Transport::Tcp { addr } => Ok(Connection::Tcp(TcpStream::connect(addr)?)),
+            #[cfg(feature = "tls")]
+            Transport::Tls {
+                addr,
+                server_name,
+                tls_config,
+            } => {
+                let stream = TcpStream::connect(addr)?;
+                let conn = rustls::ClientConnection::new(tls_config.clone(), server_name.clone())
+                    .map_err(std::io::Error::other)?;
+                Ok(Connection::Tls(Box::new(rustls::StreamOwned::new(
+                    conn, stream,
+                ))))
+            }
+        }
+    }
+}
+
+/// Build a TLS client config trusting the given CA file or the system roots
+#[cfg(feature = "tls")]
+pub(crate) fn tls_client_config(ca_cert: Option<&str>) -> Result<rustls::ClientConfig> {
+    Ok(rustls::ClientConfig::builder()
+        .with_root_certificates(root_store(ca_cert)?)
+        .with_no_client_auth())
+}
+
+/// Certificates of the given CA file, or the system roots
+#[cfg(feature = "tls")]
+pub(crate) fn root_store(ca_cert: Option<&str>) -> Result<rustls::RootCertStore> {
+    let mut roots = rustls::RootCertStore::empty();
+
+    match ca_cert {
+        Some(path) => {
+            let pem = std::fs::read(path)
+                .with_context(|| format!("Failed to read CA certificate: {}", path))?;
+            for cert in rustls_pemfile::certs(&mut pem.as_slice()) {
+                roots
+                    .add(cert.with_context(|| format!("Invalid certificate in {}", path))?)
+                    .with_context(|| format!("Invalid certificate in {}", path))?;
+            }
+        }
+        None => {
+            for cert in rustls_native_certs::load_native_certs()
+                .with_context(|| "Failed to load system root certificates")?
+            {
+                let _ = roots.add(cert);
+            }
+        }
+    }
+
+    Ok(roots)
+}
+
+/// An open connection to the collector
+enum Connection {
+    Udp(UdpSocket),
+    Tcp(TcpStream),
+    #[cfg(feature = "tls")]
+    Tls(Box<rustls::StreamOwned<rustls::ClientConnection, TcpStream>>),
+}
+
+impl Connection {
+    fn send(&mut self, frame: &[u8]) -> std::io::Result<()> {
+        match self {
+            Connection::Udp(socket) => socket.send(frame).map(|_| ()),
+            Connection::Tcp(stream) => stream.write_all(frame),
+            #[cfg(feature = "tls")]
+            Connection::Tls(stream) => {
+                stream.write_all(frame)?;
+                stream.flush()
+            }
+        }
+    }
+}
+
+/// Sender thread: (re)connects lazily and drains the queue
+fn run_sender(transport: Transport, rx: Receiver<Vec<u8>>) {
+    let mut connection: Option<Connection> = None;
+
+    while let Ok(frame) = rx.recv() {
+        if connection.is_none() {
+            match transport.connect() {
+                Ok(conn) => connection = Some(conn),
+                Err(e) => {
+                    eprintln!("syslog: failed to connect to collector: {}", e);
+                    std::thread::sleep(RECONNECT_DELAY);
+                    continue;
+                }
+            }
+        }
+
+        if let Some(conn) = connection.as_mut() {
+            if let Err(e) = conn.send(&frame) {
+                eprintln!("syslog: failed to send message: {}", e);
+                connection = None;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn header() -> Header<'static> {
+        Header {
+            facility: 16,
+            severity: 3,
+            timestamp: chrono::Utc
+                .with_ymd_and_hms(2025, 1, 15, 10, 30, 0)
+                .unwrap(),
+            hostname: "gateway-01",
+            app_name: "rustbridge",
+            proc_id: "1234",
+            msg_id: "rustbridge::bridge",
+        }
+    }
+
+    #[test]
+    fn test_format_without_fields() {
+        let msg = format_message(&header(), &[], "Bridge started");
+        assert_eq!(
+            msg,
+            "<131>1 2025-01-15T10:30:00.000000Z gateway-01 rustbridge 1234 rustbridge::bridge - Bridge started"
+        );
+    }
+
+    #[test]
+    fn test_format_with_structured_data() {
+        let fields = vec![
+            ("device_id".to_string(), "plc-001".to_string()),
+            ("register".to_string(), "temperature".to_string()),
+        ];
+        let msg = format_message(&header(), &fields, "Read failed");
+        assert!(msg.ends_with(
+            "[rustbridge@32473 device_id=\"plc-001\" register=\"temperature\"] Read failed"
+        ));
+    }
+
+    #[test]
+    fn test_escape_param() {
+        assert_eq!(escape_param(r#"a"b\c]d"#), r#"a\"b\\c\]d"#);
+        assert_eq!(escape_param("plain"), "plain");
+    }
+
+    #[test]
+    fn test_header_field_sanitized() {
+        assert_eq!(header_field("", 48), "-");
+        assert_eq!(header_field("my host", 255), "myhost");
+        assert_eq!(header_field("abcdef", 3), "abc");
+    }
+
+    #[test]
+    fn test_severity_mapping() {
+        assert_eq!(severity(&Level::ERROR), 3);
+        assert_eq!(severity(&Level::WARN), 4);
+        assert_eq!(severity(&Level::INFO), 6);
+        assert_eq!(severity(&Level::DEBUG), 7);
+        assert_eq!(severity(&Level::TRACE), 7);
+    }
+
+    #[test]
+    fn test_facility_codes() {
+        assert_eq!(facility_code("daemon"), Some(3));
+        assert_eq!(facility_code("LOCAL0"), Some(16));
+        assert_eq!(facility_code("local7"), Some(23));
+        assert_eq!(facility_code("bogus"), None);
+    }
+
+    #[test]
+    fn test_octet_counting_frame() {
+        assert_eq!(
+            frame_octet_counted("<14>1 - - - - - -"),
+            b"17 <14>1 - - - - - -"
+        );
+    }
+
+    #[test]
+    fn test_udp_delivery() {
+        let collector = UdpSocket::bind("127.0.0.1:0").unwrap();
+        collector
+            .set_read_timeout(Some(Duration::from_secs(2)))
+            .unwrap();
+        let port = collector.local_addr().unwrap().port();
+
+        let transport = Transport::new(&SyslogConfig {
+            host: "127.0.0.1".to_string(),
+            port: Some(port),
+            ..Default::default()
+        })
+        .unwrap();
+
+        let mut conn = transport.connect().unwrap();
+        conn.send(b"<14>1 - - - - - - hello").unwrap();
+
+        let mut buf = [0u8; 128];
+        let n = collector.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..n], b"<14>1 - - - - - - hello");
+    }
+}
@@ -3,41 +3,114 @@
 //! High-performance Modbus TCP/RTU to JSON/MQTT gateway
 //! Built with Rust for Industry 4.0 edge deployments
 
-use anyhow::Result;
-use tracing::{info, Level};
-use tracing_subscriber::FmtSubscriber;
-
-mod api;
-mod bridge;
-mod config;
-mod metrics;
-mod modbus;
-mod mqtt;
-
-#[tokio::main]
-async fn main() -> Result<()> {
+use anyhow::{bail, Result};
+use clap::Parser;
+use tracing::info;
+
+use rustbridge::{bridge, config, daemon, logging};
+
+mod cli;
+
+fn main() -> Result<()> {
+    let cli = cli::Cli::parse();
+
+    // Forking has to happen before the async runtime starts its threads
+    let _pid_file = if cli.daemon {
+        if !matches!(cli.command, None | Some(cli::Command::Run)) {
+            bail!("--daemon only applies to running the bridge");
+        }
+        // Fail on the terminal rather than silently in the background
+        let config = config::load_config(&cli.config)?;
+        config.validate()?;
+        daemon::check_not_running(std::path::Path::new(&config.daemon.pid_file))?;
+        daemon::daemonize()?;
+        Some(daemon::PidFile::create(&config.daemon.pid_file)?)
+    } else {
+        None
+    };
+
+    // Runtime sizing comes from the config of the bridge; other commands and
+    // invalid configs (reported once the runtime is up) use the defaults
+    let settings = match cli.command {
+        None | Some(cli::Command::Run) if !cli.dry_run => config::load_config(&cli.config)
+            .ok()
+            .filter(|config| config.validate().is_ok())
+            .map(|config| config.runtime)
+            .unwrap_or_default(),
+        _ => config::RuntimeConfig::default(),
+    };
+
+    build_runtime(&settings)?.block_on(run(cli))
+}
+
+/// Build the async runtime with the configured sizing
+fn build_runtime(settings: &config::RuntimeConfig) -> std::io::Result<tokio::runtime::Runtime> {
+    let mut builder = match settings.worker_threads {
+        Some(1) => tokio::runtime::Builder::new_current_thread(),
+        Some(threads) => {
+            let mut builder = tokio::runtime::Builder::new_multi_thread();
+            builder.worker_threads(threads);
+            builder
+        }
+        None => tokio::runtime::Builder::new_multi_thread(),
+    };
+    if let Some(threads) = settings.max_blocking_threads {
+        builder.max_blocking_threads(threads);
+    }
+    if let Some(size) = settings.thread_stack_size {
+        builder.thread_stack_size(size);
+    }
+    builder.enable_all().build()
+}
+
+async fn run(cli: cli::Cli) -> Result<()> {
+    if cli.dry_run {
+        let code = cli::dry_run::run(&cli.config).await?;
+        std::process::exit(code);
+    }
+
+    match cli.command {
+        Some(cli::Command::Read(args)) => cli::read::run(&cli.config, args).await,
+        Some(cli::Command::Scan(args)) => cli::scan::run(args).await,
+        #[cfg(feature = "monitor")]
+        Some(cli::Command::Monitor(args)) => cli::monitor::run(&cli.config, args).await,
+        #[cfg(feature = "rtu")]
+        Some(cli::Command::Ports(args)) => cli::ports::run(args).await,
+        Some(cli::Command::Bench(args)) => cli::bench::run(&cli.config, args).await,
+        #[cfg(feature = "api-client")]
+        Some(cli::Command::Snapshot(args)) => cli::snapshot::run(&cli.config, args).await,
+        Some(cli::Command::Run) | None => run_bridge(&cli.config).await,
+    }
+}
+
+/// Run the bridge until it exits
+async fn run_bridge(config_path: &str) -> Result<()> {
+    // Load configuration (logging settings live in the config file)
+    let config = config::load_config(config_path)?;
+    config.validate()?;
+
     // Initialize logging
-    FmtSubscriber::builder()
-        .with_max_level(Level::INFO)
-        .with_target(false)
-        .with_thread_ids(true)
-        .with_file(true)
-        .with_line_number(true)
-        .init();
+    logging::init(&config.logging)?;
+    reopen_logs_on_sigusr1();
 
     print_banner();
 
     info!("Starting RustBridge v{}", env!("CARGO_PKG_VERSION"));
-
-    // Load configuration
-    let config = config::load_config()?;
     info!(
         "Configuration loaded: {} devices configured",
         config.devices.len()
     );
 
+    // Count the start of a newly installed release, or roll back one that
+    // did not survive its health check
+    if config.update.is_some() {
+        rustbridge::update::check_pending()?;
+    }
+
     // Initialize bridge
-    let bridge = bridge::Bridge::new(config).await?;
+    let bridge = bridge::Bridge::new(config)
+        .await?
+        .with_config_path(config_path);
 
     // Start the bridge
     bridge.run().await?;
@@ -45,6 +118,26 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
+/// Reopen the log file on SIGUSR1 so logrotate can move it away
+fn reopen_logs_on_sigusr1() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let Ok(mut signals) = signal(SignalKind::user_defined1()) else {
+            return;
+        };
+        tokio::spawn(async move {
+            while signals.recv().await.is_some() {
+                match logging::reopen() {
+                    Ok(()) => info!("Log file reopened"),
+                    Err(e) => tracing::error!(error = %e, "Failed to reopen log file: {:#}", e),
+                }
+            }
+        });
+    }
+}
+
 fn print_banner() {
     println!(
         r#"
@@ -0,0 +1,151 @@
+//! RTU bus quality statistics
+//!
+//! Counts CRC errors, framing errors, timeouts and retransmissions per serial
+//! port, so deteriorating wiring shows up before devices drop off the bus.
+//! Devices sharing a port share its counters. The counters are process-wide,
+//! like the Prometheus metrics they are mirrored to.
+
+use metrics::counter;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// Counters of a single RTU bus
+#[derive(Debug, Default)]
+pub struct BusCounters {
+    port: String,
+    frames_ok: AtomicU64,
+    crc_errors: AtomicU64,
+    framing_errors: AtomicU64,
+    timeouts: AtomicU64,
+    retransmissions: AtomicU64,
+    bytes_sent: AtomicU64,
+    bytes_received: AtomicU64,
+}
+
+impl BusCounters {
+    fn new(port: &str) -> Self {
+        Self {
+            port: port.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// A response frame with a valid CRC was received
+    pub fn record_frame_ok(&self) {
+        self.frames_ok.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// A response frame failed its CRC check
+    pub fn record_crc_error(&self) {
+        self.crc_errors.fetch_add(1, Ordering::Relaxed);
+        counter!("rustbridge_rtu_crc_errors_total", "bus" => self.port.clone()).increment(1);
+    }
+
+    /// A response could not be framed (unknown function code, garbage bytes)
+    pub fn record_framing_error(&self) {
+        self.framing_errors.fetch_add(1, Ordering::Relaxed);
+        counter!("rustbridge_rtu_framing_errors_total", "bus" => self.port.clone()).increment(1);
+    }
+
+    /// A request got no (valid) response in time
+    pub fn record_timeout(&self) {
+        self.timeouts.fetch_add(1, Ordering::Relaxed);
+        counter!("rustbridge_rtu_timeouts_total", "bus" => self.port.clone()).increment(1);
+    }
+
+    /// A request was sent again after a failed attempt
+    pub fn record_retransmission(&self) {
+        self.retransmissions.fetch_add(1, Ordering::Relaxed);
+        counter!("rustbridge_rtu_retransmissions_total", "bus" => self.port.clone()).increment(1);
+    }
+
+    /// Bytes written to the port
+    pub fn record_sent(&self, bytes: usize) {
+        self.bytes_sent.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// Bytes read from the port
+    pub fn record_received(&self, bytes: usize) {
+        self.bytes_received
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// Current counter values
+    pub fn snapshot(&self) -> BusStats {
+        BusStats {
+            frames_ok: self.frames_ok.load(Ordering::Relaxed),
+            crc_errors: self.crc_errors.load(Ordering::Relaxed),
+            framing_errors: self.framing_errors.load(Ordering::Relaxed),
+            timeouts: self.timeouts.load(Ordering::Relaxed),
+            retransmissions: self.retransmissions.load(Ordering::Relaxed),
+            bytes_sent: self.bytes_sent.load(Ordering::Relaxed),
+            bytes_received: self.bytes_received.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Statistics of an RTU bus as reported by `/api/diagnostics`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct BusStats {
+    /// Response frames received with a valid CRC
+    pub frames_ok: u64,
+    /// Response frames with a CRC mismatch
+    pub crc_errors: u64,
+    /// Responses that could not be framed
+    pub framing_errors: u64,
+    /// Requests without a valid response in time
+    pub timeouts: u64,
+    /// Requests sent again after a timeout or corrupt response
+    pub retransmissions: u64,
+    /// Bytes written to the port
+    pub bytes_sent: u64,
+    /// Bytes read from the port
+    pub bytes_received: u64,
+}
+
+fn registry() -> &'static Mutex<HashMap<String, Arc<BusCounters>>> {
+    static BUSES: OnceLock<Mutex<HashMap<String, Arc<BusCounters>>>> = OnceLock::new();
+    BUSES.get_or_init(Default::default)
+}
+
+/// Counters of the bus on `port`, created on first use
+pub fn bus(port: &str) -> Arc<BusCounters> {
+    let mut buses = registry().lock().unwrap_or_else(|e| e.into_inner());
+    buses
+        .entry(port.to_string())
+        .or_insert_with(|| Arc::new(BusCounters::new(port)))
+        .clone()
+}
+
+/// Statistics of every bus opened so far, keyed by serial port
+pub fn snapshot() -> BTreeMap<String, BusStats> {
+    let buses = registry().lock().unwrap_or_else(|e| e.into_inner());
+    buses
+        .iter()
+        .map(|(port, counters)| (port.clone(), counters.snapshot()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counters_are_shared_per_port() {
+        let a = bus("/dev/test-bus-shared");
+        let b = bus("/dev/test-bus-shared");
+        a.record_crc_error();
+        b.record_timeout();
+        b.record_retransmission();
+        a.record_sent(8);
+
+        let stats = &snapshot()["/dev/test-bus-shared"];
+        assert_eq!(stats.crc_errors, 1);
+        assert_eq!(stats.timeouts, 1);
+        assert_eq!(stats.retransmissions, 1);
+        assert_eq!(stats.bytes_sent, 8);
+        assert_eq!(stats.framing_errors, 0);
+    }
+}
@@ -0,0 +1,578 @@
+//! Per-device poll diagnostics
+//!
+//! Keeps running poll statistics per device and per register
+//! (latency, success/error counts, consecutive failure streaks)
+//! for the `/api/diagnostics` endpoint, and the timing of the most recent
+//! poll cycles for `/api/devices/{id}/timing`.
+//!
+//! A rolling health score sums up each device over its last
+//! [`HEALTH_WINDOW`] poll cycles:
+//! `100 × success ratio − 20 × overrun ratio − 5 × reconnects`, between 0
+//! and 100. From 80 a device is healthy, from 40 degraded, below that failed.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+/// Shared diagnostics state, keyed by device ID
+pub type DiagnosticsStore = Arc<RwLock<HashMap<String, DeviceDiagnostics>>>;
+
+/// Poll cycles kept per device for the timing report
+pub const TIMING_HISTORY: usize = 50;
+
+/// Poll cycles and connection attempts the health score looks back on
+pub const HEALTH_WINDOW: usize = 20;
+
+/// Poll statistics for a single device
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DeviceDiagnostics {
+    /// Completed poll cycles
+    pub poll_cycles: u64,
+    /// Duration of the last poll cycle in milliseconds
+    pub last_cycle_ms: u64,
+    /// Longest poll cycle seen in milliseconds
+    pub max_cycle_ms: u64,
+    /// Configured poll interval in milliseconds
+    pub poll_interval_ms: u64,
+    /// Actual time between the last two poll cycle starts in milliseconds
+    pub achieved_interval_ms: u64,
+    /// Poll cycles that took longer than the poll interval
+    pub overruns: u64,
+    /// Successful register reads
+    pub reads_ok: u64,
+    /// Failed register reads
+    pub reads_failed: u64,
+    /// Consecutive failed reads across all registers of the device
+    pub consecutive_failures: u64,
+    /// Per-register statistics
+    pub registers: HashMap<String, RegisterDiagnostics>,
+    /// Timing of the most recent poll cycles, oldest first
+    #[serde(skip)]
+    pub recent_cycles: VecDeque<CycleTiming>,
+    /// Rolling health over the last [`HEALTH_WINDOW`] cycles
+    pub health: DeviceHealth,
+    #[serde(skip)]
+    health_window: VecDeque<CycleHealth>,
+}
+
+/// Health state of a device, from its score
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthState {
+    /// Score of 80 or more
+    #[default]
+    Healthy,
+    /// Score of 40 or more
+    Degraded,
+    /// Score below 40
+    Failed,
+}
+
+impl std::fmt::Display for HealthState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            HealthState::Healthy => "healthy",
+            HealthState::Degraded => "degraded",
+            HealthState::Failed => "failed",
+        })
+    }
+}
+
+/// What one poll cycle, or failed connection attempt, says about a device
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CycleHealth {
+    pub reads_ok: u32,
+    /// Failed reads; a failed connection attempt counts as one
+    pub reads_failed: u32,
+    /// Summed round trip of the reads in milliseconds
+    pub latency_ms: f64,
+    /// Whether the cycle took longer than the poll interval
+    pub overran: bool,
+    /// Whether the cycle had to (re)connect after losing the connection
+    pub reconnect: bool,
+}
+
+/// Rolling health of a device
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DeviceHealth {
+    /// 0 (failing) to 100 (fine)
+    pub score: f64,
+    pub state: HealthState,
+    /// Share of reads that succeeded
+    pub success_ratio: f64,
+    /// Mean read round trip in milliseconds
+    pub latency_ms: f64,
+    /// Share of cycles that took longer than the poll interval
+    pub overrun_ratio: f64,
+    /// Reconnects within the window
+    pub reconnects: u32,
+}
+
+impl Default for DeviceHealth {
+    fn default() -> Self {
+        Self {
+            score: 100.0,
+            state: HealthState::Healthy,
+            success_ratio: 1.0,
+            latency_ms: 0.0,
+            overrun_ratio: 0.0,
+            reconnects: 0,
+        }
+    }
+}
+
+impl DeviceHealth {
+    fn of(window: &VecDeque<CycleHealth>) -> Self {
+        if window.is_empty() {
+            return Self::default();
+        }
+        let ok: u32 = window.iter().map(|c| c.reads_ok).sum();
+        let reads = ok + window.iter().map(|c| c.reads_failed).sum::<u32>();
+        let latency: f64 = window.iter().map(|c| c.latency_ms).sum();
+        let overruns = window.iter().filter(|c| c.overran).count();
+        let reconnects = window.iter().filter(|c| c.reconnect).count() as u32;
+
+        let success_ratio = if reads == 0 {
+            1.0
+        } else {
+            ok as f64 / reads as f64
+        };
+        let overrun_ratio = overruns as f64 / window.len() as f64;
+        let score = (100.0 * success_ratio - 20.0 * overrun_ratio - 5.0 * reconnects as f64)
+            .clamp(0.0, 100.0);
+        let state = if score >= 80.0 {
+            HealthState::Healthy
+        } else if score >= 40.0 {
+            HealthState::Degraded
+        } else {
+            HealthState::Failed
+        };
+        Self {
+            score,
+            state,
+            success_ratio,
+            latency_ms: if reads == 0 {
+                0.0
+            } else {
+                latency / reads as f64
+            },
+            overrun_ratio,
+            reconnects,
+        }
+    }
+}
+
+/// Where the time of one poll cycle went
+#[derive(Debug, Clone, Serialize)]
+pub struct CycleTiming {
+    /// When the cycle started
+    pub started_at: chrono::DateTime<chrono::Utc>,
+    /// Duration of the whole cycle in milliseconds
+    pub duration_ms: f64,
+    /// How long after its scheduled tick the cycle started, in milliseconds
+    /// (0 for cycles triggered by poll-now)
+    pub queue_wait_ms: f64,
+    /// Time spent on writes queued ahead of the cycle, in milliseconds
+    pub writes_ms: f64,
+    /// Whether the cycle took longer than the poll interval
+    pub overran: bool,
+    /// Registers read during the cycle, in request order
+    pub registers: Vec<RegisterTiming>,
+}
+
+/// Timing of one register read within a poll cycle
+#[derive(Debug, Clone, Serialize)]
+pub struct RegisterTiming {
+    pub name: String,
+    /// Time from the cycle start until the request was sent, in milliseconds
+    pub wait_ms: f64,
+    /// Request round trip in milliseconds
+    pub latency_ms: f64,
+    /// Whether the read succeeded
+    pub ok: bool,
+}
+
+/// Poll statistics for a single register
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RegisterDiagnostics {
+    /// Successful reads
+    pub reads_ok: u64,
+    /// Failed reads
+    pub reads_failed: u64,
+    /// Current streak of consecutive failed reads
+    pub consecutive_failures: u64,
+    /// Longest streak of consecutive failed reads
+    pub max_consecutive_failures: u64,
+    /// Duration of the last read in milliseconds
+    pub last_duration_ms: f64,
+    /// Average read duration in milliseconds
+    pub avg_duration_ms: f64,
+    /// Longest read duration in milliseconds
+    pub max_duration_ms: f64,
+    /// Last error message, if any
+    pub last_error: Option<String>,
+    /// Timestamp of the last successful read
+    pub last_success: Option<chrono::DateTime<chrono::Utc>>,
+    /// Timestamp of the last failed read
+    pub last_failure: Option<chrono::DateTime<chrono::Utc>>,
+    /// Whether the register is skipped after repeated identical exceptions
+    pub circuit: CircuitState,
+    /// When an open circuit lets the next probe read through
+    pub retry_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// Circuit breaker state of a register
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CircuitState {
+    /// Read every cycle
+    #[default]
+    Closed,
+    /// Skipped until `retry_at`
+    Open,
+}
+
+impl RegisterDiagnostics {
+    fn record_duration(&mut self, duration_ms: f64) {
+        let samples = self.reads_ok + self.reads_failed;
+        self.last_duration_ms = duration_ms;
+        self.max_duration_ms = self.max_duration_ms.max(duration_ms);
+        self.avg_duration_ms += (duration_ms - self.avg_duration_ms) / samples as f64;
+    }
+}
+
+impl DeviceDiagnostics {
+    /// Record a successful register read and return the register's failure streak (0)
+    pub fn record_success(&mut self, register: &str, duration_ms: f64) -> u64 {
+        self.reads_ok += 1;
+        self.consecutive_failures = 0;
+
+        let reg = self.registers.entry(register.to_string()).or_default();
+        reg.reads_ok += 1;
+        reg.consecutive_failures = 0;
+        reg.last_success = Some(chrono::Utc::now());
+        reg.record_duration(duration_ms);
+
+        reg.consecutive_failures
+    }
+
+    /// Record a failed register read and return the register's failure streak
+    pub fn record_failure(&mut self, register: &str, duration_ms: f64, error: &str) -> u64 {
+        self.reads_failed += 1;
+        self.consecutive_failures += 1;
+
+        let reg = self.registers.entry(register.to_string()).or_default();
+        reg.reads_failed += 1;
+        reg.consecutive_failures += 1;
+        reg.max_consecutive_failures = reg.max_consecutive_failures.max(reg.consecutive_failures);
+        reg.last_error = Some(error.to_string());
+        reg.last_failure = Some(chrono::Utc::now());
+        reg.record_duration(duration_ms);
+
+        reg.consecutive_failures
+    }
+
+    /// Record a completed poll cycle; returns true if it overran the poll interval
+    pub fn record_cycle(&mut self, duration_ms: u64, poll_interval_ms: u64) -> bool {
+        self.poll_cycles += 1;
+        self.last_cycle_ms = duration_ms;
+        self.max_cycle_ms = self.max_cycle_ms.max(duration_ms);
+        self.poll_interval_ms = poll_interval_ms;
+
+        let overran = duration_ms > poll_interval_ms;
+        if overran {
+            self.overruns += 1;
+        }
+        overran
+    }
+
+    /// Keep the timing of a completed poll cycle, dropping the oldest beyond [`TIMING_HISTORY`]
+    pub fn record_timing(&mut self, timing: CycleTiming) {
+        if self.recent_cycles.len() == TIMING_HISTORY {
+            self.recent_cycles.pop_front();
+        }
+        self.recent_cycles.push_back(timing);
+    }
+
+    /// Open a register's circuit until `retry_at`, or close it with `None`
+    pub fn set_circuit(&mut self, register: &str, retry_at: Option<chrono::DateTime<chrono::Utc>>) {
+        let reg = self.registers.entry(register.to_string()).or_default();
+        reg.circuit = match retry_at {
+            Some(_) => CircuitState::Open,
+            None => CircuitState::Closed,
+        };
+        reg.retry_at = retry_at;
+    }
+
+    /// Record the actual time between two consecutive cycle starts
+    pub fn record_achieved_interval(&mut self, interval_ms: u64) {
+        self.achieved_interval_ms = interval_ms;
+    }
+
+    /// Add a cycle to the health window and rescore, returning the previous
+    /// state if the device's state changed
+    pub fn record_health(&mut self, cycle: CycleHealth) -> Option<HealthState> {
+        if self.health_window.len() == HEALTH_WINDOW {
+            self.health_window.pop_front();
+        }
+        self.health_window.push_back(cycle);
+        let previous = self.health.state;
+        self.health = DeviceHealth::of(&self.health_window);
+        (self.health.state != previous).then_some(previous)
+    }
+}
+
+/// Poll statistics of a device over its last few cycles, published on
+/// `{prefix}/{device_id}/$stats`
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct CycleStats {
+    #[serde(skip)]
+    pub device_id: String,
+    /// Poll cycles covered
+    pub cycles: u32,
+    /// Mean cycle duration in milliseconds
+    pub cycle_ms: f64,
+    /// Longest cycle duration in milliseconds
+    pub cycle_ms_max: f64,
+    /// Successful register reads
+    pub reads_ok: u64,
+    /// Failed register reads
+    pub reads_failed: u64,
+    /// Modbus frame bytes sent
+    pub bytes_sent: u64,
+    /// Modbus frame bytes received
+    pub bytes_received: u64,
+}
+
+/// Requests of a single poll cycle
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CycleTraffic {
+    pub reads_ok: u64,
+    pub reads_failed: u64,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+}
+
+impl CycleTraffic {
+    /// Count a request with its `(sent, received)` frame sizes; a failed
+    /// request only counts what was sent
+    pub fn record(&mut self, (sent, received): (u64, u64), ok: bool) {
+        self.bytes_sent += sent;
+        if ok {
+            self.reads_ok += 1;
+            self.bytes_received += received;
+        } else {
+            self.reads_failed += 1;
+        }
+    }
+}
+
+/// Sums up the poll cycles of a device into a [`CycleStats`] every `every` cycles
+#[derive(Debug)]
+pub struct CycleStatsWindow {
+    every: u32,
+    stats: CycleStats,
+    total_ms: f64,
+}
+
+impl CycleStatsWindow {
+    pub fn new(device_id: &str, every: u32) -> Self {
+        Self {
+            every: every.max(1),
+            stats: CycleStats {
+                device_id: device_id.to_string(),
+                ..Default::default()
+            },
+            total_ms: 0.0,
+        }
+    }
+
+    /// Add a completed cycle, returning the statistics when the window is full
+    pub fn record(&mut self, duration_ms: f64, traffic: CycleTraffic) -> Option<CycleStats> {
+        let stats = &mut self.stats;
+        stats.cycles += 1;
+        stats.cycle_ms_max = stats.cycle_ms_max.max(duration_ms);
+        stats.reads_ok += traffic.reads_ok;
+        stats.reads_failed += traffic.reads_failed;
+        stats.bytes_sent += traffic.bytes_sent;
+        stats.bytes_received += traffic.bytes_received;
+        self.total_ms += duration_ms;
+        if stats.cycles < self.every {
+            return None;
+        }
+        let mut full = std::mem::replace(
+            stats,
+            CycleStats {
+                device_id: stats.device_id.clone(),
+                ..Default::default()
+            },
+        );
+        full.cycle_ms = self.total_ms / f64::from(full.cycles);
+        self.total_ms = 0.0;
+        Some(full)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_success_resets_streak() {
+        let mut diag = DeviceDiagnostics::default();
+
+        assert_eq!(diag.record_failure("temp", 10.0, "timeout"), 1);
+        assert_eq!(diag.record_failure("temp", 10.0, "timeout"), 2);
+        assert_eq!(diag.consecutive_failures, 2);
+
+        assert_eq!(diag.record_success("temp", 5.0), 0);
+        assert_eq!(diag.consecutive_failures, 0);
+
+        let reg = &diag.registers["temp"];
+        assert_eq!(reg.reads_ok, 1);
+        assert_eq!(reg.reads_failed, 2);
+        assert_eq!(reg.consecutive_failures, 0);
+        assert_eq!(reg.max_consecutive_failures, 2);
+        assert_eq!(reg.last_error, Some("timeout".to_string()));
+        assert!(reg.last_success.is_some());
+    }
+
+    #[test]
+    fn test_duration_statistics() {
+        let mut diag = DeviceDiagnostics::default();
+
+        diag.record_success("temp", 10.0);
+        diag.record_success("temp", 20.0);
+        diag.record_success("temp", 30.0);
+
+        let reg = &diag.registers["temp"];
+        assert_eq!(reg.last_duration_ms, 30.0);
+        assert_eq!(reg.max_duration_ms, 30.0);
+        assert!((reg.avg_duration_ms - 20.0).abs() < 0.0001);
+    }
+
+    #[test]
+    fn test_cycle_statistics() {
+        let mut diag = DeviceDiagnostics::default();
+
+        diag.record_cycle(150, 1000);
+        diag.record_cycle(90, 1000);
+
+        assert_eq!(diag.poll_cycles, 2);
+        assert_eq!(diag.last_cycle_ms, 90);
+        assert_eq!(diag.max_cycle_ms, 150);
+    }
+
+    #[test]
+    fn test_overrun_detection() {
+        let mut diag = DeviceDiagnostics::default();
+
+        assert!(!diag.record_cycle(400, 500));
+        assert_eq!(diag.overruns, 0);
+
+        assert!(diag.record_cycle(750, 500));
+        diag.record_achieved_interval(750);
+        assert_eq!(diag.overruns, 1);
+        assert_eq!(diag.achieved_interval_ms, 750);
+        assert_eq!(diag.poll_interval_ms, 500);
+    }
+
+    #[test]
+    fn test_serialization() {
+        let mut diag = DeviceDiagnostics::default();
+        diag.record_failure("pressure", 3000.0, "Modbus error: timeout");
+
+        let json = serde_json::to_value(&diag).unwrap();
+        assert_eq!(json["reads_failed"], 1);
+        assert_eq!(json["registers"]["pressure"]["consecutive_failures"], 1);
+    }
+
+    #[test]
+    fn test_timing_history() {
+        let mut diag = DeviceDiagnostics::default();
+        for i in 0..TIMING_HISTORY + 5 {
+            diag.record_timing(CycleTiming {
+                started_at: chrono::Utc::now(),
+                duration_ms: i as f64,
+                queue_wait_ms: 0.0,
+                writes_ms: 0.0,
+                overran: false,
+                registers: vec![],
+            });
+        }
+
+        assert_eq!(diag.recent_cycles.len(), TIMING_HISTORY);
+        assert_eq!(diag.recent_cycles.front().unwrap().duration_ms, 5.0);
+        // The history stays out of /api/diagnostics
+        assert!(serde_json::to_value(&diag)
+            .unwrap()
+            .get("recent_cycles")
+            .is_none());
+    }
+
+    #[test]
+    fn test_cycle_stats_window() {
+        let mut window = CycleStatsWindow::new("plc-1", 2);
+        let mut traffic = CycleTraffic::default();
+        traffic.record((8, 9), true);
+        traffic.record((8, 9), false);
+
+        assert_eq!(window.record(10.0, traffic), None);
+        let stats = window.record(30.0, traffic).unwrap();
+        assert_eq!(stats.device_id, "plc-1");
+        assert_eq!(stats.cycles, 2);
+        assert_eq!(stats.cycle_ms, 20.0);
+        assert_eq!(stats.cycle_ms_max, 30.0);
+        assert_eq!((stats.reads_ok, stats.reads_failed), (2, 2));
+        assert_eq!((stats.bytes_sent, stats.bytes_received), (32, 18));
+
+        // The next window starts empty
+        assert_eq!(window.record(5.0, CycleTraffic::default()), None);
+        assert_eq!(
+            window
+                .record(5.0, CycleTraffic::default())
+                .unwrap()
+                .cycle_ms_max,
+            5.0
+        );
+    }
+
+    #[test]
+    fn test_health_score() {
+        let mut diag = DeviceDiagnostics::default();
+        let cycle = |ok, failed| CycleHealth {
+            reads_ok: ok,
+            reads_failed: failed,
+            latency_ms: 10.0 * (ok + failed) as f64,
+            ..Default::default()
+        };
+        for _ in 0..HEALTH_WINDOW {
+            assert_eq!(diag.record_health(cycle(4, 0)), None);
+        }
+        assert_eq!(diag.health.score, 100.0);
+        assert_eq!(diag.health.latency_ms, 10.0);
+
+        // A quarter of the reads failing degrades the device
+        for _ in 0..HEALTH_WINDOW / 2 {
+            diag.record_health(cycle(2, 2));
+        }
+        assert_eq!(diag.health.success_ratio, 0.75);
+        assert_eq!(diag.health.state, HealthState::Degraded);
+
+        // Failed connection attempts fill the window and fail it
+        let mut changes = vec![];
+        for _ in 0..HEALTH_WINDOW {
+            let attempt = CycleHealth {
+                reads_failed: 1,
+                reconnect: true,
+                ..Default::default()
+            };
+            changes.extend(diag.record_health(attempt));
+        }
+        assert_eq!(changes, [HealthState::Degraded]);
+        assert_eq!(diag.health.state, HealthState::Failed);
+        assert_eq!(diag.health.score, 0.0);
+        assert_eq!(diag.health.reconnects, HEALTH_WINDOW as u32);
+    }
+}
@@ -0,0 +1,284 @@
+//! In-memory log of recent error events
+//!
+//! Keeps the last N errors (Modbus exceptions, timeouts, transport and
+//! MQTT failures) with timestamps and context, served at `GET /api/errors`
+//! so commissioning doesn't require grepping container logs.
+
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+
+use super::memory::{MemorySize, RingBuffer};
+use crate::error::RustbridgeError;
+
+/// Default number of error events kept in memory
+pub const DEFAULT_CAPACITY: usize = 100;
+
+/// Subsystem an error originated from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ErrorSource {
+    Modbus,
+    Mqtt,
+}
+
+/// Error category
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ErrorKind {
+    /// Modbus exception response from the device
+    Exception,
+    /// Request timed out
+    Timeout,
+    /// Connection could not be established
+    Connection,
+    /// Transport/IO failure on an established connection
+    Transport,
+    /// MQTT publish failure
+    Publish,
+}
+
+impl ErrorKind {
+    /// Classify a failed request on an established connection
+    pub fn classify(error: &RustbridgeError) -> Self {
+        match error {
+            RustbridgeError::Exception(_) => ErrorKind::Exception,
+            RustbridgeError::Timeout => ErrorKind::Timeout,
+            RustbridgeError::Publish(_) => ErrorKind::Publish,
+            RustbridgeError::Config(_)
+            | RustbridgeError::Connection(_)
+            | RustbridgeError::InvalidResponse(_) => ErrorKind::Transport,
+        }
+    }
+
+    /// Classify a Modbus error message
+    pub fn classify_modbus(message: &str) -> Self {
+        let lower = message.to_lowercase();
+        if lower.contains("exception") {
+            ErrorKind::Exception
+        } else if lower.contains("timeout") || lower.contains("timed out") {
+            ErrorKind::Timeout
+        } else if lower.contains("connect") {
+            ErrorKind::Connection
+        } else {
+            ErrorKind::Transport
+        }
+    }
+}
+
+/// A single recorded error
+#[derive(Debug, Clone, Serialize)]
+pub struct ErrorEvent {
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    pub source: ErrorSource,
+    pub kind: ErrorKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub device_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub register: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub address: Option<u16>,
+    pub message: String,
+}
+
+impl ErrorEvent {
+    /// Create an event timestamped now
+    pub fn new(source: ErrorSource, kind: ErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            timestamp: chrono::Utc::now(),
+            source,
+            kind,
+            device_id: None,
+            register: None,
+            address: None,
+            message: message.into(),
+        }
+    }
+
+    /// Attach the device ID
+    pub fn device(mut self, device_id: impl Into<String>) -> Self {
+        self.device_id = Some(device_id.into());
+        self
+    }
+
+    /// Attach the register name and address
+    pub fn register(mut self, name: impl Into<String>, address: u16) -> Self {
+        self.register = Some(name.into());
+        self.address = Some(address);
+        self
+    }
+}
+
+impl MemorySize for ErrorEvent {
+    fn memory_size(&self) -> usize {
+        std::mem::size_of::<Self>()
+            + self.device_id.as_ref().map_or(0, String::capacity)
+            + self.register.as_ref().map_or(0, String::capacity)
+            + self.message.capacity()
+    }
+}
+
+/// Bounded ring buffer of recent errors, cheap to clone and share
+#[derive(Debug, Clone)]
+pub struct ErrorLog {
+    events: Arc<Mutex<RingBuffer<ErrorEvent>>>,
+}
+
+impl Default for ErrorLog {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY)
+    }
+}
+
+impl ErrorLog {
+    /// Create a log keeping at most `capacity` events
+    pub fn new(capacity: usize) -> Self {
+        Self::with_max_bytes(capacity, None)
+    }
+
+    /// Create a log keeping at most `capacity` events taking up to `max_bytes`
+    pub fn with_max_bytes(capacity: usize, max_bytes: Option<usize>) -> Self {
+        Self {
+            events: Arc::new(Mutex::new(
+                RingBuffer::new("error_log", capacity).with_max_bytes(max_bytes),
+            )),
+        }
+    }
+
+    /// Maximum number of events kept
+    pub fn capacity(&self) -> usize {
+        self.events().capacity()
+    }
+
+    /// Record an event, evicting the oldest when full
+    pub fn record(&self, event: ErrorEvent) {
+        self.events().push(event);
+    }
+
+    fn events(&self) -> std::sync::MutexGuard<'_, RingBuffer<ErrorEvent>> {
+        self.events.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Most recent events first, optionally filtered by device
+    pub fn recent(&self, limit: usize, device_id: Option<&str>) -> Vec<ErrorEvent> {
+        self.events()
+            .iter()
+            .rev()
+            .filter(|e| device_id.is_none() || e.device_id.as_deref() == device_id)
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+
+    /// Number of events currently stored
+    pub fn len(&self) -> usize {
+        self.events().len()
+    }
+
+    /// Estimated memory taken by the stored events
+    pub fn memory_bytes(&self) -> usize {
+        self.events().memory_bytes()
+    }
+
+    /// Whether the log is empty
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn modbus_error(device: &str, message: &str) -> ErrorEvent {
+        ErrorEvent::new(
+            ErrorSource::Modbus,
+            ErrorKind::classify_modbus(message),
+            message,
+        )
+        .device(device)
+    }
+
+    #[test]
+    fn test_ring_buffer_evicts_oldest() {
+        let log = ErrorLog::new(3);
+        for i in 0..5 {
+            log.record(modbus_error("plc-001", &format!("error {}", i)));
+        }
+
+        assert_eq!(log.len(), 3);
+        let recent = log.recent(10, None);
+        assert_eq!(recent[0].message, "error 4");
+        assert_eq!(recent[2].message, "error 2");
+    }
+
+    #[test]
+    fn test_recent_limit_and_filter() {
+        let log = ErrorLog::new(10);
+        log.record(modbus_error("plc-001", "a"));
+        log.record(modbus_error("plc-002", "b"));
+        log.record(modbus_error("plc-001", "c"));
+
+        assert_eq!(log.recent(1, None).len(), 1);
+
+        let filtered = log.recent(10, Some("plc-001"));
+        assert_eq!(filtered.len(), 2);
+        assert_eq!(filtered[0].message, "c");
+    }
+
+    #[test]
+    fn test_zero_capacity() {
+        let log = ErrorLog::new(0);
+        log.record(modbus_error("plc-001", "ignored"));
+        assert!(log.is_empty());
+    }
+
+    #[test]
+    fn test_classify_error() {
+        assert_eq!(
+            ErrorKind::classify(&RustbridgeError::Exception(
+                tokio_modbus::Exception::IllegalDataAddress
+            )),
+            ErrorKind::Exception
+        );
+        assert_eq!(
+            ErrorKind::classify(&RustbridgeError::Timeout),
+            ErrorKind::Timeout
+        );
+        assert_eq!(
+            ErrorKind::classify(&RustbridgeError::Connection("broken pipe".to_string())),
+            ErrorKind::Transport
+        );
+    }
+
+    #[test]
+    fn test_classify_modbus() {
+        assert_eq!(
+            ErrorKind::classify_modbus("Modbus error: Modbus exception: IllegalDataAddress"),
+            ErrorKind::Exception
+        );
+        assert_eq!(
+            ErrorKind::classify_modbus("Modbus error: IO error: connection timed out"),
+            ErrorKind::Timeout
+        );
+        assert_eq!(
+            ErrorKind::classify_modbus("Failed to connect to 10.0.0.5:502"),
+            ErrorKind::Connection
+        );
+        assert_eq!(
+            ErrorKind::classify_modbus("Modbus error: broken pipe"),
+            ErrorKind::Transport
+        );
+    }
+
+    #[test]
+    fn test_event_serialization() {
+        let event = modbus_error("plc-001", "Modbus error: timeout").register("temperature", 100);
+        let json = serde_json::to_value(&event).unwrap();
+
+        assert_eq!(json["source"], "modbus");
+        assert_eq!(json["kind"], "timeout");
+        assert_eq!(json["device_id"], "plc-001");
+        assert_eq!(json["register"], "temperature");
+        assert_eq!(json["address"], 100);
+    }
+}
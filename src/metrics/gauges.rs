@@ -0,0 +1,282 @@
+//! Register value gauges with configurable names and labels
+//!
+//! See [`RegisterGauges`]. The name and labels of every register's gauge are
+//! worked out when the configuration is loaded or reloaded, so a read only
+//! looks them up. Gauges of registers that are no longer exported keep their
+//! last value until the bridge restarts.
+
+use metrics::Label;
+use std::collections::BTreeMap;
+use std::sync::RwLock;
+
+use crate::config::{DeviceConfig, Meta, RegisterGauges};
+use crate::filter::{self, Subject};
+
+/// Name and labels of a register's gauge
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gauge {
+    pub name: String,
+    pub labels: Vec<Label>,
+}
+
+/// Gauge of each register by device ID and register name, `None` when the
+/// register is not exported
+type Gauges = BTreeMap<String, BTreeMap<String, Option<Gauge>>>;
+
+static GAUGES: RwLock<Gauges> = RwLock::new(BTreeMap::new());
+
+/// Work out the gauges of the registers of `devices`
+pub fn configure(config: &RegisterGauges, devices: &[DeviceConfig]) {
+    *GAUGES.write().unwrap_or_else(|e| e.into_inner()) = gauges(config, devices);
+}
+
+/// Gauge of a register, with the default name and `labels` for registers
+/// outside the configuration
+pub(super) fn lookup(device_id: &str, register: &str, labels: &[Label]) -> Option<Gauge> {
+    let gauges = GAUGES.read().unwrap_or_else(|e| e.into_inner());
+    match gauges
+        .get(device_id)
+        .and_then(|registers| registers.get(register))
+    {
+        Some(gauge) => gauge.clone(),
+        None => Some(Gauge {
+            name: RegisterGauges::default().name,
+            labels: labels.to_vec(),
+        }),
+    }
+}
+
+fn gauges(config: &RegisterGauges, devices: &[DeviceConfig]) -> Gauges {
+    let mut gauges = Gauges::new();
+    for device in devices {
+        let registers = gauges.entry(device.id.clone()).or_default();
+        for register in &device.registers {
+            let meta = device.register_meta(register);
+            let subject = Subject {
+                device_id: &device.id,
+                register: &register.name,
+                group: device.group.as_deref(),
+                meta: &meta,
+            };
+            let gauge = filter::allows(&config.filter, &subject).then(|| {
+                let values = Placeholders {
+                    subject,
+                    unit: register.unit.as_deref(),
+                };
+                gauge(config, &values, device.tenant.as_deref())
+            });
+            registers.insert(register.name.clone(), gauge);
+        }
+    }
+    gauges
+}
+
+fn gauge(config: &RegisterGauges, values: &Placeholders, tenant: Option<&str>) -> Gauge {
+    // Templates were checked by validation; a failure leaves them as they are
+    let expand = |template: &str| expand(template, values).unwrap_or_else(|_| template.into());
+    let mut labels: Vec<Label> = if config.labels.is_empty() {
+        vec![
+            Label::new("device", values.subject.device_id.to_string()),
+            Label::new("register", values.subject.register.to_string()),
+        ]
+    } else {
+        config
+            .labels
+            .iter()
+            .map(|(name, template)| Label::new(name.clone(), expand(template)))
+            .collect()
+    };
+    if let Some(tenant) = tenant {
+        // Right after `device`, like the other metrics of the device
+        let index = if config.labels.is_empty() {
+            1
+        } else {
+            labels.len()
+        };
+        labels.insert(index, Label::new("tenant", tenant.to_string()));
+    }
+    Gauge {
+        name: metric_name(&expand(&config.name)),
+        labels,
+    }
+}
+
+/// Values of the placeholders of a register
+struct Placeholders<'a> {
+    subject: Subject<'a>,
+    unit: Option<&'a str>,
+}
+
+impl Placeholders<'_> {
+    fn get(&self, placeholder: &str) -> Option<String> {
+        let value = match placeholder {
+            "device" => self.subject.device_id,
+            "register" => self.subject.register,
+            "group" => self.subject.group.unwrap_or_default(),
+            "unit" => self.unit.unwrap_or_default(),
+            _ => {
+                let key = placeholder
+                    .strip_prefix("meta.")
+                    .filter(|key| !key.is_empty())?;
+                return Some(match self.subject.meta.get(key) {
+                    Some(serde_json::Value::String(value)) => value.clone(),
+                    Some(serde_json::Value::Null) | None => String::new(),
+                    Some(value) => value.to_string(),
+                });
+            }
+        };
+        Some(value.to_string())
+    }
+}
+
+/// Replace the `{...}` placeholders of `template`
+fn expand(template: &str, values: &Placeholders) -> Result<String, String> {
+    let mut expanded = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        expanded.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find('}') else {
+            return Err(format!("unclosed '{{' in \"{}\"", template));
+        };
+        let placeholder = &rest[start + 1..start + end];
+        let value = values
+            .get(placeholder)
+            .ok_or_else(|| format!("unknown placeholder {{{}}}", placeholder))?;
+        expanded.push_str(&value);
+        rest = &rest[start + end + 1..];
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+/// `name` with characters Prometheus does not allow in metric names replaced
+fn metric_name(name: &str) -> String {
+    let mut metric: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == ':' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if metric.is_empty() || metric.starts_with(|c: char| c.is_ascii_digit()) {
+        metric.insert(0, '_');
+    }
+    metric
+}
+
+/// Problems with the templates and label names of `config`
+pub fn check(config: &RegisterGauges, errors: &mut Vec<String>) {
+    let meta = Meta::new();
+    let values = Placeholders {
+        subject: Subject {
+            device_id: "device",
+            register: "register",
+            group: None,
+            meta: &meta,
+        },
+        unit: None,
+    };
+    if let Err(e) = expand(&config.name, &values) {
+        errors.push(format!("server.register_gauges.name: {}", e));
+    }
+    for (name, template) in &config.labels {
+        let valid = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+            && !name.starts_with("__");
+        if !valid || name == "tenant" {
+            errors.push(format!(
+                "server.register_gauges.labels: '{}' is not a valid label name",
+                name
+            ));
+        }
+        if let Err(e) = expand(template, &values) {
+            errors.push(format!("server.register_gauges.labels.{}: {}", name, e));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::load_config_from_str;
+
+    #[test]
+    fn test_register_gauges() {
+        let config = load_config_from_str(
+            r#"
+server:
+  host: "127.0.0.1"
+  port: 3000
+  metrics_enabled: true
+  register_gauges:
+    name: "plant_{register}_{unit}"
+    labels: { site: "{meta.site}", asset: "{device}", line: "{group}" }
+    filter:
+      exclude:
+        - register: "debug_*"
+devices:
+  - id: press-1
+    name: Press
+    device_type: tcp
+    group: line-a
+    tenant: acme
+    meta: { site: north }
+    poll_interval_ms: 1000
+    connection: { host: 10.0.0.1, port: 502, unit_id: 1 }
+    registers:
+      - { name: oil.temp, address: 0, register_type: input, count: 1, data_type: u16, unit: "°C" }
+      - { name: debug_counter, address: 1, register_type: input, count: 1, data_type: u16 }
+"#,
+        )
+        .unwrap();
+        let exported = gauges(&config.server.register_gauges, &config.devices);
+        let registers = &exported["press-1"];
+        assert_eq!(registers["debug_counter"], None);
+        let gauge = registers["oil.temp"].as_ref().unwrap();
+        assert_eq!(gauge.name, "plant_oil_temp__C");
+        let labels: Vec<(&str, &str)> = gauge.labels.iter().map(|l| (l.key(), l.value())).collect();
+        assert_eq!(
+            labels,
+            [
+                ("asset", "press-1"),
+                ("line", "line-a"),
+                ("site", "north"),
+                ("tenant", "acme"),
+            ]
+        );
+
+        // The defaults match the other metrics of the device
+        let defaults = gauges(&RegisterGauges::default(), &config.devices);
+        let gauge = defaults["press-1"]["debug_counter"].as_ref().unwrap();
+        assert_eq!(gauge.name, "rustbridge_register_value");
+        let labels: Vec<(&str, &str)> = gauge.labels.iter().map(|l| (l.key(), l.value())).collect();
+        assert_eq!(
+            labels,
+            [
+                ("device", "press-1"),
+                ("tenant", "acme"),
+                ("register", "debug_counter"),
+            ]
+        );
+
+        let mut errors = Vec::new();
+        let invalid: RegisterGauges = serde_yaml::from_str(
+            r#"{ name: "x_{device", labels: { "__id": "{device}", tenant: "{meta.}", ok: "{ip}" } }"#,
+        )
+        .unwrap();
+        check(&invalid, &mut errors);
+        assert_eq!(
+            errors,
+            [
+                "server.register_gauges.name: unclosed '{' in \"x_{device\"",
+                "server.register_gauges.labels: '__id' is not a valid label name",
+                "server.register_gauges.labels.ok: unknown placeholder {ip}",
+                "server.register_gauges.labels: 'tenant' is not a valid label name",
+                "server.register_gauges.labels.tenant: unknown placeholder {meta.}",
+            ]
+        );
+    }
+}
@@ -0,0 +1,182 @@
+//! Memory accounting for in-memory stores and buffers
+//!
+//! Sizes are estimates of the heap and inline bytes held by each entry,
+//! close enough to keep buffers within a budget on small gateways and to
+//! report them as `rustbridge_memory_bytes` at `/metrics`.
+
+use std::collections::VecDeque;
+
+/// Estimated number of bytes an entry occupies
+pub trait MemorySize {
+    fn memory_size(&self) -> usize;
+}
+
+impl MemorySize for String {
+    fn memory_size(&self) -> usize {
+        std::mem::size_of::<Self>() + self.capacity()
+    }
+}
+
+impl<T: MemorySize> MemorySize for Option<T> {
+    fn memory_size(&self) -> usize {
+        match self {
+            Some(value) => value.memory_size(),
+            None => std::mem::size_of::<Self>(),
+        }
+    }
+}
+
+impl MemorySize for serde_json::Value {
+    fn memory_size(&self) -> usize {
+        let nested = match self {
+            serde_json::Value::String(s) => s.capacity(),
+            serde_json::Value::Array(values) => values.iter().map(MemorySize::memory_size).sum(),
+            serde_json::Value::Object(fields) => fields
+                .iter()
+                .map(|(key, value)| key.memory_size() + value.memory_size())
+                .sum(),
+            _ => 0,
+        };
+        std::mem::size_of::<Self>() + nested
+    }
+}
+
+/// Ring buffer bounded by a number of entries and, optionally, their size
+///
+/// The oldest entries are evicted first when either limit is hit.
+#[derive(Debug)]
+pub struct RingBuffer<T> {
+    /// Label of the buffer in metrics
+    name: &'static str,
+    entries: VecDeque<T>,
+    bytes: usize,
+    capacity: usize,
+    max_bytes: Option<usize>,
+}
+
+impl<T: MemorySize> RingBuffer<T> {
+    /// Buffer keeping at most `capacity` entries
+    pub fn new(name: &'static str, capacity: usize) -> Self {
+        Self {
+            name,
+            entries: VecDeque::with_capacity(capacity),
+            bytes: 0,
+            capacity,
+            max_bytes: None,
+        }
+    }
+
+    /// Also keep the estimated size of the entries within `max_bytes`
+    pub fn with_max_bytes(mut self, max_bytes: Option<usize>) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    /// Maximum number of entries kept
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Append an entry, evicting the oldest ones to make room
+    ///
+    /// An entry larger than the whole byte budget is not kept.
+    pub fn push(&mut self, entry: T) {
+        if self.capacity == 0 {
+            return;
+        }
+        let size = entry.memory_size();
+        if self.max_bytes.is_some_and(|max| size > max) {
+            super::record_memory_eviction(self.name);
+            return;
+        }
+
+        while self.entries.len() >= self.capacity {
+            self.pop_front();
+        }
+        while self
+            .max_bytes
+            .is_some_and(|max| self.bytes + size > max && !self.entries.is_empty())
+        {
+            self.pop_front();
+            super::record_memory_eviction(self.name);
+        }
+        self.bytes += size;
+        self.entries.push_back(entry);
+    }
+
+    fn pop_front(&mut self) {
+        if let Some(evicted) = self.entries.pop_front() {
+            self.bytes -= evicted.memory_size();
+        }
+    }
+
+    /// Entries from oldest to newest
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &T> {
+        self.entries.iter()
+    }
+
+    /// Number of entries stored
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the buffer is empty
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Estimated size of the stored entries
+    pub fn memory_bytes(&self) -> usize {
+        self.bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_evicts_oldest_by_count() {
+        let mut buffer = RingBuffer::new("test", 2);
+        for entry in ["a", "b", "c"] {
+            buffer.push(entry.to_string());
+        }
+
+        let entries: Vec<&String> = buffer.iter().collect();
+        assert_eq!(entries, ["b", "c"]);
+        assert_eq!(buffer.memory_bytes(), 2 * "b".to_string().memory_size());
+    }
+
+    #[test]
+    fn test_evicts_oldest_by_size() {
+        let entry = |c: char| c.to_string().repeat(100);
+        let size = entry('a').memory_size();
+        let mut buffer = RingBuffer::new("test", 10).with_max_bytes(Some(2 * size + 1));
+        for c in ['a', 'b', 'c'] {
+            buffer.push(entry(c));
+        }
+
+        assert_eq!(buffer.len(), 2);
+        assert_eq!(buffer.iter().next(), Some(&entry('b')));
+        assert!(buffer.memory_bytes() <= 2 * size + 1);
+
+        // Too large to ever fit
+        buffer.push("x".repeat(1000));
+        assert_eq!(buffer.len(), 2);
+    }
+
+    #[test]
+    fn test_zero_capacity() {
+        let mut buffer = RingBuffer::new("test", 0);
+        buffer.push("ignored".to_string());
+        assert!(buffer.is_empty());
+        assert_eq!(buffer.memory_bytes(), 0);
+    }
+
+    #[test]
+    fn test_json_size_counts_nested_strings() {
+        let small = serde_json::json!({"site": "a"});
+        let large = serde_json::json!({"site": "a".repeat(100)});
+        assert!(large.memory_size() >= small.memory_size() + 99);
+    }
+}
@@ -6,40 +6,130 @@
 //! - Poll latency histograms
 //! - Device connection status
 //! - MQTT publish counts
+//! - Consecutive failure streaks
+//! - RTU bus frame errors
+//! - Register updates dropped or delayed by slow subscribers
+//! - Memory used by in-memory stores and buffers
+//!
+//! Register values are exported as gauges whose name and labels can be
+//! configured (see [`gauges`]).
+//!
+//! Metrics of a device that belongs to a tenant carry a `tenant` label.
 
-use metrics::{counter, gauge, histogram};
+pub mod bus_stats;
+pub mod diagnostics;
+pub mod error_log;
+pub mod gauges;
+pub mod memory;
+
+use metrics::{counter, gauge, histogram, Label};
 use metrics_exporter_prometheus::{PrometheusBuilder, PrometheusHandle};
-use std::time::Instant;
-use tracing::info;
+use std::collections::BTreeMap;
+use std::sync::{OnceLock, RwLock};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+use crate::config::DeviceConfig;
 
 /// Initialize Prometheus metrics exporter
-/// Returns a handle to render metrics
+/// Returns a handle to render metrics. The recorder is process-wide, so a
+/// bridge started again in the same process shares the first one's handle.
 pub fn init_metrics() -> PrometheusHandle {
-    let handle = PrometheusBuilder::new()
-        .install_recorder()
-        .expect("Failed to install Prometheus recorder");
+    static HANDLE: OnceLock<PrometheusHandle> = OnceLock::new();
+    HANDLE
+        .get_or_init(|| {
+            let recorder = PrometheusBuilder::new().build_recorder();
+            let handle = recorder.handle();
+            match metrics::set_global_recorder(recorder) {
+                Ok(()) => info!("Prometheus metrics initialized"),
+                Err(e) => warn!("Prometheus metrics are not recorded: {}", e),
+            }
+            handle
+        })
+        .clone()
+}
+
+/// Tenant of each device, added as a `tenant` label to its metrics
+static TENANTS: RwLock<BTreeMap<String, String>> = RwLock::new(BTreeMap::new());
+
+/// Label the metrics of the configured devices with their tenant
+pub fn set_device_tenants(devices: &[DeviceConfig]) {
+    let tenants = devices
+        .iter()
+        .filter_map(|d| Some((d.id.clone(), d.tenant.clone()?)))
+        .collect();
+    *TENANTS.write().unwrap_or_else(|e| e.into_inner()) = tenants;
+}
+
+/// `device` and, for devices of a tenant, `tenant` labels followed by `extra`
+fn device_labels(device_id: &str, extra: &[(&'static str, &str)]) -> Vec<Label> {
+    let mut labels = vec![Label::new("device", device_id.to_string())];
+    if let Some(tenant) = TENANTS
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(device_id)
+    {
+        labels.push(Label::new("tenant", tenant.clone()));
+    }
+    for (key, value) in extra {
+        labels.push(Label::new(*key, value.to_string()));
+    }
+    labels
+}
+
+/// Keep only the samples of a tenant's devices in rendered metrics
+///
+/// `# HELP` and `# TYPE` lines are kept; bridge-wide samples are dropped.
+pub fn tenant_metrics(rendered: &str, tenant: &str) -> String {
+    let label = format!("tenant=\"{}\"", tenant);
+    let mut filtered = String::with_capacity(rendered.len());
+    for line in rendered.lines() {
+        if line.starts_with('#') || line.contains(&label) {
+            filtered.push_str(line);
+            filtered.push('\n');
+        }
+    }
+    filtered
+}
 
-    info!("Prometheus metrics initialized");
-    handle
+/// Labels with one more label appended
+fn with_label(labels: &[Label], key: &'static str, value: &'static str) -> Vec<Label> {
+    let mut labels = labels.to_vec();
+    labels.push(Label::new(key, value));
+    labels
 }
 
 /// Metrics for register read operations
 pub struct ReadMetrics {
     start: Instant,
-    device_id: String,
-    register_name: String,
+    /// Labels of the device
+    device: Vec<Label>,
+    /// Labels of the device and register
+    register: Vec<Label>,
+    /// Gauge of the register's value, `None` when it is not exported
+    gauge: Option<gauges::Gauge>,
 }
 
 impl ReadMetrics {
     /// Start timing a register read
     pub fn start(device_id: &str, register_name: &str) -> Self {
+        let device = device_labels(device_id, &[]);
+        let mut register = device.clone();
+        register.push(Label::new("register", register_name.to_string()));
+        let gauge = gauges::lookup(device_id, register_name, &register);
         Self {
             start: Instant::now(),
-            device_id: device_id.to_string(),
-            register_name: register_name.to_string(),
+            device,
+            register,
+            gauge,
         }
     }
 
+    /// Time elapsed since the read started
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
     /// Record successful read
     pub fn success(self, value: f64) {
         let duration = self.start.elapsed().as_secs_f64();
@@ -47,27 +137,17 @@ impl ReadMetrics {
         // Increment read counter
         counter!(
             "rustbridge_register_reads_total",
-            "device" => self.device_id.clone(),
-            "register" => self.register_name.clone(),
-            "status" => "success"
+            with_label(&self.register, "status", "success")
         )
         .increment(1);
 
         // Record latency histogram
-        histogram!(
-            "rustbridge_read_duration_seconds",
-            "device" => self.device_id.clone(),
-            "register" => self.register_name.clone()
-        )
-        .record(duration);
+        histogram!("rustbridge_read_duration_seconds", self.register.clone()).record(duration);
 
         // Set current value gauge
-        gauge!(
-            "rustbridge_register_value",
-            "device" => self.device_id,
-            "register" => self.register_name
-        )
-        .set(value);
+        if let Some(gauge) = self.gauge {
+            gauge!(gauge.name, gauge.labels).set(value);
+        }
     }
 
     /// Record failed read
@@ -77,59 +157,79 @@ impl ReadMetrics {
         // Increment error counter
         counter!(
             "rustbridge_register_reads_total",
-            "device" => self.device_id.clone(),
-            "register" => self.register_name.clone(),
-            "status" => "error"
+            with_label(&self.register, "status", "error")
         )
         .increment(1);
 
         // Increment specific error counter
-        counter!(
-            "rustbridge_errors_total",
-            "device" => self.device_id.clone(),
-            "type" => error_type.to_string()
-        )
-        .increment(1);
+        let mut labels = self.device;
+        labels.push(Label::new("type", error_type.to_string()));
+        counter!("rustbridge_errors_total", labels).increment(1);
 
         // Still record the latency
-        histogram!(
-            "rustbridge_read_duration_seconds",
-            "device" => self.device_id,
-            "register" => self.register_name
-        )
-        .record(duration);
+        histogram!("rustbridge_read_duration_seconds", self.register).record(duration);
     }
 }
 
 /// Record device connection status
 pub fn record_device_status(device_id: &str, connected: bool) {
+    gauge!("rustbridge_device_connected", device_labels(device_id, &[])).set(if connected {
+        1.0
+    } else {
+        0.0
+    });
+}
+
+/// Record the health score of a device (0 to 100)
+pub fn record_device_health(device_id: &str, score: f64) {
     gauge!(
-        "rustbridge_device_connected",
-        "device" => device_id.to_string()
+        "rustbridge_device_health_score",
+        device_labels(device_id, &[])
     )
-    .set(if connected { 1.0 } else { 0.0 });
+    .set(score);
+}
+
+/// Record consecutive failure streaks for a register and its device
+pub fn record_failure_streak(
+    device_id: &str,
+    register_name: &str,
+    register_streak: u64,
+    device_streak: u64,
+) {
+    gauge!(
+        "rustbridge_register_consecutive_failures",
+        device_labels(device_id, &[("register", register_name)])
+    )
+    .set(register_streak as f64);
+
+    gauge!(
+        "rustbridge_device_consecutive_failures",
+        device_labels(device_id, &[])
+    )
+    .set(device_streak as f64);
 }
 
 /// Record MQTT publish event
-#[allow(dead_code)] // Available for MQTT integration
 pub fn record_mqtt_publish(device_id: &str, register_name: &str, success: bool) {
     counter!(
         "rustbridge_mqtt_publishes_total",
-        "device" => device_id.to_string(),
-        "register" => register_name.to_string(),
-        "status" => if success { "success" } else { "error" }
+        device_labels(
+            device_id,
+            &[
+                ("register", register_name),
+                ("status", if success { "success" } else { "error" })
+            ]
+        )
     )
     .increment(1);
 }
 
 /// Record MQTT connection status
-#[allow(dead_code)] // Available for MQTT integration
 pub fn record_mqtt_connection(connected: bool) {
     gauge!("rustbridge_mqtt_connected").set(if connected { 1.0 } else { 0.0 });
 }
 
 /// Record active polling devices count
-#[allow(dead_code)] // Available for bridge stats
 pub fn record_active_devices(count: usize) {
     gauge!("rustbridge_active_devices").set(count as f64);
 }
@@ -138,13 +238,99 @@ pub fn record_active_devices(count: usize) {
 pub fn record_poll_cycle(device_id: &str, duration_ms: u64) {
     histogram!(
         "rustbridge_poll_cycle_seconds",
-        "device" => device_id.to_string()
+        device_labels(device_id, &[])
     )
     .record(duration_ms as f64 / 1000.0);
 }
 
+/// Record a poll cycle that took longer than the poll interval
+pub fn record_poll_overrun(device_id: &str) {
+    counter!(
+        "rustbridge_poll_overrun_total",
+        device_labels(device_id, &[])
+    )
+    .increment(1);
+}
+
+/// Record which priorities are skipped while a device is congested
+/// (0 = none, 1 = low, 2 = low and normal)
+pub fn record_load_shedding(device_id: &str, level: u8) {
+    gauge!("rustbridge_poll_shed_level", device_labels(device_id, &[])).set(level as f64);
+}
+
+/// Record registers deferred to the next cycle by `cycle_budget_ms`
+pub fn record_deferred_reads(device_id: &str, count: usize) {
+    counter!(
+        "rustbridge_poll_deferred_total",
+        device_labels(device_id, &[])
+    )
+    .increment(count as u64);
+}
+
+/// Record the achieved time between poll cycle starts
+pub fn record_achieved_interval(device_id: &str, interval_ms: u64) {
+    gauge!(
+        "rustbridge_poll_interval_achieved_seconds",
+        device_labels(device_id, &[])
+    )
+    .set(interval_ms as f64 / 1000.0);
+}
+
+/// Record register updates a lagging subscriber skipped
+pub fn record_dropped_updates(sink: &str, count: u64) {
+    counter!(
+        "rustbridge_updates_dropped_total",
+        "sink" => sink.to_string()
+    )
+    .increment(count);
+}
+
+/// Record a poller waiting for a full blocking sink to take an update
+pub fn record_backpressure_wait(sink: &str) {
+    counter!(
+        "rustbridge_update_backpressure_waits_total",
+        "sink" => sink.to_string()
+    )
+    .increment(1);
+}
+
+/// Record the estimated memory used by an in-memory store or buffer
+pub fn record_memory_usage(buffer: &str, bytes: usize) {
+    gauge!(
+        "rustbridge_memory_bytes",
+        "buffer" => buffer.to_string()
+    )
+    .set(bytes as f64);
+}
+
+/// Record an entry evicted from a full buffer
+pub fn record_memory_eviction(buffer: &str) {
+    counter!(
+        "rustbridge_memory_evictions_total",
+        "buffer" => buffer.to_string()
+    )
+    .increment(1);
+}
+
+/// Record a write blocked by an interlock (`disabled`, `range`, `rate_limit`
+/// or `interlock`)
+pub fn record_write_blocked(device_id: &str, register_name: &str, reason: &str) {
+    counter!(
+        "rustbridge_writes_blocked_total",
+        device_labels(
+            device_id,
+            &[("register", register_name), ("reason", reason)]
+        )
+    )
+    .increment(1);
+}
+
+/// Record register updates queued in the update channel
+pub fn record_update_channel_len(len: usize) {
+    gauge!("rustbridge_update_channel_len").set(len as f64);
+}
+
 /// Record WebSocket connections
-#[allow(dead_code)] // Available for WebSocket stats
 pub fn record_websocket_connections(count: usize) {
     gauge!("rustbridge_websocket_connections").set(count as f64);
 }
@@ -172,6 +358,15 @@ mod tests {
         // No panic = success
     }
 
+    #[test]
+    fn test_failure_streak_metrics() {
+        let _ = PrometheusBuilder::new().install_recorder();
+
+        record_failure_streak("plc-001", "temperature", 3, 5);
+        record_failure_streak("plc-001", "temperature", 0, 0);
+        // No panic = success
+    }
+
     #[test]
     fn test_device_status() {
         let _ = PrometheusBuilder::new().install_recorder();
@@ -196,8 +391,71 @@ mod tests {
         let _ = PrometheusBuilder::new().install_recorder();
 
         record_poll_cycle("plc-001", 150);
+        record_poll_overrun("plc-001");
+        record_achieved_interval("plc-001", 1200);
         record_active_devices(5);
         record_websocket_connections(3);
         // No panic = success
     }
+
+    #[test]
+    fn test_update_channel_metrics() {
+        let _ = PrometheusBuilder::new().install_recorder();
+
+        record_dropped_updates("websocket", 12);
+        record_backpressure_wait("mqtt");
+        record_update_channel_len(10);
+        // No panic = success
+    }
+
+    #[test]
+    fn test_tenant_labels() {
+        let device: DeviceConfig = serde_yaml::from_str(
+            r#"
+id: tenant-test-plc
+name: PLC
+device_type: tcp
+connection: { host: 127.0.0.1, port: 502, unit_id: 1 }
+poll_interval_ms: 1000
+tenant: acme
+registers: []
+"#,
+        )
+        .unwrap();
+        set_device_tenants(&[device]);
+
+        let labels = device_labels("tenant-test-plc", &[("register", "level")]);
+        let pairs: Vec<(&str, &str)> = labels.iter().map(|l| (l.key(), l.value())).collect();
+        assert_eq!(
+            pairs,
+            [
+                ("device", "tenant-test-plc"),
+                ("tenant", "acme"),
+                ("register", "level")
+            ]
+        );
+        assert_eq!(device_labels("tenant-test-other", &[]).len(), 1);
+    }
+
+    #[test]
+    fn test_tenant_metrics_filter() {
+        let rendered = "# TYPE rustbridge_device_connected gauge\n\
+            rustbridge_device_connected{device=\"a\",tenant=\"acme\"} 1\n\
+            rustbridge_device_connected{device=\"b\",tenant=\"globex\"} 1\n\
+            rustbridge_active_devices 2\n";
+        assert_eq!(
+            tenant_metrics(rendered, "acme"),
+            "# TYPE rustbridge_device_connected gauge\n\
+             rustbridge_device_connected{device=\"a\",tenant=\"acme\"} 1\n"
+        );
+    }
+
+    #[test]
+    fn test_memory_metrics() {
+        let _ = PrometheusBuilder::new().install_recorder();
+
+        record_memory_usage("register_store", 4096);
+        record_memory_eviction("error_log");
+        // No panic = success
+    }
 }
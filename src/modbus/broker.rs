@@ -0,0 +1,371 @@
+//! Request broker for shared Modbus connections
+//!
+//! A broker task owns the transport of a connection and runs the requests it
+//! receives over an mpsc command channel one at a time, so requests of
+//! different clients never interleave on the wire. Every client gets a
+//! [`BrokeredTransport`] that tags its requests with its unit ID and timeout.
+//!
+//! Devices on the same RTU serial port share one broker: polling, writes and
+//! clock synchronization of all of them are serialized on the bus. The
+//! request timeout runs in the broker, so time spent waiting behind other
+//! devices does not count against it.
+
+use async_trait::async_trait;
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
+
+use super::client::ModbusError;
+use super::transport::ModbusTransport;
+use super::BROADCAST_TURNAROUND;
+
+/// Requests waiting for a busy connection
+const QUEUE_CAPACITY: usize = 32;
+
+/// A Modbus function call with owned arguments
+#[derive(Debug)]
+enum Operation {
+    ReadHolding(u16, u16),
+    ReadInput(u16, u16),
+    ReadCoils(u16, u16),
+    ReadDiscrete(u16, u16),
+    WriteRegister(u16, u16),
+    WriteRegisters(u16, Vec<u16>),
+    WriteCoil(u16, bool),
+    ExceptionStatus,
+    Fifo(u16),
+}
+
+/// Response to an [`Operation`]
+#[derive(Debug)]
+enum Reply {
+    Registers(Vec<u16>),
+    Bits(Vec<bool>),
+    Status(u8),
+    Written,
+}
+
+struct Command {
+    unit: u8,
+    timeout: Option<Duration>,
+    operation: Operation,
+    reply_tx: oneshot::Sender<Result<Reply, ModbusError>>,
+}
+
+/// Handle to a broker task; the connection closes when the last handle and
+/// transport are dropped
+#[derive(Clone)]
+pub struct Broker {
+    tx: mpsc::Sender<Command>,
+}
+
+impl Broker {
+    /// Start a broker task that owns `transport`
+    pub fn spawn(transport: impl ModbusTransport + 'static) -> Self {
+        let (tx, rx) = mpsc::channel(QUEUE_CAPACITY);
+        tokio::spawn(run(Box::new(transport), rx));
+        Self { tx }
+    }
+
+    /// Transport for requests to `unit`, each limited to `timeout` once it runs
+    pub fn transport(&self, unit: u8, timeout: Option<Duration>) -> BrokeredTransport {
+        BrokeredTransport {
+            tx: self.tx.clone(),
+            unit,
+            timeout,
+        }
+    }
+
+    /// Whether the broker stopped after losing its connection
+    pub fn is_closed(&self) -> bool {
+        self.tx.is_closed()
+    }
+}
+
+/// Run commands in arrival order until every sender is gone or the connection is lost
+async fn run(mut transport: Box<dyn ModbusTransport>, mut rx: mpsc::Receiver<Command>) {
+    while let Some(command) = rx.recv().await {
+        transport.set_unit(command.unit);
+        let result = execute(
+            transport.as_mut(),
+            command.unit,
+            command.timeout,
+            command.operation,
+        )
+        .await;
+        let lost = matches!(&result, Err(e) if connection_lost(e));
+        if lost {
+            // Refuse new requests before answering, so a reconnect opens a new connection
+            rx.close();
+        }
+        let _ = command.reply_tx.send(result);
+        if lost {
+            return;
+        }
+    }
+}
+
+async fn execute(
+    transport: &mut dyn ModbusTransport,
+    unit: u8,
+    timeout: Option<Duration>,
+    operation: Operation,
+) -> Result<Reply, ModbusError> {
+    let request = async {
+        Ok(match operation {
+            Operation::ReadHolding(addr, cnt) => {
+                Reply::Registers(transport.read_holding_registers(addr, cnt).await?)
+            }
+            Operation::ReadInput(addr, cnt) => {
+                Reply::Registers(transport.read_input_registers(addr, cnt).await?)
+            }
+            Operation::ReadCoils(addr, cnt) => Reply::Bits(transport.read_coils(addr, cnt).await?),
+            Operation::ReadDiscrete(addr, cnt) => {
+                Reply::Bits(transport.read_discrete_inputs(addr, cnt).await?)
+            }
+            Operation::WriteRegister(addr, value) => {
+                transport.write_single_register(addr, value).await?;
+                Reply::Written
+            }
+            Operation::WriteRegisters(addr, values) => {
+                transport.write_multiple_registers(addr, &values).await?;
+                Reply::Written
+            }
+            Operation::WriteCoil(addr, value) => {
+                transport.write_single_coil(addr, value).await?;
+                Reply::Written
+            }
+            Operation::ExceptionStatus => Reply::Status(transport.read_exception_status().await?),
+            Operation::Fifo(addr) => Reply::Registers(transport.read_fifo_queue(addr).await?),
+        })
+    };
+
+    // Nobody answers a broadcast; free the bus after the turnaround delay
+    if unit == 0 {
+        return tokio::time::timeout(timeout.unwrap_or(BROADCAST_TURNAROUND), request)
+            .await
+            .unwrap_or(Ok(Reply::Written));
+    }
+    super::within(timeout, request).await
+}
+
+/// Whether an error leaves the connection unusable (as opposed to a timeout,
+/// a corrupt frame or an exception from the device)
+fn connection_lost(error: &ModbusError) -> bool {
+    let io = match error {
+        ModbusError::Io(e) | ModbusError::Transport(tokio_modbus::Error::Transport(e)) => e,
+        ModbusError::Serial(_) => return true,
+        ModbusError::Exception(_) | ModbusError::Transport(_) => return false,
+    };
+    !matches!(
+        io.kind(),
+        std::io::ErrorKind::TimedOut | std::io::ErrorKind::InvalidData
+    )
+}
+
+/// The broker's end of a shared connection, as seen by one client
+pub struct BrokeredTransport {
+    tx: mpsc::Sender<Command>,
+    unit: u8,
+    timeout: Option<Duration>,
+}
+
+impl BrokeredTransport {
+    async fn request(&self, operation: Operation) -> Result<Reply, ModbusError> {
+        let closed = || {
+            ModbusError::Io(std::io::Error::new(
+                std::io::ErrorKind::NotConnected,
+                "connection closed",
+            ))
+        };
+        let (reply_tx, reply_rx) = oneshot::channel();
+        self.tx
+            .send(Command {
+                unit: self.unit,
+                timeout: self.timeout,
+                operation,
+                reply_tx,
+            })
+            .await
+            .map_err(|_| closed())?;
+        reply_rx.await.map_err(|_| closed())?
+    }
+
+    async fn registers(&self, operation: Operation) -> Result<Vec<u16>, ModbusError> {
+        match self.request(operation).await? {
+            Reply::Registers(values) => Ok(values),
+            reply => Err(unexpected(reply)),
+        }
+    }
+
+    async fn bits(&self, operation: Operation) -> Result<Vec<bool>, ModbusError> {
+        match self.request(operation).await? {
+            Reply::Bits(values) => Ok(values),
+            reply => Err(unexpected(reply)),
+        }
+    }
+
+    async fn write(&self, operation: Operation) -> Result<(), ModbusError> {
+        match self.request(operation).await? {
+            Reply::Written => Ok(()),
+            reply => Err(unexpected(reply)),
+        }
+    }
+}
+
+fn unexpected(reply: Reply) -> ModbusError {
+    ModbusError::Io(std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        format!("unexpected reply {:?}", reply),
+    ))
+}
+
+#[async_trait]
+impl ModbusTransport for BrokeredTransport {
+    async fn read_holding_registers(
+        &mut self,
+        addr: u16,
+        cnt: u16,
+    ) -> Result<Vec<u16>, ModbusError> {
+        self.registers(Operation::ReadHolding(addr, cnt)).await
+    }
+
+    async fn read_input_registers(&mut self, addr: u16, cnt: u16) -> Result<Vec<u16>, ModbusError> {
+        self.registers(Operation::ReadInput(addr, cnt)).await
+    }
+
+    async fn read_coils(&mut self, addr: u16, cnt: u16) -> Result<Vec<bool>, ModbusError> {
+        self.bits(Operation::ReadCoils(addr, cnt)).await
+    }
+
+    async fn read_discrete_inputs(
+        &mut self,
+        addr: u16,
+        cnt: u16,
+    ) -> Result<Vec<bool>, ModbusError> {
+        self.bits(Operation::ReadDiscrete(addr, cnt)).await
+    }
+
+    async fn write_single_register(&mut self, addr: u16, value: u16) -> Result<(), ModbusError> {
+        self.write(Operation::WriteRegister(addr, value)).await
+    }
+
+    async fn write_multiple_registers(
+        &mut self,
+        addr: u16,
+        values: &[u16],
+    ) -> Result<(), ModbusError> {
+        self.write(Operation::WriteRegisters(addr, values.to_vec()))
+            .await
+    }
+
+    async fn write_single_coil(&mut self, addr: u16, value: bool) -> Result<(), ModbusError> {
+        self.write(Operation::WriteCoil(addr, value)).await
+    }
+
+    async fn read_exception_status(&mut self) -> Result<u8, ModbusError> {
+        match self.request(Operation::ExceptionStatus).await? {
+            Reply::Status(status) => Ok(status),
+            reply => Err(unexpected(reply)),
+        }
+    }
+
+    async fn read_fifo_queue(&mut self, addr: u16) -> Result<Vec<u16>, ModbusError> {
+        self.registers(Operation::Fifo(addr)).await
+    }
+
+    fn set_unit(&mut self, unit: u8) {
+        self.unit = unit;
+    }
+
+    fn set_timeout(&mut self, timeout: Option<Duration>) -> bool {
+        self.timeout = timeout;
+        true
+    }
+}
+
+/// Broker of the connection to `key` (e.g. a serial port), started with the
+/// transport from `open` unless a running broker already serves it
+#[cfg(feature = "rtu")]
+pub(crate) fn shared<T: ModbusTransport + 'static>(
+    key: &str,
+    open: impl FnOnce() -> crate::error::Result<T>,
+) -> crate::error::Result<Broker> {
+    use std::collections::HashMap;
+    use std::sync::{Mutex, OnceLock};
+
+    static BROKERS: OnceLock<Mutex<HashMap<String, mpsc::WeakSender<Command>>>> = OnceLock::new();
+    let mut brokers = BROKERS
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+
+    // The sender lives as long as some client uses the connection
+    if let Some(tx) = brokers.get(key).and_then(|tx| tx.upgrade()) {
+        if !tx.is_closed() {
+            return Ok(Broker { tx });
+        }
+    }
+    let broker = Broker::spawn(open()?);
+    brokers.insert(key.to_string(), broker.tx.downgrade());
+    Ok(broker)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modbus::MockTransport;
+
+    fn io_error(kind: std::io::ErrorKind) -> ModbusError {
+        ModbusError::Io(std::io::Error::new(kind, "test"))
+    }
+
+    #[tokio::test]
+    async fn test_clients_share_connection() {
+        let device = MockTransport::new();
+        device.set_holding(0, &[1, 2]);
+        let broker = Broker::spawn(device.clone());
+        let mut first = broker.transport(1, None);
+        let mut second = broker.transport(2, None);
+
+        let (a, b) = tokio::join!(
+            first.read_holding_registers(0, 2),
+            second.write_single_register(1, 7)
+        );
+        assert_eq!(a.unwrap(), vec![1, 2]);
+        b.unwrap();
+        assert_eq!(device.holding(1), Some(7));
+        assert_eq!(device.requests(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_broker_closes_on_lost_connection() {
+        let device = MockTransport::new();
+        device.set_holding(0, &[1]);
+        device.fail_next(io_error(std::io::ErrorKind::TimedOut));
+        device.fail_next(io_error(std::io::ErrorKind::BrokenPipe));
+        let broker = Broker::spawn(device);
+        let mut client = broker.transport(1, None);
+
+        // A timeout keeps the connection
+        assert!(client.read_holding_registers(0, 1).await.is_err());
+        assert!(!broker.is_closed());
+
+        assert!(client.read_holding_registers(0, 1).await.is_err());
+        assert!(broker.is_closed());
+        let Err(ModbusError::Io(e)) = client.read_holding_registers(0, 1).await else {
+            panic!("expected a closed connection");
+        };
+        assert_eq!(e.kind(), std::io::ErrorKind::NotConnected);
+    }
+
+    #[tokio::test]
+    async fn test_broadcast_is_not_answered() {
+        let device = MockTransport::new();
+        device.set_silent_writes(true);
+        let broker = Broker::spawn(device.clone());
+        let mut client = broker.transport(0, Some(Duration::from_millis(10)));
+
+        client.write_single_register(4, 9).await.unwrap();
+        assert_eq!(device.holding(4), Some(9));
+    }
+}
@@ -0,0 +1,274 @@
+//! Programmatic construction of a [`ModbusClient`]
+//!
+//! ```no_run
+//! # async fn example() -> anyhow::Result<()> {
+//! use std::time::Duration;
+//! use rustbridge::ModbusClient;
+//!
+//! let mut client = ModbusClient::builder()
+//!     .tcp("10.0.0.5:502")
+//!     .unit(3)
+//!     .timeout(Duration::from_secs(1))
+//!     .build()
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::time::Duration;
+
+use super::ModbusClient;
+use crate::config::{ConnectionConfig, DeviceConfig, DeviceType, RtuConnection, TcpConnection};
+use crate::error::{Result, RustbridgeError};
+
+/// Default Modbus TCP port
+pub const DEFAULT_TCP_PORT: u16 = 502;
+
+/// Transport selected on the builder
+#[derive(Debug, Clone)]
+enum Target {
+    Tcp(String),
+    Rtu(String),
+}
+
+/// Builder for [`ModbusClient`], see [`ModbusClient::builder`]
+#[derive(Debug, Clone)]
+pub struct ModbusClientBuilder {
+    id: String,
+    target: Option<Target>,
+    unit: u8,
+    timeout: Option<Duration>,
+    baud_rate: u32,
+    data_bits: u8,
+    stop_bits: u8,
+    parity: String,
+}
+
+impl Default for ModbusClientBuilder {
+    fn default() -> Self {
+        Self {
+            id: "client".to_string(),
+            target: None,
+            unit: 1,
+            timeout: None,
+            baud_rate: 9600,
+            data_bits: 8,
+            stop_bits: 1,
+            parity: "none".to_string(),
+        }
+    }
+}
+
+impl ModbusClientBuilder {
+    /// Connect over Modbus TCP to `host:port` (port defaults to 502)
+    pub fn tcp(mut self, address: impl Into<String>) -> Self {
+        self.target = Some(Target::Tcp(address.into()));
+        self
+    }
+
+    /// Connect over Modbus RTU on a serial port (e.g. `/dev/ttyUSB0`)
+    pub fn rtu(mut self, port: impl Into<String>) -> Self {
+        self.target = Some(Target::Rtu(port.into()));
+        self
+    }
+
+    /// Modbus unit ID (default 1)
+    pub fn unit(mut self, unit: u8) -> Self {
+        self.unit = unit;
+        self
+    }
+
+    /// Timeout for connecting and for each request (default: none)
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Device ID used in log messages (default "client")
+    pub fn id(mut self, id: impl Into<String>) -> Self {
+        self.id = id.into();
+        self
+    }
+
+    /// Serial baud rate (RTU, default 9600)
+    pub fn baud_rate(mut self, baud_rate: u32) -> Self {
+        self.baud_rate = baud_rate;
+        self
+    }
+
+    /// Serial data bits (RTU, default 8)
+    pub fn data_bits(mut self, data_bits: u8) -> Self {
+        self.data_bits = data_bits;
+        self
+    }
+
+    /// Serial stop bits (RTU, default 1)
+    pub fn stop_bits(mut self, stop_bits: u8) -> Self {
+        self.stop_bits = stop_bits;
+        self
+    }
+
+    /// Serial parity: "none", "even" or "odd" (RTU, default "none")
+    pub fn parity(mut self, parity: impl Into<String>) -> Self {
+        self.parity = parity.into();
+        self
+    }
+
+    /// Device configuration equivalent to the builder settings
+    pub fn device_config(&self) -> Result<DeviceConfig> {
+        let (device_type, connection) = match &self.target {
+            Some(Target::Tcp(address)) => {
+                let (host, port) = parse_tcp_target(address)?;
+                (
+                    DeviceType::Tcp,
+                    ConnectionConfig::Tcp(TcpConnection {
+                        host,
+                        port,
+                        unit_id: self.unit,
+                        ..Default::default()
+                    }),
+                )
+            }
+            Some(Target::Rtu(port)) => (
+                DeviceType::Rtu,
+                ConnectionConfig::Rtu(RtuConnection {
+                    port: port.clone(),
+                    usb: None,
+                    baud_rate: self.baud_rate,
+                    data_bits: self.data_bits,
+                    stop_bits: self.stop_bits,
+                    parity: self.parity.clone(),
+                    unit_id: self.unit,
+                    response_timeout_ms: None,
+                    retries: 0,
+                    listen_only: false,
+                }),
+            ),
+            None => {
+                return Err(RustbridgeError::Config(
+                    "No transport configured: call .tcp() or .rtu()".to_string(),
+                ))
+            }
+        };
+
+        Ok(DeviceConfig {
+            id: self.id.clone(),
+            name: self.id.clone(),
+            device_type,
+            connection,
+            poll_interval_ms: 0,
+            profile: None,
+            registers: vec![],
+            meta: Default::default(),
+            group: None,
+            tenant: None,
+            time_sync: None,
+            circuit_breaker: Default::default(),
+            cycle_budget_ms: None,
+            align_to_clock: false,
+            reconnect: Default::default(),
+            read_blocks: None,
+        })
+    }
+
+    /// Connect and return the client
+    pub async fn build(self) -> Result<ModbusClient> {
+        let device = self.device_config()?;
+        let connect = ModbusClient::new(&device);
+        let mut client = match self.timeout {
+            Some(timeout) => tokio::time::timeout(timeout, connect)
+                .await
+                .map_err(|_| RustbridgeError::Timeout)??,
+            None => connect.await?,
+        };
+        client.set_timeout(self.timeout);
+        Ok(client)
+    }
+}
+
+/// Split `host:port`, defaulting to port 502
+pub fn parse_tcp_target(target: &str) -> Result<(String, u16)> {
+    match target.rsplit_once(':') {
+        Some((host, port)) => {
+            let port = port
+                .parse()
+                .map_err(|_| RustbridgeError::Config(format!("Invalid port in '{}'", target)))?;
+            Ok((host.to_string(), port))
+        }
+        None => Ok((target.to_string(), DEFAULT_TCP_PORT)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_tcp_target() {
+        assert_eq!(
+            parse_tcp_target("10.0.0.5:502").unwrap(),
+            ("10.0.0.5".to_string(), 502)
+        );
+        assert_eq!(
+            parse_tcp_target("10.0.0.5").unwrap(),
+            ("10.0.0.5".to_string(), 502)
+        );
+        assert!(parse_tcp_target("10.0.0.5:plc").is_err());
+    }
+
+    #[test]
+    fn test_tcp_device_config() {
+        let device = ModbusClient::builder()
+            .tcp("10.0.0.5:5020")
+            .unit(3)
+            .id("plc-001")
+            .device_config()
+            .unwrap();
+
+        assert_eq!(device.id, "plc-001");
+        let ConnectionConfig::Tcp(tcp) = device.connection else {
+            panic!("expected TCP connection");
+        };
+        assert_eq!(tcp.host, "10.0.0.5");
+        assert_eq!(tcp.port, 5020);
+        assert_eq!(tcp.unit_id, 3);
+    }
+
+    #[test]
+    fn test_rtu_device_config() {
+        let device = ModbusClient::builder()
+            .rtu("/dev/ttyUSB0")
+            .baud_rate(19200)
+            .parity("even")
+            .device_config()
+            .unwrap();
+
+        let ConnectionConfig::Rtu(rtu) = device.connection else {
+            panic!("expected RTU connection");
+        };
+        assert_eq!(rtu.port, "/dev/ttyUSB0");
+        assert_eq!(rtu.baud_rate, 19200);
+        assert_eq!(rtu.parity, "even");
+        assert_eq!(rtu.unit_id, 1);
+    }
+
+    #[tokio::test]
+    async fn test_build_requires_transport() {
+        let err = ModbusClient::builder().unit(2).build().await.err().unwrap();
+        assert!(err.to_string().contains("No transport configured"));
+    }
+
+    #[tokio::test]
+    async fn test_build_connection_refused() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        drop(listener);
+
+        let result = ModbusClient::builder()
+            .tcp(format!("127.0.0.1:{}", port))
+            .timeout(Duration::from_secs(1))
+            .build()
+            .await;
+        assert!(result.is_err());
+    }
+}
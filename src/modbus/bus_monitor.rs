@@ -0,0 +1,215 @@
+//! Serial line monitor for Modbus RTU
+//!
+//! Wraps the serial port handed to tokio-modbus and inspects the response
+//! bytes. tokio-modbus silently resynchronises on corrupt frames, so this is
+//! the only place CRC and framing errors can be counted.
+
+use std::io;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+use crate::metrics::bus_stats::BusCounters;
+
+/// Longest valid RTU frame (address + PDU + CRC)
+const MAX_FRAME_LEN: usize = 256;
+
+/// Serial port wrapper that records frame statistics of every response
+#[derive(Debug)]
+pub struct MonitoredPort<T> {
+    inner: T,
+    counters: Arc<BusCounters>,
+    frame: Vec<u8>,
+}
+
+impl<T> MonitoredPort<T> {
+    pub fn new(inner: T, counters: Arc<BusCounters>) -> Self {
+        Self {
+            inner,
+            counters,
+            frame: Vec::with_capacity(MAX_FRAME_LEN),
+        }
+    }
+
+    /// Check the response bytes read so far for complete frames
+    fn inspect(&mut self, bytes: &[u8]) {
+        self.counters.record_received(bytes.len());
+        self.frame.extend_from_slice(bytes);
+
+        while !self.frame.is_empty() {
+            match response_len(&self.frame) {
+                Frame::Incomplete => break,
+                Frame::Invalid => {
+                    self.counters.record_framing_error();
+                    self.frame.clear();
+                }
+                Frame::Complete(len) => {
+                    if crc16(&self.frame[..len - 2])
+                        == u16::from_le_bytes([self.frame[len - 2], self.frame[len - 1]])
+                    {
+                        self.counters.record_frame_ok();
+                    } else {
+                        self.counters.record_crc_error();
+                    }
+                    self.frame.drain(..len);
+                }
+            }
+        }
+    }
+}
+
+impl<T: AsyncRead + Unpin> AsyncRead for MonitoredPort<T> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let filled = buf.filled().len();
+        let poll = Pin::new(&mut self.inner).poll_read(cx, buf);
+        if let Poll::Ready(Ok(())) = poll {
+            let read = buf.filled()[filled..].to_vec();
+            if !read.is_empty() {
+                self.inspect(&read);
+            }
+        }
+        poll
+    }
+}
+
+impl<T: AsyncWrite + Unpin> AsyncWrite for MonitoredPort<T> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        // A new request starts a new response; leftovers of the previous one
+        // never completed into a frame
+        if !self.frame.is_empty() {
+            self.counters.record_framing_error();
+            self.frame.clear();
+        }
+        let poll = Pin::new(&mut self.inner).poll_write(cx, buf);
+        if let Poll::Ready(Ok(written)) = poll {
+            self.counters.record_sent(written);
+        }
+        poll
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Frame {
+    Incomplete,
+    Invalid,
+    Complete(usize),
+}
+
+/// Length of the response frame at the start of `bytes`, from its function code
+fn response_len(bytes: &[u8]) -> Frame {
+    let Some(&function) = bytes.get(1) else {
+        return Frame::Incomplete;
+    };
+    let len = match function {
+        // Reads: address, function, byte count, data, CRC
+        0x01..=0x04 => match bytes.get(2) {
+            Some(&count) => 3 + count as usize + 2,
+            None => return Frame::Incomplete,
+        },
+        // Single and multiple writes echo address and value/quantity
+        0x05 | 0x06 | 0x0F | 0x10 => 8,
+        // Read exception status
+        0x07 => 5,
+        // Exception responses: address, function | 0x80, exception code, CRC
+        0x81..=0xAB => 5,
+        _ => return Frame::Invalid,
+    };
+    if len > MAX_FRAME_LEN {
+        Frame::Invalid
+    } else if bytes.len() < len {
+        Frame::Incomplete
+    } else {
+        Frame::Complete(len)
+    }
+}
+
+/// Modbus CRC-16 (polynomial 0xA001, initial value 0xFFFF)
+fn crc16(bytes: &[u8]) -> u16 {
+    bytes.iter().fold(0xFFFF, |crc, &byte| {
+        (0..8).fold(crc ^ byte as u16, |crc, _| {
+            if crc & 1 != 0 {
+                (crc >> 1) ^ 0xA001
+            } else {
+                crc >> 1
+            }
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::bus_stats;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    fn with_crc(pdu: &[u8]) -> Vec<u8> {
+        let mut frame = pdu.to_vec();
+        frame.extend_from_slice(&crc16(pdu).to_le_bytes());
+        frame
+    }
+
+    #[test]
+    fn test_crc16() {
+        // Read holding registers request: unit 1, address 0, count 1
+        assert_eq!(crc16(&[0x01, 0x03, 0x00, 0x00, 0x00, 0x01]), 0x0A84);
+    }
+
+    #[test]
+    fn test_response_len() {
+        assert_eq!(response_len(&[0x01]), Frame::Incomplete);
+        assert_eq!(response_len(&[0x01, 0x03, 0x04, 0, 0]), Frame::Incomplete);
+        assert_eq!(
+            response_len(&[0x01, 0x03, 0x02, 0, 0, 0, 0]),
+            Frame::Complete(7)
+        );
+        assert_eq!(response_len(&[0x01, 0x83, 0x02, 0, 0]), Frame::Complete(5));
+        assert_eq!(response_len(&[0x01, 0x42]), Frame::Invalid);
+    }
+
+    #[tokio::test]
+    async fn test_counts_crc_and_framing_errors() {
+        let counters = bus_stats::bus("/dev/test-bus-monitor");
+        let (device, host) = tokio::io::duplex(1024);
+        let mut port = MonitoredPort::new(host, counters.clone());
+        let (mut device_rx, mut device_tx) = tokio::io::split(device);
+
+        let good = with_crc(&[0x01, 0x03, 0x02, 0x00, 0x2A]);
+        let mut corrupt = good.clone();
+        corrupt[4] ^= 0xFF;
+
+        let mut buf = [0u8; 64];
+        for response in [good.as_slice(), corrupt.as_slice(), &[0x01, 0x42, 0x00]] {
+            port.write_all(&[0x01, 0x03, 0x00, 0x00, 0x00, 0x01, 0x84, 0x0A])
+                .await
+                .unwrap();
+            device_rx.read_exact(&mut buf[..8]).await.unwrap();
+            device_tx.write_all(response).await.unwrap();
+            let mut received = vec![0u8; response.len()];
+            port.read_exact(&mut received).await.unwrap();
+        }
+
+        let stats = counters.snapshot();
+        assert_eq!(stats.frames_ok, 1);
+        assert_eq!(stats.crc_errors, 1);
+        assert_eq!(stats.framing_errors, 1);
+        assert_eq!(stats.bytes_sent, 24);
+        assert_eq!(stats.bytes_received, 17);
+    }
+}
@@ -19,7 +19,6 @@ pub enum ModbusError {
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
     #[error("Serial port error: {0}")]
-    #[allow(dead_code)] // Available for RTU error handling
     Serial(String),
 }
 
@@ -128,6 +127,38 @@ impl Context {
         }
     }
 
+    /// Read Exception Status (FC07): the eight status bits of the device
+    pub async fn read_exception_status(&mut self) -> Result<u8, ModbusError> {
+        let data = self.call_custom(0x07, &[]).await?;
+        data.first()
+            .copied()
+            .ok_or_else(|| invalid_response("empty exception status response"))
+    }
+
+    /// Read FIFO Queue (FC24): the queued registers behind the FIFO pointer
+    /// `addr`, at most 31
+    pub async fn read_fifo_queue(&mut self, addr: u16) -> Result<Vec<u16>, ModbusError> {
+        let data = self.call_custom(0x18, &addr.to_be_bytes()).await?;
+        parse_fifo_response(&data)
+    }
+
+    /// Send a function the tokio-modbus client has no method for and return
+    /// the data of the response after the function code
+    async fn call_custom(&mut self, function: u8, data: &[u8]) -> Result<Vec<u8>, ModbusError> {
+        let request = Request::Custom(function, std::borrow::Cow::Borrowed(data));
+        let response = match self {
+            Context::Tcp(ctx) => ctx.call(request).await?,
+            Context::Rtu(ctx) => ctx.call(request).await?,
+        };
+        match response.map_err(ModbusError::Exception)? {
+            Response::Custom(code, data) if code == function => Ok(data.to_vec()),
+            other => Err(invalid_response(&format!(
+                "unexpected response {:?} to function 0x{:02X}",
+                other, function
+            ))),
+        }
+    }
+
     pub async fn write_single_coil(&mut self, addr: u16, value: bool) -> Result<(), ModbusError> {
         match self {
             Context::Tcp(ctx) => {
@@ -141,3 +172,42 @@ impl Context {
         }
     }
 }
+
+/// Byte count, FIFO count, then the queued registers, all big-endian
+fn parse_fifo_response(data: &[u8]) -> Result<Vec<u16>, ModbusError> {
+    let word = |i: usize| u16::from_be_bytes([data[i], data[i + 1]]);
+    if data.len() < 4 || word(0) as usize != data.len() - 2 {
+        return Err(invalid_response("malformed FIFO queue response"));
+    }
+    let count = word(2) as usize;
+    if count > 31 || data.len() != 4 + 2 * count {
+        return Err(invalid_response("FIFO count does not match the response"));
+    }
+    Ok((0..count).map(|i| word(4 + 2 * i)).collect())
+}
+
+fn invalid_response(message: &str) -> ModbusError {
+    ModbusError::Io(std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        message.to_string(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_fifo_response() {
+        assert_eq!(
+            parse_fifo_response(&[0x00, 0x06, 0x00, 0x02, 0x01, 0xB8, 0x12, 0x84]).unwrap(),
+            vec![0x01B8, 0x1284]
+        );
+        assert!(parse_fifo_response(&[0x00, 0x02, 0x00, 0x00])
+            .unwrap()
+            .is_empty());
+        // Count of two but only one register
+        assert!(parse_fifo_response(&[0x00, 0x04, 0x00, 0x02, 0x01, 0xB8]).is_err());
+        assert!(parse_fifo_response(&[0x00]).is_err());
+    }
+}
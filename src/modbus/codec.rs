@@ -0,0 +1,978 @@
+//! Conversion between Modbus registers and typed values
+//!
+//! [`decode`] and [`encode`] convert between raw 16-bit registers and
+//! [`TypedValue`]s for every configured data type and [`ByteOrder`]. The
+//! poll loop, the write path and external tools share these conversions.
+
+use crate::config::{DataType, RegisterConfig};
+
+/// Order of the bytes of a multi-byte value across Modbus registers
+///
+/// Letters name the bytes of the value from most to least significant, in
+/// the order they appear on the wire. `Abcd` is standard Modbus big-endian.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ByteOrder {
+    /// Big-endian words, big-endian bytes
+    #[default]
+    Abcd,
+    /// Big-endian words, bytes swapped within each word
+    Badc,
+    /// Word-swapped (low word first), big-endian bytes
+    Cdab,
+    /// Fully little-endian
+    Dcba,
+}
+
+/// What to do when a register converts to NaN or infinity
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NonFinitePolicy {
+    /// Skip the reading; nothing is stored or published
+    #[default]
+    Drop,
+    /// Publish `null` with bad quality
+    Null,
+    /// Publish the last finite value again, marked as substituted
+    LastGood,
+}
+
+/// Quality of a stored or published value
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Quality {
+    #[default]
+    Good,
+    /// The reading was not a number; the value is `null`
+    Bad,
+    /// The reading was not a number; the value is the last good one
+    Substituted,
+}
+
+impl Quality {
+    pub fn is_good(&self) -> bool {
+        *self == Quality::Good
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Quality::Good => "good",
+            Quality::Bad => "bad",
+            Quality::Substituted => "substituted",
+        }
+    }
+}
+
+/// Serialize non-finite floats as `null` and read `null` back as NaN
+pub mod finite_or_null {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &f64, serializer: S) -> Result<S::Ok, S::Error> {
+        if value.is_finite() {
+            serializer.serialize_f64(*value)
+        } else {
+            serializer.serialize_none()
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+        Ok(Option::<f64>::deserialize(deserializer)?.unwrap_or(f64::NAN))
+    }
+}
+
+impl ByteOrder {
+    fn swaps_bytes(self) -> bool {
+        matches!(self, ByteOrder::Badc | ByteOrder::Dcba)
+    }
+
+    fn swaps_words(self) -> bool {
+        matches!(self, ByteOrder::Cdab | ByteOrder::Dcba)
+    }
+
+    /// Combine registers into a 16-bit value
+    fn word(self, raw: &[u16]) -> Option<u16> {
+        let word = *raw.first()?;
+        Some(if self.swaps_bytes() {
+            word.swap_bytes()
+        } else {
+            word
+        })
+    }
+
+    /// Combine registers into a 32-bit value
+    fn dword(self, raw: &[u16]) -> Option<u32> {
+        let [first, second] = *raw.get(..2)? else {
+            return None;
+        };
+        let (high, low) = if self.swaps_words() {
+            (second, first)
+        } else {
+            (first, second)
+        };
+        let (high, low) = if self.swaps_bytes() {
+            (high.swap_bytes(), low.swap_bytes())
+        } else {
+            (high, low)
+        };
+        Some((high as u32) << 16 | low as u32)
+    }
+
+    /// Combine registers into a 64-bit value
+    fn qword(self, raw: &[u16]) -> Option<u64> {
+        let mut words: [u16; 4] = raw.get(..4)?.try_into().ok()?;
+        if self.swaps_words() {
+            words.reverse();
+        }
+        Some(words.iter().fold(0, |qword, &word| {
+            let word = if self.swaps_bytes() {
+                word.swap_bytes()
+            } else {
+                word
+            };
+            qword << 16 | word as u64
+        }))
+    }
+
+    /// Split a 16-bit value into its register
+    fn split_word(self, word: u16) -> [u16; 1] {
+        [if self.swaps_bytes() {
+            word.swap_bytes()
+        } else {
+            word
+        }]
+    }
+
+    /// Split a 32-bit value into registers in wire order
+    fn split_dword(self, dword: u32) -> [u16; 2] {
+        let (high, low) = ((dword >> 16) as u16, dword as u16);
+        let (high, low) = if self.swaps_bytes() {
+            (high.swap_bytes(), low.swap_bytes())
+        } else {
+            (high, low)
+        };
+        if self.swaps_words() {
+            [low, high]
+        } else {
+            [high, low]
+        }
+    }
+
+    /// Split a 64-bit value into registers in wire order
+    fn split_qword(self, qword: u64) -> [u16; 4] {
+        let mut words = [48, 32, 16, 0].map(|shift| {
+            let word = (qword >> shift) as u16;
+            if self.swaps_bytes() {
+                word.swap_bytes()
+            } else {
+                word
+            }
+        });
+        if self.swaps_words() {
+            words.reverse();
+        }
+        words
+    }
+}
+
+/// A Rust type that can be decoded from Modbus registers
+pub trait FromRegisters: Sized {
+    /// Number of 16-bit registers holding one value
+    const REGISTERS: u16;
+
+    /// Decode from raw registers; `None` if there are too few
+    fn from_registers(raw: &[u16], order: ByteOrder) -> Option<Self>;
+}
+
+impl FromRegisters for u16 {
+    const REGISTERS: u16 = 1;
+
+    fn from_registers(raw: &[u16], order: ByteOrder) -> Option<Self> {
+        order.word(raw)
+    }
+}
+
+impl FromRegisters for i16 {
+    const REGISTERS: u16 = 1;
+
+    fn from_registers(raw: &[u16], order: ByteOrder) -> Option<Self> {
+        order.word(raw).map(|w| w as i16)
+    }
+}
+
+impl FromRegisters for u32 {
+    const REGISTERS: u16 = 2;
+
+    fn from_registers(raw: &[u16], order: ByteOrder) -> Option<Self> {
+        order.dword(raw)
+    }
+}
+
+impl FromRegisters for i32 {
+    const REGISTERS: u16 = 2;
+
+    fn from_registers(raw: &[u16], order: ByteOrder) -> Option<Self> {
+        order.dword(raw).map(|d| d as i32)
+    }
+}
+
+impl FromRegisters for u64 {
+    const REGISTERS: u16 = 4;
+
+    fn from_registers(raw: &[u16], order: ByteOrder) -> Option<Self> {
+        order.qword(raw)
+    }
+}
+
+impl FromRegisters for i64 {
+    const REGISTERS: u16 = 4;
+
+    fn from_registers(raw: &[u16], order: ByteOrder) -> Option<Self> {
+        order.qword(raw).map(|q| q as i64)
+    }
+}
+
+impl FromRegisters for f32 {
+    const REGISTERS: u16 = 2;
+
+    fn from_registers(raw: &[u16], order: ByteOrder) -> Option<Self> {
+        order.dword(raw).map(f32::from_bits)
+    }
+}
+
+impl FromRegisters for f64 {
+    const REGISTERS: u16 = 4;
+
+    fn from_registers(raw: &[u16], order: ByteOrder) -> Option<Self> {
+        order.qword(raw).map(f64::from_bits)
+    }
+}
+
+impl FromRegisters for bool {
+    const REGISTERS: u16 = 1;
+
+    fn from_registers(raw: &[u16], _order: ByteOrder) -> Option<Self> {
+        raw.first().map(|&w| w != 0)
+    }
+}
+
+/// A Rust type that can be encoded into Modbus registers
+pub trait ToRegisters {
+    /// Encode into raw registers in wire order
+    fn to_registers(&self, order: ByteOrder) -> Vec<u16>;
+}
+
+impl ToRegisters for u16 {
+    fn to_registers(&self, order: ByteOrder) -> Vec<u16> {
+        order.split_word(*self).to_vec()
+    }
+}
+
+impl ToRegisters for i16 {
+    fn to_registers(&self, order: ByteOrder) -> Vec<u16> {
+        order.split_word(*self as u16).to_vec()
+    }
+}
+
+impl ToRegisters for u32 {
+    fn to_registers(&self, order: ByteOrder) -> Vec<u16> {
+        order.split_dword(*self).to_vec()
+    }
+}
+
+impl ToRegisters for i32 {
+    fn to_registers(&self, order: ByteOrder) -> Vec<u16> {
+        order.split_dword(*self as u32).to_vec()
+    }
+}
+
+impl ToRegisters for u64 {
+    fn to_registers(&self, order: ByteOrder) -> Vec<u16> {
+        order.split_qword(*self).to_vec()
+    }
+}
+
+impl ToRegisters for i64 {
+    fn to_registers(&self, order: ByteOrder) -> Vec<u16> {
+        order.split_qword(*self as u64).to_vec()
+    }
+}
+
+impl ToRegisters for f32 {
+    fn to_registers(&self, order: ByteOrder) -> Vec<u16> {
+        order.split_dword(self.to_bits()).to_vec()
+    }
+}
+
+impl ToRegisters for f64 {
+    fn to_registers(&self, order: ByteOrder) -> Vec<u16> {
+        order.split_qword(self.to_bits()).to_vec()
+    }
+}
+
+impl ToRegisters for bool {
+    fn to_registers(&self, _order: ByteOrder) -> Vec<u16> {
+        vec![u16::from(*self)]
+    }
+}
+
+/// A decoded register value of the configured data type
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize)]
+#[serde(untagged)]
+pub enum TypedValue {
+    U16(u16),
+    I16(i16),
+    U32(u32),
+    I32(i32),
+    U64(u64),
+    I64(i64),
+    F32(f32),
+    F64(f64),
+    Bool(bool),
+}
+
+impl TypedValue {
+    /// Numeric value (booleans map to 0.0/1.0)
+    pub fn as_f64(&self) -> f64 {
+        match *self {
+            TypedValue::U16(v) => v as f64,
+            TypedValue::I16(v) => v as f64,
+            TypedValue::U32(v) => v as f64,
+            TypedValue::I32(v) => v as f64,
+            TypedValue::U64(v) => v as f64,
+            TypedValue::I64(v) => v as f64,
+            TypedValue::F32(v) => v as f64,
+            TypedValue::F64(v) => v,
+            TypedValue::Bool(v) => {
+                if v {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+
+    /// Convert a number to `data_type`; `None` if it is out of range or,
+    /// for integer types, not a whole number
+    pub fn from_f64(value: f64, data_type: &DataType) -> Option<Self> {
+        fn integer<T: TryFrom<i64>>(value: f64) -> Option<T> {
+            // i64::MAX rounds up to 2^63 as a float, which is already out of range
+            if !value.is_finite()
+                || value.fract() != 0.0
+                || value < i64::MIN as f64
+                || value >= i64::MAX as f64
+            {
+                return None;
+            }
+            T::try_from(value as i64).ok()
+        }
+
+        fn unsigned(value: f64) -> Option<u64> {
+            // Likewise u64::MAX rounds up to 2^64
+            if !value.is_finite() || value.fract() != 0.0 || value < 0.0 || value >= u64::MAX as f64
+            {
+                return None;
+            }
+            Some(value as u64)
+        }
+
+        Some(match data_type {
+            DataType::U16 => TypedValue::U16(integer(value)?),
+            DataType::I16 => TypedValue::I16(integer(value)?),
+            DataType::U32 => TypedValue::U32(integer(value)?),
+            DataType::I32 => TypedValue::I32(integer(value)?),
+            DataType::U64 => TypedValue::U64(unsigned(value)?),
+            DataType::I64 => TypedValue::I64(integer(value)?),
+            DataType::F32 => {
+                let narrowed = value as f32;
+                if value.is_finite() && !narrowed.is_finite() {
+                    return None;
+                }
+                TypedValue::F32(narrowed)
+            }
+            DataType::F64 => TypedValue::F64(value),
+            DataType::Bool => TypedValue::Bool(value != 0.0),
+        })
+    }
+}
+
+/// Decode raw registers as the given data type; `None` if there are too few
+pub fn decode(raw: &[u16], data_type: &DataType, order: ByteOrder) -> Option<TypedValue> {
+    Some(match data_type {
+        DataType::U16 => TypedValue::U16(u16::from_registers(raw, order)?),
+        DataType::I16 => TypedValue::I16(i16::from_registers(raw, order)?),
+        DataType::U32 => TypedValue::U32(u32::from_registers(raw, order)?),
+        DataType::I32 => TypedValue::I32(i32::from_registers(raw, order)?),
+        DataType::U64 => TypedValue::U64(u64::from_registers(raw, order)?),
+        DataType::I64 => TypedValue::I64(i64::from_registers(raw, order)?),
+        DataType::F32 => TypedValue::F32(f32::from_registers(raw, order)?),
+        DataType::F64 => TypedValue::F64(f64::from_registers(raw, order)?),
+        DataType::Bool => TypedValue::Bool(bool::from_registers(raw, order)?),
+    })
+}
+
+/// Encode a value into raw registers in wire order
+pub fn encode(value: &TypedValue, order: ByteOrder) -> Vec<u16> {
+    match value {
+        TypedValue::U16(v) => v.to_registers(order),
+        TypedValue::I16(v) => v.to_registers(order),
+        TypedValue::U32(v) => v.to_registers(order),
+        TypedValue::I32(v) => v.to_registers(order),
+        TypedValue::U64(v) => v.to_registers(order),
+        TypedValue::I64(v) => v.to_registers(order),
+        TypedValue::F32(v) => v.to_registers(order),
+        TypedValue::F64(v) => v.to_registers(order),
+        TypedValue::Bool(v) => v.to_registers(order),
+    }
+}
+
+/// Bit `bit` of an exception status byte (FC07) as a raw register value
+pub fn status_bit(status: u8, bit: u16) -> u16 {
+    u16::from(bit < 8 && status & (1 << bit) != 0)
+}
+
+/// Convert raw register values to typed value
+///
+/// The decoded value goes through the register's `lookup` table or
+/// `expression` if it has one, otherwise through `scale` and `offset`.
+pub fn convert_value(raw: &[u16], config: &RegisterConfig) -> f64 {
+    let raw_value = decode(raw, &config.data_type, config.byte_order).map_or(0.0, |v| v.as_f64());
+    if let Some(lookup) = &config.lookup {
+        return lookup.apply(raw_value);
+    }
+    if let Some(expression) = &config.expression {
+        return expression.eval(raw_value);
+    }
+
+    // Apply scale and offset
+    let scale = config.scale.unwrap_or(1.0);
+    let offset = config.offset.unwrap_or(0.0);
+
+    raw_value * scale + offset
+}
+
+/// Convert raw registers and apply the register's [`NonFinitePolicy`]
+///
+/// Returns `None` when the reading is dropped: with the `drop` policy, or
+/// with `last_good` before any finite value was read. `last_good` is the
+/// last finite value of the register.
+pub fn convert_reading(
+    raw: &[u16],
+    config: &RegisterConfig,
+    last_good: Option<f64>,
+) -> Option<(f64, Quality)> {
+    let value = convert_value(raw, config);
+    if value.is_finite() {
+        return Some((value, Quality::Good));
+    }
+    match config.non_finite {
+        NonFinitePolicy::Drop => None,
+        NonFinitePolicy::Null => Some((f64::NAN, Quality::Bad)),
+        NonFinitePolicy::LastGood => last_good.map(|v| (v, Quality::Substituted)),
+    }
+}
+
+/// Encode an engineering value for writing, inverting [`convert_value`]
+///
+/// The offset is subtracted and the result divided by the scale; integer
+/// types round to the nearest raw step, so `23.5` with `scale: 0.1` writes
+/// `235`. Fails if the raw value does not fit the data type.
+pub fn encode_value(value: f64, config: &RegisterConfig) -> std::result::Result<Vec<u16>, String> {
+    if config.expression.is_some() || config.lookup.is_some() {
+        return Err(format!(
+            "register '{}' has an expression or lookup table and cannot be written",
+            config.name
+        ));
+    }
+    let scale = config.scale.unwrap_or(1.0);
+    let offset = config.offset.unwrap_or(0.0);
+    if scale == 0.0 {
+        return Err(format!("register '{}' has a scale of 0", config.name));
+    }
+
+    let raw = (value - offset) / scale;
+    let raw = match config.data_type {
+        // Only on/off, not "anything non-zero"
+        DataType::Bool if raw != 0.0 && raw != 1.0 => {
+            return Err(format!(
+                "{} is not a boolean value for register '{}'",
+                value, config.name
+            ))
+        }
+        DataType::F32 | DataType::Bool => raw,
+        _ => raw.round(),
+    };
+    let typed = TypedValue::from_f64(raw, &config.data_type).ok_or_else(|| {
+        format!(
+            "{} is out of range for register '{}' (raw value {})",
+            value, config.name, raw
+        )
+    })?;
+    Ok(encode(&typed, config.byte_order))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RegisterType;
+    use proptest::prelude::*;
+
+    fn make_register_config(
+        data_type: DataType,
+        scale: Option<f64>,
+        offset: Option<f64>,
+    ) -> RegisterConfig {
+        RegisterConfig {
+            name: "test".to_string(),
+            address: 0,
+            register_type: RegisterType::Holding,
+            count: 1,
+            data_type,
+            unit: None,
+            scale,
+            offset,
+            byte_order: ByteOrder::Abcd,
+            non_finite: NonFinitePolicy::Drop,
+            priority: Default::default(),
+            meta: Default::default(),
+            read_when: None,
+            alarm: None,
+            write_limits: None,
+            oversample: None,
+            read_after: None,
+            trigger: None,
+            min_stable_ms: None,
+            persistent: false,
+            publish_on_change: false,
+            deadband: None,
+            heartbeat_interval_s: None,
+            points: Vec::new(),
+            expression: None,
+            lookup: None,
+        }
+    }
+
+    #[test]
+    fn test_convert_u16() {
+        let config = make_register_config(DataType::U16, None, None);
+
+        assert_eq!(convert_value(&[0], &config), 0.0);
+        assert_eq!(convert_value(&[100], &config), 100.0);
+        assert_eq!(convert_value(&[65535], &config), 65535.0);
+    }
+
+    #[test]
+    fn test_convert_i16() {
+        let config = make_register_config(DataType::I16, None, None);
+
+        assert_eq!(convert_value(&[0], &config), 0.0);
+        assert_eq!(convert_value(&[100], &config), 100.0);
+        // 65535 as i16 = -1
+        assert_eq!(convert_value(&[65535], &config), -1.0);
+        // 65436 as i16 = -100
+        assert_eq!(convert_value(&[65436], &config), -100.0);
+    }
+
+    #[test]
+    fn test_convert_u32() {
+        let config = make_register_config(DataType::U32, None, None);
+
+        // 0x00000000
+        assert_eq!(convert_value(&[0, 0], &config), 0.0);
+
+        // 0x00010000 = 65536
+        assert_eq!(convert_value(&[1, 0], &config), 65536.0);
+
+        // 0x00000001 = 1
+        assert_eq!(convert_value(&[0, 1], &config), 1.0);
+
+        // 0x0001FFFF = 131071
+        assert_eq!(convert_value(&[1, 65535], &config), 131071.0);
+
+        // 0xFFFFFFFF = 4294967295
+        assert_eq!(convert_value(&[65535, 65535], &config), 4294967295.0);
+    }
+
+    #[test]
+    fn test_convert_i32() {
+        let config = make_register_config(DataType::I32, None, None);
+
+        // 0
+        assert_eq!(convert_value(&[0, 0], &config), 0.0);
+
+        // 1
+        assert_eq!(convert_value(&[0, 1], &config), 1.0);
+
+        // -1 (0xFFFFFFFF)
+        assert_eq!(convert_value(&[65535, 65535], &config), -1.0);
+
+        // -100 (0xFFFFFF9C)
+        let neg100: i32 = -100;
+        let high = ((neg100 as u32) >> 16) as u16;
+        let low = (neg100 as u32) as u16;
+        assert_eq!(convert_value(&[high, low], &config), -100.0);
+    }
+
+    #[test]
+    fn test_convert_f32() {
+        let config = make_register_config(DataType::F32, None, None);
+
+        // IEEE 754: 0.0
+        assert_eq!(convert_value(&[0, 0], &config), 0.0);
+
+        // IEEE 754: 1.0 = 0x3F800000
+        let one_bits: u32 = 1.0_f32.to_bits();
+        let high = (one_bits >> 16) as u16;
+        let low = one_bits as u16;
+        assert!((convert_value(&[high, low], &config) - 1.0).abs() < 0.0001);
+
+        // IEEE 754: 3.14159... = 0x40490FDB
+        let pi_bits: u32 = std::f32::consts::PI.to_bits();
+        let high = (pi_bits >> 16) as u16;
+        let low = pi_bits as u16;
+        assert!((convert_value(&[high, low], &config) - std::f64::consts::PI).abs() < 0.0001);
+
+        // Negative value: -42.5
+        let neg_bits: u32 = (-42.5_f32).to_bits();
+        let high = (neg_bits >> 16) as u16;
+        let low = neg_bits as u16;
+        assert!((convert_value(&[high, low], &config) - (-42.5)).abs() < 0.0001);
+    }
+
+    #[test]
+    fn test_convert_bool() {
+        let config = make_register_config(DataType::Bool, None, None);
+
+        assert_eq!(convert_value(&[0], &config), 0.0);
+        assert_eq!(convert_value(&[1], &config), 1.0);
+        assert_eq!(convert_value(&[100], &config), 1.0);
+        assert_eq!(convert_value(&[65535], &config), 1.0);
+    }
+
+    #[test]
+    fn test_scale_factor() {
+        // Temperature sensor: raw value * 0.1 = actual temperature
+        let config = make_register_config(DataType::U16, Some(0.1), None);
+
+        assert_eq!(convert_value(&[250], &config), 25.0);
+        assert_eq!(convert_value(&[1000], &config), 100.0);
+    }
+
+    #[test]
+    fn test_offset() {
+        // Sensor with offset calibration
+        let config = make_register_config(DataType::I16, None, Some(100.0));
+
+        assert_eq!(convert_value(&[0], &config), 100.0);
+        assert_eq!(convert_value(&[50], &config), 150.0);
+    }
+
+    #[test]
+    fn test_scale_and_offset() {
+        // Temperature sensor: (raw * 0.1) + (-40) for Celsius
+        let config = make_register_config(DataType::U16, Some(0.1), Some(-40.0));
+
+        // Raw 400 = 40.0 - 40.0 = 0.0°C
+        assert_eq!(convert_value(&[400], &config), 0.0);
+
+        // Raw 650 = 65.0 - 40.0 = 25.0°C
+        assert_eq!(convert_value(&[650], &config), 25.0);
+    }
+
+    #[test]
+    fn test_empty_raw_values() {
+        let config = make_register_config(DataType::U16, None, None);
+        assert_eq!(convert_value(&[], &config), 0.0);
+
+        let config32 = make_register_config(DataType::U32, None, None);
+        assert_eq!(convert_value(&[], &config32), 0.0);
+        assert_eq!(convert_value(&[1], &config32), 0.0); // Not enough values
+    }
+
+    #[test]
+    fn test_industrial_temperature_sensor() {
+        // Typical industrial temperature sensor:
+        // - Returns raw value as 10x actual temperature
+        // - Range: -40°C to 125°C
+        // - Signed value for negative temperatures
+        let config = make_register_config(DataType::I16, Some(0.1), None);
+
+        // -40°C = raw -400
+        let raw_neg40: u16 = (-400_i16) as u16;
+        assert!((convert_value(&[raw_neg40], &config) - (-40.0)).abs() < 0.01);
+
+        // 0°C = raw 0
+        assert_eq!(convert_value(&[0], &config), 0.0);
+
+        // 25°C = raw 250
+        assert!((convert_value(&[250], &config) - 25.0).abs() < 0.01);
+
+        // 125°C = raw 1250
+        assert!((convert_value(&[1250], &config) - 125.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_pressure_sensor_psi() {
+        // Pressure sensor: 0-10000 raw = 0-100 PSI
+        let config = make_register_config(DataType::U16, Some(0.01), None);
+
+        assert_eq!(convert_value(&[0], &config), 0.0);
+        assert_eq!(convert_value(&[5000], &config), 50.0);
+        assert_eq!(convert_value(&[10000], &config), 100.0);
+    }
+
+    #[test]
+    fn test_flow_meter_with_u32() {
+        // Flow meter: 32-bit counter in liters
+        let config = make_register_config(DataType::U32, None, None);
+
+        // 1,000,000 liters
+        let value: u32 = 1_000_000;
+        let high = (value >> 16) as u16;
+        let low = value as u16;
+        assert_eq!(convert_value(&[high, low], &config), 1_000_000.0);
+    }
+
+    #[test]
+    fn test_byte_orders() {
+        // 0x11223344 laid out in each order
+        let cases = [
+            (ByteOrder::Abcd, [0x1122, 0x3344]),
+            (ByteOrder::Badc, [0x2211, 0x4433]),
+            (ByteOrder::Cdab, [0x3344, 0x1122]),
+            (ByteOrder::Dcba, [0x4433, 0x2211]),
+        ];
+        for (order, raw) in cases {
+            assert_eq!(
+                u32::from_registers(&raw, order),
+                Some(0x1122_3344),
+                "{:?}",
+                order
+            );
+        }
+
+        assert_eq!(
+            u16::from_registers(&[0x1122], ByteOrder::Badc),
+            Some(0x2211)
+        );
+        assert_eq!(
+            u16::from_registers(&[0x1122], ByteOrder::Cdab),
+            Some(0x1122)
+        );
+    }
+
+    #[test]
+    fn test_from_registers_f32_word_swapped() {
+        let bits = 230.5_f32.to_bits();
+        let raw = [bits as u16, (bits >> 16) as u16];
+        assert_eq!(f32::from_registers(&raw, ByteOrder::Cdab), Some(230.5));
+        assert_eq!(f32::from_registers(&raw[..1], ByteOrder::Cdab), None);
+    }
+
+    #[test]
+    fn test_from_registers_64_bit() {
+        // 0x0102030405060708 laid out in each order
+        let cases = [
+            (ByteOrder::Abcd, [0x0102, 0x0304, 0x0506, 0x0708]),
+            (ByteOrder::Badc, [0x0201, 0x0403, 0x0605, 0x0807]),
+            (ByteOrder::Cdab, [0x0708, 0x0506, 0x0304, 0x0102]),
+            (ByteOrder::Dcba, [0x0807, 0x0605, 0x0403, 0x0201]),
+        ];
+        for (order, raw) in cases {
+            assert_eq!(
+                u64::from_registers(&raw, order),
+                Some(0x0102_0304_0506_0708),
+                "{:?}",
+                order
+            );
+        }
+
+        let bits = 1234.5678_f64.to_bits();
+        let raw = [0, 16, 32, 48].map(|shift| (bits >> shift) as u16);
+        assert_eq!(f64::from_registers(&raw, ByteOrder::Cdab), Some(1234.5678));
+        assert_eq!(f64::from_registers(&raw[..3], ByteOrder::Cdab), None);
+    }
+
+    #[test]
+    fn test_decode_typed() {
+        assert_eq!(
+            decode(&[65535], &DataType::I16, ByteOrder::Abcd),
+            Some(TypedValue::I16(-1))
+        );
+        assert_eq!(
+            decode(&[1, 0], &DataType::U32, ByteOrder::Abcd),
+            Some(TypedValue::U32(65536))
+        );
+        assert_eq!(
+            decode(&[2], &DataType::Bool, ByteOrder::Abcd),
+            Some(TypedValue::Bool(true))
+        );
+        assert_eq!(decode(&[1], &DataType::F32, ByteOrder::Abcd), None);
+        assert_eq!(TypedValue::Bool(true).as_f64(), 1.0);
+        assert_eq!(
+            serde_json::to_value(TypedValue::I32(-5)).unwrap(),
+            serde_json::json!(-5)
+        );
+    }
+
+    #[test]
+    fn test_encode_byte_orders() {
+        // 0x12345678 with each byte order, as in test_byte_orders
+        let value = TypedValue::U32(0x1234_5678);
+        assert_eq!(encode(&value, ByteOrder::Abcd), vec![0x1234, 0x5678]);
+        assert_eq!(encode(&value, ByteOrder::Badc), vec![0x3412, 0x7856]);
+        assert_eq!(encode(&value, ByteOrder::Cdab), vec![0x5678, 0x1234]);
+        assert_eq!(encode(&value, ByteOrder::Dcba), vec![0x7856, 0x3412]);
+        assert_eq!(encode(&TypedValue::I16(-1), ByteOrder::Abcd), vec![0xFFFF]);
+        assert_eq!(encode(&TypedValue::Bool(true), ByteOrder::Dcba), vec![1]);
+    }
+
+    #[test]
+    fn test_from_f64() {
+        assert_eq!(
+            TypedValue::from_f64(235.0, &DataType::U16),
+            Some(TypedValue::U16(235))
+        );
+        assert_eq!(
+            TypedValue::from_f64(-40.0, &DataType::I16),
+            Some(TypedValue::I16(-40))
+        );
+        assert_eq!(TypedValue::from_f64(-1.0, &DataType::U16), None);
+        assert_eq!(TypedValue::from_f64(70000.0, &DataType::U16), None);
+        assert_eq!(TypedValue::from_f64(23.5, &DataType::I32), None);
+        assert_eq!(TypedValue::from_f64(f64::NAN, &DataType::U32), None);
+        assert_eq!(TypedValue::from_f64(1e39, &DataType::F32), None);
+        assert_eq!(
+            TypedValue::from_f64(23.5, &DataType::F32),
+            Some(TypedValue::F32(23.5))
+        );
+        assert_eq!(
+            TypedValue::from_f64(1e15, &DataType::U64),
+            Some(TypedValue::U64(1_000_000_000_000_000))
+        );
+        assert_eq!(TypedValue::from_f64(-1.0, &DataType::U64), None);
+        assert_eq!(TypedValue::from_f64(2f64.powi(64), &DataType::U64), None);
+        assert_eq!(
+            TypedValue::from_f64(1e300, &DataType::F64),
+            Some(TypedValue::F64(1e300))
+        );
+    }
+
+    #[test]
+    fn test_status_bit() {
+        assert_eq!(status_bit(0b1000_0001, 0), 1);
+        assert_eq!(status_bit(0b1000_0001, 1), 0);
+        assert_eq!(status_bit(0b1000_0001, 7), 1);
+        assert_eq!(status_bit(0xFF, 8), 0);
+    }
+
+    #[test]
+    fn test_convert_reading_non_finite() {
+        let mut config = make_register_config(DataType::F32, Some(2.0), None);
+        let nan = 0x7FC0_0000u32.to_registers(ByteOrder::Abcd);
+        let inf = f32::INFINITY.to_registers(ByteOrder::Abcd);
+        let two = 1.0f32.to_registers(ByteOrder::Abcd);
+
+        assert_eq!(
+            convert_reading(&two, &config, None),
+            Some((2.0, Quality::Good))
+        );
+        assert_eq!(convert_reading(&nan, &config, Some(4.0)), None);
+
+        config.non_finite = NonFinitePolicy::Null;
+        let (value, quality) = convert_reading(&inf, &config, Some(4.0)).unwrap();
+        assert!(value.is_nan());
+        assert_eq!(quality, Quality::Bad);
+
+        config.non_finite = NonFinitePolicy::LastGood;
+        assert_eq!(
+            convert_reading(&nan, &config, Some(4.0)),
+            Some((4.0, Quality::Substituted))
+        );
+        assert_eq!(convert_reading(&nan, &config, None), None);
+    }
+
+    #[test]
+    fn test_encode_value_inverts_scaling() {
+        let config = make_register_config(DataType::U16, Some(0.1), None);
+        assert_eq!(encode_value(23.5, &config), Ok(vec![235]));
+        // Rounded to the nearest raw step
+        assert_eq!(encode_value(23.46, &config), Ok(vec![235]));
+        assert!(encode_value(-1.0, &config).is_err());
+        assert!(encode_value(6553.6, &config).is_err());
+
+        let config = make_register_config(DataType::I16, Some(0.1), Some(-40.0));
+        assert_eq!(encode_value(-50.0, &config), Ok(vec![(-100i16) as u16]));
+
+        let config = make_register_config(DataType::F32, Some(2.0), None);
+        assert_eq!(
+            encode_value(3.0, &config),
+            Ok(1.5f32.to_registers(ByteOrder::Abcd))
+        );
+
+        let config = make_register_config(DataType::U16, Some(0.0), None);
+        assert!(encode_value(1.0, &config).is_err());
+
+        let config = make_register_config(DataType::Bool, None, None);
+        assert_eq!(encode_value(1.0, &config), Ok(vec![1]));
+        assert_eq!(encode_value(0.0, &config), Ok(vec![0]));
+        assert!(encode_value(2.0, &config).is_err());
+    }
+
+    fn byte_order() -> impl Strategy<Value = ByteOrder> {
+        prop_oneof![
+            Just(ByteOrder::Abcd),
+            Just(ByteOrder::Badc),
+            Just(ByteOrder::Cdab),
+            Just(ByteOrder::Dcba),
+        ]
+    }
+
+    fn typed_value() -> impl Strategy<Value = (TypedValue, DataType)> {
+        prop_oneof![
+            any::<u16>().prop_map(|v| (TypedValue::U16(v), DataType::U16)),
+            any::<i16>().prop_map(|v| (TypedValue::I16(v), DataType::I16)),
+            any::<u32>().prop_map(|v| (TypedValue::U32(v), DataType::U32)),
+            any::<i32>().prop_map(|v| (TypedValue::I32(v), DataType::I32)),
+            any::<u64>().prop_map(|v| (TypedValue::U64(v), DataType::U64)),
+            any::<i64>().prop_map(|v| (TypedValue::I64(v), DataType::I64)),
+            // Finite floats; NaN never compares equal to itself
+            (-1e30f32..1e30f32).prop_map(|v| (TypedValue::F32(v), DataType::F32)),
+            (-1e300f64..1e300f64).prop_map(|v| (TypedValue::F64(v), DataType::F64)),
+            any::<bool>().prop_map(|v| (TypedValue::Bool(v), DataType::Bool)),
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn prop_encode_decode_roundtrip((value, data_type) in typed_value(), order in byte_order()) {
+            let raw = encode(&value, order);
+            prop_assert_eq!(raw.len(), data_type.register_count() as usize);
+            prop_assert_eq!(decode(&raw, &data_type, order), Some(value));
+        }
+
+        #[test]
+        fn prop_decode_encode_roundtrip(raw in any::<[u16; 2]>(), order in byte_order()) {
+            for data_type in [DataType::U16, DataType::I16, DataType::U32, DataType::I32, DataType::F32] {
+                let value = decode(&raw, &data_type, order).unwrap();
+                let encoded = encode(&value, order);
+                // Compare bit patterns so NaN payloads count as equal
+                let expected = &raw[..encoded.len()];
+                prop_assert_eq!(encoded.as_slice(), expected);
+            }
+        }
+
+        #[test]
+        fn prop_encode_value_roundtrip(raw in any::<i16>(), scale in prop_oneof![Just(0.1), Just(0.5), Just(10.0)]) {
+            let config = make_register_config(DataType::I16, Some(scale), Some(-40.0));
+            let value = convert_value(&[raw as u16], &config);
+            prop_assert_eq!(encode_value(value, &config), Ok(vec![raw as u16]));
+        }
+
+        #[test]
+        fn prop_from_f64_matches_integer_value(v in any::<i16>()) {
+            let value = TypedValue::from_f64(v as f64, &DataType::I16).unwrap();
+            prop_assert_eq!(value.as_f64(), v as f64);
+        }
+    }
+}
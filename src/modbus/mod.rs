@@ -2,105 +2,99 @@
 //!
 //! Supports both TCP and RTU (serial) connections
 
-use anyhow::{Context as AnyhowContext, Result};
+use std::future::Future;
 use std::net::SocketAddr;
+use std::time::Duration;
 use tokio_modbus::prelude::*;
-use tokio_serial::SerialPortBuilderExt;
-use tracing::{debug, info, warn};
-
-use crate::config::{ConnectionConfig, DeviceConfig, RegisterConfig, RegisterType};
-
+use tracing::{debug, info};
+
+use crate::config::{
+    ConnectionConfig, DeviceConfig, RegisterConfig, RegisterType, RtuConnection, TcpConnection,
+};
+use crate::error::{Result, RustbridgeError};
+use crate::metrics::bus_stats::{self, BusCounters};
+
+pub mod broker;
+pub mod builder;
+#[cfg(feature = "rtu")]
+pub mod bus_monitor;
 pub mod client;
+pub mod codec;
 pub mod reader;
+#[cfg(feature = "rtu")]
+pub mod sniffer;
+pub mod time_sync;
+pub mod transform;
+pub mod transport;
+
+pub use builder::ModbusClientBuilder;
+pub use transport::{MockTransport, ModbusTransport};
+
+/// Time the slaves get to process a broadcast write before the bus is used again
+pub const BROADCAST_TURNAROUND: Duration = Duration::from_millis(100);
 
 /// Modbus client abstraction supporting TCP and RTU
 pub struct ModbusClient {
     device_id: String,
     device_type: String,
-    context: Option<client::Context>,
+    context: Option<Box<dyn ModbusTransport>>,
+    timeout: Option<Duration>,
+    /// The transport applies `timeout` itself (a shared connection's broker)
+    transport_timeout: bool,
+    serial_port: Option<String>,
+    bus: Option<std::sync::Arc<BusCounters>>,
+    retries: u8,
+    /// RTU unit 0: writes reach every slave and nobody responds
+    broadcast: bool,
 }
 
 impl ModbusClient {
+    /// Build a client without a full `DeviceConfig`, e.g.
+    /// `ModbusClient::builder().tcp("10.0.0.5:502").unit(3).build().await`
+    pub fn builder() -> ModbusClientBuilder {
+        ModbusClientBuilder::default()
+    }
+
     /// Create a new Modbus client from device configuration
     pub async fn new(config: &DeviceConfig) -> Result<Self> {
         info!("Initializing Modbus client for device: {}", config.id);
+        if matches!(&config.connection, ConnectionConfig::Rtu(rtu) if rtu.listen_only) {
+            return Err(RustbridgeError::Config(format!(
+                "Device {} is listen_only and must not be polled",
+                config.id
+            )));
+        }
 
-        let (context, device_type) = match &config.connection {
+        let mut serial_port = None;
+        let mut timeout = None;
+        let mut retries = 0;
+        let mut broadcast = false;
+        let (context, device_type): (Box<dyn ModbusTransport>, _) = match &config.connection {
             ConnectionConfig::Tcp(tcp) => {
-                let addr: SocketAddr = format!("{}:{}", tcp.host, tcp.port)
-                    .parse()
-                    .with_context(|| "Invalid TCP address")?;
+                let addr: SocketAddr =
+                    format!("{}:{}", tcp.host, tcp.port).parse().map_err(|e| {
+                        RustbridgeError::Config(format!(
+                            "Invalid TCP address {}:{}: {}",
+                            tcp.host, tcp.port, e
+                        ))
+                    })?;
 
                 info!("Connecting to Modbus TCP: {} (unit {})", addr, tcp.unit_id);
 
-                let ctx = tcp::connect_slave(addr, Slave(tcp.unit_id))
-                    .await
-                    .with_context(|| format!("Failed to connect to {}", addr))?;
+                let stream = open_tcp(addr, tcp).await.map_err(|e| {
+                    RustbridgeError::Connection(format!("Failed to connect to {}: {}", addr, e))
+                })?;
+                let ctx = tcp::attach_slave(stream, Slave(tcp.unit_id));
 
-                (Some(client::Context::Tcp(ctx)), "TCP".to_string())
+                (Box::new(client::Context::Tcp(ctx)), "TCP".to_string())
             }
             ConnectionConfig::Rtu(rtu) => {
-                info!(
-                    "Connecting to Modbus RTU: {} @ {} baud (unit {})",
-                    rtu.port, rtu.baud_rate, rtu.unit_id
-                );
-
-                // Parse parity
-                let parity = match rtu.parity.to_lowercase().as_str() {
-                    "none" => tokio_serial::Parity::None,
-                    "even" => tokio_serial::Parity::Even,
-                    "odd" => tokio_serial::Parity::Odd,
-                    _ => {
-                        warn!("Unknown parity '{}', using None", rtu.parity);
-                        tokio_serial::Parity::None
-                    }
-                };
-
-                // Parse stop bits
-                let stop_bits = match rtu.stop_bits {
-                    1 => tokio_serial::StopBits::One,
-                    2 => tokio_serial::StopBits::Two,
-                    _ => {
-                        warn!("Unknown stop bits {}, using 1", rtu.stop_bits);
-                        tokio_serial::StopBits::One
-                    }
-                };
-
-                // Parse data bits
-                let data_bits = match rtu.data_bits {
-                    5 => tokio_serial::DataBits::Five,
-                    6 => tokio_serial::DataBits::Six,
-                    7 => tokio_serial::DataBits::Seven,
-                    8 => tokio_serial::DataBits::Eight,
-                    _ => {
-                        warn!("Unknown data bits {}, using 8", rtu.data_bits);
-                        tokio_serial::DataBits::Eight
-                    }
-                };
-
-                // Create serial port builder
-                let builder = tokio_serial::new(&rtu.port, rtu.baud_rate)
-                    .parity(parity)
-                    .stop_bits(stop_bits)
-                    .data_bits(data_bits);
-
-                // Open serial port
-                let port = builder.open_native_async().with_context(|| {
-                    format!(
-                        "Failed to open serial port {} at {} baud",
-                        rtu.port, rtu.baud_rate
-                    )
-                })?;
-
-                info!(
-                    "Serial port {} opened: {} baud, {} data bits, {:?} parity, {:?} stop bits",
-                    rtu.port, rtu.baud_rate, rtu.data_bits, parity, stop_bits
-                );
-
-                // Create RTU context
-                let ctx = rtu::attach_slave(port, Slave(rtu.unit_id));
-
-                (Some(client::Context::Rtu(ctx)), "RTU".to_string())
+                let (transport, port) = open_rtu(rtu)?;
+                serial_port = Some(port);
+                timeout = rtu.response_timeout_ms.map(Duration::from_millis);
+                retries = rtu.retries;
+                broadcast = rtu.unit_id == 0;
+                (transport, "RTU".to_string())
             }
         };
 
@@ -109,19 +103,94 @@ impl ModbusClient {
             device_type, config.id
         );
 
+        let mut context = context;
+        let transport_timeout = context.set_timeout(timeout);
         Ok(Self {
             device_id: config.id.clone(),
             device_type,
-            context,
+            context: Some(context),
+            timeout,
+            transport_timeout,
+            bus: serial_port.as_deref().map(bus_stats::bus),
+            serial_port,
+            retries,
+            broadcast,
         })
     }
 
+    /// Create a client on a custom transport, e.g. a [`MockTransport`] in tests
+    pub fn with_transport(
+        device_id: impl Into<String>,
+        transport: impl ModbusTransport + 'static,
+    ) -> Self {
+        Self {
+            device_id: device_id.into(),
+            device_type: "custom".to_string(),
+            context: Some(Box::new(transport)),
+            timeout: None,
+            transport_timeout: false,
+            serial_port: None,
+            bus: None,
+            retries: 0,
+            broadcast: false,
+        }
+    }
+
+    /// Serial port the client was opened on (RTU only), after resolving a USB match
+    pub fn serial_port(&self) -> Option<&str> {
+        self.serial_port.as_deref()
+    }
+
     /// Read registers from the device
+    ///
+    /// On an RTU bus, a read that times out or returns a corrupt frame is sent
+    /// again up to `retries` times.
     pub async fn read_registers(&mut self, register: &RegisterConfig) -> Result<Vec<u16>> {
+        if self.broadcast {
+            return Err(broadcast_read_error());
+        }
+        let mut attempt = 0;
+        loop {
+            let result = self.read_registers_once(register).await;
+            let Err(e) = &result else {
+                return result;
+            };
+            let retryable = self.record_bus_error(e);
+            if !retryable || attempt >= self.retries {
+                return result;
+            }
+            attempt += 1;
+            if let Some(bus) = &self.bus {
+                bus.record_retransmission();
+            }
+            debug!(
+                "Retrying read of {} on device {} ({}/{}): {}",
+                register.name, self.device_id, attempt, self.retries, e
+            );
+        }
+    }
+
+    /// Count a failed request in the bus statistics and tell whether it may be retried
+    fn record_bus_error(&self, error: &RustbridgeError) -> bool {
+        let Some(bus) = &self.bus else {
+            return false;
+        };
+        match error {
+            RustbridgeError::Timeout => {
+                bus.record_timeout();
+                true
+            }
+            RustbridgeError::InvalidResponse(_) => true,
+            _ => false,
+        }
+    }
+
+    async fn read_registers_once(&mut self, register: &RegisterConfig) -> Result<Vec<u16>> {
+        let timeout = self.request_timeout();
         let ctx = self
             .context
             .as_mut()
-            .ok_or_else(|| anyhow::anyhow!("No connection available"))?;
+            .ok_or_else(|| RustbridgeError::Connection("No connection available".to_string()))?;
 
         let values = match register.register_type {
             RegisterType::Holding => {
@@ -129,52 +198,165 @@ impl ModbusClient {
                     "Reading {} holding registers from address {} ({})",
                     register.count, register.address, self.device_type
                 );
-                ctx.read_holding_registers(register.address, register.count)
-                    .await
-                    .map_err(|e| anyhow::anyhow!("Modbus error: {}", e))?
+                within(
+                    timeout,
+                    ctx.read_holding_registers(register.address, register.count),
+                )
+                .await?
             }
             RegisterType::Input => {
                 debug!(
                     "Reading {} input registers from address {} ({})",
                     register.count, register.address, self.device_type
                 );
-                ctx.read_input_registers(register.address, register.count)
-                    .await
-                    .map_err(|e| anyhow::anyhow!("Modbus error: {}", e))?
+                within(
+                    timeout,
+                    ctx.read_input_registers(register.address, register.count),
+                )
+                .await?
             }
             RegisterType::Coil => {
-                let coils = ctx
-                    .read_coils(register.address, register.count)
-                    .await
-                    .map_err(|e| anyhow::anyhow!("Modbus error: {}", e))?;
+                let coils =
+                    within(timeout, ctx.read_coils(register.address, register.count)).await?;
                 coils.iter().map(|&b| if b { 1u16 } else { 0u16 }).collect()
             }
             RegisterType::Discrete => {
-                let inputs = ctx
-                    .read_discrete_inputs(register.address, register.count)
-                    .await
-                    .map_err(|e| anyhow::anyhow!("Modbus error: {}", e))?;
+                let inputs = within(
+                    timeout,
+                    ctx.read_discrete_inputs(register.address, register.count),
+                )
+                .await?;
                 inputs
                     .iter()
                     .map(|&b| if b { 1u16 } else { 0u16 })
                     .collect()
             }
+            RegisterType::ExceptionStatus => {
+                let status = within(timeout, ctx.read_exception_status()).await?;
+                vec![codec::status_bit(status, register.address)]
+            }
+            // One FC24 read; the poll loop drains the queue with `read_fifo_queue`
+            RegisterType::Fifo => within(timeout, ctx.read_fifo_queue(register.address)).await?,
         };
 
         Ok(values)
     }
 
-    /// Write a single register
-    #[allow(dead_code)]
-    pub async fn write_register(&mut self, address: u16, value: u16) -> Result<()> {
+    /// Read the entries queued in the FIFO at `address` (FC24), at most 31
+    pub async fn read_fifo_queue(&mut self, address: u16) -> Result<Vec<u16>> {
+        if self.broadcast {
+            return Err(broadcast_read_error());
+        }
+        let timeout = self.request_timeout();
         let ctx = self
             .context
             .as_mut()
-            .ok_or_else(|| anyhow::anyhow!("No connection available"))?;
+            .ok_or_else(|| RustbridgeError::Connection("No connection available".to_string()))?;
 
-        ctx.write_single_register(address, value)
+        debug!(
+            "Reading FIFO queue at address {} ({})",
+            address, self.device_type
+        );
+        let result = within(timeout, ctx.read_fifo_queue(address))
             .await
-            .map_err(|e| anyhow::anyhow!("Modbus write error: {}", e))?;
+            .map_err(RustbridgeError::from);
+        if let Err(e) = &result {
+            self.record_bus_error(e);
+        }
+        result
+    }
+
+    /// Read the eight exception status bits of the device (FC07)
+    pub async fn read_exception_status(&mut self) -> Result<u8> {
+        if self.broadcast {
+            return Err(broadcast_read_error());
+        }
+        let timeout = self.request_timeout();
+        let ctx = self
+            .context
+            .as_mut()
+            .ok_or_else(|| RustbridgeError::Connection("No connection available".to_string()))?;
+
+        debug!("Reading exception status ({})", self.device_type);
+        let result = within(timeout, ctx.read_exception_status())
+            .await
+            .map_err(RustbridgeError::from);
+        if let Err(e) = &result {
+            self.record_bus_error(e);
+        }
+        result
+    }
+
+    /// Read a value of type `T` from holding registers starting at `address`,
+    /// e.g. `client.read_typed::<f32>(100, ByteOrder::Cdab)`
+    pub async fn read_typed<T: codec::FromRegisters>(
+        &mut self,
+        address: u16,
+        order: codec::ByteOrder,
+    ) -> Result<T> {
+        if self.broadcast {
+            return Err(broadcast_read_error());
+        }
+        let timeout = self.request_timeout();
+        let ctx = self
+            .context
+            .as_mut()
+            .ok_or_else(|| RustbridgeError::Connection("No connection available".to_string()))?;
+
+        let raw = within(timeout, ctx.read_holding_registers(address, T::REGISTERS)).await?;
+        T::from_registers(&raw, order).ok_or_else(|| {
+            RustbridgeError::Connection(format!(
+                "Device returned {} registers, expected {}",
+                raw.len(),
+                T::REGISTERS
+            ))
+        })
+    }
+
+    /// Read a configured register and decode it as its data type (without scaling)
+    pub async fn read_as(&mut self, register: &RegisterConfig) -> Result<codec::TypedValue> {
+        let raw = self.read_registers(register).await?;
+        codec::decode(&raw, &register.data_type, register.byte_order).ok_or_else(|| {
+            RustbridgeError::Config(format!(
+                "Register {} returned {} registers, too few for {:?}",
+                register.name,
+                raw.len(),
+                register.data_type
+            ))
+        })
+    }
+
+    /// Write a value of type `T` to consecutive holding registers (FC16),
+    /// e.g. `client.write_typed(100, &21.5f32, ByteOrder::Cdab)`
+    pub async fn write_typed<T: codec::ToRegisters>(
+        &mut self,
+        address: u16,
+        value: &T,
+        order: codec::ByteOrder,
+    ) -> Result<()> {
+        self.write_registers(address, &value.to_registers(order))
+            .await
+    }
+
+    /// Write a single register
+    pub async fn write_register(&mut self, address: u16, value: u16) -> Result<()> {
+        let timeout = self.request_timeout();
+        let ctx = self
+            .context
+            .as_mut()
+            .ok_or_else(|| RustbridgeError::Connection("No connection available".to_string()))?;
+
+        if let Err(e) = send_write(
+            timeout,
+            self.broadcast,
+            ctx.write_single_register(address, value),
+        )
+        .await
+        {
+            let e = RustbridgeError::from(e);
+            self.record_bus_error(&e);
+            return Err(e);
+        }
 
         info!(
             "Wrote value {} to register {} on device {} ({})",
@@ -185,16 +367,24 @@ impl ModbusClient {
     }
 
     /// Write multiple registers
-    #[allow(dead_code)]
     pub async fn write_registers(&mut self, address: u16, values: &[u16]) -> Result<()> {
+        let timeout = self.request_timeout();
         let ctx = self
             .context
             .as_mut()
-            .ok_or_else(|| anyhow::anyhow!("No connection available"))?;
-
-        ctx.write_multiple_registers(address, values)
-            .await
-            .map_err(|e| anyhow::anyhow!("Modbus write error: {}", e))?;
+            .ok_or_else(|| RustbridgeError::Connection("No connection available".to_string()))?;
+
+        if let Err(e) = send_write(
+            timeout,
+            self.broadcast,
+            ctx.write_multiple_registers(address, values),
+        )
+        .await
+        {
+            let e = RustbridgeError::from(e);
+            self.record_bus_error(&e);
+            return Err(e);
+        }
 
         info!(
             "Wrote {} registers starting at {} on device {} ({})",
@@ -208,16 +398,24 @@ impl ModbusClient {
     }
 
     /// Write a single coil
-    #[allow(dead_code)]
     pub async fn write_coil(&mut self, address: u16, value: bool) -> Result<()> {
+        let timeout = self.request_timeout();
         let ctx = self
             .context
             .as_mut()
-            .ok_or_else(|| anyhow::anyhow!("No connection available"))?;
-
-        ctx.write_single_coil(address, value)
-            .await
-            .map_err(|e| anyhow::anyhow!("Modbus write error: {}", e))?;
+            .ok_or_else(|| RustbridgeError::Connection("No connection available".to_string()))?;
+
+        if let Err(e) = send_write(
+            timeout,
+            self.broadcast,
+            ctx.write_single_coil(address, value),
+        )
+        .await
+        {
+            let e = RustbridgeError::from(e);
+            self.record_bus_error(&e);
+            return Err(e);
+        }
 
         info!(
             "Wrote coil {} = {} on device {} ({})",
@@ -227,19 +425,241 @@ impl ModbusClient {
         Ok(())
     }
 
+    /// Set the timeout applied to each request (`None` waits indefinitely)
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+        if let Some(ctx) = self.context.as_mut() {
+            self.transport_timeout = ctx.set_timeout(timeout);
+        }
+    }
+
+    /// Timeout to wrap each request in, unless the transport applies it
+    fn request_timeout(&self) -> Option<Duration> {
+        self.timeout.filter(|_| !self.transport_timeout)
+    }
+
+    /// Request timeout, if any
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
     /// Check if connection is alive
-    #[allow(dead_code)]
     pub fn is_connected(&self) -> bool {
         self.context.is_some()
     }
 
     /// Get device type (TCP or RTU)
-    #[allow(dead_code)]
     pub fn device_type(&self) -> &str {
         &self.device_type
     }
 }
 
+/// Send a write request; a broadcast is never answered, so it counts as
+/// delivered once the turnaround delay (the request timeout, by default
+/// [`BROADCAST_TURNAROUND`]) has passed without an error
+async fn send_write(
+    timeout: Option<Duration>,
+    broadcast: bool,
+    request: impl Future<Output = Result<(), client::ModbusError>>,
+) -> Result<(), client::ModbusError> {
+    if !broadcast {
+        return within(timeout, request).await;
+    }
+    tokio::time::timeout(timeout.unwrap_or(BROADCAST_TURNAROUND), request)
+        .await
+        .unwrap_or(Ok(()))
+}
+
+fn broadcast_read_error() -> RustbridgeError {
+    RustbridgeError::Config("unit 0 is the RTU broadcast address and cannot be read".to_string())
+}
+
+/// Apply the optional request timeout
+async fn within<T>(
+    timeout: Option<Duration>,
+    request: impl Future<Output = Result<T, client::ModbusError>>,
+) -> Result<T, client::ModbusError> {
+    match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, request)
+            .await
+            .unwrap_or_else(|_| {
+                Err(client::ModbusError::Io(std::io::Error::new(
+                    std::io::ErrorKind::TimedOut,
+                    "request timed out",
+                )))
+            }),
+        None => request.await,
+    }
+}
+
+/// Serial port path of an RTU connection, looking up the USB adapter if configured
+#[cfg(feature = "rtu")]
+fn resolve_serial_port(rtu: &RtuConnection) -> Result<String> {
+    let Some(usb) = &rtu.usb else {
+        return Ok(rtu.port.clone());
+    };
+
+    let ports = tokio_serial::available_ports().map_err(|e| {
+        RustbridgeError::Connection(format!("Failed to enumerate serial ports: {}", e))
+    })?;
+    find_usb_port(&ports, usb).ok_or_else(|| {
+        RustbridgeError::Connection(format!("No USB serial adapter with {} is connected", usb))
+    })
+}
+
+/// First port whose USB adapter matches
+#[cfg(feature = "rtu")]
+fn find_usb_port(
+    ports: &[tokio_serial::SerialPortInfo],
+    usb: &crate::config::UsbMatch,
+) -> Option<String> {
+    ports.iter().find_map(|port| match &port.port_type {
+        tokio_serial::SerialPortType::UsbPort(info)
+            if usb.matches(info.vid, info.pid, info.serial_number.as_deref()) =>
+        {
+            Some(port.port_name.clone())
+        }
+        _ => None,
+    })
+}
+
+/// Attach a Modbus RTU client to the broker of the serial port, opening the
+/// port unless another device already uses it
+///
+/// Returns the client with the path of the port.
+#[cfg(feature = "rtu")]
+fn open_rtu(rtu: &RtuConnection) -> Result<(Box<dyn ModbusTransport>, String)> {
+    let port_name = resolve_serial_port(rtu)?;
+    let broker = broker::shared(&port_name, || {
+        let port = open_port(rtu, &port_name)?;
+        let port = bus_monitor::MonitoredPort::new(port, bus_stats::bus(&port_name));
+        Ok(client::Context::Rtu(rtu::attach_slave(
+            port,
+            Slave(rtu.unit_id),
+        )))
+    })?;
+    info!(
+        "Connecting to Modbus RTU: {} @ {} baud (unit {})",
+        port_name, rtu.baud_rate, rtu.unit_id
+    );
+    let timeout = rtu.response_timeout_ms.map(Duration::from_millis);
+    Ok((Box::new(broker.transport(rtu.unit_id, timeout)), port_name))
+}
+
+/// Open and configure the serial port of an RTU connection
+#[cfg(feature = "rtu")]
+pub(crate) fn open_serial_port(
+    rtu: &RtuConnection,
+) -> Result<(tokio_serial::SerialStream, String)> {
+    let port_name = resolve_serial_port(rtu)?;
+    let port = open_port(rtu, &port_name)?;
+    Ok((port, port_name))
+}
+
+/// Open `port_name` with the line settings of `rtu`
+#[cfg(feature = "rtu")]
+fn open_port(rtu: &RtuConnection, port_name: &str) -> Result<tokio_serial::SerialStream> {
+    use tokio_serial::SerialPortBuilderExt;
+    use tracing::warn;
+
+    // Parse parity
+    let parity = match rtu.parity.to_lowercase().as_str() {
+        "none" => tokio_serial::Parity::None,
+        "even" => tokio_serial::Parity::Even,
+        "odd" => tokio_serial::Parity::Odd,
+        _ => {
+            warn!("Unknown parity '{}', using None", rtu.parity);
+            tokio_serial::Parity::None
+        }
+    };
+
+    // Parse stop bits
+    let stop_bits = match rtu.stop_bits {
+        1 => tokio_serial::StopBits::One,
+        2 => tokio_serial::StopBits::Two,
+        _ => {
+            warn!("Unknown stop bits {}, using 1", rtu.stop_bits);
+            tokio_serial::StopBits::One
+        }
+    };
+
+    // Parse data bits
+    let data_bits = match rtu.data_bits {
+        5 => tokio_serial::DataBits::Five,
+        6 => tokio_serial::DataBits::Six,
+        7 => tokio_serial::DataBits::Seven,
+        8 => tokio_serial::DataBits::Eight,
+        _ => {
+            warn!("Unknown data bits {}, using 8", rtu.data_bits);
+            tokio_serial::DataBits::Eight
+        }
+    };
+
+    // Create serial port builder
+    let builder = tokio_serial::new(port_name, rtu.baud_rate)
+        .parity(parity)
+        .stop_bits(stop_bits)
+        .data_bits(data_bits);
+
+    // Open serial port
+    let port = builder.open_native_async().map_err(|e| {
+        RustbridgeError::Connection(format!(
+            "Failed to open serial port {} at {} baud: {}",
+            port_name, rtu.baud_rate, e
+        ))
+    })?;
+
+    info!(
+        "Serial port {} opened: {} baud, {} data bits, {:?} parity, {:?} stop bits",
+        port_name, rtu.baud_rate, rtu.data_bits, parity, stop_bits
+    );
+    Ok(port)
+}
+
+/// Open a TCP connection with the socket options of the connection config
+async fn open_tcp(addr: SocketAddr, tcp: &TcpConnection) -> std::io::Result<tokio::net::TcpStream> {
+    let socket = if addr.is_ipv4() {
+        tokio::net::TcpSocket::new_v4()?
+    } else {
+        tokio::net::TcpSocket::new_v6()?
+    };
+    socket.set_nodelay(tcp.tcp_nodelay)?;
+    if let Some(local) = tcp.local_address {
+        socket.bind(SocketAddr::new(local, 0)).map_err(|e| {
+            std::io::Error::new(e.kind(), format!("cannot bind to {}: {}", local, e))
+        })?;
+    }
+    if let Some(secs) = tcp.tcp_keepalive_secs {
+        let idle = Duration::from_secs(secs);
+        let keepalive = socket2::TcpKeepalive::new().with_time(idle);
+        // Probe as often as the idle time, so a dead peer is noticed within a few multiples of it
+        #[cfg(any(target_os = "linux", target_os = "macos", windows))]
+        let keepalive = keepalive.with_interval(idle);
+        socket2::SockRef::from(&socket).set_tcp_keepalive(&keepalive)?;
+    }
+
+    let connect = socket.connect(addr);
+    match tcp.connect_timeout_ms {
+        Some(ms) => tokio::time::timeout(Duration::from_millis(ms), connect)
+            .await
+            .map_err(|_| {
+                std::io::Error::new(
+                    std::io::ErrorKind::TimedOut,
+                    format!("connect timed out after {}ms", ms),
+                )
+            })?,
+        None => connect.await,
+    }
+}
+
+#[cfg(not(feature = "rtu"))]
+fn open_rtu(rtu: &RtuConnection) -> Result<(Box<dyn ModbusTransport>, String)> {
+    Err(RustbridgeError::Config(format!(
+        "Cannot open {}: RTU support not compiled in (enable the `rtu` feature)",
+        rtu.port
+    )))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -251,22 +671,125 @@ mod tests {
             host: "192.168.1.100".to_string(),
             port: 502,
             unit_id: 1,
+            ..Default::default()
         };
 
         assert_eq!(tcp.host, "192.168.1.100");
         assert_eq!(tcp.port, 502);
         assert_eq!(tcp.unit_id, 1);
+        assert!(tcp.tcp_nodelay);
+        assert_eq!(tcp.connect_timeout_ms, None);
+    }
+
+    #[tokio::test]
+    async fn test_open_tcp_applies_socket_options() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let tcp = TcpConnection {
+            connect_timeout_ms: Some(1000),
+            tcp_keepalive_secs: Some(10),
+            tcp_nodelay: true,
+            ..Default::default()
+        };
+
+        let stream = open_tcp(addr, &tcp).await.unwrap();
+        assert!(stream.nodelay().unwrap());
+        let socket = socket2::SockRef::from(&stream);
+        assert!(socket.keepalive().unwrap());
+        #[cfg(any(target_os = "linux", target_os = "macos"))]
+        assert_eq!(
+            socket.tcp_keepalive_time().unwrap(),
+            Duration::from_secs(10)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_open_tcp_from_local_address() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let tcp = TcpConnection {
+            local_address: Some("127.0.0.1".parse().unwrap()),
+            ..Default::default()
+        };
+
+        let stream = open_tcp(listener.local_addr().unwrap(), &tcp)
+            .await
+            .unwrap();
+        assert_eq!(
+            stream.local_addr().unwrap().ip(),
+            tcp.local_address.unwrap()
+        );
+
+        // An address family mismatch fails before connecting
+        let v6 = TcpConnection {
+            local_address: Some("::1".parse().unwrap()),
+            ..Default::default()
+        };
+        assert!(open_tcp(listener.local_addr().unwrap(), &v6).await.is_err());
+    }
+
+    #[cfg(feature = "rtu")]
+    #[test]
+    fn test_find_usb_port() {
+        use tokio_serial::{SerialPortInfo, SerialPortType, UsbPortInfo};
+
+        let usb_port = |name: &str, serial: &str| SerialPortInfo {
+            port_name: name.to_string(),
+            port_type: SerialPortType::UsbPort(UsbPortInfo {
+                vid: 0x0403,
+                pid: 0x6001,
+                serial_number: Some(serial.to_string()),
+                manufacturer: None,
+                product: None,
+            }),
+        };
+        let ports = vec![
+            SerialPortInfo {
+                port_name: "/dev/ttyS0".to_string(),
+                port_type: SerialPortType::Unknown,
+            },
+            usb_port("/dev/ttyUSB0", "AAAA"),
+            usb_port("/dev/ttyUSB1", "BBBB"),
+        ];
+
+        let by_serial = crate::config::UsbMatch {
+            serial_number: Some("BBBB".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            find_usb_port(&ports, &by_serial).as_deref(),
+            Some("/dev/ttyUSB1")
+        );
+
+        let by_ids = crate::config::UsbMatch {
+            vid: Some("0403".to_string()),
+            pid: Some("0x6001".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            find_usb_port(&ports, &by_ids).as_deref(),
+            Some("/dev/ttyUSB0")
+        );
+
+        let other = crate::config::UsbMatch {
+            vid: Some("1a86".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(find_usb_port(&ports, &other), None);
     }
 
     #[test]
     fn test_rtu_connection_config() {
         let rtu = RtuConnection {
             port: "/dev/ttyUSB0".to_string(),
+            usb: None,
             baud_rate: 9600,
             data_bits: 8,
             stop_bits: 1,
             parity: "none".to_string(),
             unit_id: 1,
+            response_timeout_ms: None,
+            retries: 0,
+            listen_only: false,
         };
 
         assert_eq!(rtu.port, "/dev/ttyUSB0");
@@ -310,10 +833,172 @@ mod tests {
             unit: Some("°C".to_string()),
             scale: Some(0.1),
             offset: None,
+            byte_order: Default::default(),
+            non_finite: Default::default(),
+            priority: Default::default(),
+            meta: Default::default(),
+            read_when: None,
+            alarm: None,
+            write_limits: None,
+            oversample: None,
+            read_after: None,
+            trigger: None,
+            min_stable_ms: None,
+            persistent: false,
+            publish_on_change: false,
+            deadband: None,
+            heartbeat_interval_s: None,
+            points: Vec::new(),
+            expression: None,
+            lookup: None,
         };
 
         assert_eq!(reg.name, "temperature");
         assert_eq!(reg.address, 100);
         assert!(matches!(reg.register_type, RegisterType::Holding));
     }
+
+    #[tokio::test]
+    async fn test_request_timeout() {
+        let result: Result<(), _> = within(
+            Some(Duration::from_millis(10)),
+            std::future::pending::<Result<(), client::ModbusError>>(),
+        )
+        .await;
+        let message = result.unwrap_err().to_string();
+        assert!(message.contains("timed out"), "{}", message);
+    }
+
+    fn mock_register(register_type: RegisterType, address: u16, count: u16) -> RegisterConfig {
+        RegisterConfig {
+            name: "value".to_string(),
+            address,
+            register_type,
+            count,
+            data_type: DataType::U16,
+            unit: None,
+            scale: None,
+            offset: None,
+            byte_order: Default::default(),
+            non_finite: Default::default(),
+            priority: Default::default(),
+            meta: Default::default(),
+            read_when: None,
+            alarm: None,
+            write_limits: None,
+            oversample: None,
+            read_after: None,
+            trigger: None,
+            min_stable_ms: None,
+            persistent: false,
+            publish_on_change: false,
+            deadband: None,
+            heartbeat_interval_s: None,
+            points: Vec::new(),
+            expression: None,
+            lookup: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_read_registers_from_mock() {
+        let mock = MockTransport::new();
+        mock.set_input(30, &[500, 501]);
+        mock.set_discrete(5, &[true, false, true]);
+        let mut client = ModbusClient::with_transport("plc-001", mock);
+
+        let input = mock_register(RegisterType::Input, 30, 2);
+        assert_eq!(client.read_registers(&input).await.unwrap(), vec![500, 501]);
+
+        let discrete = mock_register(RegisterType::Discrete, 5, 3);
+        assert_eq!(
+            client.read_registers(&discrete).await.unwrap(),
+            vec![1, 0, 1]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_read_exception_status() {
+        let mock = MockTransport::new();
+        let mut client = ModbusClient::with_transport("plc-001", mock.clone());
+        let mut bit = mock_register(RegisterType::ExceptionStatus, 3, 1);
+        bit.data_type = DataType::Bool;
+
+        // Devices without FC07 answer IllegalFunction
+        assert!(client.read_exception_status().await.is_err());
+
+        mock.set_exception_status(0b0000_1001);
+        assert_eq!(client.read_exception_status().await.unwrap(), 0b0000_1001);
+        assert_eq!(client.read_registers(&bit).await.unwrap(), vec![1]);
+        bit.address = 1;
+        assert_eq!(client.read_registers(&bit).await.unwrap(), vec![0]);
+    }
+
+    #[tokio::test]
+    async fn test_read_exception_is_error() {
+        let mock = MockTransport::new();
+        mock.fail_next(client::ModbusError::Exception(
+            tokio_modbus::Exception::IllegalFunction,
+        ));
+        let mut client = ModbusClient::with_transport("plc-001", mock.clone());
+
+        let err = client
+            .read_registers(&mock_register(RegisterType::Holding, 0, 1))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("IllegalFunction"), "{}", err);
+        assert_eq!(mock.requests(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_typed_read_and_write_with_mock() {
+        let mock = MockTransport::new();
+        // 123.456f32 as big-endian words
+        mock.set_holding(100, &[0x42F6, 0xE979]);
+        let mut client = ModbusClient::with_transport("plc-001", mock.clone());
+
+        let value: f32 = client
+            .read_typed(100, codec::ByteOrder::Abcd)
+            .await
+            .unwrap();
+        assert!((value - 123.456).abs() < 0.001);
+
+        client.write_registers(200, &[1, 2]).await.unwrap();
+        client.write_coil(3, true).await.unwrap();
+        assert_eq!(mock.holding(201), Some(2));
+
+        client
+            .write_typed(300, &-2i64, codec::ByteOrder::Cdab)
+            .await
+            .unwrap();
+        assert_eq!(mock.holding(300), Some(0xFFFE));
+        assert_eq!(mock.holding(303), Some(0xFFFF));
+        assert_eq!(mock.coil(3), Some(true));
+    }
+
+    #[tokio::test]
+    async fn test_broadcast_writes_expect_no_response() {
+        let mock = MockTransport::new();
+        mock.set_silent_writes(true);
+        let mut client = ModbusClient::with_transport("all-slaves", mock.clone());
+        client.broadcast = true;
+        client.set_timeout(Some(Duration::from_millis(20)));
+
+        client.write_registers(10, &[1, 2]).await.unwrap();
+        client.write_coil(3, true).await.unwrap();
+        assert_eq!(mock.holding(11), Some(2));
+        assert_eq!(mock.coil(3), Some(true));
+
+        let err = client
+            .read_registers(&mock_register(RegisterType::Holding, 10, 1))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("broadcast"), "{}", err);
+        assert_eq!(mock.requests(), 2);
+
+        // A failure that does come back is still reported
+        mock.set_silent_writes(false);
+        mock.fail_next(client::ModbusError::Io(std::io::Error::other("port gone")));
+        assert!(client.write_register(10, 5).await.is_err());
+    }
 }
@@ -1,282 +1,807 @@
 //! Modbus register reader with polling
 
-use std::collections::HashMap;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
-use crate::config::{DataType, RegisterConfig};
+use crate::config::{
+    DeviceConfig, Interlock, Oversample, ReadBlocksConfig, RegisterConfig, RegisterType,
+    SampleMethod,
+};
+use crate::error::{Result, RustbridgeError};
+use crate::metrics::memory::MemorySize;
+use crate::modbus::codec::{self, Quality, TypedValue};
+use crate::modbus::ModbusClient;
 
 /// Represents a register value with metadata
-#[derive(Debug, Clone, serde::Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RegisterValue {
-    pub name: String,
+    pub name: Arc<str>,
     pub raw: Vec<u16>,
+    /// `null` in JSON when the reading was not a number
+    #[serde(with = "crate::modbus::codec::finite_or_null")]
     pub value: f64,
+    /// Omitted when good
+    #[serde(default, skip_serializing_if = "Quality::is_good")]
+    pub quality: Quality,
+    /// Loaded from a snapshot at startup and not polled since
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub restored: bool,
     pub unit: Option<String>,
-    pub timestamp: chrono::DateTime<chrono::Utc>,
+    pub timestamp: DateTime<Utc>,
+    /// Device and register meta fields, serialized alongside the value
+    #[serde(flatten)]
+    pub meta: crate::config::Meta,
+}
+
+impl MemorySize for RegisterValue {
+    /// The name is shared with the configuration and not counted
+    fn memory_size(&self) -> usize {
+        std::mem::size_of::<Self>()
+            + self.raw.capacity() * std::mem::size_of::<u16>()
+            + self.unit.as_ref().map_or(0, String::capacity)
+            + self
+                .meta
+                .iter()
+                .map(|(key, value)| key.memory_size() + value.memory_size())
+                .sum::<usize>()
+    }
 }
 
 /// Shared state for register values
 pub type RegisterStore = Arc<RwLock<HashMap<String, HashMap<String, RegisterValue>>>>;
 
-/// Convert raw register values to typed value
-pub fn convert_value(raw: &[u16], config: &RegisterConfig) -> f64 {
-    let raw_value: f64 = match config.data_type {
-        DataType::U16 => raw.first().copied().unwrap_or(0) as f64,
-        DataType::I16 => raw.first().copied().unwrap_or(0) as i16 as f64,
-        DataType::U32 => {
-            if raw.len() >= 2 {
-                ((raw[0] as u32) << 16 | raw[1] as u32) as f64
-            } else {
-                0.0
-            }
-        }
-        DataType::I32 => {
-            if raw.len() >= 2 {
-                ((raw[0] as u32) << 16 | raw[1] as u32) as i32 as f64
-            } else {
-                0.0
-            }
-        }
-        DataType::F32 => {
-            if raw.len() >= 2 {
-                let bits = (raw[0] as u32) << 16 | raw[1] as u32;
-                f32::from_bits(bits) as f64
-            } else {
-                0.0
-            }
-        }
-        DataType::Bool => {
-            if raw.first().copied().unwrap_or(0) != 0 {
-                1.0
-            } else {
-                0.0
-            }
-        }
-    };
-
-    // Apply scale and offset
-    let scale = config.scale.unwrap_or(1.0);
-    let offset = config.offset.unwrap_or(0.0);
+/// Estimated memory used by the values in the store
+pub async fn store_memory_bytes(store: &RegisterStore) -> usize {
+    store
+        .read()
+        .await
+        .iter()
+        .map(|(device_id, values)| {
+            device_id.memory_size()
+                + values
+                    .iter()
+                    .map(|(name, value)| name.memory_size() + value.memory_size())
+                    .sum::<usize>()
+        })
+        .sum()
+}
 
-    raw_value * scale + offset
+/// Copy of every value in the store, as served by `GET /api/snapshot`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// When the snapshot was taken
+    pub taken_at: DateTime<Utc>,
+    /// Last values by device ID and register name
+    pub devices: BTreeMap<String, BTreeMap<String, RegisterValue>>,
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::config::RegisterType;
-
-    fn make_register_config(
-        data_type: DataType,
-        scale: Option<f64>,
-        offset: Option<f64>,
-    ) -> RegisterConfig {
-        RegisterConfig {
-            name: "test".to_string(),
-            address: 0,
-            register_type: RegisterType::Holding,
-            count: 1,
-            data_type,
-            unit: None,
-            scale,
-            offset,
+impl Snapshot {
+    /// Copy the current contents of the store
+    pub async fn capture(store: &RegisterStore) -> Self {
+        let store = store.read().await;
+        Self {
+            taken_at: Utc::now(),
+            devices: store
+                .iter()
+                .map(|(device_id, values)| {
+                    let values = values
+                        .iter()
+                        .map(|(name, value)| (name.clone(), value.clone()))
+                        .collect();
+                    (device_id.clone(), values)
+                })
+                .collect(),
         }
     }
 
-    #[test]
-    fn test_convert_u16() {
-        let config = make_register_config(DataType::U16, None, None);
-
-        assert_eq!(convert_value(&[0], &config), 0.0);
-        assert_eq!(convert_value(&[100], &config), 100.0);
-        assert_eq!(convert_value(&[65535], &config), 65535.0);
+    /// Read a snapshot file written by `rustbridge snapshot`
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path).map_err(|e| {
+            RustbridgeError::Config(format!("Failed to read snapshot {}: {}", path.display(), e))
+        })?;
+        serde_json::from_str(&content).map_err(|e| {
+            RustbridgeError::Config(format!("Invalid snapshot {}: {}", path.display(), e))
+        })
     }
 
-    #[test]
-    fn test_convert_i16() {
-        let config = make_register_config(DataType::I16, None, None);
-
-        assert_eq!(convert_value(&[0], &config), 0.0);
-        assert_eq!(convert_value(&[100], &config), 100.0);
-        // 65535 as i16 = -1
-        assert_eq!(convert_value(&[65535], &config), -1.0);
-        // 65436 as i16 = -100
-        assert_eq!(convert_value(&[65436], &config), -100.0);
+    /// Write the snapshot to a file, replacing it atomically
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let failed = |e: std::io::Error| {
+            RustbridgeError::Config(format!(
+                "Failed to write snapshot {}: {}",
+                path.display(),
+                e
+            ))
+        };
+        let json = serde_json::to_vec(self)
+            .map_err(|e| RustbridgeError::Config(format!("Failed to encode snapshot: {}", e)))?;
+        // A crash mid-write must not leave a truncated file behind
+        let mut temp = path.as_os_str().to_owned();
+        temp.push(".tmp");
+        std::fs::write(&temp, json).map_err(failed)?;
+        std::fs::rename(&temp, path).map_err(failed)
     }
 
-    #[test]
-    fn test_convert_u32() {
-        let config = make_register_config(DataType::U32, None, None);
-
-        // 0x00000000
-        assert_eq!(convert_value(&[0, 0], &config), 0.0);
-
-        // 0x00010000 = 65536
-        assert_eq!(convert_value(&[1, 0], &config), 65536.0);
-
-        // 0x00000001 = 1
-        assert_eq!(convert_value(&[0, 1], &config), 1.0);
-
-        // 0x0001FFFF = 131071
-        assert_eq!(convert_value(&[1, 65535], &config), 131071.0);
-
-        // 0xFFFFFFFF = 4294967295
-        assert_eq!(convert_value(&[65535, 65535], &config), 4294967295.0);
+    /// Seed the store with the snapshot values of configured registers
+    ///
+    /// Values that have already been polled are kept, and values of devices or
+    /// registers no longer in the configuration are dropped. Restored values
+    /// keep their original timestamp, take the current meta fields and are
+    /// marked `restored`. Returns the number of values restored.
+    pub async fn preload(self, store: &RegisterStore, devices: &[DeviceConfig]) -> usize {
+        let mut store = store.write().await;
+        let mut restored = 0;
+        for (device_id, mut saved) in self.devices {
+            let Some(device) = devices.iter().find(|d| d.id == device_id) else {
+                continue;
+            };
+            let current = store.entry(device_id).or_default();
+            for register in &device.registers {
+                let Some(mut value) = saved.remove(&register.name) else {
+                    continue;
+                };
+                if current.contains_key(&register.name) {
+                    continue;
+                }
+                value.name = register.name.as_str().into();
+                value.unit = register.unit.clone();
+                value.meta = device.register_meta(register);
+                value.restored = true;
+                current.insert(register.name.clone(), value);
+                restored += 1;
+            }
+        }
+        restored
     }
+}
 
-    #[test]
-    fn test_convert_i32() {
-        let config = make_register_config(DataType::I32, None, None);
-
-        // 0
-        assert_eq!(convert_value(&[0, 0], &config), 0.0);
-
-        // 1
-        assert_eq!(convert_value(&[0, 1], &config), 1.0);
+/// Whether a register's `read_when` condition allows reading it now
+///
+/// `values` are the last values of the register's device. A condition on a
+/// register that has not been read yet, or whose value is `null`, is not met.
+pub fn condition_met(
+    register: &RegisterConfig,
+    values: Option<&HashMap<String, RegisterValue>>,
+) -> bool {
+    let Some(condition) = &register.read_when else {
+        return true;
+    };
+    values
+        .and_then(|values| values.get(&condition.register))
+        .is_some_and(|gate| gate.value.is_finite() && condition.is_met(gate.value))
+}
 
-        // -1 (0xFFFFFFFF)
-        assert_eq!(convert_value(&[65535, 65535], &config), -1.0);
+/// The first of `interlocks` the stored values do not meet, as a message
+///
+/// Interlocks without a `device_id` refer to `device_id`. All are checked
+/// against the same `store` contents, so they hold together or not at all.
+/// Like `read_when`, a register without a value does not meet its interlock.
+pub fn unmet_interlock(
+    interlocks: &[Interlock],
+    device_id: &str,
+    store: &HashMap<String, HashMap<String, RegisterValue>>,
+) -> Option<String> {
+    interlocks.iter().find_map(|interlock| {
+        let device_id = interlock.device_id.as_deref().unwrap_or(device_id);
+        let value = store
+            .get(device_id)
+            .and_then(|values| values.get(&interlock.register))
+            .map(|value| value.value)
+            .filter(|value| value.is_finite());
+        match value {
+            Some(value) if interlock.test.is_met(value) => None,
+            Some(value) => Some(format!(
+                "Interlock not met: {}:{} is {}",
+                device_id, interlock.register, value
+            )),
+            None => Some(format!(
+                "Interlock not met: {}:{} has no value",
+                device_id, interlock.register
+            )),
+        }
+    })
+}
 
-        // -100 (0xFFFFFF9C)
-        let neg100: i32 = -100;
-        let high = ((neg100 as u32) >> 16) as u16;
-        let low = (neg100 as u32) as u16;
-        assert_eq!(convert_value(&[high, low], &config), -100.0);
+/// Registers in the order they are requested within a poll cycle
+///
+/// Configuration order, except that a register with `read_after` follows
+/// right after the register it names (and after that register's other
+/// dependents listed before it).
+pub fn poll_order(registers: &[RegisterConfig]) -> Vec<&RegisterConfig> {
+    fn visit<'a>(
+        index: usize,
+        registers: &'a [RegisterConfig],
+        placed: &mut [bool],
+        order: &mut Vec<&'a RegisterConfig>,
+    ) {
+        if placed[index] {
+            return;
+        }
+        placed[index] = true;
+        order.push(&registers[index]);
+        let name = &registers[index].name;
+        for (dependent, register) in registers.iter().enumerate() {
+            if register.read_after.as_ref() == Some(name) {
+                visit(dependent, registers, placed, order);
+            }
+        }
     }
 
-    #[test]
-    fn test_convert_f32() {
-        let config = make_register_config(DataType::F32, None, None);
-
-        // IEEE 754: 0.0
-        assert_eq!(convert_value(&[0, 0], &config), 0.0);
-
-        // IEEE 754: 1.0 = 0x3F800000
-        let one_bits: u32 = 1.0_f32.to_bits();
-        let high = (one_bits >> 16) as u16;
-        let low = one_bits as u16;
-        assert!((convert_value(&[high, low], &config) - 1.0).abs() < 0.0001);
-
-        // IEEE 754: 3.14159... = 0x40490FDB
-        let pi_bits: u32 = std::f32::consts::PI.to_bits();
-        let high = (pi_bits >> 16) as u16;
-        let low = pi_bits as u16;
-        assert!((convert_value(&[high, low], &config) - std::f64::consts::PI).abs() < 0.0001);
-
-        // Negative value: -42.5
-        let neg_bits: u32 = (-42.5_f32).to_bits();
-        let high = (neg_bits >> 16) as u16;
-        let low = neg_bits as u16;
-        assert!((convert_value(&[high, low], &config) - (-42.5)).abs() < 0.0001);
+    let mut placed = vec![false; registers.len()];
+    let mut order = Vec::with_capacity(registers.len());
+    for (index, register) in registers.iter().enumerate() {
+        let independent = register
+            .read_after
+            .as_ref()
+            .is_none_or(|after| !registers.iter().any(|r| r.name == *after));
+        if independent {
+            visit(index, registers, &mut placed, &mut order);
+        }
     }
-
-    #[test]
-    fn test_convert_bool() {
-        let config = make_register_config(DataType::Bool, None, None);
-
-        assert_eq!(convert_value(&[0], &config), 0.0);
-        assert_eq!(convert_value(&[1], &config), 1.0);
-        assert_eq!(convert_value(&[100], &config), 1.0);
-        assert_eq!(convert_value(&[65535], &config), 1.0);
+    // Left over only by a `read_after` cycle, which validation rejects
+    for index in 0..registers.len() {
+        visit(index, registers, &mut placed, &mut order);
     }
+    order
+}
 
-    #[test]
-    fn test_scale_factor() {
-        // Temperature sensor: raw value * 0.1 = actual temperature
-        let config = make_register_config(DataType::U16, Some(0.1), None);
-
-        assert_eq!(convert_value(&[250], &config), 25.0);
-        assert_eq!(convert_value(&[1000], &config), 100.0);
-    }
+/// Bytes of the request and response frames for polling `register` once
+///
+/// Computed from the Modbus frame layout, with RTU (address and CRC) or TCP
+/// (MBAP header) framing. A `trigger` register counts as its write; an
+/// oversampled one counts every sample. FIFO responses are assumed full.
+pub fn frame_bytes(register: &RegisterConfig, rtu: bool) -> (u64, u64) {
+    let count = u64::from(register.count);
+    let (request, response, repeat) = if register.trigger.is_some() {
+        let request = if count == 1 { 5 } else { 6 + 2 * count };
+        (request, 5, 1)
+    } else {
+        let (request, response) = match register.register_type {
+            RegisterType::Holding | RegisterType::Input => (5, 2 + 2 * count),
+            RegisterType::Coil | RegisterType::Discrete => (5, 2 + count.div_ceil(8)),
+            RegisterType::ExceptionStatus => (1, 2),
+            RegisterType::Fifo => (3, 5 + 2 * count),
+        };
+        let samples = register
+            .oversample
+            .as_ref()
+            .map_or(1, |o| u64::from(o.samples));
+        (request, response, samples)
+    };
+    let framing = if rtu { 3 } else { 7 };
+    ((request + framing) * repeat, (response + framing) * repeat)
+}
 
-    #[test]
-    fn test_offset() {
-        // Sensor with offset calibration
-        let config = make_register_config(DataType::I16, None, Some(100.0));
+/// Registers read with one request
+#[derive(Debug, Clone)]
+pub struct ReadBlock {
+    /// Request covering every member, named after the first and last of them
+    pub request: RegisterConfig,
+    /// Names of the registers read with the block, by address
+    pub members: Vec<String>,
+}
 
-        assert_eq!(convert_value(&[0], &config), 100.0);
-        assert_eq!(convert_value(&[50], &config), 150.0);
+impl ReadBlock {
+    /// Raw values of `register` within `raw`, the response to the block
+    pub fn slice(&self, raw: &[u16], register: &RegisterConfig) -> Option<Vec<u16>> {
+        let start = usize::from(register.address.checked_sub(self.request.address)?);
+        raw.get(start..start + usize::from(register.count))
+            .map(<[u16]>::to_vec)
     }
+}
 
-    #[test]
-    fn test_scale_and_offset() {
-        // Temperature sensor: (raw * 0.1) + (-40) for Celsius
-        let config = make_register_config(DataType::U16, Some(0.1), Some(-40.0));
+/// Blocks of registers that can be read together, for `read_blocks`
+///
+/// Holding registers, input registers, coils and discrete inputs are sorted
+/// by address and joined while the next one starts at most `max_gap`
+/// addresses after the block and the block stays within `max_registers`.
+/// Registers with a `trigger`, `oversample`, `read_when` or `read_after`
+/// depend on what happens before their own request and are left out, as
+/// are blocks of a single register.
+pub fn plan_blocks(registers: &[&RegisterConfig], config: &ReadBlocksConfig) -> Vec<ReadBlock> {
+    let mut blocks = Vec::new();
+    for register_type in [
+        RegisterType::Holding,
+        RegisterType::Input,
+        RegisterType::Coil,
+        RegisterType::Discrete,
+    ] {
+        let mut candidates: Vec<&RegisterConfig> = registers
+            .iter()
+            .copied()
+            .filter(|r| {
+                r.register_type == register_type
+                    && r.trigger.is_none()
+                    && r.oversample.is_none()
+                    && r.read_when.is_none()
+                    && r.read_after.is_none()
+            })
+            .collect();
+        candidates.sort_by_key(|r| r.address);
+
+        let mut members: Vec<&RegisterConfig> = Vec::new();
+        let (mut start, mut end) = (0u32, 0u32);
+        for register in candidates {
+            let first = u32::from(register.address);
+            let last = first + u32::from(register.count);
+            let joins = !members.is_empty()
+                && first <= end + u32::from(config.max_gap)
+                && last.max(end) - start <= u32::from(config.max_registers);
+            if joins {
+                end = end.max(last);
+            } else {
+                blocks.extend(block(&members, start, end));
+                members.clear();
+                (start, end) = (first, last);
+            }
+            members.push(register);
+        }
+        blocks.extend(block(&members, start, end));
+    }
+    blocks
+}
 
-        // Raw 400 = 40.0 - 40.0 = 0.0°C
-        assert_eq!(convert_value(&[400], &config), 0.0);
+/// Block reading `members` from `start` up to `end`, if there are several
+fn block(members: &[&RegisterConfig], start: u32, end: u32) -> Option<ReadBlock> {
+    let (first, last) = match members {
+        [first, .., last] => (first, last),
+        _ => return None,
+    };
+    let mut request = (*first).clone();
+    request.name = format!("{}..{}", first.name, last.name);
+    request.address = start as u16;
+    request.count = (end - start) as u16;
+    Some(ReadBlock {
+        request,
+        members: members.iter().map(|r| r.name.clone()).collect(),
+    })
+}
 
-        // Raw 650 = 65.0 - 40.0 = 25.0°C
-        assert_eq!(convert_value(&[650], &config), 25.0);
+/// Read a register `samples` times, `spacing_ms` apart, and combine the
+/// readings into one
+///
+/// The reads go through the same connection one after another, so the bus
+/// is never busier than with separate polls. A failed read fails the whole
+/// poll of the register.
+pub async fn read_oversampled(
+    client: &mut ModbusClient,
+    register: &RegisterConfig,
+    oversample: &Oversample,
+) -> Result<Vec<u16>> {
+    let spacing = std::time::Duration::from_millis(oversample.spacing_ms);
+    let mut samples = Vec::with_capacity(usize::from(oversample.samples));
+    for sample in 0..oversample.samples.max(1) {
+        if sample > 0 && !spacing.is_zero() {
+            tokio::time::sleep(spacing).await;
+        }
+        samples.push(client.read_registers(register).await?);
     }
+    Ok(combine_samples(samples, register, oversample.method))
+}
 
-    #[test]
-    fn test_empty_raw_values() {
-        let config = make_register_config(DataType::U16, None, None);
-        assert_eq!(convert_value(&[], &config), 0.0);
+/// Raw registers of the combined value of several reads
+///
+/// The median is the middle sample (the upper one of an even count), kept
+/// as read. The mean is encoded back into the register's data type, rounded
+/// to the nearest raw step for integer types. Samples that are not a number
+/// are left out; if none is, the first sample is returned.
+pub fn combine_samples(
+    mut samples: Vec<Vec<u16>>,
+    register: &RegisterConfig,
+    method: SampleMethod,
+) -> Vec<u16> {
+    let mut decoded: Vec<(f64, usize)> = samples
+        .iter()
+        .enumerate()
+        .filter_map(|(index, raw)| {
+            let value = codec::decode(raw, &register.data_type, register.byte_order)?.as_f64();
+            value.is_finite().then_some((value, index))
+        })
+        .collect();
+    if decoded.is_empty() {
+        return samples.swap_remove(0);
+    }
+    match method {
+        SampleMethod::Median => {
+            decoded.sort_by(|a, b| a.0.total_cmp(&b.0));
+            let (_, index) = decoded[decoded.len() / 2];
+            samples.swap_remove(index)
+        }
+        SampleMethod::Mean => {
+            let mean = decoded.iter().map(|(value, _)| value).sum::<f64>() / decoded.len() as f64;
+            let mean = if register.data_type.is_float() {
+                mean
+            } else {
+                mean.round()
+            };
+            match TypedValue::from_f64(mean, &register.data_type) {
+                Some(typed) => codec::encode(&typed, register.byte_order),
+                None => samples.swap_remove(decoded[0].1),
+            }
+        }
+    }
+}
 
-        let config32 = make_register_config(DataType::U32, None, None);
-        assert_eq!(convert_value(&[], &config32), 0.0);
-        assert_eq!(convert_value(&[1], &config32), 0.0); // Not enough values
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{DataType, Interlock, ReadCondition, ValueCondition};
+
+    fn value(name: &str, value: f64) -> RegisterValue {
+        RegisterValue {
+            name: name.into(),
+            raw: vec![value as u16],
+            value,
+            quality: Default::default(),
+            restored: false,
+            unit: None,
+            timestamp: chrono::Utc::now(),
+            meta: Default::default(),
+        }
     }
 
     #[test]
     fn test_register_value_creation() {
         let reg_value = RegisterValue {
-            name: "temperature".to_string(),
+            name: "temperature".into(),
             raw: vec![250],
             value: 25.0,
+            quality: Default::default(),
+            restored: false,
             unit: Some("°C".to_string()),
             timestamp: chrono::Utc::now(),
+            meta: Default::default(),
         };
 
-        assert_eq!(reg_value.name, "temperature");
+        assert_eq!(&*reg_value.name, "temperature");
         assert_eq!(reg_value.value, 25.0);
         assert_eq!(reg_value.unit, Some("°C".to_string()));
     }
 
-    #[test]
-    fn test_industrial_temperature_sensor() {
-        // Typical industrial temperature sensor:
-        // - Returns raw value as 10x actual temperature
-        // - Range: -40°C to 125°C
-        // - Signed value for negative temperatures
-        let config = make_register_config(DataType::I16, Some(0.1), None);
+    #[tokio::test]
+    async fn test_store_memory_bytes() {
+        let store: RegisterStore = Default::default();
+        assert_eq!(store_memory_bytes(&store).await, 0);
+
+        let mut reading = value("level", 7.0);
+        let small = reading.memory_size();
+        reading
+            .meta
+            .insert("site".to_string(), "north".repeat(20).into());
+        assert!(reading.memory_size() >= small + 100);
+
+        store
+            .write()
+            .await
+            .entry("plc-001".to_string())
+            .or_default()
+            .insert("level".to_string(), reading);
+        assert!(store_memory_bytes(&store).await > small + 100);
+    }
 
-        // -40°C = raw -400
-        let raw_neg40: u16 = (-400_i16) as u16;
-        assert!((convert_value(&[raw_neg40], &config) - (-40.0)).abs() < 0.01);
+    #[test]
+    fn test_condition_met() {
+        let mut register = RegisterConfig {
+            name: "totalizer".to_string(),
+            address: 10,
+            register_type: RegisterType::Holding,
+            count: 2,
+            data_type: DataType::U32,
+            unit: None,
+            scale: None,
+            offset: None,
+            byte_order: Default::default(),
+            non_finite: Default::default(),
+            priority: Default::default(),
+            meta: Default::default(),
+            read_when: None,
+            alarm: None,
+            write_limits: None,
+            oversample: None,
+            read_after: None,
+            trigger: None,
+            min_stable_ms: None,
+            persistent: false,
+            publish_on_change: false,
+            deadband: None,
+            heartbeat_interval_s: None,
+            points: Vec::new(),
+            expression: None,
+            lookup: None,
+        };
+        assert!(condition_met(&register, None));
+
+        register.read_when = Some(ReadCondition {
+            register: "pump_status".to_string(),
+            test: ValueCondition {
+                bit: Some(1),
+                ..Default::default()
+            },
+        });
+        let mut values = HashMap::new();
+        assert!(!condition_met(&register, Some(&values)));
+
+        values.insert("pump_status".to_string(), value("pump_status", 0b01.into()));
+        assert!(!condition_met(&register, Some(&values)));
+        values.insert("pump_status".to_string(), value("pump_status", 0b10.into()));
+        assert!(condition_met(&register, Some(&values)));
+    }
 
-        // 0°C = raw 0
-        assert_eq!(convert_value(&[0], &config), 0.0);
+    #[test]
+    fn test_unmet_interlock() {
+        let interlocks = [
+            Interlock {
+                register: "running".to_string(),
+                test: ValueCondition {
+                    equals: Some(1.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            Interlock {
+                device_id: Some("guard".to_string()),
+                register: "door_closed".to_string(),
+                ..Default::default()
+            },
+        ];
+        let mut store = HashMap::from([(
+            "plc-001".to_string(),
+            HashMap::from([("running".to_string(), value("running", 1.0))]),
+        )]);
+        assert_eq!(
+            unmet_interlock(&interlocks, "plc-001", &store).as_deref(),
+            Some("Interlock not met: guard:door_closed has no value")
+        );
+
+        store.insert(
+            "guard".to_string(),
+            HashMap::from([("door_closed".to_string(), value("door_closed", 1.0))]),
+        );
+        assert_eq!(unmet_interlock(&interlocks, "plc-001", &store), None);
+
+        store
+            .get_mut("plc-001")
+            .unwrap()
+            .insert("running".to_string(), value("running", 0.0));
+        assert_eq!(
+            unmet_interlock(&interlocks, "plc-001", &store).as_deref(),
+            Some("Interlock not met: plc-001:running is 0")
+        );
+    }
 
-        // 25°C = raw 250
-        assert!((convert_value(&[250], &config) - 25.0).abs() < 0.01);
+    #[test]
+    fn test_read_condition_tests() {
+        let running = ReadCondition {
+            register: "pump".to_string(),
+            ..Default::default()
+        };
+        assert!(running.is_met(1.0));
+        assert!(!running.is_met(0.0));
+
+        let band = ReadCondition {
+            register: "pump".to_string(),
+            test: ValueCondition {
+                above: Some(10.0),
+                below: Some(20.0),
+                not_equals: Some(15.0),
+                ..Default::default()
+            },
+        };
+        assert!(band.is_met(12.5));
+        assert!(!band.is_met(15.0));
+        assert!(!band.is_met(25.0));
+    }
 
-        // 125°C = raw 1250
-        assert!((convert_value(&[1250], &config) - 125.0).abs() < 0.01);
+    #[test]
+    fn test_frame_bytes() {
+        let register = |yaml: &str| -> RegisterConfig { serde_yaml::from_str(yaml).unwrap() };
+        let holding =
+            register("{ name: a, address: 0, register_type: holding, count: 2, data_type: u32 }");
+        // Request: MBAP 7 + FC, address, quantity 5; response: 7 + FC, byte count, 4 data bytes
+        assert_eq!(frame_bytes(&holding, false), (12, 13));
+        assert_eq!(frame_bytes(&holding, true), (8, 9));
+        let coils =
+            register("{ name: b, address: 0, register_type: coil, count: 10, data_type: bool }");
+        assert_eq!(frame_bytes(&coils, true), (8, 7));
+        let sampled = register(
+            "{ name: c, address: 0, register_type: input, count: 1, data_type: u16, oversample: { samples: 4 } }",
+        );
+        assert_eq!(frame_bytes(&sampled, true), (32, 28));
+        let trigger = register(
+            "{ name: d, address: 9, register_type: holding, count: 1, data_type: u16, trigger: 1 }",
+        );
+        assert_eq!(frame_bytes(&trigger, true), (8, 8));
     }
 
     #[test]
-    fn test_pressure_sensor_psi() {
-        // Pressure sensor: 0-10000 raw = 0-100 PSI
-        let config = make_register_config(DataType::U16, Some(0.01), None);
+    fn test_poll_order() {
+        let registers: Vec<RegisterConfig> = serde_yaml::from_str(
+            r#"
+- { name: level, address: 0, register_type: input, count: 1, data_type: u16,
+    read_after: latch }
+- { name: flow, address: 1, register_type: input, count: 1, data_type: u16 }
+- { name: latch, address: 2, register_type: holding, count: 1, data_type: u16,
+    trigger: 1 }
+- { name: total, address: 3, register_type: input, count: 1, data_type: u16,
+    read_after: level }
+- { name: pressure, address: 4, register_type: input, count: 1, data_type: u16,
+    read_after: latch }
+"#,
+        )
+        .unwrap();
+        let order: Vec<&str> = poll_order(&registers)
+            .iter()
+            .map(|r| r.name.as_str())
+            .collect();
+        assert_eq!(order, ["flow", "latch", "level", "total", "pressure"]);
+    }
 
-        assert_eq!(convert_value(&[0], &config), 0.0);
-        assert_eq!(convert_value(&[5000], &config), 50.0);
-        assert_eq!(convert_value(&[10000], &config), 100.0);
+    #[test]
+    fn test_plan_blocks() {
+        let registers: Vec<RegisterConfig> = serde_yaml::from_str(
+            r#"
+- { name: voltage, address: 0, register_type: input, count: 2, data_type: f32 }
+- { name: current, address: 2, register_type: input, count: 2, data_type: f32 }
+- { name: power, address: 6, register_type: input, count: 2, data_type: f32 }
+- { name: energy, address: 40, register_type: input, count: 4, data_type: u64 }
+- { name: setpoint, address: 3, register_type: holding, count: 1, data_type: u16 }
+- { name: mode, address: 4, register_type: holding, count: 1, data_type: u16,
+    read_when: { register: setpoint } }
+- { name: pump, address: 0, register_type: coil, count: 1, data_type: bool }
+- { name: fan, address: 1, register_type: coil, count: 1, data_type: bool }
+"#,
+        )
+        .unwrap();
+        let registers: Vec<&RegisterConfig> = registers.iter().collect();
+        let summary = |blocks: Vec<ReadBlock>| -> Vec<(String, u16, u16, Vec<String>)> {
+            blocks
+                .into_iter()
+                .map(|b| {
+                    (
+                        b.request.name,
+                        b.request.address,
+                        b.request.count,
+                        b.members,
+                    )
+                })
+                .collect()
+        };
+        let names = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+
+        // Only adjacent registers by default; `mode` is read on its own
+        let contiguous = plan_blocks(&registers, &ReadBlocksConfig::default());
+        assert_eq!(
+            summary(contiguous.clone()),
+            [
+                (
+                    "voltage..current".into(),
+                    0,
+                    4,
+                    names(&["voltage", "current"])
+                ),
+                ("pump..fan".into(), 0, 2, names(&["pump", "fan"])),
+            ]
+        );
+        assert_eq!(contiguous[1].request.register_type, RegisterType::Coil);
+
+        // A gap of two joins `power`; `energy` would make the block too large
+        let config = ReadBlocksConfig {
+            max_gap: 34,
+            max_registers: 40,
+        };
+        let blocks = plan_blocks(&registers, &config);
+        assert_eq!(
+            summary(blocks.clone()),
+            [
+                (
+                    "voltage..power".into(),
+                    0,
+                    8,
+                    names(&["voltage", "current", "power"])
+                ),
+                ("pump..fan".into(), 0, 2, names(&["pump", "fan"])),
+            ]
+        );
+
+        let raw: Vec<u16> = (10..18).collect();
+        assert_eq!(blocks[0].slice(&raw, registers[1]), Some(vec![12, 13]));
+        assert_eq!(blocks[0].slice(&raw, registers[2]), Some(vec![16, 17]));
+        assert_eq!(blocks[0].slice(&raw[..6], registers[2]), None);
     }
 
     #[test]
-    fn test_flow_meter_with_u32() {
-        // Flow meter: 32-bit counter in liters
-        let config = make_register_config(DataType::U32, None, None);
-
-        // 1,000,000 liters
-        let value: u32 = 1_000_000;
-        let high = (value >> 16) as u16;
-        let low = value as u16;
-        assert_eq!(convert_value(&[high, low], &config), 1_000_000.0);
+    fn test_combine_samples() {
+        let register: RegisterConfig = serde_yaml::from_str(
+            "{ name: level, address: 0, register_type: input, count: 1, data_type: u16 }",
+        )
+        .unwrap();
+        let samples = || vec![vec![100], vec![103], vec![900], vec![101]];
+
+        assert_eq!(
+            combine_samples(samples(), &register, SampleMethod::Median),
+            vec![103]
+        );
+        // (100 + 103 + 900 + 101) / 4 = 301
+        assert_eq!(
+            combine_samples(samples(), &register, SampleMethod::Mean),
+            vec![301]
+        );
+
+        let float: RegisterConfig = serde_yaml::from_str(
+            "{ name: flow, address: 0, register_type: input, count: 2, data_type: f32 }",
+        )
+        .unwrap();
+        let raw = |value: f32| codec::encode(&TypedValue::F32(value), float.byte_order);
+        let mean = combine_samples(
+            vec![raw(1.0), raw(f32::NAN), raw(2.0)],
+            &float,
+            SampleMethod::Mean,
+        );
+        assert_eq!(mean, raw(1.5));
+        // Without a number among the samples the first one is kept
+        assert_eq!(
+            combine_samples(vec![raw(f32::NAN)], &float, SampleMethod::Median),
+            raw(f32::NAN)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_preload() {
+        let device: DeviceConfig = serde_yaml::from_str(
+            r#"
+id: plc-001
+name: PLC
+device_type: tcp
+connection: { host: 127.0.0.1, port: 502, unit_id: 1 }
+poll_interval_ms: 1000
+meta: { site: north }
+registers:
+  - { name: setpoint, address: 0, register_type: holding, count: 1, data_type: u16 }
+  - { name: level, address: 1, register_type: holding, count: 1, data_type: u16 }
+"#,
+        )
+        .unwrap();
+
+        let source: RegisterStore = Default::default();
+        {
+            let mut store = source.write().await;
+            let values = store.entry("plc-001".to_string()).or_default();
+            values.insert("setpoint".to_string(), value("setpoint", 42.0));
+            values.insert("level".to_string(), value("level", 7.0));
+            values.insert("removed".to_string(), value("removed", 1.0));
+            store
+                .entry("gone".to_string())
+                .or_default()
+                .insert("x".to_string(), value("x", 1.0));
+        }
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("values.json");
+        Snapshot::capture(&source).await.save(&path).unwrap();
+        let snapshot = Snapshot::load(&path).unwrap();
+
+        let store: RegisterStore = Default::default();
+        store
+            .write()
+            .await
+            .entry("plc-001".to_string())
+            .or_default()
+            .insert("level".to_string(), value("level", 9.0));
+
+        assert_eq!(snapshot.preload(&store, &[device]).await, 1);
+        let store = store.read().await;
+        assert_eq!(store.len(), 1);
+        let values = &store["plc-001"];
+        assert_eq!(values["setpoint"].value, 42.0);
+        assert_eq!(values["setpoint"].meta["site"], "north");
+        assert!(values["setpoint"].restored);
+        assert_eq!(values["level"].value, 9.0);
+        assert!(!values["level"].restored);
+        assert!(!values.contains_key("removed"));
     }
 }
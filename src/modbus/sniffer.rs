@@ -0,0 +1,539 @@
+//! Listen-only monitoring of Modbus RTU buses
+//!
+//! A `listen_only` device never transmits. The serial port of its bus is
+//! opened once and shared by every listen-only device on it; the traffic
+//! between the existing master and its slaves is cut into frames, requests are
+//! paired with their responses, and the register values they carry are handed
+//! to the devices as [`Observation`]s.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::sync::broadcast;
+use tracing::{info, warn};
+
+use crate::config::{RegisterConfig, RegisterType, RtuConnection};
+use crate::error::Result;
+use crate::metrics::bus_stats::{self, BusCounters};
+
+/// Longest valid RTU frame (address + PDU + CRC)
+const MAX_FRAME_LEN: usize = 256;
+
+/// Observations buffered per listening device before it lags
+const OBSERVATION_CAPACITY: usize = 256;
+
+/// Register values seen on the bus, from a read response or a write request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Observation {
+    /// Slave the values belong to (0 for a broadcast write)
+    pub unit_id: u8,
+    pub register_type: RegisterType,
+    /// First address of `values`
+    pub address: u16,
+    /// One entry per register, or per bit (0/1) for coils and discrete inputs
+    pub values: Vec<u16>,
+}
+
+impl Observation {
+    /// Raw values of a configured register of `unit_id`, if this observation covers it
+    pub fn registers_of(&self, unit_id: u8, register: &RegisterConfig) -> Option<&[u16]> {
+        if (self.unit_id != unit_id && self.unit_id != 0)
+            || self.register_type != register.register_type
+        {
+            return None;
+        }
+        let start = register.address.checked_sub(self.address)? as usize;
+        self.values.get(start..start + register.count as usize)
+    }
+}
+
+/// Request waiting for its response
+#[derive(Debug, Clone)]
+struct Request {
+    unit_id: u8,
+    function: u8,
+    address: u16,
+    quantity: u16,
+    /// Values carried by a write request
+    values: Vec<u16>,
+}
+
+impl Request {
+    fn observation(self, values: Vec<u16>) -> Option<Observation> {
+        let register_type = match self.function {
+            0x01 | 0x05 => RegisterType::Coil,
+            0x02 => RegisterType::Discrete,
+            0x03 | 0x06 | 0x10 => RegisterType::Holding,
+            0x04 => RegisterType::Input,
+            0x07 => RegisterType::ExceptionStatus,
+            _ => return None,
+        };
+        Some(Observation {
+            unit_id: self.unit_id,
+            register_type,
+            address: self.address,
+            values,
+        })
+    }
+}
+
+/// Cuts the bytes of a bus into frames and decodes them into observations
+#[derive(Debug)]
+pub struct FrameDecoder {
+    counters: Arc<BusCounters>,
+    buffer: Vec<u8>,
+    pending: Option<Request>,
+    /// Cleared while skipping bytes, so a resync counts as one error
+    in_sync: bool,
+}
+
+impl FrameDecoder {
+    pub fn new(counters: Arc<BusCounters>) -> Self {
+        Self {
+            counters,
+            buffer: Vec::with_capacity(MAX_FRAME_LEN),
+            pending: None,
+            in_sync: true,
+        }
+    }
+
+    /// Feed bytes read from the bus, returning the observations they complete
+    pub fn push(&mut self, bytes: &[u8]) -> Vec<Observation> {
+        self.counters.record_received(bytes.len());
+        self.buffer.extend_from_slice(bytes);
+
+        let mut observations = Vec::new();
+        while self.buffer.len() >= 4 {
+            let candidates = self.frame_lengths();
+            let mut incomplete = false;
+            let mut checked = false;
+            let mut frame_len = None;
+            for (index, len) in candidates.into_iter().enumerate() {
+                let Some(len) = len.filter(|&len| len <= MAX_FRAME_LEN) else {
+                    continue;
+                };
+                if self.buffer.len() < len {
+                    incomplete = true;
+                } else if crc_ok(&self.buffer[..len]) {
+                    frame_len = Some((len, index == 0));
+                    break;
+                } else {
+                    checked = true;
+                }
+            }
+
+            match frame_len {
+                Some((len, is_response)) => {
+                    self.in_sync = true;
+                    self.counters.record_frame_ok();
+                    let frame: Vec<u8> = self.buffer.drain(..len).collect();
+                    observations.extend(self.decode(&frame, is_response));
+                }
+                None if incomplete => break,
+                None => {
+                    // Out of sync: skip a byte and look for the next frame
+                    if self.in_sync {
+                        if checked {
+                            self.counters.record_crc_error();
+                        } else {
+                            self.counters.record_framing_error();
+                        }
+                        self.in_sync = false;
+                    }
+                    self.buffer.remove(0);
+                    self.pending = None;
+                }
+            }
+        }
+        observations
+    }
+
+    /// Drop partial data after the bus went quiet
+    pub fn reset(&mut self) {
+        if !self.buffer.is_empty() {
+            self.counters.record_framing_error();
+            self.buffer.clear();
+        }
+    }
+
+    /// Possible lengths of the frame at the start of the buffer: as the
+    /// response to the pending request, then as a new request
+    fn frame_lengths(&self) -> [Option<usize>; 2] {
+        let (unit_id, function) = (self.buffer[0], self.buffer[1]);
+        let response = self
+            .pending
+            .as_ref()
+            .filter(|p| p.unit_id == unit_id && p.function == function & 0x7F)
+            .map(|_| match function {
+                0x01..=0x04 => 5 + self.buffer[2] as usize,
+                // Exception: address, function | 0x80, exception code, CRC
+                f if f & 0x80 != 0 => 5,
+                // Exception status: address, function, status byte, CRC
+                0x07 => 5,
+                _ => 8,
+            });
+        let request = match function {
+            0x01..=0x06 => Some(8),
+            0x07 => Some(4),
+            0x10 => self.buffer.get(6).map(|&count| 9 + count as usize),
+            _ => None,
+        };
+        [response, request]
+    }
+
+    /// Decode a complete frame with a valid CRC
+    fn decode(&mut self, frame: &[u8], is_response: bool) -> Option<Observation> {
+        let (unit_id, function) = (frame[0], frame[1]);
+
+        if let Some(request) = self.pending.take() {
+            if is_response {
+                if function & 0x80 != 0 {
+                    return None;
+                }
+                return match function {
+                    0x01 | 0x02 => {
+                        let bits = unpack_bits(&frame[3..frame.len() - 2], request.quantity)?;
+                        request.observation(bits)
+                    }
+                    0x03 | 0x04 => {
+                        let words = unpack_words(&frame[3..frame.len() - 2]);
+                        if words.len() != request.quantity as usize {
+                            return None;
+                        }
+                        request.observation(words)
+                    }
+                    0x07 => {
+                        let bits = unpack_bits(&frame[2..3], 8)?;
+                        request.observation(bits)
+                    }
+                    // Write confirmed
+                    _ => {
+                        let values = request.values.clone();
+                        request.observation(values)
+                    }
+                };
+            }
+        }
+
+        let word = |i: usize| u16::from_be_bytes([frame[i], frame[i + 1]]);
+        let request = match function {
+            0x01..=0x04 => Request {
+                unit_id,
+                function,
+                address: word(2),
+                quantity: word(4),
+                values: vec![],
+            },
+            0x05 => Request {
+                unit_id,
+                function,
+                address: word(2),
+                quantity: 1,
+                values: vec![u16::from(word(4) == 0xFF00)],
+            },
+            0x06 => Request {
+                unit_id,
+                function,
+                address: word(2),
+                quantity: 1,
+                values: vec![word(4)],
+            },
+            0x10 => Request {
+                unit_id,
+                function,
+                address: word(2),
+                quantity: word(4),
+                values: unpack_words(&frame[7..frame.len() - 2]),
+            },
+            0x07 => Request {
+                unit_id,
+                function,
+                address: 0,
+                quantity: 8,
+                values: vec![],
+            },
+            _ => return None,
+        };
+
+        // Broadcast writes get no response
+        if unit_id == 0 {
+            let values = request.values.clone();
+            return request.observation(values);
+        }
+        self.pending = Some(request);
+        None
+    }
+}
+
+fn unpack_words(bytes: &[u8]) -> Vec<u16> {
+    bytes
+        .chunks_exact(2)
+        .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+        .collect()
+}
+
+/// Coil and discrete input bits, least significant bit first
+fn unpack_bits(bytes: &[u8], quantity: u16) -> Option<Vec<u16>> {
+    if bytes.len() != (quantity as usize).div_ceil(8) {
+        return None;
+    }
+    Some(
+        (0..quantity as usize)
+            .map(|i| u16::from(bytes[i / 8] >> (i % 8) & 1))
+            .collect(),
+    )
+}
+
+fn crc_ok(frame: &[u8]) -> bool {
+    let (data, crc) = frame.split_at(frame.len() - 2);
+    crc16(data) == u16::from_le_bytes([crc[0], crc[1]])
+}
+
+/// Modbus CRC-16 (polynomial 0xA001, initial value 0xFFFF)
+fn crc16(bytes: &[u8]) -> u16 {
+    bytes.iter().fold(0xFFFF, |crc, &byte| {
+        (0..8).fold(crc ^ byte as u16, |crc, _| {
+            if crc & 1 != 0 {
+                (crc >> 1) ^ 0xA001
+            } else {
+                crc >> 1
+            }
+        })
+    })
+}
+
+/// Silence after which partial data is discarded: 3.5 characters of 11 bits,
+/// but at least a few milliseconds since USB adapters deliver bytes in bursts
+fn idle_gap(baud_rate: u32) -> Duration {
+    let char_time = 11.0 / baud_rate.max(1) as f64;
+    Duration::from_secs_f64(char_time * 3.5).max(Duration::from_millis(20))
+}
+
+fn sniffers() -> &'static Mutex<HashMap<String, broadcast::WeakSender<Observation>>> {
+    static SNIFFERS: OnceLock<Mutex<HashMap<String, broadcast::WeakSender<Observation>>>> =
+        OnceLock::new();
+    SNIFFERS.get_or_init(Default::default)
+}
+
+/// Receive the observations of the bus behind `rtu`, opening its port on the
+/// first subscription. The port is closed when the last receiver is dropped.
+pub fn subscribe(rtu: &RtuConnection) -> Result<broadcast::Receiver<Observation>> {
+    let mut sniffers = sniffers().lock().unwrap_or_else(|e| e.into_inner());
+    let key = if rtu.port.is_empty() {
+        format!("{:?}", rtu.usb)
+    } else {
+        rtu.port.clone()
+    };
+    // The sender lives as long as the task reading the port
+    if let Some(tx) = sniffers.get(&key).and_then(|tx| tx.upgrade()) {
+        return Ok(tx.subscribe());
+    }
+
+    let (port, port_name) = super::open_serial_port(rtu)?;
+    info!("Listening to Modbus RTU traffic on {}", port_name);
+    let (tx, rx) = broadcast::channel(OBSERVATION_CAPACITY);
+    sniffers.insert(key, tx.downgrade());
+    let decoder = FrameDecoder::new(bus_stats::bus(&port_name));
+    tokio::spawn(listen(port, decoder, tx, idle_gap(rtu.baud_rate)));
+    Ok(rx)
+}
+
+/// Read the bus until it fails or nobody listens anymore; the port is never written
+async fn listen<R: AsyncRead + Unpin>(
+    mut port: R,
+    mut decoder: FrameDecoder,
+    tx: broadcast::Sender<Observation>,
+    idle: Duration,
+) {
+    let mut buf = [0u8; MAX_FRAME_LEN];
+    loop {
+        if tx.receiver_count() == 0 {
+            return;
+        }
+        match tokio::time::timeout(idle, port.read(&mut buf)).await {
+            Err(_) => decoder.reset(),
+            Ok(Ok(0)) => {
+                warn!("Serial port closed while listening");
+                return;
+            }
+            Ok(Ok(read)) => {
+                for observation in decoder.push(&buf[..read]) {
+                    let _ = tx.send(observation);
+                }
+            }
+            Ok(Err(e)) => {
+                warn!("Listening on serial port failed: {}", e);
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::DataType;
+    use tokio::io::AsyncWriteExt;
+
+    fn frame(pdu: &[u8]) -> Vec<u8> {
+        let mut frame = pdu.to_vec();
+        frame.extend_from_slice(&crc16(pdu).to_le_bytes());
+        frame
+    }
+
+    fn decoder() -> FrameDecoder {
+        FrameDecoder::new(bus_stats::bus("/dev/test-sniffer"))
+    }
+
+    #[test]
+    fn test_read_request_and_response() {
+        let mut decoder = decoder();
+        let request = frame(&[0x05, 0x03, 0x00, 0x64, 0x00, 0x02]);
+        let response = frame(&[0x05, 0x03, 0x04, 0x00, 0xEB, 0x01, 0x02]);
+
+        // Split arbitrarily, as a serial port delivers it
+        let mut bus = [request, response].concat();
+        let tail = bus.split_off(5);
+        assert!(decoder.push(&bus).is_empty());
+        assert_eq!(
+            decoder.push(&tail),
+            vec![Observation {
+                unit_id: 5,
+                register_type: RegisterType::Holding,
+                address: 100,
+                values: vec![235, 0x0102],
+            }]
+        );
+    }
+
+    #[test]
+    fn test_exception_status() {
+        let mut decoder = decoder();
+        let bus = [frame(&[0x05, 0x07]), frame(&[0x05, 0x07, 0x09])].concat();
+        assert_eq!(
+            decoder.push(&bus),
+            vec![Observation {
+                unit_id: 5,
+                register_type: RegisterType::ExceptionStatus,
+                address: 0,
+                values: vec![1, 0, 0, 1, 0, 0, 0, 0],
+            }]
+        );
+    }
+
+    #[test]
+    fn test_coils_writes_and_broadcast() {
+        let mut decoder = decoder();
+        let bus = [
+            frame(&[0x01, 0x01, 0x00, 0x0A, 0x00, 0x03]),
+            frame(&[0x01, 0x01, 0x01, 0b101]),
+            frame(&[
+                0x01, 0x10, 0x00, 0x14, 0x00, 0x02, 0x04, 0x00, 0x01, 0x00, 0x02,
+            ]),
+            frame(&[0x01, 0x10, 0x00, 0x14, 0x00, 0x02]),
+            frame(&[0x00, 0x06, 0x00, 0x1E, 0x00, 0x07]),
+            // Exception response: nothing observed
+            frame(&[0x01, 0x03, 0x00, 0x00, 0x00, 0x01]),
+            frame(&[0x01, 0x83, 0x02]),
+        ]
+        .concat();
+
+        let observations = decoder.push(&bus);
+        assert_eq!(observations.len(), 3);
+        assert_eq!(observations[0].register_type, RegisterType::Coil);
+        assert_eq!(observations[0].values, vec![1, 0, 1]);
+        assert_eq!(observations[1].address, 20);
+        assert_eq!(observations[1].values, vec![1, 2]);
+        assert_eq!(observations[2].unit_id, 0);
+        assert_eq!(observations[2].values, vec![7]);
+    }
+
+    #[test]
+    fn test_resynchronizes_after_garbage() {
+        let mut decoder = decoder();
+        let bus = [
+            vec![0xFF, 0x00, 0x12],
+            // Unanswered request sent again by the master
+            frame(&[0x02, 0x04, 0x00, 0x00, 0x00, 0x01]),
+            frame(&[0x02, 0x04, 0x00, 0x00, 0x00, 0x01]),
+            frame(&[0x02, 0x04, 0x02, 0x00, 0x2A]),
+        ]
+        .concat();
+
+        let observations = decoder.push(&bus);
+        assert_eq!(observations.len(), 1);
+        assert_eq!(observations[0].register_type, RegisterType::Input);
+        assert_eq!(observations[0].values, vec![42]);
+    }
+
+    #[test]
+    fn test_registers_of() {
+        let observation = Observation {
+            unit_id: 5,
+            register_type: RegisterType::Holding,
+            address: 100,
+            values: vec![1, 2, 3],
+        };
+        let register = RegisterConfig {
+            name: "energy".to_string(),
+            address: 101,
+            register_type: RegisterType::Holding,
+            count: 2,
+            data_type: DataType::U32,
+            unit: None,
+            scale: None,
+            offset: None,
+            byte_order: Default::default(),
+            non_finite: Default::default(),
+            priority: Default::default(),
+            meta: Default::default(),
+            read_when: None,
+            alarm: None,
+            write_limits: None,
+            oversample: None,
+            read_after: None,
+            trigger: None,
+            min_stable_ms: None,
+            persistent: false,
+            publish_on_change: false,
+            deadband: None,
+            heartbeat_interval_s: None,
+            points: Vec::new(),
+            expression: None,
+            lookup: None,
+        };
+
+        assert_eq!(observation.registers_of(5, &register), Some(&[2, 3][..]));
+        assert_eq!(observation.registers_of(6, &register), None);
+        let beyond = RegisterConfig {
+            address: 102,
+            ..register.clone()
+        };
+        assert_eq!(observation.registers_of(5, &beyond), None);
+    }
+
+    #[tokio::test]
+    async fn test_listen_never_writes() {
+        let (mut bus, port) = tokio::io::duplex(64);
+        let (tx, mut rx) = broadcast::channel(4);
+        tokio::spawn(listen(port, decoder(), tx, Duration::from_millis(20)));
+
+        bus.write_all(&frame(&[0x01, 0x06, 0x00, 0x01, 0x00, 0x09]))
+            .await
+            .unwrap();
+        bus.write_all(&frame(&[0x01, 0x06, 0x00, 0x01, 0x00, 0x09]))
+            .await
+            .unwrap();
+        let observation = rx.recv().await.unwrap();
+        assert_eq!(observation.values, vec![9]);
+
+        // Nothing was sent back on the bus
+        let mut buf = [0u8; 1];
+        assert!(
+            tokio::time::timeout(Duration::from_millis(50), bus.read(&mut buf))
+                .await
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,100 @@
+//! Device clock synchronization
+//!
+//! Encodes the bridge clock into the register layout of a device's RTC, as
+//! configured by a device's `time_sync` section. The poll loop writes it after
+//! every connect and then every `interval_secs`.
+
+use chrono::{DateTime, Datelike, Local, TimeZone, Timelike, Utc};
+
+use crate::config::{TimeField, TimeSyncConfig, TimeZoneMode};
+
+/// Registers to write for the current time
+pub fn registers_now(config: &TimeSyncConfig) -> Vec<u16> {
+    let now = Utc::now();
+    match config.timezone {
+        TimeZoneMode::Utc => encode(config, &now),
+        TimeZoneMode::Local => encode(config, &now.with_timezone(&Local)),
+    }
+}
+
+/// Registers for `time` in the configured layout
+pub fn encode<Tz: TimeZone>(config: &TimeSyncConfig, time: &DateTime<Tz>) -> Vec<u16> {
+    let mut registers = Vec::with_capacity(config.register_count());
+    for field in &config.layout {
+        let value = match field {
+            TimeField::Year => time.year() as u16,
+            TimeField::Year2 => (time.year() % 100) as u16,
+            TimeField::Month => time.month() as u16,
+            TimeField::Day => time.day() as u16,
+            TimeField::Hour => time.hour() as u16,
+            TimeField::Minute => time.minute() as u16,
+            // A leap second is reported as second 59
+            TimeField::Second => time.second().min(59) as u16,
+            TimeField::Millisecond => (time.timestamp_subsec_millis() % 1000) as u16,
+            TimeField::Weekday => time.weekday().num_days_from_sunday() as u16,
+            TimeField::Unix => {
+                let secs = time.timestamp() as u32;
+                registers.push((secs >> 16) as u16);
+                registers.push(secs as u16);
+                continue;
+            }
+            TimeField::Reserved => 0,
+        };
+        registers.push(if config.bcd { to_bcd(value) } else { value });
+    }
+    registers
+}
+
+/// Binary-coded decimal of a value below 10000
+fn to_bcd(value: u16) -> u16 {
+    (0..4).fold(0, |bcd, digit| {
+        bcd | ((value / 10u16.pow(digit)) % 10) << (4 * digit)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(layout: Vec<TimeField>, bcd: bool) -> TimeSyncConfig {
+        TimeSyncConfig {
+            address: 200,
+            layout,
+            interval_secs: 3600,
+            timezone: TimeZoneMode::Utc,
+            bcd,
+        }
+    }
+
+    #[test]
+    fn test_encode_layout() {
+        let time = Utc.with_ymd_and_hms(2026, 10, 16, 7, 5, 9).unwrap();
+        let layout = vec![
+            TimeField::Year,
+            TimeField::Month,
+            TimeField::Day,
+            TimeField::Hour,
+            TimeField::Minute,
+            TimeField::Second,
+            TimeField::Weekday,
+            TimeField::Reserved,
+        ];
+
+        assert_eq!(
+            encode(&config(layout.clone(), false), &time),
+            vec![2026, 10, 16, 7, 5, 9, 5, 0]
+        );
+        assert_eq!(
+            encode(&config(layout, true), &time),
+            vec![0x2026, 0x10, 0x16, 0x07, 0x05, 0x09, 0x05, 0]
+        );
+    }
+
+    #[test]
+    fn test_encode_unix() {
+        let time = Utc.timestamp_opt(0x6A0B_1C2D, 0).unwrap();
+        let config = config(vec![TimeField::Unix, TimeField::Year2], false);
+        assert_eq!(config.register_count(), 3);
+        assert_eq!(encode(&config, &time), vec![0x6A0B, 0x1C2D, 26]);
+    }
+}
@@ -0,0 +1,479 @@
+//! Non-linear conversions from decoded register values
+//!
+//! Where `scale`/`offset` cannot describe a sensor, a register can take an
+//! [`Expression`] of the decoded value `raw`, such as `sqrt(raw) * 1.414` or
+//! the polynomial `0.2 + 0.01 * raw - 1.5e-6 * raw^2`, or a [`LookupTable`]
+//! interpolating between calibration points. Both are checked when the
+//! config is loaded and evaluated on every reading.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Arithmetic expression of the variable `raw`
+///
+/// Supports numbers, `+ - * / %`, `^` (power), parentheses, the constants
+/// `pi` and `e`, and the functions `sqrt`, `abs`, `ln`, `log10`, `exp`,
+/// `sin`, `cos`, `tan`, `floor`, `ceil`, `round`, `min`, `max`, `pow` and
+/// `clamp`.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Expression {
+    source: String,
+    root: Node,
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Number(f64),
+    Raw,
+    Negate(Box<Node>),
+    Binary(Operator, Box<Node>, Box<Node>),
+    Call(Function, Vec<Node>),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Operator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Remainder,
+    Power,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Function {
+    Sqrt,
+    Abs,
+    Ln,
+    Log10,
+    Exp,
+    Sin,
+    Cos,
+    Tan,
+    Floor,
+    Ceil,
+    Round,
+    Min,
+    Max,
+    Pow,
+    Clamp,
+}
+
+impl Function {
+    fn named(name: &str) -> Option<Self> {
+        Some(match name {
+            "sqrt" => Function::Sqrt,
+            "abs" => Function::Abs,
+            "ln" => Function::Ln,
+            "log10" => Function::Log10,
+            "exp" => Function::Exp,
+            "sin" => Function::Sin,
+            "cos" => Function::Cos,
+            "tan" => Function::Tan,
+            "floor" => Function::Floor,
+            "ceil" => Function::Ceil,
+            "round" => Function::Round,
+            "min" => Function::Min,
+            "max" => Function::Max,
+            "pow" => Function::Pow,
+            "clamp" => Function::Clamp,
+            _ => return None,
+        })
+    }
+
+    fn arity(self) -> usize {
+        match self {
+            Function::Min | Function::Max | Function::Pow => 2,
+            Function::Clamp => 3,
+            _ => 1,
+        }
+    }
+
+    fn apply(self, args: &[f64]) -> f64 {
+        match self {
+            Function::Sqrt => args[0].sqrt(),
+            Function::Abs => args[0].abs(),
+            Function::Ln => args[0].ln(),
+            Function::Log10 => args[0].log10(),
+            Function::Exp => args[0].exp(),
+            Function::Sin => args[0].sin(),
+            Function::Cos => args[0].cos(),
+            Function::Tan => args[0].tan(),
+            Function::Floor => args[0].floor(),
+            Function::Ceil => args[0].ceil(),
+            Function::Round => args[0].round(),
+            Function::Min => args[0].min(args[1]),
+            Function::Max => args[0].max(args[1]),
+            Function::Pow => args[0].powf(args[1]),
+            Function::Clamp => args[0].max(args[1]).min(args[2]),
+        }
+    }
+}
+
+impl Expression {
+    /// Parse an expression, describing the first problem on failure
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let mut parser = Parser {
+            tokens: tokenize(source)?,
+            position: 0,
+        };
+        let root = parser.expression()?;
+        if let Some(token) = parser.peek() {
+            return Err(format!("unexpected '{}' in expression '{}'", token, source));
+        }
+        Ok(Self {
+            source: source.to_string(),
+            root,
+        })
+    }
+
+    /// Value of the expression for a decoded register value
+    ///
+    /// Undefined results (`sqrt(-1)`, division by zero) are NaN or infinite
+    /// and handled by the register's `non_finite` policy.
+    pub fn eval(&self, raw: f64) -> f64 {
+        eval(&self.root, raw)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+}
+
+fn eval(node: &Node, raw: f64) -> f64 {
+    match node {
+        Node::Number(value) => *value,
+        Node::Raw => raw,
+        Node::Negate(operand) => -eval(operand, raw),
+        Node::Binary(operator, left, right) => {
+            let (a, b) = (eval(left, raw), eval(right, raw));
+            match operator {
+                Operator::Add => a + b,
+                Operator::Subtract => a - b,
+                Operator::Multiply => a * b,
+                Operator::Divide => a / b,
+                Operator::Remainder => a % b,
+                Operator::Power => a.powf(b),
+            }
+        }
+        Node::Call(function, args) => {
+            let args: Vec<f64> = args.iter().map(|arg| eval(arg, raw)).collect();
+            function.apply(&args)
+        }
+    }
+}
+
+impl TryFrom<String> for Expression {
+    type Error = String;
+
+    fn try_from(source: String) -> Result<Self, Self::Error> {
+        Self::parse(&source)
+    }
+}
+
+impl From<Expression> for String {
+    fn from(expression: Expression) -> Self {
+        expression.source
+    }
+}
+
+impl fmt::Debug for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Expression({:?})", self.source)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Name(String),
+    Symbol(char),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Number(value) => write!(f, "{}", value),
+            Token::Name(name) => write!(f, "{}", name),
+            Token::Symbol(symbol) => write!(f, "{}", symbol),
+        }
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = source.char_indices().peekable();
+    while let Some(&(start, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c.is_ascii_digit() || c == '.' {
+            let mut end = start;
+            while let Some(&(i, c)) = chars.peek() {
+                // An exponent sign belongs to the number: 1.5e-6
+                let exponent_sign =
+                    (c == '-' || c == '+') && matches!(source[..i].chars().last(), Some('e' | 'E'));
+                if !(c.is_ascii_digit() || c == '.' || c == 'e' || c == 'E' || exponent_sign) {
+                    break;
+                }
+                end = i + c.len_utf8();
+                chars.next();
+            }
+            let text = &source[start..end];
+            let value = text
+                .parse()
+                .map_err(|_| format!("invalid number '{}' in expression '{}'", text, source))?;
+            tokens.push(Token::Number(value));
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let mut end = start;
+            while let Some(&(i, c)) = chars.peek() {
+                if !(c.is_ascii_alphanumeric() || c == '_') {
+                    break;
+                }
+                end = i + c.len_utf8();
+                chars.next();
+            }
+            tokens.push(Token::Name(source[start..end].to_string()));
+        } else if "+-*/%^(),".contains(c) {
+            tokens.push(Token::Symbol(c));
+            chars.next();
+        } else {
+            return Err(format!("unexpected '{}' in expression '{}'", c, source));
+        }
+    }
+    if tokens.is_empty() {
+        return Err("empty expression".to_string());
+    }
+    Ok(tokens)
+}
+
+/// Recursive descent over the usual precedence: `+ -`, then `* / %`, then
+/// unary minus, then right-associative `^`
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn eat(&mut self, symbol: char) -> bool {
+        if self.peek() == Some(&Token::Symbol(symbol)) {
+            self.position += 1;
+            return true;
+        }
+        false
+    }
+
+    fn expect(&mut self, symbol: char) -> Result<(), String> {
+        match self.next() {
+            Some(Token::Symbol(c)) if c == symbol => Ok(()),
+            Some(token) => Err(format!("expected '{}', found '{}'", symbol, token)),
+            None => Err(format!("expected '{}' at the end", symbol)),
+        }
+    }
+
+    fn expression(&mut self) -> Result<Node, String> {
+        let mut node = self.term()?;
+        loop {
+            let operator = if self.eat('+') {
+                Operator::Add
+            } else if self.eat('-') {
+                Operator::Subtract
+            } else {
+                return Ok(node);
+            };
+            node = Node::Binary(operator, Box::new(node), Box::new(self.term()?));
+        }
+    }
+
+    fn term(&mut self) -> Result<Node, String> {
+        let mut node = self.unary()?;
+        loop {
+            let operator = if self.eat('*') {
+                Operator::Multiply
+            } else if self.eat('/') {
+                Operator::Divide
+            } else if self.eat('%') {
+                Operator::Remainder
+            } else {
+                return Ok(node);
+            };
+            node = Node::Binary(operator, Box::new(node), Box::new(self.unary()?));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Node, String> {
+        if self.eat('-') {
+            return Ok(Node::Negate(Box::new(self.unary()?)));
+        }
+        self.eat('+');
+        let base = self.atom()?;
+        if self.eat('^') {
+            // -raw^2 is -(raw^2), and 2^-1 is allowed
+            let exponent = self.unary()?;
+            return Ok(Node::Binary(
+                Operator::Power,
+                Box::new(base),
+                Box::new(exponent),
+            ));
+        }
+        Ok(base)
+    }
+
+    fn atom(&mut self) -> Result<Node, String> {
+        match self.next() {
+            Some(Token::Number(value)) => Ok(Node::Number(value)),
+            Some(Token::Symbol('(')) => {
+                let node = self.expression()?;
+                self.expect(')')?;
+                Ok(node)
+            }
+            Some(Token::Name(name)) => match name.as_str() {
+                "raw" => Ok(Node::Raw),
+                "pi" => Ok(Node::Number(std::f64::consts::PI)),
+                "e" => Ok(Node::Number(std::f64::consts::E)),
+                _ => {
+                    let function =
+                        Function::named(&name).ok_or_else(|| format!("unknown name '{}'", name))?;
+                    self.call(&name, function)
+                }
+            },
+            Some(token) => Err(format!("unexpected '{}'", token)),
+            None => Err("expression ends too early".to_string()),
+        }
+    }
+
+    fn call(&mut self, name: &str, function: Function) -> Result<Node, String> {
+        self.expect('(')?;
+        let mut args = vec![self.expression()?];
+        while self.eat(',') {
+            args.push(self.expression()?);
+        }
+        self.expect(')')?;
+        if args.len() != function.arity() {
+            return Err(format!(
+                "{}() takes {} argument(s), got {}",
+                name,
+                function.arity(),
+                args.len()
+            ));
+        }
+        Ok(Node::Call(function, args))
+    }
+}
+
+/// Calibration points `[raw, value]` with linear interpolation in between
+///
+/// Raw values must be strictly increasing. Readings outside the table are
+/// clamped to its first or last value.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "Vec<[f64; 2]>", into = "Vec<[f64; 2]>")]
+pub struct LookupTable {
+    points: Vec<[f64; 2]>,
+}
+
+impl LookupTable {
+    /// Interpolated value of a decoded register value
+    pub fn apply(&self, raw: f64) -> f64 {
+        if raw.is_nan() {
+            return raw;
+        }
+        let upper = self.points.partition_point(|[x, _]| *x < raw);
+        match upper {
+            0 => self.points[0][1],
+            n if n == self.points.len() => self.points[n - 1][1],
+            n => {
+                let [x0, y0] = self.points[n - 1];
+                let [x1, y1] = self.points[n];
+                y0 + (raw - x0) * (y1 - y0) / (x1 - x0)
+            }
+        }
+    }
+}
+
+impl TryFrom<Vec<[f64; 2]>> for LookupTable {
+    type Error = String;
+
+    fn try_from(points: Vec<[f64; 2]>) -> Result<Self, Self::Error> {
+        if points.len() < 2 {
+            return Err("a lookup table needs at least 2 points".to_string());
+        }
+        if points.iter().flatten().any(|v| !v.is_finite()) {
+            return Err("lookup table points must be finite".to_string());
+        }
+        if let Some(pair) = points.windows(2).find(|pair| pair[0][0] >= pair[1][0]) {
+            return Err(format!(
+                "lookup table raw values must increase ({} is followed by {})",
+                pair[0][0], pair[1][0]
+            ));
+        }
+        Ok(Self { points })
+    }
+}
+
+impl From<LookupTable> for Vec<[f64; 2]> {
+    fn from(table: LookupTable) -> Self {
+        table.points
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(source: &str, raw: f64) -> f64 {
+        Expression::parse(source).unwrap().eval(raw)
+    }
+
+    #[test]
+    fn test_expression_evaluation() {
+        assert_eq!(eval("sqrt(raw) * 1.414", 16.0), 4.0 * 1.414);
+        assert_eq!(eval("1 + 2 * raw - 3", 5.0), 8.0);
+        assert_eq!(eval("(1 + 2) * raw", 5.0), 15.0);
+        assert_eq!(eval("0.5 + 0.01 * raw + 1.5e-3 * raw^2", 10.0), 0.75);
+        assert_eq!(eval("-raw^2", 3.0), -9.0);
+        assert_eq!(eval("2^3^2", 0.0), 512.0);
+        assert_eq!(eval("clamp(raw, 0, 100)", 140.0), 100.0);
+        assert_eq!(eval("max(raw, 1) % 4", 10.0), 2.0);
+        assert!(eval("sqrt(raw)", -1.0).is_nan());
+    }
+
+    #[test]
+    fn test_expression_errors() {
+        for (source, error) in [
+            ("", "empty expression"),
+            ("raw +", "expression ends too early"),
+            ("x * 2", "unknown name 'x'"),
+            ("sqrt(raw, 2)", "sqrt() takes 1 argument(s), got 2"),
+            ("(raw", "expected ')' at the end"),
+            ("raw 2", "unexpected '2'"),
+            ("raw # 2", "unexpected '#'"),
+        ] {
+            let message = Expression::parse(source).unwrap_err();
+            assert!(message.contains(error), "{}: {}", source, message);
+        }
+    }
+
+    #[test]
+    fn test_lookup_table() {
+        let table =
+            LookupTable::try_from(vec![[0.0, 0.0], [4000.0, 2.5], [20000.0, 10.0]]).unwrap();
+        assert_eq!(table.apply(2000.0), 1.25);
+        assert_eq!(table.apply(12000.0), 6.25);
+        assert_eq!(table.apply(4000.0), 2.5);
+        assert_eq!(table.apply(-5.0), 0.0);
+        assert_eq!(table.apply(30000.0), 10.0);
+
+        assert!(LookupTable::try_from(vec![[0.0, 1.0]]).is_err());
+        assert!(LookupTable::try_from(vec![[1.0, 1.0], [1.0, 2.0]]).is_err());
+    }
+}
@@ -0,0 +1,382 @@
+//! Transport abstraction for Modbus requests
+//!
+//! [`ModbusClient`](super::ModbusClient) talks to devices through a
+//! [`ModbusTransport`]. The TCP and RTU connections implement it, and
+//! [`MockTransport`] serves registers from memory so register handling can be
+//! tested without sockets or serial ports.
+
+use async_trait::async_trait;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio_modbus::prelude::{Slave, SlaveContext};
+use tokio_modbus::Exception;
+
+use super::client::{Context, ModbusError};
+
+/// Modbus function calls used by the bridge
+#[async_trait]
+pub trait ModbusTransport: Send {
+    /// Read holding registers (FC03)
+    async fn read_holding_registers(
+        &mut self,
+        addr: u16,
+        cnt: u16,
+    ) -> Result<Vec<u16>, ModbusError>;
+
+    /// Read input registers (FC04)
+    async fn read_input_registers(&mut self, addr: u16, cnt: u16) -> Result<Vec<u16>, ModbusError>;
+
+    /// Read coils (FC01)
+    async fn read_coils(&mut self, addr: u16, cnt: u16) -> Result<Vec<bool>, ModbusError>;
+
+    /// Read discrete inputs (FC02)
+    async fn read_discrete_inputs(&mut self, addr: u16, cnt: u16)
+        -> Result<Vec<bool>, ModbusError>;
+
+    /// Write a single holding register (FC06)
+    async fn write_single_register(&mut self, addr: u16, value: u16) -> Result<(), ModbusError>;
+
+    /// Write consecutive holding registers (FC16)
+    async fn write_multiple_registers(
+        &mut self,
+        addr: u16,
+        values: &[u16],
+    ) -> Result<(), ModbusError>;
+
+    /// Write a single coil (FC05)
+    async fn write_single_coil(&mut self, addr: u16, value: bool) -> Result<(), ModbusError>;
+
+    /// Read the eight exception status bits (FC07)
+    async fn read_exception_status(&mut self) -> Result<u8, ModbusError>;
+
+    /// Read the queued registers of the FIFO at `addr` (FC24)
+    async fn read_fifo_queue(&mut self, addr: u16) -> Result<Vec<u16>, ModbusError>;
+
+    /// Address the following requests to another unit ID
+    ///
+    /// Used when devices share a connection; ignored by default.
+    fn set_unit(&mut self, _unit: u8) {}
+
+    /// Limit each request to `timeout`, returning whether the transport does
+    /// so itself; by default the client wraps each request instead
+    fn set_timeout(&mut self, _timeout: Option<Duration>) -> bool {
+        false
+    }
+}
+
+#[async_trait]
+impl ModbusTransport for Context {
+    async fn read_holding_registers(
+        &mut self,
+        addr: u16,
+        cnt: u16,
+    ) -> Result<Vec<u16>, ModbusError> {
+        Context::read_holding_registers(self, addr, cnt).await
+    }
+
+    async fn read_input_registers(&mut self, addr: u16, cnt: u16) -> Result<Vec<u16>, ModbusError> {
+        Context::read_input_registers(self, addr, cnt).await
+    }
+
+    async fn read_coils(&mut self, addr: u16, cnt: u16) -> Result<Vec<bool>, ModbusError> {
+        Context::read_coils(self, addr, cnt).await
+    }
+
+    async fn read_discrete_inputs(
+        &mut self,
+        addr: u16,
+        cnt: u16,
+    ) -> Result<Vec<bool>, ModbusError> {
+        Context::read_discrete_inputs(self, addr, cnt).await
+    }
+
+    async fn write_single_register(&mut self, addr: u16, value: u16) -> Result<(), ModbusError> {
+        Context::write_single_register(self, addr, value).await
+    }
+
+    async fn write_multiple_registers(
+        &mut self,
+        addr: u16,
+        values: &[u16],
+    ) -> Result<(), ModbusError> {
+        Context::write_multiple_registers(self, addr, values).await
+    }
+
+    async fn write_single_coil(&mut self, addr: u16, value: bool) -> Result<(), ModbusError> {
+        Context::write_single_coil(self, addr, value).await
+    }
+
+    async fn read_exception_status(&mut self) -> Result<u8, ModbusError> {
+        Context::read_exception_status(self).await
+    }
+
+    async fn read_fifo_queue(&mut self, addr: u16) -> Result<Vec<u16>, ModbusError> {
+        Context::read_fifo_queue(self, addr).await
+    }
+
+    fn set_unit(&mut self, unit: u8) {
+        match self {
+            Context::Tcp(ctx) | Context::Rtu(ctx) => ctx.set_slave(Slave(unit)),
+        }
+    }
+}
+
+/// Register tables and injected failures of a [`MockTransport`]
+#[derive(Default)]
+struct MockState {
+    holding: HashMap<u16, u16>,
+    input: HashMap<u16, u16>,
+    coils: HashMap<u16, bool>,
+    discrete: HashMap<u16, bool>,
+    failures: VecDeque<ModbusError>,
+    requests: usize,
+    silent_writes: bool,
+    exception_status: Option<u8>,
+    fifos: HashMap<u16, VecDeque<u16>>,
+}
+
+/// In-memory device for tests
+///
+/// Clones share the same register tables, so a test can keep one clone to
+/// set values and inspect writes after handing another to a client.
+/// Reading an address that was never set returns an `IllegalDataAddress`
+/// exception, like a real device.
+#[derive(Clone, Default)]
+pub struct MockTransport {
+    state: Arc<Mutex<MockState>>,
+}
+
+impl MockTransport {
+    /// Empty device: every read fails until registers are set
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set holding registers starting at `addr`
+    pub fn set_holding(&self, addr: u16, values: &[u16]) {
+        fill(&mut self.lock().holding, addr, values);
+    }
+
+    /// Set input registers starting at `addr`
+    pub fn set_input(&self, addr: u16, values: &[u16]) {
+        fill(&mut self.lock().input, addr, values);
+    }
+
+    /// Set coils starting at `addr`
+    pub fn set_coils(&self, addr: u16, values: &[bool]) {
+        fill(&mut self.lock().coils, addr, values);
+    }
+
+    /// Set discrete inputs starting at `addr`
+    pub fn set_discrete(&self, addr: u16, values: &[bool]) {
+        fill(&mut self.lock().discrete, addr, values);
+    }
+
+    /// Set the exception status byte; until set, FC07 fails with `IllegalFunction`
+    pub fn set_exception_status(&self, status: u8) {
+        self.lock().exception_status = Some(status);
+    }
+
+    /// Queue entries in the FIFO at `addr`; FC24 returns and removes up to 31
+    pub fn push_fifo(&self, addr: u16, entries: &[u16]) {
+        self.lock()
+            .fifos
+            .entry(addr)
+            .or_default()
+            .extend(entries.iter().copied());
+    }
+
+    /// Current value of a holding register
+    pub fn holding(&self, addr: u16) -> Option<u16> {
+        self.lock().holding.get(&addr).copied()
+    }
+
+    /// Current value of a coil
+    pub fn coil(&self, addr: u16) -> Option<bool> {
+        self.lock().coils.get(&addr).copied()
+    }
+
+    /// Fail the next request with `error` (queued failures are used in order)
+    pub fn fail_next(&self, error: ModbusError) {
+        self.lock().failures.push_back(error);
+    }
+
+    /// Apply writes without ever answering them, like slaves receiving a broadcast
+    pub fn set_silent_writes(&self, silent: bool) {
+        self.lock().silent_writes = silent;
+    }
+
+    /// Number of requests received, including failed ones
+    pub fn requests(&self) -> usize {
+        self.lock().requests
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, MockState> {
+        // Every update is a single insert, so a poisoned lock still holds consistent tables
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Count the request and apply `f` unless a failure is queued
+    fn request<T>(
+        &self,
+        f: impl FnOnce(&mut MockState) -> Result<T, ModbusError>,
+    ) -> Result<T, ModbusError> {
+        let mut state = self.lock();
+        state.requests += 1;
+        match state.failures.pop_front() {
+            Some(error) => Err(error),
+            None => f(&mut state),
+        }
+    }
+}
+
+fn fill<T: Copy>(table: &mut HashMap<u16, T>, addr: u16, values: &[T]) {
+    for (offset, value) in values.iter().enumerate() {
+        table.insert(addr.wrapping_add(offset as u16), *value);
+    }
+}
+
+fn read<T: Copy>(table: &HashMap<u16, T>, addr: u16, cnt: u16) -> Result<Vec<T>, ModbusError> {
+    (0..cnt)
+        .map(|offset| {
+            addr.checked_add(offset)
+                .and_then(|a| table.get(&a).copied())
+                .ok_or(ModbusError::Exception(Exception::IllegalDataAddress))
+        })
+        .collect()
+}
+
+#[async_trait]
+impl ModbusTransport for MockTransport {
+    async fn read_holding_registers(
+        &mut self,
+        addr: u16,
+        cnt: u16,
+    ) -> Result<Vec<u16>, ModbusError> {
+        self.request(|state| read(&state.holding, addr, cnt))
+    }
+
+    async fn read_input_registers(&mut self, addr: u16, cnt: u16) -> Result<Vec<u16>, ModbusError> {
+        self.request(|state| read(&state.input, addr, cnt))
+    }
+
+    async fn read_coils(&mut self, addr: u16, cnt: u16) -> Result<Vec<bool>, ModbusError> {
+        self.request(|state| read(&state.coils, addr, cnt))
+    }
+
+    async fn read_discrete_inputs(
+        &mut self,
+        addr: u16,
+        cnt: u16,
+    ) -> Result<Vec<bool>, ModbusError> {
+        self.request(|state| read(&state.discrete, addr, cnt))
+    }
+
+    async fn write_single_register(&mut self, addr: u16, value: u16) -> Result<(), ModbusError> {
+        let result = self.request(|state| {
+            state.holding.insert(addr, value);
+            Ok(())
+        });
+        self.respond(result).await
+    }
+
+    async fn write_multiple_registers(
+        &mut self,
+        addr: u16,
+        values: &[u16],
+    ) -> Result<(), ModbusError> {
+        let result = self.request(|state| {
+            fill(&mut state.holding, addr, values);
+            Ok(())
+        });
+        self.respond(result).await
+    }
+
+    async fn write_single_coil(&mut self, addr: u16, value: bool) -> Result<(), ModbusError> {
+        let result = self.request(|state| {
+            state.coils.insert(addr, value);
+            Ok(())
+        });
+        self.respond(result).await
+    }
+
+    async fn read_exception_status(&mut self) -> Result<u8, ModbusError> {
+        self.request(|state| {
+            state
+                .exception_status
+                .ok_or(ModbusError::Exception(Exception::IllegalFunction))
+        })
+    }
+
+    async fn read_fifo_queue(&mut self, addr: u16) -> Result<Vec<u16>, ModbusError> {
+        self.request(|state| {
+            let fifo = state
+                .fifos
+                .get_mut(&addr)
+                .ok_or(ModbusError::Exception(Exception::IllegalDataAddress))?;
+            let count = fifo.len().min(31);
+            Ok(fifo.drain(..count).collect())
+        })
+    }
+}
+
+impl MockTransport {
+    /// Answer a write, or never if writes are silent
+    async fn respond(&self, result: Result<(), ModbusError>) -> Result<(), ModbusError> {
+        if self.lock().silent_writes {
+            std::future::pending::<()>().await;
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_mock_reads_and_writes() {
+        let mock = MockTransport::new();
+        mock.set_holding(100, &[1, 2, 3]);
+        mock.set_coils(0, &[true, false]);
+
+        let mut transport = mock.clone();
+        assert_eq!(
+            transport.read_holding_registers(101, 2).await.unwrap(),
+            vec![2, 3]
+        );
+        assert_eq!(transport.read_coils(0, 2).await.unwrap(), vec![true, false]);
+
+        transport
+            .write_multiple_registers(200, &[7, 8])
+            .await
+            .unwrap();
+        transport.write_single_coil(1, true).await.unwrap();
+        assert_eq!(mock.holding(201), Some(8));
+        assert_eq!(mock.coil(1), Some(true));
+        assert_eq!(mock.requests(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_mock_unset_address_is_exception() {
+        let mut mock = MockTransport::new();
+        mock.set_input(10, &[42]);
+
+        let err = mock.read_input_registers(10, 2).await.unwrap_err();
+        assert!(matches!(
+            err,
+            ModbusError::Exception(Exception::IllegalDataAddress)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_mock_injected_failure() {
+        let mut mock = MockTransport::new();
+        mock.set_holding(0, &[1]);
+        mock.fail_next(ModbusError::Exception(Exception::ServerDeviceBusy));
+
+        assert!(mock.read_holding_registers(0, 1).await.is_err());
+        assert_eq!(mock.read_holding_registers(0, 1).await.unwrap(), vec![1]);
+    }
+}
@@ -0,0 +1,286 @@
+//! Per-device messages (`publish_mode: device`)
+//!
+//! The updates of a device that arrive within `batch.window_ms` of the first
+//! one, normally the reads of one poll cycle, are published together on
+//! `{prefix}/{device_id}`:
+//!
+//! ```json
+//! {"timestamp": "2025-01-15T10:30:00+00:00", "full": true,
+//!  "values": {"pump": true, "temperature": 21.5}}
+//! ```
+//!
+//! With `batch.delta`, a message lists only the registers whose value changed
+//! since they were last published, and every `batch.full_snapshot_every`th
+//! batch lists all of them (`"full": true`). Batches without changes are not
+//! published. Bad and substituted readings follow `quality_policy`: listed in
+//! a `quality` object (`tag`), published like good ones (`publish`) or left
+//! out (`suppress`).
+
+use serde_json::{json, Map, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::Instant;
+use tracing::{debug, error, info};
+
+use super::{publish_error, MqttPublisher, Reading};
+use crate::api::{RegisterUpdate, UpdateReceiver};
+use crate::config::{BatchConfig, QualityPolicy};
+use crate::error::Result;
+use crate::metrics;
+use crate::metrics::error_log::{ErrorEvent, ErrorKind, ErrorSource};
+
+/// Updates of a device waiting to be published, by register name
+struct DeviceBatch {
+    started: Instant,
+    updates: BTreeMap<Arc<str>, RegisterUpdate>,
+}
+
+/// What was published for a device so far
+#[derive(Default)]
+struct DeviceHistory {
+    batches: u64,
+    values: HashMap<Arc<str>, f64>,
+}
+
+impl MqttPublisher {
+    /// Publishing loop of `publish_mode: device`
+    pub(super) async fn start_device_publishing(&self, mut update_rx: UpdateReceiver) {
+        info!("MQTT device publishing loop started");
+
+        let window = Duration::from_millis(self.batch.window_ms);
+        let mut pending: HashMap<Arc<str>, DeviceBatch> = HashMap::new();
+        let mut history: HashMap<Arc<str>, DeviceHistory> = HashMap::new();
+
+        loop {
+            let deadline = pending.values().map(|batch| batch.started + window).min();
+            tokio::select! {
+                update = update_rx.recv("mqtt") => {
+                    let Some(update) = update else {
+                        break;
+                    };
+                    if !self.publishes(&update) {
+                        continue;
+                    }
+                    let device_id = update.device_id.clone();
+                    // A register read again belongs to the device's next cycle
+                    let repeated = pending
+                        .get(&device_id)
+                        .is_some_and(|batch| batch.updates.contains_key(&update.register_name));
+                    if repeated {
+                        if let Some(batch) = pending.remove(&device_id) {
+                            let history = history.entry(device_id.clone()).or_default();
+                            self.publish_batch(&device_id, batch, history).await;
+                        }
+                    }
+                    pending
+                        .entry(device_id)
+                        .or_insert_with(|| DeviceBatch {
+                            started: Instant::now(),
+                            updates: BTreeMap::new(),
+                        })
+                        .updates
+                        .insert(update.register_name.clone(), update);
+                }
+                _ = tokio::time::sleep_until(deadline.unwrap_or_else(Instant::now)), if deadline.is_some() => {
+                    let now = Instant::now();
+                    let due: Vec<Arc<str>> = pending
+                        .iter()
+                        .filter(|(_, batch)| batch.started + window <= now)
+                        .map(|(device_id, _)| device_id.clone())
+                        .collect();
+                    for device_id in due {
+                        if let Some(batch) = pending.remove(&device_id) {
+                            let history = history.entry(device_id.clone()).or_default();
+                            self.publish_batch(&device_id, batch, history).await;
+                        }
+                    }
+                }
+            }
+        }
+
+        for (device_id, batch) in pending {
+            let history = history.entry(device_id.clone()).or_default();
+            self.publish_batch(&device_id, batch, history).await;
+        }
+        info!("MQTT update channel closed, stopping publisher");
+    }
+
+    async fn publish_batch(
+        &self,
+        device_id: &str,
+        batch: DeviceBatch,
+        history: &mut DeviceHistory,
+    ) {
+        let Some(payload) =
+            device_payload(&batch.updates, history, &self.batch, self.quality_policy)
+        else {
+            debug!("MQTT batch of {} unchanged, not published", device_id);
+            return;
+        };
+        let result = self.publish_device_payload(device_id, &payload).await;
+        for register in batch.updates.keys() {
+            metrics::record_mqtt_publish(device_id, register, result.is_ok());
+        }
+        if let Err(e) = result {
+            self.error_log.record(
+                ErrorEvent::new(ErrorSource::Mqtt, ErrorKind::Publish, format!("{:#}", e))
+                    .device(device_id),
+            );
+            error!("MQTT publish error: {}", e);
+        }
+    }
+
+    async fn publish_device_payload(&self, device_id: &str, payload: &Value) -> Result<()> {
+        let topic = self.device_topic(device_id);
+        let payload_str = payload.to_string();
+
+        self.client
+            .publish(&topic, self.qos, self.retain, payload_str.as_bytes())
+            .await
+            .map_err(|e| publish_error(&topic, e))?;
+
+        debug!("MQTT publishing to {}: {}", topic, payload_str);
+
+        Ok(())
+    }
+}
+
+/// Message for a batch of updates, or `None` when it would list no register
+fn device_payload(
+    updates: &BTreeMap<Arc<str>, RegisterUpdate>,
+    history: &mut DeviceHistory,
+    batch: &BatchConfig,
+    policy: QualityPolicy,
+) -> Option<Value> {
+    let full = !batch.delta
+        || history
+            .batches
+            .is_multiple_of(u64::from(batch.full_snapshot_every));
+    history.batches += 1;
+
+    let mut values = Map::new();
+    let mut quality = Map::new();
+    for (name, update) in updates {
+        let good = update.quality.is_good();
+        if !good && policy == QualityPolicy::Suppress {
+            continue;
+        }
+        let value = update.value;
+        // A run of `null` readings is not a change
+        let changed = history
+            .values
+            .insert(name.clone(), value)
+            .is_none_or(|previous| previous != value && !(previous.is_nan() && value.is_nan()));
+        if !full && !changed {
+            continue;
+        }
+        let reading = Reading {
+            value,
+            boolean: update.boolean,
+        };
+        values.insert(
+            name.to_string(),
+            serde_json::to_value(reading).unwrap_or_default(),
+        );
+        if !good && policy == QualityPolicy::Tag {
+            quality.insert(name.to_string(), json!(update.quality.as_str()));
+        }
+    }
+    if values.is_empty() {
+        return None;
+    }
+
+    let timestamp = updates.values().map(|update| &update.timestamp).max();
+    let mut payload = json!({
+        "timestamp": timestamp,
+        "full": full,
+        "values": values,
+    });
+    if !quality.is_empty() {
+        payload["quality"] = Value::Object(quality);
+    }
+    Some(payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modbus::codec::Quality;
+
+    fn batch(values: &[(&str, f64)]) -> BTreeMap<Arc<str>, RegisterUpdate> {
+        values
+            .iter()
+            .map(|(name, value)| {
+                let update = RegisterUpdate {
+                    device_id: "plc-001".into(),
+                    register_name: (*name).into(),
+                    value: *value,
+                    raw: vec![0],
+                    unit: None,
+                    timestamp: "2025-01-15T10:30:00+00:00".to_string(),
+                    previous_value: None,
+                    changed: true,
+                    meta: Default::default(),
+                    boolean: false,
+                    quality: Quality::Good,
+                };
+                (update.register_name.clone(), update)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_delta_payloads() {
+        let config = BatchConfig {
+            delta: true,
+            full_snapshot_every: 3,
+            ..Default::default()
+        };
+        let mut history = DeviceHistory::default();
+        let mut payload = |values: &[(&str, f64)]| {
+            device_payload(&batch(values), &mut history, &config, QualityPolicy::Tag)
+        };
+
+        let first = payload(&[("flow", 1.0), ("level", 2.0)]).unwrap();
+        assert_eq!(first["full"], true);
+        assert_eq!(first["values"], json!({"flow": 1.0, "level": 2.0}));
+
+        let second = payload(&[("flow", 1.5), ("level", 2.0)]).unwrap();
+        assert_eq!(second["full"], false);
+        assert_eq!(second["values"], json!({"flow": 1.5}));
+
+        assert!(payload(&[("flow", 1.5), ("level", 2.0)]).is_none());
+
+        // Every third batch lists everything again
+        let fourth = payload(&[("flow", 1.5), ("level", 2.0)]).unwrap();
+        assert_eq!(fourth["full"], true);
+        assert_eq!(fourth["values"], json!({"flow": 1.5, "level": 2.0}));
+    }
+
+    #[test]
+    fn test_payload_quality() {
+        let mut updates = batch(&[("flow", f64::NAN), ("level", 2.0)]);
+        updates.get_mut("flow").unwrap().quality = Quality::Bad;
+
+        let tagged = device_payload(
+            &updates,
+            &mut DeviceHistory::default(),
+            &BatchConfig::default(),
+            QualityPolicy::Tag,
+        )
+        .unwrap();
+        assert!(tagged["values"]["flow"].is_null());
+        assert_eq!(tagged["quality"], json!({"flow": "bad"}));
+
+        let suppressed = device_payload(
+            &updates,
+            &mut DeviceHistory::default(),
+            &BatchConfig::default(),
+            QualityPolicy::Suppress,
+        )
+        .unwrap();
+        assert_eq!(suppressed["values"], json!({"level": 2.0}));
+        assert!(suppressed.get("quality").is_none());
+    }
+}
@@ -0,0 +1,191 @@
+//! Change-of-value publishing (`publish_on_change`)
+//!
+//! In `publish_mode: register`, a register with `publish_on_change` is
+//! published only when its value moved by more than `deadband` since it was
+//! last published, when its quality changed, or when `heartbeat_interval_s`
+//! passed since it was last published. The heartbeat goes out with the first
+//! read after it is due, so a device that is not read publishes nothing; its
+//! status topic says why. Other registers are published on every read.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::api::RegisterUpdate;
+use crate::config::DeviceConfig;
+use crate::modbus::codec::Quality;
+
+/// Change-of-value settings of a register
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct OnChange {
+    deadband: f64,
+    heartbeat: Option<Duration>,
+}
+
+/// What a register was last published with
+struct Published {
+    value: f64,
+    quality: Quality,
+    at: Instant,
+}
+
+/// Last published value of each `publish_on_change` register
+#[derive(Default)]
+pub(super) struct ChangeFilter {
+    /// Settings by device ID and register name
+    registers: HashMap<String, HashMap<String, OnChange>>,
+    published: HashMap<(Arc<str>, Arc<str>), Published>,
+}
+
+impl ChangeFilter {
+    pub(super) fn new(devices: &[DeviceConfig]) -> Self {
+        let mut changes = Self::default();
+        changes.reconfigure(devices);
+        changes
+    }
+
+    /// Take the settings of `devices` after a configuration reload, keeping
+    /// the last published values
+    pub(super) fn reconfigure(&mut self, devices: &[DeviceConfig]) {
+        self.registers = devices
+            .iter()
+            .map(|device| {
+                // Points are published under their own names
+                let registers = device.registers.iter().flat_map(|register| {
+                    if register.points.is_empty() {
+                        return vec![register.clone()];
+                    }
+                    let points = register.points.iter();
+                    points.map(|point| register.point(point)).collect()
+                });
+                let on_change = registers
+                    .filter(|register| register.publish_on_change)
+                    .map(|register| {
+                        let settings = OnChange {
+                            deadband: register.deadband.unwrap_or_default(),
+                            heartbeat: register.heartbeat_interval_s.map(Duration::from_secs),
+                        };
+                        (register.name, settings)
+                    })
+                    .collect();
+                (device.id.clone(), on_change)
+            })
+            .collect();
+        let registers = &self.registers;
+        self.published.retain(|(device_id, register), _| {
+            registers
+                .get(&**device_id)
+                .is_some_and(|on_change| on_change.contains_key(&**register))
+        });
+    }
+
+    /// Whether `update` is published, remembering it if so
+    pub(super) fn passes(&mut self, update: &RegisterUpdate, now: Instant) -> bool {
+        let Some(on_change) = self
+            .registers
+            .get(&*update.device_id)
+            .and_then(|registers| registers.get(&*update.register_name))
+        else {
+            return true;
+        };
+        let key = (update.device_id.clone(), update.register_name.clone());
+        let publish = self.published.get(&key).is_none_or(|last| {
+            last.quality != update.quality
+                || moved(last.value, update.value, on_change.deadband)
+                || on_change
+                    .heartbeat
+                    .is_some_and(|every| now.duration_since(last.at) >= every)
+        });
+        if publish {
+            let published = Published {
+                value: update.value,
+                quality: update.quality,
+                at: now,
+            };
+            self.published.insert(key, published);
+        }
+        publish
+    }
+}
+
+/// Whether `value` differs from `last` by more than `deadband`; a run of
+/// `null` readings is not a change
+fn moved(last: f64, value: f64, deadband: f64) -> bool {
+    if last.is_nan() || value.is_nan() {
+        return last.is_nan() != value.is_nan();
+    }
+    (value - last).abs() > deadband
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::load_config_from_str;
+
+    #[test]
+    fn test_change_filter() {
+        let config = load_config_from_str(
+            r#"
+server: { host: "127.0.0.1", port: 3000, metrics_enabled: false }
+devices:
+  - id: plc-1
+    name: PLC
+    device_type: tcp
+    poll_interval_ms: 1000
+    connection: { host: 10.0.0.1, port: 502, unit_id: 1 }
+    registers:
+      - { name: level, address: 0, register_type: input, count: 1, data_type: u16, publish_on_change: true, deadband: 0.5, heartbeat_interval_s: 60 }
+      - { name: counter, address: 1, register_type: input, count: 1, data_type: u16 }
+      - { name: inputs, address: 0, register_type: discrete, count: 8, data_type: bool, publish_on_change: true, points: [{ name: door, offset: 2 }] }
+"#,
+        )
+        .unwrap();
+        let mut changes = ChangeFilter::new(&config.devices);
+        let update = |register: &str, value: f64| RegisterUpdate {
+            device_id: "plc-1".into(),
+            register_name: register.into(),
+            value,
+            raw: vec![],
+            unit: None,
+            timestamp: String::new(),
+            previous_value: None,
+            changed: true,
+            meta: Default::default(),
+            boolean: false,
+            quality: Quality::Good,
+        };
+        let start = Instant::now();
+        let at = |secs: u64| start + Duration::from_secs(secs);
+
+        assert!(changes.passes(&update("level", 10.0), at(0)));
+        assert!(!changes.passes(&update("level", 10.4), at(1)));
+        // Drift adds up against the last published value
+        assert!(changes.passes(&update("level", 10.6), at(2)));
+        assert!(!changes.passes(&update("level", 10.6), at(61)));
+        assert!(changes.passes(&update("level", 10.6), at(62)));
+
+        let mut bad = update("level", 10.6);
+        bad.quality = Quality::Bad;
+        assert!(changes.passes(&bad, at(63)));
+        assert!(changes.passes(&update("level", f64::NAN), at(64)));
+        assert!(!changes.passes(&update("level", f64::NAN), at(65)));
+
+        // Registers without publish_on_change publish every read
+        assert!(changes.passes(&update("counter", 1.0), at(0)));
+        assert!(changes.passes(&update("counter", 1.0), at(1)));
+
+        assert!(changes.passes(&update("door", 1.0), at(0)));
+        assert!(!changes.passes(&update("door", 1.0), at(100)));
+        assert!(changes.passes(&update("door", 0.0), at(101)));
+
+        // Turning publish_on_change off forgets the last published value
+        let mut reloaded = config.devices.clone();
+        reloaded[0].registers[0].publish_on_change = false;
+        changes.reconfigure(&reloaded);
+        assert!(changes.passes(&update("level", 10.6), at(66)));
+        assert!(changes
+            .published
+            .keys()
+            .all(|(_, register)| &**register == "door"));
+    }
+}
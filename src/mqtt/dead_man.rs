@@ -0,0 +1,288 @@
+//! Dead-man switch (`dead_man`)
+//!
+//! Puts designated registers in a safe state when the bridge loses contact
+//! with its upstream for longer than `timeout_secs`. Contact is a message on
+//! `heartbeat_topic` or, without one, being connected to the broker. The safe
+//! state is written through the same path as API writes, so value limits, the
+//! write switch and the audit log apply, but interlocks and `min_interval_ms`
+//! do not: a safe state must not wait for the conditions it protects against.
+//! A failed write does not keep the others from being tried. Writes the
+//! device did not accept are retried until they succeed or contact comes
+//! back, after a second at first and then backing off to once a minute;
+//! writes that are rejected (value limits, the write switch) are logged once
+//! and dropped. Nothing is undone when contact comes back.
+
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc};
+use tokio::time::{interval, Instant, MissedTickBehavior};
+use tracing::{error, info, warn};
+
+use super::MqttPublisher;
+use crate::api::{WriteError, WriteRequest};
+use crate::audit::WriteSource;
+use crate::config::{DeadManConfig, SafeOutput};
+use crate::metrics::error_log::{ErrorEvent, ErrorKind, ErrorSource};
+
+/// How often the connection is checked without a heartbeat topic, and the
+/// first delay before failed safe state writes are retried
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Longest delay between retries of failed safe state writes
+const MAX_RETRY_INTERVAL: Duration = Duration::from_secs(60);
+
+/// When contact was last seen and whether the safe state is in force
+struct Contact {
+    timeout: Duration,
+    last: Instant,
+    lost: bool,
+}
+
+impl Contact {
+    fn new(timeout: Duration, now: Instant) -> Self {
+        Self {
+            timeout,
+            last: now,
+            lost: false,
+        }
+    }
+
+    /// Record contact, returning whether it was lost until now
+    fn seen(&mut self, now: Instant) -> bool {
+        self.last = now;
+        std::mem::take(&mut self.lost)
+    }
+
+    /// Whether contact was just lost for longer than the timeout
+    fn expired(&mut self, now: Instant) -> bool {
+        if self.lost || now.duration_since(self.last) < self.timeout {
+            return false;
+        }
+        self.lost = true;
+        true
+    }
+}
+
+/// When failed safe state writes are tried again, doubling the delay each time
+struct Retry {
+    delay: Duration,
+    at: Instant,
+}
+
+impl Retry {
+    fn new(now: Instant) -> Self {
+        Self {
+            delay: CHECK_INTERVAL,
+            at: now + CHECK_INTERVAL,
+        }
+    }
+
+    /// Whether a retry is due, scheduling the next one if so
+    fn due(&mut self, now: Instant) -> bool {
+        if now < self.at {
+            return false;
+        }
+        self.delay = (self.delay * 2).min(MAX_RETRY_INTERVAL);
+        self.at = now + self.delay;
+        true
+    }
+}
+
+impl MqttPublisher {
+    /// Write the safe state of `config` whenever contact is lost
+    pub async fn start_dead_man(
+        &self,
+        config: DeadManConfig,
+        write_tx: mpsc::Sender<WriteRequest>,
+    ) {
+        let mut incoming_rx = self.incoming_tx.subscribe();
+        if let Some(topic) = &config.heartbeat_topic {
+            self.subscribe(topic.clone());
+        }
+        let timeout = Duration::from_secs(config.timeout_secs);
+        info!(
+            "MQTT dead-man switch armed ({}s on {})",
+            config.timeout_secs,
+            config
+                .heartbeat_topic
+                .as_deref()
+                .unwrap_or("the broker connection")
+        );
+
+        // The first connection gets the timeout as well
+        let mut contact = Contact::new(timeout, Instant::now());
+        // Outputs of the safe state not written yet
+        let mut pending = Vec::new();
+        let mut retry = Retry::new(Instant::now());
+        let mut ticker = interval(CHECK_INTERVAL.min(timeout));
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            let (seen, tick) = tokio::select! {
+                _ = ticker.tick() => {
+                    (config.heartbeat_topic.is_none() && self.is_connected(), true)
+                }
+                incoming = incoming_rx.recv() => match incoming {
+                    Ok(publish) => (config.heartbeat_topic.as_deref() == Some(publish.topic.as_str()), false),
+                    Err(broadcast::error::RecvError::Lagged(_)) => (false, false),
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
+            };
+            let now = Instant::now();
+            if seen {
+                pending.clear();
+                if contact.seen(now) {
+                    info!("MQTT dead-man switch: contact restored");
+                }
+            } else if contact.expired(now) {
+                let message = format!(
+                    "No contact for {}s, writing the safe state",
+                    config.timeout_secs
+                );
+                warn!("MQTT dead-man switch: {}", message);
+                self.error_log.record(ErrorEvent::new(
+                    ErrorSource::Mqtt,
+                    ErrorKind::Connection,
+                    message,
+                ));
+                pending = write_safe_state(&config.safe_state, &write_tx).await;
+                retry = Retry::new(Instant::now());
+            } else if tick && !pending.is_empty() && retry.due(now) {
+                pending = write_safe_state(&pending, &write_tx).await;
+            }
+        }
+    }
+}
+
+/// Write `outputs` in order, returning those worth retrying
+async fn write_safe_state(
+    outputs: &[SafeOutput],
+    write_tx: &mpsc::Sender<WriteRequest>,
+) -> Vec<SafeOutput> {
+    let mut failed = Vec::new();
+    for output in outputs {
+        let written = crate::recipes::write(
+            write_tx,
+            &output.device_id,
+            &output.register,
+            output.value,
+            WriteSource::DeadMan,
+        );
+        match written.await {
+            Ok(_) => info!(
+                "MQTT dead-man switch: wrote {} to {}:{}",
+                output.value, output.device_id, output.register
+            ),
+            Err(e @ (WriteError::Failed(_) | WriteError::Exception(_))) => {
+                error!(
+                    "MQTT dead-man switch: failed to write {}:{}, retrying: {}",
+                    output.device_id, output.register, e
+                );
+                failed.push(output.clone());
+            }
+            // Retrying cannot change the outcome
+            Err(e) => error!(
+                "MQTT dead-man switch: write to {}:{} refused: {}",
+                output.device_id, output.register, e
+            ),
+        }
+    }
+    failed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dead_man_contact() {
+        let start = Instant::now();
+        let at = |secs: u64| start + Duration::from_secs(secs);
+        let mut contact = Contact::new(Duration::from_secs(30), start);
+
+        assert!(!contact.expired(at(29)));
+        assert!(!contact.seen(at(29)));
+        assert!(!contact.expired(at(58)));
+
+        // Expires once per loss of contact
+        assert!(contact.expired(at(59)));
+        assert!(!contact.expired(at(120)));
+        assert!(contact.seen(at(121)));
+        assert!(!contact.expired(at(150)));
+        assert!(contact.expired(at(151)));
+    }
+
+    #[test]
+    fn test_dead_man_retry() {
+        let start = Instant::now();
+        let at = |secs: u64| start + Duration::from_secs(secs);
+        let mut retry = Retry::new(start);
+
+        assert!(!retry.due(start));
+        assert!(retry.due(at(1)));
+        assert!(!retry.due(at(2)));
+        assert!(retry.due(at(3)));
+        assert!(!retry.due(at(6)));
+        assert!(retry.due(at(7)));
+        assert!(retry.due(at(1000)));
+        assert!(retry.due(at(1016)));
+        // Capped at a minute
+        assert!(retry.due(at(1048)));
+        assert!(!retry.due(at(1107)));
+        assert!(retry.due(at(1108)));
+    }
+
+    #[tokio::test]
+    async fn test_write_safe_state() {
+        use crate::api::WriteOutcome;
+
+        // The press fails its first write, the valve every write, and the
+        // mode register is refused
+        let (write_tx, mut write_rx) = mpsc::channel::<WriteRequest>(4);
+        let handler = tokio::spawn(async move {
+            let mut written = Vec::new();
+            let mut press_failed = false;
+            while let Some(request) = write_rx.recv().await {
+                assert_eq!(request.source, WriteSource::DeadMan);
+                let fails = match request.device_id.as_str() {
+                    "press" => !std::mem::replace(&mut press_failed, true),
+                    _ => request.register_name == "valve",
+                };
+                let response = if request.register_name == "mode" {
+                    Err(WriteError::Rejected("mode is read-only".into()))
+                } else if fails {
+                    Err(WriteError::Failed("Device not responding".into()))
+                } else {
+                    written.push(request.register_name.clone());
+                    Ok(WriteOutcome {
+                        address: 0,
+                        raw: vec![],
+                        read_back: None,
+                    })
+                };
+                let _ = request.response_tx.send(response);
+            }
+            written
+        });
+        let output = |device_id: &str, register: &str| SafeOutput {
+            device_id: device_id.to_string(),
+            register: register.to_string(),
+            value: 0.0,
+        };
+        let outputs = [
+            output("press", "run_enable"),
+            output("pump", "valve"),
+            output("pump", "mode"),
+            output("pump", "speed"),
+        ];
+
+        let pending = write_safe_state(&outputs, &write_tx).await;
+        assert_eq!(
+            pending,
+            [output("press", "run_enable"), output("pump", "valve")]
+        );
+        let pending = write_safe_state(&pending, &write_tx).await;
+        assert_eq!(pending, [output("pump", "valve")]);
+
+        drop(write_tx);
+        assert_eq!(handler.await.unwrap(), ["speed", "run_enable"]);
+    }
+}
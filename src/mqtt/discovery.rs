@@ -0,0 +1,321 @@
+//! Home Assistant MQTT discovery
+//!
+//! With `discovery.enabled`, every register is announced to Home Assistant
+//! with a retained message on
+//! `{discovery.prefix}/{component}/rustbridge_{device_id}/{register}/config`:
+//!
+//! - writable coils as `switch` and writable holding registers as `number`,
+//!   commanded through the register's `/set` topic (needs `accept_writes`)
+//! - discrete inputs, coils and other bits as `binary_sensor`
+//! - everything else as `sensor`
+//!
+//! The `points` of a coil or discrete input block are announced one by one,
+//! as `binary_sensor`.
+//!
+//! The entities of a device belong to one Home Assistant device whose
+//! availability follows `{prefix}/{device_id}/status`. Announcements are
+//! repeated after a configuration reload, removing the entities of deleted
+//! registers, and whenever Home Assistant comes online on
+//! `{discovery.prefix}/status`.
+
+use serde_json::{json, Value};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use tokio::sync::{broadcast, watch};
+use tracing::{debug, error, info, warn};
+
+use super::{device_topic, publish_error, MqttPublisher, ValueType, WriteSchema};
+use crate::config::{Config, DeviceConfig, DiscoveryConfig, PublishFilter, RegisterConfig};
+use crate::error::Result;
+use crate::filter::{self, Subject};
+
+/// Smallest `step` Home Assistant accepts for a number
+const MIN_STEP: f64 = 0.001;
+
+/// Discovery messages of every register of `devices`, by config topic
+pub fn discovery_messages(
+    discovery: &DiscoveryConfig,
+    topic_prefix: &str,
+    accept_writes: bool,
+    filter: &PublishFilter,
+    devices: &[DeviceConfig],
+) -> BTreeMap<String, Value> {
+    let mut messages = BTreeMap::new();
+    for device in devices {
+        let node = object_id(&device.id);
+        let base = device_topic(topic_prefix, device.tenant.as_deref(), &device.id);
+        // The points of a block are announced as read-only bits instead
+        let registers = device.registers.iter().flat_map(|register| {
+            if register.points.is_empty() {
+                return vec![(Cow::Borrowed(register), accept_writes)];
+            }
+            let points = register.points.iter();
+            points
+                .map(|point| (Cow::Owned(register.point(point)), false))
+                .collect()
+        });
+        for (register, writable) in registers {
+            let meta = device.register_meta(&register);
+            let subject = Subject {
+                device_id: &device.id,
+                register: &register.name,
+                group: device.group.as_deref(),
+                meta: &meta,
+            };
+            if !filter::allows(filter, &subject) {
+                continue;
+            }
+            let (component, payload) = entity(device, &register, &base, &node, writable);
+            let topic = format!(
+                "{}/{}/rustbridge_{}/{}/config",
+                discovery.prefix,
+                component,
+                node,
+                object_id(&register.name)
+            );
+            messages.insert(topic, payload);
+        }
+    }
+    messages
+}
+
+/// Home Assistant component and discovery payload of a register
+fn entity(
+    device: &DeviceConfig,
+    register: &RegisterConfig,
+    base: &str,
+    node: &str,
+    accept_writes: bool,
+) -> (&'static str, Value) {
+    let state_topic = format!("{}/{}", base, register.name);
+    let unique_id = format!("rustbridge_{}_{}", node, object_id(&register.name));
+    let mut payload = json!({
+        "name": register.name,
+        "unique_id": unique_id,
+        "object_id": unique_id,
+        "state_topic": state_topic,
+        "availability_topic": format!("{}/status", base),
+        "device": {
+            "identifiers": [format!("rustbridge_{}", node)],
+            "name": device.name,
+        },
+    });
+    let fields = payload.as_object_mut().expect("payload is an object");
+    if let Some(unit) = &register.unit {
+        fields.insert("unit_of_measurement".to_string(), json!(unit));
+    }
+
+    let on_off = json!("{{ 'ON' if value_json.value else 'OFF' }}");
+    let schema = WriteSchema::of(register).filter(|_| accept_writes);
+    let component = match schema {
+        Some(schema) => {
+            fields.insert(
+                "command_topic".to_string(),
+                json!(format!("{}/set", state_topic)),
+            );
+            if schema.kind == ValueType::Boolean {
+                fields.insert("value_template".to_string(), on_off);
+                fields.insert("payload_on".to_string(), json!("ON"));
+                fields.insert("payload_off".to_string(), json!("OFF"));
+                "switch"
+            } else {
+                let step = match schema.kind {
+                    ValueType::Integer => 1.0,
+                    _ => register.scale.map_or(MIN_STEP, f64::abs).max(MIN_STEP),
+                };
+                fields.insert(
+                    "value_template".to_string(),
+                    json!("{{ value_json.value }}"),
+                );
+                fields.insert(
+                    "min".to_string(),
+                    json!(schema.minimum.unwrap_or(f32::MIN as f64)),
+                );
+                fields.insert(
+                    "max".to_string(),
+                    json!(schema.maximum.unwrap_or(f32::MAX as f64)),
+                );
+                fields.insert("step".to_string(), json!(step));
+                fields.insert("mode".to_string(), json!("box"));
+                "number"
+            }
+        }
+        None if register.is_bit() => {
+            fields.insert("value_template".to_string(), on_off);
+            "binary_sensor"
+        }
+        None => {
+            fields.insert(
+                "value_template".to_string(),
+                json!("{{ value_json.value }}"),
+            );
+            "sensor"
+        }
+    };
+    (component, payload)
+}
+
+/// ID usable as a discovery topic level and entity ID
+fn object_id(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+impl MqttPublisher {
+    /// Announce the registers to Home Assistant now, after every reload and
+    /// whenever Home Assistant comes online
+    pub async fn start_discovery_publishing(
+        &self,
+        discovery: DiscoveryConfig,
+        accept_writes: bool,
+        mut config_rx: watch::Receiver<Config>,
+    ) {
+        let status_topic = format!("{}/status", discovery.prefix);
+        let mut incoming_rx = self.incoming_tx.subscribe();
+        self.subscribe(status_topic.clone());
+        info!("Home Assistant discovery on {}", discovery.prefix);
+
+        let mut published: BTreeMap<String, Value> = BTreeMap::new();
+        let mut republish = true;
+        loop {
+            let messages = discovery_messages(
+                &discovery,
+                &self.topic_prefix,
+                accept_writes,
+                &self.filter,
+                &config_rx.borrow_and_update().devices,
+            );
+            // Empty retained messages remove entities of deleted registers
+            for topic in published.keys().filter(|t| !messages.contains_key(*t)) {
+                if let Err(e) = self.publish_discovery(topic, b"").await {
+                    error!("MQTT discovery error: {}", e);
+                }
+            }
+            for (topic, payload) in &messages {
+                if republish || published.get(topic) != Some(payload) {
+                    if let Err(e) = self
+                        .publish_discovery(topic, payload.to_string().as_bytes())
+                        .await
+                    {
+                        error!("MQTT discovery error: {}", e);
+                    }
+                }
+            }
+            debug!("Announced {} entities to Home Assistant", messages.len());
+            published = messages;
+
+            republish = tokio::select! {
+                changed = config_rx.changed() => match changed {
+                    Ok(()) => false,
+                    Err(_) => break,
+                },
+                online = home_assistant_online(&mut incoming_rx, &status_topic) => match online {
+                    Some(()) => true,
+                    None => break,
+                },
+            };
+        }
+    }
+
+    async fn publish_discovery(&self, topic: &str, payload: &[u8]) -> Result<()> {
+        self.client
+            .publish(topic, self.qos, true, payload)
+            .await
+            .map_err(|e| publish_error(topic, e))
+    }
+}
+
+/// Wait for Home Assistant's `online` birth message, `None` once MQTT stops
+async fn home_assistant_online(
+    incoming_rx: &mut broadcast::Receiver<rumqttc::Publish>,
+    status_topic: &str,
+) -> Option<()> {
+    loop {
+        match incoming_rx.recv().await {
+            Ok(publish) => {
+                if publish.topic == status_topic && publish.payload.as_ref() == b"online" {
+                    return Some(());
+                }
+            }
+            Err(broadcast::error::RecvError::Lagged(n)) => {
+                warn!("MQTT discovery lagged, missed {} messages", n);
+            }
+            Err(broadcast::error::RecvError::Closed) => return None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::load_config_from_str;
+
+    #[test]
+    fn test_discovery_messages() {
+        let config = load_config_from_str(
+            r#"
+server: { host: "127.0.0.1", port: 3000, metrics_enabled: false }
+devices:
+  - id: plc.1
+    name: PLC
+    device_type: tcp
+    tenant: acme
+    poll_interval_ms: 1000
+    connection: { host: 10.0.0.1, port: 502, unit_id: 1 }
+    registers:
+      - { name: pump, address: 0, register_type: coil, count: 1, data_type: bool }
+      - { name: setpoint, address: 0, register_type: holding, count: 1, data_type: u16, scale: 0.1, unit: "°C", write_limits: { min: 5, max: 30 } }
+      - { name: door, address: 0, register_type: discrete, count: 1, data_type: u16 }
+      - { name: level, address: 0, register_type: input, count: 1, data_type: u16, unit: "%" }
+      - { name: outputs, address: 16, register_type: coil, count: 8, data_type: bool, points: [{ name: fan, offset: 3 }] }
+"#,
+        )
+        .unwrap();
+        let discovery = DiscoveryConfig::default();
+        let filter = PublishFilter::default();
+        let messages = discovery_messages(&discovery, "rb", true, &filter, &config.devices);
+        let topics: Vec<&str> = messages.keys().map(String::as_str).collect();
+        assert_eq!(
+            topics,
+            [
+                "homeassistant/binary_sensor/rustbridge_plc_1/door/config",
+                "homeassistant/binary_sensor/rustbridge_plc_1/fan/config",
+                "homeassistant/number/rustbridge_plc_1/setpoint/config",
+                "homeassistant/sensor/rustbridge_plc_1/level/config",
+                "homeassistant/switch/rustbridge_plc_1/pump/config",
+            ]
+        );
+
+        let pump = &messages["homeassistant/switch/rustbridge_plc_1/pump/config"];
+        assert_eq!(pump["state_topic"], "rb/acme/plc.1/pump");
+        assert_eq!(pump["command_topic"], "rb/acme/plc.1/pump/set");
+        assert_eq!(pump["availability_topic"], "rb/acme/plc.1/status");
+        assert_eq!(pump["unique_id"], "rustbridge_plc_1_pump");
+        assert_eq!(pump["device"]["identifiers"][0], "rustbridge_plc_1");
+
+        let setpoint = &messages["homeassistant/number/rustbridge_plc_1/setpoint/config"];
+        assert_eq!(setpoint["min"], 5.0);
+        assert_eq!(setpoint["max"], 30.0);
+        assert_eq!(setpoint["step"], 0.1);
+        assert_eq!(setpoint["unit_of_measurement"], "°C");
+
+        let fan = &messages["homeassistant/binary_sensor/rustbridge_plc_1/fan/config"];
+        assert_eq!(fan["state_topic"], "rb/acme/plc.1/fan");
+
+        let level = &messages["homeassistant/sensor/rustbridge_plc_1/level/config"];
+        assert_eq!(level["value_template"], "{{ value_json.value }}");
+        assert!(level.get("command_topic").is_none());
+
+        // Without accept_writes nothing is commandable
+        let read_only = discovery_messages(&discovery, "rb", false, &filter, &config.devices);
+        assert!(read_only.contains_key("homeassistant/binary_sensor/rustbridge_plc_1/pump/config"));
+        assert!(read_only.contains_key("homeassistant/sensor/rustbridge_plc_1/setpoint/config"));
+    }
+}
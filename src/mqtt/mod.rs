@@ -2,17 +2,71 @@
 //!
 //! Publishes register updates to MQTT broker with topics like:
 //! `{prefix}/{device_id}/{register_name}`
+//!
+//! Connection lifecycle events are published to `{prefix}/{device_id}/events`.
+//!
+//! Devices with a `tenant` publish under `{prefix}/{tenant}/{device_id}/`
+//! instead, so each customer can be given access to its own subtree.
+//!
+//! With `accept_writes`, values published to `{prefix}/{device_id}/{register}/set`
+//! are validated and written to the device, see [`set`].
+//!
+//! Device groups publish their aggregate status (retained) to
+//! `{prefix}/groups/{group}/status` and accept `pause`, `resume` and
+//! `poll_now` commands on `{prefix}/groups/{group}/command`.
+//!
+//! With `publish_mode: device`, the updates of a poll cycle are published
+//! together on `{prefix}/{device_id}` instead, see [`batch`].
+//!
+//! `filter` limits the register values published, see [`filter`]. Registers
+//! with `publish_on_change` are only published when their value changed, see
+//! [`change`].
+//!
+//! With `discovery.enabled`, registers are announced to Home Assistant as
+//! sensors, binary sensors, switches and numbers, see [`discovery`].
+//!
+//! With `dead_man`, designated registers are written with a safe state when
+//! contact with the broker is lost, see [`dead_man`].
+//!
+//! Bridge-level messages are published under `{prefix}/bridge/`:
+//! - `heartbeat` (retained) - uptime, version, device counts, error totals
+//! - `info` (retained) - version, build and configuration hash, see [`BuildInfo`]
+//! - `rpc/response` - answers to JSON-RPC requests on `rpc/request`, see [`rpc`]
+
+mod batch;
+mod change;
+mod dead_man;
+mod discovery;
+mod rpc;
+mod set;
+mod tls;
+
+pub use rpc::{rpc_request_topic, rpc_response_topic, RpcContext, RpcError};
+pub use set::{ValueType, WriteSchema};
 
-use anyhow::{Context, Result};
-use rumqttc::{AsyncClient, Event, EventLoop, MqttOptions, Packet, QoS};
-use std::sync::atomic::{AtomicBool, Ordering};
+use rumqttc::{AsyncClient, Event, EventLoop, LastWill, MqttOptions, Packet, QoS};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
-use std::time::Duration;
-use tokio::sync::broadcast;
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, watch};
 use tracing::{debug, error, info, warn};
 
-use crate::api::RegisterUpdate;
-use crate::config::MqttConfig;
+use crate::api::{RegisterUpdate, UpdateReceiver};
+use crate::build_info::BuildInfo;
+use crate::config::{
+    BatchConfig, Config, DeviceConfig, MqttConfig, PublishFilter, PublishMode, QualityPolicy,
+};
+use crate::control::{Command, DeviceControls, GroupState, GroupStatus, Target};
+use crate::error::{Result, RustbridgeError};
+use crate::events::{ConnectionEvent, ConnectionEventKind};
+use crate::filter;
+use crate::metrics;
+use crate::metrics::diagnostics::{CycleStats, DeviceDiagnostics, DiagnosticsStore};
+use crate::metrics::error_log::{ErrorEvent, ErrorKind, ErrorLog, ErrorSource};
+use crate::modbus::codec::Quality;
+use crate::update::UpdateStatus;
 
 /// MQTT Publisher for sending register values
 pub struct MqttPublisher {
@@ -20,31 +74,28 @@ pub struct MqttPublisher {
     topic_prefix: String,
     qos: QoS,
     retain: bool,
-    #[allow(dead_code)] // Used for connection status checks
     connected: Arc<AtomicBool>,
+    error_log: ErrorLog,
+    /// Topic filters (re)subscribed on every connect
+    subscriptions: Arc<std::sync::Mutex<Vec<String>>>,
+    incoming_tx: broadcast::Sender<rumqttc::Publish>,
+    /// Rendered register topics by device ID
+    register_topics: std::sync::Mutex<HashMap<String, DeviceTopics>>,
+    /// Tenant of each device
+    controls: DeviceControls,
+    /// Size of the last register payload, to allocate the next one in one go
+    payload_size: AtomicUsize,
+    quality_policy: QualityPolicy,
+    publish_mode: PublishMode,
+    batch: BatchConfig,
+    /// Quality last published on each register's quality topic, by register topic
+    register_quality: std::sync::Mutex<HashMap<String, Quality>>,
+    filter: PublishFilter,
 }
 
 impl MqttPublisher {
     /// Create a new MQTT publisher
-    pub async fn new(config: &MqttConfig) -> Result<Self> {
-        let mut mqttoptions = MqttOptions::new(&config.client_id, &config.host, config.port);
-
-        mqttoptions.set_keep_alive(Duration::from_secs(30));
-        mqttoptions.set_clean_session(true);
-
-        if let (Some(user), Some(pass)) = (&config.username, &config.password) {
-            mqttoptions.set_credentials(user, pass);
-        }
-
-        let (client, eventloop) = AsyncClient::new(mqttoptions, 100);
-        let connected = Arc::new(AtomicBool::new(false));
-
-        // Spawn event loop handler
-        let connected_clone = connected.clone();
-        let host = config.host.clone();
-        let port = config.port;
-        Self::spawn_event_loop(eventloop, connected_clone, host, port);
-
+    pub async fn new(config: &MqttConfig, error_log: ErrorLog) -> Result<Self> {
         let qos = match config.qos {
             0 => QoS::AtMostOnce,
             1 => QoS::AtLeastOnce,
@@ -55,6 +106,34 @@ impl MqttPublisher {
             }
         };
 
+        let mut options = mqtt_options(config, &config.client_id);
+        options.set_transport(tls::transport(config)?);
+        if let Some(will) = last_will(config, qos) {
+            options.set_last_will(will);
+        }
+        let (client, eventloop) = AsyncClient::new(options, 100);
+        let connected = Arc::new(AtomicBool::new(false));
+
+        // Spawn event loop handler
+        let connected_clone = connected.clone();
+        let host = config.host.clone();
+        let port = config.port;
+        let subscriptions = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let (incoming_tx, _) = broadcast::channel(INCOMING_CAPACITY);
+        Self::spawn_event_loop(
+            eventloop,
+            EventLoopContext {
+                client: client.clone(),
+                connected: connected_clone,
+                host,
+                port,
+                error_log: error_log.clone(),
+                subscriptions: subscriptions.clone(),
+                incoming_tx: incoming_tx.clone(),
+                certs: tls::CertWatch::new(config).map(|watch| (watch, config.clone())),
+            },
+        );
+
         info!(
             "MQTT publisher initialized: {}:{} (prefix: {}, qos: {})",
             config.host, config.port, config.topic_prefix, config.qos
@@ -66,32 +145,175 @@ impl MqttPublisher {
             qos,
             retain: config.retain,
             connected,
+            error_log,
+            subscriptions,
+            incoming_tx,
+            register_topics: Default::default(),
+            controls: DeviceControls::default(),
+            payload_size: AtomicUsize::new(INITIAL_PAYLOAD_SIZE),
+            quality_policy: config.quality_policy,
+            publish_mode: config.publish_mode,
+            batch: config.batch.clone(),
+            register_quality: Default::default(),
+            filter: config.filter.clone(),
         })
     }
 
+    /// Look up device tenants in `controls`, which follow config reloads
+    pub fn with_controls(mut self, controls: DeviceControls) -> Self {
+        self.controls = controls;
+        self
+    }
+
+    /// Render the topics of all configured registers up front
+    ///
+    /// Registers added later (on a config reload) get theirs on first publish.
+    pub fn prepare_topics(&self, devices: &[DeviceConfig]) {
+        let mut topics = self.register_topics.lock().unwrap();
+        for device in devices {
+            let tenant = device.tenant.as_deref();
+            let device_topics = topics.entry(device.id.clone()).or_default();
+            device_topics.tenant = tenant.map(Arc::from);
+            for register in &device.registers {
+                device_topics.registers.insert(
+                    register.name.clone(),
+                    topic_of(&self.topic_prefix, tenant, &device.id, &register.name),
+                );
+            }
+        }
+    }
+
+    /// Topic of a register update, rendered once per register
+    ///
+    /// A device moved to another tenant on a reload gets its topics rendered again.
+    fn cached_topic(&self, update: &RegisterUpdate) -> String {
+        let tenant = self.controls.tenant_of(&update.device_id);
+        let mut topics = self.register_topics.lock().unwrap();
+        if let Some(device_topics) = topics.get_mut(&*update.device_id) {
+            if device_topics.tenant != tenant {
+                device_topics.tenant = tenant.clone();
+                device_topics.registers.clear();
+            } else if let Some(topic) = device_topics.registers.get(&*update.register_name) {
+                return topic.clone();
+            }
+        }
+        let topic = register_topic(&self.topic_prefix, tenant.as_deref(), update);
+        topics
+            .entry(update.device_id.to_string())
+            .or_insert_with(|| DeviceTopics {
+                tenant,
+                registers: HashMap::new(),
+            })
+            .registers
+            .insert(update.register_name.to_string(), topic.clone());
+        topic
+    }
+
+    /// Whether the update passes `filter`
+    fn publishes(&self, update: &RegisterUpdate) -> bool {
+        if self.filter.is_empty() {
+            return true;
+        }
+        let group = self.controls.group_of(&update.device_id);
+        filter::allows(
+            &self.filter,
+            &filter::Subject {
+                device_id: &update.device_id,
+                register: &update.register_name,
+                group: group.as_deref(),
+                meta: &update.meta,
+            },
+        )
+    }
+
+    /// `{prefix}/{device_id}`, with the device's tenant if it has one
+    fn device_topic(&self, device_id: &str) -> String {
+        device_topic(
+            &self.topic_prefix,
+            self.controls.tenant_of(device_id).as_deref(),
+            device_id,
+        )
+    }
+
     /// Spawn the MQTT event loop handler
-    fn spawn_event_loop(
-        mut eventloop: EventLoop,
-        connected: Arc<AtomicBool>,
-        host: String,
-        port: u16,
-    ) {
+    fn spawn_event_loop(mut eventloop: EventLoop, ctx: EventLoopContext) {
+        let EventLoopContext {
+            client,
+            connected,
+            host,
+            port,
+            error_log,
+            subscriptions,
+            incoming_tx,
+            certs,
+        } = ctx;
+        let (mut watch, config) = certs.unzip();
+        metrics::record_mqtt_connection(false);
         tokio::spawn(async move {
             loop {
-                match eventloop.poll().await {
+                let event = tokio::select! {
+                    event = eventloop.poll() => event,
+                    () = tls::renewed(&mut watch) => {
+                        let Some(config) = &config else {
+                            continue;
+                        };
+                        match tls::transport(config) {
+                            Ok(transport) => {
+                                info!("MQTT certificates changed, reconnecting to {}:{}", host, port);
+                                eventloop.mqtt_options.set_transport(transport);
+                                // Unacknowledged publishes are sent again on the new connection
+                                eventloop.clean();
+                                connected.store(false, Ordering::SeqCst);
+                                metrics::record_mqtt_connection(false);
+                            }
+                            Err(e) => {
+                                error_log.record(ErrorEvent::new(
+                                    ErrorSource::Mqtt,
+                                    ErrorKind::Connection,
+                                    format!("{:#}", e),
+                                ));
+                                error!("MQTT certificates changed but cannot be used, keeping the current ones: {}", e);
+                            }
+                        }
+                        continue;
+                    }
+                };
+                match event {
                     Ok(Event::Incoming(Packet::ConnAck(ack))) => {
                         if ack.code == rumqttc::ConnectReturnCode::Success {
                             connected.store(true, Ordering::SeqCst);
-                            info!("Connected to MQTT broker at {}:{}", host, port);
+                            metrics::record_mqtt_connection(true);
+                            if ack.session_present {
+                                info!("Resumed MQTT session with broker at {}:{}", host, port);
+                            } else {
+                                info!("Connected to MQTT broker at {}:{}", host, port);
+                            }
+                            // Clean sessions drop subscriptions, so renew them on every connect
+                            let topics =
+                                subscriptions.lock().map(|t| t.clone()).unwrap_or_default();
+                            for topic in topics {
+                                if let Err(e) = client.try_subscribe(&topic, QoS::AtLeastOnce) {
+                                    error!("MQTT subscribe to {} failed: {}", topic, e);
+                                }
+                            }
                         } else {
+                            error_log.record(ErrorEvent::new(
+                                ErrorSource::Mqtt,
+                                ErrorKind::Connection,
+                                format!("MQTT connection rejected: {:?}", ack.code),
+                            ));
                             error!("MQTT connection rejected: {:?}", ack.code);
                         }
                     }
+                    Ok(Event::Incoming(Packet::Publish(publish))) => {
+                        let _ = incoming_tx.send(publish);
+                    }
                     Ok(Event::Incoming(Packet::PingResp)) => {
                         debug!("MQTT ping response");
                     }
                     Ok(Event::Incoming(Packet::Disconnect)) => {
                         connected.store(false, Ordering::SeqCst);
+                        metrics::record_mqtt_connection(false);
                         warn!("Disconnected from MQTT broker");
                     }
                     Ok(Event::Outgoing(_)) => {
@@ -100,6 +322,12 @@ impl MqttPublisher {
                     Ok(_) => {}
                     Err(e) => {
                         connected.store(false, Ordering::SeqCst);
+                        metrics::record_mqtt_connection(false);
+                        error_log.record(ErrorEvent::new(
+                            ErrorSource::Mqtt,
+                            ErrorKind::Connection,
+                            format!("MQTT error: {}", e),
+                        ));
                         error!("MQTT error: {:?}", e);
                         tokio::time::sleep(Duration::from_secs(5)).await;
                     }
@@ -109,87 +337,703 @@ impl MqttPublisher {
     }
 
     /// Check if connected to broker
-    #[allow(dead_code)] // Available for future health checks
     pub fn is_connected(&self) -> bool {
         self.connected.load(Ordering::SeqCst)
     }
 
+    /// Shared broker connection flag, updated by the event loop
+    pub fn connection_flag(&self) -> Arc<AtomicBool> {
+        self.connected.clone()
+    }
+
     /// Publish a register update from the broadcast channel
+    ///
+    /// Bad and substituted readings are published according to the
+    /// `quality_policy`.
     pub async fn publish_update(&self, update: &RegisterUpdate) -> Result<()> {
-        let topic = format!(
-            "{}/{}/{}",
-            self.topic_prefix, update.device_id, update.register_name
+        let topic = self.cached_topic(update);
+
+        let plain;
+        let update = match self.quality_policy {
+            QualityPolicy::Publish if !update.quality.is_good() => {
+                plain = RegisterUpdate {
+                    quality: Quality::Good,
+                    ..update.clone()
+                };
+                &plain
+            }
+            QualityPolicy::Suppress => {
+                self.publish_quality(&topic, update.quality).await?;
+                if !update.quality.is_good() {
+                    debug!(
+                        "MQTT suppressed {} reading on {}",
+                        update.quality.as_str(),
+                        topic
+                    );
+                    return Ok(());
+                }
+                update
+            }
+            _ => update,
+        };
+
+        // The client takes ownership of the payload, so the buffer cannot be
+        // reused; it is sized from the previous payload instead
+        let mut payload = Vec::with_capacity(self.payload_size.load(Ordering::Relaxed));
+        serde_json::to_writer(&mut payload, &RegisterPayload(update))
+            .map_err(|e| publish_error("serialize payload", e))?;
+        self.payload_size.store(payload.len(), Ordering::Relaxed);
+
+        debug!(
+            "MQTT publishing to {}: {}",
+            topic,
+            String::from_utf8_lossy(&payload)
         );
 
-        let payload = serde_json::json!({
-            "value": update.value,
-            "raw": update.raw,
-            "unit": update.unit,
-            "timestamp": update.timestamp,
-        });
+        self.client
+            .publish(topic.as_str(), self.qos, self.retain, payload)
+            .await
+            .map_err(|e| publish_error(&topic, e))?;
 
-        let payload_str =
-            serde_json::to_string(&payload).with_context(|| "Failed to serialize payload")?;
+        Ok(())
+    }
 
+    /// Publish a register's quality on the retained `{topic}/quality` when it changes
+    async fn publish_quality(&self, topic: &str, quality: Quality) -> Result<()> {
+        if self.register_quality.lock().unwrap().get(topic) == Some(&quality) {
+            return Ok(());
+        }
+        let quality_topic = format!("{}/quality", topic);
         self.client
-            .publish(&topic, self.qos, self.retain, payload_str.as_bytes())
+            .publish(&quality_topic, self.qos, true, quality.as_str().as_bytes())
             .await
-            .with_context(|| format!("Failed to publish to {}", topic))?;
+            .map_err(|e| publish_error(&quality_topic, e))?;
+        self.register_quality
+            .lock()
+            .unwrap()
+            .insert(topic.to_string(), quality);
 
-        debug!("MQTT published to {}: {}", topic, payload_str);
+        info!("MQTT quality: {} = {}", quality_topic, quality.as_str());
 
         Ok(())
     }
 
     /// Publish device status (online/offline)
-    #[allow(dead_code)] // Available for device lifecycle events
     pub async fn publish_status(&self, device_id: &str, online: bool) -> Result<()> {
-        let topic = format!("{}/{}/status", self.topic_prefix, device_id);
+        let topic = format!("{}/status", self.device_topic(device_id));
         let payload = if online { "online" } else { "offline" };
 
         self.client
             .publish(&topic, self.qos, true, payload.as_bytes()) // Always retain status
             .await
-            .with_context(|| format!("Failed to publish status to {}", topic))?;
+            .map_err(|e| publish_error(&topic, e))?;
 
         info!("MQTT status: {} = {}", topic, payload);
 
         Ok(())
     }
 
-    /// Start the MQTT publishing loop that listens to broadcast channel
-    pub async fn start_publishing(
+    /// Publish a connection lifecycle event (never retained)
+    pub async fn publish_event(&self, event: &ConnectionEvent) -> Result<()> {
+        let topic = format!("{}/events", self.device_topic(&event.device_id));
+
+        let payload_str =
+            serde_json::to_string(event).map_err(|e| publish_error("serialize event", e))?;
+
+        self.client
+            .publish(&topic, self.qos, false, payload_str.as_bytes())
+            .await
+            .map_err(|e| publish_error(&topic, e))?;
+
+        debug!("MQTT event published to {}: {}", topic, payload_str);
+
+        Ok(())
+    }
+
+    /// Start the loop publishing connection lifecycle events, and each
+    /// device's `online`/`offline` status whenever it changes
+    pub async fn start_event_publishing(
         self: Arc<Self>,
-        mut update_rx: broadcast::Receiver<RegisterUpdate>,
+        mut event_rx: broadcast::Receiver<ConnectionEvent>,
     ) {
-        info!("MQTT publishing loop started");
+        let mut online: HashMap<String, bool> = HashMap::new();
+        loop {
+            match event_rx.recv().await {
+                Ok(event) => {
+                    let status = match event.event {
+                        ConnectionEventKind::Connected => Some(true),
+                        ConnectionEventKind::Disconnected
+                        | ConnectionEventKind::SerialPortError => Some(false),
+                        _ => None,
+                    };
+                    if let Some(status) = status.filter(|s| online.get(&event.device_id) != Some(s))
+                    {
+                        match self.publish_status(&event.device_id, status).await {
+                            Ok(()) => {
+                                online.insert(event.device_id.clone(), status);
+                            }
+                            Err(e) => error!("MQTT status publish error: {}", e),
+                        }
+                    }
+                    if let Err(e) = self.publish_event(&event).await {
+                        self.error_log.record(
+                            ErrorEvent::new(
+                                ErrorSource::Mqtt,
+                                ErrorKind::Publish,
+                                format!("{:#}", e),
+                            )
+                            .device(event.device_id.as_str()),
+                        );
+                        error!("MQTT event publish error: {}", e);
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(n)) => {
+                    warn!("MQTT event publisher lagged, missed {} events", n);
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    }
+
+    /// Publish the bridge heartbeat (always retained)
+    pub async fn publish_heartbeat(&self, heartbeat: &Heartbeat) -> Result<()> {
+        let topic = format!("{}/bridge/heartbeat", self.topic_prefix);
+
+        let payload_str = serde_json::to_string(heartbeat)
+            .map_err(|e| publish_error("serialize heartbeat", e))?;
+
+        self.client
+            .publish(&topic, self.qos, true, payload_str.as_bytes())
+            .await
+            .map_err(|e| publish_error(&topic, e))?;
+
+        debug!("MQTT heartbeat published to {}: {}", topic, payload_str);
+
+        Ok(())
+    }
+
+    /// Publish the bridge build information (always retained)
+    pub async fn publish_info(&self, info: &BuildInfo) -> Result<()> {
+        let topic = format!("{}/bridge/info", self.topic_prefix);
+
+        let payload_str =
+            serde_json::to_string(info).map_err(|e| publish_error("serialize info", e))?;
+
+        self.client
+            .publish(&topic, self.qos, true, payload_str.as_bytes())
+            .await
+            .map_err(|e| publish_error(&topic, e))?;
+
+        debug!("MQTT bridge info published to {}: {}", topic, payload_str);
+
+        Ok(())
+    }
+
+    /// Publish the build information now and again whenever the
+    /// configuration is reloaded, since its hash changes
+    pub async fn start_info_publishing(&self, mut config_rx: watch::Receiver<Config>) {
+        loop {
+            let info = BuildInfo::new(Some(&*config_rx.borrow_and_update()));
+            if let Err(e) = self.publish_info(&info).await {
+                error!("MQTT bridge info error: {}", e);
+            }
+            if config_rx.changed().await.is_err() {
+                break;
+            }
+        }
+    }
+
+    /// Publish the result of the latest release check (always retained)
+    pub async fn publish_update_status(&self, status: &UpdateStatus) -> Result<()> {
+        let topic = format!("{}/bridge/update", self.topic_prefix);
+
+        let payload_str =
+            serde_json::to_string(status).map_err(|e| publish_error("serialize update", e))?;
+
+        self.client
+            .publish(&topic, self.qos, true, payload_str.as_bytes())
+            .await
+            .map_err(|e| publish_error(&topic, e))?;
+
+        debug!("MQTT update status published to {}: {}", topic, payload_str);
+
+        Ok(())
+    }
+
+    /// Publish the update status now and after every change
+    pub async fn start_update_publishing(&self, mut status_rx: watch::Receiver<UpdateStatus>) {
+        loop {
+            let status = status_rx.borrow_and_update().clone();
+            if let Err(e) = self.publish_update_status(&status).await {
+                error!("MQTT update status error: {}", e);
+            }
+            if status_rx.changed().await.is_err() {
+                break;
+            }
+        }
+    }
+
+    /// Publish the poll statistics of a device (not retained)
+    pub async fn publish_stats(&self, stats: &CycleStats) -> Result<()> {
+        let topic = format!("{}/$stats", self.device_topic(&stats.device_id));
+
+        let payload_str =
+            serde_json::to_string(stats).map_err(|e| publish_error("serialize stats", e))?;
+
+        self.client
+            .publish(&topic, self.qos, false, payload_str.as_bytes())
+            .await
+            .map_err(|e| publish_error(&topic, e))?;
+
+        Ok(())
+    }
 
+    /// Publish the poll statistics the devices send every `stats_every_cycles`
+    pub async fn start_stats_publishing(&self, mut stats_rx: broadcast::Receiver<CycleStats>) {
         loop {
-            match update_rx.recv().await {
-                Ok(update) => {
-                    if let Err(e) = self.publish_update(&update).await {
-                        error!("MQTT publish error: {}", e);
+            match stats_rx.recv().await {
+                Ok(stats) => {
+                    if let Err(e) = self.publish_stats(&stats).await {
+                        error!("MQTT stats error: {}", e);
                     }
                 }
                 Err(broadcast::error::RecvError::Lagged(n)) => {
-                    warn!("MQTT publisher lagged, missed {} updates", n);
+                    warn!("MQTT stats publisher lagged, missed {} messages", n);
                 }
-                Err(broadcast::error::RecvError::Closed) => {
-                    info!("MQTT broadcast channel closed, stopping publisher");
-                    break;
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    }
+
+    /// Start the heartbeat loop publishing every `interval`
+    pub async fn start_heartbeat(
+        self: Arc<Self>,
+        interval: Duration,
+        diagnostics: DiagnosticsStore,
+        devices_configured: usize,
+        started_at: Instant,
+    ) {
+        info!("MQTT heartbeat started (every {}s)", interval.as_secs());
+
+        let mut ticker = tokio::time::interval(interval);
+
+        loop {
+            ticker.tick().await;
+
+            let heartbeat = {
+                let diagnostics = diagnostics.read().await;
+                Heartbeat::new(devices_configured, &diagnostics, started_at.elapsed())
+            };
+
+            if let Err(e) = self.publish_heartbeat(&heartbeat).await {
+                error!("MQTT heartbeat error: {}", e);
+            }
+        }
+    }
+
+    /// Subscribe to a topic filter now (if connected) and after every reconnect
+    fn subscribe(&self, topic: String) {
+        if self.is_connected() {
+            if let Err(e) = self.client.try_subscribe(&topic, self.qos) {
+                error!("MQTT subscribe to {} failed: {}", topic, e);
+            }
+        }
+        if let Ok(mut subscriptions) = self.subscriptions.lock() {
+            subscriptions.push(topic);
+        }
+    }
+
+    /// Publish the aggregate status of a device group (always retained)
+    pub async fn publish_group_status(&self, state: &GroupState) -> Result<()> {
+        let topic = group_status_topic(&self.topic_prefix, &state.group);
+
+        let payload_str =
+            serde_json::to_string(state).map_err(|e| publish_error("serialize group status", e))?;
+
+        self.client
+            .publish(&topic, self.qos, true, payload_str.as_bytes())
+            .await
+            .map_err(|e| publish_error(&topic, e))?;
+
+        info!("MQTT group status: {} = {:?}", topic, state.status);
+
+        Ok(())
+    }
+
+    /// Publish every group's status at startup and whenever it changes
+    pub async fn start_group_status_publishing(
+        self: Arc<Self>,
+        controls: DeviceControls,
+        mut event_rx: broadcast::Receiver<ConnectionEvent>,
+    ) {
+        let mut published: HashMap<String, (GroupStatus, usize)> = HashMap::new();
+        for group in controls.group_names() {
+            self.update_group_status(&controls, &group, &mut published)
+                .await;
+        }
+
+        loop {
+            match event_rx.recv().await {
+                Ok(event) => {
+                    if let Some(group) = controls.group_of(&event.device_id) {
+                        self.update_group_status(&controls, &group, &mut published)
+                            .await;
+                    }
                 }
+                Err(broadcast::error::RecvError::Lagged(_)) => {
+                    // Missed events may have changed any group
+                    for group in controls.group_names() {
+                        self.update_group_status(&controls, &group, &mut published)
+                            .await;
+                    }
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    }
+
+    /// Publish a group's status if it differs from the last one published
+    async fn update_group_status(
+        &self,
+        controls: &DeviceControls,
+        group: &str,
+        published: &mut HashMap<String, (GroupStatus, usize)>,
+    ) {
+        let Some(state) = controls.group_state(group) else {
+            return;
+        };
+        let current = (state.status, state.online);
+        if published.get(group) == Some(&current) {
+            return;
+        }
+        match self.publish_group_status(&state).await {
+            Ok(()) => {
+                published.insert(group.to_string(), current);
+            }
+            Err(e) => error!("MQTT group status error: {}", e),
+        }
+    }
+
+    /// Apply commands received on `{prefix}/groups/{group}/command`
+    pub async fn start_command_handling(self: Arc<Self>, controls: DeviceControls) {
+        let mut incoming_rx = self.incoming_tx.subscribe();
+        self.subscribe(format!("{}/groups/+/command", self.topic_prefix));
+
+        loop {
+            match incoming_rx.recv().await {
+                Ok(publish) => {
+                    let Some(group) = group_command_target(&self.topic_prefix, &publish.topic)
+                    else {
+                        continue;
+                    };
+                    let result = std::str::from_utf8(&publish.payload)
+                        .map_err(|e| RustbridgeError::Config(e.to_string()))
+                        .and_then(|payload| payload.parse::<Command>())
+                        .and_then(|command| {
+                            controls
+                                .apply(Target::Group(group), command)
+                                .map(|devices| (command, devices))
+                        });
+                    match result {
+                        Ok((command, devices)) => {
+                            info!("MQTT command {:?} applied to {:?}", command, devices)
+                        }
+                        Err(e) => warn!("Rejected MQTT command on {}: {}", publish.topic, e),
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(n)) => {
+                    warn!("MQTT command handler lagged, missed {} messages", n);
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    }
+
+    /// Start the MQTT publishing loop that listens for register updates
+    ///
+    /// With a blocking receiver the pollers wait for this loop, so updates
+    /// queue up rather than being dropped while the broker is slow.
+    pub async fn start_publishing(
+        self: Arc<Self>,
+        mut update_rx: UpdateReceiver,
+        mut config_rx: watch::Receiver<Config>,
+    ) {
+        if self.publish_mode == PublishMode::Device {
+            return self.start_device_publishing(update_rx).await;
+        }
+        info!("MQTT publishing loop started");
+
+        let mut changes = change::ChangeFilter::new(&config_rx.borrow_and_update().devices);
+        while let Some(update) = update_rx.recv("mqtt").await {
+            if config_rx.has_changed().unwrap_or(false) {
+                changes.reconfigure(&config_rx.borrow_and_update().devices);
+            }
+            if !self.publishes(&update) || !changes.passes(&update, Instant::now()) {
+                continue;
+            }
+            let result = self.publish_update(&update).await;
+            metrics::record_mqtt_publish(&update.device_id, &update.register_name, result.is_ok());
+            if let Err(e) = result {
+                self.error_log.record(
+                    ErrorEvent::new(ErrorSource::Mqtt, ErrorKind::Publish, format!("{:#}", e))
+                        .device(&*update.device_id),
+                );
+                error!("MQTT publish error: {}", e);
+            }
+        }
+        info!("MQTT update channel closed, stopping publisher");
+    }
+}
+
+/// Register topics of a device, rendered for its tenant
+#[derive(Default)]
+struct DeviceTopics {
+    tenant: Option<Arc<str>>,
+    /// Topics by register name
+    registers: HashMap<String, String>,
+}
+
+/// Capacity hint for the first register payload
+const INITIAL_PAYLOAD_SIZE: usize = 256;
+
+/// Capacity of the channel forwarding received messages to command handlers
+const INCOMING_CAPACITY: usize = 64;
+
+/// Everything the MQTT event loop task needs besides the event loop itself
+struct EventLoopContext {
+    client: AsyncClient,
+    connected: Arc<AtomicBool>,
+    host: String,
+    port: u16,
+    error_log: ErrorLog,
+    subscriptions: Arc<std::sync::Mutex<Vec<String>>>,
+    incoming_tx: broadcast::Sender<rumqttc::Publish>,
+    /// Certificate files to watch and the configuration to rebuild TLS from
+    certs: Option<(tls::CertWatch, MqttConfig)>,
+}
+
+/// Publish error with what was being published
+fn publish_error(what: &str, error: impl std::fmt::Display) -> RustbridgeError {
+    RustbridgeError::Publish(format!("{}: {}", what, error))
+}
+
+/// Client options shared by the publisher and connection checks
+fn mqtt_options(config: &MqttConfig, client_id: &str) -> MqttOptions {
+    let mut mqttoptions = MqttOptions::new(client_id, &config.host, config.port);
+
+    mqttoptions.set_keep_alive(Duration::from_secs(config.keep_alive_secs.into()));
+    mqttoptions.set_clean_session(config.clean_session);
+    mqttoptions.set_inflight(config.inflight.max(1));
+    mqttoptions.set_max_packet_size(config.max_packet_size, config.max_packet_size);
+
+    if let (Some(user), Some(pass)) = (&config.username, &config.password) {
+        mqttoptions.set_credentials(user, pass);
+    }
+
+    mqttoptions
+}
+
+/// Will of the publisher's connection; connection checks go without one
+fn last_will(config: &MqttConfig, qos: QoS) -> Option<LastWill> {
+    let will = config.last_will.as_ref()?;
+    Some(LastWill::new(
+        &will.topic,
+        will.payload.clone(),
+        qos,
+        will.retain,
+    ))
+}
+
+/// Topic a register update of a device of `tenant` is published to
+pub fn register_topic(prefix: &str, tenant: Option<&str>, update: &RegisterUpdate) -> String {
+    topic_of(prefix, tenant, &update.device_id, &update.register_name)
+}
+
+fn topic_of(prefix: &str, tenant: Option<&str>, device_id: &str, register_name: &str) -> String {
+    format!(
+        "{}/{}",
+        device_topic(prefix, tenant, device_id),
+        register_name
+    )
+}
+
+/// Topic level of a device, under its tenant if it has one
+pub fn device_topic(prefix: &str, tenant: Option<&str>, device_id: &str) -> String {
+    match tenant {
+        Some(tenant) => format!("{}/{}/{}", prefix, tenant, device_id),
+        None => format!("{}/{}", prefix, device_id),
+    }
+}
+
+/// Topic a group's aggregate status is published to
+pub fn group_status_topic(prefix: &str, group: &str) -> String {
+    format!("{}/groups/{}/status", prefix, group)
+}
+
+/// Group addressed by a `{prefix}/groups/{group}/command` topic
+fn group_command_target<'a>(prefix: &str, topic: &'a str) -> Option<&'a str> {
+    topic
+        .strip_prefix(prefix)?
+        .strip_prefix("/groups/")?
+        .strip_suffix("/command")
+        .filter(|group| !group.is_empty() && !group.contains('/'))
+}
+
+/// JSON payload published for a register update
+///
+/// NaN and infinite values are published as `null`, and `quality` is added
+/// for bad or substituted readings. Meta fields of the device and register
+/// are merged into the top level; they never replace one of the fields above.
+pub fn register_payload(update: &RegisterUpdate) -> serde_json::Value {
+    serde_json::to_value(RegisterPayload(update)).unwrap_or_default()
+}
+
+/// Serializes the payload of [`register_payload`] directly, without building
+/// a `serde_json::Value` first
+pub struct RegisterPayload<'a>(pub &'a RegisterUpdate);
+
+/// Fields of a register payload; meta keys with these names are left out
+const PAYLOAD_FIELDS: &[&str] = &[
+    "value",
+    "raw",
+    "unit",
+    "timestamp",
+    "previous_value",
+    "changed",
+    "quality",
+];
+
+impl Serialize for RegisterPayload<'_> {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        use serde::ser::SerializeMap;
+
+        let update = self.0;
+        let reading = |value: f64| Reading {
+            value,
+            boolean: update.boolean,
+        };
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("value", &reading(update.value))?;
+        map.serialize_entry("raw", &update.raw)?;
+        map.serialize_entry("unit", &update.unit)?;
+        map.serialize_entry("timestamp", &update.timestamp)?;
+        map.serialize_entry("previous_value", &update.previous_value.map(reading))?;
+        map.serialize_entry("changed", &update.changed)?;
+        if !update.quality.is_good() {
+            map.serialize_entry("quality", &update.quality)?;
+        }
+        for (key, value) in &update.meta {
+            if !PAYLOAD_FIELDS.contains(&key.as_str()) {
+                map.serialize_entry(key, value)?;
             }
         }
+        map.end()
+    }
+}
+
+/// A value as published: `true`/`false` for boolean coils, otherwise a
+/// number (`null` when not finite)
+struct Reading {
+    value: f64,
+    boolean: bool,
+}
+
+impl Serialize for Reading {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        if self.boolean {
+            serializer.serialize_bool(self.value != 0.0)
+        } else if self.value.is_finite() {
+            serializer.serialize_f64(self.value)
+        } else {
+            serializer.serialize_none()
+        }
     }
 }
 
-/// Statistics for MQTT publishing
-#[allow(dead_code)] // Available for future metrics
-#[derive(Debug, Default)]
-pub struct MqttStats {
-    pub messages_sent: u64,
-    pub messages_failed: u64,
-    pub bytes_sent: u64,
+/// Connect to the broker once and wait for the CONNACK, without publishing anything
+pub async fn check_connection(config: &MqttConfig, timeout: Duration) -> Result<()> {
+    // Separate client ID so a running bridge isn't kicked off the broker
+    let client_id = format!("{}-check", config.client_id);
+    let mut options = mqtt_options(config, &client_id);
+    // Nor should it leave a session behind
+    options.set_clean_session(true);
+    options.set_transport(tls::transport(config)?);
+    let (client, mut eventloop) = AsyncClient::new(options, 10);
+
+    let broker = format!("MQTT broker {}:{}", config.host, config.port);
+    let result = tokio::time::timeout(timeout, async {
+        loop {
+            match eventloop.poll().await {
+                Ok(Event::Incoming(Packet::ConnAck(ack))) => {
+                    return if ack.code == rumqttc::ConnectReturnCode::Success {
+                        Ok(())
+                    } else {
+                        Err(RustbridgeError::Connection(format!(
+                            "{}: connection rejected: {:?}",
+                            broker, ack.code
+                        )))
+                    };
+                }
+                Ok(_) => {}
+                Err(e) => return Err(RustbridgeError::Connection(format!("{}: {}", broker, e))),
+            }
+        }
+    })
+    .await
+    .unwrap_or(Err(RustbridgeError::Timeout));
+
+    let _ = client.try_disconnect();
+    result
+}
+
+/// Bridge heartbeat payload
+#[derive(Debug, Clone, Serialize)]
+pub struct Heartbeat {
+    pub version: &'static str,
+    pub uptime_secs: u64,
+    pub timestamp: String,
+    pub devices_configured: usize,
+    /// Devices whose last read succeeded
+    pub devices_online: usize,
+    /// Devices whose last read failed
+    pub devices_failing: usize,
+    pub reads_ok: u64,
+    pub reads_failed: u64,
+}
+
+impl Heartbeat {
+    /// Build a heartbeat from the current poll diagnostics
+    pub fn new(
+        devices_configured: usize,
+        diagnostics: &HashMap<String, DeviceDiagnostics>,
+        uptime: Duration,
+    ) -> Self {
+        let devices_failing = diagnostics
+            .values()
+            .filter(|d| d.consecutive_failures > 0)
+            .count();
+        let devices_online = diagnostics
+            .values()
+            .filter(|d| d.consecutive_failures == 0 && d.reads_ok > 0)
+            .count();
+
+        Self {
+            version: env!("CARGO_PKG_VERSION"),
+            uptime_secs: uptime.as_secs(),
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            devices_configured,
+            devices_online,
+            devices_failing,
+            reads_ok: diagnostics.values().map(|d| d.reads_ok).sum(),
+            reads_failed: diagnostics.values().map(|d| d.reads_failed).sum(),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -232,6 +1076,44 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn test_persistent_session_options() {
+        let config = MqttConfig::default();
+        let options = mqtt_options(&config, "rustbridge");
+        assert!(options.clean_session());
+        assert_eq!(options.inflight(), 100);
+
+        let config: MqttConfig = serde_yaml::from_str(
+            "{ host: localhost, port: 1883, client_id: gw-1, topic_prefix: rb, qos: 1, \
+             clean_session: false, inflight: 20, max_packet_size: 65536 }",
+        )
+        .unwrap();
+        let options = mqtt_options(&config, &config.client_id);
+        assert!(!options.clean_session());
+        assert_eq!(options.inflight(), 20);
+        assert_eq!(options.max_packet_size(), 65536);
+        assert_eq!(options.keep_alive(), Duration::from_secs(30));
+        assert!(last_will(&config, QoS::AtLeastOnce).is_none());
+    }
+
+    #[test]
+    fn test_keep_alive_and_last_will() {
+        let config: MqttConfig = serde_yaml::from_str(
+            "{ host: localhost, port: 1883, client_id: gw-1, topic_prefix: rb, qos: 1, \
+             keep_alive_secs: 5, last_will: { topic: rb/bridge/status, payload: offline, retain: true } }",
+        )
+        .unwrap();
+        assert_eq!(
+            mqtt_options(&config, &config.client_id).keep_alive(),
+            Duration::from_secs(5)
+        );
+        let will = last_will(&config, QoS::AtLeastOnce).unwrap();
+        assert_eq!(will.topic, "rb/bridge/status");
+        assert_eq!(&will.message[..], b"offline");
+        assert_eq!(will.qos, QoS::AtLeastOnce);
+        assert!(will.retain);
+    }
+
     #[test]
     fn test_topic_format() {
         let prefix = "rustbridge";
@@ -242,6 +1124,266 @@ mod tests {
         assert_eq!(topic, "rustbridge/plc-001/temperature");
     }
 
+    #[test]
+    fn test_tenant_topics() {
+        assert_eq!(
+            device_topic("rustbridge", Some("acme"), "plc-001"),
+            "rustbridge/acme/plc-001"
+        );
+        assert_eq!(
+            topic_of("rustbridge", Some("acme"), "plc-001", "temperature"),
+            "rustbridge/acme/plc-001/temperature"
+        );
+        assert_eq!(
+            topic_of("rustbridge", None, "plc-001", "temperature"),
+            "rustbridge/plc-001/temperature"
+        );
+    }
+
+    #[test]
+    fn test_register_topic_and_payload() {
+        let update = RegisterUpdate {
+            device_id: "plc-001".into(),
+            register_name: "temperature".into(),
+            value: 23.5,
+            raw: vec![235],
+            unit: Some("°C".to_string()),
+            timestamp: "2025-01-15T10:30:00+00:00".to_string(),
+            previous_value: Some(23.0),
+            changed: true,
+            meta: Default::default(),
+            boolean: false,
+            quality: Default::default(),
+        };
+
+        assert_eq!(
+            register_topic("rustbridge", None, &update),
+            "rustbridge/plc-001/temperature"
+        );
+        let payload = register_payload(&update);
+        assert_eq!(payload["value"], 23.5);
+        assert_eq!(payload["raw"][0], 235);
+        assert_eq!(payload["unit"], "°C");
+        assert_eq!(payload["previous_value"], 23.0);
+        assert_eq!(payload["changed"], true);
+    }
+
+    #[test]
+    fn test_register_payload_boolean_coil() {
+        let update = RegisterUpdate {
+            device_id: "plc-001".into(),
+            register_name: "pump".into(),
+            value: 1.0,
+            raw: vec![1],
+            unit: None,
+            timestamp: "2025-01-15T10:30:00+00:00".to_string(),
+            previous_value: Some(0.0),
+            changed: true,
+            meta: Default::default(),
+            boolean: true,
+            quality: Default::default(),
+        };
+
+        let payload = register_payload(&update);
+        assert_eq!(payload["value"], true);
+        assert_eq!(payload["previous_value"], false);
+        assert_eq!(payload["raw"][0], 1);
+    }
+
+    #[test]
+    fn test_register_payload_bad_quality() {
+        let mut update = RegisterUpdate {
+            device_id: "plc-001".into(),
+            register_name: "flow".into(),
+            value: f64::NAN,
+            raw: vec![0x7fc0, 0],
+            unit: None,
+            timestamp: "2025-01-15T10:30:00+00:00".to_string(),
+            previous_value: Some(f64::INFINITY),
+            changed: true,
+            meta: Default::default(),
+            boolean: false,
+            quality: Quality::Bad,
+        };
+
+        let payload = register_payload(&update);
+        assert!(payload["value"].is_null());
+        assert!(payload["previous_value"].is_null());
+        assert_eq!(payload["quality"], "bad");
+        assert!(serde_json::to_string(&update)
+            .unwrap()
+            .contains("\"value\":null"));
+
+        update.value = 12.5;
+        update.quality = Quality::Good;
+        assert!(register_payload(&update).get("quality").is_none());
+    }
+
+    #[cfg(feature = "testing")]
+    #[tokio::test]
+    async fn test_quality_policy() {
+        use crate::testing::MqttBroker;
+
+        let broker = MqttBroker::start().await.unwrap();
+        let timeout = Duration::from_secs(5);
+        let update = |value: f64, quality| RegisterUpdate {
+            device_id: "plc-001".into(),
+            register_name: "flow".into(),
+            value,
+            raw: vec![0],
+            unit: None,
+            timestamp: "2025-01-15T10:30:00+00:00".to_string(),
+            previous_value: None,
+            changed: true,
+            meta: Default::default(),
+            boolean: false,
+            quality,
+        };
+
+        let mut config = broker.config();
+        config.quality_policy = QualityPolicy::Publish;
+        let publisher = MqttPublisher::new(&config, ErrorLog::default())
+            .await
+            .unwrap();
+        publisher
+            .publish_update(&update(3.0, Quality::Substituted))
+            .await
+            .unwrap();
+        let message = broker
+            .wait_for("rustbridge/plc-001/flow", timeout)
+            .await
+            .unwrap();
+        assert_eq!(message.json().unwrap()["value"], 3.0);
+        assert!(message.json().unwrap().get("quality").is_none());
+
+        config.quality_policy = QualityPolicy::Suppress;
+        config.topic_prefix = "suppress".to_string();
+        let publisher = MqttPublisher::new(&config, ErrorLog::default())
+            .await
+            .unwrap();
+        publisher
+            .publish_update(&update(f64::NAN, Quality::Bad))
+            .await
+            .unwrap();
+        publisher
+            .publish_update(&update(f64::NAN, Quality::Bad))
+            .await
+            .unwrap();
+        publisher
+            .publish_update(&update(4.0, Quality::Good))
+            .await
+            .unwrap();
+        let good = broker
+            .wait_for("suppress/plc-001/flow", timeout)
+            .await
+            .unwrap();
+        assert_eq!(good.json().unwrap()["value"], 4.0);
+        let qualities: Vec<_> = broker
+            .messages_on("suppress/plc-001/flow/quality")
+            .into_iter()
+            .map(|m| m.payload)
+            .collect();
+        assert_eq!(qualities, vec![b"bad".to_vec(), b"good".to_vec()]);
+        assert_eq!(broker.messages_on("suppress/plc-001/flow").len(), 1);
+    }
+
+    #[test]
+    fn test_register_payload_merges_meta() {
+        let update = RegisterUpdate {
+            device_id: "plc-001".into(),
+            register_name: "temperature".into(),
+            value: 23.5,
+            raw: vec![235],
+            unit: None,
+            timestamp: "2025-01-15T10:30:00+00:00".to_string(),
+            previous_value: None,
+            changed: true,
+            meta: [
+                ("site".to_string(), serde_json::json!("plant-2")),
+                ("value".to_string(), serde_json::json!("shadowed")),
+            ]
+            .into_iter()
+            .collect(),
+            boolean: false,
+            quality: Default::default(),
+        };
+
+        let payload = register_payload(&update);
+        assert_eq!(payload["site"], "plant-2");
+        assert_eq!(payload["value"], 23.5);
+    }
+
+    #[test]
+    fn test_group_topics() {
+        assert_eq!(
+            group_status_topic("rustbridge", "line-a"),
+            "rustbridge/groups/line-a/status"
+        );
+        assert_eq!(
+            group_command_target("rustbridge", "rustbridge/groups/line-a/command"),
+            Some("line-a")
+        );
+        assert_eq!(
+            group_command_target("rustbridge", "rustbridge/groups/line-a/status"),
+            None
+        );
+        assert_eq!(
+            group_command_target("rustbridge", "other/groups/line-a/command"),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_check_connection_refused() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        drop(listener);
+
+        let config = MqttConfig {
+            host: "127.0.0.1".to_string(),
+            port,
+            ..Default::default()
+        };
+
+        let err = check_connection(&config, Duration::from_secs(2))
+            .await
+            .unwrap_err();
+        assert!(format!("{:#}", err).contains(&format!("127.0.0.1:{}", port)));
+    }
+
+    #[test]
+    fn test_heartbeat_counts() {
+        let mut diagnostics = HashMap::new();
+
+        let mut healthy = DeviceDiagnostics::default();
+        healthy.record_success("temp", 5.0);
+        healthy.record_success("temp", 5.0);
+        diagnostics.insert("plc-001".to_string(), healthy);
+
+        let mut failing = DeviceDiagnostics::default();
+        failing.record_success("pressure", 5.0);
+        failing.record_failure("pressure", 3000.0, "timeout");
+        diagnostics.insert("plc-002".to_string(), failing);
+
+        let heartbeat = Heartbeat::new(3, &diagnostics, Duration::from_secs(120));
+
+        assert_eq!(heartbeat.uptime_secs, 120);
+        assert_eq!(heartbeat.devices_configured, 3);
+        assert_eq!(heartbeat.devices_online, 1);
+        assert_eq!(heartbeat.devices_failing, 1);
+        assert_eq!(heartbeat.reads_ok, 3);
+        assert_eq!(heartbeat.reads_failed, 1);
+        assert_eq!(heartbeat.version, env!("CARGO_PKG_VERSION"));
+    }
+
+    #[test]
+    fn test_heartbeat_topic_format() {
+        let prefix = "rustbridge";
+
+        let topic = format!("{}/bridge/heartbeat", prefix);
+        assert_eq!(topic, "rustbridge/bridge/heartbeat");
+    }
+
     #[test]
     fn test_status_topic_format() {
         let prefix = "rustbridge";
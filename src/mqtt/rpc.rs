@@ -0,0 +1,555 @@
+//! JSON-RPC 2.0 control channel over MQTT
+//!
+//! Requests published to `{prefix}/bridge/rpc/request` are answered on
+//! `{prefix}/bridge/rpc/response` with the same `id`, so the bridge can be
+//! managed where only the broker is reachable:
+//!
+//! ```json
+//! {"jsonrpc": "2.0", "id": 7, "method": "write",
+//!  "params": {"device_id": "plc-001", "register": "setpoint", "value": 21.5}}
+//! ```
+//!
+//! Methods: `list_devices`, `read`, `write`, `run_recipe`, `poll_now`,
+//! `pause` and `resume`. Requests without an `id` are notifications and get
+//! no response.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tokio::sync::{broadcast, mpsc, watch};
+use tracing::{debug, error, warn};
+
+use super::set::{forward_write, validate_write};
+use super::{publish_error, MqttPublisher};
+use crate::api::{WriteError, WriteRequest};
+use crate::audit::WriteSource;
+use crate::config::Config;
+use crate::control::{Command, DeviceControls, Target};
+use crate::error::Result;
+use crate::modbus::reader::RegisterStore;
+
+/// The payload is not JSON
+const PARSE_ERROR: i32 = -32700;
+/// Not a JSON-RPC request object
+const INVALID_REQUEST: i32 = -32600;
+const METHOD_NOT_FOUND: i32 = -32601;
+const INVALID_PARAMS: i32 = -32602;
+/// Unknown device, group or register, or a register without a value yet
+const NOT_FOUND: i32 = -32001;
+/// The write was refused before reaching the device
+const WRITE_REJECTED: i32 = -32002;
+/// The device did not accept the write
+const WRITE_FAILED: i32 = -32003;
+
+/// Topic requests are received on
+pub fn rpc_request_topic(prefix: &str) -> String {
+    format!("{}/bridge/rpc/request", prefix)
+}
+
+/// Topic responses are published to
+pub fn rpc_response_topic(prefix: &str) -> String {
+    format!("{}/bridge/rpc/response", prefix)
+}
+
+#[derive(Debug, Deserialize)]
+struct RpcRequest {
+    #[serde(default)]
+    jsonrpc: Option<String>,
+    #[serde(default)]
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Debug, Serialize)]
+struct RpcResponse {
+    jsonrpc: &'static str,
+    id: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<RpcError>,
+}
+
+impl RpcResponse {
+    fn new(id: Value, outcome: std::result::Result<Value, RpcError>) -> Self {
+        let (result, error) = match outcome {
+            Ok(result) => (Some(result), None),
+            Err(error) => (None, Some(error)),
+        };
+        Self {
+            jsonrpc: "2.0",
+            id,
+            result,
+            error,
+        }
+    }
+}
+
+/// JSON-RPC error object
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RpcError {
+    pub code: i32,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+}
+
+impl RpcError {
+    fn new(code: i32, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            data: None,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ReadParams {
+    device_id: String,
+    /// Every register of the device when omitted
+    #[serde(default)]
+    register: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct WriteParams {
+    device_id: String,
+    register: String,
+    value: Value,
+}
+
+#[derive(Deserialize)]
+struct RecipeParams {
+    name: String,
+}
+
+/// Target of `poll_now`, `pause` and `resume`: a device or a group
+#[derive(Deserialize)]
+struct CommandParams {
+    #[serde(default)]
+    device_id: Option<String>,
+    #[serde(default)]
+    group: Option<String>,
+}
+
+fn params<T: serde::de::DeserializeOwned>(params: Value) -> std::result::Result<T, RpcError> {
+    // Methods without required params may be called without any
+    let params = if params.is_null() { json!({}) } else { params };
+    serde_json::from_value(params).map_err(|e| RpcError::new(INVALID_PARAMS, e.to_string()))
+}
+
+/// What RPC methods act on
+#[derive(Clone)]
+pub struct RpcContext {
+    pub config_rx: watch::Receiver<Config>,
+    pub register_store: RegisterStore,
+    pub controls: DeviceControls,
+    pub write_tx: mpsc::Sender<WriteRequest>,
+    /// Source recorded in the audit log for writes
+    pub source: WriteSource,
+}
+
+impl RpcContext {
+    /// Run a method and return its result
+    pub async fn call(&self, method: &str, params: Value) -> std::result::Result<Value, RpcError> {
+        match method {
+            "list_devices" => Ok(self.list_devices().await),
+            "read" => self.read(self::params(params)?).await,
+            "write" => self.write(self::params(params)?).await,
+            "run_recipe" => self.run_recipe(self::params(params)?).await,
+            "poll_now" => self.command(Command::PollNow, self::params(params)?),
+            "pause" => self.command(Command::Pause, self::params(params)?),
+            "resume" => self.command(Command::Resume, self::params(params)?),
+            other => Err(RpcError::new(
+                METHOD_NOT_FOUND,
+                format!("unknown method '{}'", other),
+            )),
+        }
+    }
+
+    async fn list_devices(&self) -> Value {
+        let store = self.register_store.read().await;
+        let config = self.config_rx.borrow();
+        let devices: Vec<Value> = config
+            .devices
+            .iter()
+            .map(|device| {
+                let control = self.controls.device(&device.id);
+                let last_update = store
+                    .get(&device.id)
+                    .and_then(|registers| registers.values().map(|r| r.timestamp).max())
+                    .map(|t| t.to_rfc3339());
+                json!({
+                    "id": device.id,
+                    "name": device.name,
+                    "group": device.group,
+                    "tenant": device.tenant,
+                    "online": control.as_ref().is_some_and(|c| c.is_online()),
+                    "paused": control.as_ref().is_some_and(|c| c.is_paused()),
+                    "register_count": device.registers.len(),
+                    "last_update": last_update,
+                })
+            })
+            .collect();
+        json!({ "count": devices.len(), "devices": devices })
+    }
+
+    async fn read(&self, params: ReadParams) -> std::result::Result<Value, RpcError> {
+        let store = self.register_store.read().await;
+        let registers = store.get(&params.device_id).ok_or_else(|| {
+            RpcError::new(
+                NOT_FOUND,
+                format!("Device '{}' has no values", params.device_id),
+            )
+        })?;
+        match params.register {
+            Some(name) => {
+                let register = registers.get(&name).ok_or_else(|| {
+                    RpcError::new(NOT_FOUND, format!("Register '{}' has no value", name))
+                })?;
+                Ok(json!({ "device_id": params.device_id, "register": register }))
+            }
+            None => {
+                let mut values: Vec<_> = registers.values().collect();
+                values.sort_by(|a, b| a.name.cmp(&b.name));
+                Ok(json!({ "device_id": params.device_id, "registers": values }))
+            }
+        }
+    }
+
+    async fn write(&self, params: WriteParams) -> std::result::Result<Value, RpcError> {
+        let value = {
+            let config = self.config_rx.borrow();
+            let device = config
+                .devices
+                .iter()
+                .find(|d| d.id == params.device_id)
+                .ok_or_else(|| {
+                    RpcError::new(
+                        NOT_FOUND,
+                        format!("Device '{}' not found", params.device_id),
+                    )
+                })?;
+            validate_write(device, &params.register, &params.value).map_err(|(e, schema)| {
+                RpcError {
+                    data: schema.map(|schema| json!({ "schema": schema })),
+                    ..RpcError::new(WRITE_REJECTED, e)
+                }
+            })?
+        };
+
+        let outcome = forward_write(
+            &self.write_tx,
+            params.device_id.clone(),
+            params.register.clone(),
+            self.source.clone(),
+            value,
+        )
+        .await
+        .map_err(|e| match e {
+            WriteError::Failed(e) => RpcError::new(WRITE_FAILED, e),
+            WriteError::Exception(e) => RpcError::new(WRITE_FAILED, e.to_string()),
+            other => RpcError::new(WRITE_REJECTED, other.to_string()),
+        })?;
+        Ok(json!({
+            "device_id": params.device_id,
+            "register": params.register,
+            "value": value,
+            "raw_written": outcome.raw,
+        }))
+    }
+
+    async fn run_recipe(&self, params: RecipeParams) -> std::result::Result<Value, RpcError> {
+        let recipe = self
+            .config_rx
+            .borrow()
+            .recipes
+            .iter()
+            .find(|recipe| recipe.name == params.name)
+            .cloned()
+            .ok_or_else(|| {
+                RpcError::new(NOT_FOUND, format!("Recipe '{}' not found", params.name))
+            })?;
+        let outcome = crate::recipes::run(
+            &self.write_tx,
+            &self.register_store,
+            &recipe,
+            self.source.clone(),
+        )
+        .await;
+        if outcome.success {
+            Ok(json!(outcome))
+        } else {
+            Err(RpcError {
+                data: Some(json!(outcome)),
+                ..RpcError::new(WRITE_FAILED, format!("Recipe '{}' failed", params.name))
+            })
+        }
+    }
+
+    fn command(
+        &self,
+        command: Command,
+        params: CommandParams,
+    ) -> std::result::Result<Value, RpcError> {
+        let target = match (&params.device_id, &params.group) {
+            (Some(device_id), None) => Target::Device(device_id),
+            (None, Some(group)) => Target::Group(group),
+            _ => {
+                return Err(RpcError::new(
+                    INVALID_PARAMS,
+                    "expected either device_id or group",
+                ))
+            }
+        };
+        let devices = self
+            .controls
+            .apply(target, command)
+            .map_err(|e| RpcError::new(NOT_FOUND, e.to_string()))?;
+        Ok(json!({ "command": command, "devices": devices }))
+    }
+
+    /// Answer one request payload, `None` for notifications
+    async fn handle(&self, payload: &[u8]) -> Option<RpcResponse> {
+        let request: Value = match serde_json::from_slice(payload) {
+            Ok(request) => request,
+            Err(e) => {
+                return Some(RpcResponse::new(
+                    Value::Null,
+                    Err(RpcError::new(PARSE_ERROR, e.to_string())),
+                ))
+            }
+        };
+        let id = request.get("id").cloned().unwrap_or(Value::Null);
+        let request = match serde_json::from_value::<RpcRequest>(request) {
+            Ok(request) if request.jsonrpc.as_deref().is_none_or(|v| v == "2.0") => request,
+            Ok(_) => {
+                return Some(RpcResponse::new(
+                    id,
+                    Err(RpcError::new(INVALID_REQUEST, "jsonrpc must be \"2.0\"")),
+                ))
+            }
+            Err(e) => {
+                return Some(RpcResponse::new(
+                    id,
+                    Err(RpcError::new(INVALID_REQUEST, e.to_string())),
+                ))
+            }
+        };
+
+        let outcome = self.call(&request.method, request.params).await;
+        if let Err(e) = &outcome {
+            debug!("RPC {} failed: {}", request.method, e.message);
+        }
+        request.id.map(|id| RpcResponse::new(id, outcome))
+    }
+}
+
+impl MqttPublisher {
+    /// Answer JSON-RPC requests received on `{prefix}/bridge/rpc/request`
+    pub async fn start_rpc_handling(self: Arc<Self>, context: RpcContext) {
+        let mut incoming_rx = self.incoming_tx.subscribe();
+        let request_topic = rpc_request_topic(&self.topic_prefix);
+        self.subscribe(request_topic.clone());
+
+        loop {
+            match incoming_rx.recv().await {
+                Ok(publish) if publish.topic == request_topic => {
+                    // Writes wait for the device, so requests don't queue behind them
+                    let publisher = self.clone();
+                    let context = context.clone();
+                    tokio::spawn(async move {
+                        if let Some(response) = context.handle(&publish.payload).await {
+                            if let Err(e) = publisher.publish_rpc_response(&response).await {
+                                error!("MQTT RPC response error: {}", e);
+                            }
+                        }
+                    });
+                }
+                Ok(_) => {}
+                Err(broadcast::error::RecvError::Lagged(n)) => {
+                    warn!("MQTT RPC handler lagged, missed {} messages", n);
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    }
+
+    async fn publish_rpc_response(&self, response: &RpcResponse) -> Result<()> {
+        let topic = rpc_response_topic(&self.topic_prefix);
+        let payload =
+            serde_json::to_vec(response).map_err(|e| publish_error("serialize RPC response", e))?;
+        self.client
+            .publish(&topic, self.qos, false, payload)
+            .await
+            .map_err(|e| publish_error(&topic, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::WriteOutcome;
+    use crate::config::load_config_from_str;
+    use crate::modbus::reader::RegisterValue;
+    use std::collections::HashMap;
+    use tokio::sync::RwLock;
+
+    const CONFIG: &str = r#"
+server: { host: 127.0.0.1, port: 0, metrics_enabled: false }
+devices:
+  - id: plc-001
+    name: PLC
+    group: line-a
+    device_type: tcp
+    connection: { host: 127.0.0.1, port: 502, unit_id: 1 }
+    poll_interval_ms: 1000
+    registers:
+      - { name: temperature, address: 0, register_type: input, count: 1, data_type: u16 }
+      - { name: setpoint, address: 1, register_type: holding, count: 1, data_type: u16, scale: 0.1, write_limits: { max: 80 } }
+"#;
+
+    /// Context whose writes are answered by a fake device
+    fn context() -> RpcContext {
+        let config = load_config_from_str(CONFIG).unwrap();
+        let (write_tx, mut write_rx) = mpsc::channel::<WriteRequest>(4);
+        tokio::spawn(async move {
+            while let Some(request) = write_rx.recv().await {
+                let _ = request.response_tx.send(Ok(WriteOutcome {
+                    address: 1,
+                    raw: vec![(request.value * 10.0) as u16],
+                    read_back: None,
+                }));
+            }
+        });
+        let mut registers = HashMap::new();
+        registers.insert(
+            "temperature".to_string(),
+            RegisterValue {
+                name: "temperature".into(),
+                raw: vec![215],
+                value: 21.5,
+                quality: Default::default(),
+                restored: false,
+                unit: None,
+                timestamp: chrono::Utc::now(),
+                meta: Default::default(),
+            },
+        );
+        let store: RegisterStore = Arc::new(RwLock::new(HashMap::from([(
+            "plc-001".to_string(),
+            registers,
+        )])));
+
+        RpcContext {
+            controls: DeviceControls::new(&config.devices),
+            config_rx: watch::channel(config).1,
+            register_store: store,
+            write_tx,
+            source: WriteSource::Mqtt {
+                topic: rpc_request_topic("rustbridge"),
+            },
+        }
+    }
+
+    async fn call(context: &RpcContext, request: Value) -> Value {
+        let payload = serde_json::to_vec(&request).unwrap();
+        let response = context.handle(&payload).await.expect("response");
+        serde_json::to_value(response).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_list_and_read() {
+        let context = context();
+
+        let response = call(
+            &context,
+            json!({"jsonrpc": "2.0", "id": 1, "method": "list_devices"}),
+        )
+        .await;
+        assert_eq!(response["id"], 1);
+        assert_eq!(response["result"]["count"], 1);
+        assert_eq!(response["result"]["devices"][0]["group"], "line-a");
+        assert_eq!(response["result"]["devices"][0]["paused"], false);
+
+        let response = call(
+            &context,
+            json!({"jsonrpc": "2.0", "id": "r", "method": "read",
+                   "params": {"device_id": "plc-001", "register": "temperature"}}),
+        )
+        .await;
+        assert_eq!(response["id"], "r");
+        assert_eq!(response["result"]["register"]["value"], 21.5);
+
+        let response = call(
+            &context,
+            json!({"jsonrpc": "2.0", "id": 2, "method": "read",
+                   "params": {"device_id": "plc-001", "register": "setpoint"}}),
+        )
+        .await;
+        assert_eq!(response["error"]["code"], NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_write_and_commands() {
+        let context = context();
+
+        let response = call(
+            &context,
+            json!({"jsonrpc": "2.0", "id": 1, "method": "write",
+                   "params": {"device_id": "plc-001", "register": "setpoint", "value": 21.5}}),
+        )
+        .await;
+        assert_eq!(response["result"]["raw_written"], json!([215]));
+
+        let response = call(
+            &context,
+            json!({"jsonrpc": "2.0", "id": 2, "method": "write",
+                   "params": {"device_id": "plc-001", "register": "setpoint", "value": 95}}),
+        )
+        .await;
+        assert_eq!(response["error"]["code"], WRITE_REJECTED);
+        assert_eq!(response["error"]["data"]["schema"]["maximum"], 80.0);
+
+        let response = call(
+            &context,
+            json!({"jsonrpc": "2.0", "id": 3, "method": "pause", "params": {"group": "line-a"}}),
+        )
+        .await;
+        assert_eq!(response["result"]["devices"], json!(["plc-001"]));
+        assert!(context.controls.device("plc-001").unwrap().is_paused());
+
+        let response = call(
+            &context,
+            json!({"jsonrpc": "2.0", "id": 4, "method": "poll_now", "params": {}}),
+        )
+        .await;
+        assert_eq!(response["error"]["code"], INVALID_PARAMS);
+    }
+
+    #[tokio::test]
+    async fn test_protocol_errors() {
+        let context = context();
+
+        let response = context.handle(b"not json").await.unwrap();
+        assert_eq!(response.error.unwrap().code, PARSE_ERROR);
+
+        let response = call(&context, json!({"jsonrpc": "2.0", "id": 1})).await;
+        assert_eq!(response["error"]["code"], INVALID_REQUEST);
+
+        let response = call(
+            &context,
+            json!({"jsonrpc": "2.0", "id": 1, "method": "reboot"}),
+        )
+        .await;
+        assert_eq!(response["error"]["code"], METHOD_NOT_FOUND);
+
+        // Notifications are not answered
+        let payload =
+            br#"{"jsonrpc": "2.0", "method": "resume", "params": {"device_id": "plc-001"}}"#;
+        assert!(context.handle(payload).await.is_none());
+    }
+}
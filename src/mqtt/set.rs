@@ -0,0 +1,759 @@
+//! Register writes over MQTT
+//!
+//! A value published to `{prefix}/{device_id}/{register}/set` (or under the
+//! device's tenant level) is checked against a [`WriteSchema`] derived from
+//! the register's configuration before it is handed to the bridge. The
+//! outcome, including why a value was rejected, is published to the same
+//! topic with `/result` appended. A written register is read back and its
+//! new value published on its state topic; rejected and failed writes are
+//! also collected on `{prefix}/bridge/write_errors`.
+
+use serde::Serialize;
+use serde_json::Value;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc, oneshot, watch};
+use tracing::{debug, error, info, warn};
+
+use super::{publish_error, MqttPublisher};
+use crate::api::{WriteError, WriteOutcome, WriteRequest};
+use crate::audit::WriteSource;
+use crate::config::{Config, DataType, DeviceConfig, RegisterConfig, RegisterType};
+use crate::error::Result;
+
+/// How long to wait for the device to confirm a write
+const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// JSON type of the values a register accepts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ValueType {
+    Boolean,
+    Integer,
+    Number,
+}
+
+impl ValueType {
+    fn describe(&self) -> &'static str {
+        match self {
+            ValueType::Boolean => "a boolean",
+            ValueType::Integer => "an integer",
+            ValueType::Number => "a number",
+        }
+    }
+}
+
+/// JSON schema of the values a writable register accepts, in engineering
+/// units
+///
+/// The range combines what the data type can hold after scale and offset
+/// with the register's `write_limits`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct WriteSchema {
+    #[serde(rename = "type")]
+    pub kind: ValueType,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub minimum: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub maximum: Option<f64>,
+    #[serde(rename = "enum", skip_serializing_if = "Vec::is_empty")]
+    pub values: Vec<f64>,
+}
+
+impl WriteSchema {
+    /// Schema of a register, `None` if it cannot be written
+    pub fn of(register: &RegisterConfig) -> Option<Self> {
+        if !matches!(
+            register.register_type,
+            RegisterType::Holding | RegisterType::Coil
+        ) || register.expression.is_some()
+            || register.lookup.is_some()
+        {
+            return None;
+        }
+        if matches!(register.data_type, DataType::Bool) {
+            return Some(Self {
+                kind: ValueType::Boolean,
+                minimum: None,
+                maximum: None,
+                values: vec![],
+            });
+        }
+
+        let scale = register.scale.unwrap_or(1.0);
+        let offset = register.offset.unwrap_or(0.0);
+        // A scale of -1 only flips the sign, integers stay integers
+        let kind = if !register.data_type.is_float() && scale.abs() == 1.0 && offset.fract() == 0.0
+        {
+            ValueType::Integer
+        } else {
+            ValueType::Number
+        };
+        let (mut minimum, mut maximum) = match raw_range(&register.data_type) {
+            Some((low, high)) if scale != 0.0 => {
+                let (a, b) = (low * scale + offset, high * scale + offset);
+                (Some(a.min(b)), Some(a.max(b)))
+            }
+            _ => (None, None),
+        };
+        let mut values = vec![];
+        if let Some(limits) = &register.write_limits {
+            minimum = max_of(minimum, limits.min);
+            maximum = min_of(maximum, limits.max);
+            values = limits.values.clone();
+        }
+
+        Some(Self {
+            kind,
+            minimum,
+            maximum,
+            values,
+        })
+    }
+
+    /// Check a value against the schema, returning the number to write
+    pub fn validate(&self, value: &Value) -> std::result::Result<f64, String> {
+        let number = match (self.kind, value) {
+            (ValueType::Boolean, Value::Bool(on)) => {
+                if *on {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            (ValueType::Boolean, Value::String(text)) => {
+                match text.trim().to_ascii_lowercase().as_str() {
+                    "true" | "on" | "1" => 1.0,
+                    "false" | "off" | "0" => 0.0,
+                    _ => return Err(format!("expected a boolean, got \"{}\"", text)),
+                }
+            }
+            (ValueType::Boolean, Value::Number(n)) => match n.as_f64() {
+                Some(v) if v == 0.0 || v == 1.0 => v,
+                _ => return Err(format!("expected a boolean, got {}", n)),
+            },
+            (_, Value::Number(n)) => n
+                .as_f64()
+                .ok_or_else(|| format!("{} is not a finite number", n))?,
+            (kind, other) => {
+                return Err(format!(
+                    "expected {}, got {}",
+                    kind.describe(),
+                    json_type(other)
+                ))
+            }
+        };
+
+        if self.kind == ValueType::Integer && number.fract() != 0.0 {
+            return Err(format!("expected an integer, got {}", number));
+        }
+        if let Some(minimum) = self.minimum.filter(|minimum| number < *minimum) {
+            return Err(format!("{} is below the minimum {}", number, minimum));
+        }
+        if let Some(maximum) = self.maximum.filter(|maximum| number > *maximum) {
+            return Err(format!("{} is above the maximum {}", number, maximum));
+        }
+        if !self.values.is_empty() && !self.values.contains(&number) {
+            let values: Vec<String> = self.values.iter().map(f64::to_string).collect();
+            return Err(format!("{} is not one of {}", number, values.join(", ")));
+        }
+        Ok(number)
+    }
+}
+
+/// Raw values a data type can hold, `None` when it is not an integer
+fn raw_range(data_type: &DataType) -> Option<(f64, f64)> {
+    match data_type {
+        DataType::U16 => Some((0.0, u16::MAX as f64)),
+        DataType::I16 => Some((i16::MIN as f64, i16::MAX as f64)),
+        DataType::U32 => Some((0.0, u32::MAX as f64)),
+        DataType::I32 => Some((i32::MIN as f64, i32::MAX as f64)),
+        DataType::U64 => Some((0.0, u64::MAX as f64)),
+        DataType::I64 => Some((i64::MIN as f64, i64::MAX as f64)),
+        DataType::F32 | DataType::F64 | DataType::Bool => None,
+    }
+}
+
+fn max_of(a: Option<f64>, b: Option<f64>) -> Option<f64> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.max(b)),
+        _ => a.or(b),
+    }
+}
+
+fn min_of(a: Option<f64>, b: Option<f64>) -> Option<f64> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        _ => a.or(b),
+    }
+}
+
+fn json_type(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
+}
+
+/// Value carried by a `/set` payload: a JSON value, an object with a
+/// `value` field, or plain text such as `on`
+fn payload_value(payload: &[u8]) -> std::result::Result<Value, String> {
+    let text = std::str::from_utf8(payload).map_err(|_| "payload is not UTF-8".to_string())?;
+    match serde_json::from_str(text) {
+        Ok(Value::Object(mut fields)) => fields
+            .remove("value")
+            .ok_or_else(|| "missing \"value\" field".to_string()),
+        Ok(value) => Ok(value),
+        Err(_) => Ok(Value::String(text.trim().to_string())),
+    }
+}
+
+/// Check a `/set` payload for a register of `device`
+fn validate_set(
+    device: &DeviceConfig,
+    register_name: &str,
+    payload: &[u8],
+) -> std::result::Result<f64, (String, Option<WriteSchema>)> {
+    let value = payload_value(payload).map_err(|e| (e, None))?;
+    validate_write(device, register_name, &value)
+}
+
+/// Check a value to write to a register of `device`
+///
+/// Rejections carry the register's schema when the value did not match it.
+pub(super) fn validate_write(
+    device: &DeviceConfig,
+    register_name: &str,
+    value: &Value,
+) -> std::result::Result<f64, (String, Option<WriteSchema>)> {
+    let register = device
+        .registers
+        .iter()
+        .find(|r| r.name == register_name)
+        .ok_or_else(|| (format!("Register '{}' not found", register_name), None))?;
+    let schema = WriteSchema::of(register)
+        .ok_or_else(|| (format!("Register '{}' is read-only", register_name), None))?;
+    schema.validate(value).map_err(|e| (e, Some(schema)))
+}
+
+/// Tenant level, device and register addressed by a `/set` topic
+#[derive(Debug, PartialEq, Eq)]
+struct SetTarget<'a> {
+    tenant: Option<&'a str>,
+    device_id: &'a str,
+    register: &'a str,
+}
+
+fn set_target<'a>(prefix: &str, topic: &'a str) -> Option<SetTarget<'a>> {
+    let levels: Vec<&str> = topic
+        .strip_prefix(prefix)?
+        .strip_prefix('/')?
+        .strip_suffix("/set")?
+        .split('/')
+        .collect();
+    if levels.iter().any(|level| level.is_empty()) {
+        return None;
+    }
+    match levels[..] {
+        [device_id, register] => Some(SetTarget {
+            tenant: None,
+            device_id,
+            register,
+        }),
+        [tenant, device_id, register] => Some(SetTarget {
+            tenant: Some(tenant),
+            device_id,
+            register,
+        }),
+        _ => None,
+    }
+}
+
+/// Outcome of a `/set` command
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum SetStatus {
+    /// Written to the device
+    Ok,
+    /// Refused before reaching the device
+    Rejected,
+    /// The device did not accept the write
+    Failed,
+}
+
+/// How a register's value relates to the raw registers written:
+/// `value = raw × scale + offset`, in `unit`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+struct Conversion {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    unit: Option<String>,
+    scale: f64,
+    offset: f64,
+}
+
+impl Conversion {
+    fn of(register: &RegisterConfig) -> Self {
+        Self {
+            unit: register.unit.clone(),
+            scale: register.scale.unwrap_or(1.0),
+            offset: register.offset.unwrap_or(0.0),
+        }
+    }
+}
+
+/// Payload published to `{prefix}/bridge/write_errors`
+#[derive(Debug, Serialize)]
+struct WriteErrorMessage<'a> {
+    /// The `/set` topic the write came in on
+    topic: &'a str,
+    #[serde(flatten)]
+    result: &'a SetResult,
+}
+
+/// Payload published to `{topic}/result`
+#[derive(Debug, Serialize)]
+struct SetResult {
+    status: SetStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    value: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    raw_written: Option<Vec<u16>>,
+    /// Value the device holds after the write
+    #[serde(skip_serializing_if = "Option::is_none")]
+    read_back: Option<f64>,
+    /// Unit, scale and offset of the register, when it exists
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    conversion: Option<Conversion>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    /// Schema the value failed to match
+    #[serde(skip_serializing_if = "Option::is_none")]
+    schema: Option<WriteSchema>,
+    timestamp: String,
+}
+
+impl SetResult {
+    fn new(status: SetStatus) -> Self {
+        Self {
+            status,
+            value: None,
+            raw_written: None,
+            read_back: None,
+            conversion: None,
+            error: None,
+            schema: None,
+            timestamp: chrono::Utc::now().to_rfc3339(),
+        }
+    }
+
+    fn rejected(error: String, schema: Option<WriteSchema>) -> Self {
+        Self {
+            error: Some(error),
+            schema,
+            ..Self::new(SetStatus::Rejected)
+        }
+    }
+
+    fn with_conversion(self, conversion: Option<Conversion>) -> Self {
+        Self { conversion, ..self }
+    }
+
+    fn from_write(value: f64, result: std::result::Result<WriteOutcome, WriteError>) -> Self {
+        match result {
+            Ok(outcome) => Self {
+                value: Some(value),
+                raw_written: Some(outcome.raw),
+                read_back: outcome.read_back,
+                ..Self::new(SetStatus::Ok)
+            },
+            Err(e) => Self {
+                value: Some(value),
+                error: Some(e.to_string()),
+                ..Self::new(match e {
+                    WriteError::Failed(_) | WriteError::Exception(_) => SetStatus::Failed,
+                    _ => SetStatus::Rejected,
+                })
+            },
+        }
+    }
+}
+
+impl MqttPublisher {
+    /// Validate writes received on `/set` topics and forward them to the
+    /// bridge, publishing the outcome to `{topic}/result`
+    pub async fn start_set_handling(
+        self: Arc<Self>,
+        config_rx: watch::Receiver<Config>,
+        write_tx: mpsc::Sender<WriteRequest>,
+    ) {
+        let mut incoming_rx = self.incoming_tx.subscribe();
+        self.subscribe(format!("{}/+/+/set", self.topic_prefix));
+        self.subscribe(format!("{}/+/+/+/set", self.topic_prefix));
+
+        loop {
+            match incoming_rx.recv().await {
+                Ok(publish) => {
+                    let Some(target) = set_target(&self.topic_prefix, &publish.topic) else {
+                        continue;
+                    };
+                    // A device only answers on its own tenant level
+                    if self.controls.tenant_of(target.device_id).as_deref() != target.tenant {
+                        debug!("Ignoring {}: not a device topic", publish.topic);
+                        continue;
+                    }
+                    let (validated, conversion) = {
+                        let config = config_rx.borrow();
+                        let Some(device) = config.devices.iter().find(|d| d.id == target.device_id)
+                        else {
+                            debug!("Ignoring {}: unknown device", publish.topic);
+                            continue;
+                        };
+                        let register = device.registers.iter().find(|r| r.name == target.register);
+                        (
+                            validate_set(device, target.register, &publish.payload),
+                            register.map(Conversion::of),
+                        )
+                    };
+
+                    let result_topic = format!("{}/result", publish.topic);
+                    match validated {
+                        Ok(value) => {
+                            let device_id = target.device_id.to_string();
+                            let register = target.register.to_string();
+                            let source = WriteSource::Mqtt {
+                                topic: publish.topic.clone(),
+                            };
+                            // Queued in the order received; waiting for slow
+                            // devices doesn't hold up the others
+                            let queued =
+                                queue_write(&write_tx, device_id, register, source, value).await;
+                            let publisher = self.clone();
+                            tokio::spawn(async move {
+                                let result = match queued {
+                                    Ok(queued) => queued.outcome().await,
+                                    Err(e) => Err(e),
+                                };
+                                let result = SetResult::from_write(value, result)
+                                    .with_conversion(conversion);
+                                publisher.publish_set_result(&result_topic, &result).await;
+                            });
+                        }
+                        Err((e, schema)) => {
+                            warn!("Rejected MQTT write on {}: {}", publish.topic, e);
+                            let result = SetResult::rejected(e, schema).with_conversion(conversion);
+                            self.publish_set_result(&result_topic, &result).await;
+                        }
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(n)) => {
+                    warn!("MQTT write handler lagged, missed {} messages", n);
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    }
+
+    async fn publish_set_result(&self, topic: &str, result: &SetResult) {
+        if let Err(e) = self.try_publish(topic, result).await {
+            error!("MQTT write result error: {}", e);
+        }
+        if result.status == SetStatus::Ok {
+            return;
+        }
+        let errors_topic = format!("{}/bridge/write_errors", self.topic_prefix);
+        let message = WriteErrorMessage {
+            topic: topic.strip_suffix("/result").unwrap_or(topic),
+            result,
+        };
+        if let Err(e) = self.try_publish(&errors_topic, &message).await {
+            error!("MQTT write error message error: {}", e);
+        }
+    }
+
+    async fn try_publish(&self, topic: &str, message: &impl Serialize) -> Result<()> {
+        let payload =
+            serde_json::to_vec(message).map_err(|e| publish_error("serialize write result", e))?;
+        self.client
+            .publish(topic, self.qos, false, payload)
+            .await
+            .map_err(|e| publish_error(topic, e))
+    }
+}
+
+/// Hand a validated write to the bridge and wait for the device's answer
+pub(super) async fn forward_write(
+    write_tx: &mpsc::Sender<WriteRequest>,
+    device_id: String,
+    register_name: String,
+    source: WriteSource,
+    value: f64,
+) -> std::result::Result<WriteOutcome, WriteError> {
+    queue_write(write_tx, device_id, register_name, source, value)
+        .await?
+        .outcome()
+        .await
+}
+
+/// A write handed to the bridge whose answer is still to come
+struct QueuedWrite {
+    target: String,
+    value: f64,
+    response_rx: oneshot::Receiver<std::result::Result<WriteOutcome, WriteError>>,
+}
+
+/// Hand a validated write to the bridge, which applies the writes of a
+/// device in the order they were queued
+async fn queue_write(
+    write_tx: &mpsc::Sender<WriteRequest>,
+    device_id: String,
+    register_name: String,
+    source: WriteSource,
+    value: f64,
+) -> std::result::Result<QueuedWrite, WriteError> {
+    let target = format!("{}:{}", device_id, register_name);
+    let (response_tx, response_rx) = oneshot::channel();
+    let request = WriteRequest {
+        device_id,
+        register_name,
+        source,
+        value,
+        response_tx,
+    };
+    write_tx
+        .send(request)
+        .await
+        .map_err(|_| WriteError::Failed("The Modbus write handler is not running".to_string()))?;
+    Ok(QueuedWrite {
+        target,
+        value,
+        response_rx,
+    })
+}
+
+impl QueuedWrite {
+    /// Wait for the device's answer
+    async fn outcome(self) -> std::result::Result<WriteOutcome, WriteError> {
+        let outcome = match tokio::time::timeout(WRITE_TIMEOUT, self.response_rx).await {
+            Ok(Ok(response)) => response?,
+            Ok(Err(_)) => {
+                return Err(WriteError::Failed(
+                    "Response channel closed unexpectedly".to_string(),
+                ))
+            }
+            Err(_) => {
+                return Err(WriteError::Failed(
+                    "The Modbus device did not respond in time".to_string(),
+                ))
+            }
+        };
+        info!(
+            "MQTT write {} = {} (raw {:?})",
+            self.target, self.value, outcome.raw
+        );
+        Ok(outcome)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::load_config_from_str;
+
+    /// Device with one register per YAML flow mapping
+    fn device(registers: &[&str]) -> DeviceConfig {
+        let registers: Vec<String> = registers
+            .iter()
+            .map(|register| format!("      - {{ count: 1, {} }}\n", register))
+            .collect();
+        let yaml = format!(
+            "server: {{ host: 127.0.0.1, port: 0, metrics_enabled: false }}\n\
+             devices:\n  - id: plc-001\n    name: PLC\n    device_type: tcp\n    \
+             connection: {{ host: 127.0.0.1, port: 502, unit_id: 1 }}\n    \
+             poll_interval_ms: 1000\n    registers:\n{}",
+            registers.concat()
+        );
+        load_config_from_str(&yaml).unwrap().devices.remove(0)
+    }
+
+    #[test]
+    fn test_set_target() {
+        assert_eq!(
+            set_target("rb", "rb/plc-001/setpoint/set"),
+            Some(SetTarget {
+                tenant: None,
+                device_id: "plc-001",
+                register: "setpoint"
+            })
+        );
+        assert_eq!(
+            set_target("rb", "rb/acme/plc-001/setpoint/set").and_then(|t| t.tenant),
+            Some("acme")
+        );
+        assert_eq!(set_target("rb", "rb/plc-001/set"), None);
+        assert_eq!(set_target("rb", "rb/plc-001/setpoint/set/result"), None);
+        assert_eq!(set_target("rb", "rb/plc-001//set"), None);
+        assert_eq!(set_target("rb", "other/plc-001/setpoint/set"), None);
+    }
+
+    #[test]
+    fn test_schema_from_register() {
+        let device = device(&[
+            "name: mode, address: 0, register_type: holding, data_type: u16, write_limits: { values: [0, 1, 2] }",
+            "name: setpoint, address: 1, register_type: holding, data_type: i16, scale: 0.1, write_limits: { max: 80 }",
+            "name: pump, address: 0, register_type: coil, data_type: bool",
+            "name: temperature, address: 0, register_type: input, data_type: u16",
+        ]);
+        let schema =
+            |name: &str| WriteSchema::of(device.registers.iter().find(|r| r.name == name).unwrap());
+
+        let mode = schema("mode").unwrap();
+        assert_eq!(mode.kind, ValueType::Integer);
+        assert_eq!((mode.minimum, mode.maximum), (Some(0.0), Some(65535.0)));
+        assert_eq!(
+            serde_json::to_value(&mode).unwrap(),
+            serde_json::json!({"type": "integer", "minimum": 0.0, "maximum": 65535.0, "enum": [0.0, 1.0, 2.0]})
+        );
+
+        let setpoint = schema("setpoint").unwrap();
+        assert_eq!(setpoint.kind, ValueType::Number);
+        assert_eq!(setpoint.minimum, Some(-3276.8));
+        assert_eq!(setpoint.maximum, Some(80.0));
+
+        assert_eq!(schema("pump").unwrap().kind, ValueType::Boolean);
+        assert_eq!(schema("temperature"), None);
+    }
+
+    #[test]
+    fn test_validate_set_payloads() {
+        let device = device(&[
+            "name: mode, address: 0, register_type: holding, data_type: u16, write_limits: { values: [0, 1, 2] }",
+            "name: setpoint, address: 1, register_type: holding, data_type: i16, scale: 0.1, write_limits: { min: 5, max: 80 }",
+            "name: pump, address: 0, register_type: coil, data_type: bool",
+        ]);
+
+        assert_eq!(validate_set(&device, "setpoint", b"21.5"), Ok(21.5));
+        assert_eq!(
+            validate_set(&device, "setpoint", br#"{"value": 30}"#),
+            Ok(30.0)
+        );
+        assert_eq!(validate_set(&device, "pump", b"on"), Ok(1.0));
+        assert_eq!(validate_set(&device, "pump", b"false"), Ok(0.0));
+        assert_eq!(validate_set(&device, "mode", b"2"), Ok(2.0));
+
+        let error = |register: &str, payload: &[u8]| {
+            let (error, schema) = validate_set(&device, register, payload).unwrap_err();
+            assert!(schema.is_some());
+            error
+        };
+        assert_eq!(
+            validate_set(&device, "setpoint", br#"{"val": 1}"#),
+            Err(("missing \"value\" field".to_string(), None))
+        );
+        assert_eq!(error("setpoint", b"90"), "90 is above the maximum 80");
+        assert_eq!(error("setpoint", b"1"), "1 is below the minimum 5");
+        assert_eq!(
+            error("setpoint", b"\"warm\""),
+            "expected a number, got a string"
+        );
+        assert_eq!(error("pump", b"2"), "expected a boolean, got 2");
+        assert_eq!(error("mode", b"1.5"), "expected an integer, got 1.5");
+        assert_eq!(error("mode", b"3"), "3 is not one of 0, 1, 2");
+
+        let (error, schema) = validate_set(&device, "missing", b"1").unwrap_err();
+        assert_eq!(error, "Register 'missing' not found");
+        assert!(schema.is_none());
+    }
+
+    #[test]
+    fn test_result_payload() {
+        let result = SetResult::rejected("90 is above the maximum 80".to_string(), None);
+        let json = serde_json::to_value(&result).unwrap();
+        assert_eq!(json["status"], "rejected");
+        assert_eq!(json["error"], "90 is above the maximum 80");
+        assert!(json.get("raw_written").is_none());
+        assert!(json.get("scale").is_none());
+
+        let message = WriteErrorMessage {
+            topic: "rb/plc-001/setpoint/set",
+            result: &result,
+        };
+        let json = serde_json::to_value(&message).unwrap();
+        assert_eq!(json["topic"], "rb/plc-001/setpoint/set");
+        assert_eq!(json["status"], "rejected");
+    }
+
+    #[test]
+    fn test_negative_scale() {
+        let device = device(&[
+            "name: flow, address: 0, register_type: holding, data_type: i16, scale: -0.5, offset: 10, unit: m3/h",
+            "name: direction, address: 1, register_type: holding, data_type: i16, scale: -1",
+        ]);
+        let flow = &device.registers[0];
+        let schema = WriteSchema::of(flow).unwrap();
+        assert_eq!(schema.kind, ValueType::Number);
+        assert_eq!(schema.minimum, Some(-16373.5));
+        assert_eq!(schema.maximum, Some(16394.0));
+        assert_eq!(
+            WriteSchema::of(&device.registers[1]).unwrap().kind,
+            ValueType::Integer
+        );
+
+        // The result tells how `value` was turned into `raw_written`
+        let outcome = WriteOutcome {
+            address: 0,
+            raw: crate::modbus::codec::encode_value(12.5, flow).unwrap(),
+            read_back: Some(12.5),
+        };
+        let result =
+            SetResult::from_write(12.5, Ok(outcome)).with_conversion(Some(Conversion::of(flow)));
+        let json = serde_json::to_value(&result).unwrap();
+        assert_eq!(json["raw_written"], serde_json::json!([65531]));
+        assert_eq!(json["read_back"], 12.5);
+        assert_eq!(json["unit"], "m3/h");
+        assert_eq!(json["scale"], -0.5);
+        assert_eq!(json["offset"], 10.0);
+    }
+
+    #[tokio::test]
+    async fn test_queue_write_keeps_order() {
+        let (write_tx, mut write_rx) = mpsc::channel::<WriteRequest>(4);
+        let source = WriteSource::Mqtt {
+            topic: "rb/plc-001/setpoint/set".to_string(),
+        };
+        let mut queued = Vec::new();
+        for value in [20.0, 25.0] {
+            let write = queue_write(
+                &write_tx,
+                "plc-001".to_string(),
+                "setpoint".to_string(),
+                source.clone(),
+                value,
+            );
+            queued.push(write.await.unwrap());
+        }
+
+        // The bridge sees the writes as they were received, whoever waits first
+        let waiting: Vec<_> = queued
+            .into_iter()
+            .rev()
+            .map(|write| tokio::spawn(write.outcome()))
+            .collect();
+        for value in [20.0, 25.0] {
+            let request = write_rx.recv().await.unwrap();
+            assert_eq!(request.value, value);
+            let outcome = WriteOutcome {
+                address: 0,
+                raw: vec![value as u16],
+                read_back: None,
+            };
+            request.response_tx.send(Ok(outcome)).unwrap();
+        }
+        for (waiting, raw) in waiting.into_iter().zip([25, 20]) {
+            assert_eq!(waiting.await.unwrap().unwrap().raw, [raw]);
+        }
+    }
+}
@@ -0,0 +1,233 @@
+//! TLS to the broker, with certificate rotation
+//!
+//! With `use_tls`, the broker is verified against `ca_cert` (or the system
+//! roots) and, with `client_cert` and `client_key`, the bridge authenticates
+//! with its own certificate. These files are checked every
+//! `cert_check_secs`; once they changed and then stayed the same for one
+//! check, the connection is re-established with the new certificates, so
+//! rotated edge certificates are picked up without a restart.
+//!
+//! `insecure_skip_verify` accepts any broker certificate, checking only that
+//! the broker holds its key; it is meant for test brokers with self-signed
+//! certificates.
+
+use rumqttc::Transport;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+use tokio::time::{interval, Interval, MissedTickBehavior};
+
+use crate::config::MqttConfig;
+use crate::error::{Result, RustbridgeError};
+
+/// Transport for the broker connection: TLS from the configured files, or TCP
+#[cfg(feature = "tls")]
+pub(super) fn transport(config: &MqttConfig) -> Result<Transport> {
+    if !config.use_tls {
+        return Ok(Transport::Tcp);
+    }
+    let error = |e: anyhow::Error| RustbridgeError::Config(format!("mqtt TLS: {:#}", e));
+    let builder = if config.insecure_skip_verify {
+        tracing::warn!("MQTT TLS: the broker certificate is not verified (insecure_skip_verify)");
+        let algorithms = rustls::crypto::ring::default_provider().signature_verification_algorithms;
+        rustls::ClientConfig::builder()
+            .dangerous()
+            .with_custom_certificate_verifier(std::sync::Arc::new(SkipVerify(algorithms)))
+    } else {
+        let roots = crate::logging::syslog::root_store(config.ca_cert.as_deref()).map_err(error)?;
+        rustls::ClientConfig::builder().with_root_certificates(roots)
+    };
+    let tls = match (&config.client_cert, &config.client_key) {
+        (Some(cert), Some(key)) => {
+            let (certs, key) = client_auth(cert, key).map_err(error)?;
+            builder
+                .with_client_auth_cert(certs, key)
+                .map_err(|e| error(e.into()))?
+        }
+        _ => builder.with_no_client_auth(),
+    };
+    Ok(Transport::tls_with_config(tls.into()))
+}
+
+#[cfg(not(feature = "tls"))]
+pub(super) fn transport(config: &MqttConfig) -> Result<Transport> {
+    if config.use_tls {
+        return Err(RustbridgeError::Config(
+            "MQTT TLS support not compiled in (enable the `tls` feature)".to_string(),
+        ));
+    }
+    Ok(Transport::Tcp)
+}
+
+/// Verifier of `insecure_skip_verify`: any certificate, valid signatures
+#[cfg(feature = "tls")]
+#[derive(Debug)]
+struct SkipVerify(rustls::crypto::WebPkiSupportedAlgorithms);
+
+#[cfg(feature = "tls")]
+impl rustls::client::danger::ServerCertVerifier for SkipVerify {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &rustls::pki_types::CertificateDer<'_>,
+        _intermediates: &[rustls::pki_types::CertificateDer<'_>],
+        _server_name: &rustls::pki_types::ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: rustls::pki_types::UnixTime,
+    ) -> std::result::Result<rustls::client::danger::ServerCertVerified, rustls::Error> {
+        Ok(rustls::client::danger::ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &rustls::pki_types::CertificateDer<'_>,
+        dss: &rustls::DigitallySignedStruct,
+    ) -> std::result::Result<rustls::client::danger::HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls12_signature(message, cert, dss, &self.0)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &rustls::pki_types::CertificateDer<'_>,
+        dss: &rustls::DigitallySignedStruct,
+    ) -> std::result::Result<rustls::client::danger::HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls13_signature(message, cert, dss, &self.0)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<rustls::SignatureScheme> {
+        self.0.supported_schemes()
+    }
+}
+
+/// Certificate chain and private key of the bridge
+#[cfg(feature = "tls")]
+fn client_auth(
+    cert: &str,
+    key: &str,
+) -> anyhow::Result<(
+    Vec<rustls::pki_types::CertificateDer<'static>>,
+    rustls::pki_types::PrivateKeyDer<'static>,
+)> {
+    use anyhow::Context;
+
+    let pem = std::fs::read(cert).with_context(|| format!("Failed to read {}", cert))?;
+    let certs = rustls_pemfile::certs(&mut pem.as_slice())
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .with_context(|| format!("Invalid certificate in {}", cert))?;
+    if certs.is_empty() {
+        anyhow::bail!("No certificate in {}", cert);
+    }
+    let pem = std::fs::read(key).with_context(|| format!("Failed to read {}", key))?;
+    let key = rustls_pemfile::private_key(&mut pem.as_slice())
+        .with_context(|| format!("Invalid private key in {}", key))?
+        .with_context(|| format!("No private key in {}", key))?;
+    Ok((certs, key))
+}
+
+/// Modification time and size of a file, `None` while it is missing
+type Stamp = Option<(SystemTime, u64)>;
+
+/// Watches the certificate files of a TLS connection for changes
+pub(super) struct CertWatch {
+    files: Vec<PathBuf>,
+    ticker: Interval,
+    /// Files the connection was set up with
+    loaded: Vec<Stamp>,
+    /// Files at the last check
+    seen: Vec<Stamp>,
+}
+
+impl CertWatch {
+    /// Watch the files of `config`, `None` without TLS or checks
+    pub(super) fn new(config: &MqttConfig) -> Option<Self> {
+        if !config.use_tls || config.cert_check_secs == 0 {
+            return None;
+        }
+        let files: Vec<PathBuf> = [&config.ca_cert, &config.client_cert, &config.client_key]
+            .into_iter()
+            .flatten()
+            .map(PathBuf::from)
+            .collect();
+        if files.is_empty() {
+            return None;
+        }
+        let every = Duration::from_secs(config.cert_check_secs);
+        let mut ticker = interval(every);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        let loaded = stamps(&files);
+        Some(Self {
+            files,
+            ticker,
+            seen: loaded.clone(),
+            loaded,
+        })
+    }
+
+    /// Wait until the files changed and have settled
+    ///
+    /// Cancel safe: checks keep their schedule when the wait is dropped.
+    pub(super) async fn changed(&mut self) {
+        loop {
+            self.ticker.tick().await;
+            let now = stamps(&self.files);
+            let settled = now == self.seen;
+            self.seen = now;
+            if settled && self.seen != self.loaded {
+                self.loaded = self.seen.clone();
+                return;
+            }
+        }
+    }
+}
+
+/// Wait for renewed certificates, forever without a watch
+pub(super) async fn renewed(watch: &mut Option<CertWatch>) {
+    match watch {
+        Some(watch) => watch.changed().await,
+        None => std::future::pending().await,
+    }
+}
+
+fn stamps(files: &[PathBuf]) -> Vec<Stamp> {
+    files
+        .iter()
+        .map(|file| {
+            let metadata = std::fs::metadata(file).ok()?;
+            Some((metadata.modified().ok()?, metadata.len()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_cert_watch() {
+        let dir = tempfile::tempdir().unwrap();
+        let cert = dir.path().join("client.crt");
+        std::fs::write(&cert, "old").unwrap();
+        let config: MqttConfig = serde_yaml::from_str(&format!(
+            "{{ host: localhost, port: 8883, client_id: gw, topic_prefix: rb, qos: 1, \
+             use_tls: true, client_cert: '{}', cert_check_secs: 10 }}",
+            cert.display()
+        ))
+        .unwrap();
+        let mut watch = CertWatch::new(&config).unwrap();
+        watch.ticker = interval(Duration::from_millis(10));
+        let wait = Duration::from_millis(100);
+
+        assert!(tokio::time::timeout(wait, watch.changed()).await.is_err());
+
+        // Reported once the rotated file stayed the same for a check
+        std::fs::write(&cert, "renewed").unwrap();
+        tokio::time::timeout(wait, watch.changed()).await.unwrap();
+        assert!(tokio::time::timeout(wait, watch.changed()).await.is_err());
+
+        let plain: MqttConfig = serde_yaml::from_str(
+            "{ host: localhost, port: 1883, client_id: gw, topic_prefix: rb, qos: 1 }",
+        )
+        .unwrap();
+        assert!(CertWatch::new(&plain).is_none());
+    }
+}
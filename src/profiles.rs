@@ -0,0 +1,303 @@
+//! Device profiles: register maps shared by devices of the same model
+//!
+//! A device with `profile: eastron/sdm630` reads the registers of
+//! `eastron/sdm630.yaml` from the first source that has it: the directories
+//! in `profiles.paths`, then the git repositories in `profiles.git`. These are
+//! cloned into `profiles.cache_dir` and brought up to date the first time a
+//! process uses them, i.e. at startup; a failed update keeps the existing
+//! checkout, and git gets a minute per clone or fetch.
+//!
+//! A profile file lists registers like a device does:
+//!
+//! ```yaml
+//! manufacturer: Eastron
+//! model: SDM630
+//! registers:
+//!   - { name: voltage_l1, address: 0, register_type: input, count: 2, data_type: f32, unit: V }
+//! ```
+//!
+//! Registers of the device replace profile registers with the same name and
+//! come after the others. Resolving is idempotent, so a configuration written
+//! back by `PUT /api/config` (with the profile registers inlined) loads the
+//! same way.
+
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+use crate::config::{Config, GitProfiles, RegisterConfig};
+use crate::error::{Result, RustbridgeError};
+
+/// Contents of a profile file
+#[derive(Debug, Clone, Deserialize)]
+pub struct Profile {
+    #[serde(default)]
+    pub manufacturer: Option<String>,
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    pub registers: Vec<RegisterConfig>,
+}
+
+/// Merge the registers of each device's profile into its `registers`
+pub fn resolve(config: &mut Config) -> Result<()> {
+    if config.devices.iter().all(|device| device.profile.is_none()) {
+        return Ok(());
+    }
+    let sources = sources(config)?;
+    for device in &mut config.devices {
+        let Some(id) = &device.profile else {
+            continue;
+        };
+        let profile = load(&sources, id)
+            .map_err(|e| RustbridgeError::Config(format!("device '{}': {}", device.id, e)))?;
+        device.registers = merge(profile.registers, std::mem::take(&mut device.registers));
+    }
+    Ok(())
+}
+
+/// Profile registers in order, each replaced by the device register of the
+/// same name, followed by the remaining device registers
+pub fn merge(profile: Vec<RegisterConfig>, device: Vec<RegisterConfig>) -> Vec<RegisterConfig> {
+    let mut device: Vec<Option<RegisterConfig>> = device.into_iter().map(Some).collect();
+    let mut merged: Vec<RegisterConfig> = profile
+        .into_iter()
+        .map(|register| {
+            device
+                .iter_mut()
+                .find(|own| own.as_ref().is_some_and(|own| own.name == register.name))
+                .and_then(Option::take)
+                .unwrap_or(register)
+        })
+        .collect();
+    merged.extend(device.into_iter().flatten());
+    merged
+}
+
+/// Directories to search, in order, cloning or updating git sources
+fn sources(config: &Config) -> Result<Vec<PathBuf>> {
+    let profiles = &config.profiles;
+    let mut sources: Vec<PathBuf> = profiles.paths.iter().map(PathBuf::from).collect();
+    for git in &profiles.git {
+        sources.push(checkout(git, Path::new(&profiles.cache_dir))?);
+    }
+    if sources.is_empty() {
+        return Err(RustbridgeError::Config(
+            "devices use profiles but profiles has no paths or git sources".to_string(),
+        ));
+    }
+    Ok(sources)
+}
+
+/// Find and parse profile `id` (e.g. `eastron/sdm630`)
+fn load(sources: &[PathBuf], id: &str) -> std::result::Result<Profile, String> {
+    let valid = !id.is_empty()
+        && id.split('/').all(|part| {
+            !part.is_empty()
+                && part != "."
+                && part != ".."
+                && part
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        });
+    if !valid {
+        return Err(format!("invalid profile name '{}'", id));
+    }
+    let path = sources
+        .iter()
+        .flat_map(|source| ["yaml", "yml"].map(|ext| source.join(format!("{}.{}", id, ext))))
+        .find(|path| path.is_file())
+        .ok_or_else(|| format!("profile '{}' not found", id))?;
+    let content = std::fs::read_to_string(&path)
+        .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+    serde_yaml::from_str(&content).map_err(|e| format!("invalid profile {}: {}", path.display(), e))
+}
+
+/// Longest a git clone or fetch may run before it is killed
+const GIT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Checkouts already brought up to date by this process
+fn updated() -> &'static Mutex<HashSet<PathBuf>> {
+    static UPDATED: OnceLock<Mutex<HashSet<PathBuf>>> = OnceLock::new();
+    UPDATED.get_or_init(Default::default)
+}
+
+/// Clone a git source, or update it once per process
+fn checkout(git: &GitProfiles, cache_dir: &Path) -> Result<PathBuf> {
+    // Profiles are resolved before the configuration is validated
+    if let Some(e) = git.option_error() {
+        return Err(RustbridgeError::Config(e));
+    }
+    let name: String = git
+        .url
+        .trim_end_matches('/')
+        .trim_end_matches(".git")
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    let dir = cache_dir.join(&name);
+    let first_use = updated()
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(dir.clone());
+
+    if dir.join(".git").exists() {
+        if first_use {
+            let rev = git.rev.as_deref().unwrap_or("HEAD");
+            let result = run_git(
+                &dir,
+                &["fetch", "--quiet", "--depth", "1", "--", "origin", rev],
+            )
+            .and_then(|()| run_git(&dir, &["reset", "--quiet", "--hard", "FETCH_HEAD"]));
+            match result {
+                Ok(()) => info!("Updated device profiles from {}", git.url),
+                Err(e) => warn!(
+                    "Failed to update device profiles from {}, using the existing checkout: {}",
+                    git.url, e
+                ),
+            }
+        }
+        return Ok(dir);
+    }
+
+    std::fs::create_dir_all(cache_dir).map_err(|e| {
+        RustbridgeError::Config(format!("profiles.cache_dir {}: {}", cache_dir.display(), e))
+    })?;
+    let mut args = vec!["clone", "--quiet", "--depth", "1"];
+    if let Some(rev) = &git.rev {
+        args.extend(["--branch", rev.as_str()]);
+    }
+    args.extend(["--", git.url.as_str(), name.as_str()]);
+    run_git(cache_dir, &args).map_err(|e| {
+        RustbridgeError::Config(format!(
+            "failed to clone device profiles from {}: {}",
+            git.url, e
+        ))
+    })?;
+    info!("Cloned device profiles from {}", git.url);
+    Ok(dir)
+}
+
+/// Run git, killing it once [`GIT_TIMEOUT`] has passed
+///
+/// Resolving blocks, so async callers run it on the blocking thread pool.
+fn run_git(dir: &Path, args: &[&str]) -> std::result::Result<(), String> {
+    let mut child = Command::new("git")
+        .current_dir(dir)
+        .args(args)
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("git: {}", e))?;
+    let deadline = Instant::now() + GIT_TIMEOUT;
+    while child
+        .try_wait()
+        .map_err(|e| format!("git: {}", e))?
+        .is_none()
+    {
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(format!(
+                "git {} timed out after {}s",
+                args[0],
+                GIT_TIMEOUT.as_secs()
+            ));
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    let output = child
+        .wait_with_output()
+        .map_err(|e| format!("git: {}", e))?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_device_profile() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        std::fs::create_dir_all(dir.join("eastron")).unwrap();
+        std::fs::write(
+            dir.join("eastron/sdm630.yaml"),
+            r#"
+manufacturer: Eastron
+model: SDM630
+registers:
+  - { name: voltage_l1, address: 0, register_type: input, count: 2, data_type: f32, unit: V }
+  - { name: current_l1, address: 6, register_type: input, count: 2, data_type: f32, unit: A }
+"#,
+        )
+        .unwrap();
+        let yaml = format!(
+            r#"
+server: {{ host: "127.0.0.1", port: 3000, metrics_enabled: false }}
+profiles: {{ paths: [{}] }}
+devices:
+  - id: meter-1
+    name: Meter
+    device_type: tcp
+    poll_interval_ms: 1000
+    connection: {{ host: 10.0.0.1, port: 502, unit_id: 1 }}
+    profile: eastron/sdm630
+    registers:
+      - {{ name: current_l1, address: 6, register_type: input, count: 2, data_type: f32, scale: 0.5 }}
+      - {{ name: status, address: 100, register_type: holding, count: 1, data_type: u16 }}
+"#,
+            dir.display()
+        );
+
+        let config = crate::config::load_config_from_str(&yaml).unwrap();
+        let names: Vec<&str> = config.devices[0]
+            .registers
+            .iter()
+            .map(|r| r.name.as_str())
+            .collect();
+        assert_eq!(names, ["voltage_l1", "current_l1", "status"]);
+        assert_eq!(config.devices[0].registers[1].scale, Some(0.5));
+        assert_eq!(config.devices[0].registers[0].unit.as_deref(), Some("V"));
+
+        // Loading the written-back configuration gives the same registers
+        let mut again = config.clone();
+        resolve(&mut again).unwrap();
+        assert_eq!(
+            serde_json::to_value(&again).unwrap(),
+            serde_json::to_value(&config).unwrap()
+        );
+
+        let missing = yaml.replace("eastron/sdm630", "eastron/sdm120");
+        let error = crate::config::load_config_from_str(&missing).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("profile 'eastron/sdm120' not found"));
+        let escape = yaml.replace("eastron/sdm630", "../etc/passwd");
+        assert!(crate::config::load_config_from_str(&escape).is_err());
+
+        // A git source that would pass for an option is not cloned
+        let option = yaml.replace(
+            &format!("paths: [{}]", dir.display()),
+            &format!(
+                "git: [{{ url: --upload-pack=false }}], cache_dir: {}",
+                dir.display()
+            ),
+        );
+        let error = crate::config::load_config_from_str(&option).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("profiles.git url '--upload-pack=false' must not start with '-'"));
+    }
+}
@@ -0,0 +1,354 @@
+//! Recipes: named sequences of register writes run as a unit
+//!
+//! A recipe (see [`RecipeConfig`]) writes its steps in order, across any
+//! number of devices, through the same path as API writes, so write limits,
+//! the write switch, rate limits and the audit log apply to every step. Its
+//! `require` interlocks are checked together against the register store
+//! before every step, since an earlier step may break them; if one is not
+//! met before the first step, nothing is written. Each written value is read
+//! back from the device and compared with the step's value; the first step
+//! that fails or reads back something else aborts the recipe and the
+//! remaining steps are skipped. Steps already written are not undone.
+//!
+//! Recipes run one at a time, so two changeovers never interleave. They are
+//! started with `POST /api/recipes/{name}` or the `run_recipe` MQTT RPC method.
+
+use serde::Serialize;
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot, Mutex};
+use tracing::{info, warn};
+
+use crate::api::{WriteError, WriteRequest};
+use crate::audit::WriteSource;
+use crate::config::{RecipeConfig, RecipeStep};
+use crate::modbus::reader::{self, RegisterStore};
+
+/// Time a single step may take, like an API write
+const STEP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Relative difference a read-back value may have without a `tolerance`,
+/// enough for values stored as `f32` or scaled integers
+const ROUNDING: f64 = 1e-6;
+
+/// Held while a recipe runs
+static RUNNING: Mutex<()> = Mutex::const_new(());
+
+/// How a step of a recipe went
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StepStatus {
+    /// Written and, where the device allows it, verified
+    Ok,
+    /// Refused, not written or read back with a different value
+    Failed,
+    /// Not attempted after an earlier step failed
+    Skipped,
+}
+
+/// Result of one step
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StepResult {
+    pub device_id: String,
+    pub register: String,
+    pub value: f64,
+    pub status: StepStatus,
+    /// Value the device reported after the write
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub read_back: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Result of running a recipe
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RecipeOutcome {
+    pub recipe: String,
+    /// Whether every step was written and verified
+    pub success: bool,
+    /// Why no step was written, e.g. an interlock not met
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub steps: Vec<StepResult>,
+}
+
+/// Run the steps of `recipe` in order, stopping at the first failure
+pub async fn run(
+    write_tx: &mpsc::Sender<WriteRequest>,
+    store: &RegisterStore,
+    recipe: &RecipeConfig,
+    source: WriteSource,
+) -> RecipeOutcome {
+    let _running = RUNNING.lock().await;
+    info!("Running recipe {}", recipe.name);
+
+    let mut steps = Vec::with_capacity(recipe.steps.len());
+    let mut error = None;
+    let mut failed = false;
+    for step in &recipe.steps {
+        let mut result = StepResult {
+            device_id: step.device_id.clone(),
+            register: step.register.clone(),
+            value: step.value,
+            status: StepStatus::Skipped,
+            read_back: None,
+            error: None,
+        };
+        if !failed {
+            // Recipe interlocks always name their device; an earlier step
+            // may have broken them
+            let unmet = reader::unmet_interlock(&recipe.require, "", &*store.read().await);
+            match unmet {
+                Some(unmet) if steps.is_empty() => {
+                    warn!("Recipe {} not run: {}", recipe.name, unmet);
+                    error = Some(unmet);
+                    failed = true;
+                }
+                Some(unmet) => {
+                    result.status = StepStatus::Failed;
+                    result.error = Some(unmet);
+                }
+                None => write_step(write_tx, step, source.clone(), &mut result).await,
+            }
+            if result.status == StepStatus::Failed {
+                failed = true;
+                warn!(
+                    "Recipe {} aborted at {}:{}: {}",
+                    recipe.name,
+                    step.device_id,
+                    step.register,
+                    result.error.as_deref().unwrap_or_default()
+                );
+            }
+        }
+        steps.push(result);
+    }
+
+    if !failed {
+        info!("Recipe {} completed", recipe.name);
+    }
+    RecipeOutcome {
+        recipe: recipe.name.clone(),
+        success: !failed,
+        error,
+        steps,
+    }
+}
+
+/// Write `step` and verify the value read back
+async fn write_step(
+    write_tx: &mpsc::Sender<WriteRequest>,
+    step: &RecipeStep,
+    source: WriteSource,
+    result: &mut StepResult,
+) {
+    match write(
+        write_tx,
+        &step.device_id,
+        &step.register,
+        step.value,
+        source,
+    )
+    .await
+    {
+        Ok(read_back) => {
+            result.read_back = read_back;
+            match read_back.filter(|read_back| !matches(step, *read_back)) {
+                Some(read_back) => {
+                    result.status = StepStatus::Failed;
+                    result.error =
+                        Some(format!("Read back {}, expected {}", read_back, step.value));
+                }
+                None => result.status = StepStatus::Ok,
+            }
+        }
+        Err(e) => {
+            result.status = StepStatus::Failed;
+            result.error = Some(e.to_string());
+        }
+    }
+}
+
+/// Send a write to the write handler and wait for it like an API write,
+/// returning the value read back
+pub(crate) async fn write(
+    write_tx: &mpsc::Sender<WriteRequest>,
+    device_id: &str,
+    register: &str,
+    value: f64,
+    source: WriteSource,
+) -> Result<Option<f64>, WriteError> {
+    let (response_tx, response_rx) = oneshot::channel();
+    let request = WriteRequest {
+        device_id: device_id.to_string(),
+        register_name: register.to_string(),
+        source,
+        value,
+        response_tx,
+    };
+    write_tx
+        .send(request)
+        .await
+        .map_err(|_| WriteError::Failed("The Modbus write handler is not running".to_string()))?;
+    match tokio::time::timeout(STEP_TIMEOUT, response_rx).await {
+        Ok(Ok(response)) => response.map(|outcome| outcome.read_back),
+        Ok(Err(_)) => Err(WriteError::Failed(
+            "Response channel closed unexpectedly".to_string(),
+        )),
+        Err(_) => Err(WriteError::Failed(
+            "The Modbus device did not respond in time".to_string(),
+        )),
+    }
+}
+
+/// Whether a value read back confirms the step
+fn matches(step: &RecipeStep, read_back: f64) -> bool {
+    let tolerance = step
+        .tolerance
+        .unwrap_or_else(|| step.value.abs().max(1.0) * ROUNDING);
+    (read_back - step.value).abs() <= tolerance
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::WriteOutcome;
+
+    #[tokio::test]
+    async fn test_run_recipe() {
+        let recipe: RecipeConfig = serde_yaml::from_str(
+            r#"
+name: product-b
+steps:
+  - { device_id: press, register: pressure, value: 180 }
+  - { device_id: oven, register: temperature, value: 215.5, tolerance: 0.5 }
+  - { device_id: oven, register: fan, value: 1 }
+"#,
+        )
+        .unwrap();
+        // The oven reads back 215.2 and rejects the fan
+        let (write_tx, mut write_rx) = mpsc::channel::<WriteRequest>(4);
+        tokio::spawn(async move {
+            while let Some(request) = write_rx.recv().await {
+                let response = match request.register_name.as_str() {
+                    "fan" => Err(WriteError::Rejected("Register 'fan' is read-only".into())),
+                    register => Ok(WriteOutcome {
+                        address: 0,
+                        raw: vec![],
+                        read_back: Some(if register == "temperature" {
+                            215.2
+                        } else {
+                            request.value
+                        }),
+                    }),
+                };
+                let _ = request.response_tx.send(response);
+            }
+        });
+        let source = WriteSource::api(None);
+        let store = RegisterStore::default();
+
+        let outcome = run(&write_tx, &store, &recipe, source.clone()).await;
+        let statuses: Vec<StepStatus> = outcome.steps.iter().map(|s| s.status).collect();
+        assert_eq!(
+            statuses,
+            [StepStatus::Ok, StepStatus::Ok, StepStatus::Failed]
+        );
+        assert!(!outcome.success);
+        assert_eq!(outcome.steps[1].read_back, Some(215.2));
+        assert_eq!(
+            outcome.steps[2].error.as_deref(),
+            Some("Register 'fan' is read-only")
+        );
+
+        // Without the tolerance the read-back fails the step, the rest is skipped
+        let mut strict = recipe.clone();
+        strict.steps[1].tolerance = None;
+        let outcome = run(&write_tx, &store, &strict, source.clone()).await;
+        assert_eq!(outcome.steps[1].status, StepStatus::Failed);
+        assert_eq!(
+            outcome.steps[1].error.as_deref(),
+            Some("Read back 215.2, expected 215.5")
+        );
+        assert_eq!(outcome.steps[2].status, StepStatus::Skipped);
+        assert!(serde_json::to_value(&outcome.steps[2])
+            .unwrap()
+            .get("error")
+            .is_none());
+
+        // An interlock on a register without a value keeps every step from running
+        let mut interlocked = recipe.clone();
+        interlocked.require = vec![crate::config::Interlock {
+            device_id: Some("press".to_string()),
+            register: "running".to_string(),
+            ..Default::default()
+        }];
+        let outcome = run(&write_tx, &store, &interlocked, source).await;
+        assert!(!outcome.success);
+        assert_eq!(
+            outcome.error.as_deref(),
+            Some("Interlock not met: press:running has no value")
+        );
+        assert!(outcome
+            .steps
+            .iter()
+            .all(|step| step.status == StepStatus::Skipped));
+    }
+
+    #[tokio::test]
+    async fn test_recipe_interlock_broken_by_step() {
+        let recipe: RecipeConfig = serde_yaml::from_str(
+            r#"
+name: start-line
+require:
+  - { device_id: line, register: mode, equals: 0 }
+steps:
+  - { device_id: line, register: mode, value: 1 }
+  - { device_id: press, register: pressure, value: 180 }
+"#,
+        )
+        .unwrap();
+        let store = RegisterStore::default();
+        let set_mode = |store: RegisterStore, value: f64| async move {
+            let mode = crate::modbus::reader::RegisterValue {
+                name: "mode".into(),
+                raw: vec![value as u16],
+                value,
+                quality: Default::default(),
+                restored: false,
+                unit: None,
+                timestamp: chrono::Utc::now(),
+                meta: Default::default(),
+            };
+            let mut store = store.write().await;
+            let line = store.entry("line".to_string()).or_default();
+            line.insert("mode".to_string(), mode);
+        };
+        set_mode(store.clone(), 0.0).await;
+
+        // The device reports every write back to the store, like a poll
+        let (write_tx, mut write_rx) = mpsc::channel::<WriteRequest>(4);
+        let device_store = store.clone();
+        tokio::spawn(async move {
+            while let Some(request) = write_rx.recv().await {
+                if request.register_name == "mode" {
+                    set_mode(device_store.clone(), request.value).await;
+                }
+                let _ = request.response_tx.send(Ok(WriteOutcome {
+                    address: 0,
+                    raw: vec![],
+                    read_back: Some(request.value),
+                }));
+            }
+        });
+
+        let outcome = run(&write_tx, &store, &recipe, WriteSource::api(None)).await;
+        assert!(!outcome.success);
+        assert_eq!(outcome.error, None);
+        assert_eq!(outcome.steps[0].status, StepStatus::Ok);
+        assert_eq!(outcome.steps[1].status, StepStatus::Failed);
+        assert_eq!(
+            outcome.steps[1].error.as_deref(),
+            Some("Interlock not met: line:mode is 1")
+        );
+    }
+}
@@ -0,0 +1,299 @@
+//! Configuration reload
+//!
+//! `POST /api/config/reload` re-reads the configuration file and validates
+//! it. A valid file is applied in one step by the bridge: polling tasks of
+//! added, removed and changed devices are started or restarted, while
+//! unchanged devices keep polling undisturbed. An invalid file is rejected and
+//! the running configuration stays in place. Sections other than `devices`
+//! only take effect after a restart and are reported as such.
+//!
+//! `PUT /api/config` applies an edited configuration the same way after
+//! writing it to the file. The previous file is kept next to it with a `.bak`
+//! suffix, which `POST /api/config/rollback` puts back.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::Path;
+
+use crate::config::{Config, DeviceConfig};
+use crate::error::{Result, RustbridgeError};
+
+/// Request to reload the configuration, answered with what changed
+#[derive(Debug)]
+pub struct ReloadRequest {
+    pub action: ReloadAction,
+    pub response_tx: tokio::sync::oneshot::Sender<Result<ReloadReport>>,
+}
+
+/// Where the configuration to apply comes from
+#[derive(Debug, Clone, Default)]
+pub enum ReloadAction {
+    /// Re-read the configuration file
+    #[default]
+    Reload,
+    /// Write this configuration to the file, keeping a backup, then apply it
+    Apply(Box<Config>),
+    /// Restore the backup of the configuration file, then apply it
+    Rollback,
+}
+
+/// Path of the backup kept when the configuration file is overwritten
+pub fn backup_path(path: &str) -> String {
+    format!("{}.bak", path)
+}
+
+/// Write `config` to `path` as YAML, copying the current file to its backup first
+///
+/// The file is replaced atomically; comments and formatting of the old file
+/// are not preserved (the backup keeps them).
+pub fn write_config(path: &str, config: &Config) -> Result<()> {
+    let io_error = |e: std::io::Error| {
+        RustbridgeError::Config(format!("Failed to write config file {}: {}", path, e))
+    };
+    let yaml = serde_yaml::to_string(config)
+        .map_err(|e| RustbridgeError::Config(format!("Failed to serialize config: {}", e)))?;
+
+    if Path::new(path).exists() {
+        std::fs::copy(path, backup_path(path)).map_err(io_error)?;
+    }
+    let tmp = format!("{}.tmp", path);
+    std::fs::write(&tmp, yaml).map_err(io_error)?;
+    std::fs::rename(&tmp, path).map_err(io_error)
+}
+
+/// Copy the backup made by [`write_config`] back over the configuration file
+pub fn restore_backup(path: &str) -> Result<()> {
+    let backup = backup_path(path);
+    if !Path::new(&backup).exists() {
+        return Err(RustbridgeError::Config(format!(
+            "No backup of {} to roll back to",
+            path
+        )));
+    }
+    std::fs::copy(&backup, path).map(|_| ()).map_err(|e| {
+        RustbridgeError::Config(format!("Failed to restore {} from {}: {}", path, backup, e))
+    })
+}
+
+/// Changes applied by a configuration reload
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReloadReport {
+    /// Devices that started polling
+    pub devices_added: Vec<String>,
+    /// Devices that stopped polling
+    pub devices_removed: Vec<String>,
+    /// Devices whose polling task was restarted with new settings
+    pub devices_changed: Vec<DeviceChange>,
+    /// Changed sections that only take effect after a restart
+    pub restart_required: Vec<String>,
+}
+
+/// Differences in a single device
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeviceChange {
+    pub device_id: String,
+    pub registers_added: Vec<String>,
+    pub registers_removed: Vec<String>,
+    pub registers_changed: Vec<String>,
+    /// Connection, interval or other device-level settings changed
+    pub settings_changed: bool,
+}
+
+impl ReloadReport {
+    /// Compare the running configuration with a newly loaded one
+    pub fn diff(old: &Config, new: &Config) -> Self {
+        let old_ids: HashSet<&str> = old.devices.iter().map(|d| d.id.as_str()).collect();
+        let new_ids: HashSet<&str> = new.devices.iter().map(|d| d.id.as_str()).collect();
+
+        let devices_changed = new
+            .devices
+            .iter()
+            .filter_map(|device| {
+                let previous = old.devices.iter().find(|d| d.id == device.id)?;
+                device_change(previous, device)
+            })
+            .collect();
+
+        let sections = [
+            ("server", same(&old.server, &new.server)),
+            ("mqtt", same(&old.mqtt, &new.mqtt)),
+            ("auth", same(&old.auth, &new.auth)),
+            ("logging", same(&old.logging, &new.logging)),
+            ("audit", same(&old.audit, &new.audit)),
+            ("systemd", same(&old.systemd, &new.systemd)),
+            ("daemon", same(&old.daemon, &new.daemon)),
+            ("writes", same(&old.writes, &new.writes)),
+            (
+                "remote_config",
+                same(&old.remote_config, &new.remote_config),
+            ),
+            ("update", same(&old.update, &new.update)),
+        ];
+
+        Self {
+            devices_added: new
+                .devices
+                .iter()
+                .filter(|d| !old_ids.contains(d.id.as_str()))
+                .map(|d| d.id.clone())
+                .collect(),
+            devices_removed: old
+                .devices
+                .iter()
+                .filter(|d| !new_ids.contains(d.id.as_str()))
+                .map(|d| d.id.clone())
+                .collect(),
+            devices_changed,
+            restart_required: sections
+                .iter()
+                .filter(|(_, unchanged)| !unchanged)
+                .map(|(name, _)| name.to_string())
+                .collect(),
+        }
+    }
+
+    /// Whether the reload changed nothing
+    pub fn is_empty(&self) -> bool {
+        self.devices_added.is_empty()
+            && self.devices_removed.is_empty()
+            && self.devices_changed.is_empty()
+            && self.restart_required.is_empty()
+    }
+}
+
+/// Differences between two versions of a device, if any
+fn device_change(old: &DeviceConfig, new: &DeviceConfig) -> Option<DeviceChange> {
+    if same(old, new) {
+        return None;
+    }
+
+    let mut change = DeviceChange {
+        device_id: new.id.clone(),
+        ..Default::default()
+    };
+    for register in &new.registers {
+        match old.registers.iter().find(|r| r.name == register.name) {
+            None => change.registers_added.push(register.name.clone()),
+            Some(previous) if !same(previous, register) => {
+                change.registers_changed.push(register.name.clone())
+            }
+            Some(_) => {}
+        }
+    }
+    change.registers_removed = old
+        .registers
+        .iter()
+        .filter(|r| !new.registers.iter().any(|n| n.name == r.name))
+        .map(|r| r.name.clone())
+        .collect();
+
+    // Compare everything but the registers
+    let settings = |device: &DeviceConfig| DeviceConfig {
+        registers: vec![],
+        ..device.clone()
+    };
+    change.settings_changed = !same(&settings(old), &settings(new));
+    Some(change)
+}
+
+/// Compare two configuration values by their serialized form
+fn same<T: Serialize>(a: &T, b: &T) -> bool {
+    serde_json::to_value(a).ok() == serde_json::to_value(b).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::load_config_from_str;
+
+    const BASE: &str = r#"
+server:
+  host: "0.0.0.0"
+  port: 3000
+  metrics_enabled: true
+devices:
+  - id: "plc-001"
+    name: "PLC"
+    device_type: tcp
+    connection: { host: "127.0.0.1", port: 502, unit_id: 1 }
+    poll_interval_ms: 1000
+    registers:
+      - { name: temperature, address: 0, register_type: holding, count: 1, data_type: u16 }
+      - { name: pressure, address: 1, register_type: holding, count: 1, data_type: u16 }
+  - id: "meter"
+    name: "Meter"
+    device_type: tcp
+    connection: { host: "127.0.0.1", port: 502, unit_id: 2 }
+    poll_interval_ms: 1000
+    registers: []
+"#;
+
+    #[test]
+    fn test_diff_unchanged() {
+        let config = load_config_from_str(BASE).unwrap();
+        assert!(ReloadReport::diff(&config, &config.clone()).is_empty());
+    }
+
+    #[test]
+    fn test_diff_devices_and_registers() {
+        let old = load_config_from_str(BASE).unwrap();
+        let new = load_config_from_str(
+            &BASE
+                .replace("port: 3000", "port: 3001")
+                .replace("id: \"meter\"", "id: \"meter-2\"")
+                .replace(
+                    "{ name: pressure, address: 1,",
+                    "{ name: level, address: 5,",
+                )
+                .replace(
+                    "{ name: temperature, address: 0,",
+                    "{ name: temperature, address: 2,",
+                ),
+        )
+        .unwrap();
+
+        let report = ReloadReport::diff(&old, &new);
+        assert_eq!(report.devices_added, vec!["meter-2"]);
+        assert_eq!(report.devices_removed, vec!["meter"]);
+        assert_eq!(
+            report.devices_changed,
+            vec![DeviceChange {
+                device_id: "plc-001".to_string(),
+                registers_added: vec!["level".to_string()],
+                registers_removed: vec!["pressure".to_string()],
+                registers_changed: vec!["temperature".to_string()],
+                settings_changed: false,
+            }]
+        );
+        assert_eq!(report.restart_required, vec!["server"]);
+    }
+
+    #[test]
+    fn test_write_config_and_restore_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.yaml");
+        let path = path.to_str().unwrap();
+        assert!(restore_backup(path).is_err());
+
+        std::fs::write(path, BASE).unwrap();
+        let old = load_config_from_str(BASE).unwrap();
+        let mut new = old.clone();
+        new.devices.pop();
+        write_config(path, &new).unwrap();
+
+        // The written file reads back as the same configuration
+        let written = crate::config::load_config(path).unwrap();
+        assert!(ReloadReport::diff(&new, &written).is_empty());
+        assert_eq!(std::fs::read_to_string(backup_path(path)).unwrap(), BASE);
+
+        restore_backup(path).unwrap();
+        assert_eq!(std::fs::read_to_string(path).unwrap(), BASE);
+    }
+
+    #[test]
+    fn test_example_config_round_trip() {
+        let example = load_config_from_str(include_str!("../config.yaml")).unwrap();
+        let yaml = serde_yaml::to_string(&example).unwrap();
+        assert!(same(&example, &load_config_from_str(&yaml).unwrap()));
+    }
+}
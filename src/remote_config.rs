@@ -0,0 +1,273 @@
+//! Configuration pulled from a fleet management server
+//!
+//! With `remote_config`, the bridge fetches its configuration from a URL
+//! every `poll_interval_secs` and on `POST /api/config/pull`, which a fleet
+//! server can call as a webhook after publishing a new version. Requests carry
+//! the `ETag` of the last response in `If-None-Match`, so an unchanged
+//! configuration costs a `304`.
+//!
+//! A new configuration is validated and applied through the reload path: it
+//! is written to the configuration file (keeping the previous one as `.bak`)
+//! and device changes take effect without a restart. A configuration equal to
+//! the running one is not applied, so the backup is not overwritten. A pulled
+//! configuration without a `remote_config` section keeps the running one, so
+//! a gateway does not lose its way back to the server.
+//!
+//! The URL must be `https://`: whoever can change the configuration in
+//! transit controls every write of the bridge.
+
+use serde::Serialize;
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot, watch};
+use tracing::{debug, info, warn};
+
+use crate::build_info::config_hash;
+use crate::config::{Config, RemoteConfig};
+use crate::error::{Result, RustbridgeError};
+use crate::fetch::{Fetcher, RemoteUrl};
+use crate::reload::{ReloadAction, ReloadReport, ReloadRequest};
+
+/// Largest configuration accepted from the server
+const MAX_CONFIG_BYTES: u64 = 4 * 1024 * 1024;
+
+/// Request to check the server now, answered with what happened
+pub type PullRequest = oneshot::Sender<Result<PullOutcome>>;
+
+/// Result of checking the server for a new configuration
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "status", rename_all = "lowercase")]
+pub enum PullOutcome {
+    /// The server has no newer configuration than the running one
+    Unchanged,
+    /// A new configuration was applied
+    Applied(ReloadReport),
+}
+
+/// Checks the server and applies new configurations
+pub struct ConfigPuller {
+    remote: RemoteConfig,
+    fetcher: Fetcher,
+    /// `ETag` of the last configuration received
+    etag: Option<String>,
+}
+
+impl ConfigPuller {
+    pub fn new(remote: RemoteConfig) -> Result<Self> {
+        if !RemoteUrl::parse(&remote.url).is_ok_and(|url| url.tls) {
+            return Err(RustbridgeError::Config(
+                "remote_config.url must be an https:// url".to_string(),
+            ));
+        }
+        let fetcher = Fetcher::new(
+            &remote.url,
+            remote.authorization.as_deref(),
+            remote.ca_cert.as_deref(),
+            Duration::from_secs(remote.timeout_secs),
+        )
+        .map_err(|e| RustbridgeError::Config(format!("remote_config: {}", e)))?;
+        Ok(Self {
+            remote,
+            fetcher,
+            etag: None,
+        })
+    }
+
+    /// Check the server every `poll_interval_secs` and whenever asked to
+    pub async fn run(
+        mut self,
+        reload_tx: mpsc::Sender<ReloadRequest>,
+        config_rx: watch::Receiver<Config>,
+        mut pull_rx: mpsc::Receiver<PullRequest>,
+    ) {
+        info!("Pulling configuration from {}", self.remote.url);
+        let every = Duration::from_secs(self.remote.poll_interval_secs);
+        let mut ticker = tokio::time::interval(every.max(Duration::from_secs(1)));
+        loop {
+            let response_tx = tokio::select! {
+                _ = ticker.tick(), if !every.is_zero() => None,
+                request = pull_rx.recv() => match request {
+                    Some(response_tx) => Some(response_tx),
+                    None => break,
+                },
+            };
+            let result = self.pull(&reload_tx, &config_rx).await;
+            match &result {
+                Ok(PullOutcome::Unchanged) => debug!("Remote configuration unchanged"),
+                Ok(PullOutcome::Applied(_)) => {
+                    info!("Applied configuration from {}", self.remote.url)
+                }
+                Err(e) => warn!("Configuration pull from {} failed: {}", self.remote.url, e),
+            }
+            if let Some(response_tx) = response_tx {
+                let _ = response_tx.send(result);
+            }
+        }
+    }
+
+    /// Fetch the configuration and apply it if it differs from the running one
+    async fn pull(
+        &mut self,
+        reload_tx: &mpsc::Sender<ReloadRequest>,
+        config_rx: &watch::Receiver<Config>,
+    ) -> Result<PullOutcome> {
+        let mut headers = vec![("Accept", "application/yaml, text/yaml, */*")];
+        if let Some(etag) = &self.etag {
+            headers.push(("If-None-Match", etag));
+        }
+        let (response, body) = self.fetcher.get(&headers, MAX_CONFIG_BYTES).await?;
+        match response.status {
+            200 => {}
+            304 => return Ok(PullOutcome::Unchanged),
+            status => {
+                return Err(RustbridgeError::Connection(format!(
+                    "configuration server returned {}",
+                    status
+                )))
+            }
+        }
+        // A rejected configuration is not fetched again until it changes
+        self.etag = response.header("ETag").map(str::to_string);
+
+        let yaml = String::from_utf8(body)
+            .map_err(|_| RustbridgeError::Config("configuration is not UTF-8".to_string()))?;
+        // Resolving may clone device profiles from git
+        let mut config =
+            tokio::task::spawn_blocking(move || crate::config::load_config_from_str(&yaml))
+                .await
+                .map_err(|e| RustbridgeError::Config(e.to_string()))??;
+        if config.remote_config.is_none() {
+            config.remote_config = Some(self.remote.clone());
+        }
+        config.validate()?;
+        if config_hash(&config) == config_hash(&config_rx.borrow()) {
+            return Ok(PullOutcome::Unchanged);
+        }
+
+        let unavailable =
+            || RustbridgeError::Config("the bridge is not accepting reload requests".to_string());
+        let (response_tx, response_rx) = oneshot::channel();
+        reload_tx
+            .send(ReloadRequest {
+                action: ReloadAction::Apply(Box::new(config)),
+                response_tx,
+            })
+            .await
+            .map_err(|_| unavailable())?;
+        let report = response_rx.await.map_err(|_| unavailable())??;
+        Ok(PullOutcome::Applied(report))
+    }
+}
+
+// Pulling needs an https:// url
+#[cfg(all(test, feature = "tls"))]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// Serve `config` with an ETag, answering 304 to a matching `If-None-Match`
+    async fn serve(config: &'static str) -> (u16, mpsc::UnboundedReceiver<String>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (requests_tx, requests_rx) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut request = Vec::new();
+                let mut buf = [0u8; 1024];
+                while !request.ends_with(b"\r\n\r\n") {
+                    let n = stream.read(&mut buf).await.unwrap();
+                    if n == 0 {
+                        break;
+                    }
+                    request.extend_from_slice(&buf[..n]);
+                }
+                let request = String::from_utf8(request).unwrap();
+                let response = if request.contains("If-None-Match: \"v1\"") {
+                    "HTTP/1.0 304 Not Modified\r\n\r\n".to_string()
+                } else {
+                    format!("HTTP/1.0 200 OK\r\nETag: \"v1\"\r\n\r\n{}", config)
+                };
+                let _ = requests_tx.send(request);
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        (port, requests_rx)
+    }
+
+    #[tokio::test]
+    async fn test_pull_applies_new_config() {
+        let (port, mut requests) = serve(
+            r#"
+server: { host: "127.0.0.1", port: 3000, metrics_enabled: false }
+devices:
+  - id: plc-001
+    name: PLC
+    device_type: tcp
+    poll_interval_ms: 1000
+    connection: { host: 10.0.0.1, port: 502, unit_id: 1 }
+    registers:
+      - { name: level, address: 0, register_type: input, count: 1, data_type: u16 }
+"#,
+        )
+        .await;
+        let remote: RemoteConfig = serde_yaml::from_str(
+            "{ url: 'https://fleet.example.com/gw.yaml', authorization: Bearer abc }",
+        )
+        .unwrap();
+        let mut puller = ConfigPuller::new(remote.clone()).unwrap();
+        // The test server speaks plain HTTP
+        let url = format!("http://127.0.0.1:{}/gw.yaml", port);
+        puller.fetcher =
+            Fetcher::new(&url, Some("Bearer abc"), None, Duration::from_secs(5)).unwrap();
+        let plain = RemoteConfig {
+            url,
+            ..remote.clone()
+        };
+        assert!(ConfigPuller::new(plain).is_err());
+
+        let (config_tx, config_rx) = watch::channel(Config::default());
+        let (reload_tx, mut reload_rx) = mpsc::channel::<ReloadRequest>(1);
+        tokio::spawn(async move {
+            while let Some(request) = reload_rx.recv().await {
+                let ReloadAction::Apply(config) = request.action else {
+                    panic!("expected a configuration to apply");
+                };
+                let report = ReloadReport {
+                    devices_added: config.devices.iter().map(|d| d.id.clone()).collect(),
+                    ..Default::default()
+                };
+                config_tx.send_replace(*config);
+                let _ = request.response_tx.send(Ok(report));
+            }
+        });
+
+        let outcome = puller.pull(&reload_tx, &config_rx).await.unwrap();
+        let PullOutcome::Applied(report) = outcome else {
+            panic!("expected the configuration to be applied");
+        };
+        assert_eq!(report.devices_added, ["plc-001"]);
+        // The pulled file has no remote_config; the running one is kept
+        assert_eq!(config_rx.borrow().remote_config, Some(remote));
+        let first = requests.recv().await.unwrap();
+        assert!(first.starts_with("GET /gw.yaml HTTP/1.0\r\n"));
+        assert!(first.contains("Authorization: Bearer abc\r\n"));
+        assert!(!first.contains("If-None-Match"));
+
+        // The next check is answered with 304
+        assert_eq!(
+            puller.pull(&reload_tx, &config_rx).await.unwrap(),
+            PullOutcome::Unchanged
+        );
+        assert!(requests
+            .recv()
+            .await
+            .unwrap()
+            .contains("If-None-Match: \"v1\""));
+
+        // Without an ETag match the same configuration is not applied again
+        puller.etag = None;
+        assert_eq!(
+            puller.pull(&reload_tx, &config_rx).await.unwrap(),
+            PullOutcome::Unchanged
+        );
+    }
+}
@@ -0,0 +1,321 @@
+//! systemd service notification
+//!
+//! Implements the `sd_notify` protocol for `Type=notify` units: `READY=1`
+//! once all devices have connected (or the startup grace period expires),
+//! `STATUS=` lines with device counts, and `WATCHDOG=1` keepalives that stop
+//! when a device poll loop stalls so systemd restarts a wedged bridge.
+//! Everything is a no-op when `NOTIFY_SOCKET` is not set.
+
+use anyhow::Result;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+use tracing::{debug, warn};
+
+use crate::config::{Config, SystemdConfig};
+use crate::events::{ConnectionEvent, ConnectionEventKind};
+
+/// How often the status line is refreshed when no watchdog is configured
+const STATUS_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Send a notification to the service manager.
+///
+/// Returns `Ok(false)` when not running under systemd (`NOTIFY_SOCKET` unset).
+pub fn notify(state: &str) -> Result<bool> {
+    let Some(socket) = std::env::var_os("NOTIFY_SOCKET") else {
+        return Ok(false);
+    };
+    send(&socket.to_string_lossy(), state)?;
+    Ok(true)
+}
+
+#[cfg(unix)]
+fn send(socket: &str, state: &str) -> Result<()> {
+    use std::os::unix::net::UnixDatagram;
+
+    let datagram = UnixDatagram::unbound()?;
+    match socket.strip_prefix('@') {
+        #[cfg(target_os = "linux")]
+        Some(name) => {
+            use std::os::linux::net::SocketAddrExt;
+            let addr = std::os::unix::net::SocketAddr::from_abstract_name(name)?;
+            datagram.send_to_addr(state.as_bytes(), &addr)?;
+        }
+        #[cfg(not(target_os = "linux"))]
+        Some(_) => anyhow::bail!("Abstract notify sockets are only supported on Linux"),
+        None => {
+            datagram.send_to(state.as_bytes(), socket)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn send(_socket: &str, _state: &str) -> Result<()> {
+    anyhow::bail!("sd_notify is only supported on Unix")
+}
+
+/// Watchdog interval requested by systemd (`WatchdogSec=`), if any
+pub fn watchdog_interval() -> Option<Duration> {
+    parse_watchdog(
+        std::env::var("WATCHDOG_USEC").ok().as_deref(),
+        std::env::var("WATCHDOG_PID").ok().as_deref(),
+        std::process::id(),
+    )
+}
+
+fn parse_watchdog(usec: Option<&str>, pid: Option<&str>, own_pid: u32) -> Option<Duration> {
+    // WATCHDOG_PID, when set, must name this process
+    if let Some(pid) = pid {
+        if pid.parse::<u32>().ok()? != own_pid {
+            return None;
+        }
+    }
+    let usec: u64 = usec?.parse().ok()?;
+    (usec > 0).then(|| Duration::from_micros(usec))
+}
+
+/// Last poll loop tick per device, used to detect wedged poll loops
+#[derive(Clone, Default)]
+pub struct Heartbeats {
+    inner: Arc<Mutex<HashMap<String, Instant>>>,
+}
+
+impl Heartbeats {
+    /// Record that a device's poll loop is alive
+    pub fn beat(&self, device_id: &str) {
+        if let Ok(mut beats) = self.inner.lock() {
+            beats.insert(device_id.to_string(), Instant::now());
+        }
+    }
+
+    /// Stop tracking a device that is no longer polled
+    pub fn forget(&self, device_id: &str) {
+        if let Ok(mut beats) = self.inner.lock() {
+            beats.remove(device_id);
+        }
+    }
+
+    /// Devices whose poll loop has not ticked within their allowed age
+    pub fn stalled(&self, max_age: &HashMap<String, Duration>, now: Instant) -> Vec<String> {
+        let Ok(beats) = self.inner.lock() else {
+            return vec![];
+        };
+        let mut stalled: Vec<String> = beats
+            .iter()
+            .filter(|(id, last)| {
+                max_age
+                    .get(id.as_str())
+                    .is_some_and(|age| now.duration_since(**last) > *age)
+            })
+            .map(|(id, _)| id.clone())
+            .collect();
+        stalled.sort();
+        stalled
+    }
+}
+
+/// Readiness tracking from connection events
+struct Readiness {
+    devices_total: usize,
+    connected: HashSet<String>,
+    ready: bool,
+}
+
+impl Readiness {
+    fn new(devices_total: usize) -> Self {
+        Self {
+            devices_total,
+            connected: HashSet::new(),
+            ready: false,
+        }
+    }
+
+    fn apply(&mut self, event: &ConnectionEvent) {
+        match event.event {
+            ConnectionEventKind::Connected => {
+                self.connected.insert(event.device_id.clone());
+            }
+            ConnectionEventKind::Disconnected | ConnectionEventKind::SerialPortError => {
+                self.connected.remove(&event.device_id);
+            }
+            // Reconnect attempts and events that aren't about the connection
+            _ => {}
+        }
+    }
+
+    fn all_connected(&self) -> bool {
+        self.connected.len() >= self.devices_total
+    }
+
+    fn status(&self, stalled: &[String]) -> String {
+        let mut status = format!(
+            "STATUS={}/{} devices connected",
+            self.connected.len(),
+            self.devices_total
+        );
+        if !self.ready {
+            status.push_str(", starting");
+        }
+        if !stalled.is_empty() {
+            status.push_str(&format!(", poll loop stalled: {}", stalled.join(", ")));
+        }
+        status
+    }
+}
+
+/// Drive readiness, status and watchdog notifications until the process exits
+pub async fn run_notifier(
+    config: &Config,
+    mut events: broadcast::Receiver<ConnectionEvent>,
+    heartbeats: Heartbeats,
+) {
+    if std::env::var_os("NOTIFY_SOCKET").is_none() {
+        return;
+    }
+
+    let SystemdConfig {
+        startup_grace_secs,
+        stall_timeout_secs,
+    } = config.systemd;
+    let watchdog = watchdog_interval();
+    let max_age: HashMap<String, Duration> = config
+        .devices
+        .iter()
+        .map(|d| {
+            (
+                d.id.clone(),
+                Duration::from_millis(d.poll_interval_ms) + Duration::from_secs(stall_timeout_secs),
+            )
+        })
+        .collect();
+
+    let started = Instant::now();
+    let grace = Duration::from_secs(startup_grace_secs);
+    let mut readiness = Readiness::new(config.devices.len());
+    let mut tick = tokio::time::interval(watchdog.map_or(STATUS_INTERVAL, |w| w / 2));
+    let mut last_status = String::new();
+
+    loop {
+        tokio::select! {
+            event = events.recv() => match event {
+                Ok(event) => readiness.apply(&event),
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return,
+            },
+            _ = tick.tick() => {}
+        }
+
+        let now = Instant::now();
+        let stalled = heartbeats.stalled(&max_age, now);
+
+        if !readiness.ready && (readiness.all_connected() || now - started >= grace) {
+            readiness.ready = true;
+            send_state("READY=1");
+        }
+
+        let status = readiness.status(&stalled);
+        if status != last_status {
+            send_state(&status);
+            last_status = status;
+        }
+
+        if watchdog.is_some() {
+            if stalled.is_empty() {
+                send_state("WATCHDOG=1");
+            } else {
+                warn!(
+                    devices = ?stalled,
+                    "Withholding systemd watchdog keepalive: poll loop stalled"
+                );
+            }
+        }
+    }
+}
+
+fn send_state(state: &str) {
+    match notify(state) {
+        Ok(_) => debug!(state, "systemd notification sent"),
+        Err(e) => warn!(error = %e, "Failed to notify systemd: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_watchdog() {
+        assert_eq!(
+            parse_watchdog(Some("30000000"), None, 42),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            parse_watchdog(Some("30000000"), Some("42"), 42),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(parse_watchdog(Some("30000000"), Some("7"), 42), None);
+        assert_eq!(parse_watchdog(Some("0"), None, 42), None);
+        assert_eq!(parse_watchdog(None, None, 42), None);
+    }
+
+    #[test]
+    fn test_heartbeats_stalled() {
+        let heartbeats = Heartbeats::default();
+        heartbeats.beat("plc-001");
+        heartbeats.beat("plc-002");
+
+        let max_age = HashMap::from([
+            ("plc-001".to_string(), Duration::from_secs(60)),
+            ("plc-002".to_string(), Duration::from_secs(1)),
+        ]);
+        let later = Instant::now() + Duration::from_secs(5);
+        assert_eq!(heartbeats.stalled(&max_age, later), vec!["plc-002"]);
+        assert!(heartbeats.stalled(&max_age, Instant::now()).is_empty());
+    }
+
+    #[test]
+    fn test_readiness() {
+        let mut readiness = Readiness::new(2);
+        readiness.apply(&ConnectionEvent::new(
+            "plc-001",
+            ConnectionEventKind::Connected,
+        ));
+        assert!(!readiness.all_connected());
+        assert_eq!(
+            readiness.status(&[]),
+            "STATUS=1/2 devices connected, starting"
+        );
+
+        readiness.apply(&ConnectionEvent::new(
+            "plc-002",
+            ConnectionEventKind::Connected,
+        ));
+        assert!(readiness.all_connected());
+
+        readiness.ready = true;
+        readiness.apply(&ConnectionEvent::new(
+            "plc-001",
+            ConnectionEventKind::Disconnected,
+        ));
+        assert_eq!(
+            readiness.status(&["plc-002".to_string()]),
+            "STATUS=1/2 devices connected, poll loop stalled: plc-002"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_send() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notify.sock");
+        let receiver = std::os::unix::net::UnixDatagram::bind(&path).unwrap();
+
+        send(path.to_str().unwrap(), "READY=1").unwrap();
+
+        let mut buf = [0u8; 64];
+        let len = receiver.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"READY=1");
+    }
+}
@@ -0,0 +1,35 @@
+//! Fixtures for end-to-end tests of bridge setups
+//!
+//! [`ModbusServer`] simulates a Modbus TCP device and [`MqttBroker`] stands in
+//! for the MQTT broker, both in-process on free local ports, so a config or an
+//! extension can be tested in CI without hardware or external services:
+//!
+//! ```no_run
+//! # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! use std::time::Duration;
+//! use rustbridge::testing::{ModbusServer, MqttBroker};
+//!
+//! let plc = ModbusServer::start().await?;
+//! plc.device().set_holding(0, &[215]);
+//! let broker = MqttBroker::start().await?;
+//!
+//! let mut config = rustbridge::config::load_config("config.yaml")?;
+//! config.devices[0].connection = plc.connection();
+//! config.mqtt = Some(broker.config());
+//!
+//! let handle = rustbridge::Bridge::new(config).await?.start().await?;
+//! let message = broker
+//!     .wait_for("rustbridge/plc-demo/temperature", Duration::from_secs(5))
+//!     .await
+//!     .expect("temperature published");
+//! assert_eq!(message.json().unwrap()["value"], 21.5);
+//! handle.shutdown().await?;
+//! # Ok(())
+//! # }
+//! ```
+
+mod modbus;
+mod mqtt;
+
+pub use modbus::ModbusServer;
+pub use mqtt::{Message, MqttBroker};
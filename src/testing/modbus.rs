@@ -0,0 +1,241 @@
+//! In-process Modbus TCP server backed by a [`MockTransport`]
+
+use std::io;
+use std::net::SocketAddr;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+use tokio_modbus::Exception;
+use tracing::debug;
+
+use crate::config::{ConnectionConfig, TcpConnection};
+use crate::modbus::client::ModbusError;
+use crate::modbus::{MockTransport, ModbusTransport};
+
+/// Simulated Modbus TCP device on a local port
+///
+/// Every request, whatever its unit ID, is answered from the registers of the
+/// [`MockTransport`] returned by [`device`](Self::device). Failures queued with
+/// [`MockTransport::fail_next`] reach the client as they would from a real
+/// device: exceptions as exception responses, timeouts as no response, and
+/// other I/O errors as a closed connection.
+pub struct ModbusServer {
+    addr: SocketAddr,
+    device: MockTransport,
+    task: JoinHandle<()>,
+}
+
+impl ModbusServer {
+    /// Serve an empty device on a free port of 127.0.0.1
+    pub async fn start() -> io::Result<Self> {
+        Self::with_device(MockTransport::new()).await
+    }
+
+    /// Serve `device` on a free port of 127.0.0.1
+    pub async fn with_device(device: MockTransport) -> io::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let served = device.clone();
+        let task = tokio::spawn(async move {
+            while let Ok((stream, peer)) = listener.accept().await {
+                debug!("Simulated Modbus device accepted {}", peer);
+                tokio::spawn(serve(stream, served.clone()));
+            }
+        });
+        Ok(Self { addr, device, task })
+    }
+
+    /// Address the server listens on
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Port the server listens on
+    pub fn port(&self) -> u16 {
+        self.addr.port()
+    }
+
+    /// Device connection that points the bridge at this server
+    pub fn connection(&self) -> ConnectionConfig {
+        ConnectionConfig::Tcp(TcpConnection {
+            host: self.addr.ip().to_string(),
+            port: self.port(),
+            ..TcpConnection::default()
+        })
+    }
+
+    /// The simulated device, to set register values and inspect writes
+    pub fn device(&self) -> &MockTransport {
+        &self.device
+    }
+}
+
+impl Drop for ModbusServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Answer requests on one connection until the client disconnects
+async fn serve(mut stream: TcpStream, mut device: MockTransport) -> io::Result<()> {
+    loop {
+        // MBAP header: transaction ID, protocol ID, length, unit ID
+        let mut header = [0u8; 7];
+        if stream.read_exact(&mut header).await.is_err() {
+            return Ok(());
+        }
+        let length = u16::from_be_bytes([header[4], header[5]]) as usize;
+        if length < 2 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "empty request"));
+        }
+        let mut pdu = vec![0u8; length - 1];
+        stream.read_exact(&mut pdu).await?;
+
+        let response = match respond(&mut device, &pdu).await {
+            Ok(response) => response,
+            Err(ModbusError::Exception(exception)) => {
+                vec![pdu[0] | 0x80, exception_code(exception)]
+            }
+            Err(ModbusError::Io(e)) if e.kind() == io::ErrorKind::TimedOut => continue,
+            Err(e) => return Err(io::Error::other(e.to_string())),
+        };
+
+        let mut frame = Vec::with_capacity(7 + response.len());
+        frame.extend_from_slice(&header[..4]);
+        frame.extend_from_slice(&(response.len() as u16 + 1).to_be_bytes());
+        frame.push(header[6]);
+        frame.extend_from_slice(&response);
+        stream.write_all(&frame).await?;
+    }
+}
+
+/// Execute the request PDU on the device and build the response PDU
+async fn respond(device: &mut MockTransport, pdu: &[u8]) -> Result<Vec<u8>, ModbusError> {
+    let word = |at: usize| {
+        pdu.get(at..at + 2)
+            .map(|b| u16::from_be_bytes([b[0], b[1]]))
+            .ok_or(ModbusError::Exception(Exception::IllegalDataValue))
+    };
+    let function = pdu[0];
+    let mut response = vec![function];
+    match function {
+        0x01 | 0x02 => {
+            let (addr, cnt) = (word(1)?, word(3)?);
+            let bits = if function == 0x01 {
+                device.read_coils(addr, cnt).await?
+            } else {
+                device.read_discrete_inputs(addr, cnt).await?
+            };
+            let mut bytes = vec![0u8; bits.len().div_ceil(8)];
+            for (i, _) in bits.iter().enumerate().filter(|(_, bit)| **bit) {
+                bytes[i / 8] |= 1 << (i % 8);
+            }
+            response.push(bytes.len() as u8);
+            response.extend_from_slice(&bytes);
+        }
+        0x03 | 0x04 => {
+            let (addr, cnt) = (word(1)?, word(3)?);
+            let registers = if function == 0x03 {
+                device.read_holding_registers(addr, cnt).await?
+            } else {
+                device.read_input_registers(addr, cnt).await?
+            };
+            response.push((registers.len() * 2) as u8);
+            response.extend(registers.iter().flat_map(|r| r.to_be_bytes()));
+        }
+        0x05 => {
+            device
+                .write_single_coil(word(1)?, word(3)? == 0xFF00)
+                .await?;
+            response.extend_from_slice(&pdu[1..5]);
+        }
+        0x06 => {
+            device.write_single_register(word(1)?, word(3)?).await?;
+            response.extend_from_slice(&pdu[1..5]);
+        }
+        0x07 => response.push(device.read_exception_status().await?),
+        0x0F => {
+            let (addr, cnt) = (word(1)?, word(3)?);
+            for i in 0..cnt {
+                let byte = pdu
+                    .get(6 + i as usize / 8)
+                    .ok_or(ModbusError::Exception(Exception::IllegalDataValue))?;
+                let value = byte >> (i % 8) & 1 == 1;
+                device
+                    .write_single_coil(addr.wrapping_add(i), value)
+                    .await?;
+            }
+            response.extend_from_slice(&pdu[1..5]);
+        }
+        0x10 => {
+            let (addr, cnt) = (word(1)?, word(3)?);
+            let values = (0..cnt as usize)
+                .map(|i| word(6 + i * 2))
+                .collect::<Result<Vec<u16>, _>>()?;
+            device.write_multiple_registers(addr, &values).await?;
+            response.extend_from_slice(&pdu[1..5]);
+        }
+        0x18 => {
+            let entries = device.read_fifo_queue(word(1)?).await?;
+            response.extend_from_slice(&((entries.len() * 2 + 2) as u16).to_be_bytes());
+            response.extend_from_slice(&(entries.len() as u16).to_be_bytes());
+            response.extend(entries.iter().flat_map(|r| r.to_be_bytes()));
+        }
+        _ => return Err(ModbusError::Exception(Exception::IllegalFunction)),
+    }
+    Ok(response)
+}
+
+fn exception_code(exception: Exception) -> u8 {
+    match exception {
+        Exception::IllegalFunction => 0x01,
+        Exception::IllegalDataAddress => 0x02,
+        Exception::IllegalDataValue => 0x03,
+        Exception::ServerDeviceBusy => 0x06,
+        Exception::Custom(code) => code,
+        _ => 0x04,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn exchange(stream: &mut TcpStream, pdu: &[u8]) -> Vec<u8> {
+        let mut frame = vec![0x00, 0x07, 0x00, 0x00, 0x00, pdu.len() as u8 + 1, 0x01];
+        frame.extend_from_slice(pdu);
+        stream.write_all(&frame).await.unwrap();
+        let mut header = [0u8; 7];
+        stream.read_exact(&mut header).await.unwrap();
+        assert_eq!(&header[..2], &[0x00, 0x07]);
+        let mut response = vec![0u8; header[5] as usize - 1];
+        stream.read_exact(&mut response).await.unwrap();
+        response
+    }
+
+    #[tokio::test]
+    async fn test_server_answers_from_device() {
+        let server = ModbusServer::start().await.unwrap();
+        server.device().set_holding(10, &[0x41B4, 0x0000]);
+        let mut stream = TcpStream::connect(server.addr()).await.unwrap();
+
+        let response = exchange(&mut stream, &[0x03, 0x00, 0x0A, 0x00, 0x02]).await;
+        assert_eq!(response, vec![0x03, 0x04, 0x41, 0xB4, 0x00, 0x00]);
+
+        let response = exchange(
+            &mut stream,
+            &[0x10, 0x00, 0x14, 0x00, 0x02, 0x04, 0x00, 0x00, 0x00, 0x07],
+        )
+        .await;
+        assert_eq!(response, vec![0x10, 0x00, 0x14, 0x00, 0x02]);
+        assert_eq!(server.device().holding(21), Some(7));
+
+        let response = exchange(&mut stream, &[0x05, 0x00, 0x03, 0xFF, 0x00]).await;
+        assert_eq!(response, vec![0x05, 0x00, 0x03, 0xFF, 0x00]);
+        assert_eq!(server.device().coil(3), Some(true));
+
+        // Unset addresses answer with an exception, like a real device
+        let response = exchange(&mut stream, &[0x04, 0x00, 0x63, 0x00, 0x01]).await;
+        assert_eq!(response, vec![0x84, 0x02]);
+    }
+}
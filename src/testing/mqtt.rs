@@ -0,0 +1,405 @@
+//! Minimal in-process MQTT 3.1.1 broker that records every publication
+
+use bytes::BytesMut;
+use rumqttc::mqttbytes::{self, v4, QoS};
+use std::collections::HashMap;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{broadcast, mpsc};
+use tokio::task::JoinHandle;
+use tracing::debug;
+
+use crate::config::MqttConfig;
+
+/// Largest packet the broker accepts
+const MAX_PACKET_SIZE: usize = 1024 * 1024;
+
+/// A message published to the broker
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message {
+    pub topic: String,
+    pub payload: Vec<u8>,
+    pub retain: bool,
+}
+
+impl Message {
+    /// Payload as UTF-8 text
+    pub fn payload_str(&self) -> Option<&str> {
+        std::str::from_utf8(&self.payload).ok()
+    }
+
+    /// Payload parsed as JSON
+    pub fn json(&self) -> Option<serde_json::Value> {
+        serde_json::from_slice(&self.payload).ok()
+    }
+}
+
+/// A connected client: its subscriptions and the queue of messages to send it
+struct Session {
+    filters: Vec<String>,
+    tx: mpsc::UnboundedSender<Message>,
+}
+
+#[derive(Default)]
+struct State {
+    messages: Vec<Message>,
+    retained: HashMap<String, Message>,
+    sessions: HashMap<u64, Session>,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    published: broadcast::Sender<Message>,
+    next_session: AtomicU64,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Record a message, keep it if retained and forward it to subscribers
+    fn publish(&self, message: Message) {
+        let mut state = self.lock();
+        if message.retain {
+            if message.payload.is_empty() {
+                state.retained.remove(&message.topic);
+            } else {
+                state
+                    .retained
+                    .insert(message.topic.clone(), message.clone());
+            }
+        }
+        for session in state.sessions.values() {
+            if session
+                .filters
+                .iter()
+                .any(|filter| mqttbytes::matches(&message.topic, filter))
+            {
+                let _ = session.tx.send(Message {
+                    retain: false,
+                    ..message.clone()
+                });
+            }
+        }
+        state.messages.push(message.clone());
+        let _ = self.published.send(message);
+    }
+}
+
+/// MQTT broker on a local port for end-to-end tests
+///
+/// Accepts any client without authentication, keeps retained messages and
+/// forwards publications to matching subscribers, always with QoS 0. It is
+/// meant for tests only: there are no persistent sessions, wills or limits
+/// beyond the packet size.
+pub struct MqttBroker {
+    addr: SocketAddr,
+    shared: Arc<Shared>,
+    task: JoinHandle<()>,
+}
+
+impl MqttBroker {
+    /// Start the broker on a free port of 127.0.0.1
+    pub async fn start() -> io::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let shared = Arc::new(Shared {
+            state: Mutex::default(),
+            published: broadcast::channel(1024).0,
+            next_session: AtomicU64::new(0),
+        });
+        let accepted = Arc::clone(&shared);
+        let task = tokio::spawn(async move {
+            while let Ok((stream, peer)) = listener.accept().await {
+                debug!("Test MQTT broker accepted {}", peer);
+                let id = accepted.next_session.fetch_add(1, Ordering::Relaxed);
+                let shared = Arc::clone(&accepted);
+                tokio::spawn(async move {
+                    if let Err(e) = serve(stream, &shared, id).await {
+                        debug!("Test MQTT broker connection {} closed: {}", peer, e);
+                    }
+                    shared.lock().sessions.remove(&id);
+                });
+            }
+        });
+        Ok(Self { addr, shared, task })
+    }
+
+    /// Address the broker listens on
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Port the broker listens on
+    pub fn port(&self) -> u16 {
+        self.addr.port()
+    }
+
+    /// MQTT config section that connects the bridge to this broker
+    pub fn config(&self) -> MqttConfig {
+        MqttConfig {
+            enabled: true,
+            host: self.addr.ip().to_string(),
+            port: self.port(),
+            ..MqttConfig::default()
+        }
+    }
+
+    /// Every message published so far, oldest first
+    pub fn messages(&self) -> Vec<Message> {
+        self.shared.lock().messages.clone()
+    }
+
+    /// Messages published so far on topics matching `filter`
+    pub fn messages_on(&self, filter: &str) -> Vec<Message> {
+        self.shared
+            .lock()
+            .messages
+            .iter()
+            .filter(|m| mqttbytes::matches(&m.topic, filter))
+            .cloned()
+            .collect()
+    }
+
+    /// Retained message of `topic`
+    pub fn retained(&self, topic: &str) -> Option<Message> {
+        self.shared.lock().retained.get(topic).cloned()
+    }
+
+    /// Publish a message to subscribers, as another client would
+    pub fn publish(&self, topic: impl Into<String>, payload: impl Into<Vec<u8>>) {
+        self.shared.publish(Message {
+            topic: topic.into(),
+            payload: payload.into(),
+            retain: false,
+        });
+    }
+
+    /// First message on a topic matching `filter`, waiting up to `timeout`
+    /// for one if none was published yet
+    pub async fn wait_for(&self, filter: &str, timeout: Duration) -> Option<Message> {
+        self.wait_until(filter, timeout, |_| true).await
+    }
+
+    /// First message on a topic matching `filter` that satisfies `predicate`,
+    /// waiting up to `timeout` for one
+    pub async fn wait_until(
+        &self,
+        filter: &str,
+        timeout: Duration,
+        predicate: impl Fn(&Message) -> bool,
+    ) -> Option<Message> {
+        let wanted = |m: &Message| mqttbytes::matches(&m.topic, filter) && predicate(m);
+        // Subscribe before looking at the history so nothing slips in between
+        let mut published = self.shared.published.subscribe();
+        if let Some(message) = self.shared.lock().messages.iter().find(|m| wanted(m)) {
+            return Some(message.clone());
+        }
+        tokio::time::timeout(timeout, async {
+            loop {
+                match published.recv().await {
+                    Ok(message) if wanted(&message) => return Some(message),
+                    Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                }
+            }
+        })
+        .await
+        .ok()
+        .flatten()
+    }
+}
+
+impl Drop for MqttBroker {
+    fn drop(&mut self) {
+        self.task.abort();
+        // Dropping the sessions ends their connections
+        self.shared.lock().sessions.clear();
+    }
+}
+
+/// Run one client connection until it disconnects
+async fn serve(stream: TcpStream, shared: &Shared, id: u64) -> io::Result<()> {
+    let (mut reader, mut writer) = stream.into_split();
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let mut tx = Some(tx);
+    let mut incoming = BytesMut::with_capacity(4096);
+    let mut outgoing = BytesMut::with_capacity(4096);
+
+    loop {
+        tokio::select! {
+            read = reader.read_buf(&mut incoming) => {
+                if read? == 0 {
+                    return Ok(());
+                }
+                loop {
+                    let packet = match v4::read(&mut incoming, MAX_PACKET_SIZE) {
+                        Ok(packet) => packet,
+                        Err(mqttbytes::Error::InsufficientBytes(_)) => break,
+                        Err(e) => return Err(invalid(e)),
+                    };
+                    if !handle(packet, shared, id, &mut tx, &mut outgoing)? {
+                        writer.write_all(&outgoing).await?;
+                        return Ok(());
+                    }
+                }
+            }
+            message = rx.recv() => {
+                let Some(message) = message else {
+                    return Ok(());
+                };
+                v4::Publish::new(message.topic, QoS::AtMostOnce, message.payload)
+                    .write(&mut outgoing)
+                    .map_err(invalid)?;
+            }
+        }
+        writer.write_all(&outgoing).await?;
+        outgoing.clear();
+    }
+}
+
+/// Handle one packet, writing the replies to `out`; false once the client disconnects
+fn handle(
+    packet: v4::Packet,
+    shared: &Shared,
+    id: u64,
+    tx: &mut Option<mpsc::UnboundedSender<Message>>,
+    out: &mut BytesMut,
+) -> io::Result<bool> {
+    match packet {
+        v4::Packet::Connect(_) => {
+            if let Some(tx) = tx.take() {
+                let session = Session {
+                    filters: Vec::new(),
+                    tx,
+                };
+                shared.lock().sessions.insert(id, session);
+            }
+            v4::ConnAck::new(v4::ConnectReturnCode::Success, false).write(out)
+        }
+        v4::Packet::Publish(publish) => {
+            let pkid = publish.pkid;
+            let qos = publish.qos;
+            shared.publish(Message {
+                topic: publish.topic,
+                payload: publish.payload.to_vec(),
+                retain: publish.retain,
+            });
+            match qos {
+                QoS::AtMostOnce => Ok(0),
+                QoS::AtLeastOnce => v4::PubAck::new(pkid).write(out),
+                QoS::ExactlyOnce => v4::PubRec::new(pkid).write(out),
+            }
+        }
+        v4::Packet::PubRel(pubrel) => v4::PubComp::new(pubrel.pkid).write(out),
+        v4::Packet::Subscribe(subscribe) => {
+            let mut state = shared.lock();
+            let retained: Vec<Message> = state
+                .retained
+                .values()
+                .filter(|m| {
+                    subscribe
+                        .filters
+                        .iter()
+                        .any(|f| mqttbytes::matches(&m.topic, &f.path))
+                })
+                .cloned()
+                .collect();
+            if let Some(session) = state.sessions.get_mut(&id) {
+                session
+                    .filters
+                    .extend(subscribe.filters.iter().map(|f| f.path.clone()));
+            }
+            drop(state);
+
+            let codes = subscribe
+                .filters
+                .iter()
+                .map(|f| v4::SubscribeReasonCode::Success(f.qos))
+                .collect();
+            v4::SubAck::new(subscribe.pkid, codes)
+                .write(out)
+                .map_err(invalid)?;
+            for message in retained {
+                let mut publish = v4::Publish::new(message.topic, QoS::AtMostOnce, message.payload);
+                publish.retain = true;
+                publish.write(out).map_err(invalid)?;
+            }
+            return Ok(true);
+        }
+        v4::Packet::Unsubscribe(unsubscribe) => {
+            if let Some(session) = shared.lock().sessions.get_mut(&id) {
+                session.filters.retain(|f| !unsubscribe.topics.contains(f));
+            }
+            v4::UnsubAck::new(unsubscribe.pkid).write(out)
+        }
+        v4::Packet::PingReq => v4::PingResp.write(out),
+        v4::Packet::Disconnect => return Ok(false),
+        // Acknowledgements of the QoS 0 messages we send never arrive
+        _ => Ok(0),
+    }
+    .map_err(invalid)?;
+    Ok(true)
+}
+
+fn invalid(error: mqttbytes::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rumqttc::{AsyncClient, Event, MqttOptions, Packet};
+
+    #[tokio::test]
+    async fn test_broker_records_and_forwards_messages() {
+        let broker = MqttBroker::start().await.unwrap();
+        let options = MqttOptions::new("test", "127.0.0.1", broker.port());
+        let (client, mut eventloop) = AsyncClient::new(options, 10);
+
+        client
+            .publish("plant/status", QoS::AtLeastOnce, true, "online")
+            .await
+            .unwrap();
+        client
+            .subscribe("plant/+/set", QoS::AtLeastOnce)
+            .await
+            .unwrap();
+        let (subscribed_tx, subscribed_rx) = tokio::sync::oneshot::channel();
+        let received = tokio::spawn(async move {
+            let mut subscribed_tx = Some(subscribed_tx);
+            loop {
+                match eventloop.poll().await.unwrap() {
+                    Event::Incoming(Packet::SubAck(_)) => {
+                        subscribed_tx.take().map(|tx| tx.send(()));
+                    }
+                    Event::Incoming(Packet::Publish(publish)) => return publish,
+                    _ => {}
+                }
+            }
+        });
+
+        let status = broker
+            .wait_for("plant/#", Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert_eq!(status.payload_str(), Some("online"));
+        assert!(broker.retained("plant/status").is_some());
+
+        subscribed_rx.await.unwrap();
+        broker.publish("plant/pump/set", "42");
+        let publish = tokio::time::timeout(Duration::from_secs(5), received)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(publish.topic, "plant/pump/set");
+        assert_eq!(&publish.payload[..], b"42");
+        assert_eq!(broker.messages_on("plant/pump/#").len(), 1);
+    }
+}
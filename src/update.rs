@@ -0,0 +1,625 @@
+//! Release checks and self-update
+//!
+//! With `update`, the bridge fetches a release manifest every
+//! `check_interval_secs`:
+//!
+//! ```json
+//! { "version": "0.9.0", "url": "https://releases.example.com/rustbridge-0.9.0", "sha256": "9f2c…" }
+//! ```
+//!
+//! and reports on `GET /api/update` and the retained `{prefix}/bridge/update`
+//! topic whether it is newer than the running version.
+//!
+//! With `auto_install`, a newer release is downloaded over `https://` next to
+//! the running binary, checked against `sha256` and swapped in, keeping the previous
+//! binary as `<exe>.old`. The bridge then shuts down and executes the new
+//! binary with the same arguments. The new release has `health_check_secs` to
+//! read from a device; if it does not, or if it exits and is started again
+//! before that, the previous binary is restored and started, and the release is
+//! recorded in `<exe>.rejected` so it is not installed automatically again.
+//! `authorization` is only sent along with the download when the binary is on
+//! the same host as the manifest.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::cmp::Ordering;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::sync::{mpsc, watch};
+use tracing::{debug, error, info, warn};
+
+use crate::config::UpdateConfig;
+use crate::error::{Result, RustbridgeError};
+use crate::fetch::{Fetcher, RemoteUrl};
+use crate::metrics::diagnostics::DiagnosticsStore;
+
+/// Largest manifest accepted from the server
+const MAX_MANIFEST_BYTES: u64 = 64 * 1024;
+
+/// Largest binary downloaded
+const MAX_BINARY_BYTES: u64 = 256 * 1024 * 1024;
+
+/// Release announced by the manifest
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Manifest {
+    pub version: String,
+    /// Where the binary is downloaded from; needed for `auto_install`
+    #[serde(default)]
+    pub url: Option<String>,
+    /// Hex SHA-256 of the binary; needed for `auto_install`
+    #[serde(default)]
+    pub sha256: Option<String>,
+}
+
+/// What the update checker is doing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UpdateState {
+    Idle,
+    /// Downloading and swapping in a new release
+    Installing,
+    /// Running a newly installed release that has not passed its health check
+    Verifying,
+}
+
+/// Result of the latest release check, served on `GET /api/update`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct UpdateStatus {
+    pub current_version: String,
+    /// Version in the manifest, `None` before the first successful check
+    pub latest_version: Option<String>,
+    pub update_available: bool,
+    pub checked_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// Why the last check or installation failed
+    pub error: Option<String>,
+    pub state: UpdateState,
+    /// Release that failed its health check and was rolled back
+    pub rejected_version: Option<String>,
+}
+
+impl Default for UpdateStatus {
+    fn default() -> Self {
+        Self {
+            current_version: env!("CARGO_PKG_VERSION").to_string(),
+            latest_version: None,
+            update_available: false,
+            checked_at: None,
+            error: None,
+            state: UpdateState::Idle,
+            rejected_version: None,
+        }
+    }
+}
+
+/// Whether version `a` is newer than `b`
+///
+/// Versions compare as dot-separated numbers; a pre-release (`1.2.0-rc.1`) is
+/// older than the release itself, and pre-releases compare as text.
+pub fn is_newer(a: &str, b: &str) -> bool {
+    compare_versions(a, b) == Ordering::Greater
+}
+
+fn compare_versions(a: &str, b: &str) -> Ordering {
+    fn split(version: &str) -> (Vec<u64>, Option<&str>) {
+        let version = version.trim().trim_start_matches('v');
+        let version = version.split('+').next().unwrap_or(version);
+        let (release, pre) = match version.split_once('-') {
+            Some((release, pre)) => (release, Some(pre)),
+            None => (version, None),
+        };
+        let numbers = release
+            .split('.')
+            .map(|part| part.parse().unwrap_or(0))
+            .collect();
+        (numbers, pre)
+    }
+
+    let (a_numbers, a_pre) = split(a);
+    let (b_numbers, b_pre) = split(b);
+    let len = a_numbers.len().max(b_numbers.len());
+    let part = |numbers: &[u64], i: usize| numbers.get(i).copied().unwrap_or(0);
+    (0..len)
+        .map(|i| part(&a_numbers, i).cmp(&part(&b_numbers, i)))
+        .find(|ordering| ordering.is_ne())
+        .unwrap_or_else(|| match (a_pre, b_pre) {
+            (None, None) => Ordering::Equal,
+            (None, Some(_)) => Ordering::Greater,
+            (Some(_), None) => Ordering::Less,
+            (Some(a), Some(b)) => a.cmp(b),
+        })
+}
+
+/// A release installed but not yet confirmed healthy, kept in `<exe>.update.json`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Pending {
+    pub version: String,
+    pub previous_version: String,
+    /// Times the new release has been started
+    pub boots: u32,
+}
+
+/// The running binary and the files kept next to it during an update
+#[derive(Debug, Clone)]
+pub struct Installation {
+    exe: PathBuf,
+}
+
+impl Installation {
+    /// The binary of this process
+    pub fn current() -> std::io::Result<Self> {
+        Ok(Self::at(std::env::current_exe()?))
+    }
+
+    pub fn at(exe: impl Into<PathBuf>) -> Self {
+        Self { exe: exe.into() }
+    }
+
+    pub fn exe(&self) -> &Path {
+        &self.exe
+    }
+
+    fn sibling(&self, suffix: &str) -> PathBuf {
+        let mut name = self.exe.as_os_str().to_owned();
+        name.push(suffix);
+        PathBuf::from(name)
+    }
+
+    /// Release waiting for its health check, if any
+    pub fn pending(&self) -> Option<Pending> {
+        let json = std::fs::read_to_string(self.sibling(".update.json")).ok()?;
+        serde_json::from_str(&json).ok()
+    }
+
+    fn write_pending(&self, pending: &Pending) -> std::io::Result<()> {
+        let json = serde_json::to_string(pending).map_err(std::io::Error::other)?;
+        std::fs::write(self.sibling(".update.json"), json)
+    }
+
+    /// Release rolled back after failing its health check
+    pub fn rejected(&self) -> Option<String> {
+        let version = std::fs::read_to_string(self.sibling(".rejected")).ok()?;
+        Some(version.trim().to_string()).filter(|version| !version.is_empty())
+    }
+
+    /// Download the release to `<exe>.new`, checking its SHA-256
+    async fn download(&self, fetcher: &Fetcher, sha256: &str) -> Result<()> {
+        let path = self.sibling(".new");
+        let file = std::fs::File::create(&path).map_err(|e| io_error(&path, e))?;
+        let sink = HashingWriter {
+            inner: std::io::BufWriter::new(file),
+            hasher: Sha256::new(),
+        };
+        let result = fetcher.get_into(&[], sink, MAX_BINARY_BYTES).await;
+        let (response, sink) = match result {
+            Ok(downloaded) => downloaded,
+            Err(e) => {
+                let _ = std::fs::remove_file(&path);
+                return Err(e);
+            }
+        };
+        let digest = hex(&sink.hasher.finalize());
+        drop(sink.inner);
+        let failure = if response.status != 200 {
+            Some(format!("download server returned {}", response.status))
+        } else if !digest.eq_ignore_ascii_case(sha256.trim()) {
+            Some(format!(
+                "SHA-256 mismatch: expected {}, got {}",
+                sha256, digest
+            ))
+        } else {
+            None
+        };
+        if let Some(failure) = failure {
+            let _ = std::fs::remove_file(&path);
+            return Err(RustbridgeError::Connection(failure));
+        }
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755))
+                .map_err(|e| io_error(&path, e))?;
+        }
+        Ok(())
+    }
+
+    /// Replace the binary with the downloaded `<exe>.new`, keeping the
+    /// current one as `<exe>.old`
+    fn swap(&self, version: &str) -> std::io::Result<()> {
+        std::fs::rename(&self.exe, self.sibling(".old"))?;
+        if let Err(e) = std::fs::rename(self.sibling(".new"), &self.exe) {
+            let _ = std::fs::rename(self.sibling(".old"), &self.exe);
+            return Err(e);
+        }
+        self.write_pending(&Pending {
+            version: version.to_string(),
+            previous_version: env!("CARGO_PKG_VERSION").to_string(),
+            boots: 0,
+        })
+    }
+
+    /// Restore `<exe>.old` and remember the pending release as rejected
+    pub fn rollback(&self) -> std::io::Result<()> {
+        let pending = self.pending();
+        std::fs::rename(self.sibling(".old"), &self.exe)?;
+        if let Some(pending) = pending {
+            std::fs::write(self.sibling(".rejected"), pending.version)?;
+        }
+        std::fs::remove_file(self.sibling(".update.json"))
+    }
+
+    /// Keep the new release: drop `<exe>.old` and the pending record
+    pub fn confirm(&self) -> std::io::Result<()> {
+        let _ = std::fs::remove_file(self.sibling(".old"));
+        std::fs::remove_file(self.sibling(".update.json"))
+    }
+}
+
+/// Writes the download to disk while hashing it
+struct HashingWriter<W> {
+    inner: W,
+    hasher: Sha256,
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn io_error(path: &Path, error: std::io::Error) -> RustbridgeError {
+    RustbridgeError::Connection(format!("{}: {}", path.display(), error))
+}
+
+/// Account for a start of a newly installed release, before the bridge starts
+///
+/// The first start is counted; a release started again without having passed
+/// its health check crashed or hung, so the previous binary is restored and
+/// executed instead (this only returns on failure then).
+pub fn check_pending() -> Result<()> {
+    let installation =
+        Installation::current().map_err(|e| RustbridgeError::Config(e.to_string()))?;
+    let Some(mut pending) = installation.pending() else {
+        return Ok(());
+    };
+    let exe = installation.exe().display();
+    if pending.boots == 0 {
+        pending.boots = 1;
+        installation
+            .write_pending(&pending)
+            .map_err(|e| io_error(installation.exe(), e))?;
+        info!(
+            "Running release {} (updated from {}), waiting for its health check",
+            pending.version, pending.previous_version
+        );
+        return Ok(());
+    }
+    warn!(
+        "Release {} did not pass its health check before restarting, restoring {} of {}",
+        pending.version, pending.previous_version, exe
+    );
+    installation
+        .rollback()
+        .map_err(|e| io_error(installation.exe(), e))?;
+    Err(restart(installation.exe()))
+}
+
+/// Replace this process with `exe`, passing the same arguments
+///
+/// An already daemonized process stays in the background, so `--daemon` is
+/// not passed again. Only returns if the binary could not be executed.
+pub fn restart(exe: &Path) -> RustbridgeError {
+    let args = std::env::args_os().skip(1).filter(|arg| arg != "--daemon");
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        let error = std::process::Command::new(exe).args(args).exec();
+        io_error(exe, error)
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = args;
+        RustbridgeError::Config(format!(
+            "cannot restart into {} on this platform",
+            exe.display()
+        ))
+    }
+}
+
+/// Checks for new releases and installs them with `auto_install`
+pub struct UpdateChecker {
+    update: UpdateConfig,
+    fetcher: Fetcher,
+    installation: Installation,
+    status_tx: watch::Sender<UpdateStatus>,
+}
+
+impl UpdateChecker {
+    pub fn new(update: UpdateConfig, status_tx: watch::Sender<UpdateStatus>) -> Result<Self> {
+        let fetcher = Fetcher::new(
+            &update.manifest_url(),
+            update.authorization.as_deref(),
+            update.ca_cert.as_deref(),
+            Duration::from_secs(update.timeout_secs),
+        )
+        .map_err(|e| RustbridgeError::Config(format!("update: {}", e)))?;
+        let installation =
+            Installation::current().map_err(|e| RustbridgeError::Config(e.to_string()))?;
+        Ok(Self {
+            update,
+            fetcher,
+            installation,
+            status_tx,
+        })
+    }
+
+    /// Verify a freshly installed release, then check every
+    /// `check_interval_secs`
+    ///
+    /// Sends the binary to execute on `restart_tx` after installing a
+    /// release or rolling one back.
+    pub async fn run(self, diagnostics: DiagnosticsStore, restart_tx: mpsc::Sender<PathBuf>) {
+        let rejected = self.installation.rejected();
+        self.status_tx.send_modify(|status| {
+            status.rejected_version = rejected.clone();
+        });
+
+        if self.installation.pending().is_some() {
+            self.status_tx
+                .send_modify(|status| status.state = UpdateState::Verifying);
+            tokio::time::sleep(Duration::from_secs(self.update.health_check_secs)).await;
+            if healthy(&diagnostics).await {
+                info!(
+                    "Release {} passed its health check",
+                    env!("CARGO_PKG_VERSION")
+                );
+                if let Err(e) = self.installation.confirm() {
+                    warn!("Failed to clean up after the update: {}", e);
+                }
+                self.status_tx
+                    .send_modify(|status| status.state = UpdateState::Idle);
+            } else {
+                error!(
+                    "Release {} did not read from any device within {}s, rolling back",
+                    env!("CARGO_PKG_VERSION"),
+                    self.update.health_check_secs
+                );
+                match self.installation.rollback() {
+                    Ok(()) => {
+                        let _ = restart_tx.send(self.installation.exe().to_path_buf()).await;
+                        return;
+                    }
+                    Err(e) => error!("Rollback failed: {}", e),
+                }
+            }
+        }
+
+        info!("Checking for releases at {}", self.fetcher.url());
+        let every = Duration::from_secs(self.update.check_interval_secs);
+        let mut ticker = tokio::time::interval(every.max(Duration::from_secs(1)));
+        loop {
+            ticker.tick().await;
+            let manifest = match self.check().await {
+                Ok(manifest) => manifest,
+                Err(e) => {
+                    warn!("Release check at {} failed: {}", self.fetcher.url(), e);
+                    self.status_tx
+                        .send_modify(|status| status.error = Some(e.to_string()));
+                    None
+                }
+            };
+            if let Some(manifest) = manifest.filter(|_| self.update.auto_install) {
+                if rejected.as_deref() == Some(manifest.version.as_str()) {
+                    debug!(
+                        "Release {} was rolled back before, not installing",
+                        manifest.version
+                    );
+                } else if self.install(&manifest).await {
+                    let _ = restart_tx.send(self.installation.exe().to_path_buf()).await;
+                    return;
+                }
+            }
+            if every.is_zero() {
+                return;
+            }
+        }
+    }
+
+    /// Fetch the manifest and publish the result, returning it when it
+    /// announces a newer release
+    async fn check(&self) -> Result<Option<Manifest>> {
+        let (response, body) = self
+            .fetcher
+            .get(&[("Accept", "application/json")], MAX_MANIFEST_BYTES)
+            .await?;
+        if response.status != 200 {
+            return Err(RustbridgeError::Connection(format!(
+                "release server returned {}",
+                response.status
+            )));
+        }
+        let manifest: Manifest = serde_json::from_slice(&body)
+            .map_err(|e| RustbridgeError::Config(format!("invalid release manifest: {}", e)))?;
+        let available = is_newer(&manifest.version, env!("CARGO_PKG_VERSION"));
+        self.status_tx.send_modify(|status| {
+            status.latest_version = Some(manifest.version.clone());
+            status.update_available = available;
+            status.checked_at = Some(chrono::Utc::now());
+            status.error = None;
+        });
+        if available {
+            info!("Release {} is available", manifest.version);
+        }
+        Ok(available.then_some(manifest))
+    }
+
+    /// Download and swap in a release, reporting failures in the status
+    async fn install(&self, manifest: &Manifest) -> bool {
+        self.status_tx
+            .send_modify(|status| status.state = UpdateState::Installing);
+        let result = self.try_install(manifest).await;
+        if let Err(e) = &result {
+            error!("Installing release {} failed: {}", manifest.version, e);
+        }
+        self.status_tx.send_modify(|status| {
+            status.state = UpdateState::Idle;
+            status.error = result.as_ref().err().map(|e| e.to_string());
+        });
+        result.is_ok()
+    }
+
+    async fn try_install(&self, manifest: &Manifest) -> Result<()> {
+        let (Some(url), Some(sha256)) = (&manifest.url, &manifest.sha256) else {
+            return Err(RustbridgeError::Config(
+                "the release manifest has no url and sha256 to install from".to_string(),
+            ));
+        };
+        let same_origin = check_download_url(&self.update.manifest_url(), url)?;
+        // The credential is for the release server, not wherever the manifest points
+        let authorization = self.update.authorization.as_deref().filter(|_| same_origin);
+        let fetcher = Fetcher::new(
+            url,
+            authorization,
+            self.update.ca_cert.as_deref(),
+            Duration::from_secs(self.update.timeout_secs),
+        )
+        .map_err(|e| RustbridgeError::Config(format!("release url: {}", e)))?;
+        info!("Downloading release {} from {}", manifest.version, url);
+        self.installation.download(&fetcher, sha256).await?;
+        self.installation
+            .swap(&manifest.version)
+            .map_err(|e| io_error(self.installation.exe(), e))?;
+        info!("Installed release {}, restarting", manifest.version);
+        Ok(())
+    }
+}
+
+/// Refuse to download a release over plain HTTP, returning whether `url` has
+/// the same origin as the manifest
+fn check_download_url(manifest_url: &str, url: &str) -> Result<bool> {
+    let download = RemoteUrl::parse(url)
+        .map_err(|e| RustbridgeError::Config(format!("release url: {}", e)))?;
+    if !download.tls {
+        return Err(RustbridgeError::Config(format!(
+            "release url '{}' is not https://",
+            url
+        )));
+    }
+    let manifest = RemoteUrl::parse(manifest_url)
+        .map_err(|e| RustbridgeError::Config(format!("update.url: {}", e)))?;
+    Ok(download.tls == manifest.tls
+        && download.host.eq_ignore_ascii_case(&manifest.host)
+        && download.port == manifest.port)
+}
+
+/// A release is healthy once any device has been read (or none are configured)
+async fn healthy(diagnostics: &DiagnosticsStore) -> bool {
+    let diagnostics = diagnostics.read().await;
+    diagnostics.is_empty() || diagnostics.values().any(|device| device.reads_ok > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_newer() {
+        assert!(is_newer("0.10.0", "0.9.3"));
+        assert!(is_newer("v1.0.0", "0.9.9"));
+        assert!(is_newer("1.0.0", "1.0.0-rc.1"));
+        assert!(is_newer("1.0.0-rc.2", "1.0.0-rc.1"));
+        assert!(is_newer("1.0.1", "1.0"));
+        assert!(!is_newer("1.0.0", "1.0.0"));
+        assert!(!is_newer("1.0", "1.0.0+build.5"));
+        assert!(!is_newer("0.9.0", "0.10.0"));
+        assert!(!is_newer("1.0.0-rc.1", "1.0.0"));
+    }
+
+    #[test]
+    fn test_swap_and_rollback() {
+        let dir = tempfile::tempdir().unwrap();
+        let installation = Installation::at(dir.path().join("rustbridge"));
+        std::fs::write(installation.exe(), "old").unwrap();
+        std::fs::write(installation.sibling(".new"), "new").unwrap();
+
+        installation.swap("9.9.9").unwrap();
+        assert_eq!(std::fs::read_to_string(installation.exe()).unwrap(), "new");
+        let pending = installation.pending().unwrap();
+        assert_eq!((pending.version.as_str(), pending.boots), ("9.9.9", 0));
+
+        installation.rollback().unwrap();
+        assert_eq!(std::fs::read_to_string(installation.exe()).unwrap(), "old");
+        assert!(installation.pending().is_none());
+        assert_eq!(installation.rejected().as_deref(), Some("9.9.9"));
+
+        std::fs::write(installation.sibling(".new"), "newer").unwrap();
+        installation.swap("9.9.10").unwrap();
+        installation.confirm().unwrap();
+        assert_eq!(
+            std::fs::read_to_string(installation.exe()).unwrap(),
+            "newer"
+        );
+        assert!(installation.pending().is_none());
+        assert!(!installation.sibling(".old").exists());
+    }
+
+    #[cfg(feature = "tls")]
+    #[test]
+    fn test_check_download_url() {
+        let manifest = "https://releases.example.com/linux-x86_64.json";
+        assert!(check_download_url(manifest, "https://releases.example.com/rustbridge").unwrap());
+        assert!(check_download_url(manifest, "https://RELEASES.example.com:443/rb").unwrap());
+        assert!(!check_download_url(manifest, "https://cdn.example.net/rustbridge").unwrap());
+        assert!(!check_download_url(manifest, "https://releases.example.com:8443/rb").unwrap());
+        let error = check_download_url(manifest, "http://releases.example.com/rustbridge");
+        assert!(error.unwrap_err().to_string().contains("is not https://"));
+    }
+
+    #[tokio::test]
+    async fn test_download_checks_sha256() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut buf = [0u8; 1024];
+                let _ = stream.read(&mut buf).await.unwrap();
+                stream
+                    .write_all(b"HTTP/1.0 200 OK\r\n\r\nbinary")
+                    .await
+                    .unwrap();
+            }
+        });
+        let fetcher = Fetcher::new(
+            &format!("http://127.0.0.1:{}/rustbridge", port),
+            None,
+            None,
+            Duration::from_secs(5),
+        )
+        .unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let installation = Installation::at(dir.path().join("rustbridge"));
+
+        let digest = hex(&Sha256::digest(b"binary"));
+        installation.download(&fetcher, &digest).await.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(installation.sibling(".new")).unwrap(),
+            "binary"
+        );
+
+        let wrong = "0".repeat(64);
+        assert!(installation.download(&fetcher, &wrong).await.is_err());
+        assert!(!installation.sibling(".new").exists());
+    }
+}
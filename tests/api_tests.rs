@@ -8,19 +8,30 @@ use axum::{
 };
 use http_body_util::BodyExt;
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use tower::ServiceExt;
 
-use rustbridge::api::{create_router, ApiState};
+use rustbridge::api::{
+    create_router, ApiState, ModbusException, WriteError, WriteOutcome, WriteRequest,
+};
+use rustbridge::audit::{AuditEntry, WriteResult, WriteSource};
 use rustbridge::config::AuthConfig;
+use rustbridge::control::DeviceControls;
+use rustbridge::metrics::diagnostics::{CycleTiming, RegisterTiming};
+use rustbridge::metrics::error_log::{ErrorEvent, ErrorKind, ErrorSource};
 use rustbridge::modbus::reader::{RegisterStore, RegisterValue};
+use rustbridge::reload::{ReloadAction, ReloadReport, ReloadRequest};
+use rustbridge::update::UpdateStatus;
+use rustbridge::RustbridgeError;
 
 /// Helper to create a disabled auth config for tests
 fn disabled_auth() -> AuthConfig {
     AuthConfig {
         enabled: false,
         api_keys: vec![],
+        tenant_keys: Default::default(),
         exclude_paths: vec!["/health".to_string(), "/metrics".to_string()],
     }
 }
@@ -41,21 +52,27 @@ async fn populate_test_data(state: &ApiState) {
     device1_registers.insert(
         "temperature".to_string(),
         RegisterValue {
-            name: "temperature".to_string(),
+            name: "temperature".into(),
             raw: vec![250],
             value: 25.0,
+            quality: Default::default(),
+            restored: false,
             unit: Some("°C".to_string()),
             timestamp: chrono::Utc::now(),
+            meta: Default::default(),
         },
     );
     device1_registers.insert(
         "humidity".to_string(),
         RegisterValue {
-            name: "humidity".to_string(),
+            name: "humidity".into(),
             raw: vec![650],
             value: 65.0,
+            quality: Default::default(),
+            restored: false,
             unit: Some("%".to_string()),
             timestamp: chrono::Utc::now(),
+            meta: Default::default(),
         },
     );
     store.insert("plc-001".to_string(), device1_registers);
@@ -65,11 +82,14 @@ async fn populate_test_data(state: &ApiState) {
     device2_registers.insert(
         "pressure".to_string(),
         RegisterValue {
-            name: "pressure".to_string(),
+            name: "pressure".into(),
             raw: vec![1000],
             value: 10.0,
+            quality: Default::default(),
+            restored: false,
             unit: Some("bar".to_string()),
             timestamp: chrono::Utc::now(),
+            meta: Default::default(),
         },
     );
     store.insert("sensor-001".to_string(), device2_registers);
@@ -178,6 +198,407 @@ async fn test_api_info_endpoint() {
     // Verify endpoints list contains expected entries
     let endpoints = json["endpoints"].as_array().unwrap();
     assert!(endpoints.len() >= 8); // At least 8 endpoints defined
+
+    assert!(json["build_timestamp"].is_string());
+    assert!(json["features"].is_array());
+    // No running configuration exposed
+    assert!(json["config_hash"].is_null());
+}
+
+#[tokio::test]
+async fn test_api_info_config_hash() {
+    let config = rustbridge::Config::default();
+    let mut state = create_test_state();
+    state.config_rx = Some(tokio::sync::watch::channel(config.clone()).1);
+    let app = create_router(state, disabled_auth());
+
+    let (status, json) = get_json(app, "/api/info").await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        json["config_hash"],
+        rustbridge::build_info::config_hash(&config)
+    );
+}
+
+// ============================================================================
+// Diagnostics Endpoint Tests
+// ============================================================================
+
+#[tokio::test]
+async fn test_diagnostics_empty() {
+    let state = create_test_state();
+    let app = create_router(state, disabled_auth());
+
+    let (status, json) = get_json(app, "/api/diagnostics").await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["count"], 0);
+    assert!(json["devices"].is_object());
+    assert_eq!(json["mqtt"]["enabled"], false);
+    assert_eq!(json["mqtt"]["connected"], false);
+}
+
+#[tokio::test]
+async fn test_diagnostics_mqtt_status() {
+    let mut state = create_test_state();
+    state.mqtt_connected = Some(Arc::new(AtomicBool::new(true)));
+    let app = create_router(state, disabled_auth());
+
+    let (_, json) = get_json(app, "/api/diagnostics").await;
+
+    assert_eq!(json["mqtt"]["enabled"], true);
+    assert_eq!(json["mqtt"]["connected"], true);
+}
+
+#[tokio::test]
+async fn test_diagnostics_bus_stats() {
+    let bus = rustbridge::metrics::bus_stats::bus("/dev/ttyAPI0");
+    bus.record_crc_error();
+    bus.record_timeout();
+    let app = create_router(create_test_state(), disabled_auth());
+
+    let (_, json) = get_json(app, "/api/diagnostics").await;
+
+    let stats = &json["buses"]["/dev/ttyAPI0"];
+    assert_eq!(stats["crc_errors"], 1);
+    assert_eq!(stats["timeouts"], 1);
+    assert_eq!(stats["framing_errors"], 0);
+}
+
+#[tokio::test]
+async fn test_diagnostics_with_data() {
+    let state = create_test_state();
+    {
+        let mut diagnostics = state.diagnostics.write().await;
+        let device = diagnostics.entry("plc-001".to_string()).or_default();
+        device.record_success("temperature", 12.5);
+        device.record_failure("temperature", 3000.0, "Modbus error: timeout");
+        device.record_cycle(3020, 1000);
+    }
+    let app = create_router(state, disabled_auth());
+
+    let (status, json) = get_json(app, "/api/diagnostics").await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["count"], 1);
+    let device = &json["devices"]["plc-001"];
+    assert_eq!(device["poll_cycles"], 1);
+    assert_eq!(device["overruns"], 1);
+    assert_eq!(device["reads_ok"], 1);
+    assert_eq!(device["reads_failed"], 1);
+    assert_eq!(
+        device["registers"]["temperature"]["consecutive_failures"],
+        1
+    );
+    assert_eq!(
+        device["registers"]["temperature"]["last_error"],
+        "Modbus error: timeout"
+    );
+}
+
+#[tokio::test]
+async fn test_device_timing() {
+    let state = create_test_state();
+    {
+        let mut diagnostics = state.diagnostics.write().await;
+        let device = diagnostics.entry("plc-001".to_string()).or_default();
+        for (duration_ms, queue_wait_ms) in [(120.0, 0.0), (640.0, 40.0), (300.0, 380.0)] {
+            let overran = device.record_cycle(duration_ms as u64, 500);
+            device.record_timing(CycleTiming {
+                started_at: chrono::Utc::now(),
+                duration_ms,
+                queue_wait_ms,
+                writes_ms: 0.0,
+                overran,
+                registers: vec![RegisterTiming {
+                    name: "temperature".to_string(),
+                    wait_ms: 1.0,
+                    latency_ms: duration_ms - 1.0,
+                    ok: true,
+                }],
+            });
+        }
+    }
+    let app = create_router(state, disabled_auth());
+
+    let (status, json) = get_json(app.clone(), "/api/devices/plc-001/timing").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["poll_interval_ms"], 500);
+    assert_eq!(json["count"], 3);
+    assert_eq!(json["cycles"][0]["queue_wait_ms"], 380.0);
+    assert_eq!(json["cycles"][0]["registers"][0]["latency_ms"], 299.0);
+    assert_eq!(json["summary"]["max_duration_ms"], 640.0);
+    assert_eq!(json["summary"]["overruns"], 1);
+
+    let (_, json) = get_json(app.clone(), "/api/devices/plc-001/timing?limit=1").await;
+    assert_eq!(json["count"], 1);
+    assert_eq!(json["summary"]["avg_duration_ms"], 300.0);
+
+    let (status, _) = get_json(app, "/api/devices/unknown/timing").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+// ============================================================================
+// Snapshot Endpoint Tests
+// ============================================================================
+
+#[tokio::test]
+async fn test_snapshot() {
+    let state = create_test_state();
+    populate_test_data(&state).await;
+    let app = create_router(state, disabled_auth());
+
+    let (status, json) = get_json(app, "/api/snapshot").await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert!(json["taken_at"].is_string());
+    assert_eq!(json["devices"]["plc-001"]["temperature"]["value"], 25.0);
+    assert_eq!(json["devices"]["sensor-001"]["pressure"]["unit"], "bar");
+}
+
+// ============================================================================
+// Config Reload Endpoint Tests
+// ============================================================================
+
+#[tokio::test]
+async fn test_reload_unavailable() {
+    let app = create_router(create_test_state(), disabled_auth());
+
+    let (status, _) = post_json(app, "/api/config/reload", serde_json::json!({})).await;
+
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+}
+
+#[tokio::test]
+async fn test_config_pull_without_remote_config() {
+    let app = create_router(create_test_state(), disabled_auth());
+
+    let (status, json) = post_json(app, "/api/config/pull", serde_json::json!({})).await;
+
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(json["details"], "The bridge has no remote_config");
+}
+
+#[tokio::test]
+async fn test_update_status() {
+    let app = create_router(create_test_state(), disabled_auth());
+    let (status, _) = get_json(app, "/api/update").await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+
+    let mut state = create_test_state();
+    let (status_tx, status_rx) = tokio::sync::watch::channel(UpdateStatus::default());
+    state.update_status = Some(status_rx);
+    status_tx.send_modify(|status| {
+        status.latest_version = Some("99.0.0".to_string());
+        status.update_available = true;
+    });
+    let app = create_router(state, disabled_auth());
+
+    let (status, json) = get_json(app, "/api/update").await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["current_version"], env!("CARGO_PKG_VERSION"));
+    assert_eq!(json["latest_version"], "99.0.0");
+    assert_eq!(json["update_available"], true);
+    assert_eq!(json["state"], "idle");
+}
+
+#[tokio::test]
+async fn test_reload_rejected() {
+    let mut state = create_test_state();
+    let (reload_tx, mut reload_rx) = tokio::sync::mpsc::channel::<ReloadRequest>(1);
+    state.reload_tx = Some(reload_tx);
+    tokio::spawn(async move {
+        while let Some(request) = reload_rx.recv().await {
+            let _ = request.response_tx.send(Err(RustbridgeError::Config(
+                "Invalid configuration:\n  - duplicate device id 'plc-001'".to_string(),
+            )));
+        }
+    });
+    let app = create_router(state, disabled_auth());
+
+    let (status, json) = post_json(app, "/api/config/reload", serde_json::json!({})).await;
+
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert!(json["details"]
+        .as_str()
+        .unwrap()
+        .contains("duplicate device id"));
+}
+
+#[tokio::test]
+async fn test_config_edit() {
+    let config = rustbridge::config::load_config_from_str(
+        r#"
+server: { host: "0.0.0.0", port: 3000, metrics_enabled: false }
+devices:
+  - id: "plc-001"
+    name: "PLC"
+    device_type: tcp
+    connection: { host: "127.0.0.1", port: 502, unit_id: 1 }
+    poll_interval_ms: 1000
+    registers:
+      - { name: temperature, address: 0, register_type: holding, count: 1, data_type: u16 }
+"#,
+    )
+    .unwrap();
+    let mut state = create_test_state();
+    state.config_rx = Some(tokio::sync::watch::channel(config).1);
+    let (reload_tx, mut reload_rx) = tokio::sync::mpsc::channel::<ReloadRequest>(1);
+    state.reload_tx = Some(reload_tx);
+    tokio::spawn(async move {
+        while let Some(request) = reload_rx.recv().await {
+            let report = match request.action {
+                ReloadAction::Apply(config) => ReloadReport {
+                    devices_added: vec![config.devices[0].id.clone()],
+                    ..Default::default()
+                },
+                _ => ReloadReport::default(),
+            };
+            let _ = request.response_tx.send(Ok(report));
+        }
+    });
+    let app = create_router(state, disabled_auth());
+
+    let (status, mut edited) = get_json(app.clone(), "/api/config").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(edited["devices"][0]["id"], "plc-001");
+
+    // A valid edit previews its changes
+    edited["devices"][0]["poll_interval_ms"] = serde_json::json!(500);
+    let (status, json) = post_json(app.clone(), "/api/config/validate", edited.clone()).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["valid"], true);
+    assert_eq!(
+        json["changes"]["devices_changed"][0]["device_id"],
+        "plc-001"
+    );
+    assert_eq!(
+        json["changes"]["devices_changed"][0]["settings_changed"],
+        true
+    );
+
+    // Invalid edits list every problem and are never applied
+    let mut invalid = edited.clone();
+    invalid["devices"][0]["registers"][0]["data_type"] = serde_json::json!("u32");
+    invalid["server"]["update_channel_capacity"] = serde_json::json!(0);
+    let (status, json) = post_json(app.clone(), "/api/config/validate", invalid.clone()).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["valid"], false);
+    assert_eq!(json["errors"].as_array().unwrap().len(), 2);
+    let (status, json) = post_json(
+        app.clone(),
+        "/api/config/validate",
+        serde_json::json!({ "devices": "none" }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["valid"], false);
+
+    let put = |body: serde_json::Value| {
+        app.clone().oneshot(
+            Request::builder()
+                .method(Method::PUT)
+                .uri("/api/config")
+                .header("Content-Type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap(),
+        )
+    };
+    let response = put(invalid).await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let response = put(edited).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["devices_added"][0], "plc-001");
+
+    let (status, _) = post_json(app, "/api/config/rollback", serde_json::json!({})).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+// ============================================================================
+// Error Log Endpoint Tests
+// ============================================================================
+
+#[tokio::test]
+async fn test_errors_empty() {
+    let state = create_test_state();
+    let app = create_router(state, disabled_auth());
+
+    let (status, json) = get_json(app, "/api/errors").await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["count"], 0);
+    assert_eq!(json["capacity"], 100);
+    assert!(json["errors"].as_array().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_errors_newest_first_with_filters() {
+    let state = create_test_state();
+    state.error_log.record(
+        ErrorEvent::new(ErrorSource::Modbus, ErrorKind::Timeout, "first").device("plc-001"),
+    );
+    state.error_log.record(
+        ErrorEvent::new(ErrorSource::Modbus, ErrorKind::Exception, "second")
+            .device("sensor-001")
+            .register("pressure", 10),
+    );
+    state.error_log.record(
+        ErrorEvent::new(ErrorSource::Modbus, ErrorKind::Timeout, "third").device("plc-001"),
+    );
+    let app = create_router(state, disabled_auth());
+
+    let (status, json) = get_json(app.clone(), "/api/errors").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["count"], 3);
+    assert_eq!(json["errors"][0]["message"], "third");
+    assert_eq!(json["errors"][1]["kind"], "exception");
+    assert_eq!(json["errors"][1]["register"], "pressure");
+
+    let (_, json) = get_json(app.clone(), "/api/errors?limit=1").await;
+    assert_eq!(json["count"], 1);
+
+    let (_, json) = get_json(app, "/api/errors?device_id=plc-001").await;
+    assert_eq!(json["count"], 2);
+    assert_eq!(json["errors"][1]["message"], "first");
+}
+
+// ============================================================================
+// Audit Log Endpoint Tests
+// ============================================================================
+
+#[tokio::test]
+async fn test_audit_log_endpoint() {
+    let state = create_test_state();
+    state.audit_log.record(AuditEntry {
+        timestamp: chrono::Utc::now(),
+        source: WriteSource::api(Some("secret-key-123")),
+        device_id: "plc-001".to_string(),
+        register: "setpoint".to_string(),
+        address: 40,
+        old_value: Some(20.0),
+        new_value: 25.0,
+        result: WriteResult::Success,
+        error: None,
+    });
+    let app = create_router(state, disabled_auth());
+
+    let (status, json) = get_json(app.clone(), "/api/audit").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["count"], 1);
+    let entry = &json["entries"][0];
+    assert_eq!(entry["device_id"], "plc-001");
+    assert_eq!(entry["source"]["type"], "api");
+    assert_eq!(entry["source"]["api_key"], "secr****");
+    assert_eq!(entry["old_value"], 20.0);
+    assert_eq!(entry["new_value"], 25.0);
+    assert_eq!(entry["result"], "success");
+
+    let (_, json) = get_json(app, "/api/audit?device_id=other").await;
+    assert_eq!(json["count"], 0);
 }
 
 // ============================================================================
@@ -418,6 +839,275 @@ async fn test_write_register_not_found() {
     assert_eq!(json["error"], "Register not found");
 }
 
+#[tokio::test]
+async fn test_write_register_engineering_value() {
+    let register_store: RegisterStore = Arc::new(RwLock::new(HashMap::new()));
+    let (write_tx, mut write_rx) = tokio::sync::mpsc::channel::<WriteRequest>(1);
+    let state = ApiState::new(register_store, write_tx);
+    populate_test_data(&state).await;
+    tokio::spawn(async move {
+        while let Some(request) = write_rx.recv().await {
+            let result = if request.value == 23.5 {
+                Ok(WriteOutcome {
+                    address: 0,
+                    raw: vec![235],
+                    read_back: None,
+                })
+            } else {
+                Err(WriteError::Rejected(
+                    "-1 is out of range for register 'temperature' (raw value -10)".to_string(),
+                ))
+            };
+            let _ = request.response_tx.send(result);
+        }
+    });
+    let app = create_router(state, disabled_auth());
+
+    let (status, json) = post_json(
+        app.clone(),
+        "/api/devices/plc-001/registers/temperature",
+        serde_json::json!({"value": 23.5}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["value_written"], 23.5);
+    assert_eq!(json["raw_written"], serde_json::json!([235]));
+
+    let (status, json) = post_json(
+        app,
+        "/api/devices/plc-001/registers/temperature",
+        serde_json::json!({"value": -1.0}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(json["error"], "Write rejected");
+}
+
+#[tokio::test]
+async fn test_write_register_exception_status() {
+    let register_store: RegisterStore = Arc::new(RwLock::new(HashMap::new()));
+    let (write_tx, mut write_rx) = tokio::sync::mpsc::channel::<WriteRequest>(1);
+    let state = ApiState::new(register_store, write_tx);
+    populate_test_data(&state).await;
+    tokio::spawn(async move {
+        while let Some(request) = write_rx.recv().await {
+            let exception = if request.value == 1.0 {
+                tokio_modbus::Exception::IllegalDataAddress
+            } else {
+                tokio_modbus::Exception::ServerDeviceBusy
+            };
+            let exception = ModbusException::new(exception, &request.device_id, 40);
+            let _ = request
+                .response_tx
+                .send(Err(WriteError::Exception(exception)));
+        }
+    });
+    let app = create_router(state, disabled_auth());
+    let uri = "/api/devices/plc-001/registers/temperature";
+
+    let (status, json) = post_json(app.clone(), uri, serde_json::json!({"value": 1.0})).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(
+        json["exception"],
+        serde_json::json!({
+            "code": 2,
+            "name": "illegal_data_address",
+            "device_id": "plc-001",
+            "address": 40
+        })
+    );
+
+    // A busy device is worth retrying, a bad address is not
+    let (status, json) = post_json(app, uri, serde_json::json!({"value": 2.0})).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(json["exception"]["name"], "server_device_busy");
+}
+
+#[tokio::test]
+async fn test_write_switch_and_interlocks() {
+    let register_store: RegisterStore = Arc::new(RwLock::new(HashMap::new()));
+    let (write_tx, mut write_rx) = tokio::sync::mpsc::channel::<WriteRequest>(1);
+    let state = ApiState::new(register_store, write_tx);
+    populate_test_data(&state).await;
+    let writes_enabled = state.writes_enabled.clone();
+    tokio::spawn(async move {
+        while let Some(request) = write_rx.recv().await {
+            let result = if !writes_enabled.load(Ordering::Relaxed) {
+                Err(WriteError::Disabled("Writes are disabled".to_string()))
+            } else {
+                Err(WriteError::RateLimited("retry in 600 ms".to_string()))
+            };
+            let _ = request.response_tx.send(result);
+        }
+    });
+    let app = create_router(state, disabled_auth());
+
+    let (status, json) = get_json(app.clone(), "/api/writes").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["enabled"], true);
+
+    let write = serde_json::json!({"value": 23.5});
+    let path = "/api/devices/plc-001/registers/temperature";
+    let (status, json) = post_json(app.clone(), path, write.clone()).await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(json["error"], "Write rate limited");
+
+    let (status, json) = post_json(
+        app.clone(),
+        "/api/writes",
+        serde_json::json!({"enabled": false}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["enabled"], false);
+
+    let (status, json) = post_json(app, path, write).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(json["error"], "Writes disabled");
+}
+
+#[tokio::test]
+async fn test_write_register_boolean_values() {
+    let register_store: RegisterStore = Arc::new(RwLock::new(HashMap::new()));
+    let (write_tx, mut write_rx) = tokio::sync::mpsc::channel::<WriteRequest>(1);
+    let state = ApiState::new(register_store, write_tx);
+    populate_test_data(&state).await;
+    tokio::spawn(async move {
+        while let Some(request) = write_rx.recv().await {
+            let _ = request.response_tx.send(Ok(WriteOutcome {
+                address: 0,
+                raw: vec![request.value as u16],
+                read_back: None,
+            }));
+        }
+    });
+    let app = create_router(state, disabled_auth());
+
+    for (value, expected) in [
+        (serde_json::json!(true), 1),
+        (serde_json::json!(false), 0),
+        (serde_json::json!("on"), 1),
+        (serde_json::json!("OFF"), 0),
+        (serde_json::json!("1"), 1),
+        (serde_json::json!(0), 0),
+    ] {
+        let (status, json) = post_json(
+            app.clone(),
+            "/api/devices/plc-001/registers/temperature",
+            serde_json::json!({ "value": value }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK, "{}", value);
+        assert_eq!(json["raw_written"][0], expected, "{}", value);
+    }
+
+    let (status, json) = post_json(
+        app,
+        "/api/devices/plc-001/registers/temperature",
+        serde_json::json!({"value": "maybe"}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(json["error"], "Invalid value");
+}
+
+// ============================================================================
+// Group & Device Control Tests
+// ============================================================================
+
+/// Test state with two devices in group "line-a" and one ungrouped device
+fn create_grouped_state() -> ApiState {
+    let config = rustbridge::config::load_config_from_str(
+        r#"
+server:
+  host: "127.0.0.1"
+  port: 3000
+  metrics_enabled: false
+devices:
+  - { id: press-1, name: Press 1, device_type: tcp, group: line-a, poll_interval_ms: 1000,
+      connection: { host: 10.0.0.1, port: 502, unit_id: 1 }, registers: [] }
+  - { id: press-2, name: Press 2, device_type: tcp, group: line-a, poll_interval_ms: 1000,
+      connection: { host: 10.0.0.2, port: 502, unit_id: 1 }, registers: [] }
+  - { id: meter, name: Meter, device_type: tcp, poll_interval_ms: 1000,
+      connection: { host: 10.0.0.3, port: 502, unit_id: 1 }, registers: [] }
+"#,
+    )
+    .unwrap();
+    let mut state = create_test_state();
+    state.controls = DeviceControls::new(&config.devices);
+    state
+}
+
+#[tokio::test]
+async fn test_list_groups() {
+    let state = create_grouped_state();
+    state.controls.device("press-1").unwrap().set_online(true);
+    let app = create_router(state, disabled_auth());
+
+    let (status, json) = get_json(app, "/api/groups").await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["count"], 1);
+    assert_eq!(json["groups"][0]["group"], "line-a");
+    assert_eq!(json["groups"][0]["status"], "partial");
+    assert_eq!(json["groups"][0]["online"], 1);
+    assert_eq!(json["groups"][0]["total"], 2);
+}
+
+#[tokio::test]
+async fn test_get_group_not_found() {
+    let app = create_router(create_grouped_state(), disabled_auth());
+
+    let (status, json) = get_json(app, "/api/groups/line-z").await;
+
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(json["error"], "Group not found");
+}
+
+#[tokio::test]
+async fn test_group_command_pauses_members() {
+    let state = create_grouped_state();
+    let controls = state.controls.clone();
+    let app = create_router(state, disabled_auth());
+
+    let (status, json) = post_json(
+        app.clone(),
+        "/api/groups/line-a/command",
+        serde_json::json!({"command": "pause"}),
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["devices"], serde_json::json!(["press-1", "press-2"]));
+    assert!(controls.device("press-2").unwrap().is_paused());
+    assert!(!controls.device("meter").unwrap().is_paused());
+
+    let (_, json) = get_json(app, "/api/groups/line-a").await;
+    assert_eq!(json["devices"][0]["paused"], true);
+}
+
+#[tokio::test]
+async fn test_device_command_unknown_device() {
+    let app = create_router(create_grouped_state(), disabled_auth());
+
+    let (status, _) = post_json(
+        app.clone(),
+        "/api/devices/meter/command",
+        serde_json::json!({"command": "poll_now"}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, json) = post_json(
+        app,
+        "/api/devices/missing/command",
+        serde_json::json!({"command": "resume"}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(json["error"], "Unknown target");
+}
+
 // ============================================================================
 // WebSocket Tests (Basic)
 // ============================================================================
@@ -455,6 +1145,30 @@ async fn test_websocket_endpoint_exists() {
     );
 }
 
+#[tokio::test]
+async fn test_websocket_query_filters() {
+    let state = create_test_state();
+    let app = create_router(state, disabled_auth());
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/ws?device_id=test-device&register_name=temperature")
+                .header("Upgrade", "websocket")
+                .header("Connection", "upgrade")
+                .header("Sec-WebSocket-Key", "dGhlIHNhbXBsZSBub25jZQ==")
+                .header("Sec-WebSocket-Version", "13")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    // The filters are accepted; the upgrade itself is as above
+    assert_ne!(response.status(), StatusCode::BAD_REQUEST);
+    assert_ne!(response.status(), StatusCode::NOT_FOUND);
+}
+
 // ============================================================================
 // Error Response Tests
 // ============================================================================
@@ -493,6 +1207,7 @@ fn enabled_auth_with_keys(keys: Vec<&str>) -> AuthConfig {
     AuthConfig {
         enabled: true,
         api_keys: keys.iter().map(|s| s.to_string()).collect(),
+        tenant_keys: Default::default(),
         exclude_paths: vec!["/health".to_string(), "/metrics".to_string()],
     }
 }
@@ -616,3 +1331,50 @@ async fn test_auth_protected_endpoint_requires_key() {
     let (status, _) = get_json_with_key(app, "/api/info", Some("secret-key")).await;
     assert_eq!(status, StatusCode::OK);
 }
+
+#[tokio::test]
+async fn test_tenant_key_sees_only_its_devices() {
+    let mut state = create_test_state();
+    populate_test_data(&state).await;
+    let config = rustbridge::config::load_config_from_str(
+        r#"
+server:
+  host: "127.0.0.1"
+  port: 3000
+  metrics_enabled: false
+devices:
+  - { id: plc-001, name: PLC, device_type: tcp, tenant: acme, poll_interval_ms: 1000,
+      connection: { host: 10.0.0.1, port: 502, unit_id: 1 }, registers: [] }
+  - { id: sensor-001, name: Sensor, device_type: tcp, tenant: globex, poll_interval_ms: 1000,
+      connection: { host: 10.0.0.2, port: 502, unit_id: 1 }, registers: [] }
+"#,
+    )
+    .unwrap();
+    state.controls = DeviceControls::new(&config.devices);
+    let mut auth = enabled_auth_with_keys(vec!["admin-key"]);
+    auth.tenant_keys
+        .insert("acme".to_string(), vec!["acme-key".to_string()]);
+    let app = create_router(state, auth);
+
+    let (status, json) = get_json_with_key(app.clone(), "/api/devices", Some("acme-key")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["count"], 1);
+    assert_eq!(json["devices"][0]["id"], "plc-001");
+
+    let (status, _) =
+        get_json_with_key(app.clone(), "/api/devices/sensor-001", Some("acme-key")).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, json) = get_json_with_key(
+        app.clone(),
+        "/api/devices/plc-001/registers/temperature",
+        Some("acme-key"),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["value"], 25.0);
+
+    let (status, json) = get_json_with_key(app, "/api/devices", Some("admin-key")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["count"], 2);
+}
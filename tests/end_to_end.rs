@@ -0,0 +1,65 @@
+//! End-to-end test of a bridge between the simulated device and broker
+//! from `rustbridge::testing`
+
+use rustbridge::testing::{ModbusServer, MqttBroker};
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+#[tokio::test]
+async fn test_bridge_publishes_device_registers() {
+    let plc = ModbusServer::start().await.unwrap();
+    plc.device().set_holding(0, &[215]);
+    plc.device().set_coils(0, &[true]);
+    let broker = MqttBroker::start().await.unwrap();
+
+    let mut config = rustbridge::config::load_config_from_str(
+        r#"
+server:
+  host: "127.0.0.1"
+  port: 0
+  metrics_enabled: false
+devices:
+  - id: plc-001
+    name: PLC
+    device_type: tcp
+    poll_interval_ms: 100
+    connection: { host: 127.0.0.1, port: 502, unit_id: 1 }
+    registers:
+      - { name: temperature, address: 0, register_type: holding, data_type: u16, scale: 0.1 }
+      - { name: pump, address: 0, register_type: coil, data_type: bool }
+"#,
+    )
+    .unwrap();
+    config.devices[0].connection = plc.connection();
+    config.mqtt = Some(broker.config());
+
+    let handle = rustbridge::Bridge::new(config)
+        .await
+        .unwrap()
+        .start()
+        .await
+        .unwrap();
+
+    let temperature = broker
+        .wait_for("rustbridge/plc-001/temperature", TIMEOUT)
+        .await
+        .expect("temperature published");
+    assert_eq!(temperature.json().unwrap()["value"], 21.5);
+    let pump = broker
+        .wait_for("rustbridge/plc-001/pump", TIMEOUT)
+        .await
+        .expect("pump published");
+    assert_eq!(pump.json().unwrap()["value"], true);
+
+    // A new reading reaches the broker on a later poll
+    plc.device().set_holding(0, &[230]);
+    broker
+        .wait_until("rustbridge/plc-001/temperature", TIMEOUT, |m| {
+            m.json().is_some_and(|json| json["value"] == 23.0)
+        })
+        .await
+        .expect("updated temperature published");
+
+    handle.shutdown().await.unwrap();
+}